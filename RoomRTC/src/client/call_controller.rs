@@ -0,0 +1,118 @@
+//! Una llamada sin pantallas: arma el `P2PClient` con la `PeerConfig`, hace
+//! o atiende la llamada por la señalización y la corta. Es lo mismo que
+//! hacen `JoinMeetScreen` y `WaitingCall` a través de `WebRTCHandler`, sin
+//! egui de por medio; la usa el modo headless. El aviso de corte al peer
+//! (`send_hangup`) es el mismo para las dos.
+
+use crate::client::capabilities::PeerCapabilities;
+use crate::client::message_inbox::{MessageInbox, SharedInbox};
+use crate::client::p2p_client::P2PClient;
+use crate::client::webrtc_service::{CallSignaling, PeerConfig, WebRTCHandler};
+use room_rtc::log_warn;
+use room_rtc::rtc::rtc_peer_connection::{PeerConnectionError, PeerConnectionRole};
+use room_rtc::worker_thread::media_metrics::CallMetricsSnapshot;
+
+pub struct CallController {
+    role: PeerConnectionRole,
+    peer_config: PeerConfig,
+    client: Option<P2PClient>,
+    received_msgs: SharedInbox,
+    // Con quién es la llamada en curso
+    peer: Option<String>,
+}
+
+impl WebRTCHandler for CallController {
+    fn client(&mut self) -> &mut Option<P2PClient> {
+        &mut self.client
+    }
+
+    fn role(&self) -> PeerConnectionRole {
+        self.role
+    }
+
+    fn received_msgs(&self) -> &SharedInbox {
+        &self.received_msgs
+    }
+
+    fn peer_config(&self) -> PeerConfig {
+        self.peer_config.clone()
+    }
+}
+
+impl CallController {
+    pub fn new(role: PeerConnectionRole, peer_config: PeerConfig) -> Self {
+        Self {
+            role,
+            peer_config,
+            client: None,
+            received_msgs: MessageInbox::shared(),
+            peer: None,
+        }
+    }
+
+    pub fn peer(&self) -> Option<&str> {
+        self.peer.as_deref()
+    }
+
+    pub fn p2p(&mut self) -> Option<&mut P2PClient> {
+        self.client.as_mut()
+    }
+
+    /// Manda la oferta a `to`; la llamada sigue con `accept_answer` cuando
+    /// llega CALL_ACCEPTED.
+    pub fn place_call(
+        &mut self,
+        signaling: &dyn CallSignaling,
+        to: &str,
+    ) -> Result<(), PeerConnectionError> {
+        self.send_offer(signaling, to)?;
+        self.peer = Some(to.to_string());
+        Ok(())
+    }
+
+    /// Atiende la oferta de `from` y arranca a conectar.
+    pub fn answer(
+        &mut self,
+        signaling: &dyn CallSignaling,
+        from: &str,
+        offer: &str,
+        caps: &PeerCapabilities,
+    ) -> Result<(), PeerConnectionError> {
+        self.answer_offer(signaling, from, offer, caps)?;
+        self.peer = Some(from.to_string());
+        Ok(())
+    }
+
+    /// Corta la llamada en curso: RTCP BYE al peer, frena la media y avisa
+    /// al servidor. Devuelve las métricas del final y queda lista para la
+    /// próxima llamada.
+    pub fn hang_up(&mut self, signaling: &dyn CallSignaling) -> Option<CallMetricsSnapshot> {
+        let mut client = self.client.take()?;
+        let metrics = client.metrics_snapshot();
+        Self::send_hangup(&client);
+        client.stop_media();
+        if let Some(peer) = self.peer.take()
+            && let Err(err) = signaling.end_call(&peer)
+        {
+            log_warn!("No se pudo avisar el fin de la llamada a {}: {}", peer, err);
+        }
+        self.received_msgs = MessageInbox::shared();
+        metrics
+    }
+
+    /// Le avisa al peer por la media que se corta: RTCP BYE y, si no sale,
+    /// CALL_END por el canal de datos. La pantalla de video corta con esto
+    /// mismo.
+    pub fn send_hangup(client: &P2PClient) {
+        // Sin conexión no hay a dónde mandar el BYE
+        if !client.has_connection() {
+            return;
+        }
+        if let Err(err) = client.send_rtcp_bye() {
+            log_warn!("Error enviando RTCP BYE: {:?}", err);
+            if let Err(msg_err) = client.send_msg("CALL_END") {
+                log_warn!("Error enviando fin de llamada: {:?}", msg_err);
+            }
+        }
+    }
+}
@@ -0,0 +1,235 @@
+//! Capacidades que cada cliente anuncia en CALL_OFFER / CALL_ANSWER (campo
+//! `caps`), para que dos versiones distintas de RoomRTC usen solo lo que
+//! entienden las dos. Van como tokens separados por coma
+//! (`proto=2,opus,h264,fec,dtx,files2`); los que no se conocen se ignoran,
+//! así una versión vieja no se rompe con lo que agregue una nueva.
+
+use std::fmt;
+
+use room_rtc::worker_thread::worker_audio::AudioParams;
+
+/// Versión del protocolo entre clientes. La 1 es la de los clientes que
+/// todavía no mandaban `caps`.
+pub const PROTOCOL_VERSION: u32 = 2;
+const LEGACY_VERSION: u32 = 1;
+/// Codecs que este cliente sabe mandar y recibir.
+const CODECS: [&str; 2] = ["opus", "h264"];
+
+const VERSION_PREFIX: &str = "proto=";
+const FEC: &str = "fec";
+const DTX: &str = "dtx";
+const FILE_TRANSFER_V2: &str = "files2";
+const CHAT: &str = "chat";
+const CALL_WAITING: &str = "waiting";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PeerCapabilities {
+    pub version: u32,
+    pub codecs: Vec<String>,
+    /// Recupera frames perdidos con el FEC de Opus.
+    pub fec: bool,
+    /// Rellena con ruido de confort las pausas de DTX en vez de quedarse
+    /// esperando audio.
+    pub dtx: bool,
+    /// Archivos por streams de datos propios, terminados con un reset del
+    /// stream.
+    pub file_transfer_v2: bool,
+    pub chat: bool,
+    /// Una llamada entrante durante otra se muestra sin cortar la actual.
+    /// La mira el servidor en los `caps` propios de cada cliente para no
+    /// hacer sonar en plena llamada a uno que no lo entiende.
+    pub call_waiting: bool,
+}
+
+impl PeerCapabilities {
+    /// Lo que soporta este cliente.
+    pub fn local() -> Self {
+        Self {
+            version: PROTOCOL_VERSION,
+            codecs: CODECS.iter().map(|codec| codec.to_string()).collect(),
+            fec: true,
+            dtx: true,
+            file_transfer_v2: true,
+            chat: false,
+            call_waiting: true,
+        }
+    }
+
+    /// Lo que se supone de un peer que no mandó `caps`: solo audio y video.
+    pub fn legacy() -> Self {
+        Self {
+            version: LEGACY_VERSION,
+            codecs: CODECS.iter().map(|codec| codec.to_string()).collect(),
+            fec: false,
+            dtx: false,
+            file_transfer_v2: false,
+            chat: false,
+            call_waiting: false,
+        }
+    }
+
+    /// Lee el campo `caps` de un mensaje; sin él, el peer es anterior a la
+    /// negociación.
+    pub fn parse(field: Option<&str>) -> Self {
+        let Some(field) = field else {
+            return Self::legacy();
+        };
+        let mut caps = Self {
+            version: LEGACY_VERSION,
+            codecs: Vec::new(),
+            fec: false,
+            dtx: false,
+            file_transfer_v2: false,
+            chat: false,
+            call_waiting: false,
+        };
+        for token in field.split(',').map(str::trim) {
+            match token {
+                FEC => caps.fec = true,
+                DTX => caps.dtx = true,
+                FILE_TRANSFER_V2 => caps.file_transfer_v2 = true,
+                CHAT => caps.chat = true,
+                CALL_WAITING => caps.call_waiting = true,
+                _ => {
+                    if let Some(version) = token.strip_prefix(VERSION_PREFIX) {
+                        caps.version = version.parse().unwrap_or(LEGACY_VERSION);
+                    } else if CODECS.contains(&token) && !caps.codecs.iter().any(|c| c == token) {
+                        caps.codecs.push(token.to_string());
+                    }
+                }
+            }
+        }
+        caps
+    }
+
+    /// Valor del campo `caps`.
+    pub fn to_field(&self) -> String {
+        let mut tokens = vec![format!("{}{}", VERSION_PREFIX, self.version)];
+        tokens.extend(self.codecs.iter().cloned());
+        for (enabled, token) in self.features() {
+            if enabled {
+                tokens.push(token.to_string());
+            }
+        }
+        tokens.join(",")
+    }
+
+    /// Lo que soportan los dos lados.
+    pub fn intersect(&self, other: &PeerCapabilities) -> Self {
+        Self {
+            version: self.version.min(other.version),
+            codecs: self
+                .codecs
+                .iter()
+                .filter(|codec| other.codecs.contains(codec))
+                .cloned()
+                .collect(),
+            fec: self.fec && other.fec,
+            dtx: self.dtx && other.dtx,
+            file_transfer_v2: self.file_transfer_v2 && other.file_transfer_v2,
+            chat: self.chat && other.chat,
+            call_waiting: self.call_waiting && other.call_waiting,
+        }
+    }
+
+    /// Los parámetros de audio pedidos, sin lo que el peer no entiende: sin
+    /// FEC no tiene sentido gastar bitrate en él, y sin ruido de confort las
+    /// pausas de DTX le sonarían a cortes.
+    pub fn audio_params(&self, wanted: AudioParams) -> AudioParams {
+        AudioParams {
+            fec: wanted.fec && self.fec,
+            dtx: wanted.dtx && self.dtx,
+            opus_dtx: wanted.opus_dtx && self.dtx,
+            ..wanted
+        }
+    }
+
+    fn features(&self) -> [(bool, &'static str); 5] {
+        [
+            (self.fec, FEC),
+            (self.dtx, DTX),
+            (self.file_transfer_v2, FILE_TRANSFER_V2),
+            (self.chat, CHAT),
+            (self.call_waiting, CALL_WAITING),
+        ]
+    }
+}
+
+/// Para el overlay: "v2 · opus, h264 · fec, dtx, files2".
+impl fmt::Display for PeerCapabilities {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let features: Vec<&str> = self
+            .features()
+            .into_iter()
+            .filter(|(enabled, _)| *enabled)
+            .map(|(_, token)| token)
+            .collect();
+        write!(f, "v{} · {}", self.version, self.codecs.join(", "))?;
+        if features.is_empty() {
+            write!(f, " · basic")
+        } else {
+            write!(f, " · {}", features.join(", "))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_field_roundtrip_ignoring_unknown_tokens() {
+        let local = PeerCapabilities::local();
+        assert_eq!(local.to_field(), "proto=2,opus,h264,fec,dtx,files2,waiting");
+        assert_eq!(PeerCapabilities::parse(Some(&local.to_field())), local);
+
+        // Una versión más nueva con cosas que todavía no existen
+        let newer = PeerCapabilities::parse(Some("proto=7,opus,av1,h264,fec,svc,chat"));
+        assert_eq!(newer.version, 7);
+        assert_eq!(newer.codecs, vec!["opus", "h264"]);
+        assert!(newer.fec && newer.chat);
+        assert!(!newer.dtx && !newer.file_transfer_v2);
+    }
+
+    #[test]
+    fn test_peer_without_caps_gets_the_basics() {
+        let negotiated = PeerCapabilities::local().intersect(&PeerCapabilities::parse(None));
+        assert_eq!(negotiated, PeerCapabilities::legacy());
+        assert_eq!(negotiated.to_string(), "v1 · opus, h264 · basic");
+
+        let wanted = AudioParams {
+            fec: true,
+            dtx: true,
+            opus_dtx: true,
+            expected_loss_pct: 10,
+            ..AudioParams::default()
+        };
+        let audio = negotiated.audio_params(wanted.clone());
+        assert!(!audio.fec && !audio.dtx && !audio.opus_dtx);
+        assert_eq!(audio.expected_loss_pct, 10);
+    }
+
+    #[test]
+    fn test_asymmetric_sets_keep_what_both_support() {
+        // El peer tiene FEC pero no ruido de confort ni archivos v2
+        let remote = PeerCapabilities::parse(Some("proto=2,opus,fec"));
+        let negotiated = PeerCapabilities::local().intersect(&remote);
+        assert_eq!(negotiated.codecs, vec!["opus"]);
+        assert!(negotiated.fec);
+        assert!(!negotiated.dtx && !negotiated.file_transfer_v2);
+        assert!(!negotiated.call_waiting);
+        assert_eq!(negotiated.to_string(), "v2 · opus · fec");
+
+        let audio = negotiated.audio_params(AudioParams {
+            fec: true,
+            opus_dtx: true,
+            ..AudioParams::default()
+        });
+        assert!(audio.fec);
+        assert!(!audio.opus_dtx);
+
+        // Lo que no se pidió no se activa aunque los dos lo soporten
+        let audio = PeerCapabilities::local().audio_params(AudioParams::default());
+        assert!(!audio.fec && !audio.dtx);
+    }
+}
@@ -0,0 +1,277 @@
+//! Prueba de cámara, micrófono, parlantes y conectividad antes de una
+//! llamada. Usa las mismas fuentes que una llamada real, pero sin peer ni
+//! sockets de media.
+
+use std::net::UdpSocket;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+use std::time::Duration;
+
+use opencv::prelude::Mat;
+use room_rtc::audio::audio_capture::AudioCapture;
+use room_rtc::audio::audio_playback::TestTone;
+use room_rtc::camera::fake_camera::FakeCamera;
+use room_rtc::stun::{NatReport, StunClient};
+use room_rtc::worker_thread::media_channel::{MediaReceiver, media_channel};
+use room_rtc::worker_thread::worker_media::{VideoParams, WorkerMedia};
+
+use crate::logger::Logger;
+
+/// Servidores extra para ver si el NAT mapea igual hacia destinos distintos.
+const SECONDARY_STUN_SERVERS: [&str; 2] = ["stun1.l.google.com:19302", "stun2.l.google.com:19302"];
+const STUN_TIMEOUT: Duration = Duration::from_secs(3);
+/// Nivel por debajo del cual el medidor queda vacío.
+const LEVEL_FLOOR_DB: f32 = -60.0;
+/// Cuánto conserva el medidor del nivel anterior en cada lectura, para que
+/// no parpadee entre buffers.
+const LEVEL_DECAY: f32 = 0.85;
+
+/// Vista previa de la cámara, micrófono, tono de prueba y chequeo STUN
+/// corriendo mientras la pantalla de prueba está abierta. Al soltarlo se
+/// liberan todos los dispositivos.
+pub struct DeviceTest {
+    // La cámara se abre en otro hilo para no trabar la UI
+    rx_camera: Option<Receiver<Result<WorkerMedia, String>>>,
+    camera: Option<WorkerMedia>,
+    camera_error: Option<String>,
+    // Se guarda para que el stream de cpal siga vivo
+    _capture: Option<AudioCapture>,
+    rx_pcm: Option<MediaReceiver<Vec<i16>>>,
+    mic_error: Option<String>,
+    level: f32,
+    tone: Option<TestTone>,
+    speaker_error: Option<String>,
+    rx_stun: Option<Receiver<Result<NatReport, String>>>,
+    stun_result: Option<Result<NatReport, String>>,
+}
+
+impl DeviceTest {
+    pub fn start(
+        camera_index: i32,
+        video: VideoParams,
+        fake_camera: bool,
+        input_device: Option<&str>,
+    ) -> Self {
+        let mut test = Self {
+            rx_camera: None,
+            camera: None,
+            camera_error: None,
+            _capture: None,
+            rx_pcm: None,
+            mic_error: None,
+            level: 0.0,
+            tone: None,
+            speaker_error: None,
+            rx_stun: None,
+            stun_result: None,
+        };
+        test.restart_camera(camera_index, video, fake_camera);
+        test.restart_microphone(input_device);
+        test
+    }
+
+    /// Suelta la cámara actual y abre otra, solo con la vista previa: nada
+    /// se codifica ni se envía.
+    pub fn restart_camera(&mut self, camera_index: i32, video: VideoParams, fake_camera: bool) {
+        self.camera = None;
+        self.camera_error = None;
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let worker = if fake_camera {
+                let source = FakeCamera::new(video.width, video.height, video.fps);
+                Ok(WorkerMedia::preview_with_source(Box::new(source), video))
+            } else {
+                WorkerMedia::preview(camera_index, video).map_err(|err| err.to_string())
+            };
+            // Si la prueba ya se cerró, el worker se suelta aquí con la cámara
+            let _ = tx.send(worker);
+        });
+        self.rx_camera = Some(rx);
+    }
+
+    /// Cambia el micrófono del medidor; solo se captura, sin codificar.
+    pub fn restart_microphone(&mut self, input_device: Option<&str>) {
+        // El stream anterior se cierra antes de abrir el nuevo
+        self._capture = None;
+        self.rx_pcm = None;
+        self.level = 0.0;
+        let (tx_pcm, rx_pcm) = media_channel(16);
+        match AudioCapture::with_device(tx_pcm, input_device) {
+            Ok(capture) => {
+                self._capture = Some(capture);
+                self.rx_pcm = Some(rx_pcm);
+                self.mic_error = None;
+            }
+            Err(err) => self.mic_error = Some(err.to_string()),
+        }
+    }
+
+    /// Hace sonar un tono corto en los parlantes `output_device`.
+    pub fn play_test_tone(&mut self, output_device: Option<&str>) {
+        self.tone = None;
+        match TestTone::play(output_device) {
+            Ok(tone) => {
+                self.tone = Some(tone);
+                self.speaker_error = None;
+            }
+            Err(err) => self.speaker_error = Some(err.to_string()),
+        }
+    }
+
+    /// Si el tono sigue sonando; al terminar se suelta el dispositivo.
+    pub fn tone_playing(&mut self) -> bool {
+        if self.tone.as_ref().is_some_and(TestTone::finished) {
+            self.tone = None;
+        }
+        self.tone.is_some()
+    }
+
+    pub fn speaker_error(&self) -> Option<&str> {
+        self.speaker_error.as_deref()
+    }
+
+    /// Último frame BGR de la cámara, si llegó alguno desde la lectura anterior.
+    pub fn latest_frame(&mut self) -> Option<Mat> {
+        if let Some(rx) = &self.rx_camera {
+            match rx.try_recv() {
+                Ok(Ok(worker)) => self.camera = Some(worker),
+                Ok(Err(err)) => self.camera_error = Some(err.trim().to_string()),
+                Err(TryRecvError::Empty) => return None,
+                Err(TryRecvError::Disconnected) => {}
+            }
+            self.rx_camera = None;
+        }
+        let camera = self.camera.as_ref()?;
+        let mut latest = None;
+        loop {
+            match camera.get_preview_receiver().try_recv() {
+                Ok(frame) => latest = Some(frame),
+                Err(TryRecvError::Empty) => break,
+                // El hilo de captura terminó: la cámara dejó de responder
+                Err(TryRecvError::Disconnected) => {
+                    self.camera = None;
+                    self.camera_error = Some("the camera stopped sending frames".to_string());
+                    break;
+                }
+            }
+        }
+        latest
+    }
+
+    pub fn camera_error(&self) -> Option<&str> {
+        self.camera_error.as_deref()
+    }
+
+    pub fn mic_error(&self) -> Option<&str> {
+        self.mic_error.as_deref()
+    }
+
+    /// Nivel del micrófono entre 0 y 1, con lo capturado desde la última
+    /// lectura.
+    pub fn mic_level(&mut self) -> f32 {
+        let Some(rx_pcm) = &self.rx_pcm else {
+            return 0.0;
+        };
+        let mut level = self.level * LEVEL_DECAY;
+        for samples in rx_pcm.try_iter() {
+            level = level.max(level_from_samples(&samples));
+        }
+        self.level = level;
+        level
+    }
+
+    /// Arranca el chequeo STUN en otro hilo; el resultado se lee con
+    /// [`DeviceTest::stun_result`] y queda en el log.
+    pub fn start_stun_check(&mut self, server: String, logger: Logger) {
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let result = run_stun_check(&server);
+            match &result {
+                Ok(report) => logger.info(&format!(
+                    "Prueba de red: dirección pública {:?}, NAT {:?}",
+                    report.public_addr, report.nat_type
+                )),
+                Err(err) => logger.warn(&format!("Prueba de red falló: {}", err)),
+            }
+            let _ = tx.send(result);
+        });
+        self.rx_stun = Some(rx);
+        self.stun_result = None;
+    }
+
+    pub fn stun_running(&self) -> bool {
+        self.rx_stun.is_some()
+    }
+
+    pub fn stun_result(&mut self) -> Option<&Result<NatReport, String>> {
+        if let Some(rx) = &self.rx_stun {
+            match rx.try_recv() {
+                Ok(result) => {
+                    self.stun_result = Some(result);
+                    self.rx_stun = None;
+                }
+                Err(TryRecvError::Disconnected) => self.rx_stun = None,
+                Err(TryRecvError::Empty) => {}
+            }
+        }
+        self.stun_result.as_ref()
+    }
+}
+
+/// Nivel RMS de un buffer, de `LEVEL_FLOOR_DB` dBFS (0) a 0 dBFS (1).
+pub fn level_from_samples(samples: &[i16]) -> f32 {
+    if samples.is_empty() {
+        return 0.0;
+    }
+    let sum: f64 = samples.iter().map(|s| (*s as f64).powi(2)).sum();
+    let rms = (sum / samples.len() as f64).sqrt() / i16::MAX as f64;
+    meter_fraction(rms as f32)
+}
+
+/// Lleva un nivel lineal (1.0 = escala completa) a la escala en dB de los
+/// medidores: `LEVEL_FLOOR_DB` dBFS queda vacío y 0 dBFS lleno.
+pub fn meter_fraction(linear: f32) -> f32 {
+    if linear <= 0.0 {
+        return 0.0;
+    }
+    let db = 20.0 * linear.log10();
+    ((db - LEVEL_FLOOR_DB) / -LEVEL_FLOOR_DB).clamp(0.0, 1.0)
+}
+
+fn run_stun_check(server: &str) -> Result<NatReport, String> {
+    let socket = UdpSocket::bind("0.0.0.0:0").map_err(|e| e.to_string())?;
+    let mut client = StunClient::with_server(server.to_string());
+    client.timeout = STUN_TIMEOUT;
+    let others: Vec<String> = SECONDARY_STUN_SERVERS
+        .iter()
+        .map(|server| server.to_string())
+        .collect();
+    let report = client.detect_nat(&socket, &others);
+    if report.public_addr.is_none() {
+        return Err(report.nat_type.label().to_string());
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_level_from_samples() {
+        assert_eq!(level_from_samples(&[]), 0.0);
+        assert_eq!(level_from_samples(&[0; 480]), 0.0);
+        assert_eq!(level_from_samples(&[i16::MAX; 480]), 1.0);
+        // -20 dBFS queda a dos tercios del medidor
+        let quiet = vec![i16::MAX / 10; 480];
+        assert!((level_from_samples(&quiet) - 2.0 / 3.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_meter_fraction_uses_the_db_scale() {
+        assert_eq!(meter_fraction(0.0), 0.0);
+        assert_eq!(meter_fraction(1.0), 1.0);
+        assert!((meter_fraction(0.1) - 2.0 / 3.0).abs() < 0.01);
+        assert_eq!(meter_fraction(0.0001), 0.0);
+    }
+}
@@ -0,0 +1,98 @@
+//! Reporte de diagnóstico de la conexión, para adjuntar en los bug reports.
+
+use room_rtc::ice::IceCandidate;
+use room_rtc::rtc::rtc_peer_connection::RtcPeerConnection;
+
+/// Junta en un solo texto los SDP, los candidatos, el par seleccionado,
+/// el estado DTLS, el último error de conexión y el último estado de la UI.
+pub fn connection_report(
+    pc: &RtcPeerConnection,
+    last_error: Option<&str>,
+    ui_status: Option<&str>,
+) -> String {
+    let mut report = String::from("=== RoomRTC connection diagnostics ===\n");
+
+    report.push_str(&format!("Role: {:?}\n", pc.role()));
+    match pc.local_addr() {
+        Ok(addr) => report.push_str(&format!("Local socket: {}\n", addr)),
+        Err(err) => report.push_str(&format!("Local socket: error ({})\n", err)),
+    }
+    match pc.remote_addr() {
+        Ok(Some(addr)) => report.push_str(&format!("Remote address: {}\n", addr)),
+        Ok(None) => report.push_str("Remote address: (none)\n"),
+        Err(err) => report.push_str(&format!("Remote address: error ({})\n", err)),
+    }
+    report.push_str(&format!("ICE connected: {}\n", pc.is_connected()));
+    report.push_str(&format!("DTLS state: {:?}\n", pc.dtls_state()));
+    if let Some((version, cipher)) = pc.dtls_negotiated() {
+        report.push_str(&format!("DTLS cipher: {} {}\n", version, cipher));
+    }
+    report.push_str(&format!("rtcp-mux: {}\n", pc.rtcp_mux()));
+    report.push_str(&format!("Last error: {}\n", last_error.unwrap_or("(none)")));
+    report.push_str(&format!("UI status: {}\n", ui_status.unwrap_or("(none)")));
+
+    report.push_str("\n--- Local candidates ---\n");
+    push_candidates(&mut report, pc.local_candidates());
+    report.push_str("\n--- Remote candidates ---\n");
+    push_candidates(&mut report, pc.remote_candidates());
+
+    report.push_str("\n--- Candidate pairs ---\n");
+    let pairs = pc.candidate_pairs();
+    if pairs.is_empty() {
+        report.push_str("(none)\n");
+    }
+    for pair in pairs {
+        report.push_str(&format!(
+            "{} {} -> {} {} [{:?}] priority={}\n",
+            pair.local_type,
+            pair.local_address,
+            pair.remote_type,
+            pair.remote_address,
+            pair.state,
+            pair.priority
+        ));
+    }
+
+    report.push_str("\n--- Selected pair ---\n");
+    match pc.selected_candidate_pair_info() {
+        Some(pair) => report.push_str(&format!(
+            "{} {} -> {} {} rtt={}\n",
+            pair.local_type,
+            pair.local_address,
+            pair.remote_type,
+            pair.remote_address,
+            pair.rtt
+                .map(|rtt| format!("{} ms", rtt.as_millis()))
+                .unwrap_or_else(|| "(unknown)".to_string())
+        )),
+        None => report.push_str("(none)\n"),
+    }
+    report.push_str(&format!("Pairs: {}\n", pc.ice_pair_stats()));
+
+    report.push_str("\n--- Local SDP ---\n");
+    report.push_str(pc.local_description().unwrap_or("(none)\n"));
+    report.push_str("\n--- Remote SDP ---\n");
+    report.push_str(pc.remote_description().unwrap_or("(none)\n"));
+
+    report
+}
+
+fn push_candidates(report: &mut String, candidates: &[IceCandidate]) {
+    if candidates.is_empty() {
+        report.push_str("(none)\n");
+    }
+    for candidate in candidates {
+        report.push_str(&format!(
+            "{} priority={}\n",
+            describe_candidate(candidate),
+            candidate.priority
+        ));
+    }
+}
+
+fn describe_candidate(candidate: &IceCandidate) -> String {
+    format!(
+        "{} {}:{}",
+        candidate.candidate_type, candidate.address, candidate.port
+    )
+}
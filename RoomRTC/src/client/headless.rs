@@ -0,0 +1,564 @@
+//! Cliente sin ventana, con dos usos:
+//!
+//! - Pruebas automáticas y de carga: se loguea, llama a un usuario (o atiende
+//!   la primera llamada), manda video de un archivo o del patrón de prueba
+//!   durante un tiempo fijo y corta. Sin audio: las máquinas de CI no suelen
+//!   tener micrófono ni parlantes.
+//! - Kiosco (`--kiosk` o `headless=true`): atiende solo las llamadas de
+//!   `headless_allow`, una tras otra, con cámara y micrófono, hasta un SIGINT.
+//!
+//! La llamada la lleva un `CallController`; de él sale también el aviso de
+//! corte que usa la pantalla de video.
+
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use room_rtc::camera::video_file::VideoFile;
+use room_rtc::protocols::sdp::media_type::MediaType;
+use room_rtc::rtc::rtc_peer_connection::PeerConnectionRole;
+use room_rtc::worker_thread::media_metrics::CallMetricsSnapshot;
+use room_rtc::worker_thread::worker_audio::AudioParams;
+use room_rtc::worker_thread::worker_media::VideoParams;
+
+use crate::client::call_controller::CallController;
+use crate::client::p2p_client::P2PClient;
+use crate::client::signaling_client::{SignalingClient, SignalingEvent};
+use crate::client::tls::TlsClientSettings;
+use crate::client::webrtc_service::{CallProgress, PeerConfig, WebRTCHandler, handle_ice_restart};
+use crate::config::AppConfig;
+use crate::logger::Logger;
+
+/// Duración de la llamada si no se pide otra.
+const DEFAULT_DURATION: Duration = Duration::from_secs(30);
+/// Cada cuánto se vacían los frames y se miran los eventos.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+const INTERRUPTED_MSG: &str = "Interrumpido";
+/// Sin servidor no entran más llamadas: el kiosco sale con este error.
+const DISCONNECTED_MSG: &str = "Se cerró la conexión con el servidor";
+
+/// Lo prende SIGINT: la llamada en curso se corta bien antes de salir.
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Qué hacer en modo headless, de la línea de comandos.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeadlessOptions {
+    pub username: String,
+    pub password: String,
+    /// Usuario a llamar; sin valor se atiende la primera llamada que llegue.
+    pub call: Option<String>,
+    /// Cuánto dura la llamada una vez conectada.
+    pub duration: Duration,
+    /// Video a mandar en vez del patrón de prueba (o de la cámara, en kiosco).
+    pub video_file: Option<String>,
+    /// Atender llamadas una tras otra hasta un SIGINT.
+    pub kiosk: bool,
+}
+
+impl HeadlessOptions {
+    /// Lee `--user`, `--password`, `--call`, `--duration` (segundos),
+    /// `--video-file` y `--kiosk`; el resto de los argumentos se ignora.
+    pub fn from_args(args: &[String]) -> Result<Self, String> {
+        let mut username = None;
+        let mut password = None;
+        let mut call = None;
+        let mut duration = DEFAULT_DURATION;
+        let mut video_file = None;
+        let mut kiosk = false;
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .cloned()
+                    .ok_or_else(|| format!("Falta el valor de {}", arg))
+            };
+            match arg.as_str() {
+                "--user" => username = Some(value()?),
+                "--password" => password = Some(value()?),
+                "--call" => call = Some(value()?),
+                "--duration" => {
+                    let secs = value()?;
+                    let secs = secs
+                        .parse::<u64>()
+                        .map_err(|_| format!("Duración inválida: {}", secs))?;
+                    duration = Duration::from_secs(secs);
+                }
+                "--video-file" => video_file = Some(value()?),
+                "--kiosk" => kiosk = true,
+                _ => {}
+            }
+        }
+        Ok(Self {
+            username: username.ok_or("Falta --user")?,
+            password: password.ok_or("Falta --password")?,
+            call,
+            duration,
+            video_file,
+            kiosk,
+        })
+    }
+}
+
+/// Lo que pasó en la llamada, para el log y el código de salida.
+#[derive(Debug, Clone)]
+pub struct HeadlessReport {
+    pub peer: String,
+    /// Desde que arranca ICE hasta que completa el handshake DTLS.
+    pub connect_time: Duration,
+    pub call_time: Duration,
+    pub remote_frames: u64,
+    pub metrics: Option<CallMetricsSnapshot>,
+}
+
+impl fmt::Display for HeadlessReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "peer={} connect_ms={} call_secs={} remote_frames={}",
+            self.peer,
+            self.connect_time.as_millis(),
+            self.call_time.as_secs(),
+            self.remote_frames
+        )?;
+        if let Some(metrics) = &self.metrics {
+            write!(
+                f,
+                " bitrate_kbps={:.0} packet_loss_pct={:.2} jitter_ms={:.1} dropped={}",
+                metrics.bitrate_kbps,
+                metrics.packet_loss_pct,
+                metrics.jitter_ms,
+                metrics.drops.total()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Corre una llamada completa sin ventana.
+pub fn run(
+    config: &AppConfig,
+    options: &HeadlessOptions,
+    logger: &Logger,
+) -> Result<HeadlessReport, String> {
+    watch_interrupt();
+    let timeout = Duration::from_secs(config.connect_timeout_secs);
+    let signaling = login(config, options, logger)?;
+
+    let video = config.video_params();
+    let role = if options.call.is_some() {
+        PeerConnectionRole::Controlling
+    } else {
+        PeerConnectionRole::Controlled
+    };
+    let mut call = CallController::new(role, PeerConfig::from_config(config, &video));
+
+    let peer = match &options.call {
+        Some(peer) => {
+            call.place_call(&signaling, peer)
+                .map_err(|e| e.to_string())?;
+            logger.info(&format!("Headless: llamando a {}", peer));
+            let (answer, caps) = wait_for_event(&signaling, timeout, |event| match event {
+                SignalingEvent::CallAccepted { from, sdp, caps } if from == *peer => {
+                    Some(Ok((sdp, caps)))
+                }
+                SignalingEvent::CallRejected { from } if from == *peer => {
+                    Some(Err(format!("{} rechazó la llamada", from)))
+                }
+                SignalingEvent::CalleeNotFound { to } => Some(Err(format!("{} no existe", to))),
+                SignalingEvent::CalleeOffline { to } => {
+                    Some(Err(format!("{} no está conectado", to)))
+                }
+                SignalingEvent::CalleeBusy { to } => {
+                    Some(Err(format!("{} está en otra llamada", to)))
+                }
+                _ => None,
+            })?;
+            call.accept_answer(&answer, &caps)
+                .map_err(|e| e.to_string())?;
+            peer.clone()
+        }
+        None => {
+            logger.info("Headless: esperando una llamada");
+            // Sin límite: quien llama puede tardar en arrancar
+            let (from, offer, caps) =
+                wait_for_event(&signaling, Duration::MAX, |event| match event {
+                    SignalingEvent::IncomingCall { from, sdp, caps } => Some(Ok((from, sdp, caps))),
+                    _ => None,
+                })?;
+            call.answer(&signaling, &from, &offer, &caps)
+                .map_err(|e| e.to_string())?;
+            logger.info(&format!("Headless: atendida la llamada de {}", from));
+            from
+        }
+    };
+
+    let plan = MediaPlan {
+        video,
+        video_file: options.video_file.as_deref(),
+        camera: false,
+        audio: None,
+        duration: Some(options.duration),
+        bandwidth_cap_kbps: config.max_bitrate_kbps,
+    };
+    let result = connect_and_stream(&mut call, &signaling, &plan, timeout, logger);
+    // Se corta igual si algo falló a mitad de la llamada
+    let metrics = call.hang_up(&signaling);
+    let _ = signaling.logout();
+
+    let (connect_time, call_time, remote_frames) = result?;
+    let report = HeadlessReport {
+        peer,
+        connect_time,
+        call_time,
+        remote_frames,
+        metrics,
+    };
+    logger.info(&format!("Headless: llamada terminada, {}", report));
+    Ok(report)
+}
+
+/// Modo kiosco: atiende las llamadas de `headless_allow` (de cualquiera si
+/// está vacío) con cámara y micrófono; cada una dura hasta que el otro
+/// corta. Sale con un SIGINT, cortando antes la llamada en curso, y
+/// devuelve cuántas llamadas atendió. Si se cae la conexión con el
+/// servidor, aun en medio de una llamada, sale con error.
+pub fn run_kiosk(
+    config: &AppConfig,
+    options: &HeadlessOptions,
+    logger: &Logger,
+) -> Result<u32, String> {
+    watch_interrupt();
+    let timeout = Duration::from_secs(config.connect_timeout_secs);
+    let signaling = login(config, options, logger)?;
+    let video = config.video_params();
+    let plan = MediaPlan {
+        video,
+        video_file: options.video_file.as_deref(),
+        camera: !config.fake_camera,
+        audio: Some(config.audio_params()),
+        duration: None,
+        bandwidth_cap_kbps: config.max_bitrate_kbps,
+    };
+    let mut answered = 0;
+    logger.info("Headless: kiosco esperando llamadas");
+    while !interrupted() {
+        let Some(event) = signaling.try_next_event() else {
+            thread::sleep(POLL_INTERVAL);
+            continue;
+        };
+        let (from, offer, caps) = match event {
+            SignalingEvent::IncomingCall { from, sdp, caps } => (from, sdp, caps),
+            SignalingEvent::Disconnected => {
+                return Err(DISCONNECTED_MSG.to_string());
+            }
+            _ => continue,
+        };
+        if !caller_allowed(&config.headless_allow, &from) {
+            logger.info(&format!("Headless: rechazada la llamada de {}", from));
+            let _ = signaling.reject_call(&from);
+            continue;
+        }
+
+        let mut call = CallController::new(
+            PeerConnectionRole::Controlled,
+            PeerConfig::from_config(config, &video),
+        );
+        let result = call
+            .answer(&signaling, &from, &offer, &caps)
+            .map_err(|e| e.to_string())
+            .and_then(|()| connect_and_stream(&mut call, &signaling, &plan, timeout, logger));
+        call.hang_up(&signaling);
+        answered += 1;
+        match result {
+            Ok((_, call_time, _)) => logger.info(&format!(
+                "Headless: terminó la llamada con {} ({} s)",
+                from,
+                call_time.as_secs()
+            )),
+            // La conexión no vuelve sola: seguir leyéndola no sirve
+            Err(err) if err == DISCONNECTED_MSG => return Err(err),
+            // Una llamada fallida no frena al kiosco
+            Err(err) => logger.warn(&format!("Headless: llamada con {}: {}", from, err)),
+        }
+    }
+    logger.info("Headless: kiosco detenido");
+    let _ = signaling.logout();
+    Ok(answered)
+}
+
+/// Sin lista, atiende a cualquiera.
+fn caller_allowed(allow: &[String], caller: &str) -> bool {
+    allow.is_empty() || allow.iter().any(|user| user == caller)
+}
+
+fn login(
+    config: &AppConfig,
+    options: &HeadlessOptions,
+    logger: &Logger,
+) -> Result<SignalingClient, String> {
+    let timeout = Duration::from_secs(config.connect_timeout_secs);
+    let signaling =
+        SignalingClient::connect(&config.server_addr, &TlsClientSettings::from_config(config))
+            .map_err(|e| format!("No se pudo conectar a {}: {}", config.server_addr, e))?;
+    signaling
+        .login(&options.username, &options.password)
+        .map_err(|e| e.to_string())?;
+    wait_for_event(&signaling, timeout, |event| match event {
+        SignalingEvent::LoginSuccess(_) => Some(Ok(())),
+        SignalingEvent::LoginError(err) => Some(Err(format!("Login rechazado: {}", err))),
+        _ => None,
+    })?;
+    logger.info(&format!("Headless: logueado como {}", options.username));
+    Ok(signaling)
+}
+
+/// De dónde sale la media de una llamada y cuánto dura.
+struct MediaPlan<'a> {
+    video: VideoParams,
+    video_file: Option<&'a str>,
+    /// La cámara 0; si no, el patrón de prueba.
+    camera: bool,
+    /// Sin valor, la llamada va sin audio.
+    audio: Option<AudioParams>,
+    /// Sin valor, hasta que el peer corta.
+    duration: Option<Duration>,
+    /// Tope total de envío (`max_bitrate_kbps`).
+    bandwidth_cap_kbps: Option<u64>,
+}
+
+/// Espera ICE + DTLS, arranca la media y la deja correr lo que diga `plan`.
+/// Devuelve lo que tardó en conectar, cuánto duró la llamada y los frames
+/// remotos decodificados.
+fn connect_and_stream(
+    call: &mut CallController,
+    signaling: &SignalingClient,
+    plan: &MediaPlan,
+    timeout: Duration,
+    logger: &Logger,
+) -> Result<(Duration, Duration, u64), String> {
+    let start = Instant::now();
+    while call.connection_progress() != Some(CallProgress::Connected) {
+        if let Some(reason) = call.connection_failure() {
+            return Err(reason);
+        }
+        if interrupted() {
+            return Err(INTERRUPTED_MSG.to_string());
+        }
+        if start.elapsed() > timeout {
+            return Err(format!("Sin conexión después de {:?}", timeout));
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+    let connect_time = start.elapsed();
+    let peer = call.peer().unwrap_or_default().to_string();
+    let client = call.p2p().ok_or("La llamada ya no está")?;
+    logger.info(&format!(
+        "Headless: capacidades negociadas con {}: {}",
+        peer,
+        client.capabilities()
+    ));
+    client.set_bandwidth_cap(plan.bandwidth_cap_kbps);
+    start_video(client, plan)?;
+    // Se suelta al volver, antes de que se corte la llamada
+    let _audio = match &plan.audio {
+        Some(audio) if client.is_media_accepted(MediaType::Audio) => {
+            match client.start_audio(audio.clone()) {
+                Ok(worker) => Some(worker),
+                Err(err) => {
+                    logger.warn(&format!("Headless: sin audio: {}", err));
+                    None
+                }
+            }
+        }
+        _ => None,
+    };
+
+    let call_start = Instant::now();
+    let mut remote_frames = 0;
+    while !interrupted() && plan.duration.is_none_or(|d| call_start.elapsed() < d) {
+        // Los canales de frames son cortos: si nadie los vacía el pipeline
+        // se frena
+        while client.try_recv_local_frame().is_some() {}
+        while client.try_recv_remote_frame().is_some() {
+            remote_frames += 1;
+        }
+        client.resolve_ssrc_collisions();
+        if let Some(event) = client
+            .connection_events()
+            .into_iter()
+            .find(|e| e.is_terminal())
+        {
+            return Err(event.to_string());
+        }
+        while let Some(event) = signaling.try_next_event() {
+            match event {
+                SignalingEvent::CallEnded { from } if from == peer => {
+                    return Ok((connect_time, call_start.elapsed(), remote_frames));
+                }
+                SignalingEvent::IceRestart { from, sdp } if from == peer => {
+                    if let Err(err) = handle_ice_restart(client, signaling, &peer, sdp.as_deref()) {
+                        logger.warn(&format!("Headless: reinicio de ICE: {}", err));
+                    }
+                }
+                SignalingEvent::IceRestartAnswer { from, sdp } if from == peer => {
+                    if let Err(err) = client.apply_restart_answer(&sdp) {
+                        logger.warn(&format!("Headless: reinicio de ICE: {}", err));
+                    }
+                }
+                // Una sola llamada a la vez
+                SignalingEvent::IncomingCall { from, .. } => {
+                    let _ = signaling.reject_call_busy(&from);
+                }
+                SignalingEvent::Disconnected => {
+                    return Err(DISCONNECTED_MSG.to_string());
+                }
+                _ => {}
+            }
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+    Ok((connect_time, call_start.elapsed(), remote_frames))
+}
+
+fn start_video(client: &mut P2PClient, plan: &MediaPlan) -> Result<(), String> {
+    let video = plan.video;
+    match plan.video_file {
+        Some(path) => {
+            let source = VideoFile::open(path, video.width, video.height, video.fps)
+                .map_err(|e| format!("No se pudo abrir {}: {}", path, e.to_string().trim()))?;
+            client.start_media_from(Box::new(source), video)
+        }
+        None => client.start_media(0, video, !plan.camera),
+    }
+    .map_err(|e| format!("No se pudo arrancar el video: {:?}", e))
+}
+
+/// Lee eventos de señalización hasta que `matcher` decide, o hasta `timeout`.
+fn wait_for_event<T>(
+    signaling: &SignalingClient,
+    timeout: Duration,
+    mut matcher: impl FnMut(SignalingEvent) -> Option<Result<T, String>>,
+) -> Result<T, String> {
+    let start = Instant::now();
+    loop {
+        match signaling.try_next_event() {
+            Some(SignalingEvent::Disconnected) => {
+                return Err(DISCONNECTED_MSG.to_string());
+            }
+            Some(event) => {
+                if let Some(result) = matcher(event) {
+                    return result;
+                }
+            }
+            None if interrupted() => return Err(INTERRUPTED_MSG.to_string()),
+            None if start.elapsed() > timeout => {
+                return Err(format!(
+                    "Sin respuesta del servidor después de {:?}",
+                    timeout
+                ));
+            }
+            None => thread::sleep(POLL_INTERVAL),
+        }
+    }
+}
+
+fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+/// Un SIGINT no mata el proceso: prende `INTERRUPTED` y los bucles cortan
+/// la llamada y salen.
+#[cfg(unix)]
+fn watch_interrupt() {
+    extern "C" fn on_sigint(_: libc::c_int) {
+        INTERRUPTED.store(true, Ordering::SeqCst);
+    }
+    // SAFETY: el handler solo escribe un atómico, que se puede hacer desde
+    // una señal.
+    unsafe {
+        libc::signal(
+            libc::SIGINT,
+            on_sigint as extern "C" fn(libc::c_int) as libc::sighandler_t,
+        );
+    }
+}
+
+#[cfg(not(unix))]
+fn watch_interrupt() {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_options_from_args() {
+        let options = HeadlessOptions::from_args(&args(&[
+            "client.conf",
+            "--headless",
+            "--user",
+            "alice",
+            "--password",
+            "secret",
+            "--call",
+            "bob",
+            "--duration",
+            "5",
+        ]))
+        .unwrap();
+        assert_eq!(options.username, "alice");
+        assert_eq!(options.password, "secret");
+        assert_eq!(options.call.as_deref(), Some("bob"));
+        assert_eq!(options.duration, Duration::from_secs(5));
+        assert_eq!(options.video_file, None);
+        assert!(!options.kiosk);
+
+        // Sin --call atiende, con la duración por defecto
+        let options =
+            HeadlessOptions::from_args(&args(&["--user", "bob", "--password", "x"])).unwrap();
+        assert_eq!(options.call, None);
+        assert_eq!(options.duration, DEFAULT_DURATION);
+    }
+
+    #[test]
+    fn test_kiosk_flag_and_allowed_callers() {
+        let options = HeadlessOptions::from_args(&args(&[
+            "--headless",
+            "--kiosk",
+            "--user",
+            "door",
+            "--password",
+            "x",
+        ]))
+        .unwrap();
+        assert!(options.kiosk);
+        assert_eq!(options.call, None);
+
+        assert!(caller_allowed(&[], "anyone"));
+        let allow = vec!["alice".to_string(), "bob".to_string()];
+        assert!(caller_allowed(&allow, "bob"));
+        assert!(!caller_allowed(&allow, "mallory"));
+        assert!(!caller_allowed(&allow, "Bob"));
+    }
+
+    #[test]
+    fn test_options_need_credentials_and_valid_values() {
+        assert!(HeadlessOptions::from_args(&args(&["--user", "alice"])).is_err());
+        assert!(HeadlessOptions::from_args(&args(&["--user", "a", "--password"])).is_err());
+        assert!(
+            HeadlessOptions::from_args(&args(&[
+                "--user",
+                "a",
+                "--password",
+                "b",
+                "--duration",
+                "soon"
+            ]))
+            .is_err()
+        );
+    }
+}
@@ -0,0 +1,129 @@
+//! Mensajes de texto que llegan por el canal de datos, compartidos entre el
+//! hilo del listener y la UI. Solo se guardan los últimos `capacity`: un peer
+//! que inunda el canal no puede hacer crecer la memoria durante la llamada.
+//! Cada mensaje tiene un número de orden que no se reinicia, así quien lee
+//! recuerda hasta dónde llegó y se entera si se perdieron mensajes.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+/// Mensajes que se guardan si no se pide otra cosa.
+pub const DEFAULT_INBOX_CAPACITY: usize = 256;
+
+/// Bandeja compartida entre el listener y las pantallas.
+pub type SharedInbox = Arc<Mutex<MessageInbox>>;
+
+#[derive(Debug, Clone)]
+pub struct MessageInbox {
+    messages: VecDeque<String>,
+    capacity: usize,
+    /// Mensajes recibidos desde el principio, incluidos los descartados.
+    total: u64,
+}
+
+impl MessageInbox {
+    pub fn new(capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        Self {
+            messages: VecDeque::with_capacity(capacity),
+            capacity,
+            total: 0,
+        }
+    }
+
+    /// Bandeja vacía lista para compartir.
+    pub fn shared() -> SharedInbox {
+        Arc::new(Mutex::new(Self::default()))
+    }
+
+    /// Agrega un mensaje, descartando el más viejo si ya está llena.
+    pub fn push(&mut self, message: String) {
+        if self.messages.len() == self.capacity {
+            self.messages.pop_front();
+        }
+        self.messages.push_back(message);
+        self.total += 1;
+    }
+
+    /// Mensajes recibidos desde el principio; es el valor a recordar como
+    /// "leídos" para la próxima llamada a `since`.
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// Los mensajes guardados, del más viejo al más nuevo.
+    pub fn iter(&self) -> impl Iterator<Item = &str> {
+        self.messages.iter().map(String::as_str)
+    }
+
+    /// Los mensajes que llegaron después de haber leído `seen`, sin los que
+    /// ya se descartaron.
+    pub fn since(&self, seen: u64) -> impl Iterator<Item = &str> {
+        let skip = seen.saturating_sub(self.first_index());
+        self.iter()
+            .skip(skip.min(self.messages.len() as u64) as usize)
+    }
+
+    /// Cuántos mensajes posteriores a `seen` se descartaron sin leerse.
+    pub fn dropped_since(&self, seen: u64) -> u64 {
+        self.first_index().saturating_sub(seen)
+    }
+
+    /// Número de orden del mensaje más viejo que se guarda.
+    fn first_index(&self) -> u64 {
+        self.total - self.messages.len() as u64
+    }
+}
+
+impl Default for MessageInbox {
+    fn default() -> Self {
+        Self::new(DEFAULT_INBOX_CAPACITY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filled(capacity: usize, count: usize) -> MessageInbox {
+        let mut inbox = MessageInbox::new(capacity);
+        for i in 0..count {
+            inbox.push(format!("msg {}", i));
+        }
+        inbox
+    }
+
+    #[test]
+    fn test_keeps_the_latest_messages_when_full() {
+        let inbox = filled(3, 5);
+        assert_eq!(inbox.total(), 5);
+        assert_eq!(
+            inbox.iter().collect::<Vec<_>>(),
+            ["msg 2", "msg 3", "msg 4"]
+        );
+
+        // Quien leyó hasta el 3 ve solo lo nuevo
+        assert_eq!(inbox.since(4).collect::<Vec<_>>(), ["msg 4"]);
+        assert_eq!(inbox.dropped_since(4), 0);
+        assert_eq!(inbox.since(5).count(), 0);
+
+        // Quien no leyó nada se entera de los dos que se perdieron
+        assert_eq!(inbox.since(0).count(), 3);
+        assert_eq!(inbox.dropped_since(0), 2);
+    }
+
+    #[test]
+    fn test_hangup_is_seen_after_earlier_messages_are_evicted() {
+        let mut inbox = filled(4, 10);
+        let seen = inbox.total();
+        for i in 0..6 {
+            inbox.push(format!("flood {}", i));
+        }
+        inbox.push("CALL_END".to_string());
+
+        assert_eq!(inbox.dropped_since(seen), 3);
+        assert!(inbox.since(seen).any(|msg| msg.trim() == "CALL_END"));
+        // Una vez leído no vuelve a aparecer
+        assert!(inbox.since(inbox.total()).next().is_none());
+    }
+}
@@ -1,4 +1,21 @@
+pub mod call_controller;
+pub mod capabilities;
+pub mod device_test;
+pub mod diagnostics;
+pub mod headless;
+pub mod identity;
+pub mod message_inbox;
+pub mod notifier;
 pub mod p2p_client;
+pub mod p2p_client_error;
+pub mod pending_calls;
+pub mod reconnect;
+pub mod settings;
 
 pub mod signaling_client;
+pub mod snapshot;
+pub mod storage;
+pub mod thumbnail;
+pub mod tls;
+pub mod transfer_rate;
 pub mod webrtc_service;
@@ -0,0 +1,273 @@
+//! Notificaciones del sistema para lo que pasa con la ventana en segundo
+//! plano: llamadas entrantes o perdidas y transferencias de archivos.
+//!
+//! `DesktopNotifier` no agrega dependencias: usa lo que ya trae cada sistema
+//! (`notify-send` en Linux, `osascript` en macOS y los toasts de Windows por
+//! PowerShell). `NoopNotifier` no muestra nada, para los tests y las
+//! máquinas sin escritorio.
+
+use crate::client::settings::{PreferredStatus, UserSettings};
+use room_rtc::log_warn;
+use std::process::{Command, Stdio};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+
+const APP_NAME: &str = "RoomRTC";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Notification {
+    IncomingCall {
+        from: String,
+    },
+    /// Nadie atendió antes del timeout de ring.
+    MissedCall {
+        from: String,
+    },
+    FileOffer {
+        from: Option<String>,
+        name: String,
+    },
+    TransferCompleted {
+        name: String,
+        outgoing: bool,
+    },
+    TransferFailed {
+        name: String,
+        reason: String,
+    },
+}
+
+impl Notification {
+    pub fn title(&self) -> String {
+        match self {
+            Notification::IncomingCall { .. } => "Incoming call".to_string(),
+            Notification::MissedCall { .. } => "Missed call".to_string(),
+            Notification::FileOffer { .. } => "File offered".to_string(),
+            Notification::TransferCompleted { outgoing: true, .. } => "File sent".to_string(),
+            Notification::TransferCompleted {
+                outgoing: false, ..
+            } => "File received".to_string(),
+            Notification::TransferFailed { .. } => "File transfer failed".to_string(),
+        }
+    }
+
+    pub fn body(&self) -> String {
+        match self {
+            Notification::IncomingCall { from } => format!("{} is calling you", from),
+            Notification::MissedCall { from } => format!("You missed a call from {}", from),
+            Notification::FileOffer {
+                from: Some(from),
+                name,
+            } => format!("{} wants to send you {}", from, name),
+            Notification::FileOffer { from: None, name } => {
+                format!("You were offered {}", name)
+            }
+            Notification::TransferCompleted { name, .. } => name.clone(),
+            Notification::TransferFailed { name, reason } => format!("{}: {}", name, reason),
+        }
+    }
+
+    /// Hacer click en la notificación trae la ventana al frente.
+    pub fn focuses_window(&self) -> bool {
+        matches!(self, Notification::IncomingCall { .. })
+    }
+}
+
+pub trait Notifier {
+    fn notify(&self, notification: &Notification);
+
+    /// Si desde la última consulta hicieron click en una notificación que
+    /// pide traer la ventana al frente.
+    fn take_focus_request(&self) -> bool {
+        false
+    }
+}
+
+/// No muestra nada.
+pub struct NoopNotifier;
+
+impl Notifier for NoopNotifier {
+    fn notify(&self, _notification: &Notification) {}
+}
+
+/// Notificaciones nativas de cada sistema. Cada una se lanza en su propio
+/// hilo, así un comando que tarda o no existe no frena la UI.
+pub struct DesktopNotifier {
+    focus_requested: Arc<AtomicBool>,
+}
+
+impl DesktopNotifier {
+    pub fn new() -> Self {
+        Self {
+            focus_requested: Arc::new(AtomicBool::new(false)),
+        }
+    }
+}
+
+impl Default for DesktopNotifier {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Notifier for DesktopNotifier {
+    fn notify(&self, notification: &Notification) {
+        let title = notification.title();
+        let body = notification.body();
+        let focuses = notification.focuses_window();
+        let focus_requested = Arc::clone(&self.focus_requested);
+        let spawned = thread::Builder::new()
+            .name("notifier".to_string())
+            .spawn(move || {
+                if show(&title, &body, focuses) {
+                    focus_requested.store(true, Ordering::Relaxed);
+                }
+            });
+        if let Err(err) = spawned {
+            log_warn!("No se pudo mostrar la notificación: {}", err);
+        }
+    }
+
+    fn take_focus_request(&self) -> bool {
+        self.focus_requested.swap(false, Ordering::Relaxed)
+    }
+}
+
+/// Si se muestra la notificación: nunca con la ventana enfocada, en no
+/// molestar ni con las notificaciones apagadas en Settings.
+pub fn should_notify(settings: &UserSettings, window_focused: bool) -> bool {
+    settings.notifications
+        && !window_focused
+        && settings.preferred_status != PreferredStatus::DoNotDisturb
+}
+
+/// Muestra la notificación y devuelve si el usuario hizo click. Solo
+/// `notify-send` avisa del click; en los demás sistemas el click lo maneja
+/// el propio sistema.
+#[cfg(target_os = "linux")]
+fn show(title: &str, body: &str, wait_click: bool) -> bool {
+    if wait_click {
+        // --wait deja el proceso vivo hasta que se cierra la notificación y
+        // la acción elegida sale por stdout
+        let output = Command::new("notify-send")
+            .args(["--app-name", APP_NAME, "--action=default=Open", "--wait"])
+            .args([title, body])
+            .stderr(Stdio::null())
+            .output();
+        if let Ok(output) = output
+            && output.status.success()
+        {
+            return String::from_utf8_lossy(&output.stdout).trim() == "default";
+        }
+        // Versiones viejas sin --action: se muestra sin click
+    }
+    run(Command::new("notify-send").args(["--app-name", APP_NAME, title, body]));
+    false
+}
+
+#[cfg(target_os = "macos")]
+fn show(title: &str, body: &str, _wait_click: bool) -> bool {
+    // Los textos van como argumentos del script, nunca dentro del código
+    run(Command::new("osascript")
+        .args([
+            "-e",
+            "on run argv",
+            "-e",
+            "display notification (item 2 of argv) with title (item 1 of argv)",
+            "-e",
+            "end run",
+        ])
+        .args([title, body]));
+    false
+}
+
+#[cfg(target_os = "windows")]
+fn show(title: &str, body: &str, _wait_click: bool) -> bool {
+    const TOAST_SCRIPT: &str = "\
+        $m = [Windows.UI.Notifications.ToastNotificationManager, Windows.UI.Notifications, ContentType = WindowsRuntime];\
+        $t = $m::GetTemplateContent([Windows.UI.Notifications.ToastTemplateType]::ToastText02);\
+        $n = $t.GetElementsByTagName('text');\
+        $n.Item(0).AppendChild($t.CreateTextNode($env:ROOMRTC_TITLE)) > $null;\
+        $n.Item(1).AppendChild($t.CreateTextNode($env:ROOMRTC_BODY)) > $null;\
+        $m::CreateToastNotifier($env:ROOMRTC_APP).Show([Windows.UI.Notifications.ToastNotification]::new($t))";
+    run(Command::new("powershell")
+        .args(["-NoProfile", "-NonInteractive", "-Command", TOAST_SCRIPT])
+        .env("ROOMRTC_APP", APP_NAME)
+        .env("ROOMRTC_TITLE", title)
+        .env("ROOMRTC_BODY", body));
+    false
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+fn show(_title: &str, _body: &str, _wait_click: bool) -> bool {
+    false
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
+fn run(command: &mut Command) {
+    let status = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status();
+    match status {
+        Ok(status) if !status.success() => {
+            log_warn!("El comando de notificaciones terminó con {}", status)
+        }
+        Ok(_) => {}
+        Err(err) => log_warn!("No se pudo mostrar la notificación: {}", err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::AppConfig;
+
+    #[test]
+    fn test_notifications_are_suppressed_when_focused_dnd_or_disabled() {
+        let mut settings = UserSettings::from_config(&AppConfig::default());
+        assert!(should_notify(&settings, false));
+        assert!(!should_notify(&settings, true));
+
+        settings.preferred_status = PreferredStatus::DoNotDisturb;
+        assert!(!should_notify(&settings, false));
+
+        settings.preferred_status = PreferredStatus::Available;
+        settings.notifications = false;
+        assert!(!should_notify(&settings, false));
+    }
+
+    #[test]
+    fn test_notification_text() {
+        let incoming = Notification::IncomingCall {
+            from: "alice".to_string(),
+        };
+        assert_eq!(incoming.title(), "Incoming call");
+        assert_eq!(incoming.body(), "alice is calling you");
+        assert!(incoming.focuses_window());
+
+        let failed = Notification::TransferFailed {
+            name: "notes.pdf".to_string(),
+            reason: "Peer stopped sending".to_string(),
+        };
+        assert_eq!(failed.body(), "notes.pdf: Peer stopped sending");
+        assert!(!failed.focuses_window());
+
+        let sent = Notification::TransferCompleted {
+            name: "notes.pdf".to_string(),
+            outgoing: true,
+        };
+        assert_eq!(sent.title(), "File sent");
+    }
+
+    #[test]
+    fn test_noop_notifier_never_asks_for_focus() {
+        let notifier = NoopNotifier;
+        notifier.notify(&Notification::MissedCall {
+            from: "bob".to_string(),
+        });
+        assert!(!notifier.take_focus_request());
+    }
+}
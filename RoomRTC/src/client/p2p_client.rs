@@ -1,31 +1,75 @@
+use crate::client::capabilities::PeerCapabilities;
+use crate::client::diagnostics::connection_report;
+use crate::client::p2p_client_error::P2PClientError;
 use opencv::core::Mat;
+use room_rtc::camera::fake_camera::FakeCamera;
+use room_rtc::camera::frame_source::FrameSource;
+use room_rtc::camera::video_format::VideoFormat;
+use room_rtc::crypto::srtp::SharedSrtpContext;
+use room_rtc::ice::{CandidatePairInfo, IceCheckConfig};
+use room_rtc::protocols::media_control::{MEDIA_CONTROL_STREAM, MediaControlMessage};
 use room_rtc::protocols::rtcp::rtcp_packet::RtcpPacket;
 use room_rtc::protocols::rtcp::rtcp_payload::RtcpPayload;
 use room_rtc::protocols::rtp::rtp_header::RtpHeader;
+use room_rtc::protocols::sdp::media_direction::MediaDirection;
+use room_rtc::protocols::sdp::media_type::MediaType;
+use room_rtc::rtc::codec_negotiation::NegotiatedCodecs;
+use room_rtc::rtc::connection_event::{ConnectionEvent, ConnectionState};
+use room_rtc::rtc::rtc_dtls::{DtlsConfig, DtlsIdentity};
 use room_rtc::rtc::rtc_peer_connection::{
     PeerConnectionError, PeerConnectionRole, RtcPeerConnection,
 };
+use room_rtc::rtc::rtc_sctp::{SctpError, SctpEvent, SctpStreamStats};
+use room_rtc::rtc::socket::consent::{CONSENT_INTERVAL, CONSENT_SILENCE};
+use room_rtc::rtc::socket::peer_socket::PeerSocket;
+use room_rtc::rtc::socket::port_range::PortRange;
+use room_rtc::rtc::ssrc_routing::PacketRoute;
 use room_rtc::worker_thread::error::worker_error::WorkerError;
+use room_rtc::worker_thread::media_channel::MediaSender;
 use room_rtc::worker_thread::media_metrics::{CallMetricsSnapshot, MediaMetrics};
+use room_rtc::worker_thread::worker_audio::{AudioParams, WorkerAudio};
 use room_rtc::worker_thread::worker_media::{VideoParams, WorkerMedia};
-use room_rtc::crypto::srtp::SrtpContext;
-use room_rtc::rtc::socket::peer_socket::PeerSocket;
+use room_rtc::{log_debug, log_error, log_info, log_warn};
 use std::collections::VecDeque;
 use std::net::SocketAddr;
-use std::sync::mpsc::SyncSender;
-use std::sync::{Arc, Mutex};
-use std::time::Duration;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender, SyncSender};
+use std::sync::{Arc, Mutex, MutexGuard};
 use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// Stream interno por el que llegan a `sctp_incoming` los streams que el peer
+/// reseteó (cerró su `DataChannel`); el payload es el id del stream en big
+/// endian.
+pub const SCTP_STREAM_CLOSED_NOTICE: u16 = 996;
 
 pub struct P2PClient {
     // Usamos Arc<Mutex<>> para poder compartirlo de forma segura entre hilos
     peer_connection: Arc<Mutex<RtcPeerConnection>>,
     listener_handle: Option<JoinHandle<()>>,
     media_worker: Option<WorkerMedia>,
-    media_incoming: Arc<Mutex<Option<SyncSender<Vec<u8>>>>>,
-    audio_incoming: Arc<Mutex<Option<SyncSender<Vec<u8>>>>>,
+    media_incoming: Arc<Mutex<Option<MediaSender<Vec<u8>>>>>,
+    audio_incoming: Arc<Mutex<Option<MediaSender<Vec<u8>>>>>,
     media_metrics: Option<Arc<Mutex<MediaMetrics>>>,
+    // Paquetes RTP con un SSRC que el remoto no declaró, descartados
+    unknown_ssrc_packets: Arc<AtomicU64>,
+    // SSRCs nuestros que llegaron desde el peer, pendientes de resolver
+    ssrc_collisions: Arc<Mutex<Vec<u32>>>,
+    // Cuándo llegó el último RTP de audio: sin video es la señal de que el
+    // peer sigue ahí
+    last_audio_packet: Arc<Mutex<Option<Instant>>>,
     pub sctp_incoming: Arc<Mutex<Option<SyncSender<(u16, Vec<u8>)>>>>,
+    // Último error del hilo de conexión (ICE/DTLS), para el diagnóstico
+    last_error: Arc<Mutex<Option<String>>>,
+    // Fase del establecimiento, según los eventos del hilo de conexión
+    state: Arc<Mutex<ConnectionState>>,
+    // Progreso/fallos del hilo de conexión para la UI
+    events_tx: Sender<ConnectionEvent>,
+    events_rx: Arc<Mutex<Receiver<ConnectionEvent>>>,
+    // Lo que soportan los dos clientes, negociado por señalización
+    capabilities: PeerCapabilities,
+    // Tope total de envío (audio + video) en kbps, de Settings
+    bandwidth_cap_kbps: Option<u64>,
 }
 
 impl Clone for P2PClient {
@@ -37,14 +81,35 @@ impl Clone for P2PClient {
             media_incoming: Arc::clone(&self.media_incoming),
             audio_incoming: Arc::clone(&self.audio_incoming),
             media_metrics: self.media_metrics.clone(),
+            unknown_ssrc_packets: Arc::clone(&self.unknown_ssrc_packets),
+            ssrc_collisions: Arc::clone(&self.ssrc_collisions),
+            last_audio_packet: Arc::clone(&self.last_audio_packet),
             sctp_incoming: Arc::clone(&self.sctp_incoming),
+            last_error: Arc::clone(&self.last_error),
+            state: Arc::clone(&self.state),
+            events_tx: self.events_tx.clone(),
+            events_rx: Arc::clone(&self.events_rx),
+            capabilities: self.capabilities.clone(),
+            bandwidth_cap_kbps: self.bandwidth_cap_kbps,
         }
     }
 }
 
 impl P2PClient {
-    pub fn new(role: PeerConnectionRole) -> Result<Self, PeerConnectionError> {
-        let peer_connection = Arc::new(Mutex::new(RtcPeerConnection::new(None, role)?));
+    /// Con `port_range` el socket de la media toma el primer puerto libre del
+    /// rango; si están todos ocupados falla. `identity` es la identidad DTLS
+    /// de toda la corrida; sin ella se usa la efímera compartida.
+    pub fn new(
+        role: PeerConnectionRole,
+        port_range: Option<PortRange>,
+        identity: Option<Arc<DtlsIdentity>>,
+    ) -> Result<Self, PeerConnectionError> {
+        let pc = match port_range {
+            Some(range) => RtcPeerConnection::with_port_range(range, role, identity)?,
+            None => RtcPeerConnection::new(None, role, identity)?,
+        };
+        let peer_connection = Arc::new(Mutex::new(pc));
+        let (events_tx, events_rx) = mpsc::channel();
 
         Ok(Self {
             peer_connection,
@@ -53,137 +118,330 @@ impl P2PClient {
             media_incoming: Arc::new(Mutex::new(None)),
             audio_incoming: Arc::new(Mutex::new(None)),
             media_metrics: None,
+            unknown_ssrc_packets: Arc::new(AtomicU64::new(0)),
+            ssrc_collisions: Arc::new(Mutex::new(Vec::new())),
+            last_audio_packet: Arc::new(Mutex::new(None)),
             sctp_incoming: Arc::new(Mutex::new(None)),
+            last_error: Arc::new(Mutex::new(None)),
+            state: Arc::new(Mutex::new(ConnectionState::default())),
+            events_tx,
+            events_rx: Arc::new(Mutex::new(events_rx)),
+            // Sin señalización (código de sesión) se supone un peer como este
+            capabilities: PeerCapabilities::local(),
+            bandwidth_cap_kbps: None,
+        })
+    }
+
+    /// La conexión, o `P2PClientError::Poisoned` si un hilo hizo panic con
+    /// ella tomada.
+    fn pc(&self) -> Result<MutexGuard<'_, RtcPeerConnection>, P2PClientError> {
+        lock_pc(&self.peer_connection)
+    }
+
+    /// La conexión aunque esté envenenada, para lo que solo la consulta o la
+    /// configura antes de que arranquen los hilos: eso no depende de lo que
+    /// haya quedado a medias.
+    fn pc_recovered(&self) -> MutexGuard<'_, RtcPeerConnection> {
+        self.peer_connection.lock().unwrap_or_else(|poisoned| {
+            log_warn!("Peer connection poisoned, reading its last state");
+            poisoned.into_inner()
         })
     }
 
+    /// Un hilo hizo panic con la conexión tomada; la UI lo trata como una
+    /// llamada caída.
+    pub fn is_poisoned(&self) -> bool {
+        self.peer_connection.is_poisoned()
+    }
+
     pub fn role(&self) -> PeerConnectionRole {
-        self.peer_connection.lock().unwrap().role()
+        self.pc_recovered().role()
     }
 
     pub fn local_addr(&self) -> Result<SocketAddr, PeerConnectionError> {
-        self.peer_connection.lock().unwrap().local_addr()
+        self.pc()?.local_addr()
+    }
+
+    pub fn set_local_bandwidth(&self, kbps: Option<u64>) {
+        self.pc_recovered().set_local_bandwidth(kbps);
+    }
+
+    pub fn set_stun_server(&self, server: &str) {
+        self.pc_recovered().set_stun_server(server);
+    }
+
+    /// Plazo, reintentos y paralelismo de los chequeos de conectividad ICE.
+    pub fn set_ice_config(&self, config: IceCheckConfig) {
+        self.pc_recovered().set_ice_config(config);
+    }
+
+    /// Qué queremos hacer con ese medio, p.ej. `RecvOnly` para solo mirar.
+    /// Va antes de crear la oferta o la respuesta.
+    pub fn set_local_direction(&self, media_type: MediaType, direction: MediaDirection) {
+        self.pc_recovered()
+            .set_local_direction(media_type, direction);
+    }
+
+    /// Suites y versiones que acepta el handshake DTLS. Va antes de crear la
+    /// oferta o la respuesta, porque rehace el certificado.
+    pub fn set_dtls_config(&self, config: &DtlsConfig) -> Result<(), PeerConnectionError> {
+        self.pc()?.set_dtls_config(config)
+    }
+
+    /// Guarda lo que anunció el peer en CALL_OFFER / CALL_ANSWER; de ahí en
+    /// más solo se usa lo que soportan los dos.
+    pub fn set_peer_capabilities(&mut self, remote: &PeerCapabilities) {
+        self.capabilities = PeerCapabilities::local().intersect(remote);
+    }
+
+    /// Capacidades negociadas con el peer.
+    pub fn capabilities(&self) -> &PeerCapabilities {
+        &self.capabilities
+    }
+
+    /// Tope total de lo que mandamos, audio y video juntos, en kbps. Se lee
+    /// al arrancar la media; con el video ya andando, también ajusta el
+    /// pacer.
+    pub fn set_bandwidth_cap(&mut self, total_kbps: Option<u64>) {
+        self.bandwidth_cap_kbps = total_kbps;
+        if let Some(worker) = self.media_worker.as_ref()
+            && let Err(err) = worker.set_bandwidth_cap(total_kbps)
+        {
+            log_warn!("Bandwidth cap not applied: {}", err);
+        }
+    }
+
+    pub fn bandwidth_cap_kbps(&self) -> Option<u64> {
+        self.bandwidth_cap_kbps
+    }
+
+    /// Bandwidth cap announced by the remote peer in its SDP (kbps).
+    pub fn remote_bandwidth_kbps(&self) -> Option<u64> {
+        self.pc_recovered().remote_bandwidth_kbps()
+    }
+
+    /// Payload types agreed during the offer/answer exchange.
+    pub fn negotiated_codecs(&self) -> NegotiatedCodecs {
+        self.pc_recovered().negotiated_codecs()
+    }
+
+    /// Si el remoto aceptó ese medio (no lo rechazó con puerto 0).
+    pub fn is_media_accepted(&self, media_type: MediaType) -> bool {
+        self.negotiated_codecs().is_accepted(media_type)
     }
 
     pub fn create_offer(&mut self) -> Result<String, PeerConnectionError> {
-        self.peer_connection.lock().unwrap().create_offer()
+        self.pc()?.create_offer()
     }
 
     pub fn process_offer(&mut self, offer_sdp: &str) -> Result<String, PeerConnectionError> {
-        let answer = self.peer_connection.lock().unwrap().process_offer(offer_sdp)?;
+        let answer = self.pc()?.process_offer(offer_sdp)?;
         Ok(answer)
     }
 
     pub fn set_remote_description(&mut self, remote_sdp: &str) -> Result<(), PeerConnectionError> {
-        self.peer_connection
-            .lock()
-            .unwrap()
-            .set_remote_description(remote_sdp)
+        self.pc()?.set_remote_description(remote_sdp)
+    }
+
+    /// Oferta de reinicio de ICE tras un corte en plena llamada. Solo la arma
+    /// quien controla ICE; del otro lado devuelve `None` y hay que pedírsela
+    /// al peer.
+    pub fn create_restart_offer(&self) -> Result<Option<String>, PeerConnectionError> {
+        let mut pc = self.pc()?;
+        if !pc.role().is_controlling() {
+            return Ok(None);
+        }
+        pc.create_restart_offer().map(Some)
+    }
+
+    /// Responde la oferta de reinicio del peer y empieza a probar sus
+    /// candidatos nuevos; el listener sigue probando mientras no haya media.
+    pub fn accept_restart_offer(&self, offer_sdp: &str) -> Result<String, PeerConnectionError> {
+        let mut pc = self.pc()?;
+        let answer = pc.process_restart_offer(offer_sdp)?;
+        pc.probe_consent();
+        Ok(answer)
+    }
+
+    /// Aplica la respuesta a nuestra oferta de reinicio.
+    pub fn apply_restart_answer(&self, answer_sdp: &str) -> Result<(), PeerConnectionError> {
+        let mut pc = self.pc()?;
+        pc.apply_restart_answer(answer_sdp)?;
+        pc.probe_consent();
+        Ok(())
+    }
+
+    /// Descripción local como código de sesión de una línea.
+    pub fn export_session_blob(&self) -> Result<String, PeerConnectionError> {
+        self.pc()?.export_session_blob()
+    }
+
+    /// Aplica el código de sesión del otro peer. Si era una oferta devuelve
+    /// la respuesta.
+    pub fn import_session_blob(
+        &mut self,
+        blob: &str,
+    ) -> Result<Option<String>, PeerConnectionError> {
+        self.pc()?.import_session_blob(blob)
     }
 
     /// Inicia el proceso de conexión ICE y DTLS en un hilo de fondo.
     pub fn establish_connection(&mut self) -> Result<(), PeerConnectionError> {
         let pc_clone = Arc::clone(&self.peer_connection);
         let sctp_extension = Arc::clone(&self.sctp_incoming);
+        let last_error = Arc::clone(&self.last_error);
+        let state = Arc::clone(&self.state);
+        let events = self.events_tx.clone();
+        if let Ok(mut state) = state.lock() {
+            *state = ConnectionState::Checking;
+        }
 
         // Asegurarse de que el listener esté iniciado antes de empezar
-        pc_clone.lock().unwrap().ensure_listener_started()?;
+        let ice_timeout = {
+            let mut pc = self.pc()?;
+            pc.ensure_listener_started()?;
+            pc.ice_config().timeout
+        };
 
         thread::spawn(move || {
-            println!("Connection Thread: Starting...");
+            log_info!("Connection Thread: Starting...");
+            let ice_started = Instant::now();
 
             // 1. Iniciar comprobaciones de conectividad ICE
-            if let Err(e) = pc_clone.lock().unwrap().start_connectivity_checks() {
-                eprintln!("Connection Thread: ICE connectivity checks failed to start: {}", e);
+            let checks = lock_pc(&pc_clone)
+                .map_err(PeerConnectionError::from)
+                .and_then(|mut pc| pc.start_connectivity_checks());
+            if let Err(e) = checks {
+                log_warn!(
+                    "Connection Thread: ICE connectivity checks failed to start: {}",
+                    e
+                );
+                report_event(
+                    &events,
+                    &last_error,
+                    &state,
+                    ConnectionEvent::from_error(&e),
+                );
                 return;
             }
-            println!("Connection Thread: ICE checks started.");
+            log_info!("Connection Thread: ICE checks started.");
 
-            // 2. Esperar a que ICE se conecte
-            for _ in 0..50 { // Timeout de 5 segundos
-                if pc_clone.lock().unwrap().is_connected() {
-                    break;
-                }
+            // 2. Esperar a que ICE se conecte, dentro del plazo configurado
+            let is_connected = || lock_pc(&pc_clone).is_ok_and(|pc| pc.is_connected());
+            while !is_connected() && ice_started.elapsed() < ice_timeout {
                 thread::sleep(Duration::from_millis(100));
             }
 
-            if !pc_clone.lock().unwrap().is_connected() {
-                eprintln!("Connection Thread: ICE connection timed out.");
+            if !is_connected() {
+                log_warn!("Connection Thread: ICE connection timed out.");
+                report_event(
+                    &events,
+                    &last_error,
+                    &state,
+                    ConnectionEvent::IceFailed("connection timed out".to_string()),
+                );
                 return;
             }
-            println!("Connection Thread: ICE connection established!");
+            log_info!("Connection Thread: ICE connection established!");
+            report_event(&events, &last_error, &state, ConnectionEvent::IceConnected);
 
             // 3. Iniciar el handshake DTLS
-            match pc_clone.lock().unwrap().start_dtls_handshake(5000) {
+            let handshake = lock_pc(&pc_clone)
+                .map_err(PeerConnectionError::from)
+                .and_then(|mut pc| pc.start_dtls_handshake(5000));
+            match handshake {
                 Ok(_) => {
-                    println!("Connection Thread: DTLS handshake successful!");
+                    log_info!("Connection Thread: DTLS handshake successful!");
+                    report_event(&events, &last_error, &state, ConnectionEvent::DtlsConnected);
                 }
                 Err(e) => {
-                    eprintln!("Connection Thread: DTLS handshake failed: {}", e);
+                    log_warn!("Connection Thread: DTLS handshake failed: {}", e);
+                    report_event(
+                        &events,
+                        &last_error,
+                        &state,
+                        ConnectionEvent::from_error(&e),
+                    );
                     return;
                 }
             }
 
             // 4. Iniciar SCTP Association
-            {
-               let mut pc = pc_clone.lock().unwrap();
-               // Determine if client (Controlling -> Client).
-               let callback_role = pc.role(); 
-               if let Some(sctp) = &mut pc.sctp_association {
-                   // Controlling node initiates Connect
-                   // Both sides call establish; initiator will send INIT.
-                   if matches!(callback_role, PeerConnectionRole::Controlling) {
+            if let Ok(mut pc) = lock_pc(&pc_clone) {
+                // Determine if client (Controlling -> Client).
+                let callback_role = pc.role();
+                if let Some(sctp) = &mut pc.sctp_association {
+                    // Controlling node initiates Connect
+                    // Both sides call establish; initiator will send INIT.
+                    if matches!(callback_role, PeerConnectionRole::Controlling) {
                         sctp.establish();
-                   } else {
+                    } else {
                         sctp.establish();
-                   }
-               }
+                    }
+                }
             }
 
             // 5. Start SCTP Pump Loop
-            println!("Connection Thread: Entering SCTP Pump Loop...");
-            
+            log_info!("Connection Thread: Entering SCTP Pump Loop...");
+
             // Queue for packets that couldn't be sent immediately due to socket blocking
             let mut pending_outbound: VecDeque<Vec<u8>> = VecDeque::new();
+            let mut sctp_ready = false;
 
             loop {
                 thread::sleep(Duration::from_millis(1));
-                
+
                 // --- SCOPE for Mutex Lock ---
                 let mut incoming: Vec<(u16, Vec<u8>)> = Vec::new();
                 let mut keep_running = true;
-                
+
                 {
-                    let mut pc = pc_clone.lock().unwrap();
+                    let Ok(mut pc) = lock_pc(&pc_clone) else {
+                        log_error!("Connection Thread: peer connection poisoned, stopping");
+                        break;
+                    };
                     if pc.sctp_association.is_none() || !pc.has_dtls_session() {
                         keep_running = false;
                     } else {
                         // A. Read from DTLS -> Feed SCTP
                         let mut buf = [0u8; 8192]; // Bigger buffer
                         match pc.dtls_read(&mut buf) {
-                             Ok(n) => {
-                                 let data = &buf[..n];
-                                 pc.sctp_association.as_mut().unwrap().handle_input(data);
-                             }
-                             Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
-                                 // No data, continue
-                             }
-                             Err(_) => {
-                                 // DTLS error
-                             }
+                            Ok(n) => {
+                                let data = &buf[..n];
+                                pc.sctp_association.as_mut().unwrap().handle_input(data);
+                            }
+                            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                                // No data, continue
+                            }
+                            Err(_) => {
+                                // DTLS error
+                            }
                         }
 
                         // B. Poll SCTP Output -> Buffer it
                         if let Some(sctp) = pc.sctp_association.as_mut() {
                             // NEW: Drive timers to ensure SACKs/Heartbeats are sent even if no data arrives
                             sctp.drive();
-                            
+                            if !sctp_ready && sctp.is_connected() {
+                                sctp_ready = true;
+                                report_event(
+                                    &events,
+                                    &last_error,
+                                    &state,
+                                    ConnectionEvent::SctpReady,
+                                );
+                            }
+
                             while let Some(out_packet) = sctp.poll_output() {
                                 pending_outbound.push_back(out_packet);
                             }
                             while let Some(pkt) = sctp.recv_data() {
                                 incoming.push(pkt);
                             }
+                            while let Some(SctpEvent::StreamReset { id }) = sctp.poll_event() {
+                                incoming
+                                    .push((SCTP_STREAM_CLOSED_NOTICE, id.to_be_bytes().to_vec()));
+                            }
                         }
                     }
                 } // Mutex RELEASED here
@@ -204,7 +462,9 @@ impl P2PClient {
                 // D. Flush Pending Outbound (Acquiring lock only when needed)
                 let mut packets_sent = 0;
                 while let Some(packet) = pending_outbound.front() {
-                    let mut pc = pc_clone.lock().unwrap();
+                    let Ok(mut pc) = lock_pc(&pc_clone) else {
+                        break;
+                    };
                     match pc.dtls_write(packet) {
                         Ok(_) => {
                             pending_outbound.pop_front();
@@ -212,7 +472,7 @@ impl P2PClient {
                             // Limit burst to prevent hogging the lock too long in one loop iteration?
                             // Probably fine to keep going unless we want to be fair to the reader.
                             if packets_sent > 10 {
-                                break; 
+                                break;
                             }
                         }
                         Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
@@ -226,7 +486,8 @@ impl P2PClient {
                     }
                 }
             }
-            println!("Connection Thread: SCTP Pump Loop exited.");
+            log_info!("Connection Thread: SCTP Pump Loop exited.");
+            report_event(&events, &last_error, &state, ConnectionEvent::Closed);
         });
 
         Ok(())
@@ -234,29 +495,171 @@ impl P2PClient {
 
     pub fn has_connection(&self) -> bool {
         // Ahora comprobamos tanto ICE como DTLS
-        let pc = self.peer_connection.lock().unwrap();
-        pc.is_connected() && pc.is_dtls_connected()
+        self.pc()
+            .is_ok_and(|pc| pc.is_connected() && pc.is_dtls_connected())
+    }
+
+    /// Ya creamos nuestra oferta o respuesta.
+    pub fn has_local_description(&self) -> bool {
+        self.pc_recovered().local_description().is_some()
+    }
+
+    /// Ya tenemos la descripción del otro peer.
+    pub fn has_remote_description(&self) -> bool {
+        self.pc_recovered().remote_description().is_some()
+    }
+
+    /// En qué paso del establecimiento (ICE, DTLS) está la conexión.
+    pub fn connection_state(&self) -> ConnectionState {
+        self.state
+            .lock()
+            .map(|state| state.clone())
+            .unwrap_or_default()
+    }
+
+    /// Eventos del hilo de conexión pendientes desde la última consulta.
+    pub fn connection_events(&self) -> Vec<ConnectionEvent> {
+        match self.events_rx.lock() {
+            Ok(rx) => rx.try_iter().collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    /// Texto con el estado de la conexión para copiar en un bug report.
+    pub fn diagnostics(&self, ui_status: Option<&str>) -> String {
+        let last_error = self.last_error.lock().ok().and_then(|err| err.clone());
+        // Justo cuando algo salió mal es cuando más sirve el reporte
+        let pc = self.pc_recovered();
+        connection_report(&pc, last_error.as_deref(), ui_status)
+    }
+
+    /// Par de candidatos por el que va la media, para el overlay de stats.
+    pub fn selected_candidate_pair(&self) -> Option<CandidatePairInfo> {
+        self.pc_recovered().selected_candidate_pair_info()
+    }
+
+    /// Fingerprint del certificado que presentó el peer, ya verificado
+    /// contra su SDP. Hasta que termina el handshake DTLS no hay.
+    pub fn remote_fingerprint(&self) -> Option<String> {
+        self.pc().ok()?.remote_certificate_fingerprint()
+    }
+
+    /// Número de seguridad de la llamada, para compararlo de palabra con el
+    /// peer.
+    pub fn safety_number(&self) -> Option<String> {
+        self.pc().ok()?.safety_number()
     }
 
     pub fn is_dtls_connected(&self) -> bool {
-        self.peer_connection.lock().unwrap().is_dtls_connected()
+        self.pc().is_ok_and(|pc| pc.is_dtls_connected())
     }
 
+    /// Arranca el pipeline de video. Con `fake_camera` los frames salen de un
+    /// patrón de prueba en vez del dispositivo `camera_index`.
     pub fn start_media(
         &mut self,
         camera_index: i32,
         video: VideoParams,
+        fake_camera: bool,
+    ) -> Result<(), WorkerError> {
+        let source = fake_camera.then(|| {
+            Box::new(FakeCamera::new(video.width, video.height, video.fps)) as Box<dyn FrameSource>
+        });
+        self.start_video(camera_index, video, source, true)
+    }
+
+    /// Como `start_media`, pero los frames salen de `source` (un archivo de
+    /// video, por ejemplo) en vez de una cámara.
+    pub fn start_media_from(
+        &mut self,
+        source: Box<dyn FrameSource>,
+        video: VideoParams,
+    ) -> Result<(), WorkerError> {
+        self.start_video(0, video, Some(source), true)
+    }
+
+    /// Arranca el video solo para recibir el del peer, sin abrir la cámara.
+    /// Es lo que queda cuando `start_media` no pudo abrirla.
+    pub fn start_receive_only(&mut self, video: VideoParams) -> Result<(), WorkerError> {
+        self.start_video(0, video, None, false)
+    }
+
+    fn start_video(
+        &mut self,
+        camera_index: i32,
+        video: VideoParams,
+        source: Option<Box<dyn FrameSource>>,
+        send: bool,
     ) -> Result<(), WorkerError> {
         if self.media_worker.is_some() {
             return Ok(());
         }
+        let codecs = self.negotiated_codecs();
+        let sending = send && codecs.sends(MediaType::Video);
+        if !sending && !codecs.receives(MediaType::Video) {
+            log_debug!("Video was rejected or is inactive in the SDP, not starting WorkerMedia");
+            return Ok(());
+        }
 
-        println!("DEBUG: start_media acquiring locks...");
-        let socket = self.peer_connection.lock().unwrap().media_socket();
-        let context = self.peer_connection.lock().unwrap().srtp_context();
-        println!("DEBUG: Locks acquired. Starting WorkerMedia...");
-        let worker = WorkerMedia::start(camera_index, socket, video, context)?;
+        log_debug!("start_media acquiring locks...");
+        let (socket, rtcp_socket, context, video_ssrc, remote_video_ssrc) = {
+            let pc = self.pc().map_err(|_| WorkerError::SendError)?;
+            (
+                pc.media_socket(),
+                pc.rtcp_socket(),
+                pc.shared_srtp_context(),
+                pc.local_streams().video_ssrc,
+                // Las métricas siguen el SSRC de video que declaró el remoto
+                pc.ssrc_router().remote_ssrc(MediaType::Video),
+            )
+        };
+        // The remote b= line is an upper bound for our encoder, and our own
+        // total cap is above everything else
+        let video = video
+            .with_remote_cap(self.remote_bandwidth_kbps())
+            .with_bandwidth_cap(self.bandwidth_cap_kbps);
+        log_debug!("Locks acquired. Starting WorkerMedia...");
+        let worker = if !sending {
+            // Recvonly: la cámara ni se abre
+            WorkerMedia::start_receive_only(
+                socket,
+                rtcp_socket,
+                video,
+                codecs.video_pt,
+                video_ssrc,
+                context,
+                codecs.abs_send_time_id,
+            )?
+        } else if let Some(source) = source {
+            WorkerMedia::start_with_source(
+                source,
+                socket,
+                rtcp_socket,
+                video,
+                codecs.video_pt,
+                video_ssrc,
+                context,
+                codecs.abs_send_time_id,
+            )?
+        } else {
+            WorkerMedia::start(
+                camera_index,
+                socket,
+                rtcp_socket,
+                video,
+                codecs.video_pt,
+                video_ssrc,
+                context,
+                codecs.abs_send_time_id,
+            )?
+        };
+        worker.set_bandwidth_cap(self.bandwidth_cap_kbps)?;
         let metrics_handle = worker.metrics();
+        if let Some(ssrc) = remote_video_ssrc
+            && let Ok(mut metrics) = metrics_handle.lock()
+        {
+            metrics.expect_remote_ssrc(ssrc);
+        }
         let incoming = worker.incoming_sender();
         {
             if let Ok(mut guard) = self.media_incoming.lock() {
@@ -270,20 +673,170 @@ impl P2PClient {
         Ok(())
     }
 
-    /// Returns the socket and SRTP context for audio (to be started in UI thread).
-    pub fn audio_params(&self) -> (Arc<Mutex<PeerSocket>>, Option<SrtpContext>) {
-        let socket = self.peer_connection.lock().unwrap().media_socket();
-        let context = self.peer_connection.lock().unwrap().srtp_context();
-        (socket, context)
+    /// Cambia resolución/fps/bitrate en caliente y avisa al remoto por el
+    /// canal de control, sin rehacer ICE/DTLS. Si la cámara no tiene ese
+    /// formato se usa el más cercano; devuelve lo que quedó.
+    pub fn renegotiate_video(&mut self, video: VideoParams) -> Result<VideoParams, String> {
+        let video = video
+            .with_remote_cap(self.remote_bandwidth_kbps())
+            .with_bandwidth_cap(self.bandwidth_cap_kbps);
+        let worker = self
+            .media_worker
+            .as_mut()
+            .ok_or_else(|| "Media not started".to_string())?;
+        let video = worker.set_format(video).map_err(|err| err.to_string())?;
+        let msg = MediaControlMessage::VideoParams(video);
+        self.send_sctp_data(MEDIA_CONTROL_STREAM, msg.to_bytes())
+            .map_err(|err| err.to_string())?;
+        Ok(video)
+    }
+
+    /// Pausa o reanuda la cámara sin cortar la llamada.
+    pub fn set_video_enabled(&self, enabled: bool) {
+        if let Some(worker) = self.media_worker.as_ref() {
+            worker.set_video_enabled(enabled);
+        }
+    }
+
+    /// Avisa al remoto que pusimos la llamada en espera o la retomamos.
+    pub fn send_hold(&self, held: bool) -> Result<(), SctpError> {
+        let msg = if held {
+            MediaControlMessage::Hold
+        } else {
+            MediaControlMessage::Resume
+        };
+        self.send_sctp_data(MEDIA_CONTROL_STREAM, msg.to_bytes())
+    }
+
+    /// Aplica un mensaje de control recibido del remoto y lo devuelve, para
+    /// lo que le toca a la UI (la espera, por ejemplo).
+    pub fn handle_media_control(&self, payload: &[u8]) -> Option<MediaControlMessage> {
+        let msg = MediaControlMessage::from_bytes(payload)?;
+        match &msg {
+            // El decoder toma la resolución nueva del SPS que viene en el
+            // stream, así que de este lado no hay nada que reconfigurar
+            MediaControlMessage::VideoParams(_) => {}
+            MediaControlMessage::SsrcChanged { old, new } => {
+                let (old, new) = (*old, *new);
+                let media = self
+                    .pc()
+                    .ok()
+                    .and_then(|mut pc| pc.replace_remote_ssrc(old, new));
+                // Las métricas siguen al stream de video con su SSRC nuevo
+                if media == Some(MediaType::Video)
+                    && let Some(metrics) = self.media_metrics.as_ref()
+                    && let Ok(mut metrics) = metrics.lock()
+                {
+                    metrics.expect_remote_ssrc(new);
+                }
+            }
+            MediaControlMessage::Hold | MediaControlMessage::Resume => {}
+        }
+        Some(msg)
+    }
+
+    /// Resuelve las colisiones de SSRC que vio el listener (RFC 3550 8.2):
+    /// el stream pasa a un SSRC nuevo, sale un BYE por el viejo y el remoto
+    /// se entera por el canal de control. Devuelve el SSRC nuevo de audio si
+    /// cambió, para el `WorkerAudio` que vive en la UI.
+    pub fn resolve_ssrc_collisions(&mut self) -> Option<u32> {
+        let pending = self
+            .ssrc_collisions
+            .lock()
+            .map(|mut pending| std::mem::take(&mut *pending))
+            .unwrap_or_default();
+        let mut new_audio_ssrc = None;
+        for ssrc in pending {
+            // Varios paquetes pueden avisar del mismo choque
+            let Some((media, old, new)) = self
+                .pc()
+                .ok()
+                .and_then(|mut pc| pc.resolve_ssrc_collision(ssrc))
+            else {
+                continue;
+            };
+            log_debug!(
+                "SSRC {} de {:?} coincide con el del peer, pasamos a {}",
+                old,
+                media,
+                new
+            );
+            self.send_rtcp_bye_for(old);
+            match media {
+                MediaType::Video => {
+                    if let Some(worker) = self.media_worker.as_mut()
+                        && let Err(err) = worker.set_ssrc(new)
+                    {
+                        log_warn!("No se pudo cambiar el SSRC de video: {:?}", err);
+                    }
+                }
+                MediaType::Audio => new_audio_ssrc = Some(new),
+            }
+            let msg = MediaControlMessage::SsrcChanged { old, new };
+            if let Err(err) = self.send_sctp_data(MEDIA_CONTROL_STREAM, msg.to_bytes()) {
+                log_warn!("No se pudo avisar el cambio de SSRC: {}", err);
+            }
+        }
+        new_audio_ssrc
+    }
+
+    fn send_rtcp_bye_for(&self, ssrc: u32) {
+        let Ok(socket) = self.pc().map(|pc| pc.rtcp_socket()) else {
+            return;
+        };
+        let bytes = RtcpPacket::bye(ssrc).write_bytes();
+        if let Ok(socket) = socket.lock() {
+            let _ = socket.send(&bytes);
+        }
+    }
+
+    /// Returns the socket, negotiated Opus payload type, local audio SSRC and
+    /// SRTP context for audio (to be started in UI thread).
+    pub fn audio_params(
+        &self,
+    ) -> Result<(Arc<Mutex<PeerSocket>>, u8, u32, SharedSrtpContext), P2PClientError> {
+        let pc = self.pc()?;
+        Ok((
+            pc.media_socket(),
+            pc.negotiated_codecs().audio_pt,
+            pc.local_streams().audio_ssrc,
+            pc.shared_srtp_context(),
+        ))
     }
 
     /// Sets the audio incoming sender (called from VideoCall after WorkerAudio is created).
-    pub fn set_audio_incoming(&self, sender: SyncSender<Vec<u8>>) {
+    pub fn set_audio_incoming(&self, sender: MediaSender<Vec<u8>>) {
         if let Ok(mut guard) = self.audio_incoming.lock() {
             *guard = Some(sender);
         }
     }
 
+    /// Arranca el audio con lo que el peer entiende de `audio` y le conecta
+    /// los paquetes que llegan. Se llama desde el hilo que después lo maneja
+    /// (cpal no deja moverlo). Si la dirección negociada no incluye mandar,
+    /// el micrófono no se abre.
+    pub fn start_audio(&self, audio: AudioParams) -> Result<WorkerAudio, String> {
+        let (socket, audio_pt, audio_ssrc, context) =
+            self.audio_params().map_err(|e| e.to_string())?;
+        let audio = AudioParams {
+            send: self.negotiated_codecs().sends(MediaType::Audio),
+            ..audio
+        };
+        let worker = WorkerAudio::start(
+            socket,
+            audio_pt,
+            audio_ssrc,
+            context,
+            self.capabilities()
+                .audio_params(audio)
+                .with_bandwidth_cap(self.bandwidth_cap_kbps),
+            self.negotiated_codecs().audio_latency_id,
+        )
+        .map_err(|e| e.to_string())?;
+        self.set_audio_incoming(worker.incoming_sender());
+        Ok(worker)
+    }
+
     pub fn stop_media(&mut self) {
         self.media_worker.take();
         if let Ok(mut guard) = self.media_incoming.lock() {
@@ -295,6 +848,34 @@ impl P2PClient {
         self.media_metrics = None;
     }
 
+    /// Formatos que reportó la cámara, vacío si acepta cualquiera o no hay
+    /// video.
+    pub fn supported_video_formats(&self) -> Vec<VideoFormat> {
+        self.media_worker
+            .as_ref()
+            .map(|worker| worker.supported_formats().to_vec())
+            .unwrap_or_default()
+    }
+
+    /// Parámetros con los que está capturando y codificando el video.
+    pub fn video_params(&self) -> Option<VideoParams> {
+        self.media_worker
+            .as_ref()
+            .map(|worker| worker.video_params())
+    }
+
+    /// Si el pipeline de video está corriendo. Sin él la llamada sigue
+    /// solo con audio.
+    pub fn has_video(&self) -> bool {
+        self.media_worker.is_some()
+    }
+
+    /// Tiempo desde el último paquete de audio del peer, si llegó alguno.
+    pub fn since_last_audio(&self) -> Option<Duration> {
+        let last = self.last_audio_packet.lock().ok().and_then(|last| *last)?;
+        Some(last.elapsed())
+    }
+
     pub fn try_recv_local_frame(&self) -> Option<Mat> {
         self.media_worker
             .as_ref()
@@ -315,27 +896,76 @@ impl P2PClient {
             return Ok(());
         }
 
-        let receiver = self.peer_connection.lock().unwrap().take_receiver()?;
+        let (receiver, rtcp_receiver, srtp_context, local_addr) = {
+            let mut pc = self.pc()?;
+            (
+                pc.take_receiver()?,
+                pc.take_rtcp_receiver(),
+                pc.shared_srtp_context(),
+                pc.local_addr().ok(),
+            )
+        };
         let callback = Arc::new(on_msg);
         let thread_callback = Arc::clone(&callback);
         let media_input = Arc::clone(&self.media_incoming);
         let audio_input = Arc::clone(&self.audio_incoming);
 
-        let srtp_context = self.peer_connection.lock().unwrap().srtp_context();
+        // Sin rtcp-mux el RTCP llega por el socket del puerto siguiente
+        if let Some(rtcp_receiver) = rtcp_receiver {
+            let rtcp_callback = Arc::clone(&callback);
+            let rtcp_input = Arc::clone(&self.media_incoming);
+            let rtcp_pc = Arc::clone(&self.peer_connection);
+            thread::spawn(move || {
+                while let Ok((bytes, _src_addr)) = rtcp_receiver.recv() {
+                    if is_hangup_bye(&bytes, &rtcp_pc) {
+                        rtcp_callback("CALL_END".to_string());
+                    }
+                    if let Ok(lock) = rtcp_input.lock()
+                        && let Some(tx) = lock.as_ref()
+                    {
+                        let _ = tx.send(bytes);
+                    }
+                }
+            });
+        }
+
+        let mut srtp_cache = srtp_context.cache();
+        let unknown_ssrc_packets = Arc::clone(&self.unknown_ssrc_packets);
+        let ssrc_collisions = Arc::clone(&self.ssrc_collisions);
+        let last_audio_packet = Arc::clone(&self.last_audio_packet);
 
         let pc_for_addr_update = Arc::clone(&self.peer_connection);
         let mut last_packet_time = std::time::Instant::now();
         let mut packet_count: u64 = 0;
 
         let handle = thread::spawn(move || {
-            while let Ok((data, src_addr)) = receiver.recv() {
+            loop {
+                let (data, src_addr) = match receiver.recv_timeout(CONSENT_INTERVAL) {
+                    Ok(packet) => packet,
+                    Err(RecvTimeoutError::Timeout) => {
+                        // Sin media: se revalida el camino con STUN (RFC 7675)
+                        if last_packet_time.elapsed() >= CONSENT_SILENCE
+                            && let Ok(mut pc) = pc_for_addr_update.lock()
+                            && let Some(addr) = pc.probe_consent()
+                        {
+                            log_debug!("Consent check answered from {}, using it as remote", addr);
+                        }
+                        continue;
+                    }
+                    Err(RecvTimeoutError::Disconnected) => break,
+                };
                 packet_count += 1;
                 let now = std::time::Instant::now();
                 let gap = now.duration_since(last_packet_time).as_millis();
-                
+
                 // Log if there was a gap > 1 second (possible reconnection)
                 if gap > 1000 {
-                    println!("DEBUG: Packet received after {}ms gap from {} (total: {})", gap, src_addr, packet_count);
+                    log_debug!(
+                        "Packet received after {}ms gap from {} (total: {})",
+                        gap,
+                        src_addr,
+                        packet_count
+                    );
                 }
                 last_packet_time = now;
 
@@ -346,14 +976,15 @@ impl P2PClient {
 
                 // Intentamos descifrar el paquete. Si falla, lo tratamos como texto.
                 let mut decrypted_data = data.clone();
-                if let Some(ctx) = &srtp_context {
-                    // Verificamos longitud mínima segura para leer el header (12 bytes + CSRC list)
-                    let min_len = if data.len() >= 1 { 12 + ((data[0] & 0x0F) as usize * 4) } else { 12 };
-                    
-                    if data.len() >= min_len {
-                        let (header, header_size) = RtpHeader::read_bytes(&data);
+                if let Some(ctx) = srtp_cache.current() {
+                    // El parser rechaza lo que no alcanza para el header fijo y la lista de CSRC
+                    if let Ok((header, header_size)) = RtpHeader::read_bytes(&data) {
                         let encrypted_payload = &data[header_size..];
-                        if let Some(unprotected) = ctx.unprotect(header.get_sequence_number(), header.get_timestamp(), encrypted_payload) {
+                        if let Some(unprotected) = ctx.unprotect(
+                            header.get_sequence_number(),
+                            header.get_timestamp(),
+                            encrypted_payload,
+                        ) {
                             let mut new_bytes = Vec::with_capacity(header_size + unprotected.len());
                             new_bytes.extend_from_slice(&data[..header_size]);
                             new_bytes.extend_from_slice(&unprotected);
@@ -367,44 +998,54 @@ impl P2PClient {
                     Ok(message) => thread_callback(message),
                     Err(_err) => {
                         //If it's not UTF8, it's a media packet (RTP/RTCP)
-                        
-                        let bytes = decrypted_data;
 
-                        let is_rtcp_bye = bytes.len() >= 4
-                            && RtcpPacket::read_bytes(&bytes)
-                                .is_ok_and(|packet| matches!(packet.payload, RtcpPayload::Bye(_)));
+                        let bytes = decrypted_data;
 
-                        if is_rtcp_bye {
+                        if is_hangup_bye(&bytes, &pc_for_addr_update) {
                             thread_callback("CALL_END".to_string());
                         }
-                        
-                        // Route RTP packets by SSRC: 1000 = video, 2000 = audio
-                        if bytes.len() >= 12 {
-                            let (header, _) = RtpHeader::read_bytes(&bytes);
-                            let ssrc = header.get_ssrc();
-                            
-                            if ssrc == 2000 {
-                                 // Audio packet
-                                if let Ok(lock) = audio_input.lock() {
-                                    if let Some(tx) = lock.as_ref() {
-                                        let _ = tx.send(bytes);
-                                    }
-                                }
-                            } else {
-                                // Video packet (or default)
-                                if let Ok(lock) = media_input.lock()
-                                    && let Some(tx) = lock.as_ref()
-                                {
-                                    let _ = tx.send(bytes);
+
+                        // Route RTP packets by the SSRCs declared in the remote SDP
+                        // (the table changes when the peer announces a new SSRC)
+                        let (route, collided) = match pc_for_addr_update.lock() {
+                            Ok(pc) => {
+                                let route = pc.ssrc_router().classify(&bytes);
+                                let collided = route
+                                    .ssrc()
+                                    .filter(|ssrc| pc.local_streams().media_of(*ssrc).is_some());
+                                (route, collided)
+                            }
+                            Err(_) => (PacketRoute::Malformed, None),
+                        };
+                        // Nuestro SSRC desde otra dirección: el peer eligió el mismo
+                        if let Some(ssrc) = collided
+                            && local_addr != Some(src_addr)
+                            && let Ok(mut pending) = ssrc_collisions.lock()
+                            && !pending.contains(&ssrc)
+                        {
+                            pending.push(ssrc);
+                        }
+                        let input = match route {
+                            PacketRoute::Media(MediaType::Audio, _) => {
+                                if let Ok(mut last) = last_audio_packet.lock() {
+                                    *last = Some(Instant::now());
                                 }
+                                &audio_input
                             }
-                        } else {
-                            // Fallback: short packets go to video
-                            if let Ok(lock) = media_input.lock()
-                                && let Some(tx) = lock.as_ref()
-                            {
-                                let _ = tx.send(bytes);
+                            PacketRoute::Media(MediaType::Video, _) | PacketRoute::Rtcp => {
+                                &media_input
                             }
+                            // Sin SSRC declarado no se adivina el medio: se cuenta y se descarta
+                            PacketRoute::Unknown(_) => {
+                                unknown_ssrc_packets.fetch_add(1, Ordering::Relaxed);
+                                continue;
+                            }
+                            PacketRoute::Malformed => continue,
+                        };
+                        if let Ok(lock) = input.lock()
+                            && let Some(tx) = lock.as_ref()
+                        {
+                            let _ = tx.send(bytes);
                         }
                     }
                 }
@@ -416,7 +1057,7 @@ impl P2PClient {
     }
 
     pub fn send_msg(&self, msg: &str) -> Result<(), PeerConnectionError> {
-        self.peer_connection.lock().unwrap().send(msg.as_bytes())
+        self.pc()?.send(msg.as_bytes())
     }
 
     pub fn send_rtcp_bye(&self) -> Result<(), WorkerError> {
@@ -426,55 +1067,271 @@ impl P2PClient {
             .send_rtcp_bye()
     }
 
+    /// Métricas de la llamada; `None` sin media o con la conexión
+    /// envenenada, que ya no se puede seguir usando.
     pub fn metrics_snapshot(&self) -> Option<CallMetricsSnapshot> {
+        if self.is_poisoned() {
+            return None;
+        }
+        let unknown_ssrc_packets = self.unknown_ssrc_packets.load(Ordering::Relaxed);
+        let drops = self
+            .media_worker
+            .as_ref()
+            .map(|worker| worker.drops())
+            .unwrap_or_default();
         self.media_metrics
             .as_ref()
             .and_then(|metrics| metrics.lock().ok().map(|m| m.snapshot()))
+            .map(|snapshot| CallMetricsSnapshot {
+                unknown_ssrc_packets,
+                drops,
+                ..snapshot
+            })
     }
-    
-    pub fn send_sctp_data(&self, stream: u16, payload: Vec<u8>) -> Result<(), String> {
+
+    /// Manda `payload` por el stream SCTP. Con `SctpError::BufferFull` hay
+    /// que reintentar más tarde; el resto de los errores no se arreglan solos.
+    pub fn send_sctp_data(&self, stream: u16, payload: Vec<u8>) -> Result<(), SctpError> {
         // Step 1: Push data to SCTP engine
         let mut outbound_queue = VecDeque::new();
         {
-            let mut pc = self.peer_connection.lock().unwrap();
+            let mut pc = self.pc()?;
             if let Some(sctp) = &mut pc.sctp_association {
                 sctp.send_data(stream, payload)?; // This queues inside SCTP struct
-                
+
                 // Drain immediate output from SCTP to our local queue
                 while let Some(out) = sctp.poll_output() {
                     outbound_queue.push_back(out);
                 }
             } else {
-                return Err("SCTP not initialized".to_string());
+                return Err(SctpError::NotEstablished);
             }
         } // Lock released
 
-        // Step 2: Send chunks via DTLS with retry, RELEASING LOCK on block
+        self.flush_sctp_output(outbound_queue)
+    }
+
+    /// Un stream SCTP como canal de datos: se manda por él y se cierra con
+    /// un reset, que el peer ve como fin de stream.
+    pub fn data_channel(&self, stream: u16) -> DataChannel {
+        DataChannel {
+            client: self.clone(),
+            stream,
+        }
+    }
+
+    /// Resetea el stream en los dos sentidos; el peer recibe
+    /// `SCTP_STREAM_CLOSED_NOTICE`.
+    fn reset_sctp_stream(&self, stream: u16) -> Result<(), SctpError> {
+        let mut outbound_queue = VecDeque::new();
+        {
+            let mut pc = self.pc()?;
+            let Some(sctp) = pc.sctp_association.as_mut() else {
+                return Err(SctpError::NotEstablished);
+            };
+            sctp.reset_stream(stream)?;
+            while let Some(out) = sctp.poll_output() {
+                outbound_queue.push_back(out);
+            }
+        }
+        self.flush_sctp_output(outbound_queue)
+    }
+
+    fn reopen_sctp_stream(&self, stream: u16) {
+        if let Ok(mut pc) = self.pc()
+            && let Some(sctp) = pc.sctp_association.as_mut()
+        {
+            sctp.reopen_stream(stream);
+        }
+    }
+
+    /// La asociación SCTP (canal de datos) ya está lista para mandar.
+    pub fn is_sctp_connected(&self) -> bool {
+        self.pc().is_ok_and(|pc| {
+            pc.sctp_association
+                .as_ref()
+                .is_some_and(|sctp| sctp.is_connected())
+        })
+    }
+
+    /// Contadores de flujo de un stream: lo encolado, lo confirmado y las
+    /// retransmisiones. `None` sin asociación SCTP.
+    pub fn sctp_stream_stats(&self, stream: u16) -> Option<SctpStreamStats> {
+        let mut pc = self.pc().ok()?;
+        pc.sctp_association
+            .as_mut()
+            .map(|sctp| sctp.stream_stats(stream))
+    }
+
+    /// Con la conexión envenenada no queda nada abierto.
+    fn is_sctp_stream_closed(&self, stream: u16) -> bool {
+        self.pc().map_or(true, |pc| {
+            pc.sctp_association
+                .as_ref()
+                .is_some_and(|sctp| sctp.is_stream_closed(stream))
+        })
+    }
+
+    /// Send chunks via DTLS with retry, RELEASING LOCK on block
+    fn flush_sctp_output(&self, mut outbound_queue: VecDeque<Vec<u8>>) -> Result<(), SctpError> {
         while let Some(packet) = outbound_queue.pop_front() {
-             let mut backoff = 1;
-             loop {
-                 let mut pc = self.peer_connection.lock().unwrap();
-                 match pc.dtls_write(&packet) {
-                     Ok(_) => break, // Success, move to next packet
-                     Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
-                         // Backend full. Drop lock and wait.
-                         drop(pc);
-                         thread::sleep(Duration::from_millis(backoff));
-                         backoff = (backoff * 2).min(50);
-                     }
-                     Err(e) => {
-                         eprintln!("DTLS Write Error: {}", e);
-                         return Err(e.to_string());
-                     }
-                 }
-             }
+            let mut backoff = 1;
+            loop {
+                let mut pc = self.pc()?;
+                match pc.dtls_write(&packet) {
+                    Ok(_) => break, // Success, move to next packet
+                    Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                        // Backend full. Drop lock and wait.
+                        drop(pc);
+                        thread::sleep(Duration::from_millis(backoff));
+                        backoff = (backoff * 2).min(50);
+                    }
+                    Err(e) => {
+                        log_warn!("DTLS Write Error: {}", e);
+                        return Err(SctpError::Io(e.to_string()));
+                    }
+                }
+            }
         }
         Ok(())
     }
-    
+
     pub fn set_sctp_incoming(&self, sender: SyncSender<(u16, Vec<u8>)>) {
-          if let Ok(mut guard) = self.sctp_incoming.lock() {
-               *guard = Some(sender);
-          }
+        if let Ok(mut guard) = self.sctp_incoming.lock() {
+            *guard = Some(sender);
+        }
+    }
+}
+
+/// Toma la conexión; envenenada, un panic en otro hilo pudo dejarla a medio
+/// actualizar y no se sigue usando.
+fn lock_pc(
+    pc: &Mutex<RtcPeerConnection>,
+) -> Result<MutexGuard<'_, RtcPeerConnection>, P2PClientError> {
+    pc.lock().map_err(|_| P2PClientError::Poisoned)
+}
+
+/// Publica un evento de conexión y, si es un fallo, lo guarda como último error.
+fn report_event(
+    events: &Sender<ConnectionEvent>,
+    last_error: &Mutex<Option<String>>,
+    state: &Mutex<ConnectionState>,
+    event: ConnectionEvent,
+) {
+    if let Ok(mut state) = state.lock() {
+        *state = state.on_event(&event);
+    }
+    if matches!(
+        event,
+        ConnectionEvent::IceFailed(_) | ConnectionEvent::DtlsFailed(_)
+    ) && let Ok(mut last) = last_error.lock()
+    {
+        *last = Some(event.to_string());
+    }
+    let _ = events.send(event);
+}
+
+/// Un BYE corta la llamada salvo que sea por un SSRC que también es nuestro:
+/// ese lo manda el peer al resolver una colisión, no al colgar.
+fn is_hangup_bye(bytes: &[u8], pc: &Mutex<RtcPeerConnection>) -> bool {
+    if bytes.len() < 4 {
+        return false;
+    }
+    let Ok(RtcpPacket {
+        payload: RtcpPayload::Bye(bye),
+        ..
+    }) = RtcpPacket::read_bytes(bytes)
+    else {
+        return false;
+    };
+    match pc.lock() {
+        Ok(pc) => !pc.owns_ssrc(bye.get_ssrc()),
+        Err(_) => true,
+    }
+}
+
+/// Handle de un stream SCTP, obtenido con [`P2PClient::data_channel`].
+#[derive(Clone)]
+pub struct DataChannel {
+    client: P2PClient,
+    stream: u16,
+}
+
+impl DataChannel {
+    pub fn stream(&self) -> u16 {
+        self.stream
+    }
+
+    pub fn send(&self, payload: Vec<u8>) -> Result<(), SctpError> {
+        self.client.send_sctp_data(self.stream, payload)
+    }
+
+    /// Cierre ordenado: el peer recibe el reset después de todo lo mandado,
+    /// así que le sirve de fin de stream. No hace nada si ya estaba cerrado.
+    pub fn close(&self) -> Result<(), SctpError> {
+        self.client.reset_sctp_stream(self.stream)
+    }
+
+    /// Permite volver a usar el canal después de un cierre.
+    pub fn reopen(&self) {
+        self.client.reopen_sctp_stream(self.stream);
+    }
+
+    /// Cerrado por nosotros o por el peer.
+    pub fn is_closed(&self) -> bool {
+        self.client.is_sctp_stream_closed(self.stream)
+    }
+
+    /// Cuánto está encolado y cuánto confirmó el peer desde que se abrió.
+    pub fn stats(&self) -> Option<SctpStreamStats> {
+        self.client.sctp_stream_stats(self.stream)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::webrtc_service::apply_connection_events;
+
+    #[test]
+    fn test_poisoned_connection_fails_without_panicking() {
+        let client = P2PClient::new(PeerConnectionRole::Controlling, None, None).unwrap();
+        let pc = Arc::clone(&client.peer_connection);
+        let _ = thread::spawn(move || {
+            let _guard = pc.lock().unwrap();
+            panic!("panic con la conexión tomada");
+        })
+        .join();
+
+        assert!(client.is_poisoned());
+        assert!(client.metrics_snapshot().is_none());
+        assert!(client.local_addr().is_err());
+        assert!(client.send_msg("hola").is_err());
+        assert!(matches!(
+            client.send_sctp_data(0, b"hola".to_vec()),
+            Err(SctpError::Other(_))
+        ));
+        assert!(client.audio_params().is_err());
+        assert!(!client.has_connection());
+        // Lo que solo lee el estado sigue respondiendo
+        assert_eq!(client.role(), PeerConnectionRole::Controlling);
+        assert!(!client.diagnostics(None).is_empty());
+
+        let mut status = None;
+        assert_eq!(
+            apply_connection_events(&client, &mut status),
+            Some(P2PClientError::Poisoned.to_string())
+        );
+    }
+
+    #[test]
+    fn test_data_channel_before_the_association_is_not_established() {
+        let client = P2PClient::new(PeerConnectionRole::Controlling, None, None).unwrap();
+        let channel = client.data_channel(2);
+        assert_eq!(
+            channel.send(b"hola".to_vec()),
+            Err(SctpError::NotEstablished)
+        );
+        assert_eq!(channel.close(), Err(SctpError::NotEstablished));
     }
 }
@@ -0,0 +1,38 @@
+//! Errores de `P2PClient` que no vienen de la conexión en sí.
+
+use std::fmt;
+use std::io;
+
+use room_rtc::rtc::rtc_peer_connection::PeerConnectionError;
+use room_rtc::rtc::rtc_sctp::SctpError;
+
+#[derive(Debug)]
+pub enum P2PClientError {
+    /// Un hilo hizo panic con la conexión tomada: su estado puede haber
+    /// quedado a medio actualizar y la llamada no se puede seguir usando.
+    Poisoned,
+}
+
+impl fmt::Display for P2PClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            P2PClientError::Poisoned => write!(f, "The call stopped after an internal error"),
+        }
+    }
+}
+
+impl std::error::Error for P2PClientError {}
+
+// Para los métodos que ya devolvían errores de la conexión
+impl From<P2PClientError> for PeerConnectionError {
+    fn from(err: P2PClientError) -> Self {
+        PeerConnectionError::Io(io::Error::other(err.to_string()))
+    }
+}
+
+// Para el canal de datos, que se maneja por variante (BufferFull, etc.)
+impl From<P2PClientError> for SctpError {
+    fn from(err: P2PClientError) -> Self {
+        SctpError::Other(err.to_string())
+    }
+}
@@ -0,0 +1,168 @@
+//! Llamadas entrantes que suenan a la vez mientras el usuario decide cuál
+//! atender. Las que no entran se rechazan como ocupado.
+
+use crate::client::capabilities::PeerCapabilities;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Cuántas llamadas suenan a la vez si no se configura otra cosa.
+pub const DEFAULT_MAX_PENDING_CALLS: usize = 3;
+
+/// Una llamada sonando, con lo que hace falta para atenderla.
+#[derive(Debug, Clone)]
+pub struct PendingCall {
+    pub from: String,
+    pub sdp: String,
+    pub caps: PeerCapabilities,
+    since: Instant,
+}
+
+impl PendingCall {
+    /// Cuánto le queda sonar antes de darla por perdida.
+    pub fn remaining(&self, ring_timeout: Duration) -> Duration {
+        ring_timeout.saturating_sub(self.since.elapsed())
+    }
+}
+
+/// Las llamadas sonando, de la más vieja a la más nueva.
+#[derive(Debug)]
+pub struct PendingCalls {
+    calls: VecDeque<PendingCall>,
+    max: usize,
+}
+
+impl Default for PendingCalls {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_PENDING_CALLS)
+    }
+}
+
+impl PendingCalls {
+    pub fn new(max: usize) -> Self {
+        Self {
+            calls: VecDeque::new(),
+            max: max.max(1),
+        }
+    }
+
+    /// Hace sonar la llamada de `from`. Devuelve `false` si ya suenan todas
+    /// las que entran: hay que rechazarla como ocupado. Una oferta nueva de
+    /// quien ya estaba sonando reemplaza la anterior sin perder su lugar.
+    pub fn push(&mut self, from: String, sdp: String, caps: PeerCapabilities) -> bool {
+        if let Some(call) = self.calls.iter_mut().find(|call| call.from == from) {
+            call.sdp = sdp;
+            call.caps = caps;
+            return true;
+        }
+        if self.calls.len() >= self.max {
+            return false;
+        }
+        self.calls.push_back(PendingCall {
+            from,
+            sdp,
+            caps,
+            since: Instant::now(),
+        });
+        true
+    }
+
+    pub fn get(&self, from: &str) -> Option<&PendingCall> {
+        self.calls.iter().find(|call| call.from == from)
+    }
+
+    /// Saca la llamada de `from`, si seguía sonando.
+    pub fn remove(&mut self, from: &str) -> Option<PendingCall> {
+        let index = self.calls.iter().position(|call| call.from == from)?;
+        self.calls.remove(index)
+    }
+
+    /// Saca todas, p.ej. para rechazarlas al atender una.
+    pub fn take_all(&mut self) -> Vec<PendingCall> {
+        self.calls.drain(..).collect()
+    }
+
+    /// Saca la más vieja si sonó `ring_timeout` sin que nadie la atendiera.
+    /// Las demás llegaron después, así que vencen después.
+    pub fn take_expired(&mut self, ring_timeout: Duration) -> Option<PendingCall> {
+        let oldest = self.calls.front()?;
+        if oldest.since.elapsed() < ring_timeout {
+            return None;
+        }
+        self.calls.pop_front()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &PendingCall> {
+        self.calls.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.calls.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.calls.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ring(calls: &mut PendingCalls, from: &str) -> bool {
+        calls.push(
+            from.to_string(),
+            format!("sdp de {}", from),
+            PeerCapabilities::legacy(),
+        )
+    }
+
+    fn callers(calls: &PendingCalls) -> Vec<&str> {
+        calls.iter().map(|call| call.from.as_str()).collect()
+    }
+
+    #[test]
+    fn test_calls_queue_up_to_the_limit() {
+        let mut calls = PendingCalls::new(2);
+        assert!(ring(&mut calls, "alice"));
+        assert!(ring(&mut calls, "bob"));
+        assert!(!ring(&mut calls, "carol"));
+        assert_eq!(callers(&calls), ["alice", "bob"]);
+
+        // Al salir una, hay lugar otra vez
+        assert_eq!(
+            calls.remove("alice").map(|call| call.from),
+            Some("alice".into())
+        );
+        assert!(calls.remove("alice").is_none());
+        assert!(ring(&mut calls, "carol"));
+        assert_eq!(callers(&calls), ["bob", "carol"]);
+    }
+
+    #[test]
+    fn test_repeated_offer_replaces_the_previous_one() {
+        let mut calls = PendingCalls::new(1);
+        assert!(ring(&mut calls, "alice"));
+        assert!(calls.push(
+            "alice".to_string(),
+            "otra oferta".to_string(),
+            PeerCapabilities::legacy()
+        ));
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls.get("alice").unwrap().sdp, "otra oferta");
+    }
+
+    #[test]
+    fn test_oldest_call_expires_first() {
+        let mut calls = PendingCalls::new(3);
+        ring(&mut calls, "alice");
+        ring(&mut calls, "bob");
+        assert!(calls.take_expired(Duration::from_secs(30)).is_none());
+        let expired = calls.take_expired(Duration::ZERO).unwrap();
+        assert_eq!(expired.from, "alice");
+        assert_eq!(callers(&calls), ["bob"]);
+
+        assert_eq!(calls.take_all().len(), 1);
+        assert!(calls.is_empty());
+        assert!(calls.take_expired(Duration::ZERO).is_none());
+    }
+}
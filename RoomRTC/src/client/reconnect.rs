@@ -0,0 +1,100 @@
+//! Qué hacer cuando deja de llegar media en plena llamada. Un corte corto
+//! (cambio de WiFi, NAT que reasigna el puerto) se recupera con un reinicio
+//! de ICE sobre la misma llamada; solo si no vuelve nada en
+//! `RECONNECT_WINDOW` se corta.
+
+use std::time::Duration;
+
+/// Silencio a partir del cual se da la conexión por perdida y se reinicia ICE.
+pub const RECONNECT_AFTER: Duration = Duration::from_secs(5);
+
+/// Espera entre un reinicio de ICE y el siguiente mientras siga sin llegar nada.
+pub const RESTART_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Silencio tras el cual se abandona la llamada.
+pub const RECONNECT_WINDOW: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReconnectStep {
+    /// Nada que hacer por ahora.
+    Wait,
+    /// Pedir un reinicio de ICE al peer.
+    RestartIce,
+    /// Volvió la media después de al menos un reinicio.
+    Recovered,
+    /// Se agotó la ventana, hay que cortar.
+    GiveUp,
+}
+
+#[derive(Debug, Default)]
+pub struct Reconnect {
+    restarts: u32,
+}
+
+impl Reconnect {
+    /// Decide el próximo paso según cuánto hace que no llega media.
+    pub fn poll(&mut self, silence: Duration) -> ReconnectStep {
+        if silence < RECONNECT_AFTER {
+            if self.restarts > 0 {
+                self.restarts = 0;
+                return ReconnectStep::Recovered;
+            }
+            return ReconnectStep::Wait;
+        }
+        if silence >= RECONNECT_WINDOW {
+            return ReconnectStep::GiveUp;
+        }
+        if silence >= RECONNECT_AFTER + RESTART_INTERVAL * self.restarts {
+            self.restarts += 1;
+            return ReconnectStep::RestartIce;
+        }
+        ReconnectStep::Wait
+    }
+
+    /// Hay al menos un reinicio de ICE en curso.
+    pub fn is_reconnecting(&self) -> bool {
+        self.restarts > 0
+    }
+
+    /// Reinicios pedidos en este corte.
+    pub fn attempts(&self) -> u32 {
+        self.restarts
+    }
+
+    pub fn reset(&mut self) {
+        self.restarts = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secs(s: u64) -> Duration {
+        Duration::from_secs(s)
+    }
+
+    #[test]
+    fn test_restarts_are_spaced_until_the_window_runs_out() {
+        let mut reconnect = Reconnect::default();
+        assert_eq!(reconnect.poll(secs(1)), ReconnectStep::Wait);
+        assert!(!reconnect.is_reconnecting());
+
+        assert_eq!(reconnect.poll(secs(5)), ReconnectStep::RestartIce);
+        assert_eq!(reconnect.poll(secs(6)), ReconnectStep::Wait);
+        assert_eq!(reconnect.poll(secs(14)), ReconnectStep::Wait);
+        assert_eq!(reconnect.poll(secs(15)), ReconnectStep::RestartIce);
+        assert_eq!(reconnect.attempts(), 2);
+        assert_eq!(reconnect.poll(secs(60)), ReconnectStep::GiveUp);
+    }
+
+    #[test]
+    fn test_media_back_ends_the_reconnection() {
+        let mut reconnect = Reconnect::default();
+        assert_eq!(reconnect.poll(secs(7)), ReconnectStep::RestartIce);
+        assert!(reconnect.is_reconnecting());
+        assert_eq!(reconnect.poll(secs(0)), ReconnectStep::Recovered);
+        assert_eq!(reconnect.poll(secs(0)), ReconnectStep::Wait);
+        assert_eq!(reconnect.attempts(), 0);
+    }
+}
@@ -0,0 +1,491 @@
+//! Preferencias de cada usuario que se pueden cambiar desde la pantalla de
+//! Settings: cámara (o patrón de prueba), dispositivos de audio, video, estado, tema
+//! y servidor.
+//!
+//! Se guardan en `<settings_dir>/<usuario>.settings.conf`, aparte de
+//! `client.conf`, con el mismo formato `clave=valor`. Las claves que faltan
+//! (archivo viejo o editado a mano) toman el valor de `AppConfig`.
+//!
+//! Con "Recordarme" también se guarda el token de sesión que da el servidor
+//! (nunca la contraseña), para volver a entrar solo al abrir el cliente.
+//! También van los fingerprints DTLS fijados por contacto, uno por línea
+//! (`pin.<contacto>=AA:BB:...`).
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use room_rtc::worker_thread::worker_media::VideoParams;
+
+use crate::config::{AppConfig, parse_kv};
+
+const SETTINGS_SUFFIX: &str = ".settings.conf";
+const PIN_PREFIX: &str = "pin.";
+
+/// Estado con el que el usuario quiere aparecer cuando no está en llamada.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PreferredStatus {
+    #[default]
+    Available,
+    /// Las llamadas entrantes se rechazan solas.
+    DoNotDisturb,
+}
+
+impl PreferredStatus {
+    pub const ALL: [PreferredStatus; 2] =
+        [PreferredStatus::Available, PreferredStatus::DoNotDisturb];
+
+    fn as_conf(&self) -> &'static str {
+        match self {
+            PreferredStatus::Available => "available",
+            PreferredStatus::DoNotDisturb => "do_not_disturb",
+        }
+    }
+
+    fn from_conf(value: &str) -> Option<Self> {
+        match value {
+            "available" => Some(PreferredStatus::Available),
+            "do_not_disturb" => Some(PreferredStatus::DoNotDisturb),
+            _ => None,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            PreferredStatus::Available => "Available",
+            PreferredStatus::DoNotDisturb => "Do not disturb",
+        }
+    }
+}
+
+/// Colores de la interfaz.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Theme {
+    #[default]
+    Dark,
+    Light,
+}
+
+impl Theme {
+    pub const ALL: [Theme; 2] = [Theme::Dark, Theme::Light];
+
+    fn as_conf(&self) -> &'static str {
+        match self {
+            Theme::Dark => "dark",
+            Theme::Light => "light",
+        }
+    }
+
+    fn from_conf(value: &str) -> Option<Self> {
+        match value {
+            "dark" => Some(Theme::Dark),
+            "light" => Some(Theme::Light),
+            _ => None,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Theme::Dark => "Dark",
+            Theme::Light => "Light",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct UserSettings {
+    pub camera_index: i32,
+    /// Patrón de prueba en vez de la cámara; arranca con `fake_camera` de
+    /// `client.conf`.
+    pub test_pattern: bool,
+    /// Micrófono por nombre; `None` usa el del sistema.
+    pub input_device: Option<String>,
+    /// Parlantes por nombre; `None` usa los del sistema.
+    pub output_device: Option<String>,
+    pub video: VideoParams,
+    /// Tope total de envío (audio + video) en kbps; `None` sin tope.
+    pub max_bitrate_kbps: Option<u64>,
+    pub preferred_status: PreferredStatus,
+    /// Notificaciones del sistema con la ventana en segundo plano.
+    pub notifications: bool,
+    pub theme: Theme,
+    pub server_addr: String,
+    /// Token para `LOGIN_TOKEN` en `server_addr`; solo con "Recordarme".
+    pub session_token: Option<String>,
+    /// Fingerprint DTLS que el usuario verificó para cada contacto.
+    pub pinned_keys: BTreeMap<String, String>,
+}
+
+impl UserSettings {
+    /// Valores iniciales de un usuario que nunca guardó nada.
+    pub fn from_config(config: &AppConfig) -> Self {
+        Self {
+            camera_index: 0,
+            test_pattern: config.fake_camera,
+            input_device: None,
+            output_device: None,
+            video: config.video_params(),
+            max_bitrate_kbps: config.max_bitrate_kbps,
+            preferred_status: PreferredStatus::Available,
+            notifications: true,
+            theme: Theme::Dark,
+            server_addr: config.server_addr.clone(),
+            session_token: None,
+            pinned_keys: BTreeMap::new(),
+        }
+    }
+
+    /// Lee las preferencias guardadas en `path`. Si el archivo no existe
+    /// devuelve `defaults`; si le faltan claves o tienen valores inválidos,
+    /// esas claves quedan con el valor de `defaults`.
+    pub fn load(path: &Path, defaults: &UserSettings) -> io::Result<Self> {
+        if !path.exists() {
+            return Ok(defaults.clone());
+        }
+        let content = fs::read_to_string(path)?;
+        Ok(Self::parse(&content, defaults))
+    }
+
+    fn parse(content: &str, defaults: &UserSettings) -> Self {
+        let entries = parse_kv(content);
+        let mut settings = defaults.clone();
+
+        if let Some(index) = entries.get("camera_index").and_then(|v| v.parse().ok()) {
+            settings.camera_index = index;
+        }
+        if let Some(test_pattern) = entries.get("test_pattern").and_then(|v| v.parse().ok()) {
+            settings.test_pattern = test_pattern;
+        }
+        if let Some(device) = entries.get("input_device") {
+            settings.input_device = non_empty(device);
+        }
+        if let Some(device) = entries.get("output_device") {
+            settings.output_device = non_empty(device);
+        }
+        if let Some(w) = entries.get("video_width").and_then(|v| v.parse().ok()) {
+            settings.video.width = w;
+        }
+        if let Some(h) = entries.get("video_height").and_then(|v| v.parse().ok()) {
+            settings.video.height = h;
+        }
+        if let Some(fps) = entries.get("video_fps").and_then(|v| v.parse().ok()) {
+            settings.video.fps = fps;
+        }
+        if let Some(kbps) = entries.get("video_max_bitrate_kbps") {
+            // Vacío significa "sin tope", que es distinto de no tener la clave
+            if kbps.is_empty() {
+                settings.video.max_bitrate_kbps = None;
+            } else if let Ok(kbps) = kbps.parse() {
+                settings.video.max_bitrate_kbps = Some(kbps);
+            }
+        }
+        if let Some(kbps) = entries.get("max_bitrate_kbps") {
+            if kbps.is_empty() {
+                settings.max_bitrate_kbps = None;
+            } else if let Ok(kbps) = kbps.parse() {
+                settings.max_bitrate_kbps = Some(kbps);
+            }
+        }
+        if let Some(status) = entries
+            .get("preferred_status")
+            .and_then(|v| PreferredStatus::from_conf(v))
+        {
+            settings.preferred_status = status;
+        }
+        if let Some(notifications) = entries.get("notifications").and_then(|v| v.parse().ok()) {
+            settings.notifications = notifications;
+        }
+        if let Some(theme) = entries.get("theme").and_then(|v| Theme::from_conf(v)) {
+            settings.theme = theme;
+        }
+        if let Some(addr) = entries.get("server_addr").filter(|v| !v.is_empty()) {
+            settings.server_addr = addr.clone();
+        }
+        if let Some(token) = entries.get("session_token") {
+            settings.session_token = non_empty(token);
+        }
+        for (key, fingerprint) in &entries {
+            if let Some(peer) = key.strip_prefix(PIN_PREFIX)
+                && !peer.is_empty()
+                && !fingerprint.is_empty()
+            {
+                settings
+                    .pinned_keys
+                    .insert(peer.to_string(), fingerprint.clone());
+            }
+        }
+        settings
+    }
+
+    fn to_conf(&self) -> String {
+        let optional = |kbps: Option<u64>| kbps.map(|kbps| kbps.to_string()).unwrap_or_default();
+        let mut conf = format!(
+            "# Preferencias de RoomRTC, se reescribe desde la pantalla de Settings\n\
+             camera_index={}\n\
+             test_pattern={}\n\
+             input_device={}\n\
+             output_device={}\n\
+             video_width={}\n\
+             video_height={}\n\
+             video_fps={}\n\
+             video_max_bitrate_kbps={}\n\
+             max_bitrate_kbps={}\n\
+             preferred_status={}\n\
+             notifications={}\n\
+             theme={}\n\
+             server_addr={}\n\
+             session_token={}\n",
+            self.camera_index,
+            self.test_pattern,
+            self.input_device.as_deref().unwrap_or_default(),
+            self.output_device.as_deref().unwrap_or_default(),
+            self.video.width,
+            self.video.height,
+            self.video.fps,
+            optional(self.video.max_bitrate_kbps),
+            optional(self.max_bitrate_kbps),
+            self.preferred_status.as_conf(),
+            self.notifications,
+            self.theme.as_conf(),
+            self.server_addr,
+            self.session_token.as_deref().unwrap_or_default(),
+        );
+        for (peer, fingerprint) in &self.pinned_keys {
+            conf.push_str(&format!("{}{}={}\n", PIN_PREFIX, peer, fingerprint));
+        }
+        conf
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, self.to_conf())
+    }
+
+    /// Toma la cámara, el micrófono y los parlantes elegidos en `other`,
+    /// como los de la prueba de dispositivos. Devuelve si cambió algo.
+    pub fn copy_devices_from(&mut self, other: &UserSettings) -> bool {
+        let changed = self.camera_index != other.camera_index
+            || self.test_pattern != other.test_pattern
+            || self.input_device != other.input_device
+            || self.output_device != other.output_device;
+        self.camera_index = other.camera_index;
+        self.test_pattern = other.test_pattern;
+        self.input_device = other.input_device.clone();
+        self.output_device = other.output_device.clone();
+        changed
+    }
+}
+
+/// Archivo de preferencias de `username` dentro de `dir`.
+pub fn settings_path(dir: &str, username: &str) -> PathBuf {
+    user_file(dir, username, SETTINGS_SUFFIX)
+}
+
+/// Archivo `<username><suffix>` dentro de `dir`.
+pub(crate) fn user_file(dir: &str, username: &str, suffix: &str) -> PathBuf {
+    // El servidor ya limita los usernames, pero el nombre termina en una ruta
+    let safe: String = username
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    Path::new(dir).join(format!("{}{}", safe, suffix))
+}
+
+/// Usuario que pidió que lo recuerden y sus preferencias, para entrar solo
+/// al arrancar. Si hay varios, el que guardó por última vez.
+pub fn remembered_session(dir: &str, defaults: &UserSettings) -> Option<(String, UserSettings)> {
+    let mut newest = None;
+    for entry in fs::read_dir(dir).ok()?.flatten() {
+        let file_name = entry.file_name();
+        // Los usernames válidos quedan iguales en el nombre del archivo
+        let Some(username) = file_name
+            .to_str()
+            .and_then(|name| name.strip_suffix(SETTINGS_SUFFIX))
+        else {
+            continue;
+        };
+        let Ok(modified) = entry.metadata().and_then(|meta| meta.modified()) else {
+            continue;
+        };
+        if newest
+            .as_ref()
+            .is_some_and(|(_, _, time)| *time >= modified)
+        {
+            continue;
+        }
+        let Ok(settings) = UserSettings::load(&entry.path(), defaults) else {
+            continue;
+        };
+        if settings.session_token.is_some() {
+            newest = Some((username.to_string(), settings, modified));
+        }
+    }
+    newest.map(|(username, settings, _)| (username, settings))
+}
+
+fn non_empty(value: &str) -> Option<String> {
+    if value.is_empty() {
+        None
+    } else {
+        Some(value.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn defaults() -> UserSettings {
+        UserSettings::from_config(&AppConfig::default())
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "roomrtc-settings-{}-{}.conf",
+            name,
+            std::process::id()
+        ))
+    }
+
+    #[test]
+    fn test_roundtrip_keeps_every_field() {
+        let settings = UserSettings {
+            camera_index: 2,
+            test_pattern: true,
+            input_device: Some("USB Mic: Front (hw:1,0)".to_string()),
+            output_device: None,
+            video: VideoParams {
+                width: 1280,
+                height: 720,
+                fps: 24,
+                max_bitrate_kbps: Some(900),
+            },
+            max_bitrate_kbps: Some(500),
+            preferred_status: PreferredStatus::DoNotDisturb,
+            notifications: false,
+            theme: Theme::Light,
+            server_addr: "10.0.0.5:8443".to_string(),
+            session_token: Some("ab12".repeat(16)),
+            pinned_keys: BTreeMap::from([
+                ("bob".to_string(), "AA:BB:CC".to_string()),
+                ("carol_2".to_string(), "01:02:03".to_string()),
+            ]),
+        };
+        let path = temp_path("roundtrip");
+        settings.save(&path).unwrap();
+        let loaded = UserSettings::load(&path, &defaults()).unwrap();
+        let _ = fs::remove_file(&path);
+        assert_eq!(loaded, settings);
+    }
+
+    #[test]
+    fn test_missing_file_gives_defaults() {
+        let path = temp_path("missing");
+        let _ = fs::remove_file(&path);
+        assert_eq!(UserSettings::load(&path, &defaults()).unwrap(), defaults());
+    }
+
+    #[test]
+    fn test_partial_file_keeps_defaults_for_missing_keys() {
+        let mut base = defaults();
+        base.video.max_bitrate_kbps = Some(500);
+        let loaded = UserSettings::parse("camera_index=1\nvideo_fps=15\n", &base);
+
+        assert_eq!(loaded.camera_index, 1);
+        assert_eq!(loaded.video.fps, 15);
+        assert_eq!(loaded.video.width, base.video.width);
+        assert_eq!(loaded.video.max_bitrate_kbps, Some(500));
+        assert_eq!(loaded.server_addr, base.server_addr);
+        assert_eq!(loaded.preferred_status, PreferredStatus::Available);
+    }
+
+    #[test]
+    fn test_invalid_values_are_ignored() {
+        let base = defaults();
+        let loaded = UserSettings::parse(
+            "camera_index=front\nvideo_width=-3\npreferred_status=away\ntheme=blue\nserver_addr=\n",
+            &base,
+        );
+        assert_eq!(loaded, base);
+
+        // Un tope vacío sí se respeta: es "sin tope"
+        let mut capped = defaults();
+        capped.video.max_bitrate_kbps = Some(800);
+        capped.max_bitrate_kbps = Some(500);
+        let loaded = UserSettings::parse("video_max_bitrate_kbps=\nmax_bitrate_kbps=\n", &capped);
+        assert_eq!(loaded.video.max_bitrate_kbps, None);
+        assert_eq!(loaded.max_bitrate_kbps, None);
+    }
+
+    #[test]
+    fn test_test_pattern_defaults_to_the_config() {
+        let config = AppConfig {
+            fake_camera: true,
+            ..AppConfig::default()
+        };
+        let base = UserSettings::from_config(&config);
+        assert!(base.test_pattern);
+        assert!(!UserSettings::parse("test_pattern=false\n", &base).test_pattern);
+        assert!(UserSettings::parse("test_pattern=maybe\n", &base).test_pattern);
+    }
+
+    #[test]
+    fn test_copy_devices_only_touches_the_devices() {
+        let mut settings = defaults();
+        let mut tested = defaults();
+        assert!(!settings.copy_devices_from(&tested));
+
+        tested.camera_index = 1;
+        tested.output_device = Some("HDMI".to_string());
+        tested.video.fps = 5;
+        tested.server_addr = "10.0.0.9:8443".to_string();
+        assert!(settings.copy_devices_from(&tested));
+        assert_eq!(settings.camera_index, 1);
+        assert_eq!(settings.output_device.as_deref(), Some("HDMI"));
+        assert_eq!(settings.video, defaults().video);
+        assert_eq!(settings.server_addr, defaults().server_addr);
+    }
+
+    #[test]
+    fn test_settings_path_per_user() {
+        assert_eq!(
+            settings_path("prefs", "alice"),
+            Path::new("prefs").join("alice.settings.conf")
+        );
+        assert_eq!(
+            settings_path(".", "../bob"),
+            Path::new(".").join("___bob.settings.conf")
+        );
+    }
+
+    #[test]
+    fn test_remembered_session_needs_a_token() {
+        let dir = std::env::temp_dir().join(format!("roomrtc-remember-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let dir_str = dir.to_str().unwrap();
+        assert!(remembered_session(dir_str, &defaults()).is_none());
+
+        let mut settings = defaults();
+        settings.save(&settings_path(dir_str, "alice")).unwrap();
+        assert!(remembered_session(dir_str, &defaults()).is_none());
+
+        settings.session_token = Some("00ff".repeat(16));
+        settings.server_addr = "10.0.0.5:8443".to_string();
+        settings.save(&settings_path(dir_str, "bob")).unwrap();
+        let remembered = remembered_session(dir_str, &defaults());
+        let _ = fs::remove_dir_all(&dir);
+
+        let (username, loaded) = remembered.unwrap();
+        assert_eq!(username, "bob");
+        assert_eq!(loaded, settings);
+    }
+}
@@ -2,43 +2,112 @@ use std::collections::HashMap;
 use std::io::{BufRead, BufReader, ErrorKind, Write};
 use std::net::TcpStream;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc::{self, Receiver, Sender};
 use std::thread;
 use std::time::Duration;
 
-use rustls::client::{ServerCertVerified, ServerCertVerifier};
-use rustls::{ClientConfig, ClientConnection, RootCertStore, ServerName, StreamOwned};
+use room_rtc::log_debug;
+use rustls::{ClientConnection, StreamOwned};
+
+use super::capabilities::PeerCapabilities;
+use super::tls::TlsClientSettings;
+use crate::server::protocol::{FEATURE_B64, has_feature, payload, payload_field, redact_for_log};
+
+/// Usuario de USER_LIST con su estado tal como lo manda el servidor
+/// (`AVAILABLE`, `BUSY`, `DISCONNECTED`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UserEntry {
+    pub username: String,
+    pub status: String,
+}
 
 #[derive(Debug, Clone)]
 pub enum SignalingEvent {
     Registered(String),
     RegisterError(String),
-    LoginSuccess(()),
+    // Token para volver a entrar con `login_with_token`
+    LoginSuccess(Option<String>),
     LoginError(String),
     LoggedOut,
-    UserList(Vec<(String, String)>),
+    UserList(Vec<UserEntry>),
     UserStatusChanged {
         username: String,
         status: String,
     },
+    UserJoined {
+        username: String,
+        status: String,
+    },
+    // Cerró sesión o se desconectó; sigue registrado
+    UserLeft {
+        username: String,
+    },
+    // `caps`: lo que soporta el cliente del otro lado
     IncomingCall {
         from: String,
         sdp: String,
+        caps: PeerCapabilities,
+    },
+    // El cliente de `from` ya muestra nuestra llamada
+    CallRinging {
+        from: String,
     },
     CallAccepted {
         from: String,
         sdp: String,
+        caps: PeerCapabilities,
     },
     CallRejected {
         from: String,
     },
+    // CALL_ERROR con motivo: `to` no puede atender la llamada
+    CalleeNotFound {
+        to: String,
+    },
+    CalleeOffline {
+        to: String,
+    },
+    CalleeBusy {
+        to: String,
+    },
+    // `to` nos bloqueó
+    CallNotPermitted {
+        to: String,
+    },
     CallEnded {
         from: String,
     },
+    // `from` nos pasó su llamada: hay que llamar a `to`
+    CallTransferRequested {
+        from: String,
+        to: String,
+    },
+    // Nuestra transferencia a `to` fue aceptada por el servidor
+    CallTransferred {
+        to: String,
+    },
+    TransferFailed {
+        target: String,
+        error: String,
+    },
     IceCandidate {
         from: String,
         candidate: String,
     },
+    // Reinicio de ICE en plena llamada: con `sdp` es la oferta, sin él el
+    // peer nos pide que la mandemos nosotros
+    IceRestart {
+        from: String,
+        sdp: Option<String>,
+    },
+    IceRestartAnswer {
+        from: String,
+        sdp: String,
+    },
+    // Usuarios que bloqueamos, tras cada BLOCK/UNBLOCK/GET_BLOCKLIST
+    Blocklist(Vec<String>),
+    BlockError(String),
     Error(String),
     Disconnected,
 }
@@ -46,15 +115,18 @@ pub enum SignalingEvent {
 pub struct SignalingClient {
     outgoing: Sender<String>,
     receiver: Receiver<SignalingEvent>,
+    /// El servidor anunció `features:b64` al iniciar sesión: los SDP salen
+    /// en base64. Lo actualiza el hilo de red con cada LOGIN_SUCCESS.
+    payload_b64: Arc<AtomicBool>,
 }
 
 impl SignalingClient {
-    pub fn connect(server_addr: &str) -> std::io::Result<Self> {
+    pub fn connect(server_addr: &str, tls: &TlsClientSettings) -> std::io::Result<Self> {
+        let server_name = tls.server_name(server_addr)?;
+        let config = tls.client_config()?;
         let stream = TcpStream::connect(server_addr)?;
         stream.set_read_timeout(Some(Duration::from_millis(200)))?;
 
-        let server_name = parse_server_name(server_addr)?;
-        let config = build_client_config();
         let connection = ClientConnection::new(config, server_name)
             .map_err(|e| std::io::Error::other(format!("Error TLS: {}", e)))?;
         let tls_stream = StreamOwned::new(connection, stream);
@@ -62,13 +134,16 @@ impl SignalingClient {
         let (event_tx, event_rx) = mpsc::channel::<SignalingEvent>();
         let (out_tx, out_rx) = mpsc::channel::<String>();
 
+        let payload_b64 = Arc::new(AtomicBool::new(false));
+        let loop_b64 = payload_b64.clone();
         thread::spawn(move || {
-            run_client_loop(tls_stream, event_tx, out_rx);
+            run_client_loop(tls_stream, event_tx, out_rx, loop_b64);
         });
 
         Ok(Self {
             outgoing: out_tx,
             receiver: event_rx,
+            payload_b64,
         })
     }
 
@@ -82,7 +157,19 @@ impl SignalingClient {
     }
 
     pub fn login(&self, username: &str, password: &str) -> std::io::Result<()> {
-        let msg = format!("LOGIN|username:{}|password:{}", username, password);
+        let msg = format!(
+            "LOGIN|username:{}|password:{}|features:{}",
+            username, password, FEATURE_B64
+        );
+        self.send_message(&msg)
+    }
+
+    /// Vuelve a entrar con el token de una sesión recordada.
+    pub fn login_with_token(&self, username: &str, token: &str) -> std::io::Result<()> {
+        let msg = format!(
+            "LOGIN_TOKEN|username:{}|token:{}|features:{}",
+            username, token, FEATURE_B64
+        );
         self.send_message(&msg)
     }
 
@@ -94,76 +181,101 @@ impl SignalingClient {
         self.send_message("GET_USERS")
     }
 
+    /// Ofrece la llamada junto con las capacidades de este cliente.
     pub fn call(&self, to: &str, sdp: &str) -> std::io::Result<()> {
         let msg = format!(
-            "CALL_OFFER|to:{}|sdp:{}",
-            to, escape_payload(sdp)
+            "CALL_OFFER|to:{}|{}|caps:{}",
+            to,
+            self.sdp_field(sdp),
+            PeerCapabilities::local().to_field()
         );
         self.send_message(&msg)
     }
 
     pub fn answer_call(&self, to: &str, sdp: &str) -> std::io::Result<()> {
         let msg = format!(
-            "CALL_ANSWER|to:{}|accept:true|sdp:{}",
-            to, escape_payload(sdp)
+            "CALL_ANSWER|to:{}|accept:true|{}|caps:{}",
+            to,
+            self.sdp_field(sdp),
+            PeerCapabilities::local().to_field()
         );
         self.send_message(&msg)
     }
 
+    /// Avisa a `to` que su llamada ya se está mostrando.
+    pub fn call_ringing(&self, to: &str) -> std::io::Result<()> {
+        let msg = format!("CALL_RINGING|to:{}", to);
+        self.send_message(&msg)
+    }
+
     pub fn reject_call(&self, to: &str) -> std::io::Result<()> {
         let msg = format!("CALL_REJECT|to:{}", to);
         self.send_message(&msg)
     }
 
+    /// Rechaza la llamada de `to` porque ya hay otra; a quien llama le llega
+    /// como ocupado.
+    pub fn reject_call_busy(&self, to: &str) -> std::io::Result<()> {
+        let msg = format!("CALL_REJECT|to:{}|reason:busy", to);
+        self.send_message(&msg)
+    }
+
     pub fn end_call(&self, to: &str) -> std::io::Result<()> {
         let msg = format!("CALL_END|to:{}", to);
         self.send_message(&msg)
     }
 
-    fn send_message(&self, msg: &str) -> std::io::Result<()> {
-        self.outgoing
-            .send(msg.to_string())
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::BrokenPipe, e))
+    /// Pide al servidor pasar la llamada con `current` al usuario `target`.
+    pub fn transfer_call(&self, current: &str, target: &str) -> std::io::Result<()> {
+        let msg = format!("CALL_TRANSFER|to:{}|target:{}", current, target);
+        self.send_message(&msg)
     }
-}
 
-fn build_client_config() -> Arc<ClientConfig> {
-    let root_store = RootCertStore::empty();
-    let mut config = ClientConfig::builder()
-        .with_safe_defaults()
-        .with_root_certificates(root_store)
-        .with_no_client_auth();
-    config
-        .dangerous()
-        .set_certificate_verifier(Arc::new(InsecureVerifier));
-    Arc::new(config)
-}
+    /// Manda la oferta de reinicio de ICE de la llamada con `to`, o sin
+    /// `sdp` le pide que la mande él.
+    pub fn restart_ice(&self, to: &str, sdp: Option<&str>) -> std::io::Result<()> {
+        let msg = match sdp {
+            Some(sdp) => format!("ICE_RESTART|to:{}|{}", to, self.sdp_field(sdp)),
+            None => format!("ICE_RESTART|to:{}", to),
+        };
+        self.send_message(&msg)
+    }
+
+    pub fn answer_ice_restart(&self, to: &str, sdp: &str) -> std::io::Result<()> {
+        let msg = format!("ICE_RESTART_ANSWER|to:{}|{}", to, self.sdp_field(sdp));
+        self.send_message(&msg)
+    }
 
-struct InsecureVerifier;
+    /// Bloquea a `username`: sus llamadas dejan de llegarnos.
+    pub fn block(&self, username: &str) -> std::io::Result<()> {
+        self.send_message(&format!("BLOCK|username:{}", username))
+    }
 
-impl ServerCertVerifier for InsecureVerifier {
-    fn verify_server_cert(
-        &self,
-        _end_entity: &rustls::Certificate,
-        _intermediates: &[rustls::Certificate],
-        _server_name: &ServerName,
-        _scts: &mut dyn Iterator<Item = &[u8]>,
-        _ocsp_response: &[u8],
-        _now: std::time::SystemTime,
-    ) -> Result<ServerCertVerified, rustls::Error> {
-        Ok(ServerCertVerified::assertion())
+    pub fn unblock(&self, username: &str) -> std::io::Result<()> {
+        self.send_message(&format!("UNBLOCK|username:{}", username))
+    }
+
+    pub fn request_blocklist(&self) -> std::io::Result<()> {
+        self.send_message("GET_BLOCKLIST")
+    }
+
+    /// Campo del SDP: en base64 si el servidor lo entiende, si no escapado.
+    fn sdp_field(&self, sdp: &str) -> String {
+        payload_field("sdp", sdp, self.payload_b64.load(Ordering::Relaxed))
     }
-}
 
-fn parse_server_name(_addr: &str) -> std::io::Result<ServerName> {
-    ServerName::try_from("roomrtc.local")
-        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))
+    fn send_message(&self, msg: &str) -> std::io::Result<()> {
+        self.outgoing
+            .send(msg.to_string())
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::BrokenPipe, e))
+    }
 }
 
 fn run_client_loop(
     tls_stream: StreamOwned<ClientConnection, TcpStream>,
     event_tx: Sender<SignalingEvent>,
     outgoing: Receiver<String>,
+    payload_b64: Arc<AtomicBool>,
 ) {
     let mut reader = BufReader::new(tls_stream);
 
@@ -185,7 +297,11 @@ fn run_client_loop(
                 if trimmed.is_empty() {
                     continue;
                 }
+                trace_message("<<", trimmed);
                 let msg = parse_message(trimmed);
+                if msg.get("type").map(String::as_str) == Some("LOGIN_SUCCESS") {
+                    payload_b64.store(has_feature(&msg, FEATURE_B64), Ordering::Relaxed);
+                }
                 if let Some(event) = map_to_event(msg) {
                     let _ = event_tx.send(event);
                 }
@@ -210,10 +326,17 @@ fn flush_outgoing(
         stream.write_all(msg.as_bytes())?;
         stream.write_all(b"\n")?;
         stream.flush()?;
+        trace_message(">>", &msg);
     }
     Ok(())
 }
 
+/// Con el log en debug, deja cada mensaje de señalización sin contraseñas ni
+/// tokens y con el SDP recortado.
+fn trace_message(direction: &str, msg: &str) {
+    log_debug!("Señalización {} {}", direction, redact_for_log(msg));
+}
+
 fn parse_message(msg: &str) -> HashMap<String, String> {
     let mut map = HashMap::new();
     let parts: Vec<&str> = msg.split('|').collect();
@@ -249,99 +372,338 @@ fn map_to_event(msg: HashMap<String, String>) -> Option<SignalingEvent> {
             Some(SignalingEvent::Registered(message))
         }
         "REGISTER_ERROR" => {
-            let error = msg.get("error")?.clone();
+            let error = describe_error(msg.get("error")?);
             Some(SignalingEvent::RegisterError(error))
         }
-        "LOGIN_SUCCESS" => Some(SignalingEvent::LoginSuccess(())),
+        "LOGIN_SUCCESS" => Some(SignalingEvent::LoginSuccess(msg.get("token").cloned())),
         "LOGIN_ERROR" => {
-            let error = msg.get("error")?.clone();
+            let error = describe_error(msg.get("error")?);
             Some(SignalingEvent::LoginError(error))
         }
         "LOGOUT_SUCCESS" => Some(SignalingEvent::LoggedOut),
-        "USER_LIST" => {
-            let mut users = Vec::new();
-            for (key, value) in msg.iter() {
-                if key != "type" {
-                    users.push((key.clone(), value.clone()));
-                }
-            }
-            Some(SignalingEvent::UserList(users))
-        }
+        "USER_LIST" => Some(SignalingEvent::UserList(parse_user_list(&msg))),
         "USER_STATUS_CHANGED" => {
             let username = msg.get("username").cloned()?;
             let status = msg.get("status").cloned()?;
             Some(SignalingEvent::UserStatusChanged { username, status })
         }
+        "USER_JOINED" => {
+            let username = msg.get("username").cloned()?;
+            let status = msg.get("status").cloned()?;
+            Some(SignalingEvent::UserJoined { username, status })
+        }
+        "USER_LEFT" => {
+            let username = msg.get("username").cloned()?;
+            Some(SignalingEvent::UserLeft { username })
+        }
         "INCOMING_CALL" => {
             let from = msg.get("from").cloned()?;
-            let sdp = unescape_payload(msg.get("sdp"));
-            Some(SignalingEvent::IncomingCall {
-                from,
-                sdp,
-            })
+            let sdp = match payload(&msg, "sdp") {
+                Ok(sdp) => sdp.unwrap_or_default(),
+                Err(err) => return Some(SignalingEvent::Error(describe_error(&err))),
+            };
+            let caps = PeerCapabilities::parse(msg.get("caps").map(String::as_str));
+            Some(SignalingEvent::IncomingCall { from, sdp, caps })
+        }
+        "CALL_RINGING" => {
+            let from = msg.get("from").cloned()?;
+            Some(SignalingEvent::CallRinging { from })
         }
         "CALL_ACCEPTED" => {
             let from = msg.get("from").cloned()?;
-            let sdp = unescape_payload(msg.get("sdp"));
-            Some(SignalingEvent::CallAccepted {
-                from,
-                sdp,
-            })
+            let sdp = match payload(&msg, "sdp") {
+                Ok(sdp) => sdp.unwrap_or_default(),
+                Err(err) => return Some(SignalingEvent::Error(describe_error(&err))),
+            };
+            let caps = PeerCapabilities::parse(msg.get("caps").map(String::as_str));
+            Some(SignalingEvent::CallAccepted { from, sdp, caps })
         }
         "CALL_REJECTED" => {
             let from = msg.get("from").cloned()?;
+            // Atendió otra llamada o ya tiene demasiadas sonando
+            if msg.get("reason").map(String::as_str) == Some("busy") {
+                return Some(SignalingEvent::CalleeBusy { to: from });
+            }
             Some(SignalingEvent::CallRejected { from })
         }
         "CALL_ENDED" => {
             let from = msg.get("from").cloned()?;
             Some(SignalingEvent::CallEnded { from })
         }
+        "TRANSFER_REQUESTED" => {
+            let from = msg.get("from").cloned()?;
+            let to = msg.get("to").cloned()?;
+            Some(SignalingEvent::CallTransferRequested { from, to })
+        }
+        "CALL_TRANSFERRED" => {
+            let to = msg.get("to").cloned()?;
+            Some(SignalingEvent::CallTransferred { to })
+        }
+        "TRANSFER_FAILED" => {
+            let target = msg.get("target").cloned()?;
+            let error = msg.get("error").cloned()?;
+            Some(SignalingEvent::TransferFailed { target, error })
+        }
         "ICE_CANDIDATE" => {
             let from = msg.get("from").cloned()?;
-            let candidate = unescape_payload(msg.get("candidate"));
+            let candidate = match payload(&msg, "candidate") {
+                Ok(candidate) => candidate.unwrap_or_default(),
+                Err(err) => return Some(SignalingEvent::Error(describe_error(&err))),
+            };
             Some(SignalingEvent::IceCandidate { from, candidate })
         }
-        "ERROR" | "CALL_ERROR" => {
-            let err = msg.get("error").cloned()?;
+        "ICE_RESTART" => {
+            let from = msg.get("from").cloned()?;
+            let sdp = match payload(&msg, "sdp") {
+                Ok(sdp) => sdp,
+                Err(err) => return Some(SignalingEvent::Error(describe_error(&err))),
+            };
+            Some(SignalingEvent::IceRestart { from, sdp })
+        }
+        "ICE_RESTART_ANSWER" => {
+            let from = msg.get("from").cloned()?;
+            let sdp = match payload(&msg, "sdp") {
+                Ok(sdp) => sdp.unwrap_or_default(),
+                Err(err) => return Some(SignalingEvent::Error(describe_error(&err))),
+            };
+            Some(SignalingEvent::IceRestartAnswer { from, sdp })
+        }
+        "CALL_ERROR" => {
+            // Los servidores viejos no mandan `reason` ni `to`
+            let to = msg.get("to").cloned().unwrap_or_default();
+            match msg.get("reason").map(String::as_str) {
+                Some("not_found") => Some(SignalingEvent::CalleeNotFound { to }),
+                Some("offline") => Some(SignalingEvent::CalleeOffline { to }),
+                Some("busy") => Some(SignalingEvent::CalleeBusy { to }),
+                Some("not_permitted") => Some(SignalingEvent::CallNotPermitted { to }),
+                _ => {
+                    let err = describe_error(msg.get("error")?);
+                    Some(SignalingEvent::Error(err))
+                }
+            }
+        }
+        "BLOCKLIST" => {
+            let users = msg
+                .get("users")
+                .map(|users| {
+                    users
+                        .split(',')
+                        .filter(|user| !user.is_empty())
+                        .map(str::to_string)
+                        .collect()
+                })
+                .unwrap_or_default();
+            Some(SignalingEvent::Blocklist(users))
+        }
+        "BLOCK_ERROR" => {
+            let err = describe_error(msg.get("error")?);
+            Some(SignalingEvent::BlockError(err))
+        }
+        "ERROR" => {
+            let err = describe_error(msg.get("error")?);
             Some(SignalingEvent::Error(err))
         }
         _ => missing("type"),
     }
 }
 
-fn escape_payload(data: &str) -> String {
-    let mut out = String::with_capacity(data.len());
-    for ch in data.chars() {
-        match ch {
-            '\\' => out.push_str("\\\\"),
-            '\n' => out.push_str("\\n"),
-            '\r' => out.push_str("\\r"),
-            _ => out.push(ch),
+/// Lista de `USER_LIST|users:alice=AVAILABLE,bob=BUSY`. Los servidores
+/// anteriores mandan un campo por usuario (`USER_LIST|alice:AVAILABLE`); se
+/// aceptan hasta la próxima versión, ordenados por nombre porque los campos
+/// llegan sin orden.
+fn parse_user_list(msg: &HashMap<String, String>) -> Vec<UserEntry> {
+    // Un usuario viejo llamado "users" no trae `=` en el estado
+    let list = msg
+        .get("users")
+        .filter(|users| users.is_empty() || users.contains('='));
+    let Some(list) = list else {
+        let mut users: Vec<UserEntry> = msg
+            .iter()
+            .filter(|(key, _)| key.as_str() != "type")
+            .map(|(username, status)| UserEntry {
+                username: username.clone(),
+                status: status.clone(),
+            })
+            .collect();
+        users.sort_by(|a, b| a.username.cmp(&b.username));
+        return users;
+    };
+    list.split(',')
+        .filter_map(|entry| entry.split_once('='))
+        .map(|(username, status)| UserEntry {
+            username: username.to_string(),
+            status: status.to_string(),
+        })
+        .collect()
+}
+
+/// Texto para mostrar de los códigos de error de validación del servidor.
+fn describe_error(code: &str) -> String {
+    match code {
+        "invalid_username" => {
+            "Invalid username: 3-32 characters, only letters, numbers, _ or -".to_string()
         }
+        "invalid_password" => "Invalid password: 8-128 characters, no ':' or '|'".to_string(),
+        "sdp_too_large" => "Session description too large (max 32 KB)".to_string(),
+        "invalid_payload" => "Malformed session description".to_string(),
+        other => other.to_string(),
     }
-    out
 }
 
-fn unescape_payload(value: Option<&String>) -> String {
-    let Some(raw) = value else {
-        return String::new();
-    };
-    let mut out = String::with_capacity(raw.len());
-    let mut chars = raw.chars();
-    while let Some(ch) = chars.next() {
-        if ch == '\\' {
-            match chars.next() {
-                Some('n') => out.push('\n'),
-                Some('r') => out.push('\r'),
-                Some('\\') => out.push('\\'),
-                Some(other) => {
-                    out.push(other);
-                }
-                None => break,
-            }
-        } else {
-            out.push(ch);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::protocol::escape_payload;
+
+    fn event(line: &str) -> Option<SignalingEvent> {
+        map_to_event(parse_message(line))
+    }
+
+    #[test]
+    fn test_call_error_reasons_map_to_their_events() {
+        assert!(matches!(
+            event("CALL_ERROR|error:User not available|reason:busy|to:bob"),
+            Some(SignalingEvent::CalleeBusy { to }) if to == "bob"
+        ));
+        assert!(matches!(
+            event("CALL_ERROR|error:user not connected|reason:offline|to:bob"),
+            Some(SignalingEvent::CalleeOffline { to }) if to == "bob"
+        ));
+        assert!(matches!(
+            event("CALL_ERROR|error:User does not exist|reason:not_found|to:zed"),
+            Some(SignalingEvent::CalleeNotFound { to }) if to == "zed"
+        ));
+        // Sin motivo, o uno que todavía no conocemos, sigue siendo un error
+        assert!(matches!(
+            event("CALL_ERROR|error:User not available"),
+            Some(SignalingEvent::Error(err)) if err == "User not available"
+        ));
+        assert!(matches!(
+            event("CALL_ERROR|error:sdp_too_large|reason:later"),
+            Some(SignalingEvent::Error(err)) if err.starts_with("Session description too large")
+        ));
+    }
+
+    #[test]
+    fn test_busy_rejection_is_not_a_plain_rejection() {
+        assert!(matches!(
+            event("CALL_REJECTED|from:bob|reason:busy"),
+            Some(SignalingEvent::CalleeBusy { to }) if to == "bob"
+        ));
+        assert!(matches!(
+            event("CALL_REJECTED|from:bob"),
+            Some(SignalingEvent::CallRejected { from }) if from == "bob"
+        ));
+    }
+
+    #[test]
+    fn test_call_ringing() {
+        assert!(matches!(
+            event("CALL_RINGING|from:bob"),
+            Some(SignalingEvent::CallRinging { from }) if from == "bob"
+        ));
+        assert!(event("CALL_RINGING").is_none());
+    }
+
+    #[test]
+    fn test_ice_restart_offer_and_request() {
+        let offer = format!(
+            "ICE_RESTART|from:alice|sdp:{}",
+            escape_payload("v=0\r\no=-")
+        );
+        assert!(matches!(
+            event(&offer),
+            Some(SignalingEvent::IceRestart { from, sdp: Some(sdp) })
+                if from == "alice" && sdp == "v=0\r\no=-"
+        ));
+        assert!(matches!(
+            event("ICE_RESTART|from:bob"),
+            Some(SignalingEvent::IceRestart { sdp: None, .. })
+        ));
+        assert!(matches!(
+            event("ICE_RESTART_ANSWER|from:bob|sdp:v=0"),
+            Some(SignalingEvent::IceRestartAnswer { from, sdp }) if from == "bob" && sdp == "v=0"
+        ));
+    }
+
+    #[test]
+    fn test_b64_sdp_is_preferred() {
+        let sdp = "v=0\r\ns=Sala|Reunión: café\r\n";
+        let line = format!(
+            "INCOMING_CALL|from:alice|sdp:viejo|{}|caps:v1",
+            payload_field("sdp", sdp, true)
+        );
+        assert!(matches!(
+            event(&line),
+            Some(SignalingEvent::IncomingCall { from, sdp: got, .. })
+                if from == "alice" && got == sdp
+        ));
+        assert!(matches!(
+            event("CALL_ACCEPTED|from:bob|sdp_b64:%%%"),
+            Some(SignalingEvent::Error(err)) if err == "Malformed session description"
+        ));
+    }
+
+    fn users(line: &str) -> Vec<(String, String)> {
+        match event(line) {
+            Some(SignalingEvent::UserList(users)) => users
+                .into_iter()
+                .map(|user| (user.username, user.status))
+                .collect(),
+            other => panic!("expected USER_LIST, got {:?}", other),
         }
     }
-    out
+
+    fn pair(username: &str, status: &str) -> (String, String) {
+        (username.to_string(), status.to_string())
+    }
+
+    #[test]
+    fn test_user_list_in_both_formats() {
+        assert_eq!(
+            users("USER_LIST|users:alice=AVAILABLE,bob=BUSY"),
+            vec![pair("alice", "AVAILABLE"), pair("bob", "BUSY")]
+        );
+        assert!(users("USER_LIST|users:").is_empty());
+        // Formato anterior: un campo por usuario, incluido uno llamado "users"
+        assert_eq!(
+            users("USER_LIST|users:DISCONNECTED|bob:BUSY|alice:AVAILABLE"),
+            vec![
+                pair("alice", "AVAILABLE"),
+                pair("bob", "BUSY"),
+                pair("users", "DISCONNECTED")
+            ]
+        );
+        assert!(users("USER_LIST").is_empty());
+    }
+
+    #[test]
+    fn test_presence_deltas() {
+        assert!(matches!(
+            event("USER_JOINED|username:carol|status:AVAILABLE"),
+            Some(SignalingEvent::UserJoined { username, status })
+                if username == "carol" && status == "AVAILABLE"
+        ));
+        assert!(matches!(
+            event("USER_LEFT|username:carol"),
+            Some(SignalingEvent::UserLeft { username }) if username == "carol"
+        ));
+        assert!(event("USER_LEFT").is_none());
+    }
+
+    #[test]
+    fn test_blocklist_and_blocked_call() {
+        assert!(matches!(
+            event("BLOCKLIST|users:alice,carol"),
+            Some(SignalingEvent::Blocklist(users)) if users == ["alice", "carol"]
+        ));
+        assert!(matches!(
+            event("BLOCKLIST|users:"),
+            Some(SignalingEvent::Blocklist(users)) if users.is_empty()
+        ));
+        assert!(matches!(
+            event("CALL_ERROR|error:call not permitted|reason:not_permitted|to:bob"),
+            Some(SignalingEvent::CallNotPermitted { to }) if to == "bob"
+        ));
+    }
 }
@@ -0,0 +1,120 @@
+//! Capturas del video remoto: el último frame recibido (BGR) guardado como
+//! PNG, JPEG o WebP según la extensión que elija el usuario.
+
+use std::path::{Path, PathBuf};
+
+use opencv::core::{Mat, Vector};
+use opencv::imgcodecs;
+use opencv::prelude::*;
+
+const JPEG_QUALITY: i32 = 92;
+const WEBP_QUALITY: i32 = 90;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnapshotFormat {
+    Png,
+    Jpeg,
+    Webp,
+}
+
+impl SnapshotFormat {
+    /// El formato que pide la extensión de `path`, si es uno de los que
+    /// se guardan.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_string_lossy().to_ascii_lowercase();
+        match extension.as_str() {
+            "png" => Some(SnapshotFormat::Png),
+            "jpg" | "jpeg" => Some(SnapshotFormat::Jpeg),
+            "webp" => Some(SnapshotFormat::Webp),
+            _ => None,
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            SnapshotFormat::Png => ".png",
+            SnapshotFormat::Jpeg => ".jpg",
+            SnapshotFormat::Webp => ".webp",
+        }
+    }
+
+    fn params(self) -> Vector<i32> {
+        match self {
+            SnapshotFormat::Png => Vector::new(),
+            SnapshotFormat::Jpeg => {
+                Vector::from_slice(&[imgcodecs::IMWRITE_JPEG_QUALITY, JPEG_QUALITY])
+            }
+            SnapshotFormat::Webp => {
+                Vector::from_slice(&[imgcodecs::IMWRITE_WEBP_QUALITY, WEBP_QUALITY])
+            }
+        }
+    }
+}
+
+/// Nombre propuesto en el diálogo de guardado.
+pub fn default_file_name(peer: Option<&str>, unix_secs: u64) -> String {
+    match peer {
+        Some(peer) => format!("roomrtc-{}-{}.png", peer, unix_secs),
+        None => format!("roomrtc-{}.png", unix_secs),
+    }
+}
+
+/// Codifica `frame` en `format`.
+pub fn encode(frame: &Mat, format: SnapshotFormat) -> Result<Vec<u8>, String> {
+    if frame.empty() {
+        return Err("No hay imagen para capturar".to_string());
+    }
+    let mut buf = Vector::<u8>::new();
+    let encoded = imgcodecs::imencode(format.extension(), frame, &mut buf, &format.params())
+        .map_err(|e| format!("No se pudo codificar la captura: {}", e))?;
+    if !encoded {
+        return Err(format!(
+            "Este opencv no sabe escribir {}",
+            format.extension()
+        ));
+    }
+    Ok(buf.to_vec())
+}
+
+/// Guarda `frame` en `path`, en el formato que pide su extensión. Con otra
+/// extensión (o ninguna) va en PNG y con `.png`. Devuelve dónde quedó.
+pub fn save(frame: &Mat, path: &Path) -> Result<PathBuf, String> {
+    let (format, path) = match SnapshotFormat::from_path(path) {
+        Some(format) => (format, path.to_path_buf()),
+        None => (SnapshotFormat::Png, path.with_extension("png")),
+    };
+    let bytes = encode(frame, format)?;
+    std::fs::write(&path, bytes)
+        .map_err(|e| format!("No se pudo guardar {}: {}", path.display(), e))?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_follows_the_extension() {
+        let format = |name: &str| SnapshotFormat::from_path(Path::new(name));
+        assert_eq!(format("board.jpg"), Some(SnapshotFormat::Jpeg));
+        assert_eq!(format("board.JPEG"), Some(SnapshotFormat::Jpeg));
+        assert_eq!(format("board.webp"), Some(SnapshotFormat::Webp));
+        assert_eq!(format("board.png"), Some(SnapshotFormat::Png));
+        assert_eq!(format("board"), None);
+        assert_eq!(format("board.bmp"), None);
+    }
+
+    #[test]
+    fn test_default_file_name() {
+        assert_eq!(
+            default_file_name(Some("alice"), 1_700_000_000),
+            "roomrtc-alice-1700000000.png"
+        );
+        assert_eq!(default_file_name(None, 5), "roomrtc-5.png");
+    }
+
+    #[test]
+    fn test_empty_frame_is_not_encoded() {
+        assert!(encode(&Mat::default(), SnapshotFormat::Png).is_err());
+    }
+}
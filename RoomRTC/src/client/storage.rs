@@ -0,0 +1,166 @@
+//! Chequeos de disco para las transferencias de archivos: espacio para los
+//! entrantes y que los salientes se puedan leer. También abre los recibidos
+//! con la aplicación del sistema.
+
+use room_rtc::log_warn;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use std::process::Command;
+
+/// Bytes libres en el filesystem que contiene `path`.
+/// Devuelve `None` si no se puede averiguar en esta plataforma.
+#[cfg(unix)]
+pub fn available_space(path: &Path) -> Option<u64> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    // SAFETY: c_path es un string C válido y stat un buffer del tamaño correcto.
+    let res = unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) };
+    if res != 0 {
+        return None;
+    }
+    Some((stat.f_bavail as u64).saturating_mul(stat.f_frsize as u64))
+}
+
+#[cfg(not(unix))]
+pub fn available_space(_path: &Path) -> Option<u64> {
+    None
+}
+
+/// Valida que un archivo ofrecido de `size` bytes se pueda guardar en `save_path`.
+pub fn check_incoming_file(size: usize, max_size: u64, save_path: &Path) -> Result<(), String> {
+    let size = size as u64;
+    if size > max_size {
+        return Err(format!(
+            "El archivo ({:.1} MB) supera el máximo permitido ({:.1} MB)",
+            size as f64 / 1_048_576.0,
+            max_size as f64 / 1_048_576.0
+        ));
+    }
+
+    let dir = save_path
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new("."));
+    check_free_space(size, dir, available_space(dir))
+}
+
+/// Si entran `size` bytes en `dir` con `free` libres. Sin saber cuánto hay
+/// libre se acepta igual.
+fn check_free_space(size: u64, dir: &Path, free: Option<u64>) -> Result<(), String> {
+    match free {
+        Some(free) if free < size => Err(format!(
+            "Espacio insuficiente en {} ({:.1} MB libres)",
+            dir.display(),
+            free as f64 / 1_048_576.0
+        )),
+        Some(_) => Ok(()),
+        None => {
+            log_warn!(
+                "No se pudo verificar el espacio libre en {}, se acepta igual",
+                dir.display()
+            );
+            Ok(())
+        }
+    }
+}
+
+/// Valida un archivo a mandar: que exista, se pueda leer y no esté vacío.
+/// Devuelve su tamaño.
+pub fn check_outgoing_file(path: &Path) -> Result<usize, String> {
+    let metadata = std::fs::metadata(path)
+        .map_err(|e| format!("No se encuentra {}: {}", path.display(), e))?;
+    if !metadata.is_file() {
+        return Err(format!("{} no es un archivo", path.display()));
+    }
+    if metadata.len() == 0 {
+        return Err(format!("{} está vacío", path.display()));
+    }
+    File::open(path).map_err(|e| format!("No se puede leer {}: {}", path.display(), e))?;
+    Ok(metadata.len() as usize)
+}
+
+/// Primeros `len` bytes del archivo (menos si es más corto), para reconocer
+/// su tipo. Vacío si no se puede leer.
+pub fn read_head(path: &Path, len: usize) -> Vec<u8> {
+    let mut head = Vec::with_capacity(len);
+    if let Ok(file) = File::open(path) {
+        let _ = file.take(len as u64).read_to_end(&mut head);
+    }
+    head
+}
+
+/// Abre el archivo con la aplicación que el sistema tenga asociada.
+pub fn open_with_system(path: &Path) -> Result<(), String> {
+    #[cfg(target_os = "windows")]
+    let mut command = {
+        let mut command = Command::new("cmd");
+        command.args(["/C", "start", ""]);
+        command
+    };
+    #[cfg(target_os = "macos")]
+    let mut command = Command::new("open");
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    let mut command = Command::new("xdg-open");
+
+    let mut child = command
+        .arg(path)
+        .spawn()
+        .map_err(|e| format!("No se pudo abrir {}: {}", path.display(), e))?;
+    // El opener termina enseguida; se espera en otro hilo para no dejar un zombie
+    std::thread::spawn(move || child.wait());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_outgoing_file() {
+        let dir = std::env::temp_dir().join(format!("roomrtc-storage-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let empty = dir.join("empty.bin");
+        let full = dir.join("full.bin");
+        std::fs::write(&empty, b"").unwrap();
+        std::fs::write(&full, b"hola").unwrap();
+
+        assert_eq!(check_outgoing_file(&full), Ok(4));
+        assert!(check_outgoing_file(&empty).is_err());
+        assert!(check_outgoing_file(&dir).is_err());
+        assert!(check_outgoing_file(&dir.join("missing.bin")).is_err());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_incoming_file_over_the_maximum_is_rejected() {
+        let path = std::env::temp_dir().join("roomrtc-incoming.bin");
+        let err = check_incoming_file(3 * 1_048_576, 2 * 1_048_576, &path).unwrap_err();
+        assert!(err.contains("supera el máximo"), "{}", err);
+        assert_eq!(check_incoming_file(1024, 2 * 1_048_576, &path), Ok(()));
+    }
+
+    #[test]
+    fn test_free_space_check() {
+        let dir = Path::new("/descargas");
+        let err = check_free_space(2 * 1_048_576, dir, Some(1_048_576)).unwrap_err();
+        assert!(err.contains("Espacio insuficiente"), "{}", err);
+        assert_eq!(check_free_space(1_048_576, dir, Some(1_048_576)), Ok(()));
+        // Sin saber el espacio libre no se frena la transferencia
+        assert_eq!(check_free_space(u64::MAX, dir, None), Ok(()));
+    }
+
+    #[test]
+    fn test_read_head() {
+        let path = std::env::temp_dir().join(format!("roomrtc-head-{}.bin", std::process::id()));
+        std::fs::write(&path, b"\x89PNG\r\n\x1a\nrest").unwrap();
+        assert_eq!(read_head(&path, 8), b"\x89PNG\r\n\x1a\n");
+        assert_eq!(read_head(&path, 64).len(), 12);
+        let _ = std::fs::remove_file(&path);
+        assert!(read_head(&path, 8).is_empty());
+    }
+}
@@ -0,0 +1,66 @@
+//! Miniaturas de las imágenes que se ofrecen por la transferencia de
+//! archivos: JPEG chico que viaja en el `Offer`.
+
+use std::path::Path;
+
+use opencv::core::{Mat, Size, Vector};
+use opencv::prelude::*;
+use opencv::{imgcodecs, imgproc};
+use room_rtc::protocols::file_transfer::MAX_THUMBNAIL_BYTES;
+
+/// Lado más largo de la miniatura, en píxeles.
+pub const THUMBNAIL_MAX_SIDE: i32 = 256;
+
+/// Calidades JPEG que se prueban hasta que la miniatura entra en
+/// `MAX_THUMBNAIL_BYTES`.
+const JPEG_QUALITIES: [i32; 3] = [80, 60, 40];
+
+/// Achica la imagen de `path` y la codifica en JPEG. `None` si opencv no la
+/// puede leer o no entra en el tamaño máximo ni con la peor calidad.
+pub fn make_thumbnail(path: &Path) -> Option<Vec<u8>> {
+    let image = imgcodecs::imread(path.to_str()?, imgcodecs::IMREAD_COLOR).ok()?;
+    if image.empty() {
+        return None;
+    }
+    let small = downscale(&image)?;
+    for quality in JPEG_QUALITIES {
+        let mut jpeg = Vector::<u8>::new();
+        let params = Vector::<i32>::from_slice(&[imgcodecs::IMWRITE_JPEG_QUALITY, quality]);
+        if !imgcodecs::imencode(".jpg", &small, &mut jpeg, &params).ok()? {
+            return None;
+        }
+        if jpeg.len() <= MAX_THUMBNAIL_BYTES {
+            return Some(jpeg.to_vec());
+        }
+    }
+    None
+}
+
+fn downscale(image: &Mat) -> Option<Mat> {
+    let (width, height) = (image.cols(), image.rows());
+    let scale = THUMBNAIL_MAX_SIDE as f64 / width.max(height) as f64;
+    if scale >= 1.0 {
+        return image.try_clone().ok();
+    }
+    let size = Size::new(
+        ((width as f64 * scale).round() as i32).max(1),
+        ((height as f64 * scale).round() as i32).max(1),
+    );
+    let mut small = Mat::default();
+    imgproc::resize(image, &mut small, size, 0.0, 0.0, imgproc::INTER_AREA).ok()?;
+    Some(small)
+}
+
+/// Decodifica la miniatura de un offer (BGR). Viene del peer, así que solo
+/// se acepta un JPEG dentro de los límites que respeta quien la genera.
+pub fn decode_thumbnail(jpeg: &[u8]) -> Option<Mat> {
+    if jpeg.len() > MAX_THUMBNAIL_BYTES || !jpeg.starts_with(&[0xff, 0xd8, 0xff]) {
+        return None;
+    }
+    let buf = Vector::<u8>::from_slice(jpeg);
+    let image = imgcodecs::imdecode(&buf, imgcodecs::IMREAD_COLOR).ok()?;
+    if image.empty() || image.cols() > THUMBNAIL_MAX_SIDE || image.rows() > THUMBNAIL_MAX_SIDE {
+        return None;
+    }
+    Some(image)
+}
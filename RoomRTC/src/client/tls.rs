@@ -0,0 +1,293 @@
+//! Verificación del certificado del servidor de señalización.
+//!
+//! El cliente confía en el servidor por una de estas vías, en orden:
+//! fingerprint fijado (`tls_fingerprint`), CA propia (`tls_ca_file`) o, solo
+//! en desarrollo, cualquier certificado (`tls_dev_mode`).
+
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use rustls::client::{ServerCertVerified, ServerCertVerifier};
+use rustls::{Certificate, ClientConfig, RootCertStore, ServerName};
+use sha2::{Digest, Sha256};
+
+use crate::config::AppConfig;
+
+/// Lo que el cliente necesita de `AppConfig` para conectarse por TLS.
+#[derive(Debug, Clone, Default)]
+pub struct TlsClientSettings {
+    pub ca_file: Option<String>,
+    pub fingerprint: Option<String>,
+    pub server_name: Option<String>,
+    pub dev_mode: bool,
+}
+
+impl TlsClientSettings {
+    pub fn from_config(config: &AppConfig) -> Self {
+        Self {
+            ca_file: config.tls_ca_file.clone(),
+            fingerprint: config.tls_fingerprint.clone(),
+            server_name: config.tls_server_name.clone(),
+            dev_mode: config.tls_dev_mode,
+        }
+    }
+
+    /// Nombre a verificar en el certificado: el configurado o el host de
+    /// `server_addr`.
+    pub fn server_name(&self, server_addr: &str) -> io::Result<ServerName> {
+        let name = match &self.server_name {
+            Some(name) => name.as_str(),
+            None => host_of(server_addr),
+        };
+        ServerName::try_from(name).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+    }
+
+    pub fn client_config(&self) -> io::Result<Arc<ClientConfig>> {
+        let builder = ClientConfig::builder().with_safe_defaults();
+
+        if let Some(fingerprint) = &self.fingerprint {
+            let expected = parse_fingerprint(fingerprint)?;
+            let mut config = builder
+                .with_root_certificates(RootCertStore::empty())
+                .with_no_client_auth();
+            config
+                .dangerous()
+                .set_certificate_verifier(Arc::new(PinnedVerifier { expected }));
+            return Ok(Arc::new(config));
+        }
+
+        if let Some(ca_file) = &self.ca_file {
+            let mut reader = BufReader::new(File::open(ca_file)?);
+            let mut roots = RootCertStore::empty();
+            let (added, _) = roots.add_parsable_certificates(&rustls_pemfile::certs(&mut reader)?);
+            if added == 0 {
+                return Err(io::Error::other(format!("no certificates in {}", ca_file)));
+            }
+            return Ok(Arc::new(
+                builder.with_root_certificates(roots).with_no_client_auth(),
+            ));
+        }
+
+        if self.dev_mode {
+            let mut config = builder
+                .with_root_certificates(RootCertStore::empty())
+                .with_no_client_auth();
+            config
+                .dangerous()
+                .set_certificate_verifier(Arc::new(InsecureVerifier));
+            return Ok(Arc::new(config));
+        }
+
+        Err(io::Error::other(
+            "no way to verify the server: set tls_ca_file or tls_fingerprint (or tls_dev_mode=true)",
+        ))
+    }
+}
+
+/// Host de un `host:port`, también con IPv6 entre corchetes.
+fn host_of(server_addr: &str) -> &str {
+    if let Some(rest) = server_addr.strip_prefix('[') {
+        return rest.split(']').next().unwrap_or(rest);
+    }
+    match server_addr.rsplit_once(':') {
+        Some((host, _)) => host,
+        None => server_addr,
+    }
+}
+
+/// Acepta `AB:CD:...` o hex corrido, en mayúsculas o minúsculas.
+fn parse_fingerprint(value: &str) -> io::Result<[u8; 32]> {
+    let hex: String = value.chars().filter(|c| *c != ':').collect();
+    let invalid = || {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "tls_fingerprint must be a SHA-256 in hex",
+        )
+    };
+    if hex.len() != 64 || !hex.is_ascii() {
+        return Err(invalid());
+    }
+    let mut out = [0u8; 32];
+    for (i, byte) in out.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).map_err(|_| invalid())?;
+    }
+    Ok(out)
+}
+
+/// Acepta solo el certificado con ese SHA-256. La firma del handshake se
+/// sigue verificando, así que el servidor tiene que tener la clave.
+struct PinnedVerifier {
+    expected: [u8; 32],
+}
+
+impl ServerCertVerifier for PinnedVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        _intermediates: &[Certificate],
+        _server_name: &ServerName,
+        _scts: &mut dyn Iterator<Item = &[u8]>,
+        _ocsp_response: &[u8],
+        _now: SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        if Sha256::digest(&end_entity.0).as_slice() == self.expected {
+            Ok(ServerCertVerified::assertion())
+        } else {
+            Err(rustls::Error::General(
+                "server certificate does not match tls_fingerprint".to_string(),
+            ))
+        }
+    }
+}
+
+/// Solo para `tls_dev_mode`: acepta cualquier certificado.
+struct InsecureVerifier;
+
+impl ServerCertVerifier for InsecureVerifier {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &Certificate,
+        _intermediates: &[Certificate],
+        _server_name: &ServerName,
+        _scts: &mut dyn Iterator<Item = &[u8]>,
+        _ocsp_response: &[u8],
+        _now: SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::tls::{build_tls_config, cert_fingerprint, load_cert_chain};
+    use rcgen::{BasicConstraints, Certificate as RcgenCert, CertificateParams, IsCa};
+    use rustls::{ClientConnection, ServerConfig, ServerConnection};
+    use std::path::PathBuf;
+
+    fn temp_file(name: &str, content: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("roomrtc-tls-{}-{}", std::process::id(), name));
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    /// Handshake en memoria, sin sockets.
+    fn handshake(
+        tls: &TlsClientSettings,
+        server: Arc<ServerConfig>,
+        server_addr: &str,
+    ) -> Result<(), rustls::Error> {
+        let mut client = ClientConnection::new(
+            tls.client_config().unwrap(),
+            tls.server_name(server_addr).unwrap(),
+        )?;
+        let mut server = ServerConnection::new(server)?;
+        for _ in 0..10 {
+            if !client.is_handshaking() && !server.is_handshaking() {
+                return Ok(());
+            }
+            let mut buf = Vec::new();
+            client.write_tls(&mut buf).unwrap();
+            server.read_tls(&mut buf.as_slice()).unwrap();
+            server.process_new_packets()?;
+
+            let mut buf = Vec::new();
+            server.write_tls(&mut buf).unwrap();
+            client.read_tls(&mut buf.as_slice()).unwrap();
+            client.process_new_packets()?;
+        }
+        panic!("handshake did not finish");
+    }
+
+    fn dev_server() -> (Arc<ServerConfig>, String) {
+        let config = AppConfig {
+            tls_dev_mode: true,
+            ..AppConfig::default()
+        };
+        build_tls_config(&config).unwrap()
+    }
+
+    #[test]
+    fn test_pinned_fingerprint() {
+        let (server, fingerprint) = dev_server();
+        let pinned = TlsClientSettings {
+            fingerprint: Some(fingerprint.to_lowercase().replace(':', "")),
+            ..TlsClientSettings::default()
+        };
+        assert!(handshake(&pinned, Arc::clone(&server), "127.0.0.1:8443").is_ok());
+
+        let (_, other) = dev_server();
+        let wrong = TlsClientSettings {
+            fingerprint: Some(other),
+            ..TlsClientSettings::default()
+        };
+        assert!(handshake(&wrong, server, "127.0.0.1:8443").is_err());
+    }
+
+    #[test]
+    fn test_ca_file_verifies_chain_and_name() {
+        let mut ca_params = CertificateParams::new(Vec::new());
+        ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        let ca = RcgenCert::from_params(ca_params).unwrap();
+        let leaf =
+            RcgenCert::from_params(CertificateParams::new(vec!["localhost".to_string()])).unwrap();
+
+        let ca_file = temp_file("ca.pem", &ca.serialize_pem().unwrap());
+        let cert_file = temp_file("cert.pem", &leaf.serialize_pem_with_signer(&ca).unwrap());
+        let key_file = temp_file("key.pem", &leaf.serialize_private_key_pem());
+        let config = AppConfig {
+            tls_cert_file: Some(cert_file.to_string_lossy().to_string()),
+            tls_key_file: Some(key_file.to_string_lossy().to_string()),
+            ..AppConfig::default()
+        };
+        let (server, fingerprint) = build_tls_config(&config).unwrap();
+        let leaf_der = load_cert_chain(config.tls_cert_file.as_deref().unwrap())
+            .unwrap()
+            .remove(0);
+        assert_eq!(fingerprint, cert_fingerprint(&leaf_der.0));
+
+        let tls = TlsClientSettings {
+            ca_file: Some(ca_file.to_string_lossy().to_string()),
+            ..TlsClientSettings::default()
+        };
+        assert!(handshake(&tls, Arc::clone(&server), "localhost:8443").is_ok());
+        // Mismo certificado, pero no es el nombre al que nos conectamos
+        assert!(handshake(&tls, Arc::clone(&server), "127.0.0.1:8443").is_err());
+
+        // Sin dev mode, un servidor self-signed no pasa contra la CA
+        let (dev, _) = dev_server();
+        assert!(handshake(&tls, dev, "localhost:8443").is_err());
+
+        for path in [ca_file, cert_file, key_file] {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+
+    #[test]
+    fn test_trust_must_be_configured() {
+        assert!(TlsClientSettings::default().client_config().is_err());
+        let config = AppConfig::default();
+        assert!(build_tls_config(&config).is_err());
+        let half = AppConfig {
+            tls_cert_file: Some("cert.pem".to_string()),
+            ..AppConfig::default()
+        };
+        assert!(build_tls_config(&half).is_err());
+    }
+
+    #[test]
+    fn test_fingerprint_formats_and_host() {
+        let colons = "AB:".repeat(31) + "AB";
+        assert_eq!(parse_fingerprint(&colons).unwrap(), [0xAB; 32]);
+        assert_eq!(parse_fingerprint(&"ab".repeat(32)).unwrap(), [0xAB; 32]);
+        assert!(parse_fingerprint("AB:CD").is_err());
+        assert!(parse_fingerprint(&"zz".repeat(32)).is_err());
+
+        assert_eq!(host_of("signal.example.com:8443"), "signal.example.com");
+        assert_eq!(host_of("[::1]:8443"), "::1");
+        assert_eq!(host_of("localhost"), "localhost");
+    }
+}
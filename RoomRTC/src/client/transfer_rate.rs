@@ -0,0 +1,187 @@
+//! Velocidad y tiempo restante de las transferencias de archivos.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Ventana sobre la que se suaviza la velocidad mostrada.
+const RATE_WINDOW: Duration = Duration::from_secs(2);
+
+/// Velocidad de transferencia calculada sobre una ventana móvil de ~2 segundos
+/// de muestras (instante, bytes acumulados).
+pub struct TransferRate {
+    samples: VecDeque<(Instant, usize)>,
+}
+
+impl TransferRate {
+    pub fn new() -> Self {
+        Self::started_at(Instant::now())
+    }
+
+    fn started_at(start: Instant) -> Self {
+        let mut samples = VecDeque::new();
+        samples.push_back((start, 0));
+        Self { samples }
+    }
+
+    /// Registra el total de bytes transferidos hasta ahora.
+    pub fn record(&mut self, total_bytes: usize) {
+        self.record_at(total_bytes, Instant::now());
+    }
+
+    fn record_at(&mut self, total_bytes: usize, now: Instant) {
+        self.samples.push_back((now, total_bytes));
+        // Se conserva una muestra anterior a la ventana como punto de partida
+        while self.samples.len() > 2 && now.duration_since(self.samples[1].0) >= RATE_WINDOW {
+            self.samples.pop_front();
+        }
+    }
+
+    /// Bytes por segundo en la última ventana. Si la transferencia se trabó
+    /// más de una ventana, da 0.
+    pub fn bytes_per_sec(&self) -> f64 {
+        self.bytes_per_sec_at(Instant::now())
+    }
+
+    fn bytes_per_sec_at(&self, now: Instant) -> f64 {
+        let Some(&(_, newest_bytes)) = self.samples.back() else {
+            return 0.0;
+        };
+        let baseline = self
+            .samples
+            .iter()
+            .rev()
+            .find(|(at, _)| now.saturating_duration_since(*at) >= RATE_WINDOW)
+            .or_else(|| self.samples.front());
+        let Some(&(start, start_bytes)) = baseline else {
+            return 0.0;
+        };
+
+        let elapsed = now.saturating_duration_since(start).as_secs_f64();
+        if elapsed <= f64::EPSILON {
+            return 0.0;
+        }
+        newest_bytes.saturating_sub(start_bytes) as f64 / elapsed
+    }
+
+    /// Tiempo estimado para completar `total_bytes`, si hay velocidad medible.
+    pub fn eta(&self, total_bytes: usize) -> Option<Duration> {
+        self.eta_at(total_bytes, Instant::now())
+    }
+
+    fn eta_at(&self, total_bytes: usize, now: Instant) -> Option<Duration> {
+        let rate = self.bytes_per_sec_at(now);
+        if rate < 1.0 {
+            return None;
+        }
+        let done = self.samples.back().map(|(_, bytes)| *bytes).unwrap_or(0);
+        let remaining = total_bytes.saturating_sub(done) as f64;
+        Some(Duration::from_secs_f64(remaining / rate))
+    }
+
+    /// Texto para el overlay: "1.4 MB/s, 0:12 left" o "stalled".
+    pub fn summary(&self, total_bytes: usize) -> String {
+        self.summary_at(total_bytes, Instant::now())
+    }
+
+    fn summary_at(&self, total_bytes: usize, now: Instant) -> String {
+        let rate = self.bytes_per_sec_at(now);
+        let started = self.samples.back().is_some_and(|(_, bytes)| *bytes > 0);
+        match self.eta_at(total_bytes, now) {
+            Some(eta) => format!("{}, {} left", format_rate(rate), format_eta(eta)),
+            None if started => "stalled".to_string(),
+            None => "starting...".to_string(),
+        }
+    }
+}
+
+impl Default for TransferRate {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn format_rate(bytes_per_sec: f64) -> String {
+    const KB: f64 = 1024.0;
+    const MB: f64 = KB * 1024.0;
+    if bytes_per_sec >= MB {
+        format!("{:.1} MB/s", bytes_per_sec / MB)
+    } else if bytes_per_sec >= KB {
+        format!("{:.0} KB/s", bytes_per_sec / KB)
+    } else {
+        format!("{:.0} B/s", bytes_per_sec)
+    }
+}
+
+fn format_eta(eta: Duration) -> String {
+    let secs = eta.as_secs();
+    if secs >= 3600 {
+        format!("{}:{:02}:{:02}", secs / 3600, (secs % 3600) / 60, secs % 60)
+    } else {
+        format!("{}:{:02}", secs / 60, secs % 60)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secs(n: u64) -> Duration {
+        Duration::from_secs(n)
+    }
+
+    #[test]
+    fn test_rate_is_smoothed_over_the_window() {
+        let start = Instant::now();
+        let mut rate = TransferRate::started_at(start);
+        rate.record_at(1_000, start + secs(1));
+        rate.record_at(2_000, start + secs(2));
+        rate.record_at(5_000, start + secs(3));
+
+        // Parte de la muestra de hace 2 s, no de la primera
+        assert_eq!(rate.bytes_per_sec_at(start + secs(3)), 2_000.0);
+        // La muestra del inicio ya quedó afuera de la ventana
+        assert_eq!(
+            rate.samples.front().map(|(at, _)| *at),
+            Some(start + secs(1))
+        );
+    }
+
+    #[test]
+    fn test_no_elapsed_time_gives_no_rate() {
+        let start = Instant::now();
+        let mut rate = TransferRate::started_at(start);
+        assert_eq!(rate.bytes_per_sec_at(start), 0.0);
+
+        rate.record_at(4_096, start);
+        assert_eq!(rate.bytes_per_sec_at(start), 0.0);
+        assert_eq!(rate.eta_at(8_192, start), None);
+    }
+
+    #[test]
+    fn test_idle_transfer_is_reported_as_stalled() {
+        let start = Instant::now();
+        let mut rate = TransferRate::started_at(start);
+        rate.record_at(1_000, start + secs(1));
+        assert!(rate.summary_at(10_000, start + secs(2)).ends_with(" left"));
+
+        // Nada nuevo en más de una ventana
+        let idle = start + secs(1) + RATE_WINDOW;
+        assert_eq!(rate.bytes_per_sec_at(idle), 0.0);
+        assert_eq!(rate.eta_at(10_000, idle), None);
+        assert_eq!(rate.summary_at(10_000, idle), "stalled");
+    }
+
+    #[test]
+    fn test_eta_needs_a_rate() {
+        let start = Instant::now();
+        let mut rate = TransferRate::started_at(start);
+        assert_eq!(rate.eta_at(1_000, start + secs(1)), None);
+        assert_eq!(rate.summary_at(1_000, start + secs(1)), "starting...");
+
+        rate.record_at(500, start + secs(1));
+        assert_eq!(
+            rate.eta_at(1_000, start + secs(1)),
+            Some(Duration::from_secs(1))
+        );
+    }
+}
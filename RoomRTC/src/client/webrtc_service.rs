@@ -1,12 +1,205 @@
-use std::sync::{Arc, Mutex};
+use std::fmt;
+use std::io;
+use std::sync::Arc;
 
+use crate::client::capabilities::PeerCapabilities;
+use crate::client::message_inbox::SharedInbox;
 use crate::client::p2p_client::P2PClient;
+use crate::client::p2p_client_error::P2PClientError;
+use crate::client::signaling_client::SignalingClient;
+use crate::config::AppConfig;
+use room_rtc::ice::IceCheckConfig;
+use room_rtc::rtc::connection_event::{ConnectionEvent, ConnectionState};
+use room_rtc::rtc::rtc_dtls::DtlsConfig;
 use room_rtc::rtc::rtc_peer_connection::{PeerConnectionError, PeerConnectionRole};
+use room_rtc::rtc::socket::port_range::PortRange;
+use room_rtc::worker_thread::worker_media::VideoParams;
+use room_rtc::{log_info, log_warn};
+
+/// Vuelca los eventos del hilo de conexión en `status` y devuelve el motivo
+/// si la conexión falló de forma definitiva, o quedó envenenada por un panic.
+pub fn apply_connection_events(client: &P2PClient, status: &mut Option<String>) -> Option<String> {
+    if client.is_poisoned() {
+        return Some(P2PClientError::Poisoned.to_string());
+    }
+    for event in client.connection_events() {
+        if event.is_terminal() {
+            return Some(event.to_string());
+        }
+        // El canal de datos listo no cambia nada visible para el usuario
+        if event != ConnectionEvent::SctpReady {
+            *status = Some(event.to_string());
+        }
+    }
+    None
+}
+
+/// Lo que las pantallas necesitan del servidor para armar una llamada; en los
+/// tests se reemplaza por un mock.
+pub trait CallSignaling {
+    fn call(&self, to: &str, sdp: &str) -> io::Result<()>;
+    fn answer_call(&self, to: &str, sdp: &str) -> io::Result<()>;
+    fn restart_ice(&self, to: &str, sdp: Option<&str>) -> io::Result<()>;
+    fn answer_ice_restart(&self, to: &str, sdp: &str) -> io::Result<()>;
+    fn end_call(&self, to: &str) -> io::Result<()>;
+    fn reject_call(&self, to: &str) -> io::Result<()>;
+}
+
+impl CallSignaling for SignalingClient {
+    fn call(&self, to: &str, sdp: &str) -> io::Result<()> {
+        SignalingClient::call(self, to, sdp)
+    }
+
+    fn answer_call(&self, to: &str, sdp: &str) -> io::Result<()> {
+        SignalingClient::answer_call(self, to, sdp)
+    }
+
+    fn restart_ice(&self, to: &str, sdp: Option<&str>) -> io::Result<()> {
+        SignalingClient::restart_ice(self, to, sdp)
+    }
+
+    fn answer_ice_restart(&self, to: &str, sdp: &str) -> io::Result<()> {
+        SignalingClient::answer_ice_restart(self, to, sdp)
+    }
+
+    fn end_call(&self, to: &str) -> io::Result<()> {
+        SignalingClient::end_call(self, to)
+    }
+
+    fn reject_call(&self, to: &str) -> io::Result<()> {
+        SignalingClient::reject_call(self, to)
+    }
+}
+
+/// Reinicia ICE con `peer` sin cortar la llamada: quien controla ICE manda
+/// la oferta, el otro lado le pide que la mande. La sesión DTLS y las claves
+/// SRTP siguen siendo las mismas, solo cambia el camino.
+pub fn restart_ice(
+    client: &P2PClient,
+    signaling: &dyn CallSignaling,
+    peer: &str,
+) -> Result<(), PeerConnectionError> {
+    let offer = client.create_restart_offer()?;
+    signaling
+        .restart_ice(peer, offer.as_deref())
+        .map_err(PeerConnectionError::Io)
+}
+
+/// Un ICE_RESTART del peer: con oferta se responde; sin ella es el pedido
+/// de que la mandemos, que solo atiende quien controla ICE.
+pub fn handle_ice_restart(
+    client: &P2PClient,
+    signaling: &dyn CallSignaling,
+    peer: &str,
+    offer: Option<&str>,
+) -> Result<(), PeerConnectionError> {
+    match offer {
+        Some(offer) => {
+            let answer = client.accept_restart_offer(offer)?;
+            signaling
+                .answer_ice_restart(peer, &answer)
+                .map_err(PeerConnectionError::Io)
+        }
+        None if client.role().is_controlling() => restart_ice(client, signaling, peer),
+        None => Ok(()),
+    }
+}
+
+/// En qué paso está la llamada, de la oferta a la conexión segura.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CallProgress {
+    GatheringCandidates,
+    /// Mandamos la oferta y falta la respuesta del otro lado.
+    WaitingForAnswer,
+    IceChecking,
+    DtlsHandshake,
+    Connected,
+    Failed(String),
+}
+
+impl CallProgress {
+    /// Fase a partir del estado del hilo de conexión; antes de arrancar ICE
+    /// distingue si todavía esperamos la respuesta del peer.
+    pub fn from_state(state: &ConnectionState, waiting_for_answer: bool) -> Self {
+        match state {
+            ConnectionState::Gathering if waiting_for_answer => CallProgress::WaitingForAnswer,
+            ConnectionState::Gathering => CallProgress::GatheringCandidates,
+            ConnectionState::Checking => CallProgress::IceChecking,
+            ConnectionState::Handshaking => CallProgress::DtlsHandshake,
+            ConnectionState::Ready => CallProgress::Connected,
+            ConnectionState::Failed(reason) => CallProgress::Failed(reason.clone()),
+            ConnectionState::Closed => CallProgress::Failed(state.to_string()),
+        }
+    }
+
+    pub fn of(client: &P2PClient) -> Self {
+        let waiting_for_answer = client.has_local_description() && !client.has_remote_description();
+        Self::from_state(&client.connection_state(), waiting_for_answer)
+    }
+
+    /// Avance aproximado, de 0 a 1, para las barras de progreso.
+    pub fn fraction(&self) -> f32 {
+        match self {
+            CallProgress::GatheringCandidates => 0.05,
+            CallProgress::WaitingForAnswer => 0.1,
+            CallProgress::IceChecking => 0.4,
+            CallProgress::DtlsHandshake => 0.7,
+            CallProgress::Connected => 1.0,
+            CallProgress::Failed(_) => 0.0,
+        }
+    }
+}
+
+impl fmt::Display for CallProgress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CallProgress::GatheringCandidates => write!(f, "Gathering candidates..."),
+            CallProgress::WaitingForAnswer => write!(f, "Waiting for the answer..."),
+            CallProgress::IceChecking => write!(f, "Checking connectivity..."),
+            CallProgress::DtlsHandshake => write!(f, "Securing connection (DTLS)..."),
+            CallProgress::Connected => write!(f, "Connected"),
+            CallProgress::Failed(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+/// Cómo se arma el `P2PClient` de cada llamada; sale de `client.conf` y
+/// del video elegido en Settings.
+#[derive(Debug, Clone, Default)]
+pub struct PeerConfig {
+    /// Tope (kbps) que se anuncia con b=AS en nuestro SDP.
+    pub bandwidth_kbps: Option<u64>,
+    /// STUN para los candidatos reflexivos; sin valor, el público.
+    pub stun_server: Option<String>,
+    /// Plazo y ritmo de los chequeos de conectividad ICE.
+    pub ice: IceCheckConfig,
+    /// Suites y versiones permitidas en el handshake DTLS.
+    pub dtls: DtlsConfig,
+    /// Puertos UDP locales para el socket de media.
+    pub media_port_range: Option<PortRange>,
+}
+
+impl PeerConfig {
+    pub fn from_config(config: &AppConfig, video: &VideoParams) -> Self {
+        Self {
+            bandwidth_kbps: Some(video.bandwidth_kbps()),
+            stun_server: config.stun_server.clone(),
+            ice: config.ice_config(),
+            dtls: config.dtls_config(),
+            media_port_range: config.media_port_range(),
+        }
+    }
+}
 
 pub trait WebRTCHandler {
     fn client(&mut self) -> &mut Option<P2PClient>;
     fn role(&self) -> PeerConnectionRole;
-    fn received_msgs(&self) -> &Arc<Mutex<Vec<String>>>;
+    fn received_msgs(&self) -> &SharedInbox;
+
+    // Settings for the next peer
+    fn peer_config(&self) -> PeerConfig {
+        PeerConfig::default()
+    }
 
     // Starts peer
     fn initialize_peer(&mut self) -> Result<(), PeerConnectionError> {
@@ -14,7 +207,18 @@ pub trait WebRTCHandler {
             return Ok(());
         }
 
-        let client = P2PClient::new(self.role())?;
+        let config = self.peer_config();
+        let client = P2PClient::new(
+            self.role(),
+            config.media_port_range,
+            config.dtls.identity.clone(),
+        )?;
+        client.set_local_bandwidth(config.bandwidth_kbps);
+        if let Some(server) = &config.stun_server {
+            client.set_stun_server(server);
+        }
+        client.set_ice_config(config.ice);
+        client.set_dtls_config(&config.dtls)?;
         *self.client() = Some(client);
         Ok(())
     }
@@ -36,7 +240,7 @@ pub trait WebRTCHandler {
             .as_mut()
             .ok_or_else(|| PeerConnectionError::Sdp("Client not initialized".into()))?;
         if let Err(e) = client.set_remote_description(sdp) {
-            eprintln!("REMOTE DESCRIPTION ERROR: {}", e);
+            log_warn!("REMOTE DESCRIPTION ERROR: {}", e);
         };
 
         Ok(())
@@ -65,6 +269,73 @@ pub trait WebRTCHandler {
         client.send_msg(msg)
     }
 
+    // Creates our offer and sends it to `to`
+    fn send_offer(
+        &mut self,
+        signaling: &dyn CallSignaling,
+        to: &str,
+    ) -> Result<String, PeerConnectionError> {
+        self.initialize_peer()?;
+        let offer = self.generate_offer()?;
+        signaling
+            .call(to, &offer)
+            .map_err(PeerConnectionError::Io)?;
+        Ok(offer)
+    }
+
+    // Answers `from`'s offer and starts connecting (ICE, DTLS and SCTP)
+    fn answer_offer(
+        &mut self,
+        signaling: &dyn CallSignaling,
+        from: &str,
+        offer: &str,
+        caps: &PeerCapabilities,
+    ) -> Result<String, PeerConnectionError> {
+        self.initialize_peer()?;
+        if let Some(client) = self.client().as_mut() {
+            client.set_peer_capabilities(caps);
+        }
+        let answer = self.process_remote_offer(offer)?;
+        signaling
+            .answer_call(from, &answer)
+            .map_err(PeerConnectionError::Io)?;
+        self.start_ice()?;
+        Ok(answer)
+    }
+
+    // Applies the callee's answer and starts connecting (ICE, DTLS and SCTP)
+    fn accept_answer(
+        &mut self,
+        answer: &str,
+        caps: &PeerCapabilities,
+    ) -> Result<(), PeerConnectionError> {
+        let client = self
+            .client()
+            .as_mut()
+            .ok_or_else(|| PeerConnectionError::Sdp("Client not initialized".into()))?;
+        client.set_peer_capabilities(caps);
+        client.set_remote_description(answer)?;
+        self.start_ice()
+    }
+
+    // Phase of the call, None without a peer
+    fn connection_progress(&mut self) -> Option<CallProgress> {
+        self.client().as_ref().map(CallProgress::of)
+    }
+
+    // Why the connection failed for good, if it did since the last poll
+    fn connection_failure(&mut self) -> Option<String> {
+        let client = self.client().as_ref()?;
+        if client.is_poisoned() {
+            return Some(P2PClientError::Poisoned.to_string());
+        }
+        client
+            .connection_events()
+            .into_iter()
+            .find(|event| event.is_terminal())
+            .map(|event| event.to_string())
+    }
+
     // Starts ice checks
     fn start_ice(&mut self) -> Result<(), PeerConnectionError> {
         let client = self
@@ -85,22 +356,335 @@ pub trait WebRTCHandler {
         Ok(())
     }
 
+    // Session code for manual pairing (Advanced Debug)
+    fn export_session_code(&mut self) -> Result<String, PeerConnectionError> {
+        let client = self
+            .client()
+            .as_mut()
+            .ok_or_else(|| PeerConnectionError::Sdp("Client not initialized".into()))?;
+
+        client.export_session_blob()
+    }
+
+    // Applies the other peer's session code, creating the peer if needed.
+    // Returns our answer when the code carried an offer
+    fn import_session_code(&mut self, code: &str) -> Result<Option<String>, PeerConnectionError> {
+        self.initialize_peer()?;
+        let client = self
+            .client()
+            .as_mut()
+            .ok_or_else(|| PeerConnectionError::Sdp("Client not initialized".into()))?;
+
+        client.import_session_blob(code)
+    }
+
     //Join meet screen only ///
     // Processes the remote sdp offer
     fn process_remote_offer(&mut self, remote_sdp: &str) -> Result<String, PeerConnectionError> {
         if remote_sdp.trim().is_empty() {
-            
-            eprintln!("Remote SDP is empty");
+            log_warn!("Remote SDP is empty");
             return Err(PeerConnectionError::Sdp("Client not initialized".into()));
         }
         if let Some(client) = self.client().as_mut() {
             let answer = client.process_offer(remote_sdp)?;
-            println!("JOIN MEET SCREEN: Offer processed successfully.");
+            log_info!("JOIN MEET SCREEN: Offer processed successfully.");
 
             Ok(answer)
         } else {
-            eprintln!("JOIN MEET SCREEN: Client not initialized");
+            log_warn!("JOIN MEET SCREEN: Client not initialized");
             Err(PeerConnectionError::Sdp("Client not initialized".into()))
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use crate::client::call_controller::CallController;
+    use crate::client::message_inbox::MessageInbox;
+    use room_rtc::stun::StunServer;
+
+    const DEADLINE: Duration = Duration::from_secs(30);
+
+    /// Guarda lo que se mandaría al servidor, o falla como un socket cerrado.
+    #[derive(Default)]
+    struct MockSignaling {
+        sent: RefCell<Vec<(&'static str, String, String)>>,
+        closed: bool,
+    }
+
+    impl MockSignaling {
+        fn record(&self, kind: &'static str, to: &str, sdp: &str) -> io::Result<()> {
+            if self.closed {
+                return Err(io::Error::new(io::ErrorKind::BrokenPipe, "server closed"));
+            }
+            self.sent
+                .borrow_mut()
+                .push((kind, to.to_string(), sdp.to_string()));
+            Ok(())
+        }
+
+        fn last(&self) -> (&'static str, String, String) {
+            self.sent.borrow().last().cloned().expect("nothing sent")
+        }
+    }
+
+    impl CallSignaling for MockSignaling {
+        fn call(&self, to: &str, sdp: &str) -> io::Result<()> {
+            self.record("CALL_OFFER", to, sdp)
+        }
+
+        fn answer_call(&self, to: &str, sdp: &str) -> io::Result<()> {
+            self.record("CALL_ANSWER", to, sdp)
+        }
+
+        fn restart_ice(&self, to: &str, sdp: Option<&str>) -> io::Result<()> {
+            self.record("ICE_RESTART", to, sdp.unwrap_or_default())
+        }
+
+        fn answer_ice_restart(&self, to: &str, sdp: &str) -> io::Result<()> {
+            self.record("ICE_RESTART_ANSWER", to, sdp)
+        }
+
+        fn end_call(&self, to: &str) -> io::Result<()> {
+            self.record("CALL_END", to, "")
+        }
+
+        fn reject_call(&self, to: &str) -> io::Result<()> {
+            self.record("CALL_REJECT", to, "")
+        }
+    }
+
+    /// Pantalla mínima: solo lo que el trait necesita.
+    struct TestPeer {
+        role: PeerConnectionRole,
+        client: Option<P2PClient>,
+        inbox: SharedInbox,
+        stun: Option<String>,
+    }
+
+    impl TestPeer {
+        fn new(role: PeerConnectionRole, stun: Option<String>) -> Self {
+            Self {
+                role,
+                client: None,
+                inbox: MessageInbox::shared(),
+                stun,
+            }
+        }
+    }
+
+    impl WebRTCHandler for TestPeer {
+        fn client(&mut self) -> &mut Option<P2PClient> {
+            &mut self.client
+        }
+
+        fn role(&self) -> PeerConnectionRole {
+            self.role
+        }
+
+        fn received_msgs(&self) -> &SharedInbox {
+            &self.inbox
+        }
+
+        fn peer_config(&self) -> PeerConfig {
+            PeerConfig {
+                stun_server: self.stun.clone(),
+                ..PeerConfig::default()
+            }
+        }
+    }
+
+    /// STUN local: sin él la oferta consulta al servidor público.
+    fn local_stun() -> Option<String> {
+        let stun = StunServer::bind("127.0.0.1:0").unwrap();
+        let addr = stun.local_addr().unwrap();
+        stun.spawn();
+        Some(addr.to_string())
+    }
+
+    fn wait_connected(peer: &mut impl WebRTCHandler, name: &str) {
+        let start = Instant::now();
+        while peer.connection_progress() != Some(CallProgress::Connected) {
+            if let Some(reason) = peer.connection_failure() {
+                panic!("{} failed to connect: {}", name, reason);
+            }
+            assert!(start.elapsed() < DEADLINE, "{} not connected", name);
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn test_progress_follows_the_connection_state() {
+        let progress = |state, waiting| CallProgress::from_state(&state, waiting);
+        assert_eq!(
+            progress(ConnectionState::Gathering, true),
+            CallProgress::WaitingForAnswer
+        );
+        assert_eq!(
+            progress(ConnectionState::Gathering, false),
+            CallProgress::GatheringCandidates
+        );
+        assert_eq!(
+            progress(ConnectionState::Checking, false),
+            CallProgress::IceChecking
+        );
+        assert_eq!(
+            progress(ConnectionState::Handshaking, false),
+            CallProgress::DtlsHandshake
+        );
+        assert_eq!(
+            progress(ConnectionState::Ready, false),
+            CallProgress::Connected
+        );
+        assert_eq!(
+            progress(ConnectionState::Closed, false),
+            CallProgress::Failed("Connection closed".to_string())
+        );
+        assert_eq!(CallProgress::Connected.fraction(), 1.0);
+    }
+
+    #[test]
+    fn test_offer_answer_and_connect_through_the_trait() {
+        let stun = local_stun();
+        let signaling = MockSignaling::default();
+        let mut caller = TestPeer::new(PeerConnectionRole::Controlling, stun.clone());
+        let mut callee = TestPeer::new(PeerConnectionRole::Controlled, stun);
+        assert_eq!(caller.connection_progress(), None);
+
+        let offer = caller.send_offer(&signaling, "bob").unwrap();
+        assert_eq!(
+            signaling.last(),
+            ("CALL_OFFER", "bob".to_string(), offer.clone())
+        );
+        assert_eq!(
+            caller.connection_progress(),
+            Some(CallProgress::WaitingForAnswer)
+        );
+
+        let caps = PeerCapabilities::local();
+        let answer = callee
+            .answer_offer(&signaling, "alice", &offer, &caps)
+            .unwrap();
+        assert_eq!(
+            signaling.last(),
+            ("CALL_ANSWER", "alice".to_string(), answer.clone())
+        );
+        // Quien atiende arranca a conectar apenas manda la respuesta
+        assert_ne!(
+            callee.connection_progress(),
+            Some(CallProgress::GatheringCandidates)
+        );
+
+        caller.accept_answer(&answer, &caps).unwrap();
+        wait_connected(&mut caller, "caller");
+        wait_connected(&mut callee, "callee");
+        // Los dos caminos terminan con DTLS y el canal de datos
+        for peer in [&caller, &callee] {
+            let client = peer.client.as_ref().unwrap();
+            assert!(client.has_connection());
+            let start = Instant::now();
+            while !client.is_sctp_connected() {
+                assert!(start.elapsed() < DEADLINE, "SCTP not ready");
+                thread::sleep(Duration::from_millis(10));
+            }
+        }
+    }
+
+    #[test]
+    fn test_ice_restart_round_trip_through_the_trait() {
+        let stun = local_stun();
+        let signaling = MockSignaling::default();
+        let mut caller = TestPeer::new(PeerConnectionRole::Controlling, stun.clone());
+        let mut callee = TestPeer::new(PeerConnectionRole::Controlled, stun);
+        let caps = PeerCapabilities::local();
+        let offer = caller.send_offer(&signaling, "bob").unwrap();
+        let answer = callee
+            .answer_offer(&signaling, "alice", &offer, &caps)
+            .unwrap();
+        caller.accept_answer(&answer, &caps).unwrap();
+        let caller = caller.client.as_ref().unwrap();
+        let callee = callee.client.as_ref().unwrap();
+
+        // Quien no controla ICE solo puede pedir la oferta
+        restart_ice(callee, &signaling, "alice").unwrap();
+        assert_eq!(
+            signaling.last(),
+            ("ICE_RESTART", "alice".to_string(), String::new())
+        );
+        handle_ice_restart(caller, &signaling, "bob", None).unwrap();
+        let (kind, to, restart_offer) = signaling.last();
+        assert_eq!((kind, to.as_str()), ("ICE_RESTART", "bob"));
+        assert_ne!(restart_offer, offer);
+
+        handle_ice_restart(callee, &signaling, "alice", Some(restart_offer.as_str())).unwrap();
+        let (kind, to, restart_answer) = signaling.last();
+        assert_eq!((kind, to.as_str()), ("ICE_RESTART_ANSWER", "alice"));
+        caller.apply_restart_answer(&restart_answer).unwrap();
+
+        // Un pedido sin oferta no rebota desde el lado controlado
+        let sent = signaling.sent.borrow().len();
+        handle_ice_restart(callee, &signaling, "alice", None).unwrap();
+        assert_eq!(signaling.sent.borrow().len(), sent);
+    }
+
+    #[test]
+    fn test_call_controller_runs_a_call_and_hangs_up() {
+        let signaling = MockSignaling::default();
+        let config = PeerConfig {
+            stun_server: local_stun(),
+            ..PeerConfig::default()
+        };
+        let mut caller = CallController::new(PeerConnectionRole::Controlling, config.clone());
+        let mut callee = CallController::new(PeerConnectionRole::Controlled, config);
+        let caps = PeerCapabilities::local();
+
+        caller.place_call(&signaling, "bob").unwrap();
+        let (_, _, offer) = signaling.last();
+        callee.answer(&signaling, "alice", &offer, &caps).unwrap();
+        let (_, _, answer) = signaling.last();
+        caller.accept_answer(&answer, &caps).unwrap();
+        assert_eq!(caller.peer(), Some("bob"));
+        assert_eq!(callee.peer(), Some("alice"));
+        wait_connected(&mut caller, "caller");
+        wait_connected(&mut callee, "callee");
+
+        callee.hang_up(&signaling);
+        assert_eq!(
+            signaling.last(),
+            ("CALL_END", "alice".to_string(), String::new())
+        );
+        assert!(callee.p2p().is_none());
+        assert_eq!(callee.peer(), None);
+        // Ya cortada no hay nada más que avisar
+        let sent = signaling.sent.borrow().len();
+        assert!(callee.hang_up(&signaling).is_none());
+        assert_eq!(signaling.sent.borrow().len(), sent);
+    }
+
+    #[test]
+    fn test_signaling_errors_are_reported() {
+        let signaling = MockSignaling {
+            closed: true,
+            ..MockSignaling::default()
+        };
+        let mut caller = TestPeer::new(PeerConnectionRole::Controlling, local_stun());
+        assert!(matches!(
+            caller.send_offer(&signaling, "bob"),
+            Err(PeerConnectionError::Io(_))
+        ));
+        assert!(signaling.sent.borrow().is_empty());
+
+        // Sin oferta previa no hay a quién aplicarle la respuesta
+        let mut other = TestPeer::new(PeerConnectionRole::Controlling, None);
+        assert!(
+            other
+                .accept_answer("v=0", &PeerCapabilities::local())
+                .is_err()
+        );
+    }
+}
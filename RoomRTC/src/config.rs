@@ -2,16 +2,127 @@ use std::collections::HashMap;
 use std::fs;
 use std::io;
 use std::path::Path;
+use std::time::Duration;
+
+use room_rtc::audio::playout::{PlayoutTarget, SAMPLES_PER_MS};
+use room_rtc::ice::IceCheckConfig;
+use room_rtc::rtc::rtc_dtls::{DtlsConfig, DtlsVersion};
+use room_rtc::rtc::socket::port_range::PortRange;
+use room_rtc::worker_thread::worker_audio::{AudioParams, DEFAULT_AUDIO_QUEUE_DEPTH};
+use room_rtc::worker_thread::worker_media::VideoParams;
+
+use room_rtc::log_warn;
+
+use crate::logger::{LogLevel, LogOptions};
+
+/// Variable de entorno con la passphrase del archivo de usuarios.
+pub const USERS_PASSPHRASE_ENV: &str = "ROOMRTC_USERS_PASSPHRASE";
 
 #[derive(Clone, Debug)]
 pub struct AppConfig {
     pub server_addr: String,
     pub users_file: String,
+    /// Servidor: passphrase con la que se cifra `users_file`. La variable de
+    /// entorno `ROOMRTC_USERS_PASSPHRASE` tiene prioridad, para no dejarla
+    /// en el conf.
+    pub users_passphrase: Option<String>,
     pub max_clients: usize,
+    /// Servidor: segundos que se guarda la sesión (estado y llamada) de un
+    /// cliente que se cayó, por si vuelve a entrar con su token. 0 la limpia
+    /// enseguida.
+    pub reconnect_grace_secs: u64,
     pub log_file: String,
+    /// Nivel mínimo que llega al log: error, warn, info o debug.
+    pub log_level: LogLevel,
+    /// Tamaño a partir del cual se rota el log.
+    pub log_max_size_mb: u64,
+    /// Logs rotados que se conservan (`<log_file>.1`, `.2`...).
+    pub log_max_files: usize,
+    /// Servidor: loguea cada mensaje de señalización (sin contraseñas y con
+    /// los SDP recortados).
+    pub trace_messages: bool,
     pub video_width: u32,
     pub video_height: u32,
     pub video_fps: u32,
+    pub video_max_bitrate_kbps: Option<u64>,
+    /// Tope de todo lo que manda el cliente en una llamada (audio y video),
+    /// en kbps.
+    pub max_bitrate_kbps: Option<u64>,
+    pub max_file_size_mb: u64,
+    /// Cuántos archivos se mandan a la vez; el resto espera en cola.
+    pub max_concurrent_transfers: usize,
+    /// Segundos para aceptar o rechazar un archivo entrante antes de que se
+    /// rechace solo.
+    pub file_offer_timeout_secs: u64,
+    /// Segundos que suena una llamada entrante sin atender antes de
+    /// rechazarla y avisar que se perdió.
+    pub ring_timeout_secs: u64,
+    /// Cuántas llamadas entrantes suenan a la vez; las que llegan de más se
+    /// rechazan como ocupado.
+    pub max_pending_calls: usize,
+    /// Segundos que se espera a que la conexión con el peer se establezca
+    /// antes de ofrecer reintentar.
+    pub connect_timeout_secs: u64,
+    /// Segundos que tienen los chequeos ICE para encontrar un par que ande.
+    pub ice_timeout_secs: u64,
+    /// Espera de la respuesta a un chequeo antes de reenviarlo.
+    pub ice_check_interval_ms: u64,
+    /// Pares de candidatos que se chequean a la vez.
+    pub ice_parallel_checks: usize,
+    /// Tope de pares que se chequean; se quedan los de más prioridad.
+    pub ice_max_pairs: usize,
+    pub stun_server: Option<String>,
+    /// Puertos UDP locales para la media, para abrir solo ese rango en el
+    /// firewall; sin los dos, uno cualquiera.
+    pub media_port_min: Option<u16>,
+    pub media_port_max: Option<u16>,
+    /// Suites que puede negociar el handshake DTLS, en formato de OpenSSL;
+    /// sin valor, las de OpenSSL.
+    pub dtls_cipher_list: Option<String>,
+    /// Rango de versiones DTLS aceptadas (`1.0` o `1.2`).
+    pub dtls_min_version: Option<DtlsVersion>,
+    pub dtls_max_version: Option<DtlsVersion>,
+    pub stun_addr: Option<String>,
+    /// Dirección del listener HTTP de `/metrics`; sin valor no se levanta.
+    pub metrics_addr: Option<String>,
+    /// Patrón de prueba en lugar de la cámara, para demos sin dispositivo.
+    pub fake_camera: bool,
+    /// Arrancar sin ventana, atendiendo llamadas solo (kiosco).
+    pub headless: bool,
+    /// Quiénes pueden llamar en modo headless; vacío, cualquiera.
+    pub headless_allow: Vec<String>,
+    /// Bitrate de Opus; sin valor lo elige el encoder.
+    pub audio_bitrate_kbps: Option<u32>,
+    pub audio_fec: bool,
+    pub audio_expected_loss_pct: u8,
+    /// Frames de 20 ms que entran en cada cola del audio; sin valor, el
+    /// default de la librería.
+    pub audio_queue_depth: Option<usize>,
+    /// No mandar audio mientras el micrófono está en silencio (DTX).
+    pub audio_dtx: bool,
+    /// DTX propio de Opus: en silencio solo sale ruido de confort cada 400 ms.
+    pub audio_opus_dtx: bool,
+    /// Colchón del audio recibido contra el jitter, en ms: arranca en el
+    /// mínimo y crece hasta el máximo si el audio se corta. Sin valor, los
+    /// de la librería.
+    pub audio_jitter_min_ms: Option<u32>,
+    pub audio_jitter_max_ms: Option<u32>,
+    /// Carpeta de las preferencias de cada usuario.
+    pub settings_dir: String,
+    /// Usuarios que pueden pedir STATS y administrar las cuentas.
+    pub admin_users: Vec<String>,
+    /// Certificado y clave del servidor, en PEM.
+    pub tls_cert_file: Option<String>,
+    pub tls_key_file: Option<String>,
+    /// CA (PEM) contra la que el cliente verifica al servidor.
+    pub tls_ca_file: Option<String>,
+    /// SHA-256 del certificado del servidor que el cliente acepta.
+    pub tls_fingerprint: Option<String>,
+    /// Nombre a verificar en el certificado; sin valor, el host de `server_addr`.
+    pub tls_server_name: Option<String>,
+    /// Solo desarrollo: el servidor usa un cert self-signed y el cliente
+    /// acepta cualquier certificado.
+    pub tls_dev_mode: bool,
 }
 
 impl Default for AppConfig {
@@ -20,11 +131,56 @@ impl Default for AppConfig {
             server_addr: "127.0.0.1:8443".to_string(),
             //server_addr: "0.0.0.0:8443".to_string(),
             users_file: "users.txt".to_string(),
+            users_passphrase: None,
             max_clients: 100,
+            reconnect_grace_secs: 10,
             log_file: "roomrtc.log".to_string(),
+            log_level: LogLevel::Info,
+            log_max_size_mb: 10,
+            log_max_files: 3,
+            trace_messages: false,
             video_width: 640,
             video_height: 480,
             video_fps: 30,
+            video_max_bitrate_kbps: None,
+            max_bitrate_kbps: None,
+            max_file_size_mb: 1024,
+            max_concurrent_transfers: 1,
+            file_offer_timeout_secs: 60,
+            ring_timeout_secs: 30,
+            max_pending_calls: 3,
+            connect_timeout_secs: 30,
+            ice_timeout_secs: 10,
+            ice_check_interval_ms: 500,
+            ice_parallel_checks: 5,
+            ice_max_pairs: 20,
+            stun_server: None,
+            media_port_min: None,
+            media_port_max: None,
+            dtls_cipher_list: None,
+            dtls_min_version: None,
+            dtls_max_version: None,
+            stun_addr: None,
+            metrics_addr: None,
+            fake_camera: false,
+            headless: false,
+            headless_allow: Vec::new(),
+            audio_bitrate_kbps: None,
+            audio_fec: false,
+            audio_expected_loss_pct: 0,
+            audio_queue_depth: None,
+            audio_dtx: false,
+            audio_opus_dtx: false,
+            audio_jitter_min_ms: None,
+            audio_jitter_max_ms: None,
+            settings_dir: ".".to_string(),
+            admin_users: Vec::new(),
+            tls_cert_file: None,
+            tls_key_file: None,
+            tls_ca_file: None,
+            tls_fingerprint: None,
+            tls_server_name: None,
+            tls_dev_mode: false,
         }
     }
 }
@@ -45,12 +201,36 @@ impl AppConfig {
         if let Some(users) = entries.get("users_file") {
             cfg.users_file = users.clone();
         }
+        if let Some(passphrase) = entries.get("users_passphrase").filter(|v| !v.is_empty()) {
+            cfg.users_passphrase = Some(passphrase.clone());
+        }
         if let Some(max) = entries.get("max_clients").and_then(|v| v.parse().ok()) {
             cfg.max_clients = max;
         }
+        if let Some(secs) = entries
+            .get("reconnect_grace_secs")
+            .and_then(|v| v.parse().ok())
+        {
+            cfg.reconnect_grace_secs = secs;
+        }
         if let Some(log) = entries.get("log_file") {
             cfg.log_file = log.clone();
         }
+        if let Some(level) = entries.get("log_level").and_then(|v| v.parse().ok()) {
+            cfg.log_level = level;
+        }
+        if let Some(mb) = entries
+            .get("log_max_size_mb")
+            .and_then(|v| v.parse::<u64>().ok())
+        {
+            cfg.log_max_size_mb = mb.max(1);
+        }
+        if let Some(n) = entries.get("log_max_files").and_then(|v| v.parse().ok()) {
+            cfg.log_max_files = n;
+        }
+        if let Some(trace) = entries.get("trace_messages").and_then(|v| v.parse().ok()) {
+            cfg.trace_messages = trace;
+        }
         if let Some(w) = entries.get("video_width").and_then(|v| v.parse().ok()) {
             cfg.video_width = w;
         }
@@ -60,12 +240,298 @@ impl AppConfig {
         if let Some(fps) = entries.get("video_fps").and_then(|v| v.parse().ok()) {
             cfg.video_fps = fps;
         }
+        if let Some(kbps) = entries
+            .get("video_max_bitrate_kbps")
+            .and_then(|v| v.parse().ok())
+        {
+            cfg.video_max_bitrate_kbps = Some(kbps);
+        }
+        if let Some(kbps) = entries.get("max_bitrate_kbps").and_then(|v| v.parse().ok()) {
+            cfg.max_bitrate_kbps = Some(kbps);
+        }
+        if let Some(mb) = entries.get("max_file_size_mb").and_then(|v| v.parse().ok()) {
+            cfg.max_file_size_mb = mb;
+        }
+        if let Some(n) = entries
+            .get("max_concurrent_transfers")
+            .and_then(|v| v.parse::<usize>().ok())
+        {
+            cfg.max_concurrent_transfers = n.max(1);
+        }
+        if let Some(secs) = entries
+            .get("file_offer_timeout_secs")
+            .and_then(|v| v.parse::<u64>().ok())
+        {
+            cfg.file_offer_timeout_secs = secs.max(1);
+        }
+        if let Some(secs) = entries
+            .get("ring_timeout_secs")
+            .and_then(|v| v.parse::<u64>().ok())
+        {
+            cfg.ring_timeout_secs = secs.max(1);
+        }
+        if let Some(max) = entries
+            .get("max_pending_calls")
+            .and_then(|v| v.parse::<usize>().ok())
+        {
+            cfg.max_pending_calls = max.max(1);
+        }
+        if let Some(secs) = entries
+            .get("connect_timeout_secs")
+            .and_then(|v| v.parse::<u64>().ok())
+        {
+            cfg.connect_timeout_secs = secs.max(1);
+        }
+        if let Some(secs) = entries
+            .get("ice_timeout_secs")
+            .and_then(|v| v.parse::<u64>().ok())
+        {
+            cfg.ice_timeout_secs = secs.max(1);
+        }
+        if let Some(ms) = entries
+            .get("ice_check_interval_ms")
+            .and_then(|v| v.parse::<u64>().ok())
+        {
+            cfg.ice_check_interval_ms = ms.max(10);
+        }
+        if let Some(n) = entries
+            .get("ice_parallel_checks")
+            .and_then(|v| v.parse::<usize>().ok())
+        {
+            cfg.ice_parallel_checks = n.max(1);
+        }
+        if let Some(n) = entries
+            .get("ice_max_pairs")
+            .and_then(|v| v.parse::<usize>().ok())
+        {
+            cfg.ice_max_pairs = n.max(1);
+        }
+        if let Some(server) = entries.get("stun_server") {
+            cfg.stun_server = Some(server.clone());
+        }
+        if let Some(port) = entries
+            .get("media_port_min")
+            .and_then(|v| v.parse::<u16>().ok())
+        {
+            cfg.media_port_min = Some(port);
+        }
+        if let Some(port) = entries
+            .get("media_port_max")
+            .and_then(|v| v.parse::<u16>().ok())
+        {
+            cfg.media_port_max = Some(port);
+        }
+        if let Err(err) = check_port_range(cfg.media_port_min, cfg.media_port_max) {
+            log_warn!("{}; la media usa puertos efímeros", err);
+            cfg.media_port_min = None;
+            cfg.media_port_max = None;
+        }
+        if let Some(list) = entries.get("dtls_cipher_list") {
+            cfg.dtls_cipher_list = Some(list.clone());
+        }
+        if let Some(version) = dtls_version(&entries, "dtls_min_version") {
+            cfg.dtls_min_version = Some(version);
+        }
+        if let Some(version) = dtls_version(&entries, "dtls_max_version") {
+            cfg.dtls_max_version = Some(version);
+        }
+        if let Some(addr) = entries.get("stun_addr") {
+            cfg.stun_addr = Some(addr.clone());
+        }
+        if let Some(addr) = entries.get("metrics_addr") {
+            cfg.metrics_addr = Some(addr.clone());
+        }
+        if let Some(fake) = entries.get("fake_camera").and_then(|v| v.parse().ok()) {
+            cfg.fake_camera = fake;
+        }
+        if let Some(headless) = entries.get("headless").and_then(|v| v.parse().ok()) {
+            cfg.headless = headless;
+        }
+        if let Some(users) = entries.get("headless_allow") {
+            cfg.headless_allow = name_list(users);
+        }
+        if let Some(kbps) = entries
+            .get("audio_bitrate_kbps")
+            .and_then(|v| v.parse().ok())
+        {
+            cfg.audio_bitrate_kbps = Some(kbps);
+        }
+        if let Some(fec) = entries.get("audio_fec").and_then(|v| v.parse().ok()) {
+            cfg.audio_fec = fec;
+        }
+        if let Some(loss) = entries
+            .get("audio_expected_loss_pct")
+            .and_then(|v| v.parse::<u8>().ok())
+        {
+            cfg.audio_expected_loss_pct = loss.min(100);
+        }
+        if let Some(depth) = entries
+            .get("audio_queue_depth")
+            .and_then(|v| v.parse::<usize>().ok())
+            .filter(|depth| *depth > 0)
+        {
+            cfg.audio_queue_depth = Some(depth);
+        }
+        if let Some(dtx) = entries.get("audio_dtx").and_then(|v| v.parse().ok()) {
+            cfg.audio_dtx = dtx;
+        }
+        if let Some(dtx) = entries.get("audio_opus_dtx").and_then(|v| v.parse().ok()) {
+            cfg.audio_opus_dtx = dtx;
+        }
+        if let Some(ms) = entries
+            .get("audio_jitter_min_ms")
+            .and_then(|v| v.parse().ok())
+        {
+            cfg.audio_jitter_min_ms = Some(ms);
+        }
+        if let Some(ms) = entries
+            .get("audio_jitter_max_ms")
+            .and_then(|v| v.parse().ok())
+        {
+            cfg.audio_jitter_max_ms = Some(ms);
+        }
+        if let Some(dir) = entries.get("settings_dir") {
+            cfg.settings_dir = dir.clone();
+        }
+        if let Some(admins) = entries.get("admin_users") {
+            cfg.admin_users = name_list(admins);
+        }
+        if let Some(path) = entries.get("tls_cert_file") {
+            cfg.tls_cert_file = Some(path.clone());
+        }
+        if let Some(path) = entries.get("tls_key_file") {
+            cfg.tls_key_file = Some(path.clone());
+        }
+        if let Some(path) = entries.get("tls_ca_file") {
+            cfg.tls_ca_file = Some(path.clone());
+        }
+        if let Some(fp) = entries.get("tls_fingerprint") {
+            cfg.tls_fingerprint = Some(fp.clone());
+        }
+        if let Some(name) = entries.get("tls_server_name") {
+            cfg.tls_server_name = Some(name.clone());
+        }
+        if let Some(dev) = entries.get("tls_dev_mode").and_then(|v| v.parse().ok()) {
+            cfg.tls_dev_mode = dev;
+        }
 
         Ok(cfg)
     }
+
+    /// Video de las llamadas hasta que el usuario elija otro en Settings.
+    pub fn video_params(&self) -> VideoParams {
+        VideoParams {
+            width: self.video_width,
+            height: self.video_height,
+            fps: self.video_fps,
+            max_bitrate_kbps: self.video_max_bitrate_kbps,
+        }
+    }
+
+    /// Encoder de Opus y colas del audio; los dispositivos salen de Settings.
+    pub fn audio_params(&self) -> AudioParams {
+        let default = PlayoutTarget::default();
+        let playout = PlayoutTarget::from_ms(
+            self.audio_jitter_min_ms
+                .unwrap_or((default.min / SAMPLES_PER_MS) as u32),
+            self.audio_jitter_max_ms
+                .unwrap_or((default.max / SAMPLES_PER_MS) as u32),
+        );
+        AudioParams {
+            bitrate_bps: self.audio_bitrate_kbps.map(|kbps| kbps * 1000),
+            fec: self.audio_fec,
+            expected_loss_pct: self.audio_expected_loss_pct,
+            queue_depth: self.audio_queue_depth.unwrap_or(DEFAULT_AUDIO_QUEUE_DEPTH),
+            dtx: self.audio_dtx,
+            opus_dtx: self.audio_opus_dtx,
+            playout,
+            ..AudioParams::default()
+        }
+    }
+
+    /// Tiempos de los chequeos de conectividad ICE de cada llamada.
+    pub fn ice_config(&self) -> IceCheckConfig {
+        IceCheckConfig {
+            timeout: Duration::from_secs(self.ice_timeout_secs),
+            check_interval: Duration::from_millis(self.ice_check_interval_ms),
+            max_parallel_checks: self.ice_parallel_checks,
+            max_pairs: self.ice_max_pairs,
+        }
+    }
+
+    /// Passphrase del archivo de usuarios: la de `ROOMRTC_USERS_PASSPHRASE`
+    /// o, si no está, la del conf.
+    pub fn users_passphrase(&self) -> Option<String> {
+        std::env::var(USERS_PASSPHRASE_ENV)
+            .ok()
+            .filter(|v| !v.is_empty())
+            .or_else(|| self.users_passphrase.clone())
+    }
+
+    /// Rango de puertos de la media; `None` sin rango configurado. `load`
+    /// ya descartó los rangos inválidos.
+    pub fn media_port_range(&self) -> Option<PortRange> {
+        PortRange::new(self.media_port_min?, self.media_port_max?)
+    }
+
+    /// Suites y versiones que acepta el handshake DTLS de cada llamada. La
+    /// identidad es la del usuario y se agrega al entrar.
+    pub fn dtls_config(&self) -> DtlsConfig {
+        DtlsConfig {
+            cipher_list: self.dtls_cipher_list.clone(),
+            min_version: self.dtls_min_version,
+            max_version: self.dtls_max_version,
+            identity: None,
+        }
+    }
+
+    /// Nivel y rotación del archivo de log.
+    pub fn log_options(&self) -> LogOptions {
+        LogOptions {
+            min_level: self.log_level,
+            max_bytes: self.log_max_size_mb * 1024 * 1024,
+            max_files: self.log_max_files,
+        }
+    }
+}
+
+/// Usuarios separados por coma, sin espacios ni entradas vacías.
+fn name_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .collect()
+}
+
+/// `media_port_min` y `media_port_max` van juntos: uno solo, un rango al
+/// revés o uno que incluye el 0 es un error.
+fn check_port_range(min: Option<u16>, max: Option<u16>) -> Result<(), String> {
+    match (min, max) {
+        (None, None) => Ok(()),
+        (Some(min), Some(max)) if PortRange::new(min, max).is_some() => Ok(()),
+        (Some(min), Some(max)) => Err(format!(
+            "Rango de puertos de media inválido: {}-{}",
+            min, max
+        )),
+        _ => Err("media_port_min y media_port_max van juntos".to_string()),
+    }
+}
+
+/// Versión DTLS de `key`. Una que no es `1.0` ni `1.2` se ignora con un
+/// aviso y quedan las que acepta OpenSSL.
+fn dtls_version(entries: &HashMap<String, String>, key: &str) -> Option<DtlsVersion> {
+    let value = entries.get(key)?;
+    match value.parse() {
+        Ok(version) => Some(version),
+        Err(err) => {
+            log_warn!("{} inválida en la configuración, se ignora: {}", key, err);
+            None
+        }
+    }
 }
 
-fn parse_kv(content: &str) -> HashMap<String, String> {
+pub(crate) fn parse_kv(content: &str) -> HashMap<String, String> {
     let mut map = HashMap::new();
     for line in content.lines() {
         let line = line.trim();
@@ -78,3 +544,47 @@ fn parse_kv(content: &str) -> HashMap<String, String> {
     }
     map
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load_str(name: &str, content: &str) -> AppConfig {
+        let path = std::env::temp_dir().join(format!(
+            "roomrtc-config-{}-{}.conf",
+            name,
+            std::process::id()
+        ));
+        fs::write(&path, content).unwrap();
+        let cfg = AppConfig::load(path.to_str().unwrap()).unwrap();
+        let _ = fs::remove_file(&path);
+        cfg
+    }
+
+    #[test]
+    fn test_port_range_needs_both_ends_in_order() {
+        assert!(check_port_range(None, None).is_ok());
+        assert!(check_port_range(Some(40000), Some(40100)).is_ok());
+        assert!(check_port_range(Some(40100), Some(40000)).is_err());
+        assert!(check_port_range(Some(0), Some(40000)).is_err());
+        assert!(check_port_range(Some(40000), None).is_err());
+        assert!(check_port_range(None, Some(40100)).is_err());
+    }
+
+    #[test]
+    fn test_invalid_port_range_is_dropped_on_load() {
+        let cfg = load_str("ports-ok", "media_port_min=40000\nmedia_port_max=40100\n");
+        assert_eq!(cfg.media_port_range(), PortRange::new(40000, 40100));
+
+        for content in [
+            "media_port_min=40100\nmedia_port_max=40000\n",
+            "media_port_min=40000\n",
+            "media_port_min=40000\nmedia_port_max=70000\n",
+        ] {
+            let cfg = load_str("ports-bad", content);
+            assert_eq!(cfg.media_port_min, None);
+            assert_eq!(cfg.media_port_max, None);
+            assert!(cfg.media_port_range().is_none());
+        }
+    }
+}
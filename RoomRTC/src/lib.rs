@@ -0,0 +1,11 @@
+//! RoomRTC: cliente de videollamadas y servidor de señalización.
+//!
+//! Los binarios `roomrtc` y `signaling_server` solo leen la configuración y
+//! arrancan; todo lo demás vive en esta librería, así los tests de
+//! integración de `tests/` pueden levantar un servidor y dos clientes reales.
+
+pub mod client;
+pub mod config;
+pub mod logger;
+pub mod server;
+pub mod ui;
@@ -1,13 +1,37 @@
-use std::fs::OpenOptions;
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions};
 use std::io::{self, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::mpsc::{self, Sender};
 use std::thread;
 use std::time::{SystemTime, UNIX_EPOCH};
 
+pub use room_rtc::log::Level as LogLevel;
+
+/// Nivel mínimo y rotación del archivo de log.
+#[derive(Clone, Copy, Debug)]
+pub struct LogOptions {
+    pub min_level: LogLevel,
+    /// Tamaño a partir del cual el archivo pasa a `<log>.1`.
+    pub max_bytes: u64,
+    /// Archivos rotados que se conservan (`<log>.1` ... `<log>.N`).
+    pub max_files: usize,
+}
+
+impl Default for LogOptions {
+    fn default() -> Self {
+        Self {
+            min_level: LogLevel::Info,
+            max_bytes: 10 * 1024 * 1024,
+            max_files: 3,
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct Logger {
     tx: Sender<String>,
+    min_level: LogLevel,
 }
 
 impl Logger {
@@ -15,35 +39,103 @@ impl Logger {
     #[allow(dead_code)]
     pub fn noop() -> Self {
         let (tx, _rx) = mpsc::channel();
-        Logger { tx }
+        Logger {
+            tx,
+            min_level: LogLevel::Error,
+        }
     }
 
-    pub fn start(log_path: impl Into<PathBuf>) -> io::Result<Self> {
+    pub fn start(log_path: impl Into<PathBuf>, options: LogOptions) -> io::Result<Self> {
         let path = log_path.into();
+        let mut file = open_append(&path)?;
+        let mut size = file.metadata()?.len();
         let (tx, rx) = mpsc::channel::<String>();
 
         thread::spawn(move || {
             while let Ok(line) = rx.recv() {
-                if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(&path) {
-                    let _ = writeln!(file, "{}", line);
+                let len = line.len() as u64 + 1;
+                if size > 0 && size + len > options.max_bytes {
+                    rotate(&path, options.max_files);
+                    match open_append(&path) {
+                        Ok(new_file) => {
+                            file = new_file;
+                            size = 0;
+                        }
+                        Err(_) => continue,
+                    }
+                }
+                if writeln!(file, "{}", line).is_ok() {
+                    size += len;
                 }
             }
         });
 
-        Ok(Logger { tx })
+        Ok(Logger {
+            tx,
+            min_level: options.min_level,
+        })
+    }
+
+    /// Manda al log los mensajes de `room_rtc` (ICE, DTLS, SCTP, media) con
+    /// el mismo nivel mínimo. Solo lo toma el primer logger que lo pida.
+    pub fn capture_library_logs(&self) {
+        let logger = self.clone();
+        room_rtc::log::set_sink(
+            self.min_level,
+            Box::new(move |level, msg| logger.log(level, msg)),
+        );
+    }
+
+    pub fn enabled(&self, level: LogLevel) -> bool {
+        level <= self.min_level
+    }
+
+    pub fn log(&self, level: LogLevel, msg: &str) {
+        if self.enabled(level) {
+            let _ = self
+                .tx
+                .send(format!("[{}][{}] {}", level, timestamp(), msg));
+        }
+    }
+
+    pub fn debug(&self, msg: &str) {
+        self.log(LogLevel::Debug, msg);
     }
 
     pub fn info(&self, msg: &str) {
-        let _ = self.tx.send(format!("[INFO][{}] {}", timestamp(), msg));
+        self.log(LogLevel::Info, msg);
     }
 
     pub fn warn(&self, msg: &str) {
-        let _ = self.tx.send(format!("[WARN][{}] {}", timestamp(), msg));
+        self.log(LogLevel::Warn, msg);
     }
 
     pub fn error(&self, msg: &str) {
-        let _ = self.tx.send(format!("[ERROR][{}] {}", timestamp(), msg));
+        self.log(LogLevel::Error, msg);
+    }
+}
+
+fn open_append(path: &Path) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+/// Corre `<log>.N-1` a `<log>.N` y el log actual a `<log>.1`; el más viejo
+/// se pisa. Sin archivos rotados, el log simplemente se vacía.
+fn rotate(path: &Path, max_files: usize) {
+    if max_files == 0 {
+        let _ = fs::remove_file(path);
+        return;
+    }
+    for n in (1..max_files).rev() {
+        let _ = fs::rename(rotated(path, n), rotated(path, n + 1));
     }
+    let _ = fs::rename(path, rotated(path, 1));
+}
+
+fn rotated(path: &Path, n: usize) -> PathBuf {
+    let mut name = OsString::from(path.as_os_str());
+    name.push(format!(".{}", n));
+    PathBuf::from(name)
 }
 
 fn timestamp() -> u64 {
@@ -52,3 +144,70 @@ fn timestamp() -> u64 {
         .map(|d| d.as_secs())
         .unwrap_or_else(|_| 0)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn temp_log(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("roomrtc-logger-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir.join("test.log")
+    }
+
+    /// El escritor es otro hilo: espera a que aparezca `last` en el archivo.
+    fn wait_for(path: &Path, last: &str) -> String {
+        for _ in 0..100 {
+            let content = fs::read_to_string(path).unwrap_or_default();
+            if content.contains(last) {
+                return content;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        fs::read_to_string(path).unwrap_or_default()
+    }
+
+    #[test]
+    fn test_messages_below_the_minimum_level_are_dropped() {
+        let path = temp_log("level");
+        let options = LogOptions {
+            min_level: LogLevel::Warn,
+            ..LogOptions::default()
+        };
+        let logger = Logger::start(&path, options).unwrap();
+        logger.debug("trace sctp");
+        logger.info("login ok");
+        logger.warn("reintento");
+        logger.error("falló");
+
+        let content = wait_for(&path, "falló");
+        assert!(content.contains("[WARN]") && content.contains("reintento"));
+        assert!(content.contains("[ERROR]") && content.contains("falló"));
+        assert!(!content.contains("trace sctp") && !content.contains("login ok"));
+    }
+
+    #[test]
+    fn test_the_file_rotates_past_the_size_threshold() {
+        let path = temp_log("rotate");
+        let options = LogOptions {
+            min_level: LogLevel::Info,
+            max_bytes: 64,
+            max_files: 2,
+        };
+        let logger = Logger::start(&path, options).unwrap();
+        for n in 0..12 {
+            logger.info(&format!("mensaje {:02}", n));
+        }
+        logger.info("último");
+
+        let current = wait_for(&path, "último");
+        assert!(current.contains("último"));
+        assert!(fs::metadata(&path).unwrap().len() <= 64);
+        assert!(rotated(&path, 1).exists());
+        assert!(rotated(&path, 2).exists());
+        assert!(!rotated(&path, 3).exists());
+    }
+}
@@ -1,14 +1,17 @@
-mod client;
-mod config;
-mod logger;
-mod server;
-mod ui;
+use roomrtc::client::headless::{self, HeadlessOptions};
+use roomrtc::config::AppConfig;
+use roomrtc::logger::Logger;
+use roomrtc::ui;
 
-use config::AppConfig;
+const HEADLESS_USAGE: &str = "Uso: roomrtc [client.conf] --headless --user <usuario> --password <clave> \
+[--call <usuario>] [--duration <segundos>] [--video-file <archivo>] [--kiosk]";
 
 fn main() -> eframe::Result<()> {
-    let config_path = std::env::args()
-        .nth(1)
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let config_path = args
+        .first()
+        .filter(|arg| !arg.starts_with("--"))
+        .cloned()
         .unwrap_or_else(|| "client.conf".to_string());
     let config = match AppConfig::load(&config_path) {
         Ok(cfg) => cfg,
@@ -28,5 +31,53 @@ fn main() -> eframe::Result<()> {
     // The theme must be set on the context provided by eframe during setup.
     // So we will modify ui::launcher::run instead to apply theme on startup.
     
+    if config.headless || args.iter().any(|arg| arg == "--headless") {
+        std::process::exit(run_headless(&config, &args));
+    }
     ui::launcher::run(config)
 }
+
+/// Sin ventana. Una llamada sale con 0 si llegó video del peer, 1 si la
+/// llamada falló o no llegó nada y 2 si faltan argumentos; el kiosco sale
+/// con 0 tras un SIGINT y con 1 si pierde el servidor.
+fn run_headless(config: &AppConfig, args: &[String]) -> i32 {
+    let options = match HeadlessOptions::from_args(args) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("{}\n{}", err, HEADLESS_USAGE);
+            return 2;
+        }
+    };
+    let logger =
+        Logger::start(&config.log_file, config.log_options()).unwrap_or_else(|_| Logger::noop());
+    logger.capture_library_logs();
+    if options.call.is_none() && (options.kiosk || config.headless) {
+        return match headless::run_kiosk(config, &options, &logger) {
+            Ok(answered) => {
+                println!("calls_answered={}", answered);
+                0
+            }
+            Err(err) => {
+                logger.error(&format!("Headless: {}", err));
+                eprintln!("{}", err);
+                1
+            }
+        };
+    }
+    match headless::run(config, &options, &logger) {
+        Ok(report) => {
+            println!("{}", report);
+            if report.remote_frames > 0 {
+                0
+            } else {
+                eprintln!("No llegó video del peer");
+                1
+            }
+        }
+        Err(err) => {
+            logger.error(&format!("Headless: {}", err));
+            eprintln!("{}", err);
+            1
+        }
+    }
+}
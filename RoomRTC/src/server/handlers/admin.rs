@@ -0,0 +1,192 @@
+//! Handlers de administración: STATS y la gestión de cuentas (ADMIN_USERS,
+//! ADMIN_ADD_USER, ADMIN_REMOVE_USER, ADMIN_DISABLE_USER, ADMIN_ENABLE_USER).
+//!
+//! Los de cuentas responden con la lista actualizada
+//! (`ADMIN_USERS|users:a,b|disabled:b`), como los de bloqueos.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::mpsc::Sender;
+
+use super::context::HandlerResult;
+use crate::server::state::ServerState;
+
+/// Procesa el mensaje STATS. Solo responde a los usuarios de `admin_users`.
+pub fn handle_stats(
+    tx: &Sender<String>,
+    state: &Arc<ServerState>,
+    authenticated_user: &Option<String>,
+) -> HandlerResult {
+    if require_admin(tx, state, authenticated_user, "STATS", "STATS_ERROR").is_none() {
+        return HandlerResult::Continue;
+    }
+
+    let stats = state.stats();
+    ServerState::send_message(
+        tx,
+        &format!(
+            "STATS|connected:{}|active_calls:{}|total_logins:{}|failed_logins:{}|rejected_connections:{}|uptime_secs:{}",
+            stats.connected_clients,
+            stats.active_calls,
+            stats.total_logins,
+            stats.failed_logins,
+            stats.rejected_connections,
+            stats.uptime.as_secs()
+        ),
+    );
+    HandlerResult::Continue
+}
+
+/// Procesa el mensaje ADMIN_USERS: la lista de cuentas.
+pub fn handle_admin_users(
+    tx: &Sender<String>,
+    state: &Arc<ServerState>,
+    authenticated_user: &Option<String>,
+) -> HandlerResult {
+    if require_admin(tx, state, authenticated_user, "ADMIN_USERS", "ADMIN_ERROR").is_some() {
+        send_accounts(tx, state);
+    }
+    HandlerResult::Continue
+}
+
+/// Procesa el mensaje ADMIN_ADD_USER: da de alta una cuenta como un REGISTER.
+pub fn handle_admin_add_user(
+    msg: &HashMap<String, String>,
+    tx: &Sender<String>,
+    state: &Arc<ServerState>,
+    authenticated_user: &Option<String>,
+) -> HandlerResult {
+    let Some(admin) = require_admin(
+        tx,
+        state,
+        authenticated_user,
+        "ADMIN_ADD_USER",
+        "ADMIN_ERROR",
+    ) else {
+        return HandlerResult::Continue;
+    };
+    let (Some(target), Some(password)) = (msg.get("username"), msg.get("password")) else {
+        ServerState::send_message(tx, "ADMIN_ERROR|error:missing username or password");
+        return HandlerResult::Continue;
+    };
+
+    if let Err(err) = state.register_user(target.clone(), password.clone()) {
+        ServerState::send_message(tx, &format!("ADMIN_ERROR|error:{}", err));
+        return HandlerResult::Continue;
+    }
+    state
+        .logger
+        .info(&format!("{} dio de alta la cuenta {}", admin, target));
+    send_accounts(tx, state);
+    HandlerResult::Continue
+}
+
+/// Procesa el mensaje ADMIN_REMOVE_USER.
+pub fn handle_admin_remove_user(
+    msg: &HashMap<String, String>,
+    tx: &Sender<String>,
+    state: &Arc<ServerState>,
+    authenticated_user: &Option<String>,
+) -> HandlerResult {
+    let Some(admin) = require_admin(
+        tx,
+        state,
+        authenticated_user,
+        "ADMIN_REMOVE_USER",
+        "ADMIN_ERROR",
+    ) else {
+        return HandlerResult::Continue;
+    };
+    let Some(target) = msg.get("username") else {
+        ServerState::send_message(tx, "ADMIN_ERROR|error:missing username");
+        return HandlerResult::Continue;
+    };
+
+    if let Err(err) = state.remove_user(target) {
+        ServerState::send_message(tx, &format!("ADMIN_ERROR|error:{}", err));
+        return HandlerResult::Continue;
+    }
+    state
+        .logger
+        .info(&format!("{} borró la cuenta {}", admin, target));
+    send_accounts(tx, state);
+    HandlerResult::Continue
+}
+
+/// Procesa ADMIN_DISABLE_USER (`disabled`) y ADMIN_ENABLE_USER.
+pub fn handle_admin_set_disabled(
+    msg: &HashMap<String, String>,
+    tx: &Sender<String>,
+    state: &Arc<ServerState>,
+    authenticated_user: &Option<String>,
+    disabled: bool,
+) -> HandlerResult {
+    let request = if disabled {
+        "ADMIN_DISABLE_USER"
+    } else {
+        "ADMIN_ENABLE_USER"
+    };
+    let Some(admin) = require_admin(tx, state, authenticated_user, request, "ADMIN_ERROR") else {
+        return HandlerResult::Continue;
+    };
+    let Some(target) = msg.get("username") else {
+        ServerState::send_message(tx, "ADMIN_ERROR|error:missing username");
+        return HandlerResult::Continue;
+    };
+
+    if let Err(err) = state.set_disabled(target, disabled) {
+        ServerState::send_message(tx, &format!("ADMIN_ERROR|error:{}", err));
+        return HandlerResult::Continue;
+    }
+    state.logger.info(&format!(
+        "{} {} la cuenta {}",
+        admin,
+        if disabled {
+            "deshabilitó"
+        } else {
+            "habilitó"
+        },
+        target
+    ));
+    send_accounts(tx, state);
+    HandlerResult::Continue
+}
+
+/// El usuario autenticado si es admin. Si no, responde `error_type` y
+/// devuelve `None`.
+fn require_admin<'a>(
+    tx: &Sender<String>,
+    state: &ServerState,
+    authenticated_user: &'a Option<String>,
+    request: &str,
+    error_type: &str,
+) -> Option<&'a str> {
+    let Some(username) = authenticated_user else {
+        ServerState::send_message(tx, &format!("{}|error:not authenticated", error_type));
+        return None;
+    };
+    if !state.is_admin(username) {
+        ServerState::send_message(tx, &format!("{}|error:forbidden", error_type));
+        state
+            .logger
+            .warn(&format!("{} pidió {} sin ser admin", username, request));
+        return None;
+    }
+    Some(username)
+}
+
+fn send_accounts(tx: &Sender<String>, state: &ServerState) {
+    let accounts = state.accounts();
+    let names: Vec<&str> = accounts.iter().map(|(name, _)| name.as_str()).collect();
+    let disabled: Vec<&str> = accounts
+        .iter()
+        .filter(|(_, disabled)| *disabled)
+        .map(|(name, _)| name.as_str())
+        .collect();
+    let msg = format!(
+        "ADMIN_USERS|users:{}|disabled:{}",
+        names.join(","),
+        disabled.join(",")
+    );
+    ServerState::send_message(tx, &msg);
+}
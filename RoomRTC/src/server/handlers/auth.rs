@@ -1,13 +1,16 @@
-//! Handlers de autenticación: REGISTER, LOGIN, LOGOUT.
+//! Handlers de autenticación: REGISTER, LOGIN, LOGIN_TOKEN, LOGOUT.
 
 use std::collections::HashMap;
-use std::sync::mpsc::Sender;
 use std::sync::Arc;
+use std::sync::mpsc::Sender;
 
 use super::context::HandlerResult;
+use crate::server::protocol::{FEATURE_B64, has_feature};
 use crate::server::state::ServerState;
 use crate::server::types::{ConnectedClient, UserStatus};
-use crate::server::validation::{validate_password, validate_username};
+use crate::server::validation::{
+    protocol_value, validate_password, validate_password_format, validate_username,
+};
 
 /// Procesa el mensaje REGISTER.
 pub fn handle_register(
@@ -34,7 +37,7 @@ pub fn handle_register(
             state.logger.info("Registro de usuario exitoso");
         }
         Err(e) => {
-            ServerState::send_message(tx, &format!("REGISTER_ERROR|error:{}", e));
+            ServerState::send_message(tx, &format!("REGISTER_ERROR|error:{}", protocol_value(&e)));
             state
                 .logger
                 .error(&format!("Error registrando usuario: {}", e));
@@ -58,54 +61,126 @@ pub fn handle_login(
         ServerState::send_message(tx, "LOGIN_ERROR|error:missing password");
         return HandlerResult::Continue;
     };
-    if let Err(err) = validate_username(&username).and_then(|_| validate_password(&password)) {
+    // El formato del username se exige sólo al registrarse: acá se busca por
+    // nombre exacto para no dejar afuera cuentas anteriores a esas reglas.
+    if let Err(err) = validate_password_format(&password) {
         ServerState::send_message(tx, &format!("LOGIN_ERROR|error:{}", err));
         return HandlerResult::Continue;
     }
 
     match state.authenticate(&username, &password) {
-        Ok(_) => {
-            let already_connected = match state.connected_clients.read() {
-                Ok(clients) => clients.contains_key(&username),
-                Err(_) => {
-                    ServerState::send_message(tx, "LOGIN_ERROR|error:internal server error");
-                    state
-                        .logger
-                        .error("No se pudo leer clientes conectados (lock envenenado)");
-                    return HandlerResult::Continue;
-                }
-            };
-            if already_connected {
-                ServerState::send_message(tx, "LOGIN_ERROR|error:User already connected");
-                return HandlerResult::Continue;
-            }
-
-            *authenticated_user = Some(username.clone());
-
-            let client = ConnectedClient { sender: tx.clone() };
+        Ok(_) => start_session(username, false, msg, tx, state, authenticated_user),
+        Err(e) => {
+            state.record_failed_login();
+            ServerState::send_message(tx, &format!("LOGIN_ERROR|error:{}", protocol_value(&e)));
+            state.logger.error(&format!("Error de login: {}", e));
+        }
+    }
+    HandlerResult::Continue
+}
 
-            if let Ok(mut guard) = state.connected_clients.write() {
-                guard.insert(username.clone(), client);
-            } else {
-                ServerState::send_message(tx, "LOGIN_ERROR|error:internal server error");
-                state
-                    .logger
-                    .error("No se pudo guardar cliente (lock envenenado)");
-                return HandlerResult::Continue;
-            }
-            state.set_user_status(&username, UserStatus::Available);
+/// Procesa el mensaje LOGIN_TOKEN: vuelve a entrar con el token de una
+/// sesión recordada en vez de la contraseña.
+pub fn handle_login_token(
+    msg: &HashMap<String, String>,
+    tx: &Sender<String>,
+    state: &Arc<ServerState>,
+    authenticated_user: &mut Option<String>,
+) -> HandlerResult {
+    let Some(username) = msg.get("username").cloned() else {
+        ServerState::send_message(tx, "LOGIN_ERROR|error:missing username");
+        return HandlerResult::Continue;
+    };
+    let Some(token) = msg.get("token") else {
+        ServerState::send_message(tx, "LOGIN_ERROR|error:missing token");
+        return HandlerResult::Continue;
+    };
+    // Antes de consumir el token: si la sesión sigue abierta, el token sirve
+    // para cuando se cierre
+    if state.clients().contains_key(&username) {
+        ServerState::send_message(tx, "LOGIN_ERROR|error:User already connected");
+        return HandlerResult::Continue;
+    }
 
-            ServerState::send_message(tx, "LOGIN_SUCCESS|message:Login success");
-            state.logger.info(&format!("{} inició sesión", username));
-        }
+    match state.authenticate_token(&username, token) {
+        Ok(_) => start_session(username, true, msg, tx, state, authenticated_user),
         Err(e) => {
-            ServerState::send_message(tx, &format!("LOGIN_ERROR|error:{}", e));
-            state.logger.error(&format!("Error de login: {}", e));
+            state.record_failed_login();
+            ServerState::send_message(tx, &format!("LOGIN_ERROR|error:{}", protocol_value(&e)));
+            state
+                .logger
+                .error(&format!("Error de login con token: {}", e));
         }
     }
     HandlerResult::Continue
 }
 
+/// Registra al usuario ya autenticado como conectado y le manda un token
+/// nuevo, que el cliente guarda si el usuario pidió que lo recuerde.
+///
+/// Con `resume` (entró con el token) retoma la sesión que se le cayó hace
+/// menos de `reconnect_grace`, con su estado y su llamada; la respuesta
+/// lleva `resumed:true`.
+///
+/// La respuesta anuncia `features:b64`; si el LOGIN también lo trajo, los
+/// payloads que le lleguen a este cliente van en base64.
+fn start_session(
+    username: String,
+    resume: bool,
+    msg: &HashMap<String, String>,
+    tx: &Sender<String>,
+    state: &Arc<ServerState>,
+    authenticated_user: &mut Option<String>,
+) {
+    if state.clients().contains_key(&username) {
+        ServerState::send_message(tx, "LOGIN_ERROR|error:User already connected");
+        return;
+    }
+
+    // Entrar con la contraseña arranca de cero aunque haya una sesión caída
+    let held = state.reclaim_session(&username);
+    if held && !resume {
+        state.finish_disconnect(&username);
+    }
+    let resumed = held && resume;
+
+    *authenticated_user = Some(username.clone());
+
+    let client = ConnectedClient {
+        sender: tx.clone(),
+        payload_b64: has_feature(msg, FEATURE_B64),
+    };
+
+    state.clients_mut().insert(username.clone(), client);
+    if !resumed {
+        state.set_user_status(&username, UserStatus::Available);
+        state.announce_user_joined(&username);
+    }
+    state.record_login();
+
+    let token = state.issue_token(&username);
+    if resumed {
+        // Los demás nunca lo vieron irse: estado y llamada siguen igual
+        ServerState::send_message(
+            tx,
+            &format!(
+                "LOGIN_SUCCESS|message:Session resumed|resumed:true|token:{}|features:{}",
+                token, FEATURE_B64
+            ),
+        );
+        state.logger.info(&format!("{} retomó su sesión", username));
+    } else {
+        ServerState::send_message(
+            tx,
+            &format!(
+                "LOGIN_SUCCESS|message:Login success|token:{}|features:{}",
+                token, FEATURE_B64
+            ),
+        );
+        state.logger.info(&format!("{} inició sesión", username));
+    }
+}
+
 /// Procesa el mensaje LOGOUT.
 pub fn handle_logout(
     tx: &Sender<String>,
@@ -113,10 +188,10 @@ pub fn handle_logout(
     authenticated_user: &Option<String>,
 ) -> HandlerResult {
     if let Some(username) = authenticated_user {
-        if let Ok(mut guard) = state.connected_clients.write() {
-            guard.remove(username);
-        }
+        state.clients_mut().remove(username);
+        state.revoke_tokens(username);
         state.set_user_status(username, UserStatus::Disconnected);
+        state.announce_user_left(username);
         ServerState::send_message(tx, "LOGOUT_SUCCESS");
         state.logger.info(&format!("{} cerró sesión", username));
     }
@@ -0,0 +1,80 @@
+//! Handlers de bloqueos: BLOCK, UNBLOCK, GET_BLOCKLIST.
+//!
+//! Los tres responden con la lista actualizada (`BLOCKLIST|users:a,b`), así
+//! el cliente no tiene que seguir los cambios por su cuenta.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::mpsc::Sender;
+
+use super::context::HandlerResult;
+use crate::server::state::ServerState;
+
+/// Procesa el mensaje BLOCK.
+pub fn handle_block(
+    msg: &HashMap<String, String>,
+    tx: &Sender<String>,
+    state: &Arc<ServerState>,
+    authenticated_user: &Option<String>,
+) -> HandlerResult {
+    update_blocklist(msg, tx, state, authenticated_user, true)
+}
+
+/// Procesa el mensaje UNBLOCK.
+pub fn handle_unblock(
+    msg: &HashMap<String, String>,
+    tx: &Sender<String>,
+    state: &Arc<ServerState>,
+    authenticated_user: &Option<String>,
+) -> HandlerResult {
+    update_blocklist(msg, tx, state, authenticated_user, false)
+}
+
+/// Procesa el mensaje GET_BLOCKLIST.
+pub fn handle_get_blocklist(
+    tx: &Sender<String>,
+    state: &Arc<ServerState>,
+    authenticated_user: &Option<String>,
+) -> HandlerResult {
+    let Some(username) = authenticated_user else {
+        ServerState::send_message(tx, "BLOCK_ERROR|error:not authenticated");
+        return HandlerResult::Continue;
+    };
+    send_blocklist(tx, state, username);
+    HandlerResult::Continue
+}
+
+fn update_blocklist(
+    msg: &HashMap<String, String>,
+    tx: &Sender<String>,
+    state: &Arc<ServerState>,
+    authenticated_user: &Option<String>,
+    blocked: bool,
+) -> HandlerResult {
+    let Some(username) = authenticated_user else {
+        ServerState::send_message(tx, "BLOCK_ERROR|error:not authenticated");
+        return HandlerResult::Continue;
+    };
+    let Some(target) = msg.get("username") else {
+        ServerState::send_message(tx, "BLOCK_ERROR|error:missing username");
+        return HandlerResult::Continue;
+    };
+
+    if let Err(err) = state.set_blocked(username, target, blocked) {
+        ServerState::send_message(tx, &format!("BLOCK_ERROR|error:{}", err));
+        return HandlerResult::Continue;
+    }
+    state.logger.info(&format!(
+        "{} {} a {}",
+        username,
+        if blocked { "bloqueó" } else { "desbloqueó" },
+        target
+    ));
+    send_blocklist(tx, state, username);
+    HandlerResult::Continue
+}
+
+fn send_blocklist(tx: &Sender<String>, state: &ServerState, username: &str) {
+    let msg = format!("BLOCKLIST|users:{}", state.blocklist(username).join(","));
+    ServerState::send_message(tx, &msg);
+}
@@ -1,15 +1,22 @@
 //! Contexto y dispatcher de handlers.
 
 use std::collections::HashMap;
-use std::sync::mpsc::Sender;
 use std::sync::Arc;
+use std::sync::mpsc::Sender;
 
 use crate::server::state::ServerState;
 
-use super::auth::{handle_login, handle_logout, handle_register};
+use super::admin::{
+    handle_admin_add_user, handle_admin_remove_user, handle_admin_set_disabled, handle_admin_users,
+    handle_stats,
+};
+use super::auth::{handle_login, handle_login_token, handle_logout, handle_register};
+use super::blocklist::{handle_block, handle_get_blocklist, handle_unblock};
 use super::presence::handle_get_users;
 use super::signaling::{
-    handle_call_answer, handle_call_end, handle_call_offer, handle_call_reject, handle_ice_candidate,
+    handle_call_answer, handle_call_end, handle_call_offer, handle_call_reject,
+    handle_call_ringing, handle_call_transfer, handle_ice_candidate, handle_ice_restart,
+    handle_ice_restart_answer,
 };
 
 /// Resultado de un handler.
@@ -36,13 +43,27 @@ pub fn dispatch(
     match msg_type {
         "REGISTER" => handle_register(msg, tx, state),
         "LOGIN" => handle_login(msg, tx, state, authenticated_user),
+        "LOGIN_TOKEN" => handle_login_token(msg, tx, state, authenticated_user),
         "LOGOUT" => handle_logout(tx, state, authenticated_user),
         "GET_USERS" => handle_get_users(tx, state),
         "CALL_OFFER" => handle_call_offer(msg, tx, state, authenticated_user),
+        "CALL_RINGING" => handle_call_ringing(msg, tx, state, authenticated_user),
         "CALL_ANSWER" => handle_call_answer(msg, tx, state, authenticated_user),
         "CALL_REJECT" => handle_call_reject(msg, tx, state, authenticated_user),
         "CALL_END" => handle_call_end(msg, tx, state, authenticated_user),
+        "CALL_TRANSFER" => handle_call_transfer(msg, tx, state, authenticated_user),
         "ICE_CANDIDATE" => handle_ice_candidate(msg, tx, state, authenticated_user),
+        "ICE_RESTART" => handle_ice_restart(msg, tx, state, authenticated_user),
+        "ICE_RESTART_ANSWER" => handle_ice_restart_answer(msg, tx, state, authenticated_user),
+        "STATS" => handle_stats(tx, state, authenticated_user),
+        "ADMIN_USERS" => handle_admin_users(tx, state, authenticated_user),
+        "ADMIN_ADD_USER" => handle_admin_add_user(msg, tx, state, authenticated_user),
+        "ADMIN_REMOVE_USER" => handle_admin_remove_user(msg, tx, state, authenticated_user),
+        "ADMIN_DISABLE_USER" => handle_admin_set_disabled(msg, tx, state, authenticated_user, true),
+        "ADMIN_ENABLE_USER" => handle_admin_set_disabled(msg, tx, state, authenticated_user, false),
+        "BLOCK" => handle_block(msg, tx, state, authenticated_user),
+        "UNBLOCK" => handle_unblock(msg, tx, state, authenticated_user),
+        "GET_BLOCKLIST" => handle_get_blocklist(tx, state, authenticated_user),
         _ => {
             ServerState::send_message(
                 tx,
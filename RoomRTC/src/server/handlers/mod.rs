@@ -1,8 +1,10 @@
 //! Módulo de handlers para mensajes del protocolo de señalización.
 
+pub mod admin;
 pub mod auth;
+pub mod blocklist;
 pub mod presence;
 pub mod signaling;
 
 mod context;
-pub use context::{dispatch, HandlerResult};
+pub use context::{HandlerResult, dispatch};
@@ -1,18 +1,28 @@
 //! Handler de presencia: GET_USERS.
 
-use std::sync::mpsc::Sender;
 use std::sync::Arc;
+use std::sync::mpsc::Sender;
 
 use super::context::HandlerResult;
 use crate::server::state::ServerState;
+use crate::server::validation::protocol_value;
 
-/// Procesa el mensaje GET_USERS.
+/// Procesa el mensaje GET_USERS. Responde
+/// `USER_LIST|users:alice=AVAILABLE,bob=BUSY`, ordenado por nombre; los
+/// cambios posteriores llegan como USER_JOINED, USER_LEFT y
+/// USER_STATUS_CHANGED.
 pub fn handle_get_users(tx: &Sender<String>, state: &Arc<ServerState>) -> HandlerResult {
-    let users = state.get_user_list();
-    let mut response = String::from("USER_LIST");
-    for (username, status) in users {
-        response.push_str(&format!("|{}:{}", username, status.to_string()));
-    }
-    ServerState::send_message(tx, &response);
+    let users: Vec<String> = state
+        .get_user_list()
+        .into_iter()
+        .map(|(username, status)| format!("{}={}", list_entry(&username), status.to_string()))
+        .collect();
+    ServerState::send_message(tx, &format!("USER_LIST|users:{}", users.join(",")));
     HandlerResult::Continue
 }
+
+/// Los usuarios cargados del archivo pueden ser anteriores a las reglas: sin
+/// `,` ni `=` no pueden partir ni agregar entradas a la lista.
+fn list_entry(username: &str) -> String {
+    protocol_value(username).replace([',', '='], "_")
+}
@@ -1,12 +1,27 @@
-//! Handlers de señalización: CALL_OFFER, CALL_ANSWER, CALL_REJECT, CALL_END, ICE_CANDIDATE.
+//! Handlers de señalización: CALL_OFFER, CALL_RINGING, CALL_ANSWER, CALL_REJECT, CALL_END,
+//! CALL_TRANSFER, ICE_CANDIDATE, ICE_RESTART, ICE_RESTART_ANSWER.
 
 use std::collections::HashMap;
-use std::sync::mpsc::Sender;
 use std::sync::Arc;
+use std::sync::mpsc::Sender;
 
 use super::context::HandlerResult;
+use crate::server::protocol::{payload, payload_field};
 use crate::server::state::ServerState;
 use crate::server::types::UserStatus;
+use crate::server::validation::validate_sdp;
+
+/// Motivos (`reason`) de un CALL_ERROR por el destino de la llamada, para
+/// que el cliente muestre cada caso; `error` queda con el texto de siempre.
+pub const CALL_ERROR_NOT_FOUND: &str = "not_found";
+pub const CALL_ERROR_OFFLINE: &str = "offline";
+pub const CALL_ERROR_BUSY: &str = "busy";
+/// `to` bloqueó a quien llama; se responde antes de mirar su estado, así no
+/// se entera de si está conectado.
+pub const CALL_ERROR_NOT_PERMITTED: &str = "not_permitted";
+/// Token de `caps` de los clientes que muestran una llamada entrante sin
+/// cortar la que tienen en curso.
+pub const CALL_WAITING_CAP: &str = "waiting";
 
 /// Procesa el mensaje CALL_OFFER.
 pub fn handle_call_offer(
@@ -23,59 +38,125 @@ pub fn handle_call_offer(
         ServerState::send_message(tx, "CALL_ERROR|error:missing destination");
         return HandlerResult::Continue;
     };
-    let Some(sdp) = msg.get("sdp").cloned() else {
-        ServerState::send_message(tx, "CALL_ERROR|error:missing sdp");
-        return HandlerResult::Continue;
+    let sdp = match read_sdp(msg) {
+        Ok(Some(sdp)) => sdp,
+        Ok(None) => {
+            ServerState::send_message(tx, "CALL_ERROR|error:missing sdp");
+            return HandlerResult::Continue;
+        }
+        Err(err) => {
+            ServerState::send_message(tx, &format!("CALL_ERROR|error:{}", err));
+            return HandlerResult::Continue;
+        }
     };
     let srtp_key = msg.get("srtp_key").cloned().unwrap_or_default();
+    let caps = caps_field(msg);
+    state.set_call_waiting(caller, announces_call_waiting(msg));
 
-    let callee_status = match state.user_statuses.read() {
-        Ok(statuses) => statuses.get(&to).cloned(),
-        Err(_) => {
-            ServerState::send_message(tx, "CALL_ERROR|error:internal server error");
-            state
-                .logger
-                .error("No se pudo leer estados (lock envenenado)");
-            return HandlerResult::Continue;
-        }
+    // Si era la llamada de un traspaso, quien lo pidió se entera del error
+    let call_error = |error: &str, reason: &str| {
+        send_call_error(tx, error, reason, &to);
+        state.fail_transfer(caller, &to, error);
     };
 
+    // Al que bloqueó no le llega nada, ni siquiera el aviso del intento
+    if state.is_blocked(&to, caller) {
+        call_error("call not permitted", CALL_ERROR_NOT_PERMITTED);
+        state
+            .logger
+            .info(&format!("Llamada de {} a {} bloqueada", caller, to));
+        return HandlerResult::Continue;
+    }
+
+    let callee_status = state.statuses().get(&to).cloned();
+
+    let incoming = format!(
+        "INCOMING_CALL|from:{}|{}|srtp_key:{}{}",
+        caller,
+        payload_field("sdp", &sdp, wants_b64(state, &to)),
+        srtp_key,
+        caps
+    );
+
     if let Some(status) = callee_status {
+        if status == UserStatus::Busy && ring_call_waiting(state, caller, &to, &incoming) {
+            return HandlerResult::Continue;
+        }
         if status != UserStatus::Available {
-            ServerState::send_message(tx, "CALL_ERROR|error:User not available");
+            let reason = if status == UserStatus::Busy {
+                CALL_ERROR_BUSY
+            } else {
+                CALL_ERROR_OFFLINE
+            };
+            call_error("User not available", reason);
             return HandlerResult::Continue;
         }
 
-        let callee_sender = match state.connected_clients.read() {
-            Ok(clients) => clients.get(&to).map(|c| c.sender.clone()),
-            Err(_) => {
-                state
-                    .logger
-                    .error("No se pudo leer clientes (lock envenenado)");
-                None
-            }
-        };
+        let callee_sender = state.clients().get(&to).map(|c| c.sender.clone());
 
         if let Some(callee_sender) = callee_sender {
+            // `to` recién queda Busy al atender: mientras suena le pueden
+            // entrar otras llamadas y su cliente decide qué hacer con ellas
             state.set_user_status(caller, UserStatus::Busy);
-            state.set_user_status(&to, UserStatus::Busy);
-            if let Ok(mut calls) = state.active_calls.write() {
-                calls.insert(caller.clone(), to.clone());
-                calls.insert(to.clone(), caller.clone());
-            } else {
-                state
-                    .logger
-                    .error("No se pudo registrar llamada (lock envenenado)");
-            }
+            state.calls_mut().insert(caller.clone(), to.clone());
 
-            let msg = format!("INCOMING_CALL|from:{}|sdp:{}|srtp_key:{}", caller, sdp, srtp_key);
-            ServerState::send_message(&callee_sender, &msg);
+            ServerState::send_message(&callee_sender, &incoming);
             state.logger.info(&format!("{} llamó a {}", caller, to));
         } else {
-            ServerState::send_message(tx, "CALL_ERROR|error:user not connected");
+            call_error("user not connected", CALL_ERROR_OFFLINE);
         }
     } else {
-        ServerState::send_message(tx, "CALL_ERROR|error:User does not exist");
+        call_error("User does not exist", CALL_ERROR_NOT_FOUND);
+    }
+    HandlerResult::Continue
+}
+
+/// Hace sonar la llamada de `caller` aunque `callee` esté en otra, si su
+/// cliente lo soporta y no tiene ya a alguien esperando. La llamada en curso
+/// sigue igual hasta que `callee` atienda la nueva.
+fn ring_call_waiting(state: &Arc<ServerState>, caller: &str, callee: &str, incoming: &str) -> bool {
+    if !state.accepts_call_waiting(callee) {
+        return false;
+    }
+    let Some(callee_sender) = state.clients().get(callee).map(|c| c.sender.clone()) else {
+        return false;
+    };
+    if !state.wait_for_call(caller, callee) {
+        return false;
+    }
+    state.set_user_status(caller, UserStatus::Busy);
+    ServerState::send_message(&callee_sender, incoming);
+    state.logger.info(&format!(
+        "{} llamó a {}, que está en otra llamada",
+        caller, callee
+    ));
+    true
+}
+
+/// Procesa el mensaje CALL_RINGING: el cliente de quien recibe la llamada ya
+/// la está mostrando. Solo le llega a `to` si de verdad lo está llamando.
+pub fn handle_call_ringing(
+    msg: &HashMap<String, String>,
+    tx: &Sender<String>,
+    state: &Arc<ServerState>,
+    authenticated_user: &Option<String>,
+) -> HandlerResult {
+    let Some(callee) = authenticated_user else {
+        return HandlerResult::Continue;
+    };
+
+    let Some(caller) = msg.get("to").cloned() else {
+        ServerState::send_message(tx, "CALL_ERROR|error:missing destination");
+        return HandlerResult::Continue;
+    };
+
+    let calling = state.calls().get(&caller) == Some(callee);
+    if !calling {
+        return HandlerResult::Continue;
+    }
+    if let Some(client) = state.clients().get(&caller) {
+        let msg = format!("CALL_RINGING|from:{}", callee);
+        ServerState::send_message(&client.sender, &msg);
     }
     HandlerResult::Continue
 }
@@ -96,18 +177,18 @@ pub fn handle_call_answer(
         return HandlerResult::Continue;
     };
     let accept = msg.get("accept").map(|v| v == "true").unwrap_or(false);
-    let sdp = msg.get("sdp").cloned();
-    let srtp_key = msg.get("srtp_key").cloned().unwrap_or_default();
-
-    let caller_sender = match state.connected_clients.read() {
-        Ok(clients) => clients.get(&to).map(|c| c.sender.clone()),
-        Err(_) => {
-            state
-                .logger
-                .error("No se pudo leer clientes (lock envenenado)");
-            None
+    let sdp = match read_sdp(msg) {
+        Ok(sdp) => sdp,
+        Err(err) => {
+            ServerState::send_message(tx, &format!("CALL_ERROR|error:{}", err));
+            return HandlerResult::Continue;
         }
     };
+    let srtp_key = msg.get("srtp_key").cloned().unwrap_or_default();
+    let caps = caps_field(msg);
+    state.set_call_waiting(callee, announces_call_waiting(msg));
+
+    let caller_sender = state.clients().get(&to).map(|c| c.sender.clone());
 
     if let Some(caller_sender) = caller_sender {
         if accept {
@@ -115,10 +196,27 @@ pub fn handle_call_answer(
                 ServerState::send_message(&caller_sender, "CALL_REJECTED|from:server");
                 return HandlerResult::Continue;
             };
+            // Quien llamaba cortó antes de que lo atendieran
+            if state.calls().get(&to) != Some(callee) {
+                ServerState::send_message(tx, &format!("CALL_ENDED|from:{}", to));
+                return HandlerResult::Continue;
+            }
+            // Atendió una llamada en espera sin cortar antes la que tenía
+            if let Some(previous) = state.switch_call(callee, &to) {
+                if let Some(client) = state.clients().get(&previous) {
+                    let msg = format!("CALL_ENDED|from:{}", callee);
+                    ServerState::send_message(&client.sender, &msg);
+                }
+                state.release_user(&previous);
+            }
             state.set_user_status(callee, UserStatus::Busy);
+            state.complete_transfer(&to, callee);
             let msg = format!(
-                "CALL_ACCEPTED|from:{}|sdp:{}|srtp_key:{}",
-                callee, sdp_val, srtp_key
+                "CALL_ACCEPTED|from:{}|{}|srtp_key:{}{}",
+                callee,
+                payload_field("sdp", &sdp_val, wants_b64(state, &to)),
+                srtp_key,
+                caps
             );
             ServerState::send_message(&caller_sender, &msg);
             state.logger.info(&format!("{} aceptó la llamada", callee));
@@ -126,19 +224,66 @@ pub fn handle_call_answer(
             let msg = format!("CALL_REJECTED|from:{}", callee);
             ServerState::send_message(&caller_sender, &msg);
 
-            state.set_user_status(&to, UserStatus::Available);
-            state.set_user_status(callee, UserStatus::Available);
-            if let Ok(mut calls) = state.active_calls.write() {
-                calls.remove(&to);
-                calls.remove(callee);
-            }
+            reject_call(state, &to, callee);
+            state.fail_transfer(&to, callee, "call rejected");
             state.logger.info(&format!("{} rechazó la llamada", callee));
         }
     }
     HandlerResult::Continue
 }
 
-/// Procesa el mensaje CALL_REJECT.
+/// El SDP del mensaje, escapado (`sdp`) o en base64 (`sdp_b64`), ya
+/// decodificado y validado. El error es el código para el cliente.
+fn read_sdp(msg: &HashMap<String, String>) -> Result<Option<String>, String> {
+    let sdp = payload(msg, "sdp")?;
+    if let Some(sdp) = &sdp {
+        validate_sdp(sdp)?;
+    }
+    Ok(sdp)
+}
+
+/// Si a `username` hay que mandarle los payloads en base64.
+fn wants_b64(state: &ServerState, username: &str) -> bool {
+    state
+        .clients()
+        .get(username)
+        .is_some_and(|client| client.payload_b64)
+}
+
+/// CALL_ERROR para quien llama cuando `to` no puede atender.
+fn send_call_error(tx: &Sender<String>, error: &str, reason: &str, to: &str) {
+    ServerState::send_message(
+        tx,
+        &format!("CALL_ERROR|error:{}|reason:{}|to:{}", error, reason, to),
+    );
+}
+
+/// Saca la llamada de `caller` que `callee` no atendió. Si la rechazó en
+/// espera, `callee` sigue ocupado con la que tenía.
+fn reject_call(state: &Arc<ServerState>, caller: &str, callee: &str) {
+    state.end_call(caller, callee);
+    state.release_user(caller);
+    state.release_user(callee);
+}
+
+/// Si el cliente anunció en sus `caps` que muestra las llamadas que entran
+/// durante otra.
+fn announces_call_waiting(msg: &HashMap<String, String>) -> bool {
+    msg.get("caps")
+        .is_some_and(|caps| caps.split(',').any(|cap| cap.trim() == CALL_WAITING_CAP))
+}
+
+/// Las capacidades que anunció el cliente (`caps`), tal cual, para
+/// reenviarlas al peer. Los clientes viejos no las mandan y el peer tiene
+/// que notar que faltan, así que sin ellas no se agrega el campo.
+fn caps_field(msg: &HashMap<String, String>) -> String {
+    msg.get("caps")
+        .map(|caps| format!("|caps:{}", caps))
+        .unwrap_or_default()
+}
+
+/// Procesa el mensaje CALL_REJECT. Con `reason:busy` el cliente rechazó la
+/// llamada porque ya tiene otra, y a quien llama le llega así.
 pub fn handle_call_reject(
     msg: &HashMap<String, String>,
     tx: &Sender<String>,
@@ -154,26 +299,19 @@ pub fn handle_call_reject(
         return HandlerResult::Continue;
     };
 
-    let caller_sender = match state.connected_clients.read() {
-        Ok(clients) => clients.get(&to).map(|c| c.sender.clone()),
-        Err(_) => {
-            state
-                .logger
-                .error("No se pudo leer clientes (lock envenenado)");
-            None
-        }
+    // Otro motivo no se reenvía: el que llama no sabría qué mostrar
+    let reason = match msg.get("reason").map(String::as_str) {
+        Some(CALL_ERROR_BUSY) => format!("|reason:{}", CALL_ERROR_BUSY),
+        _ => String::new(),
     };
+    let caller_sender = state.clients().get(&to).map(|c| c.sender.clone());
     if let Some(caller_sender) = caller_sender {
-        let msg = format!("CALL_REJECTED|from:{}", callee);
+        let msg = format!("CALL_REJECTED|from:{}{}", callee, reason);
         ServerState::send_message(&caller_sender, &msg);
     }
 
-    state.set_user_status(&to, UserStatus::Available);
-    state.set_user_status(callee, UserStatus::Available);
-    if let Ok(mut calls) = state.active_calls.write() {
-        calls.remove(&to);
-        calls.remove(callee);
-    }
+    reject_call(state, &to, callee);
+    state.fail_transfer(&to, callee, "call rejected");
     state.logger.info(&format!("{} rechazó la llamada", callee));
     HandlerResult::Continue
 }
@@ -194,19 +332,20 @@ pub fn handle_call_end(
         return HandlerResult::Continue;
     };
 
-    if let Ok(clients) = state.connected_clients.read()
-        && let Some(other_client) = clients.get(&to)
-    {
-        let msg = format!("CALL_ENDED|from:{}", username);
-        ServerState::send_message(&other_client.sender, &msg);
+    // Si cortaron los dos a la vez, el segundo CALL_END encuentra la
+    // llamada ya terminada y no hace nada
+    if !state.end_call(username, &to) {
+        return HandlerResult::Continue;
     }
+    // Si cortó mientras sonaba la llamada de un traspaso, este no se hizo
+    state.fail_transfer(username, &to, "call cancelled");
 
-    state.set_user_status(username, UserStatus::Available);
-    state.set_user_status(&to, UserStatus::Available);
+    state.release_user(username);
+    state.release_user(&to);
 
-    if let Ok(mut calls) = state.active_calls.write() {
-        calls.remove(username);
-        calls.remove(&to);
+    if let Some(other_client) = state.clients().get(&to) {
+        let msg = format!("CALL_ENDED|from:{}", username);
+        ServerState::send_message(&other_client.sender, &msg);
     }
     state
         .logger
@@ -214,6 +353,93 @@ pub fn handle_call_end(
     HandlerResult::Continue
 }
 
+/// Procesa el mensaje CALL_TRANSFER: el usuario autenticado pasa su llamada
+/// con `to` al usuario `target`.
+///
+/// Si el target no está disponible o alguno de los dos no está conectado, la
+/// llamada original sigue intacta y solo se avisa con TRANSFER_FAILED. Si
+/// después el target rechaza la llamada o se desconecta antes de atender,
+/// `to` recibe el rechazo o el CALL_ENDED de siempre y quien traspasó un
+/// TRANSFER_FAILED.
+pub fn handle_call_transfer(
+    msg: &HashMap<String, String>,
+    tx: &Sender<String>,
+    state: &Arc<ServerState>,
+    authenticated_user: &Option<String>,
+) -> HandlerResult {
+    let Some(transferor) = authenticated_user else {
+        return HandlerResult::Continue;
+    };
+
+    let Some(peer) = msg.get("to").cloned() else {
+        ServerState::send_message(tx, "CALL_ERROR|error:missing destination");
+        return HandlerResult::Continue;
+    };
+    let Some(target) = msg.get("target").cloned() else {
+        ServerState::send_message(tx, "CALL_ERROR|error:missing target");
+        return HandlerResult::Continue;
+    };
+
+    let fail = |error: &str| {
+        let msg = format!("TRANSFER_FAILED|target:{}|error:{}", target, error);
+        ServerState::send_message(tx, &msg);
+        HandlerResult::Continue
+    };
+
+    if &target == transferor || target == peer {
+        return fail("invalid transfer target");
+    }
+    if state.is_blocked(&target, &peer) {
+        return fail("call not permitted");
+    }
+
+    // El lock de llamadas se mantiene hasta terminar el traspaso para que un
+    // CALL_END o una desconexión concurrente no dejen estados a medias.
+    let mut calls = state.calls_mut();
+    if calls.get(transferor) != Some(&peer) {
+        return fail("not in a call with that user");
+    }
+
+    let target_status = state.statuses().get(&target).cloned();
+    match target_status {
+        Some(UserStatus::Available) => {}
+        Some(_) => return fail("User not available"),
+        None => return fail("User does not exist"),
+    }
+
+    let (peer_sender, target_connected) = {
+        let clients = state.clients();
+        (
+            clients.get(&peer).map(|c| c.sender.clone()),
+            clients.contains_key(&target),
+        )
+    };
+    let Some(peer_sender) = peer_sender else {
+        return fail("user not connected");
+    };
+    if !target_connected {
+        return fail("user not connected");
+    }
+
+    // El peer queda libre y su CALL_OFFER al target lo vuelve a marcar Busy;
+    // si nunca llega la oferta, nadie queda trabado en Busy.
+    calls.remove(transferor);
+    calls.remove(&peer);
+    state.set_user_status(transferor, UserStatus::Available);
+    state.set_user_status(&peer, UserStatus::Available);
+    drop(calls);
+    state.start_transfer(&peer, transferor, &target);
+
+    let msg = format!("TRANSFER_REQUESTED|from:{}|to:{}", transferor, target);
+    ServerState::send_message(&peer_sender, &msg);
+    ServerState::send_message(tx, &format!("CALL_TRANSFERRED|to:{}", target));
+    state.logger.info(&format!(
+        "{} transfirió la llamada con {} a {}",
+        transferor, peer, target
+    ));
+    HandlerResult::Continue
+}
+
 /// Procesa el mensaje ICE_CANDIDATE.
 pub fn handle_ice_candidate(
     msg: &HashMap<String, String>,
@@ -229,16 +455,93 @@ pub fn handle_ice_candidate(
         ServerState::send_message(tx, "ERROR|error:missing destination");
         return HandlerResult::Continue;
     };
-    let Some(candidate) = msg.get("candidate").cloned() else {
-        ServerState::send_message(tx, "ERROR|error:missing candidate");
+    let candidate = match payload(msg, "candidate") {
+        Ok(Some(candidate)) => candidate,
+        Ok(None) => {
+            ServerState::send_message(tx, "ERROR|error:missing candidate");
+            return HandlerResult::Continue;
+        }
+        Err(err) => {
+            ServerState::send_message(tx, &format!("ERROR|error:{}", err));
+            return HandlerResult::Continue;
+        }
+    };
+
+    if let Some(to_client) = state.clients().get(&to) {
+        let msg = format!(
+            "ICE_CANDIDATE|from:{}|{}",
+            from,
+            payload_field("candidate", &candidate, to_client.payload_b64)
+        );
+        ServerState::send_message(&to_client.sender, &msg);
+    }
+    HandlerResult::Continue
+}
+
+/// Procesa el mensaje ICE_RESTART: la oferta de reinicio de ICE de una
+/// llamada en curso. Sin `sdp` es el pedido de que el otro lado, el que
+/// controla ICE, mande la oferta.
+pub fn handle_ice_restart(
+    msg: &HashMap<String, String>,
+    tx: &Sender<String>,
+    state: &Arc<ServerState>,
+    authenticated_user: &Option<String>,
+) -> HandlerResult {
+    relay_ice_restart("ICE_RESTART", msg, tx, state, authenticated_user)
+}
+
+/// Procesa el mensaje ICE_RESTART_ANSWER: la respuesta a un ICE_RESTART.
+pub fn handle_ice_restart_answer(
+    msg: &HashMap<String, String>,
+    tx: &Sender<String>,
+    state: &Arc<ServerState>,
+    authenticated_user: &Option<String>,
+) -> HandlerResult {
+    if !msg.contains_key("sdp") && !msg.contains_key("sdp_b64") {
+        ServerState::send_message(tx, "ERROR|error:missing sdp");
+        return HandlerResult::Continue;
+    }
+    relay_ice_restart("ICE_RESTART_ANSWER", msg, tx, state, authenticated_user)
+}
+
+/// Reenvía el reinicio al otro participante, solo si los dos están en una
+/// llamada entre ellos.
+fn relay_ice_restart(
+    msg_type: &str,
+    msg: &HashMap<String, String>,
+    tx: &Sender<String>,
+    state: &Arc<ServerState>,
+    authenticated_user: &Option<String>,
+) -> HandlerResult {
+    let Some(from) = authenticated_user else {
+        return HandlerResult::Continue;
+    };
+
+    let Some(to) = msg.get("to").cloned() else {
+        ServerState::send_message(tx, "ERROR|error:missing destination");
         return HandlerResult::Continue;
     };
+    let sdp = match read_sdp(msg) {
+        Ok(sdp) => sdp,
+        Err(err) => {
+            ServerState::send_message(tx, &format!("ERROR|error:{}", err));
+            return HandlerResult::Continue;
+        }
+    };
+    if state.calls().get(from) != Some(&to) {
+        ServerState::send_message(tx, "ERROR|error:not in a call with that user");
+        return HandlerResult::Continue;
+    }
 
-    if let Ok(clients) = state.connected_clients.read()
-        && let Some(to_client) = clients.get(&to)
-    {
-        let msg = format!("ICE_CANDIDATE|from:{}|candidate:{}", from, candidate);
+    if let Some(to_client) = state.clients().get(&to) {
+        let sdp_field = sdp
+            .map(|sdp| format!("|{}", payload_field("sdp", &sdp, to_client.payload_b64)))
+            .unwrap_or_default();
+        let msg = format!("{}|from:{}{}", msg_type, from, sdp_field);
         ServerState::send_message(&to_client.sender, &msg);
     }
+    state
+        .logger
+        .info(&format!("{}: {} -> {}", msg_type, from, to));
     HandlerResult::Continue
 }
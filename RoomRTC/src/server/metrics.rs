@@ -0,0 +1,246 @@
+//! Métricas del servidor en el formato de texto de Prometheus, servidas por
+//! un listener HTTP mínimo (`GET /metrics`) aparte del de señalización.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use super::state::ServerState;
+
+/// Tipos de mensaje que se cuentan por separado; el resto va a `other`, así
+/// un cliente que inventa tipos no hace crecer las series.
+const COUNTED_TYPES: [&str; 23] = [
+    "REGISTER",
+    "LOGIN",
+    "LOGIN_TOKEN",
+    "LOGOUT",
+    "GET_USERS",
+    "CALL_OFFER",
+    "CALL_RINGING",
+    "CALL_ANSWER",
+    "CALL_REJECT",
+    "CALL_END",
+    "CALL_TRANSFER",
+    "ICE_CANDIDATE",
+    "ICE_RESTART",
+    "ICE_RESTART_ANSWER",
+    "STATS",
+    "ADMIN_USERS",
+    "ADMIN_ADD_USER",
+    "ADMIN_REMOVE_USER",
+    "ADMIN_DISABLE_USER",
+    "ADMIN_ENABLE_USER",
+    "BLOCK",
+    "UNBLOCK",
+    "GET_BLOCKLIST",
+];
+
+/// Lo mismo para los mensajes que el servidor manda a los clientes.
+const SENT_TYPES: [&str; 27] = [
+    "REGISTER_SUCCESS",
+    "REGISTER_ERROR",
+    "LOGIN_SUCCESS",
+    "LOGIN_ERROR",
+    "LOGOUT_SUCCESS",
+    "USER_LIST",
+    "USER_STATUS_CHANGED",
+    "USER_JOINED",
+    "USER_LEFT",
+    "INCOMING_CALL",
+    "CALL_RINGING",
+    "CALL_ACCEPTED",
+    "CALL_REJECTED",
+    "CALL_ENDED",
+    "CALL_ERROR",
+    "CALL_TRANSFERRED",
+    "TRANSFER_REQUESTED",
+    "TRANSFER_FAILED",
+    "ICE_CANDIDATE",
+    "STATS",
+    "STATS_ERROR",
+    "ADMIN_USERS",
+    "ADMIN_ERROR",
+    "BLOCKLIST",
+    "BLOCK_ERROR",
+    "SESSION_CLOSED",
+    "ERROR",
+];
+
+/// Plazo para leer el pedido y escribir la respuesta: un scraper lento no
+/// traba al siguiente.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Lo que se lee de la línea del pedido; el resto de los headers se ignora.
+const MAX_REQUEST_LINE: u64 = 1024;
+
+/// Contadores que actualizan los hilos de los clientes; solo atómicos, sin
+/// locks en el camino de la señalización.
+#[derive(Default)]
+pub struct ServerMetrics {
+    messages: [AtomicU64; COUNTED_TYPES.len() + 1],
+    sent: [AtomicU64; SENT_TYPES.len() + 1],
+    bytes_forwarded: AtomicU64,
+}
+
+impl ServerMetrics {
+    pub fn record_message(&self, msg_type: &str) {
+        self.messages[type_index(&COUNTED_TYPES, msg_type)].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Cuenta un mensaje escrito a un cliente, por el tipo de `msg`.
+    pub fn record_sent(&self, msg: &str) {
+        let msg_type = msg.split('|').next().unwrap_or_default();
+        self.sent[type_index(&SENT_TYPES, msg_type)].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Bytes escritos a un cliente: señalización reenviada y respuestas.
+    pub fn record_bytes_forwarded(&self, bytes: usize) {
+        self.bytes_forwarded
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn messages(&self, msg_type: &str) -> u64 {
+        self.messages[type_index(&COUNTED_TYPES, msg_type)].load(Ordering::Relaxed)
+    }
+
+    pub fn sent(&self, msg_type: &str) -> u64 {
+        self.sent[type_index(&SENT_TYPES, msg_type)].load(Ordering::Relaxed)
+    }
+
+    pub fn bytes_forwarded(&self) -> u64 {
+        self.bytes_forwarded.load(Ordering::Relaxed)
+    }
+}
+
+/// Posición del contador de `msg_type` en `types`; la última es la de `other`.
+fn type_index(types: &[&str], msg_type: &str) -> usize {
+    types
+        .iter()
+        .position(|counted| *counted == msg_type)
+        .unwrap_or(types.len())
+}
+
+/// Arma el cuerpo de `/metrics`.
+pub fn render(state: &ServerState) -> String {
+    let stats = state.stats();
+    let metrics = state.metrics();
+    let mut out = String::new();
+
+    let mut metric = |name: &str, kind: &str, help: &str, value: u64| {
+        out.push_str(&format!(
+            "# HELP {name} {help}\n# TYPE {name} {kind}\n{name} {value}\n"
+        ));
+    };
+    metric(
+        "roomrtc_connected_clients",
+        "gauge",
+        "Clients logged in.",
+        stats.connected_clients as u64,
+    );
+    metric(
+        "roomrtc_active_calls",
+        "gauge",
+        "Calls in progress.",
+        stats.active_calls as u64,
+    );
+    metric(
+        "roomrtc_logins_total",
+        "counter",
+        "Successful logins.",
+        stats.total_logins,
+    );
+    metric(
+        "roomrtc_auth_failures_total",
+        "counter",
+        "Logins rejected by password or token.",
+        stats.failed_logins,
+    );
+    metric(
+        "roomrtc_rejected_connections_total",
+        "counter",
+        "Connections refused at accept.",
+        stats.rejected_connections,
+    );
+    metric(
+        "roomrtc_bytes_forwarded_total",
+        "counter",
+        "Bytes written to clients, forwarded signaling and replies.",
+        metrics.bytes_forwarded(),
+    );
+    metric(
+        "roomrtc_uptime_seconds",
+        "gauge",
+        "Seconds since the server started.",
+        stats.uptime.as_secs(),
+    );
+
+    out.push_str("# HELP roomrtc_messages_total Messages received by type.\n");
+    out.push_str("# TYPE roomrtc_messages_total counter\n");
+    for msg_type in COUNTED_TYPES.iter().chain(["other"].iter()) {
+        out.push_str(&format!(
+            "roomrtc_messages_total{{type=\"{}\"}} {}\n",
+            msg_type,
+            metrics.messages(msg_type)
+        ));
+    }
+
+    out.push_str("# HELP roomrtc_messages_sent_total Messages sent to clients by type.\n");
+    out.push_str("# TYPE roomrtc_messages_sent_total counter\n");
+    for msg_type in SENT_TYPES.iter().chain(["other"].iter()) {
+        out.push_str(&format!(
+            "roomrtc_messages_sent_total{{type=\"{}\"}} {}\n",
+            msg_type,
+            metrics.sent(msg_type)
+        ));
+    }
+    out
+}
+
+/// Atiende los pedidos de métricas de a uno, en el hilo que lo llame. Vuelve
+/// solo si el listener deja de aceptar.
+pub fn serve(listener: TcpListener, state: Arc<ServerState>) {
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                if let Err(err) = answer(stream, &state) {
+                    state
+                        .logger
+                        .warn(&format!("Pedido de métricas fallido: {}", err));
+                }
+            }
+            Err(err) => {
+                state
+                    .logger
+                    .error(&format!("Error aceptando pedido de métricas: {}", err));
+            }
+        }
+    }
+}
+
+fn answer(stream: TcpStream, state: &ServerState) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    stream.set_write_timeout(Some(REQUEST_TIMEOUT))?;
+    let mut reader = BufReader::new(stream);
+    let mut request_line = String::new();
+    (&mut reader)
+        .take(MAX_REQUEST_LINE)
+        .read_line(&mut request_line)?;
+
+    let mut parts = request_line.split_whitespace();
+    let (status, body) = match (parts.next(), parts.next()) {
+        (Some("GET"), Some("/metrics")) => ("200 OK", render(state)),
+        (Some("GET"), _) => ("404 Not Found", "not found\n".to_string()),
+        _ => ("405 Method Not Allowed", "method not allowed\n".to_string()),
+    };
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    let stream = reader.get_mut();
+    stream.write_all(response.as_bytes())?;
+    stream.flush()
+}
@@ -1,26 +1,78 @@
 //! Servidor de señalización RoomRTC.
 //!
-//! Este módulo contiene el loop principal del cliente y reexports de todos los submódulos.
+//! Este módulo contiene el loop de aceptación (`run`), el loop principal del
+//! cliente y reexports de todos los submódulos.
 
 pub mod handlers;
+pub mod metrics;
 pub mod protocol;
 pub mod state;
 pub mod tls;
 pub mod types;
+pub mod user_store;
+pub mod users_crypto;
 pub mod validation;
 
-use std::io::{BufRead, BufReader, ErrorKind};
-use std::net::{SocketAddr, TcpStream};
-use std::sync::mpsc;
+#[cfg(test)]
+mod tests;
+
+use std::io::{BufRead, BufReader, ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::panic::{self, AssertUnwindSafe};
 use std::sync::Arc;
+use std::sync::mpsc;
+use std::thread;
 use std::time::Duration;
 
 use rustls::{ServerConfig, ServerConnection, StreamOwned};
 
-use handlers::{dispatch, HandlerResult};
-use protocol::{flush_outgoing, parse_message};
+use handlers::{HandlerResult, dispatch};
+use protocol::{flush_outgoing, parse_message, redact_for_log};
 use state::ServerState;
-use types::{TlsStream, UserStatus};
+use types::TlsStream;
+use validation::{MAX_LINE_LEN, validate_message};
+
+/// Acepta conexiones en `listener` y atiende cada una en su propio hilo,
+/// sobre TLS. Las que llegan con `state.max_clients()` clientes logueados se
+/// cortan. Vuelve solo si el listener deja de aceptar.
+pub fn run(listener: TcpListener, state: Arc<ServerState>, tls_config: Arc<ServerConfig>) {
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                let addr = match stream.peer_addr() {
+                    Ok(a) => a,
+                    Err(e) => {
+                        state
+                            .logger
+                            .error(&format!("No se pudo obtener addr del cliente: {}", e));
+                        continue;
+                    }
+                };
+
+                // Limitar conexiones concurrentes
+                if state.clients().len() >= state.max_clients() {
+                    state.logger.warn(&format!(
+                        "Capacidad máxima alcanzada, rechazando conexión de {}",
+                        addr
+                    ));
+                    state.record_rejected_connection();
+                    continue;
+                }
+
+                let state = Arc::clone(&state);
+                let tls_config = Arc::clone(&tls_config);
+                thread::spawn(move || {
+                    handle_client(stream, addr, state, tls_config);
+                });
+            }
+            Err(e) => {
+                state
+                    .logger
+                    .error(&format!("Error aceptando conexión: {}", e));
+            }
+        }
+    }
+}
 
 /// Maneja una conexión de cliente individual.
 pub fn handle_client(
@@ -29,37 +81,81 @@ pub fn handle_client(
     state: Arc<ServerState>,
     tls_config: Arc<ServerConfig>,
 ) {
-    println!("New connection from: {}", addr);
+    state
+        .logger
+        .debug(&format!("Nueva conexión desde {}", addr));
     let _ = stream.set_read_timeout(Some(Duration::from_millis(200)));
 
     let server_conn = match ServerConnection::new(tls_config) {
         Ok(conn) => conn,
         Err(err) => {
-            eprintln!("Error creating TLS connection: {}", err);
+            state
+                .logger
+                .error(&format!("Error creando la conexión TLS: {}", err));
             return;
         }
     };
 
     let tls_stream: TlsStream = StreamOwned::new(server_conn, stream);
-    let mut reader = BufReader::new(tls_stream);
+    serve_client(tls_stream, addr, state);
+}
+
+/// Loop de mensajes de un cliente sobre cualquier stream ya establecido (TLS
+/// en producción, TCP plano en los tests). El stream debe tener un timeout
+/// de lectura para poder despachar los mensajes salientes entre lecturas.
+pub fn serve_client<S: Read + Write>(stream: S, addr: SocketAddr, state: Arc<ServerState>) {
+    let mut reader = BufReader::new(stream);
     let (tx, rx) = mpsc::channel::<String>();
     let mut authenticated_user: Option<String> = None;
 
     loop {
-        if let Err(e) = flush_outgoing(&mut reader, &rx) {
-            eprintln!("Error sending message: {}", e);
+        // Un admin borró o deshabilitó la cuenta: la sesión abierta se corta
+        if let Some(username) = &authenticated_user
+            && !state.account_active(username)
+        {
+            state.logger.warn(&format!(
+                "Se cierra la sesión de {}: la cuenta ya no está habilitada",
+                username
+            ));
+            ServerState::send_message(&tx, "SESSION_CLOSED|reason:account disabled");
+            let _ = flush(&mut reader, &rx, &state, addr, &authenticated_user);
             break;
         }
 
+        match flush(&mut reader, &rx, &state, addr, &authenticated_user) {
+            Ok(written) => state.metrics().record_bytes_forwarded(written),
+            Err(e) => {
+                state
+                    .logger
+                    .warn(&format!("Error enviando a {}: {}", addr, e));
+                break;
+            }
+        }
+
         let mut line = String::new();
-        match reader.read_line(&mut line) {
+        // Acotamos la lectura para que una línea gigante no agote la memoria
+        match (&mut reader)
+            .take(MAX_LINE_LEN as u64 + 1)
+            .read_line(&mut line)
+        {
             Ok(0) => break,
+            Ok(n) if n > MAX_LINE_LEN => {
+                state.logger.warn(&format!(
+                    "Mensaje demasiado largo desde {}, desconectando",
+                    addr
+                ));
+                ServerState::send_message(&tx, "ERROR|error:message too long");
+                let _ = flush(&mut reader, &rx, &state, addr, &authenticated_user);
+                break;
+            }
             Ok(_) => {}
             Err(e) if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut => {
                 continue;
             }
             Err(e) => {
-                println!("Error reading line: {}", e);
+                state
+                    .logger
+                    .warn(&format!("Error leyendo de {}: {}", addr, e));
                 break;
             }
         }
@@ -69,36 +165,98 @@ pub fn handle_client(
             continue;
         }
 
+        trace_message(&state, "recibido de", addr, &authenticated_user, trimmed);
         let msg = parse_message(trimmed);
-        let result = dispatch(&msg, &tx, &state, &mut authenticated_user);
-
-        if result == HandlerResult::Disconnect {
+        if let Err(err) = validate_message(&msg) {
+            state
+                .logger
+                .warn(&format!("Mensaje inválido desde {}: {}", addr, err));
+            ServerState::send_message(&tx, "ERROR|error:malformed message");
+            let _ = flush(&mut reader, &rx, &state, addr, &authenticated_user);
             break;
         }
+        if let Some(msg_type) = msg.get("type") {
+            state.metrics().record_message(msg_type);
+        }
+        // Un panic en un handler corta solo esta conexión: se hace la misma
+        // limpieza que en una desconexión y los locks se recuperan solos.
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            dispatch(&msg, &tx, &state, &mut authenticated_user)
+        }));
+        match result {
+            Ok(HandlerResult::Continue) => {}
+            Ok(HandlerResult::Disconnect) => break,
+            Err(_) => {
+                state
+                    .logger
+                    .error(&format!("Panic atendiendo a {}, desconectando", addr));
+                ServerState::send_message(&tx, "ERROR|error:internal server error");
+                let _ = flush(&mut reader, &rx, &state, addr, &authenticated_user);
+                break;
+            }
+        }
     }
 
     // Cleanup al desconectar
     if let Some(username) = authenticated_user {
-        println!("Client {} disconnected", username);
-        if let Ok(mut guard) = state.connected_clients.write() {
-            guard.remove(&username);
+        // Después de un LOGOUT el cliente ya no está, y una cuenta
+        // deshabilitada no puede volver: no hay a qué esperar
+        let dropped = state.clients_mut().remove(&username).is_some();
+        let grace = state.reconnect_grace();
+        if !dropped || grace.is_zero() || !state.account_active(&username) {
+            state.finish_disconnect(&username);
+            return;
         }
-        state.set_user_status(&username, UserStatus::Disconnected);
-        state.logger.warn(&format!("{} se desconectó", username));
-
-        // Si estaba en llamada, notificar al otro
-        if let Ok(mut calls) = state.active_calls.write()
-            && let Some(other) = calls.remove(&username)
-        {
-            calls.remove(&other);
-            state.set_user_status(&other, UserStatus::Available);
-
-            if let Ok(clients) = state.connected_clients.read()
-                && let Some(other_client) = clients.get(&other)
-            {
-                let msg = format!("CALL_ENDED|from:{}", username);
-                ServerState::send_message(&other_client.sender, &msg);
+        // Un corte corto no debería tirar la llamada: la sesión espera a que
+        // vuelva con su token
+        let hold_id = state.hold_session(&username);
+        state.logger.warn(&format!(
+            "{} se cayó, se guarda su sesión {} s",
+            username,
+            grace.as_secs()
+        ));
+        thread::spawn(move || {
+            thread::sleep(grace);
+            if state.release_held_session(&username, hold_id) {
+                state.finish_disconnect(&username);
             }
-        }
+        });
+    }
+}
+
+/// Escribe los mensajes pendientes del cliente, contándolos por tipo.
+fn flush<S: Write>(
+    reader: &mut BufReader<S>,
+    rx: &mpsc::Receiver<String>,
+    state: &ServerState,
+    addr: SocketAddr,
+    user: &Option<String>,
+) -> std::io::Result<usize> {
+    flush_outgoing(reader, rx, |msg| {
+        state.metrics().record_sent(msg);
+        trace_message(state, "enviado a", addr, user, msg);
+    })
+}
+
+/// Con `trace_messages` activo, loguea el mensaje con su dirección, el
+/// usuario (o `-` si todavía no entró) y el tipo.
+fn trace_message(
+    state: &ServerState,
+    direction: &str,
+    addr: SocketAddr,
+    user: &Option<String>,
+    msg: &str,
+) {
+    if !state.trace_messages() {
+        return;
     }
+    let msg_type = msg.split('|').next().unwrap_or_default();
+    state.logger.info(&format!(
+        "Mensaje {} {} ({}) [{}]: {}",
+        direction,
+        user.as_deref().unwrap_or("-"),
+        addr,
+        msg_type,
+        redact_for_log(msg)
+    ));
 }
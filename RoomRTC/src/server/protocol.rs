@@ -4,7 +4,17 @@ use std::collections::HashMap;
 use std::io::{self, BufReader, Write};
 use std::sync::mpsc::Receiver;
 
-use super::types::TlsStream;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+
+use super::validation::INVALID_PAYLOAD;
+
+/// Capacidad que el servidor anuncia en `features` del LOGIN_SUCCESS y el
+/// cliente en el LOGIN: entiende los payloads en base64 (`sdp_b64`,
+/// `candidate_b64`), que pueden llevar `|`, `:` o cualquier byte.
+pub const FEATURE_B64: &str = "b64";
+/// Sufijo del nombre de un campo de payload cuando viaja en base64.
+pub const B64_SUFFIX: &str = "_b64";
 
 /// Parsea un mensaje del protocolo en formato "TYPE|key:value|key:value".
 pub fn parse_message(msg: &str) -> HashMap<String, String> {
@@ -26,13 +36,206 @@ pub fn parse_message(msg: &str) -> HashMap<String, String> {
     map
 }
 
-/// Envía todos los mensajes pendientes en el canal al stream TLS.
-pub fn flush_outgoing(reader: &mut BufReader<TlsStream>, rx: &Receiver<String>) -> io::Result<()> {
+/// Si la lista `features` del mensaje incluye `feature`.
+pub fn has_feature(msg: &HashMap<String, String>, feature: &str) -> bool {
+    msg.get("features")
+        .is_some_and(|features| features.split(',').any(|f| f == feature))
+}
+
+/// Escapa un payload para mandarlo como `key:value`: `\`, `\n` y `\r`.
+/// No cubre `|`; un payload que pueda tenerlo tiene que ir en base64.
+pub fn escape_payload(data: &str) -> String {
+    let mut out = String::with_capacity(data.len());
+    for ch in data.chars() {
+        match ch {
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            _ => out.push(ch),
+        }
+    }
+    out
+}
+
+/// Inverso de [`escape_payload`].
+pub fn unescape_payload(raw: &str) -> String {
+    let mut out = String::with_capacity(raw.len());
+    let mut chars = raw.chars();
+    while let Some(ch) = chars.next() {
+        if ch == '\\' {
+            match chars.next() {
+                Some('n') => out.push('\n'),
+                Some('r') => out.push('\r'),
+                Some('\\') => out.push('\\'),
+                Some(other) => {
+                    out.push(other);
+                }
+                None => break,
+            }
+        } else {
+            out.push(ch);
+        }
+    }
+    out
+}
+
+/// Campo `key` con `data`: `key_b64` en base64 si `b64`, o `key` escapado
+/// como entienden los clientes y servidores viejos.
+pub fn payload_field(key: &str, data: &str, b64: bool) -> String {
+    if b64 {
+        format!("{}{}:{}", key, B64_SUFFIX, STANDARD.encode(data))
+    } else {
+        format!("{}:{}", key, escape_payload(data))
+    }
+}
+
+/// El payload `key` del mensaje, prefiriendo `key_b64` si vinieron los dos.
+/// `Ok(None)` si no vino ninguno; [`INVALID_PAYLOAD`] si el base64 no es
+/// válido o no es UTF-8.
+pub fn payload(msg: &HashMap<String, String>, key: &str) -> Result<Option<String>, String> {
+    if let Some(encoded) = msg.get(&format!("{}{}", key, B64_SUFFIX)) {
+        let bytes = STANDARD
+            .decode(encoded)
+            .map_err(|_| INVALID_PAYLOAD.to_string())?;
+        let data = String::from_utf8(bytes).map_err(|_| INVALID_PAYLOAD.to_string())?;
+        return Ok(Some(data));
+    }
+    Ok(msg.get(key).map(|raw| unescape_payload(raw)))
+}
+
+/// Envía todos los mensajes pendientes en el canal al stream del cliente y
+/// devuelve cuántos bytes escribió. `on_sent` se llama con cada mensaje
+/// escrito.
+pub fn flush_outgoing<S: Write>(
+    reader: &mut BufReader<S>,
+    rx: &Receiver<String>,
+    mut on_sent: impl FnMut(&str),
+) -> io::Result<usize> {
+    let mut written = 0;
     while let Ok(msg) = rx.try_recv() {
         let stream = reader.get_mut();
         stream.write_all(msg.as_bytes())?;
         stream.write_all(b"\n")?;
         stream.flush()?;
+        written += msg.len() + 1;
+        on_sent(&msg);
+    }
+    Ok(written)
+}
+
+/// Campos que nunca se escriben en un log.
+const REDACTED_FIELDS: [&str; 2] = ["password", "token"];
+
+/// Largo máximo de un SDP en el log; lo que sigue se resume.
+pub const MAX_LOGGED_SDP: usize = 120;
+
+/// Versión de un mensaje del protocolo apta para el log: contraseñas y
+/// tokens se reemplazan por `***` y los SDP se cortan a
+/// [`MAX_LOGGED_SDP`] bytes. Todo log de mensajes tiene que pasar por acá.
+pub fn redact_for_log(msg: &str) -> String {
+    msg.split('|')
+        .map(|part| match part.split_once(':') {
+            Some((key, _)) if REDACTED_FIELDS.contains(&key) => format!("{}:***", key),
+            Some((key, value))
+                if (key == "sdp" || key == "sdp_b64") && value.len() > MAX_LOGGED_SDP =>
+            {
+                let mut end = MAX_LOGGED_SDP;
+                while !value.is_char_boundary(end) {
+                    end -= 1;
+                }
+                format!("{}:{}...({} bytes)", key, &value[..end], value.len())
+            }
+            _ => part.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("|")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact_hides_passwords_and_tokens() {
+        assert_eq!(
+            redact_for_log("LOGIN|username:alice|password:alice:secret"),
+            "LOGIN|username:alice|password:***"
+        );
+        assert_eq!(
+            redact_for_log("LOGIN_SUCCESS|message:Login success|token:abc123"),
+            "LOGIN_SUCCESS|message:Login success|token:***"
+        );
+    }
+
+    #[test]
+    fn test_redact_truncates_long_sdp() {
+        let sdp = "v=0".repeat(100);
+        let redacted = redact_for_log(&format!("CALL_OFFER|to:bob|sdp:{}|caps:v1", sdp));
+        assert_eq!(
+            redacted,
+            format!(
+                "CALL_OFFER|to:bob|sdp:{}...(300 bytes)|caps:v1",
+                &sdp[..MAX_LOGGED_SDP]
+            )
+        );
+        assert_eq!(redact_for_log("CALL_OFFER|sdp:v=0"), "CALL_OFFER|sdp:v=0");
+    }
+
+    #[test]
+    fn test_b64_payload_survives_protocol_delimiters() {
+        let sdp = "v=0\r\no=- 1 1 IN IP4 10.0.0.1\r\ns=Sala|Reunión: café ☕\r\na=x:\\|y\r\n";
+        let line = format!(
+            "CALL_OFFER|to:bob|{}|caps:v1",
+            payload_field("sdp", sdp, true)
+        );
+        let msg = parse_message(&line);
+        assert_eq!(msg.get("caps").map(String::as_str), Some("v1"));
+        assert_eq!(payload(&msg, "sdp"), Ok(Some(sdp.to_string())));
+    }
+
+    #[test]
+    fn test_escaped_payload_still_accepted() {
+        let sdp = "v=0\r\na=fingerprint:sha-256 AB:CD\r\ns=Reunión\\x";
+        let msg = parse_message(&format!(
+            "CALL_OFFER|to:bob|{}",
+            payload_field("sdp", sdp, false)
+        ));
+        assert_eq!(payload(&msg, "sdp"), Ok(Some(sdp.to_string())));
+        assert_eq!(payload(&msg, "candidate"), Ok(None));
+    }
+
+    #[test]
+    fn test_b64_variant_wins_and_garbage_is_rejected() {
+        let line = format!(
+            "CALL_ACCEPTED|sdp:old|{}",
+            payload_field("sdp", "new", true)
+        );
+        assert_eq!(
+            payload(&parse_message(&line), "sdp"),
+            Ok(Some("new".to_string()))
+        );
+
+        let msg = parse_message("CALL_ACCEPTED|sdp_b64:no es base64");
+        assert_eq!(payload(&msg, "sdp"), Err(INVALID_PAYLOAD.to_string()));
+        let not_utf8 = format!("CALL_ACCEPTED|sdp_b64:{}", STANDARD.encode([0xff, 0xfe]));
+        assert_eq!(
+            payload(&parse_message(&not_utf8), "sdp"),
+            Err(INVALID_PAYLOAD.to_string())
+        );
+    }
+
+    #[test]
+    fn test_features_list() {
+        let msg = parse_message("LOGIN_SUCCESS|message:Login success|features:b64,zstd");
+        assert!(has_feature(&msg, FEATURE_B64));
+        assert!(!has_feature(&msg, "b6"));
+        assert!(!has_feature(&parse_message("LOGIN_SUCCESS"), FEATURE_B64));
+    }
+
+    #[test]
+    fn test_redact_leaves_other_messages_alone() {
+        let msg = "CALL_ERROR|error:user busy|reason:busy|to:bob";
+        assert_eq!(redact_for_log(msg), msg);
+        assert_eq!(redact_for_log("LOGOUT"), "LOGOUT");
     }
-    Ok(())
 }
@@ -1,103 +1,515 @@
 //! Estado global del servidor de señalización.
 
-use std::collections::HashMap;
-use std::fs::{File, OpenOptions};
-use std::io::{self, BufRead, BufReader, Write};
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::mpsc::Sender;
-use std::sync::RwLock;
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::time::{Duration, Instant};
+
+use rand::Rng;
 
 use crate::config::AppConfig;
 use crate::logger::Logger;
 
+use super::metrics::ServerMetrics;
 use super::types::{ConnectedClient, User, UserStatus};
-use super::validation::{validate_password, validate_username};
+use super::user_store::{Blocklists, FileUserStore, UserStore};
+use super::validation::{
+    protocol_value, validate_password, validate_password_format, validate_username,
+};
+
+/// Error del LOGIN de una cuenta que un admin deshabilitó.
+pub const ACCOUNT_DISABLED: &str = "Account disabled";
+
+/// Cuánto vale un token de sesión desde que se emite.
+pub const SESSION_TOKEN_TTL: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// Sesión recordada por un cliente: permite volver a entrar con
+/// `LOGIN_TOKEN` sin mandar la contraseña.
+struct Session {
+    username: String,
+    expires_at: Instant,
+}
+
+/// Llamada traspasada que todavía espera a que el target atienda.
+struct PendingTransfer {
+    transferor: String,
+    target: String,
+}
+
+/// Foto de los contadores del servidor para el mensaje STATS.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServerStats {
+    pub connected_clients: usize,
+    pub active_calls: usize,
+    pub total_logins: u64,
+    pub failed_logins: u64,
+    pub rejected_connections: u64,
+    pub uptime: Duration,
+}
 
 /// Estado compartido del servidor.
+///
+/// Los mapas se acceden solo con los métodos `users()`, `clients_mut()`,
+/// etc., que recuperan un lock envenenado: si un hilo de cliente hace panic
+/// con un lock tomado, el resto del servidor sigue andando.
 pub struct ServerState {
-    pub users_file: String,
-    pub users: RwLock<HashMap<String, User>>,
-    pub connected_clients: RwLock<HashMap<String, ConnectedClient>>,
-    pub user_statuses: RwLock<HashMap<String, UserStatus>>,
-    pub active_calls: RwLock<HashMap<String, String>>, // caller -> callee
+    store: Box<dyn UserStore>,
+    users: RwLock<HashMap<String, User>>,
+    connected_clients: RwLock<HashMap<String, ConnectedClient>>,
+    user_statuses: RwLock<HashMap<String, UserStatus>>,
+    active_calls: RwLock<HashMap<String, String>>, // caller -> callee
+    // Clientes que muestran una llamada entrante sin cortar la que tienen
+    call_waiting: RwLock<HashSet<String>>,
+    // Traspasos en curso, por el usuario que tiene que llamar al target
+    transfers: RwLock<HashMap<String, PendingTransfer>>,
+    sessions: RwLock<HashMap<String, Session>>, // token -> sesión
+    // Caídos que todavía pueden volver, con el número de su caída
+    held_sessions: RwLock<HashMap<String, u64>>,
+    blocklists: RwLock<Blocklists>,
     pub logger: Logger,
+    admins: Vec<String>,
+    max_clients: usize,
+    trace_messages: bool,
+    reconnect_grace: Duration,
+    next_hold_id: AtomicU64,
+    started_at: Instant,
+    total_logins: AtomicU64,
+    failed_logins: AtomicU64,
+    rejected_connections: AtomicU64,
+    metrics: ServerMetrics,
 }
 
 impl ServerState {
+    /// Estado con los usuarios en el archivo de la configuración.
     pub fn new(config: &AppConfig, logger: Logger) -> Self {
+        let store =
+            FileUserStore::new(&config.users_file).with_passphrase(config.users_passphrase());
+        Self::with_store(Box::new(store), logger)
+            .with_admins(config.admin_users.clone())
+            .with_max_clients(config.max_clients)
+            .with_trace_messages(config.trace_messages)
+            .with_reconnect_grace(Duration::from_secs(config.reconnect_grace_secs))
+    }
+
+    /// Estado con cualquier almacenamiento de usuarios (p. ej. en memoria).
+    pub fn with_store(store: Box<dyn UserStore>, logger: Logger) -> Self {
         Self {
-            users_file: config.users_file.clone(),
+            store,
             users: RwLock::new(HashMap::new()),
             connected_clients: RwLock::new(HashMap::new()),
             user_statuses: RwLock::new(HashMap::new()),
             active_calls: RwLock::new(HashMap::new()),
+            call_waiting: RwLock::new(HashSet::new()),
+            transfers: RwLock::new(HashMap::new()),
+            sessions: RwLock::new(HashMap::new()),
+            held_sessions: RwLock::new(HashMap::new()),
+            blocklists: RwLock::new(Blocklists::new()),
             logger,
+            admins: Vec::new(),
+            max_clients: usize::MAX,
+            trace_messages: false,
+            reconnect_grace: Duration::ZERO,
+            next_hold_id: AtomicU64::new(0),
+            started_at: Instant::now(),
+            total_logins: AtomicU64::new(0),
+            failed_logins: AtomicU64::new(0),
+            rejected_connections: AtomicU64::new(0),
+            metrics: ServerMetrics::default(),
         }
     }
 
-    pub fn load_users(&self) -> std::io::Result<()> {
-        let file = match File::open(&self.users_file) {
-            Ok(f) => f,
-            Err(_) => {
-                File::create(&self.users_file)?;
-                return Ok(());
-            }
+    pub fn users(&self) -> RwLockReadGuard<'_, HashMap<String, User>> {
+        self.read_lock(&self.users, "usuarios")
+    }
+
+    pub fn users_mut(&self) -> RwLockWriteGuard<'_, HashMap<String, User>> {
+        self.write_lock(&self.users, "usuarios")
+    }
+
+    pub fn clients(&self) -> RwLockReadGuard<'_, HashMap<String, ConnectedClient>> {
+        self.read_lock(&self.connected_clients, "clientes")
+    }
+
+    pub fn clients_mut(&self) -> RwLockWriteGuard<'_, HashMap<String, ConnectedClient>> {
+        self.write_lock(&self.connected_clients, "clientes")
+    }
+
+    pub fn statuses(&self) -> RwLockReadGuard<'_, HashMap<String, UserStatus>> {
+        self.read_lock(&self.user_statuses, "estados")
+    }
+
+    pub fn statuses_mut(&self) -> RwLockWriteGuard<'_, HashMap<String, UserStatus>> {
+        self.write_lock(&self.user_statuses, "estados")
+    }
+
+    /// Llamadas activas, guardadas en los dos sentidos (caller -> callee y
+    /// callee -> caller). Las que todavía suenan o esperan solo tienen la
+    /// entrada de caller.
+    pub fn calls(&self) -> RwLockReadGuard<'_, HashMap<String, String>> {
+        self.read_lock(&self.active_calls, "llamadas")
+    }
+
+    pub fn calls_mut(&self) -> RwLockWriteGuard<'_, HashMap<String, String>> {
+        self.write_lock(&self.active_calls, "llamadas")
+    }
+
+    /// Saca la llamada entre `user` y `peer` si sigue en curso, todo bajo el
+    /// mismo lock. Devuelve `false` si ya no estaba: pasa cuando los dos
+    /// mandan CALL_END a la vez y el segundo llega tarde.
+    pub fn end_call(&self, user: &str, peer: &str) -> bool {
+        let mut calls = self.calls_mut();
+        if calls.get(user).map(String::as_str) != Some(peer) {
+            return false;
+        }
+        calls.remove(user);
+        if calls.get(peer).map(String::as_str) == Some(user) {
+            calls.remove(peer);
+        }
+        true
+    }
+
+    /// Saca la llamada en curso de `user` y devuelve con quién era. La
+    /// entrada del otro solo se borra si sigue apuntando a `user`.
+    pub fn take_call(&self, user: &str) -> Option<String> {
+        let mut calls = self.calls_mut();
+        let other = calls.remove(user)?;
+        if calls.get(&other).map(String::as_str) == Some(user) {
+            calls.remove(&other);
+        }
+        Some(other)
+    }
+
+    /// Deja a `caller` sonando en espera para `callee`, que sigue en su
+    /// llamada: solo la entrada de `caller` apunta a `callee`. Devuelve
+    /// `false` si `callee` no está en una llamada o ya tiene a otro
+    /// esperando.
+    pub fn wait_for_call(&self, caller: &str, callee: &str) -> bool {
+        let mut calls = self.calls_mut();
+        let Some(peer) = calls.get(callee).cloned() else {
+            return false;
         };
+        let someone_waiting = calls.iter().any(|(user, to)| to == callee && *user != peer);
+        if peer == caller || someone_waiting {
+            return false;
+        }
+        calls.insert(caller.to_string(), callee.to_string());
+        true
+    }
 
-        let reader = BufReader::new(file);
-        let mut users = self
-            .users
-            .write()
-            .map_err(|_| io::Error::other("users lock poisoned"))?;
-        let mut statuses = self
-            .user_statuses
-            .write()
-            .map_err(|_| io::Error::other("statuses lock poisoned"))?;
-
-        for line in reader.lines() {
-            let line = line?;
-            let parts: Vec<&str> = line.split(':').collect();
-
-            if parts.len() >= 2 {
-                let metadata = match parts.get(2) {
-                    Some(val) => val.to_string(),
-                    None => String::new(),
-                };
-                let user = User {
-                    username: parts[0].to_string(),
-                    password: parts[1].to_string(),
-                    metadata,
-                };
-                statuses.insert(user.username.clone(), UserStatus::Disconnected);
-                users.insert(user.username.clone(), user);
+    /// `callee` atiende a `caller`. Si estaba en otra llamada (atendió una
+    /// en espera sin cortar antes), esa se saca y se devuelve con quién era.
+    pub fn switch_call(&self, callee: &str, caller: &str) -> Option<String> {
+        let mut calls = self.calls_mut();
+        if calls.get(caller).map(String::as_str) != Some(callee) {
+            return None;
+        }
+        let previous = calls
+            .insert(callee.to_string(), caller.to_string())
+            .filter(|previous| previous != caller)?;
+        if calls.get(&previous).map(String::as_str) == Some(callee) {
+            calls.remove(&previous);
+        }
+        Some(previous)
+    }
+
+    /// Saca las llamadas que esperaban a que `user` atendiera y devuelve
+    /// quiénes llamaban.
+    pub fn take_waiting_callers(&self, user: &str) -> Vec<String> {
+        let mut calls = self.calls_mut();
+        let callers: Vec<String> = calls
+            .iter()
+            .filter(|(caller, to)| *to == user && calls.get(user) != Some(*caller))
+            .map(|(caller, _)| caller.clone())
+            .collect();
+        for caller in &callers {
+            calls.remove(caller);
+        }
+        callers
+    }
+
+    /// Si `user` tiene alguna llamada, en curso, sonando o en espera.
+    pub fn in_call(&self, user: &str) -> bool {
+        let calls = self.calls();
+        calls.contains_key(user) || calls.values().any(|to| to == user)
+    }
+
+    /// Pasa a `user` a Available salvo que todavía le quede una llamada:
+    /// quien corta una en espera sigue ocupado con la otra.
+    pub fn release_user(&self, user: &str) {
+        if !self.in_call(user) {
+            self.set_user_status(user, UserStatus::Available);
+        }
+    }
+
+    /// Anota si el cliente de `username` muestra las llamadas que entran
+    /// durante otra, según los `caps` que mandó en su última oferta o
+    /// respuesta.
+    pub fn set_call_waiting(&self, username: &str, enabled: bool) {
+        let mut users = self.write_lock(&self.call_waiting, "llamadas en espera");
+        if enabled {
+            users.insert(username.to_string());
+        } else {
+            users.remove(username);
+        }
+    }
+
+    pub fn accepts_call_waiting(&self, username: &str) -> bool {
+        self.read_lock(&self.call_waiting, "llamadas en espera")
+            .contains(username)
+    }
+
+    /// `transferor` le pasó a `peer` su llamada: `peer` va a llamar a
+    /// `target`, y si no se concreta hay que avisarle a `transferor`.
+    pub fn start_transfer(&self, peer: &str, transferor: &str, target: &str) {
+        self.write_lock(&self.transfers, "traspasos").insert(
+            peer.to_string(),
+            PendingTransfer {
+                transferor: transferor.to_string(),
+                target: target.to_string(),
+            },
+        );
+    }
+
+    /// `target` atendió a `peer`: el traspaso terminó bien.
+    pub fn complete_transfer(&self, peer: &str, target: &str) {
+        self.take_transfer(peer, target);
+    }
+
+    /// El traspaso que mandó a `peer` a llamar a `target` no se concretó:
+    /// se le avisa con TRANSFER_FAILED a quien lo pidió. Sin un traspaso así
+    /// en curso no hace nada.
+    pub fn fail_transfer(&self, peer: &str, target: &str, error: &str) {
+        if let Some(transferor) = self.take_transfer(peer, target) {
+            self.notify_transfer_failed(&transferor, target, error);
+        }
+    }
+
+    fn take_transfer(&self, peer: &str, target: &str) -> Option<String> {
+        let mut transfers = self.write_lock(&self.transfers, "traspasos");
+        if transfers.get(peer).map(|t| t.target.as_str()) != Some(target) {
+            return None;
+        }
+        transfers.remove(peer).map(|t| t.transferor)
+    }
+
+    /// `username` se fue: los traspasos en los que era el traspasado o el
+    /// target ya no pueden terminar, y se avisa a quien los pidió. Los que
+    /// pidió él mismo se descartan.
+    fn fail_transfers_of(&self, username: &str) {
+        let failed: Vec<PendingTransfer> = {
+            let mut transfers = self.write_lock(&self.transfers, "traspasos");
+            let peers: Vec<String> = transfers
+                .iter()
+                .filter(|(peer, t)| {
+                    *peer == username || t.target == username || t.transferor == username
+                })
+                .map(|(peer, _)| peer.clone())
+                .collect();
+            peers
+                .iter()
+                .filter_map(|peer| transfers.remove(peer))
+                .filter(|t| t.transferor != username)
+                .collect()
+        };
+        for transfer in failed {
+            self.notify_transfer_failed(
+                &transfer.transferor,
+                &transfer.target,
+                "user not connected",
+            );
+        }
+    }
+
+    fn notify_transfer_failed(&self, transferor: &str, target: &str, error: &str) {
+        if let Some(client) = self.clients().get(transferor) {
+            let msg = format!("TRANSFER_FAILED|target:{}|error:{}", target, error);
+            Self::send_message(&client.sender, &msg);
+        }
+        self.logger.info(&format!(
+            "El traspaso de {} a {} no se concretó: {}",
+            transferor, target, error
+        ));
+    }
+
+    // Un panic a mitad de una escritura puede dejar el mapa a medio
+    // actualizar, pero cada handler toca pocas entradas y eso es mucho mejor
+    // que rechazar todas las operaciones para siempre.
+    fn read_lock<'a, T>(&self, lock: &'a RwLock<T>, name: &str) -> RwLockReadGuard<'a, T> {
+        lock.read().unwrap_or_else(|poisoned| {
+            self.recover_poison(lock, name);
+            poisoned.into_inner()
+        })
+    }
+
+    fn write_lock<'a, T>(&self, lock: &'a RwLock<T>, name: &str) -> RwLockWriteGuard<'a, T> {
+        lock.write().unwrap_or_else(|poisoned| {
+            self.recover_poison(lock, name);
+            poisoned.into_inner()
+        })
+    }
+
+    fn recover_poison<T>(&self, lock: &RwLock<T>, name: &str) {
+        self.logger.warn(&format!(
+            "Lock de {} envenenado por un panic, se recupera",
+            name
+        ));
+        lock.clear_poison();
+    }
+
+    /// Usuarios que pueden pedir STATS.
+    pub fn with_admins(mut self, admins: Vec<String>) -> Self {
+        self.admins = admins;
+        self
+    }
+
+    pub fn is_admin(&self, username: &str) -> bool {
+        self.admins.iter().any(|admin| admin == username)
+    }
+
+    /// Clientes logueados a la vez; las conexiones de más se cortan en el
+    /// accept. Sin llamar a esto no hay límite.
+    pub fn with_max_clients(mut self, max_clients: usize) -> Self {
+        self.max_clients = max_clients;
+        self
+    }
+
+    pub fn max_clients(&self) -> usize {
+        self.max_clients
+    }
+
+    /// Loguea cada mensaje que entra y sale, ya pasado por `redact_for_log`.
+    pub fn with_trace_messages(mut self, trace: bool) -> Self {
+        self.trace_messages = trace;
+        self
+    }
+
+    pub fn trace_messages(&self) -> bool {
+        self.trace_messages
+    }
+
+    /// Cuánto se espera a que vuelva un cliente que se cayó antes de darlo
+    /// por desconectado. Sin llamar a esto la limpieza es inmediata.
+    pub fn with_reconnect_grace(mut self, grace: Duration) -> Self {
+        self.reconnect_grace = grace;
+        self
+    }
+
+    pub fn reconnect_grace(&self) -> Duration {
+        self.reconnect_grace
+    }
+
+    /// Guarda la sesión de `username`, que se acaba de caer, hasta que
+    /// vuelva o venza el plazo. Devuelve el número de esta caída para
+    /// `release_held_session`.
+    pub fn hold_session(&self, username: &str) -> u64 {
+        let id = self.next_hold_id.fetch_add(1, Ordering::Relaxed);
+        self.write_lock(&self.held_sessions, "sesiones caídas")
+            .insert(username.to_string(), id);
+        id
+    }
+
+    /// `username` volvió: su sesión caída deja de estar pendiente. Devuelve
+    /// si había una.
+    pub fn reclaim_session(&self, username: &str) -> bool {
+        self.write_lock(&self.held_sessions, "sesiones caídas")
+            .remove(username)
+            .is_some()
+    }
+
+    /// Venció el plazo de la caída `id`: devuelve `true` si nadie la
+    /// reclamó entre tanto y toca limpiarla.
+    pub fn release_held_session(&self, username: &str, id: u64) -> bool {
+        let mut held = self.write_lock(&self.held_sessions, "sesiones caídas");
+        if held.get(username) != Some(&id) {
+            return false;
+        }
+        held.remove(username);
+        true
+    }
+
+    /// Da a `username` por desconectado: lo avisa a los demás y corta su
+    /// llamada, si tenía.
+    pub fn finish_disconnect(&self, username: &str) {
+        self.set_user_status(username, UserStatus::Disconnected);
+        self.announce_user_left(username);
+        self.logger.warn(&format!("{} se desconectó", username));
+
+        // Si estaba en llamada, notificar al otro y a quien esperaba que
+        // atendiera
+        let mut others = self.take_waiting_callers(username);
+        others.extend(self.take_call(username));
+        for other in others {
+            self.release_user(&other);
+
+            if let Some(other_client) = self.clients().get(&other) {
+                let msg = format!("CALL_ENDED|from:{}", username);
+                Self::send_message(&other_client.sender, &msg);
             }
         }
-        self.logger
-            .info(&format!("Usuarios cargados desde {}", self.users_file));
+        self.set_call_waiting(username, false);
+        self.fail_transfers_of(username);
+    }
+
+    pub fn record_login(&self) {
+        self.total_logins.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_failed_login(&self) {
+        self.failed_logins.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Conexión cortada en el accept, p. ej. por superar `max_clients`.
+    pub fn record_rejected_connection(&self) {
+        self.rejected_connections.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Contadores por tipo de mensaje y de bytes, para `/metrics`.
+    pub fn metrics(&self) -> &ServerMetrics {
+        &self.metrics
+    }
+
+    /// Toma cada lock solo para contar, así un STATS no frena la señalización.
+    pub fn stats(&self) -> ServerStats {
+        let connected_clients = self.clients().len();
+        // Cada llamada está guardada en los dos sentidos
+        let active_calls = self.calls().len() / 2;
+        ServerStats {
+            connected_clients,
+            active_calls,
+            total_logins: self.total_logins.load(Ordering::Relaxed),
+            failed_logins: self.failed_logins.load(Ordering::Relaxed),
+            rejected_connections: self.rejected_connections.load(Ordering::Relaxed),
+            uptime: self.started_at.elapsed(),
+        }
+    }
+
+    pub fn load_users(&self) -> std::io::Result<()> {
+        let loaded = self.store.load()?;
+        *self.write_lock(&self.blocklists, "bloqueos") = self.store.load_blocklists()?;
+        let mut users = self.users_mut();
+        let mut statuses = self.statuses_mut();
+
+        for user in loaded {
+            statuses.insert(user.username.clone(), UserStatus::Disconnected);
+            users.insert(user.username.clone(), user);
+        }
+        self.logger.info(&format!(
+            "Usuarios cargados desde {}",
+            self.store.describe()
+        ));
 
         Ok(())
     }
 
     pub fn save_user(&self, user: &User) -> std::io::Result<()> {
-        let mut file = OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(&self.users_file)?;
-
-        writeln!(
-            file,
-            "{}:{}:{}",
-            user.username, user.password, user.metadata
-        )?;
-        Ok(())
+        self.store.save(user)
     }
 
     pub fn register_user(&self, username: String, password: String) -> Result<(), String> {
         validate_username(&username)?;
         validate_password(&password)?;
-        let mut users = self
-            .users
-            .write()
-            .map_err(|_| "Users lock poisoned".to_string())?;
+        let mut users = self.users_mut();
 
         if users.contains_key(&username) {
             return Err("User already exist".to_string());
@@ -115,11 +527,8 @@ impl ServerState {
 
         users.insert(username.clone(), user);
 
-        let mut statuses = self
-            .user_statuses
-            .write()
-            .map_err(|_| "Statuses lock poisoned".to_string())?;
-        statuses.insert(username, UserStatus::Disconnected);
+        self.statuses_mut()
+            .insert(username, UserStatus::Disconnected);
 
         self.logger
             .info("Nuevo usuario registrado en el archivo de usuarios");
@@ -127,37 +536,226 @@ impl ServerState {
     }
 
     pub fn authenticate(&self, username: &str, password: &str) -> Result<(), String> {
-        validate_username(username)?;
-        validate_password(password)?;
-        let users = self
-            .users
-            .read()
-            .map_err(|_| "Users lock poisoned".to_string())?;
+        validate_password_format(password)?;
+        let users = self.users();
 
         match users.get(username) {
-            Some(user) if user.password == password => Ok(()),
-            Some(_) => Err("Invalid password".to_string()),
+            Some(user) if user.password != password => Err("Invalid password".to_string()),
+            Some(user) if user.is_disabled() => Err(ACCOUNT_DISABLED.to_string()),
+            Some(_) => Ok(()),
             None => Err("User does not exist".to_string()),
         }
     }
 
-    pub fn get_user_list(&self) -> Vec<(String, UserStatus)> {
-        let statuses = match self.user_statuses.read() {
-            Ok(guard) => guard,
-            Err(_) => {
-                self.logger.error("Statuses lock poisoned");
-                return Vec::new();
-            }
+    /// Emite un token de sesión nuevo para `username`: 32 bytes al azar en
+    /// hexa, válido por `SESSION_TOKEN_TTL`. Los vencidos se descartan acá.
+    pub fn issue_token(&self, username: &str) -> String {
+        let mut bytes = [0u8; 32];
+        rand::thread_rng().fill(&mut bytes);
+        let token: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+        let now = Instant::now();
+        let mut sessions = self.write_lock(&self.sessions, "sesiones");
+        sessions.retain(|_, session| session.expires_at > now);
+        sessions.insert(
+            token.clone(),
+            Session {
+                username: username.to_string(),
+                expires_at: now + SESSION_TOKEN_TTL,
+            },
+        );
+        token
+    }
+
+    /// Valida un token de `username` y lo consume: quien entra con él recibe
+    /// uno nuevo con `issue_token`.
+    pub fn authenticate_token(&self, username: &str, token: &str) -> Result<(), String> {
+        let mut sessions = self.write_lock(&self.sessions, "sesiones");
+        let Some(session) = sessions.get(token).filter(|s| s.username == username) else {
+            return Err("Invalid token".to_string());
         };
-        let users = match self.users.read() {
-            Ok(guard) => guard,
-            Err(_) => {
-                self.logger.error("Users lock poisoned");
-                return Vec::new();
+        let expired = session.expires_at <= Instant::now();
+        sessions.remove(token);
+        if expired {
+            return Err("Token expired".to_string());
+        }
+        if !self.account_active(username) {
+            return Err(ACCOUNT_DISABLED.to_string());
+        }
+        Ok(())
+    }
+
+    /// Invalida todas las sesiones recordadas de `username` (LOGOUT).
+    pub fn revoke_tokens(&self, username: &str) {
+        self.write_lock(&self.sessions, "sesiones")
+            .retain(|_, session| session.username != username);
+    }
+
+    /// Cuentas registradas en orden alfabético, con si están deshabilitadas
+    /// (ADMIN_USERS).
+    pub fn accounts(&self) -> Vec<(String, bool)> {
+        let mut accounts: Vec<(String, bool)> = self
+            .users()
+            .values()
+            .map(|user| (user.username.clone(), user.is_disabled()))
+            .collect();
+        accounts.sort();
+        accounts
+    }
+
+    /// Si `username` existe y puede entrar. Una sesión abierta de una cuenta
+    /// que un admin borró o deshabilitó se cierra.
+    pub fn account_active(&self, username: &str) -> bool {
+        self.users()
+            .get(username)
+            .is_some_and(|user| !user.is_disabled())
+    }
+
+    /// Borra la cuenta de `username` (ADMIN_REMOVE_USER), con sus tokens y
+    /// sus bloqueos. Si no se pudo guardar, la cuenta sigue.
+    pub fn remove_user(&self, username: &str) -> Result<(), String> {
+        let mut users = self.users_mut();
+        if !users.contains_key(username) {
+            return Err("User does not exist".to_string());
+        }
+        self.ensure_admin_remains(&users, username)?;
+
+        let mut updated = users.clone();
+        updated.remove(username);
+        self.persist_users(&updated)?;
+        *users = updated;
+        drop(users);
+
+        self.revoke_tokens(username);
+        self.statuses_mut().remove(username);
+        let mut blocklists = self.write_lock(&self.blocklists, "bloqueos");
+        let mut cleaned = blocklists.clone();
+        cleaned.remove(username);
+        for blocked in cleaned.values_mut() {
+            blocked.remove(username);
+        }
+        cleaned.retain(|_, blocked| !blocked.is_empty());
+        if cleaned != *blocklists {
+            match self.store.save_blocklists(&cleaned) {
+                Ok(()) => *blocklists = cleaned,
+                Err(e) => self.logger.warn(&format!(
+                    "No se pudieron limpiar los bloqueos de {}: {}",
+                    username, e
+                )),
             }
+        }
+        Ok(())
+    }
+
+    /// Deshabilita o rehabilita la cuenta de `username` y lo persiste. Al
+    /// deshabilitarla se invalidan sus tokens.
+    pub fn set_disabled(&self, username: &str, disabled: bool) -> Result<(), String> {
+        let mut users = self.users_mut();
+        let Some(user) = users.get(username) else {
+            return Err("User does not exist".to_string());
         };
+        if user.is_disabled() == disabled {
+            return Ok(());
+        }
+        if disabled {
+            self.ensure_admin_remains(&users, username)?;
+        }
 
-        users
+        let mut updated = users.clone();
+        if let Some(user) = updated.get_mut(username) {
+            user.set_disabled(disabled);
+        }
+        self.persist_users(&updated)?;
+        *users = updated;
+        drop(users);
+
+        if disabled {
+            self.revoke_tokens(username);
+        }
+        Ok(())
+    }
+
+    /// Falla si sacar a `username` deja al servidor sin ningún admin que
+    /// pueda entrar.
+    fn ensure_admin_remains(
+        &self,
+        users: &HashMap<String, User>,
+        username: &str,
+    ) -> Result<(), String> {
+        if !self.is_admin(username) {
+            return Ok(());
+        }
+        let other_admin = self.admins.iter().any(|admin| {
+            admin != username && users.get(admin).is_some_and(|user| !user.is_disabled())
+        });
+        if other_admin {
+            Ok(())
+        } else {
+            Err("cannot remove the last admin".to_string())
+        }
+    }
+
+    /// Guarda la lista entera de usuarios, en orden alfabético.
+    fn persist_users(&self, users: &HashMap<String, User>) -> Result<(), String> {
+        let mut list: Vec<User> = users.values().cloned().collect();
+        list.sort_by(|a, b| a.username.cmp(&b.username));
+        self.store
+            .save_all(&list)
+            .map_err(|e| format!("Error saving users: {}", e))
+    }
+
+    /// Usuarios que bloqueó `username`, en orden alfabético.
+    pub fn blocklist(&self, username: &str) -> Vec<String> {
+        self.read_lock(&self.blocklists, "bloqueos")
+            .get(username)
+            .map(|blocked| blocked.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Si `username` bloqueó a `other`: sus llamadas no le llegan.
+    pub fn is_blocked(&self, username: &str, other: &str) -> bool {
+        self.read_lock(&self.blocklists, "bloqueos")
+            .get(username)
+            .is_some_and(|blocked| blocked.contains(other))
+    }
+
+    /// Bloquea o desbloquea a `target` para `username` y lo persiste. Si no
+    /// se pudo guardar, el cambio no se aplica.
+    pub fn set_blocked(&self, username: &str, target: &str, blocked: bool) -> Result<(), String> {
+        if username == target {
+            return Err("cannot block yourself".to_string());
+        }
+        if blocked && !self.users().contains_key(target) {
+            return Err("User does not exist".to_string());
+        }
+
+        let mut blocklists = self.write_lock(&self.blocklists, "bloqueos");
+        let mut updated = blocklists.clone();
+        let list = updated.entry(username.to_string()).or_default();
+        let changed = if blocked {
+            list.insert(target.to_string())
+        } else {
+            list.remove(target)
+        };
+        if !changed {
+            return Ok(());
+        }
+        if list.is_empty() {
+            updated.remove(username);
+        }
+        self.store
+            .save_blocklists(&updated)
+            .map_err(|e| format!("Error saving blocklist: {}", e))?;
+        *blocklists = updated;
+        Ok(())
+    }
+
+    /// Usuarios registrados con su estado, ordenados por nombre para que la
+    /// lista no cambie de orden entre un pedido y otro.
+    pub fn get_user_list(&self) -> Vec<(String, UserStatus)> {
+        let statuses = self.statuses();
+        let users = self.users();
+
+        let mut list: Vec<(String, UserStatus)> = users
             .keys()
             .map(|u| {
                 let status = match statuses.get(u) {
@@ -166,43 +764,57 @@ impl ServerState {
                 };
                 (u.clone(), status)
             })
-            .collect()
+            .collect();
+        list.sort_by(|a, b| a.0.cmp(&b.0));
+        list
     }
 
     pub fn set_user_status(&self, username: &str, status: UserStatus) {
-        let mut statuses = match self.user_statuses.write() {
-            Ok(guard) => guard,
-            Err(_) => {
-                self.logger
-                    .error("No se pudo actualizar estado: lock envenenado");
-                return;
-            }
-        };
-        statuses.insert(username.to_string(), status.clone());
-        drop(statuses);
+        self.statuses_mut()
+            .insert(username.to_string(), status.clone());
 
         // Notificar a todos los clientes conectados
-        let clients = match self.connected_clients.read() {
-            Ok(guard) => guard,
-            Err(_) => {
-                self.logger
-                    .error("No se pudo notificar estado: lock envenenado");
-                return;
-            }
-        };
         let msg = format!(
             "USER_STATUS_CHANGED|username:{}|status:{}",
-            username,
+            protocol_value(username),
             status.to_string()
         );
-
-        for client in clients.values() {
-            Self::send_message(&client.sender, &msg);
-        }
+        self.broadcast(&msg, None);
         self.logger
             .info(&format!("Estado de {} -> {}", username, status.to_string()));
     }
 
+    /// Avisa a los demás clientes que `username` inició sesión, para que
+    /// lo sumen a su lista sin volver a pedirla.
+    pub fn announce_user_joined(&self, username: &str) {
+        let status = self
+            .statuses()
+            .get(username)
+            .cloned()
+            .unwrap_or(UserStatus::Available);
+        let msg = format!(
+            "USER_JOINED|username:{}|status:{}",
+            protocol_value(username),
+            status.to_string()
+        );
+        self.broadcast(&msg, Some(username));
+    }
+
+    /// Avisa a los clientes que `username` cerró sesión o se desconectó.
+    pub fn announce_user_left(&self, username: &str) {
+        let msg = format!("USER_LEFT|username:{}", protocol_value(username));
+        self.broadcast(&msg, Some(username));
+    }
+
+    /// Manda `msg` a todos los clientes autenticados salvo `except`.
+    fn broadcast(&self, msg: &str, except: Option<&str>) {
+        for (name, client) in self.clients().iter() {
+            if Some(name.as_str()) != except {
+                Self::send_message(&client.sender, msg);
+            }
+        }
+    }
+
     pub fn send_message(sender: &Sender<String>, msg: &str) {
         let _ = sender.send(msg.to_string());
     }
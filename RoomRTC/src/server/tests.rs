@@ -0,0 +1,1337 @@
+//! Tests de integración del servidor: `serve_client` corre sobre TCP plano en
+//! 127.0.0.1 y los usuarios viven en memoria.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::logger::{LogOptions, Logger};
+
+use super::metrics;
+use super::protocol::{parse_message, payload, payload_field};
+use super::serve_client;
+use super::state::ServerState;
+use super::types::{User, UserStatus};
+use super::user_store::{MemoryUserStore, UserStore};
+use super::validation::{MAX_FIELD_LEN, MAX_LINE_LEN};
+
+const READ_TIMEOUT: Duration = Duration::from_millis(50);
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Levanta el servidor en un puerto libre y devuelve su dirección.
+fn start_server(state: Arc<ServerState>) -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(stream) = stream else { continue };
+            let Ok(peer) = stream.peer_addr() else {
+                continue;
+            };
+            let _ = stream.set_read_timeout(Some(READ_TIMEOUT));
+            let state = Arc::clone(&state);
+            thread::spawn(move || serve_client(stream, peer, state));
+        }
+    });
+    addr
+}
+
+fn memory_state(store: &MemoryUserStore) -> Arc<ServerState> {
+    let state = ServerState::with_store(Box::new(store.clone()), Logger::noop());
+    state.load_users().unwrap();
+    Arc::new(state)
+}
+
+fn user(username: &str, password: &str) -> User {
+    User {
+        username: username.to_string(),
+        password: password.to_string(),
+        metadata: String::new(),
+    }
+}
+
+/// Espera hasta que `check` se cumpla, para el estado que se actualiza en el
+/// hilo del servidor.
+fn wait_until(check: impl Fn() -> bool) -> bool {
+    let deadline = Instant::now() + RESPONSE_TIMEOUT;
+    while Instant::now() < deadline {
+        if check() {
+            return true;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    false
+}
+
+struct TestClient {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+}
+
+impl TestClient {
+    fn connect(addr: SocketAddr) -> Self {
+        let stream = TcpStream::connect(addr).unwrap();
+        stream.set_read_timeout(Some(RESPONSE_TIMEOUT)).unwrap();
+        let writer = stream.try_clone().unwrap();
+        Self {
+            reader: BufReader::new(stream),
+            writer,
+        }
+    }
+
+    /// Manda la línea en una sola escritura: el servidor descarta lo leído
+    /// si el timeout de lectura corta una línea a la mitad.
+    fn send(&mut self, line: &str) {
+        self.writer
+            .write_all(format!("{}\n", line).as_bytes())
+            .unwrap();
+        self.writer.flush().unwrap();
+    }
+
+    /// Lee líneas hasta encontrar una que empiece con `prefix`. Los
+    /// USER_STATUS_CHANGED y demás avisos intermedios se descartan.
+    fn expect(&mut self, prefix: &str) -> String {
+        loop {
+            let mut line = String::new();
+            match self.reader.read_line(&mut line) {
+                Ok(0) => panic!("connection closed while waiting for {}", prefix),
+                Ok(_) if line.starts_with(prefix) => return line.trim_end().to_string(),
+                Ok(_) => continue,
+                Err(err) => panic!("no {} received: {}", prefix, err),
+            }
+        }
+    }
+
+    /// Como `expect`, pero devuelve también las líneas descartadas.
+    fn lines_until(&mut self, prefix: &str) -> Vec<String> {
+        let mut lines = Vec::new();
+        loop {
+            let mut line = String::new();
+            match self.reader.read_line(&mut line) {
+                Ok(0) => panic!("connection closed while waiting for {}", prefix),
+                Ok(_) => {
+                    let done = line.starts_with(prefix);
+                    lines.push(line.trim_end().to_string());
+                    if done {
+                        return lines;
+                    }
+                }
+                Err(err) => panic!("no {} received: {}", prefix, err),
+            }
+        }
+    }
+
+    fn login(addr: SocketAddr, username: &str, password: &str) -> Self {
+        let mut client = Self::connect(addr);
+        client.send(&format!(
+            "LOGIN|username:{}|password:{}",
+            username, password
+        ));
+        client.expect("LOGIN_SUCCESS");
+        client
+    }
+
+    /// Como `login`, anunciando que entiende payloads en base64.
+    fn login_b64(addr: SocketAddr, username: &str, password: &str) -> Self {
+        let mut client = Self::connect(addr);
+        client.send(&format!(
+            "LOGIN|username:{}|password:{}|features:b64",
+            username, password
+        ));
+        assert!(client.expect("LOGIN_SUCCESS").ends_with("|features:b64"));
+        client
+    }
+}
+
+fn status_of(state: &ServerState, username: &str) -> Option<UserStatus> {
+    state.statuses().get(username).cloned()
+}
+
+#[test]
+fn test_full_call_flow() {
+    let store = MemoryUserStore::new();
+    let state = memory_state(&store);
+    let addr = start_server(Arc::clone(&state));
+
+    let mut alice = TestClient::connect(addr);
+    alice.send("REGISTER|username:alice|password:alice-secret");
+    alice.expect("REGISTER_SUCCESS");
+    alice.send("LOGIN|username:alice|password:alice-secret");
+    alice.expect("LOGIN_SUCCESS");
+
+    let mut bob = TestClient::connect(addr);
+    bob.send("REGISTER|username:bob|password:bob-secret");
+    bob.expect("REGISTER_SUCCESS");
+    bob.send("LOGIN|username:bob|password:bob-secret");
+    bob.expect("LOGIN_SUCCESS");
+
+    alice.send("GET_USERS");
+    assert_eq!(
+        alice.expect("USER_LIST"),
+        "USER_LIST|users:alice=AVAILABLE,bob=AVAILABLE"
+    );
+
+    alice.send("CALL_OFFER|to:bob|sdp:v=0\\no=- 1 1 IN IP4 0.0.0.0|srtp_key:abc");
+    let incoming = bob.expect("INCOMING_CALL");
+    assert!(incoming.starts_with("INCOMING_CALL|from:alice|sdp:v=0\\no=-"));
+    assert!(incoming.ends_with("|srtp_key:abc"));
+
+    bob.send("CALL_ANSWER|to:alice|accept:true|sdp:v=0\\no=- 2 2 IN IP4 0.0.0.0");
+    let accepted = alice.expect("CALL_ACCEPTED");
+    assert!(accepted.starts_with("CALL_ACCEPTED|from:bob|sdp:v=0"));
+    assert_eq!(status_of(&state, "alice"), Some(UserStatus::Busy));
+    assert_eq!(status_of(&state, "bob"), Some(UserStatus::Busy));
+
+    alice.send("CALL_END|to:bob");
+    assert_eq!(bob.expect("CALL_ENDED"), "CALL_ENDED|from:alice");
+    assert!(wait_until(|| {
+        status_of(&state, "alice") == Some(UserStatus::Available)
+            && status_of(&state, "bob") == Some(UserStatus::Available)
+    }));
+    assert!(state.calls().is_empty());
+
+    // El registro quedó en el store, no en disco
+    let saved = store.load().unwrap();
+    assert!(saved.iter().any(|u| u.username == "alice"));
+    assert!(saved.iter().any(|u| u.username == "bob"));
+}
+
+#[test]
+fn test_call_forwards_capabilities_only_when_sent() {
+    let store = MemoryUserStore::with_users(vec![
+        user("alice", "alice-secret"),
+        user("bob", "bob-secret"),
+    ]);
+    let state = memory_state(&store);
+    let addr = start_server(Arc::clone(&state));
+
+    let mut alice = TestClient::login(addr, "alice", "alice-secret");
+    let mut bob = TestClient::login(addr, "bob", "bob-secret");
+
+    alice.send("CALL_OFFER|to:bob|sdp:v=0|caps:proto=2,opus,fec");
+    assert!(
+        bob.expect("INCOMING_CALL")
+            .ends_with("|caps:proto=2,opus,fec")
+    );
+    // Bob es un cliente viejo que no manda caps
+    bob.send("CALL_ANSWER|to:alice|accept:true|sdp:v=0");
+    assert!(!alice.expect("CALL_ACCEPTED").contains("caps:"));
+}
+
+#[test]
+fn test_ringing_reaches_only_the_caller() {
+    let store = MemoryUserStore::with_users(vec![
+        user("alice", "alice-secret"),
+        user("bob", "bob-secret"),
+        user("carol", "carol-secret"),
+    ]);
+    let state = memory_state(&store);
+    let addr = start_server(Arc::clone(&state));
+
+    let mut alice = TestClient::login(addr, "alice", "alice-secret");
+    let mut bob = TestClient::login(addr, "bob", "bob-secret");
+    let mut carol = TestClient::login(addr, "carol", "carol-secret");
+
+    alice.send("CALL_OFFER|to:bob|sdp:v=0");
+    bob.expect("INCOMING_CALL");
+
+    // Carol no llama a nadie: su RINGING no llega
+    carol.send("CALL_RINGING|to:alice");
+    carol.send("GET_USERS");
+    carol.expect("USER_LIST");
+
+    bob.send("CALL_RINGING|to:alice");
+    assert_eq!(alice.expect("CALL_RINGING"), "CALL_RINGING|from:bob");
+
+    // Después de cortar, ya no suena para nadie
+    alice.send("CALL_END|to:bob");
+    bob.expect("CALL_ENDED");
+    bob.send("CALL_RINGING|to:alice");
+    bob.send("GET_USERS");
+    bob.expect("USER_LIST");
+    alice.send("GET_USERS");
+    assert!(
+        alice
+            .lines_until("USER_LIST")
+            .iter()
+            .all(|l| !l.starts_with("CALL_RINGING"))
+    );
+}
+
+#[test]
+fn test_sdp_is_forwarded_in_the_encoding_each_client_understands() {
+    let store = MemoryUserStore::with_users(vec![
+        user("alice", "alice-secret"),
+        user("bob", "bob-secret"),
+        user("carol", "carol-secret"),
+    ]);
+    let state = memory_state(&store);
+    let addr = start_server(Arc::clone(&state));
+
+    let mut alice = TestClient::login_b64(addr, "alice", "alice-secret");
+    let mut bob = TestClient::login(addr, "bob", "bob-secret");
+    let mut carol = TestClient::login_b64(addr, "carol", "carol-secret");
+
+    // Bob es un cliente viejo: le llega escapado, como siempre
+    let offer = "v=0\r\ns=Reunión: café\r\n";
+    alice.send(&format!(
+        "CALL_OFFER|to:bob|{}",
+        payload_field("sdp", offer, true)
+    ));
+    let incoming = bob.expect("INCOMING_CALL");
+    assert!(incoming.contains(&format!("|{}|", payload_field("sdp", offer, false))));
+    bob.send("CALL_ANSWER|to:alice|accept:false");
+    alice.expect("CALL_REJECTED");
+
+    let offer = "v=0\r\ns=Sala|1: ☕\r\na=x:y|z\r\n";
+    alice.send(&format!(
+        "CALL_OFFER|to:carol|{}",
+        payload_field("sdp", offer, true)
+    ));
+    let incoming = parse_message(&carol.expect("INCOMING_CALL"));
+    assert_eq!(incoming.get("from").map(String::as_str), Some("alice"));
+    assert_eq!(payload(&incoming, "sdp"), Ok(Some(offer.to_string())));
+
+    let answer = "v=0\r\ns=Respuesta|sí\r\n";
+    carol.send(&format!(
+        "CALL_ANSWER|to:alice|accept:true|{}",
+        payload_field("sdp", answer, true)
+    ));
+    let accepted = parse_message(&alice.expect("CALL_ACCEPTED"));
+    assert_eq!(payload(&accepted, "sdp"), Ok(Some(answer.to_string())));
+
+    carol.send("ICE_RESTART|to:alice|sdp_b64:abc");
+    assert_eq!(carol.expect("ERROR"), "ERROR|error:invalid_payload");
+}
+
+#[test]
+fn test_call_error_carries_a_reason() {
+    let store = MemoryUserStore::with_users(vec![
+        user("alice", "alice-secret"),
+        user("bob", "bob-secret"),
+        user("carol", "carol-secret"),
+        user("dave", "dave-secret"),
+    ]);
+    let state = memory_state(&store);
+    let addr = start_server(Arc::clone(&state));
+
+    let mut alice = TestClient::login(addr, "alice", "alice-secret");
+    let mut bob = TestClient::login(addr, "bob", "bob-secret");
+    let mut carol = TestClient::login(addr, "carol", "carol-secret");
+
+    alice.send("CALL_OFFER|to:nobody|sdp:v=0");
+    assert_eq!(
+        alice.expect("CALL_ERROR"),
+        "CALL_ERROR|error:User does not exist|reason:not_found|to:nobody"
+    );
+    alice.send("CALL_OFFER|to:dave|sdp:v=0");
+    assert_eq!(
+        alice.expect("CALL_ERROR"),
+        "CALL_ERROR|error:User not available|reason:offline|to:dave"
+    );
+
+    bob.send("CALL_OFFER|to:carol|sdp:v=0");
+    carol.expect("INCOMING_CALL");
+    carol.send("CALL_ANSWER|to:bob|accept:true|sdp:v=0");
+    bob.expect("CALL_ACCEPTED");
+    alice.send("CALL_OFFER|to:carol|sdp:v=0");
+    assert_eq!(
+        alice.expect("CALL_ERROR"),
+        "CALL_ERROR|error:User not available|reason:busy|to:carol"
+    );
+}
+
+#[test]
+fn test_several_calls_ring_until_one_is_answered() {
+    let store = MemoryUserStore::with_users(vec![
+        user("alice", "alice-secret"),
+        user("bob", "bob-secret"),
+        user("carol", "carol-secret"),
+    ]);
+    let state = memory_state(&store);
+    let addr = start_server(Arc::clone(&state));
+
+    let mut alice = TestClient::login(addr, "alice", "alice-secret");
+    let mut bob = TestClient::login(addr, "bob", "bob-secret");
+    let mut carol = TestClient::login(addr, "carol", "carol-secret");
+
+    // Mientras suena, bob sigue disponible y le llega la segunda llamada
+    alice.send("CALL_OFFER|to:bob|sdp:v=0");
+    bob.expect("INCOMING_CALL|from:alice");
+    assert_eq!(status_of(&state, "bob"), Some(UserStatus::Available));
+    carol.send("CALL_OFFER|to:bob|sdp:v=0");
+    bob.expect("INCOMING_CALL|from:carol");
+
+    bob.send("CALL_ANSWER|to:carol|accept:true|sdp:v=0");
+    carol.expect("CALL_ACCEPTED|from:bob");
+    bob.send("CALL_REJECT|to:alice|reason:busy");
+    assert_eq!(
+        alice.expect("CALL_REJECTED"),
+        "CALL_REJECTED|from:bob|reason:busy"
+    );
+    assert!(wait_until(
+        || status_of(&state, "alice") == Some(UserStatus::Available)
+    ));
+    assert_eq!(status_of(&state, "bob"), Some(UserStatus::Busy));
+    assert_eq!(state.calls().len(), 2);
+    assert_eq!(state.calls().get("bob").map(String::as_str), Some("carol"));
+
+    // Atender una llamada que ya se cortó no deja a nadie ocupado
+    alice.send("CALL_OFFER|to:carol|sdp:v=0");
+    assert!(alice.expect("CALL_ERROR").contains("reason:busy"));
+    carol.send("CALL_END|to:bob");
+    bob.expect("CALL_ENDED|from:carol");
+    alice.send("CALL_OFFER|to:bob|sdp:v=0");
+    bob.expect("INCOMING_CALL|from:alice");
+    alice.send("CALL_END|to:bob");
+    bob.expect("CALL_ENDED|from:alice");
+    bob.send("CALL_ANSWER|to:alice|accept:true|sdp:v=0");
+    assert_eq!(bob.expect("CALL_ENDED"), "CALL_ENDED|from:alice");
+    assert!(wait_until(
+        || status_of(&state, "bob") == Some(UserStatus::Available)
+    ));
+    assert!(state.calls().is_empty());
+}
+
+/// Alice llama a bob, que atiende y le pasa la llamada a carol.
+fn transferred_call(state: &Arc<ServerState>) -> (TestClient, TestClient, TestClient) {
+    let addr = start_server(Arc::clone(state));
+    let mut alice = TestClient::login(addr, "alice", "alice-secret");
+    let mut bob = TestClient::login(addr, "bob", "bob-secret");
+    let carol = TestClient::login(addr, "carol", "carol-secret");
+
+    alice.send("CALL_OFFER|to:bob|sdp:v=0");
+    bob.expect("INCOMING_CALL|from:alice");
+    bob.send("CALL_ANSWER|to:alice|accept:true|sdp:v=0");
+    alice.expect("CALL_ACCEPTED|from:bob");
+
+    bob.send("CALL_TRANSFER|to:alice|target:carol");
+    assert_eq!(bob.expect("CALL_TRANSFERRED"), "CALL_TRANSFERRED|to:carol");
+    assert_eq!(
+        alice.expect("TRANSFER_REQUESTED"),
+        "TRANSFER_REQUESTED|from:bob|to:carol"
+    );
+    assert!(state.calls().is_empty());
+    (alice, bob, carol)
+}
+
+fn transfer_users() -> MemoryUserStore {
+    MemoryUserStore::with_users(vec![
+        user("alice", "alice-secret"),
+        user("bob", "bob-secret"),
+        user("carol", "carol-secret"),
+        user("dave", "dave-secret"),
+    ])
+}
+
+#[test]
+fn test_transfer_accepted_by_the_target() {
+    let state = memory_state(&transfer_users());
+    let (mut alice, mut bob, mut carol) = transferred_call(&state);
+
+    alice.send("CALL_OFFER|to:carol|sdp:v=0");
+    carol.expect("INCOMING_CALL|from:alice");
+    carol.send("CALL_ANSWER|to:alice|accept:true|sdp:v=0");
+    alice.expect("CALL_ACCEPTED|from:carol");
+    assert_eq!(
+        state.calls().get("carol").map(String::as_str),
+        Some("alice")
+    );
+    assert_eq!(status_of(&state, "bob"), Some(UserStatus::Available));
+
+    // El traspaso ya terminó: cortar después no es un traspaso fallido
+    carol.send("CALL_END|to:alice");
+    alice.expect("CALL_ENDED|from:carol");
+    bob.send("GET_USERS");
+    let lines = bob.lines_until("USER_LIST");
+    assert!(!lines.iter().any(|line| line.starts_with("TRANSFER_FAILED")));
+}
+
+#[test]
+fn test_transfer_rejected_by_the_target() {
+    let state = memory_state(&transfer_users());
+    let (mut alice, mut bob, mut carol) = transferred_call(&state);
+
+    alice.send("CALL_OFFER|to:carol|sdp:v=0");
+    carol.expect("INCOMING_CALL|from:alice");
+    carol.send("CALL_REJECT|to:alice");
+    alice.expect("CALL_REJECTED|from:carol");
+    assert_eq!(
+        bob.expect("TRANSFER_FAILED"),
+        "TRANSFER_FAILED|target:carol|error:call rejected"
+    );
+    assert!(wait_until(
+        || status_of(&state, "alice") == Some(UserStatus::Available)
+    ));
+    assert!(state.calls().is_empty());
+}
+
+#[test]
+fn test_transfer_to_an_offline_target() {
+    let state = memory_state(&transfer_users());
+    let addr = start_server(Arc::clone(&state));
+    let mut alice = TestClient::login(addr, "alice", "alice-secret");
+    let mut bob = TestClient::login(addr, "bob", "bob-secret");
+    alice.send("CALL_OFFER|to:bob|sdp:v=0");
+    bob.expect("INCOMING_CALL|from:alice");
+    bob.send("CALL_ANSWER|to:alice|accept:true|sdp:v=0");
+    alice.expect("CALL_ACCEPTED|from:bob");
+
+    // Dave no está conectado: la llamada original sigue como estaba
+    bob.send("CALL_TRANSFER|to:alice|target:dave");
+    assert_eq!(
+        bob.expect("TRANSFER_FAILED"),
+        "TRANSFER_FAILED|target:dave|error:User not available"
+    );
+    assert_eq!(state.calls().get("alice").map(String::as_str), Some("bob"));
+    assert_eq!(state.calls().get("bob").map(String::as_str), Some("alice"));
+    assert_eq!(status_of(&state, "alice"), Some(UserStatus::Busy));
+    assert_eq!(status_of(&state, "bob"), Some(UserStatus::Busy));
+}
+
+#[test]
+fn test_transfer_target_disconnects_while_ringing() {
+    let state = memory_state(&transfer_users());
+    let (mut alice, mut bob, mut carol) = transferred_call(&state);
+
+    alice.send("CALL_OFFER|to:carol|sdp:v=0");
+    carol.expect("INCOMING_CALL|from:alice");
+    drop(carol);
+    alice.expect("CALL_ENDED|from:carol");
+    assert_eq!(
+        bob.expect("TRANSFER_FAILED"),
+        "TRANSFER_FAILED|target:carol|error:user not connected"
+    );
+    assert!(wait_until(
+        || status_of(&state, "alice") == Some(UserStatus::Available)
+    ));
+}
+
+#[test]
+fn test_call_waiting_rings_only_clients_that_support_it() {
+    let store = MemoryUserStore::with_users(vec![
+        user("alice", "alice-secret"),
+        user("bob", "bob-secret"),
+        user("carol", "carol-secret"),
+        user("dave", "dave-secret"),
+    ]);
+    let state = memory_state(&store);
+    let addr = start_server(Arc::clone(&state));
+
+    let mut alice = TestClient::login(addr, "alice", "alice-secret");
+    let mut bob = TestClient::login(addr, "bob", "bob-secret");
+    let mut carol = TestClient::login(addr, "carol", "carol-secret");
+    let mut dave = TestClient::login(addr, "dave", "dave-secret");
+
+    // Alice es un cliente viejo; bob muestra las llamadas en espera
+    alice.send("CALL_OFFER|to:bob|sdp:v=0");
+    bob.expect("INCOMING_CALL");
+    bob.send("CALL_ANSWER|to:alice|accept:true|sdp:v=0|caps:proto=2,waiting");
+    alice.expect("CALL_ACCEPTED");
+
+    carol.send("CALL_OFFER|to:alice|sdp:v=0");
+    assert!(carol.expect("CALL_ERROR").contains("reason:busy"));
+
+    // Rechazada en espera: la llamada con alice sigue
+    carol.send("CALL_OFFER|to:bob|sdp:v=0");
+    bob.expect("INCOMING_CALL|from:carol");
+    dave.send("CALL_OFFER|to:bob|sdp:v=0");
+    assert!(dave.expect("CALL_ERROR").contains("reason:busy"));
+    bob.send("CALL_REJECT|to:carol");
+    carol.expect("CALL_REJECTED|from:bob");
+    assert!(wait_until(
+        || status_of(&state, "carol") == Some(UserStatus::Available)
+    ));
+    assert_eq!(status_of(&state, "bob"), Some(UserStatus::Busy));
+    assert_eq!(state.calls().get("alice").map(String::as_str), Some("bob"));
+    assert_eq!(state.calls().get("bob").map(String::as_str), Some("alice"));
+
+    // Atendida en espera: la de alice se corta
+    carol.send("CALL_OFFER|to:bob|sdp:v=0");
+    bob.expect("INCOMING_CALL|from:carol");
+    bob.send("CALL_ANSWER|to:carol|accept:true|sdp:v=0|caps:proto=2,waiting");
+    carol.expect("CALL_ACCEPTED|from:bob");
+    assert_eq!(alice.expect("CALL_ENDED"), "CALL_ENDED|from:bob");
+    assert!(wait_until(
+        || status_of(&state, "alice") == Some(UserStatus::Available)
+    ));
+    assert_eq!(state.calls().len(), 2);
+    assert_eq!(state.calls().get("bob").map(String::as_str), Some("carol"));
+}
+
+#[test]
+fn test_blocked_caller_never_reaches_the_callee() {
+    let store = MemoryUserStore::with_users(vec![
+        user("alice", "alice-secret"),
+        user("bob", "bob-secret"),
+    ]);
+    let state = memory_state(&store);
+    let addr = start_server(Arc::clone(&state));
+
+    let mut alice = TestClient::login(addr, "alice", "alice-secret");
+    let mut bob = TestClient::login(addr, "bob", "bob-secret");
+
+    bob.send("BLOCK|username:alice");
+    assert_eq!(bob.expect("BLOCKLIST"), "BLOCKLIST|users:alice");
+
+    alice.send("CALL_OFFER|to:bob|sdp:v=0");
+    assert_eq!(
+        alice.expect("CALL_ERROR"),
+        "CALL_ERROR|error:call not permitted|reason:not_permitted|to:bob"
+    );
+    assert_eq!(status_of(&state, "bob"), Some(UserStatus::Available));
+
+    // Todo lo que le llegó a bob antes de su propio pedido, sin la llamada
+    bob.send("GET_BLOCKLIST");
+    loop {
+        let mut line = String::new();
+        bob.reader.read_line(&mut line).unwrap();
+        assert!(!line.starts_with("INCOMING_CALL"), "bob was notified");
+        if line.starts_with("BLOCKLIST") {
+            break;
+        }
+    }
+
+    bob.send("UNBLOCK|username:alice");
+    assert_eq!(bob.expect("BLOCKLIST"), "BLOCKLIST|users:");
+    alice.send("CALL_OFFER|to:bob|sdp:v=0");
+    assert!(bob.expect("INCOMING_CALL").contains("from:alice"));
+}
+
+#[test]
+fn test_blocklist_is_saved_in_the_store() {
+    let store = MemoryUserStore::with_users(vec![
+        user("alice", "alice-secret"),
+        user("bob", "bob-secret"),
+    ]);
+    let state = memory_state(&store);
+
+    assert!(state.set_blocked("bob", "bob", true).is_err());
+    assert!(state.set_blocked("bob", "nobody", true).is_err());
+    state.set_blocked("bob", "alice", true).unwrap();
+
+    // Otro arranque del servidor con el mismo almacenamiento
+    let restarted = memory_state(&store);
+    assert!(restarted.is_blocked("bob", "alice"));
+    assert!(!restarted.is_blocked("alice", "bob"));
+    assert_eq!(restarted.blocklist("bob"), vec!["alice"]);
+}
+
+#[test]
+fn test_ice_restart_is_relayed_only_within_the_call() {
+    let store = MemoryUserStore::with_users(vec![
+        user("alice", "alice-secret"),
+        user("bob", "bob-secret"),
+        user("carol", "carol-secret"),
+    ]);
+    let state = memory_state(&store);
+    let addr = start_server(Arc::clone(&state));
+
+    let mut alice = TestClient::login(addr, "alice", "alice-secret");
+    let mut bob = TestClient::login(addr, "bob", "bob-secret");
+    let mut carol = TestClient::login(addr, "carol", "carol-secret");
+
+    alice.send("CALL_OFFER|to:bob|sdp:v=0");
+    bob.expect("INCOMING_CALL");
+    bob.send("CALL_ANSWER|to:alice|accept:true|sdp:v=0");
+    alice.expect("CALL_ACCEPTED");
+
+    // Bob pide el reinicio y alice, que controla ICE, manda la oferta
+    bob.send("ICE_RESTART|to:alice");
+    assert_eq!(alice.expect("ICE_RESTART"), "ICE_RESTART|from:bob");
+    alice.send("ICE_RESTART|to:bob|sdp:v=1");
+    assert_eq!(bob.expect("ICE_RESTART"), "ICE_RESTART|from:alice|sdp:v=1");
+    bob.send("ICE_RESTART_ANSWER|to:alice|sdp:v=2");
+    assert_eq!(
+        alice.expect("ICE_RESTART_ANSWER"),
+        "ICE_RESTART_ANSWER|from:bob|sdp:v=2"
+    );
+
+    // Nadie de afuera de la llamada puede mover el camino de la media
+    carol.send("ICE_RESTART|to:alice|sdp:v=1");
+    assert_eq!(
+        carol.expect("ERROR"),
+        "ERROR|error:not in a call with that user"
+    );
+    bob.send("ICE_RESTART_ANSWER|to:alice");
+    assert_eq!(bob.expect("ERROR"), "ERROR|error:missing sdp");
+}
+
+#[test]
+fn test_presence_deltas_reach_the_other_clients() {
+    let store = MemoryUserStore::with_users(vec![
+        user("alice", "alice-secret"),
+        user("bob", "bob-secret"),
+        user("carol", "carol-secret"),
+    ]);
+    let addr = start_server(memory_state(&store));
+
+    let mut alice = TestClient::login(addr, "alice", "alice-secret");
+    alice.send("GET_USERS");
+    assert_eq!(
+        alice.expect("USER_LIST"),
+        "USER_LIST|users:alice=AVAILABLE,bob=DISCONNECTED,carol=DISCONNECTED"
+    );
+
+    let mut bob = TestClient::login(addr, "bob", "bob-secret");
+    assert_eq!(
+        alice.expect("USER_JOINED"),
+        "USER_JOINED|username:bob|status:AVAILABLE"
+    );
+    bob.send("LOGOUT");
+    assert_eq!(alice.expect("USER_LEFT"), "USER_LEFT|username:bob");
+
+    // Una caída sin LOGOUT también se avisa
+    let carol = TestClient::login(addr, "carol", "carol-secret");
+    alice.expect("USER_JOINED|username:carol");
+    drop(carol);
+    assert_eq!(alice.expect("USER_LEFT"), "USER_LEFT|username:carol");
+}
+
+#[test]
+fn test_second_call_end_is_a_no_op() {
+    let store = MemoryUserStore::with_users(vec![
+        user("alice", "alice-secret"),
+        user("bob", "bob-secret"),
+    ]);
+    let state = memory_state(&store);
+    let addr = start_server(Arc::clone(&state));
+
+    let mut alice = TestClient::login(addr, "alice", "alice-secret");
+    let mut bob = TestClient::login(addr, "bob", "bob-secret");
+
+    alice.send("CALL_OFFER|to:bob|sdp:v=0");
+    bob.expect("INCOMING_CALL");
+    bob.send("CALL_ANSWER|to:alice|accept:true|sdp:v=0");
+    alice.expect("CALL_ACCEPTED");
+
+    // Los dos cortan a la vez: el CALL_END de bob llega con la llamada ya
+    // terminada
+    alice.send("CALL_END|to:bob");
+    assert_eq!(bob.expect("CALL_ENDED"), "CALL_ENDED|from:alice");
+    bob.send("CALL_END|to:alice");
+    bob.send("GET_USERS");
+    bob.expect("USER_LIST");
+
+    // Lo que bob pudo haberle mandado a alice ya está antes del USER_LIST
+    alice.send("GET_USERS");
+    let lines = alice.lines_until("USER_LIST");
+    assert!(!lines.iter().any(|l| l.starts_with("CALL_ENDED")));
+    assert_eq!(
+        lines.last().unwrap(),
+        "USER_LIST|users:alice=AVAILABLE,bob=AVAILABLE"
+    );
+    assert!(state.calls().is_empty());
+}
+
+#[test]
+fn test_disconnect_during_call_notifies_peer() {
+    let store = MemoryUserStore::with_users(vec![
+        user("alice", "alice-secret"),
+        user("bob", "bob-secret"),
+    ]);
+    let state = memory_state(&store);
+    let addr = start_server(Arc::clone(&state));
+
+    let mut alice = TestClient::login(addr, "alice", "alice-secret");
+    let mut bob = TestClient::login(addr, "bob", "bob-secret");
+
+    alice.send("CALL_OFFER|to:bob|sdp:v=0");
+    bob.expect("INCOMING_CALL");
+    bob.send("CALL_ANSWER|to:alice|accept:true|sdp:v=0");
+    alice.expect("CALL_ACCEPTED");
+
+    // Alice se cae sin mandar CALL_END ni LOGOUT
+    drop(alice);
+
+    assert_eq!(bob.expect("CALL_ENDED"), "CALL_ENDED|from:alice");
+    assert!(wait_until(|| {
+        status_of(&state, "alice") == Some(UserStatus::Disconnected)
+            && status_of(&state, "bob") == Some(UserStatus::Available)
+    }));
+    assert!(state.calls().is_empty());
+    assert!(!state.clients().contains_key("alice"));
+
+    // Alice vuelve a entrar y Bob puede recibir llamadas otra vez
+    let mut alice = TestClient::login(addr, "alice", "alice-secret");
+    alice.send("CALL_OFFER|to:bob|sdp:v=0");
+    bob.expect("INCOMING_CALL|from:alice");
+}
+
+#[test]
+fn test_login_rejects_wrong_password_and_duplicate_session() {
+    let store = MemoryUserStore::with_users(vec![user("alice", "alice-secret")]);
+    let addr = start_server(memory_state(&store));
+
+    let mut intruder = TestClient::connect(addr);
+    intruder.send("LOGIN|username:alice|password:wrong-pass");
+    assert_eq!(
+        intruder.expect("LOGIN_ERROR"),
+        "LOGIN_ERROR|error:Invalid password"
+    );
+
+    let _alice = TestClient::login(addr, "alice", "alice-secret");
+    let mut second = TestClient::connect(addr);
+    second.send("LOGIN|username:alice|password:alice-secret");
+    assert_eq!(
+        second.expect("LOGIN_ERROR"),
+        "LOGIN_ERROR|error:User already connected"
+    );
+}
+
+/// Token que vino en el LOGIN_SUCCESS.
+fn token_of(success: &str) -> String {
+    parse_message(success).remove("token").unwrap()
+}
+
+#[test]
+fn test_dropped_client_resumes_its_call_within_the_grace_period() {
+    let store = MemoryUserStore::with_users(vec![
+        user("alice", "alice-secret"),
+        user("bob", "bob-secret"),
+    ]);
+    let state = ServerState::with_store(Box::new(store.clone()), Logger::noop())
+        .with_reconnect_grace(Duration::from_secs(60));
+    state.load_users().unwrap();
+    let state = Arc::new(state);
+    let addr = start_server(Arc::clone(&state));
+
+    let mut alice = TestClient::connect(addr);
+    alice.send("LOGIN|username:alice|password:alice-secret");
+    let token = token_of(&alice.expect("LOGIN_SUCCESS"));
+    let mut bob = TestClient::login(addr, "bob", "bob-secret");
+    alice.send("CALL_OFFER|to:bob|sdp:v=0");
+    bob.expect("INCOMING_CALL");
+    bob.send("CALL_ANSWER|to:alice|accept:true|sdp:v=0");
+    alice.expect("CALL_ACCEPTED");
+
+    // Alice se cae y vuelve con su token antes del plazo
+    drop(alice);
+    assert!(wait_until(|| !state.clients().contains_key("alice")));
+    let mut alice = TestClient::connect(addr);
+    alice.send(&format!("LOGIN_TOKEN|username:alice|token:{}", token));
+    assert!(
+        alice
+            .expect("LOGIN_SUCCESS")
+            .starts_with("LOGIN_SUCCESS|message:Session resumed|resumed:true|token:")
+    );
+
+    // La llamada siguió: bob nunca vio irse a alice
+    assert_eq!(state.calls().get("alice").map(String::as_str), Some("bob"));
+    assert_eq!(status_of(&state, "alice"), Some(UserStatus::Busy));
+    bob.send("GET_USERS");
+    let lines = bob.lines_until("USER_LIST");
+    assert!(
+        !lines
+            .iter()
+            .any(|l| l.starts_with("CALL_ENDED") || l.starts_with("USER_LEFT"))
+    );
+    alice.send("CALL_END|to:bob");
+    assert_eq!(bob.expect("CALL_ENDED"), "CALL_ENDED|from:alice");
+}
+
+#[test]
+fn test_held_session_is_cleaned_up_when_the_grace_period_ends() {
+    let store = MemoryUserStore::with_users(vec![
+        user("alice", "alice-secret"),
+        user("bob", "bob-secret"),
+    ]);
+    let state = ServerState::with_store(Box::new(store.clone()), Logger::noop())
+        .with_reconnect_grace(Duration::from_millis(300));
+    state.load_users().unwrap();
+    let state = Arc::new(state);
+    let addr = start_server(Arc::clone(&state));
+
+    let mut alice = TestClient::login(addr, "alice", "alice-secret");
+    let mut bob = TestClient::login(addr, "bob", "bob-secret");
+    alice.send("CALL_OFFER|to:bob|sdp:v=0");
+    bob.expect("INCOMING_CALL");
+    bob.send("CALL_ANSWER|to:alice|accept:true|sdp:v=0");
+    alice.expect("CALL_ACCEPTED");
+
+    drop(alice);
+    assert_eq!(bob.expect("CALL_ENDED"), "CALL_ENDED|from:alice");
+    assert!(wait_until(|| {
+        status_of(&state, "alice") == Some(UserStatus::Disconnected)
+            && status_of(&state, "bob") == Some(UserStatus::Available)
+    }));
+    assert!(state.calls().is_empty());
+
+    // Con la contraseña se entra de cero, sin nada pendiente
+    let mut alice = TestClient::connect(addr);
+    alice.send("LOGIN|username:alice|password:alice-secret");
+    assert!(
+        alice
+            .expect("LOGIN_SUCCESS")
+            .starts_with("LOGIN_SUCCESS|message:Login success|token:")
+    );
+    assert_eq!(status_of(&state, "alice"), Some(UserStatus::Available));
+}
+
+#[test]
+fn test_token_login_and_logout_revokes_it() {
+    let store = MemoryUserStore::with_users(vec![user("alice", "alice-secret")]);
+    let state = memory_state(&store);
+    let addr = start_server(Arc::clone(&state));
+    let login_token = |token: &str| format!("LOGIN_TOKEN|username:alice|token:{}", token);
+    let disconnected = || wait_until(|| !state.clients().contains_key("alice"));
+
+    let mut alice = TestClient::connect(addr);
+    alice.send("LOGIN|username:alice|password:alice-secret");
+    let token = token_of(&alice.expect("LOGIN_SUCCESS"));
+    assert_eq!(token.len(), 64);
+    assert!(token.chars().all(|c| c.is_ascii_hexdigit()));
+
+    // Con la sesión abierta no se puede entrar, pero el token no se pierde
+    let mut early = TestClient::connect(addr);
+    early.send(&login_token(&token));
+    assert_eq!(
+        early.expect("LOGIN_ERROR"),
+        "LOGIN_ERROR|error:User already connected"
+    );
+
+    // Se cortó sin LOGOUT: el token sirve una vez y se cambia por otro
+    drop(alice);
+    assert!(disconnected());
+    let mut other = TestClient::connect(addr);
+    other.send(&format!("LOGIN_TOKEN|username:bob|token:{}", token));
+    assert_eq!(
+        other.expect("LOGIN_ERROR"),
+        "LOGIN_ERROR|error:Invalid token"
+    );
+
+    let mut again = TestClient::connect(addr);
+    again.send(&login_token(&token));
+    let renewed = token_of(&again.expect("LOGIN_SUCCESS"));
+    assert_ne!(renewed, token);
+    drop(again);
+    assert!(disconnected());
+
+    let mut replay = TestClient::connect(addr);
+    replay.send(&login_token(&token));
+    assert_eq!(
+        replay.expect("LOGIN_ERROR"),
+        "LOGIN_ERROR|error:Invalid token"
+    );
+
+    // Después del LOGOUT el último token tampoco sirve
+    let mut last = TestClient::connect(addr);
+    last.send(&login_token(&renewed));
+    let latest = token_of(&last.expect("LOGIN_SUCCESS"));
+    last.send("LOGOUT");
+    assert!(disconnected());
+
+    let mut after = TestClient::connect(addr);
+    after.send(&login_token(&latest));
+    assert_eq!(
+        after.expect("LOGIN_ERROR"),
+        "LOGIN_ERROR|error:Invalid token"
+    );
+}
+
+#[test]
+fn test_malformed_message_closes_connection() {
+    let addr = start_server(memory_state(&MemoryUserStore::new()));
+
+    let mut client = TestClient::connect(addr);
+    client.send("bad type|x:y");
+    assert_eq!(client.expect("ERROR"), "ERROR|error:malformed message");
+
+    let mut rest = String::new();
+    assert_eq!(client.reader.read_line(&mut rest).unwrap(), 0);
+}
+
+#[test]
+fn test_invalid_fields_close_connection() {
+    let addr = start_server(memory_state(&MemoryUserStore::new()));
+
+    let long_field = format!("PING|note:{}", "n".repeat(MAX_FIELD_LEN + 1));
+    let lines = [
+        long_field.as_str(),
+        "PING|bad-key:1",
+        "LOGIN|username:al\u{1}ice|password:secret123",
+        "CALL_OFFER|to:bob|reason:a:b",
+        "CALL_OFFER|to:bob|sdp:v=0\\x",
+        "CALL_OFFER|to:bob|sdp_b64:no base64",
+    ];
+    for line in lines {
+        let mut client = TestClient::connect(addr);
+        client.send(line);
+        assert_eq!(
+            client.expect("ERROR"),
+            "ERROR|error:malformed message",
+            "{}",
+            line
+        );
+        let mut rest = String::new();
+        assert_eq!(client.reader.read_line(&mut rest).unwrap(), 0, "{}", line);
+    }
+}
+
+#[test]
+fn test_password_may_contain_colons() {
+    let addr = start_server(memory_state(&MemoryUserStore::new()));
+
+    // El password es el único campo que admite `:`; llega al handler.
+    let mut client = TestClient::connect(addr);
+    client.send("LOGIN|username:ghost|password:a:b:c:d:e");
+    assert!(client.expect("LOGIN_").starts_with("LOGIN_ERROR"));
+}
+
+#[test]
+fn test_oversized_line_closes_connection() {
+    let addr = start_server(memory_state(&MemoryUserStore::new()));
+
+    // Justo un byte de más y sin salto de línea: el servidor lo lee entero
+    // antes de cortar, así el cierre no se convierte en un reset.
+    let mut client = TestClient::connect(addr);
+    let line = format!("PING|note:{}", "n".repeat(MAX_LINE_LEN));
+    client
+        .writer
+        .write_all(&line.as_bytes()[..MAX_LINE_LEN + 1])
+        .unwrap();
+    client.writer.flush().unwrap();
+    assert_eq!(client.expect("ERROR"), "ERROR|error:message too long");
+
+    let mut rest = String::new();
+    assert_eq!(client.reader.read_line(&mut rest).unwrap(), 0);
+}
+
+#[test]
+fn test_stats_only_for_admins() {
+    let store = MemoryUserStore::with_users(vec![
+        user("alice", "alice-secret"),
+        user("bob", "bob-secret"),
+        user("carol", "carol-secret"),
+    ]);
+    let state = ServerState::with_store(Box::new(store), Logger::noop())
+        .with_admins(vec!["alice".to_string()]);
+    state.load_users().unwrap();
+    let state = Arc::new(state);
+    let addr = start_server(Arc::clone(&state));
+
+    let mut anonymous = TestClient::connect(addr);
+    anonymous.send("STATS");
+    assert_eq!(
+        anonymous.expect("STATS_ERROR"),
+        "STATS_ERROR|error:not authenticated"
+    );
+    anonymous.send("LOGIN|username:carol|password:wrong-pass");
+    anonymous.expect("LOGIN_ERROR");
+
+    let mut alice = TestClient::login(addr, "alice", "alice-secret");
+    let mut bob = TestClient::login(addr, "bob", "bob-secret");
+    bob.send("STATS");
+    assert_eq!(bob.expect("STATS_ERROR"), "STATS_ERROR|error:forbidden");
+
+    alice.send("CALL_OFFER|to:bob|sdp:v=0");
+    bob.expect("INCOMING_CALL");
+    bob.send("CALL_ANSWER|to:alice|accept:true|sdp:v=0");
+    alice.expect("CALL_ACCEPTED");
+
+    alice.send("STATS");
+    let stats = alice.expect("STATS|");
+    assert!(stats.starts_with(
+        "STATS|connected:2|active_calls:1|total_logins:2|failed_logins:1|rejected_connections:0|uptime_secs:"
+    ));
+}
+
+fn admin_state(store: &MemoryUserStore, admins: &[&str]) -> Arc<ServerState> {
+    let state = ServerState::with_store(Box::new(store.clone()), Logger::noop())
+        .with_admins(admins.iter().map(|admin| admin.to_string()).collect());
+    state.load_users().unwrap();
+    Arc::new(state)
+}
+
+#[test]
+fn test_admin_manages_accounts() {
+    let store = MemoryUserStore::with_users(vec![
+        user("alice", "alice-secret"),
+        user("bob", "bob-secret"),
+    ]);
+    let state = admin_state(&store, &["alice"]);
+    let addr = start_server(Arc::clone(&state));
+
+    let mut alice = TestClient::login(addr, "alice", "alice-secret");
+    let mut bob = TestClient::login(addr, "bob", "bob-secret");
+    bob.send("ADMIN_USERS");
+    assert_eq!(bob.expect("ADMIN_ERROR"), "ADMIN_ERROR|error:forbidden");
+
+    alice.send("ADMIN_USERS");
+    assert_eq!(
+        alice.expect("ADMIN_USERS"),
+        "ADMIN_USERS|users:alice,bob|disabled:"
+    );
+    alice.send("ADMIN_ADD_USER|username:carol|password:carol-secret");
+    assert_eq!(
+        alice.expect("ADMIN_USERS"),
+        "ADMIN_USERS|users:alice,bob,carol|disabled:"
+    );
+    alice.send("ADMIN_ADD_USER|username:carol|password:carol-secret");
+    assert_eq!(
+        alice.expect("ADMIN_ERROR"),
+        "ADMIN_ERROR|error:User already exist"
+    );
+
+    // Deshabilitar corta la sesión abierta y no deja volver a entrar
+    alice.send("ADMIN_DISABLE_USER|username:bob");
+    assert_eq!(
+        alice.expect("ADMIN_USERS"),
+        "ADMIN_USERS|users:alice,bob,carol|disabled:bob"
+    );
+    assert_eq!(
+        bob.expect("SESSION_CLOSED"),
+        "SESSION_CLOSED|reason:account disabled"
+    );
+    assert!(wait_until(
+        || status_of(&state, "bob") == Some(UserStatus::Disconnected)
+    ));
+    let mut again = TestClient::connect(addr);
+    again.send("LOGIN|username:bob|password:bob-secret");
+    assert_eq!(
+        again.expect("LOGIN_ERROR"),
+        "LOGIN_ERROR|error:Account disabled"
+    );
+
+    alice.send("ADMIN_ENABLE_USER|username:bob");
+    alice.expect("ADMIN_USERS");
+    TestClient::login(addr, "bob", "bob-secret");
+
+    alice.send("ADMIN_REMOVE_USER|username:carol");
+    assert_eq!(
+        alice.expect("ADMIN_USERS"),
+        "ADMIN_USERS|users:alice,bob|disabled:"
+    );
+
+    // Todo quedó guardado: otro arranque ve las mismas cuentas
+    let restarted = memory_state(&store);
+    assert_eq!(
+        restarted.accounts(),
+        vec![("alice".to_string(), false), ("bob".to_string(), false)]
+    );
+}
+
+/// Cuentas creadas antes de las reglas de username (acá "jo", de dos
+/// letras) siguen pudiendo entrar; el registro sí exige el formato.
+#[test]
+fn test_legacy_username_still_logs_in() {
+    let store = MemoryUserStore::with_users(vec![user("jo", "jo-secret")]);
+    let state = admin_state(&store, &[]);
+    let addr = start_server(Arc::clone(&state));
+
+    let _jo = TestClient::login(addr, "jo", "jo-secret");
+
+    let mut newcomer = TestClient::connect(addr);
+    newcomer.send("REGISTER|username:jo|password:jo-secret");
+    assert_eq!(
+        newcomer.expect("REGISTER_ERROR"),
+        "REGISTER_ERROR|error:invalid_username"
+    );
+}
+
+/// Un admin maneja por nombre exacto las cuentas anteriores a las reglas
+/// de username.
+#[test]
+fn test_admin_manages_legacy_accounts_by_exact_name() {
+    let store =
+        MemoryUserStore::with_users(vec![user("alice", "alice-secret"), user("jo", "jo-secret")]);
+    let state = admin_state(&store, &["alice"]);
+    let addr = start_server(Arc::clone(&state));
+
+    let mut alice = TestClient::login(addr, "alice", "alice-secret");
+    let mut jo = TestClient::login(addr, "jo", "jo-secret");
+
+    alice.send("ADMIN_DISABLE_USER|username:jo");
+    assert_eq!(
+        alice.expect("ADMIN_USERS"),
+        "ADMIN_USERS|users:alice,jo|disabled:jo"
+    );
+    jo.expect("SESSION_CLOSED");
+
+    alice.send("ADMIN_REMOVE_USER|username:jo");
+    assert_eq!(
+        alice.expect("ADMIN_USERS"),
+        "ADMIN_USERS|users:alice|disabled:"
+    );
+}
+
+/// Llamar o bloquear a una cuenta anterior a las reglas de username no es
+/// un mensaje mal formado: quien lo manda sigue conectado.
+#[test]
+fn test_calling_a_legacy_username_keeps_the_caller_connected() {
+    let store =
+        MemoryUserStore::with_users(vec![user("alice", "alice-secret"), user("jo", "jo-secret")]);
+    let state = admin_state(&store, &[]);
+    let addr = start_server(Arc::clone(&state));
+
+    let mut alice = TestClient::login(addr, "alice", "alice-secret");
+    let mut jo = TestClient::login(addr, "jo", "jo-secret");
+
+    // Un nombre corto que no existe es un error de la llamada, no del mensaje
+    alice.send("CALL_OFFER|to:xy|sdp:v=0");
+    assert_eq!(
+        alice.expect("CALL_ERROR"),
+        "CALL_ERROR|error:User does not exist|reason:not_found|to:xy"
+    );
+
+    alice.send("CALL_OFFER|to:jo|sdp:v=0");
+    assert!(
+        jo.expect("INCOMING_CALL")
+            .starts_with("INCOMING_CALL|from:alice|")
+    );
+    jo.send("CALL_ANSWER|to:alice|accept:false");
+    alice.expect("CALL_REJECTED");
+
+    alice.send("BLOCK|username:jo");
+    assert_eq!(alice.expect("BLOCKLIST"), "BLOCKLIST|users:jo");
+}
+
+#[test]
+fn test_last_admin_cannot_be_removed_or_disabled() {
+    let store = MemoryUserStore::with_users(vec![
+        user("alice", "alice-secret"),
+        user("bob", "bob-secret"),
+    ]);
+    let state = admin_state(&store, &["alice", "bob", "ghost"]);
+
+    // `ghost` figura como admin pero no tiene cuenta: no cuenta
+    state.set_disabled("bob", true).unwrap();
+    assert_eq!(
+        state.remove_user("alice"),
+        Err("cannot remove the last admin".to_string())
+    );
+    assert_eq!(
+        state.set_disabled("alice", true),
+        Err("cannot remove the last admin".to_string())
+    );
+
+    state.set_disabled("bob", false).unwrap();
+    state.remove_user("alice").unwrap();
+    assert!(!state.account_active("alice"));
+    assert_eq!(
+        state.authenticate("alice", "alice-secret"),
+        Err("User does not exist".to_string())
+    );
+}
+
+/// Hace un GET a `path` en el listener de métricas y devuelve la respuesta.
+fn http_get(addr: SocketAddr, path: &str) -> String {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    stream.set_read_timeout(Some(RESPONSE_TIMEOUT)).unwrap();
+    write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    response
+}
+
+#[test]
+fn test_metrics_endpoint_reports_the_counters() {
+    let store = MemoryUserStore::with_users(vec![
+        user("alice", "alice-secret"),
+        user("bob", "bob-secret"),
+    ]);
+    let state = memory_state(&store);
+    let addr = start_server(Arc::clone(&state));
+    let metrics_listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let metrics_addr = metrics_listener.local_addr().unwrap();
+    let metrics_state = Arc::clone(&state);
+    thread::spawn(move || metrics::serve(metrics_listener, metrics_state));
+
+    let mut anonymous = TestClient::connect(addr);
+    anonymous.send("LOGIN|username:bob|password:wrong-pass");
+    anonymous.expect("LOGIN_ERROR");
+    let mut alice = TestClient::login(addr, "alice", "alice-secret");
+    alice.send("GET_USERS");
+    alice.expect("USER_LIST");
+    alice.send("NOT_A_MESSAGE");
+    alice.expect("ERROR");
+
+    let response = http_get(metrics_addr, "/metrics");
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(response.contains("Content-Type: text/plain; version=0.0.4"));
+    let body = response.split("\r\n\r\n").nth(1).unwrap();
+    let lines: Vec<&str> = body.lines().collect();
+    for expected in [
+        "roomrtc_connected_clients 1",
+        "roomrtc_active_calls 0",
+        "roomrtc_logins_total 1",
+        "roomrtc_auth_failures_total 1",
+        "roomrtc_messages_total{type=\"LOGIN\"} 2",
+        "roomrtc_messages_total{type=\"GET_USERS\"} 1",
+        "roomrtc_messages_total{type=\"CALL_OFFER\"} 0",
+        "roomrtc_messages_total{type=\"other\"} 1",
+        "roomrtc_messages_sent_total{type=\"LOGIN_SUCCESS\"} 1",
+        "roomrtc_messages_sent_total{type=\"LOGIN_ERROR\"} 1",
+        "roomrtc_messages_sent_total{type=\"USER_LIST\"} 1",
+        "# TYPE roomrtc_auth_failures_total counter",
+    ] {
+        assert!(
+            lines.contains(&expected),
+            "missing {:?} in\n{}",
+            expected,
+            body
+        );
+    }
+    let forwarded: u64 = lines
+        .iter()
+        .find_map(|line| line.strip_prefix("roomrtc_bytes_forwarded_total "))
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!(forwarded > 0);
+
+    assert!(http_get(metrics_addr, "/other").starts_with("HTTP/1.1 404"));
+}
+
+#[test]
+fn test_message_trace_redacts_passwords() {
+    let log_path = std::env::temp_dir().join(format!("roomrtc-trace-{}.log", std::process::id()));
+    let _ = std::fs::remove_file(&log_path);
+    let logger = Logger::start(&log_path, LogOptions::default()).unwrap();
+    let store = MemoryUserStore::with_users(vec![user("alice", "alice-secret")]);
+    let state = ServerState::with_store(Box::new(store), logger).with_trace_messages(true);
+    state.load_users().unwrap();
+    let addr = start_server(Arc::new(state));
+
+    let mut alice = TestClient::login(addr, "alice", "alice-secret");
+    alice.send("GET_USERS");
+    alice.expect("USER_LIST");
+
+    let read_log = || std::fs::read_to_string(&log_path).unwrap_or_default();
+    assert!(wait_until(|| read_log().contains("[USER_LIST]")));
+    let log = read_log();
+    assert!(log.contains("Mensaje recibido de - ("));
+    assert!(log.contains("[LOGIN]: LOGIN|username:alice|password:***"));
+    assert!(log.contains("Mensaje enviado a alice ("));
+    assert!(log.contains("token:***"));
+    assert!(!log.contains("alice-secret"));
+}
+
+#[test]
+fn test_poisoned_lock_does_not_break_the_server() {
+    let store = MemoryUserStore::with_users(vec![
+        user("alice", "alice-secret"),
+        user("bob", "bob-secret"),
+    ]);
+    let state = memory_state(&store);
+    let addr = start_server(Arc::clone(&state));
+
+    // Un hilo que hace panic con los locks tomados los deja envenenados
+    let poisoner = Arc::clone(&state);
+    let crashed = thread::spawn(move || {
+        let _clients = poisoner.clients_mut();
+        let _calls = poisoner.calls_mut();
+        panic!("handler roto a propósito");
+    })
+    .join();
+    assert!(crashed.is_err());
+
+    let mut alice = TestClient::login(addr, "alice", "alice-secret");
+    let mut bob = TestClient::login(addr, "bob", "bob-secret");
+    alice.send("CALL_OFFER|to:bob|sdp:v=0");
+    bob.expect("INCOMING_CALL|from:alice");
+    assert_eq!(state.calls().get("alice").map(String::as_str), Some("bob"));
+    assert_eq!(state.stats().connected_clients, 2);
+}
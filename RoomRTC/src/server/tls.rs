@@ -1,23 +1,86 @@
 //! Configuración TLS del servidor.
 
+use std::fs::File;
+use std::io::{self, BufReader};
 use std::sync::Arc;
 
 use rcgen::generate_simple_self_signed;
-use rustls::ServerConfig;
+use rustls::{Certificate, PrivateKey, ServerConfig};
+use rustls_pemfile::Item;
+use sha2::{Digest, Sha256};
 
-/// Construye la configuración TLS con un certificado self-signed.
-pub fn build_tls_config() -> Arc<ServerConfig> {
-    let cert = generate_simple_self_signed(["roomrtc.local".to_string()]).expect("cert");
-    let cert_der = cert.serialize_der().expect("cert der");
-    let key_der = cert.serialize_private_key_der();
+use crate::config::AppConfig;
 
-    let rustls_cert = rustls::Certificate(cert_der);
-    let rustls_key = rustls::PrivateKey(key_der);
+/// Construye la configuración TLS con el certificado de `tls_cert_file` y
+/// `tls_key_file`. Sin ellos solo arranca en `tls_dev_mode`, con un
+/// certificado self-signed generado en el momento.
+///
+/// Devuelve también el fingerprint SHA-256 del certificado, para que los
+/// clientes lo puedan fijar con `tls_fingerprint`.
+pub fn build_tls_config(config: &AppConfig) -> io::Result<(Arc<ServerConfig>, String)> {
+    let (cert, key) = match (&config.tls_cert_file, &config.tls_key_file) {
+        (Some(cert_file), Some(key_file)) => (load_cert_chain(cert_file)?, load_key(key_file)?),
+        (None, None) if config.tls_dev_mode => self_signed()?,
+        (None, None) => {
+            return Err(io::Error::other(
+                "missing tls_cert_file/tls_key_file (set tls_dev_mode=true for a self-signed certificate)",
+            ));
+        }
+        _ => {
+            return Err(io::Error::other(
+                "tls_cert_file and tls_key_file must be set together",
+            ));
+        }
+    };
+    let fingerprint = cert_fingerprint(&cert[0].0);
 
-    let config = rustls::ServerConfig::builder()
+    let config = ServerConfig::builder()
         .with_safe_defaults()
         .with_no_client_auth()
-        .with_single_cert(vec![rustls_cert], rustls_key)
-        .expect("config");
-    Arc::new(config)
+        .with_single_cert(cert, key)
+        .map_err(|e| io::Error::other(format!("invalid certificate or key: {}", e)))?;
+    Ok((Arc::new(config), fingerprint))
+}
+
+/// SHA-256 de un certificado DER, en hex con `:` como en los SDP.
+pub fn cert_fingerprint(cert_der: &[u8]) -> String {
+    Sha256::digest(cert_der)
+        .iter()
+        .map(|b| format!("{:02X}", b))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+fn self_signed() -> io::Result<(Vec<Certificate>, PrivateKey)> {
+    let cert = generate_simple_self_signed(["roomrtc.local".to_string()])
+        .map_err(|e| io::Error::other(e.to_string()))?;
+    let cert_der = cert
+        .serialize_der()
+        .map_err(|e| io::Error::other(e.to_string()))?;
+    let key_der = cert.serialize_private_key_der();
+    Ok((vec![Certificate(cert_der)], PrivateKey(key_der)))
+}
+
+/// Lee todos los certificados de un PEM (el del servidor primero).
+pub fn load_cert_chain(path: &str) -> io::Result<Vec<Certificate>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let certs = rustls_pemfile::certs(&mut reader)?;
+    if certs.is_empty() {
+        return Err(io::Error::other(format!("no certificates in {}", path)));
+    }
+    Ok(certs.into_iter().map(Certificate).collect())
+}
+
+/// Lee la primera clave privada de un PEM (PKCS#8, RSA o EC).
+fn load_key(path: &str) -> io::Result<PrivateKey> {
+    let mut reader = BufReader::new(File::open(path)?);
+    while let Some(item) = rustls_pemfile::read_one(&mut reader)? {
+        match item {
+            Item::PKCS8Key(key) | Item::RSAKey(key) | Item::ECKey(key) => {
+                return Ok(PrivateKey(key));
+            }
+            _ => {}
+        }
+    }
+    Err(io::Error::other(format!("no private key in {}", path)))
 }
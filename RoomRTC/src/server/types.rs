@@ -31,10 +31,37 @@ pub struct User {
     pub metadata: String,
 }
 
+/// Marca en `metadata` de una cuenta que un admin deshabilitó.
+const DISABLED_FLAG: &str = "disabled";
+
+impl User {
+    /// La cuenta existe pero no puede entrar.
+    pub fn is_disabled(&self) -> bool {
+        self.metadata.split(',').any(|flag| flag == DISABLED_FLAG)
+    }
+
+    /// Agrega o saca la marca de deshabilitada, sin tocar el resto de
+    /// `metadata`.
+    pub fn set_disabled(&mut self, disabled: bool) {
+        let mut flags: Vec<&str> = self
+            .metadata
+            .split(',')
+            .filter(|flag| !flag.is_empty() && *flag != DISABLED_FLAG)
+            .collect();
+        if disabled {
+            flags.push(DISABLED_FLAG);
+        }
+        self.metadata = flags.join(",");
+    }
+}
+
 /// Alias para el stream TLS del servidor.
 pub type TlsStream = StreamOwned<ServerConnection, TcpStream>;
 
 /// Cliente conectado con su canal de envío.
 pub struct ConnectedClient {
     pub sender: Sender<String>,
+    /// Anunció `features:b64` en el LOGIN: se le mandan los payloads en
+    /// base64.
+    pub payload_b64: bool,
 }
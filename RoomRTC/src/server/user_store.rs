@@ -0,0 +1,435 @@
+//! Persistencia de usuarios del servidor.
+//!
+//! `ServerState` solo conoce el trait: en producción los usuarios viven en un
+//! archivo `usuario:password:metadata` (y sus bloqueos en `<archivo>.blocked`,
+//! `usuario:bloqueado1,bloqueado2`) y en los tests en memoria.
+//!
+//! Con una passphrase el archivo de usuarios se guarda cifrado (ver
+//! `users_crypto`). Uno viejo en texto plano se lee igual y se reescribe
+//! cifrado al guardar el próximo usuario.
+
+use std::collections::{BTreeSet, HashMap};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+
+use super::types::User;
+use super::users_crypto::{self, DEFAULT_KDF_ITERATIONS};
+
+/// Usuarios que bloqueó cada usuario.
+pub type Blocklists = HashMap<String, BTreeSet<String>>;
+
+/// Almacenamiento de los usuarios registrados.
+pub trait UserStore: Send + Sync {
+    /// Devuelve los usuarios guardados. Si el almacenamiento todavía no
+    /// existe, lo crea vacío.
+    fn load(&self) -> io::Result<Vec<User>>;
+
+    /// Agrega un usuario nuevo.
+    fn save(&self, user: &User) -> io::Result<()>;
+
+    /// Reemplaza todos los usuarios guardados (altas, bajas y cuentas
+    /// deshabilitadas desde los comandos de admin).
+    fn save_all(&self, users: &[User]) -> io::Result<()>;
+
+    /// Devuelve los bloqueos guardados; sin ninguno, un mapa vacío.
+    fn load_blocklists(&self) -> io::Result<Blocklists>;
+
+    /// Reemplaza todos los bloqueos guardados.
+    fn save_blocklists(&self, blocklists: &Blocklists) -> io::Result<()>;
+
+    /// Descripción para los logs (ruta del archivo, "memoria").
+    fn describe(&self) -> String;
+}
+
+/// Usuarios en un archivo de texto, una línea por usuario.
+pub struct FileUserStore {
+    path: String,
+    passphrase: Option<String>,
+    kdf_iterations: u32,
+    // Con passphrase, el archivo todavía está en texto plano
+    plaintext: AtomicBool,
+}
+
+impl FileUserStore {
+    pub fn new(path: &str) -> Self {
+        Self {
+            path: path.to_string(),
+            passphrase: None,
+            kdf_iterations: DEFAULT_KDF_ITERATIONS,
+            plaintext: AtomicBool::new(false),
+        }
+    }
+
+    /// Guarda el archivo cifrado con una clave derivada de `passphrase`.
+    /// Con `None` queda en texto plano, como antes.
+    pub fn with_passphrase(mut self, passphrase: Option<String>) -> Self {
+        self.passphrase = passphrase.filter(|p| !p.is_empty());
+        self
+    }
+
+    #[cfg(test)]
+    fn with_kdf_iterations(mut self, iterations: u32) -> Self {
+        self.kdf_iterations = iterations;
+        self
+    }
+
+    fn blocklists_path(&self) -> String {
+        format!("{}.blocked", self.path)
+    }
+
+    /// Contenido del archivo en claro, y si estaba cifrado. Un archivo cifrado
+    /// sin passphrase, o con otra, es un error: tratarlo como vacío borraría
+    /// todas las cuentas en el próximo guardado.
+    fn read_plaintext(&self) -> io::Result<Option<(String, bool)>> {
+        let content = match fs::read_to_string(&self.path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err),
+        };
+        if !users_crypto::is_encrypted(&content) {
+            return Ok(Some((content, false)));
+        }
+        let Some(passphrase) = &self.passphrase else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "{} está cifrado: falta users_passphrase o ROOMRTC_USERS_PASSPHRASE",
+                    self.path
+                ),
+            ));
+        };
+        let plaintext = users_crypto::decrypt(&content, passphrase)
+            .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", self.path, e)))?;
+        Ok(Some((plaintext, true)))
+    }
+
+    /// Reescribe todo el archivo cifrado, a un temporal que después se
+    /// renombra.
+    fn write_encrypted(&self, plaintext: &str, passphrase: &str) -> io::Result<()> {
+        let sealed = users_crypto::encrypt(plaintext, passphrase, self.kdf_iterations)?;
+        self.replace_file(&sealed)
+    }
+
+    fn replace_file(&self, content: &str) -> io::Result<()> {
+        let tmp = format!("{}.tmp", self.path);
+        let mut options = OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        // Las contraseñas solo las lee el servidor
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        options.open(&tmp)?.write_all(content.as_bytes())?;
+        fs::rename(&tmp, &self.path)
+    }
+}
+
+fn parse_users(content: &str) -> Vec<User> {
+    let mut users = Vec::new();
+    for line in content.lines() {
+        let parts: Vec<&str> = line.split(':').collect();
+
+        if parts.len() >= 2 {
+            let metadata = match parts.get(2) {
+                Some(val) => val.to_string(),
+                None => String::new(),
+            };
+            users.push(User {
+                username: parts[0].to_string(),
+                password: parts[1].to_string(),
+                metadata,
+            });
+        }
+    }
+    users
+}
+
+fn user_line(user: &User) -> String {
+    format!("{}:{}:{}\n", user.username, user.password, user.metadata)
+}
+
+impl UserStore for FileUserStore {
+    fn load(&self) -> io::Result<Vec<User>> {
+        let Some((content, encrypted)) = self.read_plaintext()? else {
+            File::create(&self.path)?;
+            return Ok(Vec::new());
+        };
+        self.plaintext
+            .store(self.passphrase.is_some() && !encrypted, Ordering::Relaxed);
+        Ok(parse_users(&content))
+    }
+
+    fn save(&self, user: &User) -> io::Result<()> {
+        let Some(passphrase) = &self.passphrase else {
+            let mut file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)?;
+            return file.write_all(user_line(user).as_bytes());
+        };
+
+        // Cifrado no se puede agregar al final: se reescribe entero, y un
+        // archivo en texto plano queda migrado
+        let mut plaintext = self
+            .read_plaintext()?
+            .map(|(content, _)| content)
+            .unwrap_or_default();
+        if !plaintext.is_empty() && !plaintext.ends_with('\n') {
+            plaintext.push('\n');
+        }
+        plaintext.push_str(&user_line(user));
+        self.write_encrypted(&plaintext, passphrase)?;
+        self.plaintext.store(false, Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn save_all(&self, users: &[User]) -> io::Result<()> {
+        let plaintext: String = users.iter().map(user_line).collect();
+        match &self.passphrase {
+            Some(passphrase) => {
+                self.write_encrypted(&plaintext, passphrase)?;
+                self.plaintext.store(false, Ordering::Relaxed);
+                Ok(())
+            }
+            None => self.replace_file(&plaintext),
+        }
+    }
+
+    fn load_blocklists(&self) -> io::Result<Blocklists> {
+        let content = match fs::read_to_string(self.blocklists_path()) {
+            Ok(content) => content,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Blocklists::new()),
+            Err(err) => return Err(err),
+        };
+
+        let mut blocklists = Blocklists::new();
+        for line in content.lines() {
+            let Some((username, blocked)) = line.split_once(':') else {
+                continue;
+            };
+            let blocked: BTreeSet<String> = blocked
+                .split(',')
+                .filter(|name| !name.is_empty())
+                .map(str::to_string)
+                .collect();
+            if !blocked.is_empty() {
+                blocklists.insert(username.to_string(), blocked);
+            }
+        }
+        Ok(blocklists)
+    }
+
+    /// Escribe a un temporal y lo renombra, así un corte a mitad de camino
+    /// no deja el archivo a medias.
+    fn save_blocklists(&self, blocklists: &Blocklists) -> io::Result<()> {
+        let mut usernames: Vec<&String> = blocklists.keys().collect();
+        usernames.sort();
+        let mut content = String::new();
+        for username in usernames {
+            let blocked = &blocklists[username];
+            if blocked.is_empty() {
+                continue;
+            }
+            let blocked: Vec<&str> = blocked.iter().map(String::as_str).collect();
+            content.push_str(&format!("{}:{}\n", username, blocked.join(",")));
+        }
+
+        let path = self.blocklists_path();
+        let tmp = format!("{}.tmp", path);
+        fs::write(&tmp, content)?;
+        fs::rename(&tmp, &path)
+    }
+
+    fn describe(&self) -> String {
+        if self.plaintext.load(Ordering::Relaxed) {
+            format!(
+                "{} (en texto plano, se cifra al guardar el próximo usuario)",
+                self.path
+            )
+        } else if self.passphrase.is_some() {
+            format!("{} (cifrado)", self.path)
+        } else {
+            self.path.clone()
+        }
+    }
+}
+
+/// Usuarios en memoria, sin tocar el disco. Lo usan los tests del servidor;
+/// los clones comparten la misma lista.
+#[allow(dead_code)]
+#[derive(Clone, Default)]
+pub struct MemoryUserStore {
+    users: Arc<Mutex<Vec<User>>>,
+    blocklists: Arc<Mutex<Blocklists>>,
+}
+
+#[allow(dead_code)]
+impl MemoryUserStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Arranca con usuarios ya registrados.
+    pub fn with_users(users: Vec<User>) -> Self {
+        Self {
+            users: Arc::new(Mutex::new(users)),
+            blocklists: Arc::default(),
+        }
+    }
+}
+
+impl UserStore for MemoryUserStore {
+    fn load(&self) -> io::Result<Vec<User>> {
+        Ok(self
+            .users
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone())
+    }
+
+    fn save(&self, user: &User) -> io::Result<()> {
+        self.users
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(user.clone());
+        Ok(())
+    }
+
+    fn save_all(&self, users: &[User]) -> io::Result<()> {
+        *self.users.lock().unwrap_or_else(PoisonError::into_inner) = users.to_vec();
+        Ok(())
+    }
+
+    fn load_blocklists(&self) -> io::Result<Blocklists> {
+        Ok(self
+            .blocklists
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone())
+    }
+
+    fn save_blocklists(&self, blocklists: &Blocklists) -> io::Result<()> {
+        *self
+            .blocklists
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = blocklists.clone();
+        Ok(())
+    }
+
+    fn describe(&self) -> String {
+        "memoria".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PASSPHRASE: &str = "correct horse battery";
+
+    fn user(name: &str) -> User {
+        User {
+            username: name.to_string(),
+            password: format!("{}-secret", name),
+            metadata: String::new(),
+        }
+    }
+
+    fn temp_path(name: &str) -> String {
+        let path =
+            std::env::temp_dir().join(format!("roomrtc-users-{}-{}.txt", name, std::process::id()));
+        let _ = fs::remove_file(&path);
+        path.to_str().unwrap().to_string()
+    }
+
+    fn encrypted_store(path: &str, passphrase: &str) -> FileUserStore {
+        FileUserStore::new(path)
+            .with_passphrase(Some(passphrase.to_string()))
+            .with_kdf_iterations(1_000)
+    }
+
+    fn names(users: &[User]) -> Vec<&str> {
+        users.iter().map(|u| u.username.as_str()).collect()
+    }
+
+    #[test]
+    fn test_encrypted_roundtrip() {
+        let path = temp_path("roundtrip");
+        let store = encrypted_store(&path, PASSPHRASE);
+        assert!(store.load().unwrap().is_empty());
+        store.save(&user("alice")).unwrap();
+        store.save(&user("bob")).unwrap();
+
+        let on_disk = fs::read_to_string(&path).unwrap();
+        let loaded = encrypted_store(&path, PASSPHRASE).load();
+        let _ = fs::remove_file(&path);
+
+        assert!(!on_disk.contains("alice"));
+        let loaded = loaded.unwrap();
+        assert_eq!(names(&loaded), ["alice", "bob"]);
+        assert_eq!(loaded[1].password, "bob-secret");
+    }
+
+    #[test]
+    fn test_plaintext_file_is_migrated_on_first_save() {
+        let path = temp_path("migration");
+        fs::write(&path, "alice:alice-secret:\nbob:bob-secret:").unwrap();
+        let store = encrypted_store(&path, PASSPHRASE);
+        assert_eq!(names(&store.load().unwrap()), ["alice", "bob"]);
+        assert!(store.describe().contains("texto plano"));
+
+        store.save(&user("carol")).unwrap();
+        let on_disk = fs::read_to_string(&path).unwrap();
+        let reloaded = encrypted_store(&path, PASSPHRASE).load();
+        let without_key = FileUserStore::new(&path).load();
+        let _ = fs::remove_file(&path);
+
+        assert!(users_crypto::is_encrypted(&on_disk));
+        assert!(store.describe().contains("cifrado"));
+        assert_eq!(names(&reloaded.unwrap()), ["alice", "bob", "carol"]);
+        assert!(without_key.is_err());
+    }
+
+    #[test]
+    fn test_save_all_replaces_the_file() {
+        let path = temp_path("save-all");
+        let store = FileUserStore::new(&path);
+        store.load().unwrap();
+        store.save(&user("alice")).unwrap();
+        store.save(&user("bob")).unwrap();
+
+        let mut carol = user("carol");
+        carol.set_disabled(true);
+        store.save_all(&[user("alice"), carol]).unwrap();
+        let reloaded = FileUserStore::new(&path).load();
+        let _ = fs::remove_file(&path);
+
+        let reloaded = reloaded.unwrap();
+        assert_eq!(names(&reloaded), ["alice", "carol"]);
+        assert!(!reloaded[0].is_disabled());
+        assert!(reloaded[1].is_disabled());
+    }
+
+    #[test]
+    fn test_wrong_passphrase_fails_instead_of_wiping() {
+        let path = temp_path("wrong-key");
+        encrypted_store(&path, PASSPHRASE)
+            .save(&user("alice"))
+            .unwrap();
+
+        let store = encrypted_store(&path, "not the passphrase");
+        let loaded = store.load();
+        let saved = store.save(&user("mallory"));
+        let still_there = encrypted_store(&path, PASSPHRASE).load();
+        let _ = fs::remove_file(&path);
+
+        let err = loaded.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("passphrase"));
+        assert!(saved.is_err());
+        assert_eq!(names(&still_there.unwrap()), ["alice"]);
+    }
+}
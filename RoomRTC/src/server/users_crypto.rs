@@ -0,0 +1,183 @@
+//! Cifrado en reposo del archivo de usuarios.
+//!
+//! El archivo cifrado es una línea de encabezado y el contenido en base64:
+//!
+//! ```text
+//! ROOMRTC-USERS v1 pbkdf2-sha256 <iteraciones> <sal> <nonce>
+//! <usuarios cifrados con AES-256-GCM>
+//! ```
+//!
+//! La clave sale de la passphrase con PBKDF2-HMAC-SHA256 y la sal del
+//! encabezado; el encabezado va como dato asociado, así que tocarlo también
+//! hace fallar el descifrado. Un archivo que no empieza con `ROOMRTC-USERS`
+//! es del formato viejo, en texto plano.
+
+use std::io;
+
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use rand::RngCore;
+use sha2::Sha256;
+
+const MAGIC: &str = "ROOMRTC-USERS";
+const FORMAT_VERSION: &str = "v1";
+const KDF: &str = "pbkdf2-sha256";
+/// Iteraciones de PBKDF2 para los archivos nuevos. Los que ya existen
+/// guardan las suyas en el encabezado.
+pub const DEFAULT_KDF_ITERATIONS: u32 = 200_000;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+const KEY_LEN: usize = 32;
+
+/// Si `content` está en el formato cifrado (de cualquier versión).
+pub fn is_encrypted(content: &str) -> bool {
+    content.starts_with(MAGIC)
+}
+
+/// Cifra `plaintext` con una clave derivada de `passphrase`. Cada llamada
+/// usa sal y nonce nuevos.
+pub fn encrypt(plaintext: &str, passphrase: &str, iterations: u32) -> io::Result<String> {
+    let mut salt = [0u8; SALT_LEN];
+    let mut nonce = [0u8; NONCE_LEN];
+    rand::thread_rng().fill_bytes(&mut salt);
+    rand::thread_rng().fill_bytes(&mut nonce);
+
+    let header = format!(
+        "{} {} {} {} {} {}",
+        MAGIC,
+        FORMAT_VERSION,
+        KDF,
+        iterations,
+        STANDARD.encode(salt),
+        STANDARD.encode(nonce)
+    );
+    let cipher = cipher(passphrase, &salt, iterations)?;
+    let ciphertext = cipher
+        .encrypt(
+            Nonce::from_slice(&nonce),
+            Payload {
+                msg: plaintext.as_bytes(),
+                aad: header.as_bytes(),
+            },
+        )
+        .map_err(|_| invalid_data("No se pudo cifrar el archivo de usuarios".to_string()))?;
+    Ok(format!("{}\n{}\n", header, STANDARD.encode(ciphertext)))
+}
+
+/// Descifra un archivo escrito por [`encrypt`]. Con otra passphrase, o si el
+/// archivo fue modificado, falla en vez de devolver algo a medias.
+pub fn decrypt(content: &str, passphrase: &str) -> io::Result<String> {
+    let (header, body) = content
+        .split_once('\n')
+        .ok_or_else(|| invalid_data("Archivo de usuarios cifrado incompleto".to_string()))?;
+    let fields: Vec<&str> = header.split(' ').collect();
+    if fields.get(1) != Some(&FORMAT_VERSION) {
+        return Err(invalid_data(format!(
+            "Versión del archivo de usuarios no soportada: {}",
+            fields.get(1).unwrap_or(&"?")
+        )));
+    }
+    let [_, _, kdf, iterations, salt, nonce] = fields.as_slice() else {
+        return Err(invalid_data(
+            "Encabezado del archivo de usuarios inválido".to_string(),
+        ));
+    };
+    if *kdf != KDF {
+        return Err(invalid_data(format!(
+            "Derivación de clave no soportada: {}",
+            kdf
+        )));
+    }
+    let iterations: u32 = iterations
+        .parse()
+        .ok()
+        .filter(|n| *n > 0)
+        .ok_or_else(|| invalid_data(format!("Iteraciones inválidas: {}", iterations)))?;
+    let salt = decode(salt)?;
+    let nonce = decode(nonce)?;
+    if nonce.len() != NONCE_LEN {
+        return Err(invalid_data(
+            "Nonce del archivo de usuarios inválido".to_string(),
+        ));
+    }
+    let ciphertext = decode(body.trim())?;
+
+    let plaintext = cipher(passphrase, &salt, iterations)?
+        .decrypt(
+            Nonce::from_slice(&nonce),
+            Payload {
+                msg: &ciphertext,
+                aad: header.as_bytes(),
+            },
+        )
+        .map_err(|_| {
+            invalid_data(
+                "No se pudo descifrar el archivo de usuarios: passphrase incorrecta o archivo dañado"
+                    .to_string(),
+            )
+        })?;
+    String::from_utf8(plaintext)
+        .map_err(|_| invalid_data("El archivo de usuarios descifrado no es texto".to_string()))
+}
+
+fn cipher(passphrase: &str, salt: &[u8], iterations: u32) -> io::Result<Aes256Gcm> {
+    let mut key = [0u8; KEY_LEN];
+    pbkdf2::pbkdf2_hmac::<Sha256>(passphrase.as_bytes(), salt, iterations, &mut key);
+    Aes256Gcm::new_from_slice(&key).map_err(|e| invalid_data(e.to_string()))
+}
+
+fn decode(field: &str) -> io::Result<Vec<u8>> {
+    STANDARD
+        .decode(field)
+        .map_err(|_| invalid_data("Archivo de usuarios cifrado con base64 inválido".to_string()))
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ITERATIONS: u32 = 1_000;
+
+    #[test]
+    fn test_roundtrip_and_fresh_nonce() {
+        let plain = "alice:secret:\nbob:hunter22:\n";
+        let first = encrypt(plain, "correct horse", ITERATIONS).unwrap();
+        let second = encrypt(plain, "correct horse", ITERATIONS).unwrap();
+        assert!(is_encrypted(&first));
+        assert!(!first.contains("alice"));
+        assert_ne!(first, second);
+        assert_eq!(decrypt(&first, "correct horse").unwrap(), plain);
+    }
+
+    #[test]
+    fn test_wrong_passphrase_or_tampering_fails() {
+        let sealed = encrypt("alice:secret:\n", "correct horse", ITERATIONS).unwrap();
+        let err = decrypt(&sealed, "battery staple").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("passphrase"));
+
+        // El encabezado va autenticado: bajar las iteraciones no pasa
+        let tampered = sealed.replacen(" 1000 ", " 999 ", 1);
+        assert!(decrypt(&tampered, "correct horse").is_err());
+
+        let future = sealed.replacen(" v1 ", " v2 ", 1);
+        assert!(
+            decrypt(&future, "correct horse")
+                .unwrap_err()
+                .to_string()
+                .contains("v2")
+        );
+    }
+
+    #[test]
+    fn test_legacy_file_is_not_encrypted() {
+        assert!(!is_encrypted("alice:secret:\n"));
+        assert!(!is_encrypted(""));
+    }
+}
@@ -1,32 +1,293 @@
-//! Validación de credenciales de usuario.
+//! Validación de credenciales de usuario y de los campos del protocolo.
 
-/// Valida que el username sea alfanumérico, no vacío y máximo 32 caracteres.
+use std::collections::HashMap;
+
+/// Código de error para usernames fuera de las reglas.
+pub const INVALID_USERNAME: &str = "invalid_username";
+/// Código de error para passwords fuera de las reglas.
+pub const INVALID_PASSWORD: &str = "invalid_password";
+/// Código de error para SDPs que superan `MAX_SDP_LEN`.
+pub const SDP_TOO_LARGE: &str = "sdp_too_large";
+/// Código de error para un payload en base64 que no decodifica a texto.
+pub const INVALID_PAYLOAD: &str = "invalid_payload";
+
+pub const USERNAME_MIN_LEN: usize = 3;
+pub const USERNAME_MAX_LEN: usize = 32;
+pub const PASSWORD_MIN_LEN: usize = 8;
+pub const PASSWORD_MAX_LEN: usize = 128;
+/// Tamaño máximo del SDP de CALL_OFFER / CALL_ANSWER.
+pub const MAX_SDP_LEN: usize = 32 * 1024;
+
+/// Valida que el username tenga entre 3 y 32 caracteres de `[A-Za-z0-9_-]`.
 pub fn validate_username(username: &str) -> Result<(), String> {
-    if username.is_empty() {
-        return Err("Username vacío".to_string());
+    let valid_len = (USERNAME_MIN_LEN..=USERNAME_MAX_LEN).contains(&username.len());
+    let valid_chars = username
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if !valid_len || !valid_chars {
+        return Err(INVALID_USERNAME.to_string());
     }
-    if username.len() > 32 {
-        return Err("Username demasiado largo (máx 32)".to_string());
+    Ok(())
+}
+
+/// Valida un password nuevo: entre 8 y 128 caracteres y sin delimitadores.
+pub fn validate_password(password: &str) -> Result<(), String> {
+    if password.chars().count() < PASSWORD_MIN_LEN {
+        return Err(INVALID_PASSWORD.to_string());
     }
-    if !username
+    validate_password_format(password)
+}
+
+/// Reglas de formato del password, sin el mínimo de longitud. Se usa en el
+/// LOGIN para no dejar afuera cuentas creadas antes de ese mínimo.
+pub fn validate_password_format(password: &str) -> Result<(), String> {
+    if password.is_empty()
+        || password.chars().count() > PASSWORD_MAX_LEN
+        || password
+            .chars()
+            .any(|c| c.is_control() || matches!(c, ':' | '|'))
+    {
+        return Err(INVALID_PASSWORD.to_string());
+    }
+    Ok(())
+}
+
+/// Valida el tamaño del SDP de una oferta o respuesta.
+pub fn validate_sdp(sdp: &str) -> Result<(), String> {
+    if sdp.len() > MAX_SDP_LEN {
+        return Err(SDP_TOO_LARGE.to_string());
+    }
+    Ok(())
+}
+
+/// Deja un valor listo para interpolarlo en un mensaje `TYPE|key:value`:
+/// reemplaza `|` y los caracteres de control para que no pueda agregar
+/// campos ni cortar la línea.
+pub fn protocol_value(value: &str) -> String {
+    value
         .chars()
-        .all(|c| c.is_ascii_alphanumeric() || c == '_')
+        .map(|c| if c == '|' || c.is_control() { '_' } else { c })
+        .collect()
+}
+
+/// Tamaño máximo de una línea del protocolo (incluye el SDP escapado).
+pub const MAX_LINE_LEN: usize = 64 * 1024;
+/// Tamaño máximo de los campos con payload escapado (candidate). El SDP se
+/// acota en los handlers con `validate_sdp` para poder responder
+/// `sdp_too_large`.
+pub const MAX_PAYLOAD_LEN: usize = 32 * 1024;
+/// Tamaño máximo del resto de los campos.
+pub const MAX_FIELD_LEN: usize = 256;
+/// Tamaño máximo del tipo de mensaje y de las claves.
+pub const MAX_KEY_LEN: usize = 32;
+
+/// Campos que viajan escapados con `protocol::escape_payload`.
+const PAYLOAD_FIELDS: [&str; 2] = ["sdp", "candidate"];
+/// Los mismos payloads en base64 (`sdp_b64`, `candidate_b64`).
+const B64_PAYLOAD_FIELDS: [&str; 2] = ["sdp_b64", "candidate_b64"];
+
+/// Valida un mensaje ya parseado: tipo, claves y valores acotados y sin
+/// caracteres de control ni delimitadores crudos. Los usuarios de `to` y
+/// `target` no pasan por `validate_username`: los handlers los buscan por
+/// nombre exacto, así también se llega a cuentas anteriores a esas reglas.
+pub fn validate_message(msg: &HashMap<String, String>) -> Result<(), String> {
+    let Some(msg_type) = msg.get("type") else {
+        return Err("Mensaje sin tipo".to_string());
+    };
+    if msg_type.is_empty()
+        || msg_type.len() > MAX_KEY_LEN
+        || !msg_type.chars().all(|c| c.is_ascii_uppercase() || c == '_')
     {
-        return Err("Username inválido: solo letras, números o _".to_string());
+        return Err("Tipo de mensaje inválido".to_string());
+    }
+
+    for (key, value) in msg {
+        if key == "type" {
+            continue;
+        }
+        validate_key(key)?;
+        if PAYLOAD_FIELDS.contains(&key.as_str()) {
+            validate_payload(key, value)?;
+        } else if B64_PAYLOAD_FIELDS.contains(&key.as_str()) {
+            validate_b64_payload(key, value)?;
+        } else {
+            validate_field(key, value)?;
+        }
     }
     Ok(())
 }
 
-/// Valida que el password no esté vacío, máximo 64 caracteres y sin caracteres prohibidos.
-pub fn validate_password(password: &str) -> Result<(), String> {
-    if password.is_empty() {
-        return Err("Password vacío".to_string());
+fn validate_key(key: &str) -> Result<(), String> {
+    if key.is_empty()
+        || key.len() > MAX_KEY_LEN
+        || !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    {
+        return Err("Clave de campo inválida".to_string());
+    }
+    Ok(())
+}
+
+/// Los payloads escapados no pueden tener caracteres de control ni `|`,
+/// y toda `\` debe ser una secuencia válida (`\\`, `\n`, `\r`).
+fn validate_payload(key: &str, value: &str) -> Result<(), String> {
+    if key != "sdp" && value.len() > MAX_PAYLOAD_LEN {
+        return Err(format!(
+            "Campo {} demasiado largo (máx {})",
+            key, MAX_PAYLOAD_LEN
+        ));
     }
-    if password.len() > 64 {
-        return Err("Password demasiado largo (máx 64)".to_string());
+    if value.chars().any(|c| c.is_control() || c == '|') {
+        return Err(format!("Campo {} con caracteres prohibidos", key));
     }
-    if password.chars().any(|c| matches!(c, ':' | '|' | '\n' | '\r')) {
-        return Err("Password inválido: no usar ':', '|' ni saltos de línea".to_string());
+    let mut chars = value.chars();
+    while let Some(ch) = chars.next() {
+        if ch == '\\' && !matches!(chars.next(), Some('\\' | 'n' | 'r')) {
+            return Err(format!("Campo {} mal escapado", key));
+        }
     }
     Ok(())
 }
+
+/// Los payloads en base64 solo usan su alfabeto; que decodifiquen lo mira
+/// el handler. El SDP se acota como el escapado, ya decodificado.
+fn validate_b64_payload(key: &str, value: &str) -> Result<(), String> {
+    if key != "sdp_b64" && value.len() > MAX_PAYLOAD_LEN {
+        return Err(format!(
+            "Campo {} demasiado largo (máx {})",
+            key, MAX_PAYLOAD_LEN
+        ));
+    }
+    if !value
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '/' | '='))
+    {
+        return Err(format!("Campo {} con caracteres prohibidos", key));
+    }
+    Ok(())
+}
+
+/// El resto de los campos no admite delimitadores del protocolo.
+fn validate_field(key: &str, value: &str) -> Result<(), String> {
+    if value.len() > MAX_FIELD_LEN {
+        return Err(format!(
+            "Campo {} demasiado largo (máx {})",
+            key, MAX_FIELD_LEN
+        ));
+    }
+    if value.chars().any(|c| c.is_control() || c == '|') {
+        return Err(format!("Campo {} con caracteres prohibidos", key));
+    }
+    if key != "password" && value.contains(':') {
+        return Err(format!("Campo {} con caracteres prohibidos", key));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::protocol::parse_message;
+
+    #[test]
+    fn username_length_bounds() {
+        assert_eq!(validate_username("ab"), Err(INVALID_USERNAME.to_string()));
+        assert!(validate_username("abc").is_ok());
+        assert!(validate_username(&"a".repeat(USERNAME_MAX_LEN)).is_ok());
+        assert!(validate_username(&"a".repeat(USERNAME_MAX_LEN + 1)).is_err());
+    }
+
+    #[test]
+    fn username_charset() {
+        assert!(validate_username("Alen_o-2").is_ok());
+        for bad in ["bob|x", "bob:x", "bob\nx", "bob x", "bób", "bob.x"] {
+            assert_eq!(
+                validate_username(bad),
+                Err(INVALID_USERNAME.to_string()),
+                "{bad:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn password_length_bounds() {
+        assert_eq!(
+            validate_password("1234567"),
+            Err(INVALID_PASSWORD.to_string())
+        );
+        assert!(validate_password("12345678").is_ok());
+        assert!(validate_password(&"p".repeat(PASSWORD_MAX_LEN)).is_ok());
+        assert!(validate_password(&"p".repeat(PASSWORD_MAX_LEN + 1)).is_err());
+    }
+
+    #[test]
+    fn password_rejects_separators() {
+        for bad in ["password|x", "password:x", "password\nx", "password\rx"] {
+            assert_eq!(
+                validate_password(bad),
+                Err(INVALID_PASSWORD.to_string()),
+                "{bad:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn login_password_format_skips_minimum() {
+        assert!(validate_password_format("abc").is_ok());
+        assert!(validate_password_format("").is_err());
+        assert!(validate_password_format("ab|c").is_err());
+    }
+
+    #[test]
+    fn sdp_size_limit() {
+        assert!(validate_sdp(&"v".repeat(MAX_SDP_LEN)).is_ok());
+        assert_eq!(
+            validate_sdp(&"v".repeat(MAX_SDP_LEN + 1)),
+            Err(SDP_TOO_LARGE.to_string())
+        );
+    }
+
+    #[test]
+    fn oversized_sdp_reaches_the_handler() {
+        let sdp = "v".repeat(MAX_SDP_LEN + 1);
+        let msg = parse_message(&format!("CALL_OFFER|to:bob|sdp:{}", sdp));
+        assert!(validate_message(&msg).is_ok());
+        let candidate = "c".repeat(MAX_PAYLOAD_LEN + 1);
+        let msg = parse_message(&format!("ICE_CANDIDATE|to:bob|candidate:{}", candidate));
+        assert!(validate_message(&msg).is_err());
+    }
+
+    #[test]
+    fn b64_payloads_only_use_their_alphabet() {
+        let msg = parse_message("CALL_OFFER|to:bob|sdp_b64:djA9MA0Kcz1hfGI6Yw==");
+        assert!(validate_message(&msg).is_ok());
+        let msg = parse_message("CALL_OFFER|to:bob|sdp_b64:djA9MA\\n");
+        assert!(validate_message(&msg).is_err());
+        let candidate = "Y".repeat(MAX_PAYLOAD_LEN + 1);
+        let msg = parse_message(&format!("ICE_CANDIDATE|to:bob|candidate_b64:{}", candidate));
+        assert!(validate_message(&msg).is_err());
+    }
+
+    #[test]
+    fn malicious_username_cannot_smuggle_fields() {
+        let malicious = "bob|sdp:evil|srtp_key:stolen";
+        assert!(validate_username(malicious).is_err());
+
+        // Aunque viniera de un archivo de usuarios viejo, al reenviarlo no
+        // agrega campos al mensaje
+        let forwarded = format!(
+            "USER_STATUS_CHANGED|username:{}|status:available",
+            protocol_value(malicious)
+        );
+        let msg = parse_message(&forwarded);
+        assert_eq!(msg.len(), 3);
+        assert!(!msg.contains_key("sdp"));
+        assert!(!msg.contains_key("srtp_key"));
+        assert_eq!(msg.get("status").map(String::as_str), Some("available"));
+    }
+
+    #[test]
+    fn protocol_value_strips_line_breaks() {
+        assert_eq!(protocol_value("a\nLOGIN_SUCCESS"), "a_LOGIN_SUCCESS");
+        assert_eq!(protocol_value("plain value"), "plain value");
+    }
+}
@@ -1,13 +1,12 @@
 //! Punto de entrada del servidor de señalización.
 
-mod config;
-mod logger;
-mod server;
-
-use config::AppConfig;
-use logger::Logger;
-use server::state::ServerState;
-use server::tls::build_tls_config;
+use room_rtc::stun::StunServer;
+use roomrtc::config::AppConfig;
+use roomrtc::logger::Logger;
+use roomrtc::server;
+use roomrtc::server::metrics;
+use roomrtc::server::state::ServerState;
+use roomrtc::server::tls::build_tls_config;
 
 use std::net::TcpListener;
 use std::sync::Arc;
@@ -28,62 +27,66 @@ fn main() -> std::io::Result<()> {
             AppConfig::default()
         }
     };
-    let logger = Logger::start(&config.log_file)?;
+    let logger = Logger::start(&config.log_file, config.log_options())?;
+    logger.capture_library_logs();
 
     let listener = TcpListener::bind(&config.server_addr)?;
     let state = Arc::new(ServerState::new(&config, logger.clone()));
-    let tls_config = build_tls_config();
+    let (tls_config, fingerprint) = build_tls_config(&config).map_err(|e| {
+        eprintln!("No se pudo configurar TLS: {}", e);
+        e
+    })?;
+
+    // Sin los usuarios no se arranca: seguir con la lista vacía borraría
+    // todas las cuentas al registrar la próxima
+    state.load_users().map_err(|e| {
+        eprintln!("No se pudieron cargar los usuarios: {}", e);
+        e
+    })?;
+
+    if let Some(stun_addr) = &config.stun_addr {
+        let stun = StunServer::bind(stun_addr)?;
+        println!("STUN server listening in udp://{}", stun.local_addr()?);
+        logger.info(&format!("Servidor STUN iniciado en {}", stun_addr));
+        stun.spawn();
+    }
 
-    state.load_users()?;
+    if let Some(metrics_addr) = &config.metrics_addr {
+        let metrics_listener = TcpListener::bind(metrics_addr)?;
+        println!(
+            "Metrics listening in http://{}/metrics",
+            metrics_listener.local_addr()?
+        );
+        logger.info(&format!("Métricas en {}", metrics_addr));
+        let state = Arc::clone(&state);
+        thread::spawn(move || metrics::serve(metrics_listener, state));
+    }
 
     println!("Signaling server listening in {}", config.server_addr);
-    println!("Users file: {}", config.users_file);
+    println!(
+        "Users file: {}{}",
+        config.users_file,
+        if config.users_passphrase().is_some() {
+            " (encrypted)"
+        } else {
+            ""
+        }
+    );
     println!("Max clients: {}", config.max_clients);
-    println!("Encryption: TLS (self-signed)\n");
+    println!("Admins: {}", config.admin_users.len());
+    if config.tls_cert_file.is_some() {
+        println!("Encryption: TLS");
+    } else {
+        println!("Encryption: TLS (self-signed, dev mode only)");
+        logger.warn("TLS con certificado self-signed (tls_dev_mode)");
+    }
+    println!("Certificate SHA-256: {}\n", fingerprint);
     logger.info(&format!(
         "Servidor iniciado en {} con archivo de usuarios {}",
         config.server_addr, config.users_file
     ));
 
-    for stream in listener.incoming() {
-        match stream {
-            Ok(stream) => {
-                let addr = match stream.peer_addr() {
-                    Ok(a) => a,
-                    Err(e) => {
-                        logger.error(&format!("No se pudo obtener addr del cliente: {}", e));
-                        continue;
-                    }
-                };
-
-                // Limitar conexiones concurrentes
-                let over_capacity = match state.connected_clients.read() {
-                    Ok(clients) => clients.len() >= config.max_clients,
-                    Err(_) => {
-                        logger.error("Lock de clientes envenenado");
-                        true
-                    }
-                };
-                if over_capacity {
-                    println!(
-                        "Max clients capacity reached, refuse connection from {}",
-                        addr
-                    );
-                    logger.warn("Capacidad máxima alcanzada, rechazando conexión");
-                    continue;
-                }
-
-                let state = Arc::clone(&state);
-                let tls_config = Arc::clone(&tls_config);
-                thread::spawn(move || {
-                    server::handle_client(stream, addr, state, tls_config);
-                });
-            }
-            Err(e) => {
-                logger.error(&format!("Error aceptando conexión: {}", e));
-            }
-        }
-    }
+    server::run(listener, state, tls_config);
 
     Ok(())
 }
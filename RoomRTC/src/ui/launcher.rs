@@ -1,3 +1,5 @@
+use crate::client::notifier::DesktopNotifier;
+use crate::client::settings::Theme;
 use crate::config::AppConfig;
 use crate::ui::screen_manager::MainApp;
 
@@ -14,8 +16,11 @@ pub fn run(config: AppConfig) -> eframe::Result<()> {
         "RoomRTC - P2P Video Meets",
         opt,
         Box::new(|cc| {
-            crate::ui::theme::configure_visuals(&cc.egui_ctx);
-            Ok(Box::new(MainApp::new(config)))
+            crate::ui::theme::configure_visuals(&cc.egui_ctx, Theme::default());
+            Ok(Box::new(MainApp::new(
+                config,
+                Box::new(DesktopNotifier::new()),
+            )))
         }),
     )
 }
@@ -1,25 +1,39 @@
+use crate::client::identity::{identity_path, load_or_create};
+use crate::client::notifier::{Notification, Notifier, should_notify};
+use crate::client::settings::{
+    PreferredStatus, Theme, UserSettings, remembered_session, settings_path,
+};
 use crate::client::signaling_client::{SignalingClient, SignalingEvent};
+use crate::client::tls::TlsClientSettings;
+use crate::client::webrtc_service::PeerConfig;
 use crate::config::AppConfig;
 use crate::logger::Logger;
+use crate::ui::screens::device_test::{DeviceTestAction, DeviceTestScreen};
 use crate::ui::screens::join_meet::JoinMeetAction;
 use crate::ui::screens::join_meet::JoinMeetScreen;
 use crate::ui::screens::lobby::LobbyAction;
 use crate::ui::screens::lobby::LobbyScreen;
 use crate::ui::screens::login::{LoginAction, LoginScreen};
+use crate::ui::screens::settings::{SettingsAction, SettingsScreen};
 use crate::ui::screens::video::VideoCall;
 use crate::ui::screens::video::VideoMeetAction;
 use crate::ui::screens::waiting_call::WaitingCall;
 use crate::ui::screens::waiting_call::WaitingCallAction;
-use std::time::Duration;
+use crate::ui::theme;
 use eframe::egui;
 use room_rtc::rtc::rtc_peer_connection::PeerConnectionRole;
-use room_rtc::worker_thread::worker_media::VideoParams;
+use room_rtc::stun::StunClient;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
 pub enum Screen {
     Login,
     Lobby,
     JoinMeet,
     WaitingCall,
     VideoCall,
+    Settings,
+    DeviceTest,
 }
 
 pub struct MainApp {
@@ -29,36 +43,376 @@ pub struct MainApp {
     waiting_call: WaitingCall,
     video_meet: VideoCall,
     login: LoginScreen,
+    settings_screen: SettingsScreen,
+    device_test: DeviceTestScreen,
+    // A dónde vuelve la prueba de dispositivos
+    device_test_from_settings: bool,
     signaling: Option<SignalingClient>,
     username: Option<String>,
     active_peer: Option<String>,
+    // Archivos soltados sobre un usuario del lobby, para la llamada con él
+    files_for_call: Option<(String, Vec<PathBuf>)>,
     logger: Logger,
+    config: AppConfig,
+    // Preferencias del usuario logueado; antes del login, las de AppConfig
+    settings: UserSettings,
+    notifier: Box<dyn Notifier>,
+    // Con la ventana enfocada no se notifica nada
+    window_focused: bool,
+    // El tema puesto en egui; cambia con el de `settings`
+    applied_theme: Theme,
 }
 
 impl MainApp {
-    pub fn new(config: AppConfig) -> Self {
-        let logger = Logger::start(&config.log_file).unwrap_or_else(|err| {
+    pub fn new(config: AppConfig, notifier: Box<dyn Notifier>) -> Self {
+        let log_options = config.log_options();
+        let logger = Logger::start(&config.log_file, log_options).unwrap_or_else(|err| {
             eprintln!(
                 "No se pudo abrir log {} ({}), usando /tmp/roomrtc-client.log",
                 config.log_file, err
             );
-            Logger::start("/tmp/roomrtc-client.log").unwrap_or_else(|_| Logger::noop())
+            Logger::start("/tmp/roomrtc-client.log", log_options).unwrap_or_else(|_| Logger::noop())
         });
-        Self {
+        logger.capture_library_logs();
+        let video = config.video_params();
+        let settings = UserSettings::from_config(&config);
+        let remembered = remembered_session(&config.settings_dir, &settings);
+        let connect_timeout = Duration::from_secs(config.connect_timeout_secs);
+        let mut app = Self {
             current_screen: Screen::Login,
             lobby: LobbyScreen::new(),
-            join_meet: JoinMeetScreen::new(PeerConnectionRole::Controlled),
-            waiting_call: WaitingCall::new(PeerConnectionRole::Controlling),
-            video_meet: VideoCall::new(VideoParams {
-                width: config.video_width,
-                height: config.video_height,
-                fps: config.video_fps,
-            }),
-            login: LoginScreen::new(config.server_addr.clone(), Some(logger.clone())),
+            join_meet: JoinMeetScreen::new(
+                PeerConnectionRole::Controlled,
+                PeerConfig::from_config(&config, &video),
+                connect_timeout,
+            )
+            .with_ring_timeout(Duration::from_secs(config.ring_timeout_secs))
+            .with_max_pending_calls(config.max_pending_calls),
+            waiting_call: WaitingCall::new(
+                PeerConnectionRole::Controlling,
+                PeerConfig::from_config(&config, &video),
+                connect_timeout,
+            ),
+            video_meet: VideoCall::new(video, config.max_file_size_mb.saturating_mul(1_048_576))
+                .with_max_concurrent_transfers(config.max_concurrent_transfers)
+                .with_file_offer_timeout(Duration::from_secs(config.file_offer_timeout_secs))
+                .with_audio_params(config.audio_params())
+                .with_ring_timeout(Duration::from_secs(config.ring_timeout_secs)),
+            login: LoginScreen::new(
+                config.server_addr.clone(),
+                TlsClientSettings::from_config(&config),
+                Some(logger.clone()),
+            ),
+            settings_screen: SettingsScreen::new(settings.clone()),
+            device_test: DeviceTestScreen::new(
+                settings.clone(),
+                config
+                    .stun_server
+                    .clone()
+                    .unwrap_or_else(|| StunClient::new().default_server),
+                logger.clone(),
+            ),
+            device_test_from_settings: false,
             signaling: None,
             username: None,
             active_peer: None,
+            files_for_call: None,
             logger,
+            config,
+            settings,
+            notifier,
+            window_focused: true,
+            applied_theme: Theme::default(),
+        };
+        // Quien pidió que lo recuerden entra solo; si el token ya no sirve
+        // queda la pantalla de login
+        if let Some((username, saved)) = remembered
+            && let Some(token) = &saved.session_token
+        {
+            app.logger
+                .info(&format!("Retomando la sesión guardada de {}", username));
+            app.login
+                .resume_session(&username, &saved.server_addr, token);
+        }
+        app
+    }
+
+    /// Muestra `notification` salvo con la ventana enfocada, en no molestar
+    /// o con las notificaciones apagadas.
+    fn notify(&self, notification: Notification) {
+        if should_notify(&self.settings, self.window_focused) {
+            self.notifier.notify(&notification);
+        }
+    }
+
+    /// Carga las preferencias de `username`. Quien nunca guardó nada arranca
+    /// con las de `AppConfig` y el servidor en el que acaba de entrar.
+    fn load_user_settings(&mut self, username: &str) {
+        let mut defaults = UserSettings::from_config(&self.config);
+        defaults.server_addr = self.login.server_addr.clone();
+        let path = settings_path(&self.config.settings_dir, username);
+        let settings = UserSettings::load(&path, &defaults).unwrap_or_else(|err| {
+            self.logger.warn(&format!(
+                "No se pudieron leer las preferencias de {} ({}), usando valores por defecto",
+                path.display(),
+                err
+            ));
+            defaults
+        });
+        self.apply_settings(settings);
+    }
+
+    /// Certificado DTLS de `username` para sus llamadas, el mismo siempre
+    /// para que sus contactos lo puedan fijar. Si no se puede leer ni crear,
+    /// cada llamada genera el suyo.
+    fn load_identity(&mut self, username: &str) {
+        let path = identity_path(&self.config.settings_dir, username);
+        let identity = match load_or_create(&path) {
+            Ok(identity) => Some(Arc::new(identity)),
+            Err(err) => {
+                self.logger.warn(&format!(
+                    "Sin identidad DTLS fija ({}), cada llamada usa un certificado nuevo",
+                    err
+                ));
+                None
+            }
+        };
+        self.join_meet.set_dtls_identity(identity.clone());
+        self.waiting_call.set_dtls_identity(identity);
+    }
+
+    /// Fija el certificado que `peer` presentó en la llamada, que el usuario
+    /// verificó comparando el número de seguridad.
+    fn pin_key(&mut self, peer: String, fingerprint: String) {
+        let Some(username) = self.username.clone() else {
+            return;
+        };
+        self.settings
+            .pinned_keys
+            .insert(peer.clone(), fingerprint.clone());
+        let path = settings_path(&self.config.settings_dir, &username);
+        match self.settings.save(&path) {
+            Ok(()) => self
+                .logger
+                .info(&format!("Certificado de {} fijado ({})", peer, fingerprint)),
+            Err(err) => self.logger.warn(&format!(
+                "No se pudo guardar el certificado de {} en {}: {}",
+                peer,
+                path.display(),
+                err
+            )),
+        }
+    }
+
+    /// Guarda el token de la sesión de `username` para entrar solo la
+    /// próxima vez, o lo borra con `None`. Las preferencias de `username`
+    /// tienen que estar cargadas.
+    fn remember_session(&mut self, username: &str, token: Option<String>) {
+        if token.is_none() && self.settings.session_token.is_none() {
+            return;
+        }
+        if token.is_some() {
+            // El token vale solo en el servidor que lo dio
+            self.settings.server_addr = self.login.server_addr.clone();
+        }
+        self.settings.session_token = token;
+        let path = settings_path(&self.config.settings_dir, username);
+        if let Err(err) = self.settings.save(&path) {
+            self.logger.warn(&format!(
+                "No se pudo guardar la sesión en {}: {}",
+                path.display(),
+                err
+            ));
+        }
+    }
+
+    /// Las llamadas que ya están en curso no cambian: todo esto se lee al
+    /// armar la próxima.
+    fn apply_settings(&mut self, settings: UserSettings) {
+        // Lo que pedimos recibir tampoco pasa del tope total
+        let bandwidth = settings
+            .video
+            .with_bandwidth_cap(settings.max_bitrate_kbps)
+            .bandwidth_kbps();
+        self.video_meet.apply_settings(&settings);
+        self.join_meet.set_bandwidth_kbps(bandwidth);
+        self.waiting_call.set_bandwidth_kbps(bandwidth);
+        self.waiting_call
+            .set_output_device(settings.output_device.clone());
+        self.lobby
+            .set_do_not_disturb(settings.preferred_status == PreferredStatus::DoNotDisturb);
+        self.settings = settings;
+    }
+
+    /// Guarda y aplica `settings`. Si no se pudo guardar no cambia nada y
+    /// devuelve el mensaje para mostrar.
+    fn save_settings(&mut self, mut settings: UserSettings) -> Result<(), String> {
+        let Some(username) = self.username.clone() else {
+            self.current_screen = Screen::Login;
+            return Ok(());
+        };
+        let server_changed = settings.server_addr != self.settings.server_addr;
+        if server_changed {
+            // El token es del servidor anterior
+            settings.session_token = None;
+        }
+        let path = settings_path(&self.config.settings_dir, &username);
+        if let Err(err) = settings.save(&path) {
+            return Err(format!("Could not save settings: {}", err));
+        }
+        self.logger
+            .info(&format!("Preferencias guardadas en {}", path.display()));
+
+        self.apply_settings(settings);
+        if server_changed {
+            // La sesión es del servidor anterior: hay que volver a entrar
+            if let Some(signaling) = self.signaling.take() {
+                let _ = signaling.logout();
+            }
+            self.login.server_addr = self.settings.server_addr.clone();
+            self.login.status_message = Some("Server changed, please log in again".into());
+            self.current_screen = Screen::Login;
+        } else {
+            self.current_screen = Screen::Lobby;
+        }
+        Ok(())
+    }
+
+    /// Otra llamada entró y no hay lugar para ella: se rechaza como ocupado
+    /// sin tocar la que está en curso.
+    fn reject_busy_call(&self, from: &str) {
+        if let Some(signaling) = self.signaling.as_ref() {
+            let _ = signaling.reject_call_busy(from);
+        }
+        self.logger.info(&format!(
+            "Llamada de {} rechazada, ya hay otra en curso",
+            from
+        ));
+    }
+
+    /// Vuelve al lobby mostrando por qué no se pudo establecer la conexión.
+    fn connection_failed(&mut self, reason: String) {
+        self.logger
+            .error(&format!("Conexión con el peer fallida: {}", reason));
+        self.lobby.set_error(reason);
+        self.active_peer = None;
+        self.current_screen = Screen::Lobby;
+    }
+
+    /// La llamada a `to` no se pudo hacer: la pantalla de espera muestra el
+    /// motivo y después vuelve sola al lobby.
+    fn call_failed(&mut self, to: &str, reason: String) {
+        self.logger
+            .warn(&format!("Llamada a {} fallida: {}", to, reason));
+        self.waiting_call.on_call_failed(to, reason);
+    }
+
+    fn call_user(&mut self, username: &str) {
+        self.current_screen = Screen::WaitingCall;
+        match self.waiting_call.pending_call() {
+            // Ya lo estamos llamando: solo se vuelve a la pantalla de espera
+            Some(pending) if pending == username => return,
+            // Otra llamada sin atender: se cancela antes de la nueva
+            Some(pending) => {
+                if let Some(signaling) = self.signaling.as_ref() {
+                    let _ = signaling.end_call(&pending);
+                }
+                self.waiting_call.cancel();
+            }
+            None => {}
+        }
+        if let Some(signaling) = self.signaling.as_ref()
+            && let Err(e) = self.waiting_call.call_user(username, signaling)
+        {
+            self.logger.error(&format!("Failed to call: {}", e));
+            self.waiting_call.status_message = Some(format!("Failed to place call: {}", e));
+        }
+    }
+
+    /// Deja la pantalla de la llamada. Si había otra llamada en espera,
+    /// pasa a sonar como cualquier llamada entrante; si no, se vuelve al
+    /// lobby.
+    fn close_video_call(&mut self) {
+        let waiting = self.video_meet.take_call_waiting();
+        self.video_meet.reset();
+        match waiting {
+            Some(call) => {
+                self.active_peer = Some(call.from.clone());
+                self.join_meet.clear_calls();
+                self.join_meet
+                    .on_incoming_call(call.from, call.sdp, call.caps);
+                self.current_screen = Screen::JoinMeet;
+            }
+            None => {
+                self.active_peer = None;
+                self.current_screen = Screen::Lobby;
+            }
+        }
+    }
+
+    /// Le avisa a `from` que su llamada ya se está mostrando.
+    fn announce_ringing(&self, from: &str) {
+        if let Some(signaling) = self.signaling.as_ref()
+            && let Err(err) = signaling.call_ringing(from)
+        {
+            self.logger
+                .warn(&format!("No se pudo avisar a {} que suena: {}", from, err));
+        }
+    }
+
+    /// Rechaza la llamada en espera, si hay una.
+    fn reject_call_waiting(&mut self) {
+        if let Some(call) = self.video_meet.take_call_waiting()
+            && let Some(signaling) = self.signaling.as_ref()
+        {
+            let _ = signaling.reject_call(&call.from);
+        }
+    }
+
+    /// Atiende la llamada en espera: corta la actual y contesta la nueva. Si
+    /// no se puede contestar, queda sonando en la pantalla de llamada
+    /// entrante para volver a intentarlo.
+    fn accept_call_waiting(&mut self) {
+        let Some(call) = self.video_meet.take_call_waiting() else {
+            return;
+        };
+        if let (Some(signaling), Some(peer)) = (self.signaling.as_ref(), self.video_meet.peer()) {
+            let _ = signaling.end_call(&peer);
+        }
+        self.video_meet.reset();
+        self.logger.info(&format!(
+            "Llamada en espera de {} atendida, se corta la actual",
+            call.from
+        ));
+        self.active_peer = Some(call.from.clone());
+        self.join_meet.clear_calls();
+        self.join_meet
+            .on_incoming_call(call.from.clone(), call.sdp, call.caps);
+        self.current_screen = Screen::JoinMeet;
+        let Some(signaling) = self.signaling.as_ref() else {
+            return;
+        };
+        match self.join_meet.accept_call(signaling, &call.from) {
+            Ok(()) => self.enter_video_from_join_meet(),
+            Err(err) => self.logger.warn(&err),
+        }
+    }
+
+    fn enter_video_from_join_meet(&mut self) {
+        if let Some((client, inbox)) = self.join_meet.take_client_with_inbox() {
+            self.video_meet
+                .set_client(client, inbox, self.join_meet.active_peer());
+        }
+        self.current_screen = Screen::VideoCall;
+    }
+
+    /// Los archivos soltados sobre el usuario que atendió salen apenas se
+    /// abra el canal de datos.
+    fn hand_over_files_for_call(&mut self) {
+        if let Some((peer, files)) = self.files_for_call.take()
+            && self.waiting_call.active_peer().as_deref() == Some(peer.as_str())
+        {
+            self.video_meet.send_files_on_connect(files);
         }
     }
 
@@ -73,33 +427,142 @@ impl MainApp {
                 SignalingEvent::UserStatusChanged { username, status } => {
                     self.lobby.update_user_status(username, status)
                 }
-                SignalingEvent::IncomingCall { from, sdp } => {
+                SignalingEvent::UserJoined { username, status } => {
+                    self.lobby.user_joined(username, status)
+                }
+                SignalingEvent::UserLeft { username } => self.lobby.user_left(username),
+                SignalingEvent::IncomingCall { from, sdp, caps } => {
+                    if self.settings.preferred_status == PreferredStatus::DoNotDisturb {
+                        if let Some(signaling) = self.signaling.as_ref() {
+                            let _ = signaling.reject_call(&from);
+                        }
+                        self.logger
+                            .info(&format!("Llamada de {} rechazada (no molestar)", from));
+                        continue;
+                    }
+                    match self.current_screen {
+                        // En plena llamada solo se avisa; se corta si el
+                        // usuario atiende la nueva
+                        Screen::VideoCall if self.video_meet.peer().is_some() => {
+                            self.notify(Notification::IncomingCall { from: from.clone() });
+                            self.logger.info(&format!("Llamada de {} en espera", from));
+                            self.announce_ringing(&from);
+                            self.video_meet.on_incoming_call(from, sdp, caps);
+                            continue;
+                        }
+                        // Ya hay una llamada saliendo
+                        Screen::WaitingCall if self.waiting_call.pending_call().is_some() => {
+                            self.reject_busy_call(&from);
+                            continue;
+                        }
+                        // Se suma a las que ya suenan, si hay lugar
+                        Screen::JoinMeet => {}
+                        // Lo que quedó de una visita anterior ya no cuenta
+                        _ => self.join_meet.clear_calls(),
+                    }
+                    if !self.join_meet.on_incoming_call(from.clone(), sdp, caps) {
+                        self.reject_busy_call(&from);
+                        continue;
+                    }
+                    // La llamada necesita la cámara y el micrófono que la
+                    // prueba de dispositivos pueda tener abiertos
+                    self.device_test.close();
                     self.active_peer = Some(from.clone());
-                    self.join_meet.on_incoming_call(from, sdp);
+                    self.notify(Notification::IncomingCall { from: from.clone() });
+                    self.announce_ringing(&from);
                     self.current_screen = Screen::JoinMeet;
                     self.logger.info("Llamada entrante recibida");
                 }
-                SignalingEvent::CallAccepted { from, sdp } => {
+                SignalingEvent::CallAccepted { from, sdp, caps } => {
                     self.active_peer = Some(from.clone());
-                    self.waiting_call.on_call_accepted(from, sdp);
+                    self.waiting_call.on_call_accepted(from, sdp, caps);
                     if let Some((client, inbox)) = self.waiting_call.take_client_with_inbox() {
-                        self.video_meet.set_client(client, inbox, self.waiting_call.active_peer());
+                        self.video_meet
+                            .set_client(client, inbox, self.waiting_call.active_peer());
+                        self.hand_over_files_for_call();
                         self.current_screen = Screen::VideoCall;
                     }
                     self.logger.info("Oferta aceptada por el peer remoto");
                 }
+                SignalingEvent::CallRinging { from } => self.waiting_call.on_call_ringing(&from),
                 SignalingEvent::CallRejected { from } => self.waiting_call.on_call_rejected(from),
+                SignalingEvent::CalleeNotFound { to } => {
+                    self.call_failed(&to, format!("{} doesn't exist", to))
+                }
+                SignalingEvent::CalleeOffline { to } => {
+                    self.call_failed(&to, format!("{} is offline", to))
+                }
+                SignalingEvent::CalleeBusy { to } => {
+                    self.call_failed(&to, format!("{} is busy in another call", to))
+                }
+                SignalingEvent::CallNotPermitted { to } => {
+                    self.call_failed(&to, format!("{} is not accepting your calls", to))
+                }
+                SignalingEvent::Blocklist(users) => {
+                    self.lobby.set_blocklist(users.clone());
+                    self.settings_screen.set_blocklist(users);
+                }
+                SignalingEvent::BlockError(err) => {
+                    self.lobby.set_error(err.clone());
+                    self.settings_screen.status_message = Some(err);
+                }
+                SignalingEvent::CallTransferRequested { from, to } => {
+                    // El servidor ya liberó la llamada con `from`: cortamos la
+                    // media y llamamos al nuevo usuario
+                    self.reject_call_waiting();
+                    self.video_meet.reset();
+                    self.waiting_call.client = None;
+                    self.active_peer = None;
+                    self.current_screen = Screen::WaitingCall;
+                    if let Some(signaling) = self.signaling.as_ref()
+                        && let Err(e) = self.waiting_call.call_user(&to, signaling)
+                    {
+                        self.waiting_call.status_message =
+                            Some(format!("Failed to place call: {}", e));
+                    }
+                    self.logger
+                        .info(&format!("{} transfirió la llamada hacia {}", from, to));
+                }
+                SignalingEvent::CallTransferred { to } => {
+                    self.close_video_call();
+                    self.logger.info(&format!("Llamada transferida a {}", to));
+                }
+                SignalingEvent::TransferFailed { target, error } => {
+                    // Si el target no atendió, ya habíamos dejado la llamada
+                    if matches!(self.current_screen, Screen::VideoCall) {
+                        self.video_meet.on_transfer_failed(&target, &error);
+                    } else {
+                        self.lobby
+                            .set_error(format!("Could not transfer to {}: {}", target, error));
+                    }
+                    self.logger
+                        .warn(&format!("Transferencia a {} fallida: {}", target, error));
+                }
                 SignalingEvent::CallEnded { from } => {
+                    if self.video_meet.dismiss_call_waiting(&from) {
+                        self.logger
+                            .info(&format!("{} cortó antes de que lo atendieran", from));
+                        self.notify(Notification::MissedCall { from });
+                        continue;
+                    }
+                    // Cortó uno de los que sonaban: los demás siguen
+                    if self.join_meet.dismiss_pending_call(&from) {
+                        self.logger
+                            .info(&format!("{} cortó antes de que lo atendieran", from));
+                        self.notify(Notification::MissedCall { from });
+                        if !self.join_meet.is_ringing() {
+                            self.active_peer = None;
+                            self.current_screen = Screen::Lobby;
+                        }
+                        continue;
+                    }
                     self.waiting_call.on_call_ended(&from);
                     self.join_meet.on_call_ended(&from);
                     self.video_meet.handle_call_ended(from.clone());
-                    self.video_meet.reset();
-                    self.active_peer = None;
-                    self.current_screen = Screen::Lobby;
+                    self.close_video_call();
                     self.logger.info("Llamada finalizada");
                 }
                 SignalingEvent::Error(err) => {
-                    eprintln!("Signaling error: {}", err);
                     self.logger
                         .error(&format!("Error de señalización: {}", err));
                 }
@@ -117,6 +580,7 @@ impl MainApp {
                 }
                 SignalingEvent::Disconnected | SignalingEvent::LoggedOut => {
                     self.login.status_message = Some("Conexión con el servidor cerrada".into());
+                    self.device_test.close();
                     self.signaling = None;
                     self.current_screen = Screen::Login;
                     self.logger
@@ -124,7 +588,24 @@ impl MainApp {
                     break;
                 }
                 SignalingEvent::IceCandidate { from, candidate } => {
-                    eprintln!("ICE desde {}: {}", from, candidate);
+                    self.logger
+                        .debug(&format!("ICE desde {}: {}", from, candidate));
+                }
+                SignalingEvent::IceRestart { from, sdp } => {
+                    if let Some(signaling) = self.signaling.as_ref()
+                        && let Err(e) =
+                            self.video_meet
+                                .on_ice_restart(signaling, &from, sdp.as_deref())
+                    {
+                        self.logger
+                            .warn(&format!("Reinicio de ICE con {} fallido: {}", from, e));
+                    }
+                }
+                SignalingEvent::IceRestartAnswer { from, sdp } => {
+                    if let Err(e) = self.video_meet.on_ice_restart_answer(&from, &sdp) {
+                        self.logger
+                            .warn(&format!("Reinicio de ICE con {} fallido: {}", from, e));
+                    }
                 }
                 SignalingEvent::LoginSuccess(_) => {}
             }
@@ -136,38 +617,72 @@ impl eframe::App for MainApp {
     fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
         // Repaint frecuente para procesar eventos de señalización aunque no haya input
         ctx.request_repaint_after(Duration::from_millis(30));
+        self.window_focused = ctx.input(|i| i.viewport().focused).unwrap_or(true);
+        if self.applied_theme != self.settings.theme {
+            theme::configure_visuals(ctx, self.settings.theme);
+            self.applied_theme = self.settings.theme;
+        }
+        if self.notifier.take_focus_request() {
+            ctx.send_viewport_cmd(egui::ViewportCommand::Minimized(false));
+            ctx.send_viewport_cmd(egui::ViewportCommand::Focus);
+        }
         self.handle_signaling_events();
         match self.current_screen {
-            Screen::Login => {
-                if let Some(LoginAction::LoggedIn {
+            Screen::Login => match self.login.update(ctx) {
+                Some(LoginAction::LoggedIn {
                     username,
                     signaling,
-                }) = self.login.update(ctx)
-                {
+                    token,
+                }) => {
+                    self.load_user_settings(&username);
+                    self.load_identity(&username);
+                    self.remember_session(&username, token);
                     self.username = Some(username);
                     self.signaling = Some(signaling);
                     if let Some(sig) = self.signaling.as_ref() {
                         let _ = sig.request_users();
+                        let _ = sig.request_blocklist();
                     }
                     self.current_screen = Screen::Lobby;
                 }
-            }
+                Some(LoginAction::SessionRejected { username }) => {
+                    self.load_user_settings(&username);
+                    self.remember_session(&username, None);
+                }
+                None => {}
+            },
             Screen::Lobby => {
+                self.lobby
+                    .set_pending_call(self.waiting_call.pending_call());
                 let signaling = self.signaling.as_ref();
                 let username = self.username.as_deref();
                 if let Some(action) = self.lobby.update(ctx, signaling, username) {
                     match action {
                         LobbyAction::GoToWaitingCall(username) => {
-                            self.current_screen = Screen::WaitingCall;
-                            if let Some(signaling) = self.signaling.as_ref()
-                                && let Err(e) = self.waiting_call.call_user(&username, signaling)
-                            {
-                                self.logger.error(&format!("Failed to call: {}", e));
-                                self.waiting_call.status_message =
-                                    Some(format!("Failed to place call: {}", e));
+                            self.files_for_call = None;
+                            self.call_user(&username);
+                        }
+                        LobbyAction::CallWithFiles(username, files) => {
+                            self.files_for_call = Some((username.clone(), files));
+                            self.call_user(&username);
+                        }
+                        LobbyAction::OpenSettings => {
+                            if let Some(sig) = self.signaling.as_ref() {
+                                let _ = sig.request_blocklist();
                             }
+                            self.settings_screen.open(self.settings.clone());
+                            self.current_screen = Screen::Settings;
+                        }
+                        LobbyAction::TestDevices => {
+                            self.device_test.open(&self.settings);
+                            self.device_test_from_settings = false;
+                            self.current_screen = Screen::DeviceTest;
                         }
                         LobbyAction::Logout => {
+                            // El servidor ya invalidó el token con el LOGOUT
+                            if let Some(username) = self.username.clone() {
+                                self.remember_session(&username, None);
+                            }
                             self.signaling = None;
                             self.current_screen = Screen::Login;
                             self.logger.info("Usuario cerró sesión desde lobby");
@@ -175,11 +690,49 @@ impl eframe::App for MainApp {
                     }
                 }
             }
+            Screen::Settings => match self.settings_screen.update(ctx) {
+                Some(SettingsAction::Save(settings)) => {
+                    if let Err(err) = self.save_settings(settings) {
+                        self.settings_screen.status_message = Some(err);
+                    }
+                }
+                Some(SettingsAction::Cancel) => self.current_screen = Screen::Lobby,
+                Some(SettingsAction::TestDevices(draft)) => {
+                    self.device_test.open(&draft);
+                    self.device_test_from_settings = true;
+                    self.current_screen = Screen::DeviceTest;
+                }
+                Some(SettingsAction::Unblock(username)) => {
+                    if let Some(sig) = self.signaling.as_ref() {
+                        let _ = sig.unblock(&username);
+                    }
+                }
+                None => {}
+            },
+            Screen::DeviceTest => {
+                if let Some(DeviceTestAction::Back(devices)) = self.device_test.update(ctx) {
+                    if self.device_test_from_settings {
+                        // Quedan en el borrador, se guardan con Save
+                        self.settings_screen.set_devices(&devices);
+                        self.current_screen = Screen::Settings;
+                    } else {
+                        self.current_screen = Screen::Lobby;
+                        let mut settings = self.settings.clone();
+                        if settings.copy_devices_from(&devices)
+                            && let Err(err) = self.save_settings(settings)
+                        {
+                            self.lobby.set_error(err);
+                        }
+                    }
+                }
+            }
             Screen::JoinMeet => {
                 let signaling = self.signaling.as_ref();
                 if let Some(action) = self.join_meet.update(ctx, frame, signaling) {
                     match action {
                         JoinMeetAction::GoToLobby => {
+                            self.join_meet
+                                .decline_pending_calls(self.signaling.as_ref());
                             if let (Some(signaling), Some(peer)) =
                                 (self.signaling.as_ref(), self.join_meet.active_peer())
                             {
@@ -187,15 +740,25 @@ impl eframe::App for MainApp {
                             }
                             self.current_screen = Screen::Lobby
                         }
-                        JoinMeetAction::GoToVideo => {
-                            if let Some((client, inbox)) = self.join_meet.take_client_with_inbox() {
-                                self.video_meet.set_client(
-                                    client,
-                                    inbox,
-                                    self.join_meet.active_peer(),
-                                );
+                        JoinMeetAction::GoToVideo => self.enter_video_from_join_meet(),
+                        JoinMeetAction::ConnectionFailed(reason) => {
+                            if let (Some(signaling), Some(peer)) =
+                                (self.signaling.as_ref(), self.join_meet.active_peer())
+                            {
+                                let _ = signaling.end_call(&peer);
                             }
-                            self.current_screen = Screen::VideoCall;
+                            self.connection_failed(reason);
+                        }
+                        JoinMeetAction::MissedCall(from) => {
+                            self.logger
+                                .info(&format!("Llamada de {} sin atender", from));
+                            // Si quedan otras sonando, se sigue en la pantalla
+                            if !self.join_meet.is_ringing() {
+                                self.lobby.set_error(format!("Missed call from {}", from));
+                                self.active_peer = None;
+                                self.current_screen = Screen::Lobby;
+                            }
+                            self.notify(Notification::MissedCall { from });
                         }
                     }
                 }
@@ -209,6 +772,7 @@ impl eframe::App for MainApp {
                             {
                                 let _ = signaling.end_call(&peer);
                             }
+                            self.waiting_call.cancel();
                             self.current_screen = Screen::Lobby
                         }
                         WaitingCallAction::GoToVideo => {
@@ -220,14 +784,47 @@ impl eframe::App for MainApp {
                                     inbox,
                                     self.waiting_call.active_peer(),
                                 );
+                                self.hand_over_files_for_call();
                             }
                             self.current_screen = Screen::VideoCall;
                         }
+                        WaitingCallAction::ConnectionFailed(reason) => {
+                            if let (Some(signaling), Some(peer)) =
+                                (self.signaling.as_ref(), self.waiting_call.active_peer())
+                            {
+                                let _ = signaling.end_call(&peer);
+                            }
+                            self.connection_failed(reason);
+                        }
+                        WaitingCallAction::CallFailed(reason) => {
+                            self.lobby.set_error(reason);
+                            self.active_peer = None;
+                            self.current_screen = Screen::Lobby;
+                        }
+                        WaitingCallAction::Retry => {
+                            let signaling = self.signaling.as_ref();
+                            if let (Some(signaling), Some(peer)) =
+                                (signaling, self.waiting_call.active_peer())
+                            {
+                                let _ = signaling.end_call(&peer);
+                            }
+                            if let Err(e) = self.waiting_call.retry(signaling) {
+                                self.waiting_call.status_message =
+                                    Some(format!("Failed to place call: {}", e));
+                            }
+                            self.logger
+                                .warn("Conexión con el peer sin completar, reintentando");
+                        }
                     }
                 }
             }
             Screen::VideoCall => {
-                if let Some(action) = self.video_meet.update(ctx, frame) {
+                let transfer_targets = self.lobby.available_users(self.username.as_deref());
+                let action = self.video_meet.update(ctx, frame, &transfer_targets);
+                for notification in self.video_meet.take_notifications() {
+                    self.notify(notification);
+                }
+                if let Some(action) = action {
                     match action {
                         VideoMeetAction::GoToLobby => {
                             if let (Some(signaling), Some(peer)) =
@@ -235,9 +832,57 @@ impl eframe::App for MainApp {
                             {
                                 let _ = signaling.end_call(&peer);
                             }
+                            self.close_video_call();
+                        }
+                        VideoMeetAction::ConnectionFailed(reason) => {
+                            if let (Some(signaling), Some(peer)) =
+                                (self.signaling.as_ref(), self.video_meet.peer())
+                            {
+                                let _ = signaling.end_call(&peer);
+                            }
+                            self.reject_call_waiting();
                             self.video_meet.reset();
-                            self.current_screen = Screen::Lobby;
-                            self.active_peer = None;
+                            self.connection_failed(reason);
+                        }
+                        VideoMeetAction::Transfer(target) => {
+                            // La llamada sigue hasta que el servidor confirme
+                            if let (Some(signaling), Some(peer)) =
+                                (self.signaling.as_ref(), self.video_meet.peer())
+                                && let Err(e) = signaling.transfer_call(&peer, &target)
+                            {
+                                self.video_meet.on_transfer_failed(&target, &e.to_string());
+                            }
+                        }
+                        VideoMeetAction::RestartIce => {
+                            if let Some(signaling) = self.signaling.as_ref() {
+                                match self.video_meet.restart_ice(signaling) {
+                                    Ok(()) => {
+                                        self.logger.info("Sin media del peer, reiniciando ICE")
+                                    }
+                                    Err(e) => self
+                                        .logger
+                                        .warn(&format!("No se pudo reiniciar ICE: {}", e)),
+                                }
+                            }
+                        }
+                        VideoMeetAction::AcceptWaitingCall => self.accept_call_waiting(),
+                        VideoMeetAction::RejectWaitingCall(from) => {
+                            if let Some(signaling) = self.signaling.as_ref() {
+                                let _ = signaling.reject_call(&from);
+                            }
+                            self.logger
+                                .info(&format!("Llamada en espera de {} rechazada", from));
+                        }
+                        VideoMeetAction::MissedWaitingCall(from) => {
+                            if let Some(signaling) = self.signaling.as_ref() {
+                                let _ = signaling.reject_call(&from);
+                            }
+                            self.logger
+                                .info(&format!("Llamada en espera de {} sin atender", from));
+                            self.notify(Notification::MissedCall { from });
+                        }
+                        VideoMeetAction::PinKey { peer, fingerprint } => {
+                            self.pin_key(peer, fingerprint);
                         }
                     }
                 }
@@ -0,0 +1,212 @@
+use crate::client::device_test::DeviceTest;
+use crate::client::settings::UserSettings;
+use crate::logger::Logger;
+use crate::ui::screens::settings::{camera_picker, device_combo};
+use crate::ui::screens::video::VideoCall;
+use crate::ui::theme::colors;
+use eframe::egui::{self, RichText, TextureHandle};
+use room_rtc::audio::audio_capture::input_device_names;
+use room_rtc::audio::audio_playback::output_device_names;
+use room_rtc::stun::NatType;
+
+const PREVIEW_SIZE: egui::Vec2 = egui::vec2(480.0, 270.0);
+
+pub enum DeviceTestAction {
+    /// Vuelve con los dispositivos elegidos en la prueba.
+    Back(UserSettings),
+}
+
+/// Pantalla para probar cámara, micrófono, parlantes y red desde el lobby o
+/// la configuración, eligiendo los dispositivos sobre la marcha.
+pub struct DeviceTestScreen {
+    test: Option<DeviceTest>,
+    settings: UserSettings,
+    input_devices: Vec<String>,
+    output_devices: Vec<String>,
+    preview: Option<TextureHandle>,
+    stun_server: String,
+    logger: Logger,
+}
+
+impl DeviceTestScreen {
+    pub fn new(settings: UserSettings, stun_server: String, logger: Logger) -> Self {
+        Self {
+            test: None,
+            settings,
+            input_devices: Vec::new(),
+            output_devices: Vec::new(),
+            preview: None,
+            stun_server,
+            logger,
+        }
+    }
+
+    pub fn open(&mut self, settings: &UserSettings) {
+        // La cámara anterior se suelta antes de volver a abrirla
+        self.close();
+        self.settings = settings.clone();
+        self.input_devices = input_device_names();
+        self.output_devices = output_device_names();
+        self.test = Some(DeviceTest::start(
+            settings.camera_index,
+            settings.video,
+            settings.test_pattern,
+            settings.input_device.as_deref(),
+        ));
+    }
+
+    /// Libera cámara, micrófono y parlantes, por ejemplo antes de atender una
+    /// llamada.
+    pub fn close(&mut self) {
+        self.test = None;
+        self.preview = None;
+    }
+
+    pub fn update(&mut self, ctx: &egui::Context) -> Option<DeviceTestAction> {
+        let mut action = None;
+        let Some(test) = self.test.as_mut() else {
+            return Some(DeviceTestAction::Back(self.settings.clone()));
+        };
+        let before = self.settings.clone();
+
+        if let Some(image) = test
+            .latest_frame()
+            .and_then(|frame| VideoCall::mat_to_color_image(&frame))
+        {
+            VideoCall::update_texture(ctx, &mut self.preview, "device_test_preview", image);
+        }
+        let level = test.mic_level();
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.add_space(20.0);
+            ui.heading(
+                RichText::new("Test devices")
+                    .size(28.0)
+                    .strong()
+                    .color(egui::Color32::WHITE),
+            );
+            ui.label(
+                RichText::new("Check your camera, microphone, speakers and network before a call")
+                    .color(colors::TEXT_MUTED),
+            );
+            ui.add_space(20.0);
+
+            ui.horizontal(|ui| {
+                ui.label(RichText::new("Camera").strong());
+                camera_picker(ui, "device_test_camera", &mut self.settings);
+            });
+            let placeholder = test.camera_error().unwrap_or("Opening camera...");
+            VideoCall::draw_video_slot(ui, self.preview.as_ref(), placeholder, PREVIEW_SIZE);
+            if let Some(err) = test.camera_error() {
+                ui.colored_label(colors::DANGER, format!("Camera error: {}", err));
+            }
+            ui.add_space(16.0);
+
+            ui.horizontal(|ui| {
+                ui.label(RichText::new("Microphone").strong());
+                device_combo(
+                    ui,
+                    "device_test_input",
+                    &mut self.settings.input_device,
+                    &self.input_devices,
+                );
+            });
+            match test.mic_error() {
+                Some(err) => {
+                    ui.colored_label(colors::DANGER, format!("Microphone error: {}", err));
+                }
+                None => {
+                    ui.add(
+                        egui::ProgressBar::new(level)
+                            .desired_width(PREVIEW_SIZE.x)
+                            .text("Speak to see the level move"),
+                    );
+                }
+            }
+            ui.add_space(16.0);
+
+            ui.horizontal(|ui| {
+                ui.label(RichText::new("Speakers").strong());
+                device_combo(
+                    ui,
+                    "device_test_output",
+                    &mut self.settings.output_device,
+                    &self.output_devices,
+                );
+            });
+            ui.horizontal(|ui| {
+                let playing = test.tone_playing();
+                if ui
+                    .add_enabled(!playing, egui::Button::new("Play test tone"))
+                    .clicked()
+                {
+                    test.play_test_tone(self.settings.output_device.as_deref());
+                }
+                if playing {
+                    ui.spinner();
+                    ui.label("You should hear a short beep");
+                }
+            });
+            if let Some(err) = test.speaker_error() {
+                ui.colored_label(colors::DANGER, format!("Speaker error: {}", err));
+            }
+            ui.add_space(16.0);
+
+            ui.label(RichText::new("Network").strong());
+            ui.horizontal(|ui| {
+                let running = test.stun_running();
+                if ui
+                    .add_enabled(!running, egui::Button::new("Check connectivity"))
+                    .clicked()
+                {
+                    test.start_stun_check(self.stun_server.clone(), self.logger.clone());
+                }
+                if running {
+                    ui.spinner();
+                    ui.label(format!("Contacting {}...", self.stun_server));
+                }
+            });
+            match test.stun_result() {
+                Some(Ok(report)) => {
+                    if let Some(addr) = report.public_addr {
+                        ui.label(format!("Public address: {}", addr));
+                    }
+                    let color = match report.nat_type {
+                        NatType::Open | NatType::EndpointIndependent => colors::SUCCESS,
+                        _ => colors::WARNING,
+                    };
+                    ui.colored_label(color, format!("NAT type: {}", report.nat_type.label()));
+                }
+                Some(Err(err)) => {
+                    ui.colored_label(colors::DANGER, format!("STUN check failed: {}", err));
+                }
+                None => {}
+            }
+
+            ui.add_space(20.0);
+            if ui
+                .add(egui::Button::new("Back").min_size(egui::vec2(120.0, 36.0)))
+                .clicked()
+            {
+                action = Some(DeviceTestAction::Back(self.settings.clone()));
+            }
+        });
+
+        // Lo que cambió se prueba enseguida, sin salir de la pantalla
+        let settings = &self.settings;
+        if settings.camera_index != before.camera_index
+            || settings.test_pattern != before.test_pattern
+        {
+            self.preview = None;
+            test.restart_camera(settings.camera_index, settings.video, settings.test_pattern);
+        }
+        if settings.input_device != before.input_device {
+            test.restart_microphone(settings.input_device.as_deref());
+        }
+
+        if action.is_some() {
+            self.close();
+        }
+        action
+    }
+}
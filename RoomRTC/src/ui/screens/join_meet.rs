@@ -1,27 +1,49 @@
+use crate::client::capabilities::PeerCapabilities;
+use crate::client::message_inbox::{MessageInbox, SharedInbox};
 use crate::client::p2p_client::P2PClient;
+use crate::client::pending_calls::PendingCalls;
 use crate::client::signaling_client::SignalingClient;
-use crate::client::webrtc_service::WebRTCHandler;
+use crate::client::webrtc_service::{CallProgress, CallSignaling, PeerConfig, WebRTCHandler};
+use crate::ui::screens::status_utils::connection_progress::{
+    ConnectionProgress, ConnectionProgressAction,
+};
 use eframe::egui::{self, Button};
 use egui::RichText;
 use egui::Vec2;
+use room_rtc::rtc::rtc_dtls::DtlsIdentity;
 use room_rtc::rtc::rtc_peer_connection::PeerConnectionRole;
-use std::sync::{Arc, Mutex};
+use room_rtc::{log_debug, log_warn};
+use std::sync::Arc;
+use std::time::Duration;
+
+// Lo que suena una llamada sin atender si no se configura otra cosa
+pub const DEFAULT_RING_TIMEOUT: Duration = Duration::from_secs(30);
+
 pub enum JoinMeetAction {
     GoToLobby,
     GoToVideo,
+    // ICE/DTLS falló: volver al lobby mostrando el motivo
+    ConnectionFailed(String),
+    // Sonó hasta el timeout sin que nadie atendiera; ya se rechazó
+    MissedCall(String),
 }
 pub struct JoinMeetScreen {
     pub local_sdp: String,
 
     role: PeerConnectionRole,
     outgoing_msg: String,
-    received_msgs: Arc<Mutex<Vec<String>>>,
+    received_msgs: SharedInbox,
     client: Option<P2PClient>,
-    pub remote_sdp: String,
     ice_started: bool,
     status_message: Option<String>,
-    incoming_from: Option<String>,
+    // Las llamadas que suenan, cada una con su oferta y lo que anunció
+    pending: PendingCalls,
+    // La llamada atendida que se está conectando
     active_peer: Option<String>,
+    peer_config: PeerConfig,
+    session_code: String,
+    connecting: ConnectionProgress,
+    ring_timeout: Duration,
 }
 
 impl WebRTCHandler for JoinMeetScreen {
@@ -32,28 +54,60 @@ impl WebRTCHandler for JoinMeetScreen {
     fn role(&self) -> PeerConnectionRole {
         self.role
     }
-    fn received_msgs(&self) -> &Arc<Mutex<Vec<String>>> {
+    fn received_msgs(&self) -> &SharedInbox {
         &self.received_msgs
     }
+
+    fn peer_config(&self) -> PeerConfig {
+        self.peer_config.clone()
+    }
 }
 
 impl JoinMeetScreen {
-    pub fn new(role: PeerConnectionRole) -> Self {
+    pub fn new(
+        role: PeerConnectionRole,
+        peer_config: PeerConfig,
+        connect_timeout: Duration,
+    ) -> Self {
         Self {
             local_sdp: String::new(),
             role,
             //Refactor these fields later
             outgoing_msg: String::new(),
-            received_msgs: Arc::new(Mutex::new(Vec::new())),
+            received_msgs: MessageInbox::shared(),
             client: None,
-            remote_sdp: String::new(),
             ice_started: false,
             status_message: None,
-            incoming_from: None,
+            pending: PendingCalls::default(),
             active_peer: None,
+            peer_config,
+            session_code: String::new(),
+            connecting: ConnectionProgress::new(connect_timeout),
+            ring_timeout: DEFAULT_RING_TIMEOUT,
         }
     }
 
+    pub fn with_ring_timeout(mut self, ring_timeout: Duration) -> Self {
+        self.ring_timeout = ring_timeout;
+        self
+    }
+
+    /// Cuántas llamadas suenan a la vez; las demás se rechazan como ocupado.
+    pub fn with_max_pending_calls(mut self, max: usize) -> Self {
+        self.pending = PendingCalls::new(max);
+        self
+    }
+
+    /// Ancho de banda a anunciar en el SDP de la próxima llamada.
+    pub fn set_bandwidth_kbps(&mut self, bandwidth_kbps: u64) {
+        self.peer_config.bandwidth_kbps = Some(bandwidth_kbps);
+    }
+
+    /// Certificado DTLS de las próximas llamadas; `None` genera uno por llamada.
+    pub fn set_dtls_identity(&mut self, identity: Option<Arc<DtlsIdentity>>) {
+        self.peer_config.dtls.identity = identity;
+    }
+
     pub fn update(
         &mut self,
         ctx: &egui::Context,
@@ -62,12 +116,27 @@ impl JoinMeetScreen {
     ) -> Option<JoinMeetAction> {
         let mut next_action = None;
 
+        if let Some(reason) = self.connection_failure() {
+            self.client = None;
+            self.ice_started = false;
+            self.connecting.stop();
+            next_action = Some(JoinMeetAction::ConnectionFailed(reason));
+        }
+
+        if let Some(missed) = self.reject_expired_call(signaling.map(|s| s as &dyn CallSignaling)) {
+            next_action = Some(missed);
+        }
+        if self.is_ringing() {
+            // Para la cuenta regresiva y para que venzan a tiempo
+            ctx.request_repaint_after(Duration::from_secs(1));
+        }
+
         egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
             ui.heading("Join Meeting");
 
             let res_go_lobby = ui.add(Button::new("Go to Lobby"));
             if res_go_lobby.clicked() {
-                println!("Returning to Lobby");
+                log_debug!("Returning to Lobby");
                 next_action = Some(JoinMeetAction::GoToLobby);
             }
             /* DEBUG */
@@ -95,167 +164,243 @@ impl JoinMeetScreen {
                 ui.label(RichText::new(status).color(crate::ui::theme::colors::TEXT_PRIMARY));
                 ui.add_space(10.0);
             }
-            
-            if self.incoming_from.is_some() {
+            if let Some(progress) = self.connection_progress() {
+                match self.connecting.show(ui, &progress) {
+                    Some(ConnectionProgressAction::Retry) => self.retry_connection(),
+                    Some(ConnectionProgressAction::BackToLobby) => {
+                        next_action = Some(JoinMeetAction::GoToLobby);
+                    }
+                    None => {}
+                }
+                ui.add_space(10.0);
+            }
+
+            if !self.pending.is_empty() {
+                // (quién, si la atendió); se aplica después de recorrer la lista
+                let mut choice: Option<(String, bool)> = None;
                 ui.vertical_centered(|ui| {
                     ui.add_space(40.0);
-                    
-                    egui::Frame::none()
-                        .fill(crate::ui::theme::colors::BACKGROUND_SECONDARY)
-                        .rounding(16.0)
-                        .shadow(eframe::egui::Shadow::default())
-                        .inner_margin(32.0)
-                        .show(ui, |ui| {
-                            let caller = self.incoming_from.as_deref().unwrap_or("Unknown");
-                            ui.heading(RichText::new("Incoming Call").size(24.0).color(egui::Color32::WHITE));
-                            ui.add_space(8.0);
-                            ui.label(RichText::new(format!("{} is calling you...", caller)).size(18.0).color(crate::ui::theme::colors::TEXT_PRIMARY));
-                            ui.add_space(32.0);
-                            
-                            ui.horizontal(|ui| {
-                                ui.add_space(20.0);
-                                // Accept Button
-                                let accept_btn = Button::new(RichText::new("📞 Accept").size(20.0).color(egui::Color32::WHITE))
+                    let heading = match self.pending.len() {
+                        1 => "Incoming Call".to_string(),
+                        n => format!("{} Incoming Calls", n),
+                    };
+                    ui.heading(
+                        RichText::new(heading)
+                            .size(24.0)
+                            .color(egui::Color32::WHITE),
+                    );
+
+                    for call in self.pending.iter() {
+                        ui.add_space(16.0);
+                        egui::Frame::none()
+                            .fill(crate::ui::theme::colors::BACKGROUND_SECONDARY)
+                            .rounding(16.0)
+                            .shadow(eframe::egui::Shadow::default())
+                            .inner_margin(24.0)
+                            .show(ui, |ui| {
+                                ui.label(
+                                    RichText::new(format!("{} is calling you...", call.from))
+                                        .size(18.0)
+                                        .color(crate::ui::theme::colors::TEXT_PRIMARY),
+                                );
+                                ui.label(
+                                    RichText::new(format!(
+                                        "{} s left to answer",
+                                        call.remaining(self.ring_timeout).as_secs()
+                                    ))
+                                    .color(crate::ui::theme::colors::TEXT_MUTED),
+                                );
+                                ui.add_space(16.0);
+
+                                ui.horizontal(|ui| {
+                                    ui.add_space(20.0);
+                                    let accept_btn = Button::new(
+                                        RichText::new("📞 Accept")
+                                            .size(20.0)
+                                            .color(egui::Color32::WHITE),
+                                    )
                                     .fill(crate::ui::theme::colors::SUCCESS)
                                     .rounding(30.0) // Circular/Pill
                                     .min_size(Vec2::new(140.0, 60.0));
-                                    
-                                if ui.add(accept_btn).clicked() {
-                                    if let Some(signaling) = signaling {
-                                        match self.accept_current_call(signaling) {
-                                            Ok(_) => {
-                                                self.status_message =
-                                                    Some("Answer sent... Starting ICE...".into());
-                                                next_action = Some(JoinMeetAction::GoToVideo);
-                                            }
-                                            Err(err) => self.status_message = Some(err),
-                                        }
-                                    } else {
-                                        self.status_message =
-                                            Some("First connect to the signaling server.".to_string());
+                                    if ui.add(accept_btn).clicked() {
+                                        choice = Some((call.from.clone(), true));
                                     }
-                                }
-                                
-                                ui.add_space(40.0);
-                                
-                                // Decline Button
-                                let decline_btn = Button::new(RichText::new("✖ Decline").size(20.0).color(egui::Color32::WHITE))
+
+                                    ui.add_space(40.0);
+
+                                    let decline_btn = Button::new(
+                                        RichText::new("✖ Decline")
+                                            .size(20.0)
+                                            .color(egui::Color32::WHITE),
+                                    )
                                     .fill(crate::ui::theme::colors::DANGER)
                                     .rounding(30.0)
                                     .min_size(Vec2::new(140.0, 60.0));
-                                    
-                                if ui.add(decline_btn).clicked() {
-                                    if let Some(signaling) = signaling
-                                        && let Some(peer) = &self.incoming_from
-                                    {
-                                        let _ = signaling.reject_call(peer);
+                                    if ui.add(decline_btn).clicked() {
+                                        choice = Some((call.from.clone(), false));
                                     }
-                                    self.incoming_from = None;
-                                    self.active_peer = None;
-                                    self.status_message = Some("Call was declined".to_string());
-                                }
-                                ui.add_space(20.0);
+                                    ui.add_space(20.0);
+                                });
                             });
-                        });
-                });
-            } else {
-                 ui.vertical_centered(|ui| {
-                      ui.add_space(50.0);
-                      ui.heading(RichText::new("Waiting for calls...").color(crate::ui::theme::colors::TEXT_MUTED));
-                      ui.add_space(10.0);
-                      ui.spinner();
-                 });
-                 
-                 // Hidden advanced debug
-                 ui.collapsing("Debug / Manual Join", |ui| {
-                ui.separator();
-                ui.vertical(|ui| {
-                    ui.label("Respuesta SDP local");
-                    ui.add(egui::TextEdit::multiline(&mut self.local_sdp).desired_rows(6));
-
-                    let sdp_copy_btn = Button::new("Click to copy");
-                    let res_sdp_copy_btn = ui.add(sdp_copy_btn);
-
-                    if res_sdp_copy_btn.clicked() {
-                        ctx.output_mut(|o| o.copied_text = self.local_sdp.clone());
-                        println!("SDP copied");
                     }
                 });
-                ui.separator();
-                let ice_starter = ui.add(Button::new("Start ice"));
-                if ice_starter.clicked() {
-                    if self.ice_started {
-                        self.status_message = Some("ICE ya está iniciado".to_string());
-                    } else if let Some(result) = self.ensure_peer_and_start_ice()
-                        && let Err(err) = result
-                    {
-                        eprintln!("ICE ERROR {}", err);
-                        self.status_message = Some(format!("Error iniciando ICE: {}", err));
+
+                match choice {
+                    Some((from, true)) => {
+                        if let Some(signaling) = signaling {
+                            match self.accept_call(signaling, &from) {
+                                Ok(_) => next_action = Some(JoinMeetAction::GoToVideo),
+                                Err(err) => self.status_message = Some(err),
+                            }
+                        } else {
+                            self.status_message =
+                                Some("First connect to the signaling server.".to_string());
+                        }
+                    }
+                    Some((from, false)) => {
+                        if self.pending.remove(&from).is_some()
+                            && let Some(signaling) = signaling
+                        {
+                            let _ = signaling.reject_call(&from);
+                        }
+                        self.status_message = Some("Call was declined".to_string());
                     }
+                    None => {}
                 }
-                ui.separator();
-                ui.horizontal(|ui| {
-                    ui.label("Message");
-                    ui.text_edit_singleline(&mut self.outgoing_msg);
+            } else {
+                ui.vertical_centered(|ui| {
+                    ui.add_space(50.0);
+                    ui.heading(
+                        RichText::new("Waiting for calls...")
+                            .color(crate::ui::theme::colors::TEXT_MUTED),
+                    );
+                    ui.add_space(10.0);
+                    ui.spinner();
                 });
 
-                if ui.button("Send").clicked()
-                    && let Err(err) = self.send_message(&self.outgoing_msg.clone())
-                {
-                    eprintln!("Error: {:?}", err);
-                    return;
-                }
+                // Hidden advanced debug
+                ui.collapsing("Debug / Manual Join", |ui| {
+                    ui.separator();
+                    ui.vertical(|ui| {
+                        ui.label("Respuesta SDP local");
+                        ui.add(egui::TextEdit::multiline(&mut self.local_sdp).desired_rows(6));
 
-                ui.separator();
-                ui.label("received messages:");
-                if let Ok(messages) = self.received_msgs.lock() {
-                    for msg in messages.iter() {
-                        ui.label(msg);
-                    }
-                }
+                        let sdp_copy_btn = Button::new("Click to copy");
+                        let res_sdp_copy_btn = ui.add(sdp_copy_btn);
 
-                if let Some(status) = &self.status_message {
+                        if res_sdp_copy_btn.clicked() {
+                            ctx.output_mut(|o| o.copied_text = self.local_sdp.clone());
+                            log_debug!("SDP copied");
+                        }
+                    });
                     ui.separator();
-                    ui.label(status);
-                }
-
-                ui.separator();
-                let go_meet = ui.add(Button::new("Go to meet"));
-                if go_meet.clicked() {
-                    println!("Joining meet");
-                    if self.client.is_none() {
-                        self.status_message = Some(
-                            "Espera una llamada y acéptala antes de entrar al video.".to_string(),
+                    // Emparejar sin servidor: código de sesión en una sola línea
+                    ui.label("Session code");
+                    ui.horizontal(|ui| {
+                        ui.add(
+                            egui::TextEdit::singleline(&mut self.session_code)
+                                .hint_text("Caller's session code"),
                         );
-                    } else {
-                        if !self.ice_started {
-                            if let Some(result) = self.ensure_peer_and_start_ice() {
-                                if let Err(err) = result {
-                                    self.status_message = Some(format!("Error: {}", err));
-                                } else {
-                                    self.status_message = Some("Iniciando conexión...".to_string());
+                        if ui.button("Paste session code").clicked() {
+                            let code = self.session_code.clone();
+                            match self.import_session_code(&code) {
+                                Ok(answer) => {
+                                    self.local_sdp = answer.unwrap_or_default();
+                                    self.session_code.clear();
+                                    self.connecting.start();
+                                    self.status_message = Some(
+                                        "Offer imported: send your session code back and start ICE"
+                                            .to_string(),
+                                    );
+                                }
+                                Err(err) => {
+                                    self.status_message =
+                                        Some(format!("Invalid session code: {}", err));
                                 }
                             }
-                        } else if let Some(client) = &self.client {
-                            if client.has_connection() {
-                                self.status_message = Some("Entrando a la sala de video...".to_string());
-                                next_action = Some(JoinMeetAction::GoToVideo);
-                            } else {
-                                self.status_message = Some("Esperando conexión...".to_string());
+                        }
+                    });
+                    if ui.button("Copy session code").clicked() {
+                        match self.export_session_code() {
+                            Ok(code) => ctx.output_mut(|o| o.copied_text = code),
+                            Err(err) => {
+                                self.status_message = Some(format!("No session code yet: {}", err));
                             }
                         }
                     }
-                }
+                    ui.separator();
+                    let ice_starter = ui.add(Button::new("Start ice"));
+                    if ice_starter.clicked() {
+                        if self.ice_started {
+                            self.status_message = Some("ICE ya está iniciado".to_string());
+                        } else if let Some(result) = self.ensure_peer_and_start_ice()
+                            && let Err(err) = result
+                        {
+                            log_warn!("ICE ERROR {}", err);
+                            self.status_message = Some(format!("Error iniciando ICE: {}", err));
+                        }
+                    }
+                    ui.separator();
+                    ui.horizontal(|ui| {
+                        ui.label("Message");
+                        ui.text_edit_singleline(&mut self.outgoing_msg);
+                    });
+
+                    if ui.button("Send").clicked()
+                        && let Err(err) = self.send_message(&self.outgoing_msg.clone())
+                    {
+                        log_warn!("Error: {:?}", err);
+                        return;
+                    }
+
+                    ui.separator();
+                    ui.label("received messages:");
+                    if let Ok(messages) = self.received_msgs.lock() {
+                        for msg in messages.iter() {
+                            ui.label(msg);
+                        }
+                    }
+
+                    if let Some(status) = &self.status_message {
+                        ui.separator();
+                        ui.label(status);
+                    }
+
+                    if let Some(client) = &self.client
+                        && ui.button("Copy diagnostics").clicked()
+                    {
+                        let report = client.diagnostics(self.status_message.as_deref());
+                        ctx.output_mut(|o| o.copied_text = report);
+                    }
+
+                    ui.separator();
+                    let go_meet = ui.add(Button::new("Go to meet"));
+                    if go_meet.clicked() {
+                        log_debug!("Joining meet");
+                        if self.client.is_none() {
+                            self.status_message = Some(
+                                "Espera una llamada y acéptala antes de entrar al video."
+                                    .to_string(),
+                            );
+                        } else if !self.ice_started {
+                            if let Some(Err(err)) = self.ensure_peer_and_start_ice() {
+                                self.status_message = Some(format!("Error: {}", err));
+                            }
+                        } else if self.connection_progress() == Some(CallProgress::Connected) {
+                            next_action = Some(JoinMeetAction::GoToVideo);
+                        }
+                    }
                 }); // Close collapsing
             }
         });
         next_action
     }
 
-    pub fn take_client_with_inbox(&mut self) -> Option<(P2PClient, Arc<Mutex<Vec<String>>>)> {
+    pub fn take_client_with_inbox(&mut self) -> Option<(P2PClient, SharedInbox)> {
+        self.connecting.stop();
         if let Some(client) = self.client.take() {
             let inbox = Arc::clone(&self.received_msgs);
-            self.received_msgs = Arc::new(Mutex::new(Vec::new()));
+            self.received_msgs = MessageInbox::shared();
             return Some((client, inbox));
         }
         None
@@ -274,29 +419,96 @@ impl JoinMeetScreen {
         match self.start_ice() {
             Ok(_) => {
                 self.ice_started = true;
-                self.status_message = Some("ICE iniciado, esperando conexión...".to_string());
+                self.connecting.start();
                 Some(Ok(()))
             }
             Err(err) => Some(Err(err)),
         }
     }
 
-    pub fn on_incoming_call(&mut self, from: String, sdp: String) {
-        self.remote_sdp = sdp;
-        self.incoming_from = Some(from.clone());
-        self.active_peer = Some(from.clone());
-        self.status_message = Some(format!("Llamada entrante de {}", from));
+    /// Vuelve a correr los chequeos ICE con la misma oferta; la respuesta ya
+    /// la tiene el peer.
+    fn retry_connection(&mut self) {
+        self.ice_started = false;
+        match self.ensure_peer_and_start_ice() {
+            Some(Err(err)) => {
+                self.connecting.stop();
+                self.status_message = Some(format!("Error iniciando ICE: {}", err));
+            }
+            Some(Ok(())) => {}
+            // Sin peer ya no hay nada que reintentar
+            None => self.connecting.stop(),
+        }
+    }
+
+    /// Suma la llamada de `from` a las que suenan. Devuelve `false` si no
+    /// hay lugar o ya se está conectando otra: hay que rechazarla como
+    /// ocupado.
+    pub fn on_incoming_call(&mut self, from: String, sdp: String, caps: PeerCapabilities) -> bool {
+        if self.active_peer.is_some() {
+            return false;
+        }
+        let status = format!("Llamada entrante de {}", from);
+        if !self.pending.push(from, sdp, caps) {
+            return false;
+        }
+        self.status_message = Some(status);
+        true
+    }
+
+    /// Rechaza la llamada que sonó `ring_timeout` sin que nadie la atendiera.
+    /// De a una por frame: la más vieja vence primero.
+    fn reject_expired_call(
+        &mut self,
+        signaling: Option<&dyn CallSignaling>,
+    ) -> Option<JoinMeetAction> {
+        let call = self.pending.take_expired(self.ring_timeout)?;
+        if let Some(signaling) = signaling {
+            let _ = signaling.reject_call(&call.from);
+        }
+        self.status_message = Some(format!("Missed call from {}", call.from));
+        Some(JoinMeetAction::MissedCall(call.from))
+    }
+
+    /// Olvida las llamadas de una visita anterior a la pantalla, sin avisar
+    /// a nadie.
+    pub fn clear_calls(&mut self) {
+        self.pending.take_all();
+        self.active_peer = None;
+    }
+
+    /// Si hay alguna llamada sonando.
+    pub fn is_ringing(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    /// Saca la llamada de `from` si seguía sonando (cortó antes de que la
+    /// atendieran).
+    pub fn dismiss_pending_call(&mut self, from: &str) -> bool {
+        if self.pending.remove(from).is_none() {
+            return false;
+        }
+        self.status_message = Some(format!("{} colgó antes de que atendieras", from));
+        true
+    }
+
+    /// Rechaza todas las que suenan, p.ej. al volver al lobby.
+    pub fn decline_pending_calls(&mut self, signaling: Option<&SignalingClient>) {
+        for call in self.pending.take_all() {
+            if let Some(signaling) = signaling {
+                let _ = signaling.reject_call(&call.from);
+            }
+        }
     }
 
     pub fn on_call_ended(&mut self, from: &str) {
         if self.active_peer.as_deref() == Some(from) {
             self.status_message = Some(format!("{} colgó la llamada", from));
-            self.incoming_from = None;
             self.active_peer = None;
             self.client = None;
-            self.remote_sdp.clear();
             self.local_sdp.clear();
             self.ice_started = false;
+            self.connecting.stop();
         }
     }
 
@@ -304,25 +516,93 @@ impl JoinMeetScreen {
         self.active_peer.clone()
     }
 
-    fn accept_current_call(&mut self, signaling: &SignalingClient) -> Result<(), String> {
-        let Some(caller) = self.incoming_from.clone() else {
-            return Err("No hay ninguna llamada entrante".to_string());
+    /// Contesta la llamada de `from` y arranca la conexión. Las demás que
+    /// sonaban se rechazan como ocupado. Si no se puede contestar, sigue
+    /// sonando para volver a intentarlo.
+    pub fn accept_call(&mut self, signaling: &SignalingClient, from: &str) -> Result<(), String> {
+        let Some(call) = self.pending.get(from).cloned() else {
+            return Err(format!("No hay ninguna llamada entrante de {}", from));
         };
-        self.initialize_peer()
-            .map_err(|e| format!("No se pudo iniciar el peer: {}", e))?;
-        let remote_sdp = self.remote_sdp.clone();
         let answer = self
-            .process_remote_offer(&remote_sdp)
-            .map_err(|e| format!("No se pudo procesar la oferta: {}", e))?;
-        signaling
-            .answer_call(&caller, &answer)
-            .map_err(|e| e.to_string())?;
-        self.local_sdp = answer;
-        if let Err(err) = self.start_ice() {
-            self.status_message = Some(format!("Error iniciando ICE: {}", err));
-        } else {
-            self.ice_started = true;
+            .answer_offer(signaling, &call.from, &call.sdp, &call.caps)
+            .map_err(|e| format!("No se pudo atender la llamada: {}", e))?;
+        self.pending.remove(from);
+        for other in self.pending.take_all() {
+            if let Err(err) = signaling.reject_call_busy(&other.from) {
+                log_warn!("No se pudo rechazar la llamada de {}: {}", other.from, err);
+            }
         }
+        self.active_peer = Some(call.from);
+        self.local_sdp = answer;
+        self.ice_started = true;
+        self.connecting.start();
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::io;
+    use std::thread;
+
+    /// Guarda los CALL_REJECT que se mandarían al servidor.
+    #[derive(Default)]
+    struct RejectRecorder {
+        rejected: RefCell<Vec<String>>,
+    }
+
+    impl CallSignaling for RejectRecorder {
+        fn call(&self, _to: &str, _sdp: &str) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn answer_call(&self, _to: &str, _sdp: &str) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn restart_ice(&self, _to: &str, _sdp: Option<&str>) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn answer_ice_restart(&self, _to: &str, _sdp: &str) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn end_call(&self, _to: &str) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn reject_call(&self, to: &str) -> io::Result<()> {
+            self.rejected.borrow_mut().push(to.to_string());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_unanswered_call_is_rejected_after_the_ring_timeout() {
+        let mut screen = JoinMeetScreen::new(
+            PeerConnectionRole::Controlled,
+            PeerConfig::default(),
+            Duration::from_secs(10),
+        )
+        .with_ring_timeout(Duration::from_millis(50));
+        let signaling = RejectRecorder::default();
+        assert!(screen.on_incoming_call(
+            "bob".to_string(),
+            "v=0".to_string(),
+            PeerCapabilities::legacy()
+        ));
+
+        // Todavía suena
+        assert!(screen.reject_expired_call(Some(&signaling)).is_none());
+        assert!(signaling.rejected.borrow().is_empty());
+
+        thread::sleep(Duration::from_millis(80));
+        let action = screen.reject_expired_call(Some(&signaling));
+        assert!(matches!(action, Some(JoinMeetAction::MissedCall(from)) if from == "bob"));
+        assert_eq!(*signaling.rejected.borrow(), vec!["bob".to_string()]);
+        assert!(!screen.is_ringing());
+    }
+}
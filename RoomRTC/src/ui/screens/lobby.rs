@@ -1,16 +1,33 @@
-use crate::client::signaling_client::SignalingClient;
-use crate::ui::screens::status_utils::ui_status;
+use crate::client::signaling_client::{SignalingClient, UserEntry};
+use crate::ui::screens::status_utils::ui_status::Status;
 use eframe::egui::{self};
+use std::path::PathBuf;
 
 pub enum LobbyAction {
     GoToWaitingCall(String),
+    // Archivos soltados sobre un usuario: llamarlo y mandarlos al conectar
+    CallWithFiles(String, Vec<PathBuf>),
+    OpenSettings,
+    TestDevices,
     Logout,
 }
 
+/// Con más usuarios que esto se muestra el buscador.
+const FILTER_THRESHOLD: usize = 6;
+
 pub struct LobbyScreen {
     err_message: Option<String>,
     users: Vec<(String, String)>,
     status_message: Option<String>,
+    search: String,
+    only_available: bool,
+    do_not_disturb: bool,
+    // Usuario al que ya estamos llamando, para no llamarlo dos veces
+    pending_call: Option<String>,
+    // Alternativa a la lista: llamar escribiendo el nombre
+    manual_target: String,
+    // Usuarios que bloqueamos; siguen en la lista, marcados
+    blocked: Vec<String>,
 }
 
 impl eframe::App for LobbyScreen {
@@ -25,9 +42,25 @@ impl LobbyScreen {
             err_message: None,
             users: Vec::new(),
             status_message: None,
+            search: String::new(),
+            only_available: false,
+            do_not_disturb: false,
+            pending_call: None,
+            manual_target: String::new(),
+            blocked: Vec::new(),
         }
     }
 
+    /// Usuario con una llamada saliente sin atender todavía.
+    pub fn set_pending_call(&mut self, user: Option<String>) {
+        self.pending_call = user;
+    }
+
+    /// Muestra en la barra lateral que las llamadas entrantes se rechazan.
+    pub fn set_do_not_disturb(&mut self, do_not_disturb: bool) {
+        self.do_not_disturb = do_not_disturb;
+    }
+
     pub fn update(
         &mut self,
         ctx: &egui::Context,
@@ -35,6 +68,14 @@ impl LobbyScreen {
         current_user: Option<&str>,
     ) -> Option<LobbyAction> {
         let mut next_action = None;
+        let dropped: Vec<PathBuf> = ctx.input(|i| {
+            i.raw
+                .dropped_files
+                .iter()
+                .filter_map(|file| file.path.clone())
+                .collect()
+        });
+        let dragging_files = ctx.input(|i| !i.raw.hovered_files.is_empty());
 
         // Top/Side Panel for User Info
         egui::SidePanel::left("lobby_sidebar")
@@ -46,110 +87,322 @@ impl LobbyScreen {
                     // Avatar/Icon placeholder
                     ui.label(egui::RichText::new("👤").size(60.0));
                     ui.add_space(10.0);
-                    
+
                     #[allow(clippy::manual_unwrap_or)]
                     let user_display_name = match current_user {
                         Some(name) => name,
                         None => "Unknown",
                     };
-                    
-                    ui.heading(egui::RichText::new(user_display_name).size(20.0).color(egui::Color32::WHITE));
-                    ui.label(egui::RichText::new("Online").color(crate::ui::theme::colors::SUCCESS));
+
+                    ui.heading(
+                        egui::RichText::new(user_display_name)
+                            .size(20.0)
+                            .color(egui::Color32::WHITE),
+                    );
+                    if self.do_not_disturb {
+                        ui.label(
+                            egui::RichText::new("Do not disturb")
+                                .color(crate::ui::theme::colors::DANGER),
+                        );
+                    } else {
+                        ui.label(
+                            egui::RichText::new("Online").color(crate::ui::theme::colors::SUCCESS),
+                        );
+                    }
                 });
-                
+
                 ui.add_space(40.0);
                 ui.separator();
                 ui.add_space(20.0);
-                
+
                 // Actions in Sidebar
                 ui.vertical_centered(|ui| {
                     if let Some(signaling) = signaling {
-                        let refresh_btn = egui::Button::new(egui::RichText::new("🔄 Refresh List").size(14.0))
-                            .fill(crate::ui::theme::colors::BACKGROUND_SECONDARY)
-                            .min_size(egui::vec2(180.0, 40.0));
-                            
+                        let refresh_btn =
+                            egui::Button::new(egui::RichText::new("🔄 Refresh List").size(14.0))
+                                .fill(crate::ui::theme::colors::BACKGROUND_SECONDARY)
+                                .min_size(egui::vec2(180.0, 40.0));
+
                         if ui.add(refresh_btn).clicked() {
-                             let _ = signaling.request_users();
+                            let _ = signaling.request_users();
+                        }
+
+                        ui.add_space(10.0);
+
+                        let settings_btn =
+                            egui::Button::new(egui::RichText::new("⚙ Settings").size(14.0))
+                                .fill(crate::ui::theme::colors::BACKGROUND_SECONDARY)
+                                .min_size(egui::vec2(180.0, 40.0));
+
+                        if ui.add(settings_btn).clicked() {
+                            next_action = Some(LobbyAction::OpenSettings);
+                        }
+
+                        ui.add_space(10.0);
+
+                        let test_btn =
+                            egui::Button::new(egui::RichText::new("🎥 Test devices").size(14.0))
+                                .fill(crate::ui::theme::colors::BACKGROUND_SECONDARY)
+                                .min_size(egui::vec2(180.0, 40.0));
+
+                        if ui.add(test_btn).clicked() {
+                            next_action = Some(LobbyAction::TestDevices);
                         }
-                        
+
                         ui.add_space(10.0);
-                        
+
                         // Debug/Error box in sidebar
                         if let Some(err) = &self.err_message {
-                            ui.colored_label(crate::ui::theme::colors::DANGER, format!("Error: {}", err));
+                            ui.colored_label(
+                                crate::ui::theme::colors::DANGER,
+                                format!("Error: {}", err),
+                            );
                         }
                     }
                 });
-                
+
                 ui.with_layout(egui::Layout::bottom_up(egui::Align::Center), |ui| {
-                   ui.add_space(20.0);
-                   if let Some(signaling) = signaling {
-                        let logout_btn = egui::Button::new(egui::RichText::new("� Log Out").size(14.0).color(egui::Color32::WHITE))
-                            .fill(crate::ui::theme::colors::DANGER)
-                            .rounding(4.0)
-                            .min_size(egui::vec2(180.0, 40.0));
+                    ui.add_space(20.0);
+                    if let Some(signaling) = signaling {
+                        let logout_btn = egui::Button::new(
+                            egui::RichText::new("� Log Out")
+                                .size(14.0)
+                                .color(egui::Color32::WHITE),
+                        )
+                        .fill(crate::ui::theme::colors::DANGER)
+                        .rounding(4.0)
+                        .min_size(egui::vec2(180.0, 40.0));
 
                         if ui.add(logout_btn).clicked() {
                             let _ = signaling.logout();
                             self.status_message = Some("Session closed".to_string());
                             next_action = Some(LobbyAction::Logout);
                         }
-                   }
+                    }
                 });
             });
 
         egui::CentralPanel::default().show(ctx, |ui| {
             ui.add_space(20.0);
-            ui.heading(egui::RichText::new("Active Users").size(28.0).strong().color(egui::Color32::WHITE));
-            ui.label(egui::RichText::new("Connect with peers in the room").color(crate::ui::theme::colors::TEXT_MUTED));
+            ui.heading(
+                egui::RichText::new("Active Users")
+                    .size(28.0)
+                    .strong()
+                    .color(egui::Color32::WHITE),
+            );
+            ui.label(
+                egui::RichText::new("Connect with peers in the room")
+                    .color(crate::ui::theme::colors::TEXT_MUTED),
+            );
             ui.add_space(30.0);
 
             if let Some(status) = &self.status_message {
-                 ui.colored_label(crate::ui::theme::colors::SUCCESS, status);
-                 ui.add_space(10.0);
+                ui.colored_label(crate::ui::theme::colors::SUCCESS, status);
+                ui.add_space(10.0);
             }
 
+            ui.collapsing("Call someone not in the list", |ui| {
+                ui.horizontal(|ui| {
+                    let input = ui.add(
+                        egui::TextEdit::singleline(&mut self.manual_target)
+                            .hint_text("Username")
+                            .desired_width(220.0),
+                    );
+                    let submitted =
+                        input.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                    if (ui.button("📞 Call").clicked() || submitted)
+                        && !self.manual_target.trim().is_empty()
+                    {
+                        let target = self.manual_target.trim().to_string();
+                        next_action = self.request_call(&target, current_user);
+                        if next_action.is_some() {
+                            self.manual_target.clear();
+                        }
+                    }
+                });
+            });
+            ui.add_space(10.0);
+
             // User list grid
             if self.users.is_empty() {
                 ui.centered_and_justified(|ui| {
-                   ui.label(egui::RichText::new("No other users found.\nTry clicking Refresh.").size(18.0).color(crate::ui::theme::colors::TEXT_MUTED)); 
+                    ui.label(
+                        egui::RichText::new("No other users found.\nTry clicking Refresh.")
+                            .size(18.0)
+                            .color(crate::ui::theme::colors::TEXT_MUTED),
+                    );
                 });
             } else {
+                if self.users.len() > FILTER_THRESHOLD {
+                    ui.horizontal(|ui| {
+                        ui.add(
+                            egui::TextEdit::singleline(&mut self.search)
+                                .hint_text("🔍 Search users")
+                                .desired_width(220.0),
+                        );
+                        ui.checkbox(&mut self.only_available, "Only available");
+                    });
+                    ui.add_space(10.0);
+                } else {
+                    // Sin buscador visible no debe quedar un filtro escondido
+                    self.search.clear();
+                    self.only_available = false;
+                }
+
+                let visible = self.visible_users();
+                if visible.is_empty() {
+                    ui.label(
+                        egui::RichText::new("No users match the filter.")
+                            .color(crate::ui::theme::colors::TEXT_MUTED),
+                    );
+                }
                 egui::ScrollArea::vertical().show(ui, |ui| {
                     ui.spacing_mut().item_spacing = egui::vec2(10.0, 10.0);
-                    
-                    for (user, status) in &self.users {
+
+                    for (user, status) in &visible {
+                        let status = Status::from_wire(status);
                         // Custom Card for each user
-                        egui::Frame::none()
+                        let card = egui::Frame::none()
                             .fill(crate::ui::theme::colors::BACKGROUND_SECONDARY)
                             .rounding(8.0)
                             .inner_margin(16.0)
                             .show(ui, |ui| {
                                 ui.horizontal(|ui| {
                                     // Status Dot
-                                    let dot_color = if status == "AVAILABLE" { crate::ui::theme::colors::SUCCESS } else { crate::ui::theme::colors::DANGER };
-                                    ui.painter().circle_filled(ui.cursor().min + egui::vec2(5.0, 10.0), 5.0, dot_color);
+                                    ui.painter().circle_filled(
+                                        ui.cursor().min + egui::vec2(5.0, 10.0),
+                                        5.0,
+                                        status.color(),
+                                    );
                                     ui.add_space(15.0);
-                                    
+
                                     ui.vertical(|ui| {
-                                        ui.label(egui::RichText::new(user).size(16.0).strong().color(egui::Color32::WHITE));
-                                        ui.label(egui::RichText::new(status).size(12.0).color(crate::ui::theme::colors::TEXT_MUTED));
-                                    });
-                                    
-                                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
-                                         if ui_status::Status::Connected.is_callable(user, current_user) && status == "AVAILABLE" {
-                                             let call_btn = egui::Button::new(egui::RichText::new("📞 Call").color(egui::Color32::WHITE))
-                                                .fill(crate::ui::theme::colors::SUCCESS)
-                                                .rounding(20.0)
-                                                .min_size(egui::vec2(80.0, 30.0));
-                                                
-                                             if ui.add(call_btn).clicked() {
-                                                 next_action = Some(LobbyAction::GoToWaitingCall(user.to_string()));
-                                             }
-                                         }
+                                        // Hacer click en el nombre también llama
+                                        let name = ui.add(
+                                            egui::Label::new(
+                                                egui::RichText::new(user)
+                                                    .size(16.0)
+                                                    .strong()
+                                                    .color(egui::Color32::WHITE),
+                                            )
+                                            .sense(egui::Sense::click()),
+                                        );
+                                        if status.is_callable(user, current_user) {
+                                            let name = name
+                                                .on_hover_cursor(egui::CursorIcon::PointingHand);
+                                            if name.clicked() {
+                                                next_action = self.request_call(user, current_user);
+                                            }
+                                        }
+                                        ui.horizontal(|ui| {
+                                            ui.label(
+                                                egui::RichText::new(status.label())
+                                                    .size(12.0)
+                                                    .color(status.color()),
+                                            );
+                                            if self.is_blocked(user) {
+                                                ui.label(
+                                                    egui::RichText::new("🚫 Blocked")
+                                                        .size(12.0)
+                                                        .color(crate::ui::theme::colors::DANGER),
+                                                );
+                                            }
+                                        });
                                     });
+
+                                    ui.with_layout(
+                                        egui::Layout::right_to_left(egui::Align::Center),
+                                        |ui| {
+                                            if Some(user.as_str()) == current_user {
+                                                ui.label(
+                                                    egui::RichText::new("You").color(
+                                                        crate::ui::theme::colors::TEXT_MUTED,
+                                                    ),
+                                                );
+                                                return;
+                                            }
+                                            let pending =
+                                                self.pending_call.as_deref() == Some(user.as_str());
+                                            let callable =
+                                                status.is_callable(user, current_user) && !pending;
+                                            let call_btn = egui::Button::new(
+                                                egui::RichText::new(if pending {
+                                                    "📞 Calling..."
+                                                } else {
+                                                    "📞 Call"
+                                                })
+                                                .color(egui::Color32::WHITE),
+                                            )
+                                            .fill(if callable {
+                                                crate::ui::theme::colors::SUCCESS
+                                            } else {
+                                                crate::ui::theme::colors::BACKGROUND_TERTIARY
+                                            })
+                                            .rounding(20.0)
+                                            .min_size(egui::vec2(80.0, 30.0));
+
+                                            let response = ui
+                                                .add_enabled(callable, call_btn)
+                                                .on_disabled_hover_text(if pending {
+                                                    format!("Already calling {}", user)
+                                                } else {
+                                                    format!(
+                                                        "{} is {}",
+                                                        user,
+                                                        status.label().to_lowercase()
+                                                    )
+                                                });
+                                            if response.clicked() {
+                                                next_action = self.request_call(user, current_user);
+                                            }
+                                        },
+                                    );
                                 });
+                            })
+                            .response;
+
+                        if Some(user.as_str()) != current_user
+                            && let Some(signaling) = signaling
+                        {
+                            let blocked = self.is_blocked(user);
+                            card.context_menu(|ui| {
+                                let label = if blocked { "Unblock" } else { "🚫 Block" };
+                                if ui.button(label).clicked() {
+                                    let _ = if blocked {
+                                        signaling.unblock(user)
+                                    } else {
+                                        signaling.block(user)
+                                    };
+                                    ui.close_menu();
+                                }
                             });
+                        }
+
+                        // Drop target: files dragged over a user card
+                        if Some(user.as_str()) != current_user && card.contains_pointer() {
+                            if dragging_files {
+                                ui.painter().rect_stroke(
+                                    card.rect,
+                                    8.0,
+                                    egui::Stroke::new(2.0, crate::ui::theme::colors::PRIMARY),
+                                );
+                            }
+                            if !dropped.is_empty() {
+                                if status.is_callable(user, current_user) {
+                                    self.err_message = None;
+                                    next_action = Some(LobbyAction::CallWithFiles(
+                                        user.to_string(),
+                                        dropped.clone(),
+                                    ));
+                                } else {
+                                    self.err_message = Some(format!(
+                                        "{} is {}",
+                                        user,
+                                        status.label().to_lowercase()
+                                    ));
+                                }
+                            }
+                        }
                     }
                 });
             }
@@ -157,11 +410,84 @@ impl LobbyScreen {
         next_action
     }
 
-    pub fn set_users(&mut self, users: Vec<(String, String)>) {
-        self.users = users;
+    /// Pedido de llamar a `user`, desde la lista o escrito a mano. No llama
+    /// a quien ya estamos llamando ni a quien la lista muestra ocupado o
+    /// desconectado; un nombre que no está en la lista se intenta igual y el
+    /// servidor dice si existe.
+    fn request_call(&mut self, user: &str, current_user: Option<&str>) -> Option<LobbyAction> {
+        if Some(user) == current_user {
+            self.err_message = Some("You can't call yourself".to_string());
+            return None;
+        }
+        if self.pending_call.as_deref() == Some(user) {
+            self.err_message = Some(format!("Already calling {}", user));
+            return None;
+        }
+        if let Some((_, status)) = self.users.iter().find(|(name, _)| name == user) {
+            let status = Status::from_wire(status);
+            if !status.is_callable(user, current_user) {
+                self.err_message = Some(format!("{} is {}", user, status.label().to_lowercase()));
+                return None;
+            }
+        }
+        self.err_message = None;
+        Some(LobbyAction::GoToWaitingCall(user.to_string()))
+    }
+
+    pub fn set_blocklist(&mut self, blocked: Vec<String>) {
+        self.blocked = blocked;
+    }
+
+    fn is_blocked(&self, user: &str) -> bool {
+        self.blocked.iter().any(|blocked| blocked == user)
+    }
+
+    /// Muestra un error en la barra lateral (p. ej. por qué se cortó una llamada).
+    pub fn set_error(&mut self, err: String) {
+        self.err_message = Some(err);
+    }
+
+    /// Aplica la lista completa del servidor. Los usuarios que ya estaban
+    /// quedan en su lugar y los nuevos van al final, así la lista no salta.
+    pub fn set_users(&mut self, users: Vec<UserEntry>) {
+        let mut merged: Vec<(String, String)> = self
+            .users
+            .iter()
+            .filter_map(|(name, _)| users.iter().find(|user| &user.username == name))
+            .map(|user| (user.username.clone(), user.status.clone()))
+            .collect();
+        for user in users {
+            if !merged.iter().any(|(name, _)| *name == user.username) {
+                merged.push((user.username, user.status));
+            }
+        }
+        self.users = merged;
         self.status_message = Some("Updated user list".to_string());
     }
 
+    /// Usuarios que pasan el buscador y el filtro de disponibles, en el
+    /// orden en que llegaron.
+    fn visible_users(&self) -> Vec<(String, String)> {
+        let search = self.search.trim().to_lowercase();
+        self.users
+            .iter()
+            .filter(|(user, _)| search.is_empty() || user.to_lowercase().contains(&search))
+            .filter(|(_, status)| {
+                !self.only_available || Status::from_wire(status) == Status::Connected
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Usuarios disponibles para llamar (o para transferirles una llamada).
+    pub fn available_users(&self, current_user: Option<&str>) -> Vec<String> {
+        self.users
+            .iter()
+            .filter(|(user, status)| Status::from_wire(status).is_callable(user, current_user))
+            .map(|(user, _)| user.clone())
+            .collect()
+    }
+
     pub fn update_user_status(&mut self, username: String, status: String) {
         if let Some(entry) = self.users.iter_mut().find(|(u, _)| u == &username) {
             entry.1 = status.clone();
@@ -170,4 +496,126 @@ impl LobbyScreen {
         }
         self.status_message = Some(format!("{} -> {}", username, status));
     }
+
+    pub fn user_joined(&mut self, username: String, status: String) {
+        self.update_user_status(username.clone(), status);
+        self.status_message = Some(format!("{} is online", username));
+    }
+
+    /// Sigue en la lista como desconectado, igual que en la que manda el
+    /// servidor.
+    pub fn user_left(&mut self, username: String) {
+        self.update_user_status(username.clone(), "DISCONNECTED".to_string());
+        self.status_message = Some(format!("{} went offline", username));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lobby() -> LobbyScreen {
+        let mut lobby = LobbyScreen::new();
+        lobby.set_users(entries(&[
+            ("alice", "AVAILABLE"),
+            ("Bob", "BUSY"),
+            ("bobby", "DISCONNECTED"),
+            ("carol", "AVAILABLE"),
+        ]));
+        lobby
+    }
+
+    fn entries(users: &[(&str, &str)]) -> Vec<UserEntry> {
+        users
+            .iter()
+            .map(|(username, status)| UserEntry {
+                username: username.to_string(),
+                status: status.to_string(),
+            })
+            .collect()
+    }
+
+    fn names(users: Vec<(String, String)>) -> Vec<String> {
+        users.into_iter().map(|(user, _)| user).collect()
+    }
+
+    #[test]
+    fn test_search_is_case_insensitive() {
+        let mut lobby = lobby();
+        lobby.search = " BOB".to_string();
+        assert_eq!(names(lobby.visible_users()), vec!["Bob", "bobby"]);
+    }
+
+    #[test]
+    fn test_only_available_hides_busy_and_offline() {
+        let mut lobby = lobby();
+        lobby.only_available = true;
+        assert_eq!(names(lobby.visible_users()), vec!["alice", "carol"]);
+
+        lobby.update_user_status("Bob".to_string(), "AVAILABLE".to_string());
+        assert_eq!(names(lobby.visible_users()), vec!["alice", "Bob", "carol"]);
+    }
+
+    fn requested(action: Option<LobbyAction>) -> Option<String> {
+        match action {
+            Some(LobbyAction::GoToWaitingCall(user)) => Some(user),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_call_requests_skip_pending_and_unavailable_users() {
+        let mut lobby = lobby();
+        assert_eq!(
+            requested(lobby.request_call("alice", Some("carol"))),
+            Some("alice".to_string())
+        );
+
+        // Mientras la llamada a alice sigue pendiente no se la vuelve a llamar
+        lobby.set_pending_call(Some("alice".to_string()));
+        assert_eq!(requested(lobby.request_call("alice", Some("carol"))), None);
+        assert_eq!(lobby.err_message.as_deref(), Some("Already calling alice"));
+
+        assert_eq!(requested(lobby.request_call("Bob", Some("carol"))), None);
+        assert_eq!(requested(lobby.request_call("carol", Some("carol"))), None);
+
+        // A mano se puede llamar a alguien que la lista todavía no trajo
+        assert_eq!(
+            requested(lobby.request_call("dave", Some("carol"))),
+            Some("dave".to_string())
+        );
+        assert_eq!(lobby.err_message, None);
+    }
+
+    #[test]
+    fn test_deltas_and_full_lists_keep_the_order() {
+        let mut lobby = lobby();
+        lobby.user_joined("dave".to_string(), "AVAILABLE".to_string());
+        lobby.user_left("alice".to_string());
+        assert_eq!(
+            names(lobby.visible_users()),
+            vec!["alice", "Bob", "bobby", "carol", "dave"]
+        );
+        assert_eq!(lobby.available_users(None), vec!["carol", "dave"]);
+
+        // Una lista completa en otro orden no mueve a los que ya estaban
+        lobby.set_users(entries(&[
+            ("Bob", "AVAILABLE"),
+            ("alice", "DISCONNECTED"),
+            ("carol", "BUSY"),
+            ("dave", "AVAILABLE"),
+            ("erin", "AVAILABLE"),
+        ]));
+        assert_eq!(
+            names(lobby.visible_users()),
+            vec!["alice", "Bob", "carol", "dave", "erin"]
+        );
+        assert_eq!(lobby.available_users(None), vec!["Bob", "dave", "erin"]);
+    }
+
+    #[test]
+    fn test_available_users_skip_current_user() {
+        let lobby = lobby();
+        assert_eq!(lobby.available_users(Some("alice")), vec!["carol"]);
+    }
 }
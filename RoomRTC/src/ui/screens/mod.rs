@@ -1,6 +1,8 @@
+pub mod device_test;
 pub mod join_meet;
 pub mod lobby;
 pub mod login;
+pub mod settings;
 pub mod status_utils;
 pub mod video;
-pub mod waiting_call;
\ No newline at end of file
+pub mod waiting_call;
@@ -0,0 +1,316 @@
+use crate::client::settings::{PreferredStatus, Theme, UserSettings};
+use crate::ui::theme::colors;
+use eframe::egui::{self, RichText};
+use room_rtc::audio::audio_capture::input_device_names;
+use room_rtc::audio::audio_playback::output_device_names;
+
+/// Resoluciones que se ofrecen en el combo; otras siguen valiendo si vienen
+/// del archivo.
+const RESOLUTIONS: [(u32, u32); 4] = [(320, 240), (640, 480), (1280, 720), (1920, 1080)];
+const MAX_CAMERA_INDEX: i32 = 9;
+// Fuente sintética: barras de colores con el número de frame
+const TEST_PATTERN: &str = "Test Pattern";
+
+pub enum SettingsAction {
+    Save(UserSettings),
+    Cancel,
+    /// Abre la prueba de dispositivos con lo que se está editando.
+    TestDevices(UserSettings),
+    // Se aplica enseguida en el servidor, no espera a Save
+    Unblock(String),
+}
+
+pub struct SettingsScreen {
+    draft: UserSettings,
+    saved_server: String,
+    input_devices: Vec<String>,
+    output_devices: Vec<String>,
+    // Lo último que mandó el servidor en BLOCKLIST
+    blocked: Vec<String>,
+    pub status_message: Option<String>,
+}
+
+impl SettingsScreen {
+    pub fn new(settings: UserSettings) -> Self {
+        Self {
+            saved_server: settings.server_addr.clone(),
+            draft: settings,
+            input_devices: Vec::new(),
+            output_devices: Vec::new(),
+            blocked: Vec::new(),
+            status_message: None,
+        }
+    }
+
+    /// Arranca la edición desde las preferencias guardadas y vuelve a listar
+    /// los dispositivos, por si se conectó alguno.
+    pub fn open(&mut self, settings: UserSettings) {
+        self.saved_server = settings.server_addr.clone();
+        self.draft = settings;
+        self.input_devices = input_device_names();
+        self.output_devices = output_device_names();
+        self.status_message = None;
+    }
+
+    /// Vuelve de la prueba de dispositivos con lo que se eligió allí, sin
+    /// tocar el resto de lo editado.
+    pub fn set_devices(&mut self, devices: &UserSettings) {
+        self.draft.copy_devices_from(devices);
+    }
+
+    pub fn set_blocklist(&mut self, blocked: Vec<String>) {
+        self.blocked = blocked;
+    }
+
+    fn server_changed(&self) -> bool {
+        self.draft.server_addr.trim() != self.saved_server
+    }
+
+    pub fn update(&mut self, ctx: &egui::Context) -> Option<SettingsAction> {
+        let mut action = None;
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.add_space(20.0);
+            ui.heading(
+                RichText::new("Settings")
+                    .size(28.0)
+                    .strong()
+                    .color(egui::Color32::WHITE),
+            );
+            ui.label(
+                RichText::new("Video and audio changes apply from the next call")
+                    .color(colors::TEXT_MUTED),
+            );
+            ui.add_space(20.0);
+
+            egui::Grid::new("settings_grid")
+                .num_columns(2)
+                .spacing([20.0, 12.0])
+                .show(ui, |ui| {
+                    ui.label("Camera");
+                    camera_picker(ui, "settings_camera", &mut self.draft);
+                    ui.end_row();
+
+                    ui.label("Microphone");
+                    device_combo(
+                        ui,
+                        "settings_input",
+                        &mut self.draft.input_device,
+                        &self.input_devices,
+                    );
+                    ui.end_row();
+
+                    ui.label("Speakers");
+                    device_combo(
+                        ui,
+                        "settings_output",
+                        &mut self.draft.output_device,
+                        &self.output_devices,
+                    );
+                    ui.end_row();
+
+                    ui.label("Resolution");
+                    let video = &mut self.draft.video;
+                    egui::ComboBox::from_id_salt("settings_resolution")
+                        .selected_text(format!("{}x{}", video.width, video.height))
+                        .show_ui(ui, |ui| {
+                            for (w, h) in RESOLUTIONS {
+                                let selected = video.width == w && video.height == h;
+                                if ui
+                                    .selectable_label(selected, format!("{}x{}", w, h))
+                                    .clicked()
+                                {
+                                    video.width = w;
+                                    video.height = h;
+                                }
+                            }
+                        });
+                    ui.end_row();
+
+                    ui.label("Frame rate");
+                    ui.add(
+                        egui::DragValue::new(&mut video.fps)
+                            .range(5..=60)
+                            .suffix(" fps"),
+                    );
+                    ui.end_row();
+
+                    ui.label("Bitrate cap");
+                    ui.horizontal(|ui| {
+                        let mut capped = video.max_bitrate_kbps.is_some();
+                        if ui.checkbox(&mut capped, "").changed() {
+                            video.max_bitrate_kbps = capped.then(|| video.bandwidth_kbps());
+                        }
+                        if let Some(kbps) = video.max_bitrate_kbps.as_mut() {
+                            ui.add(egui::DragValue::new(kbps).range(64..=8000).suffix(" kbps"));
+                        } else {
+                            ui.label(RichText::new("Automatic").color(colors::TEXT_MUTED));
+                        }
+                    });
+                    ui.end_row();
+
+                    ui.label("Total bandwidth cap")
+                        .on_hover_text("Audio and video together, for metered or slow uplinks");
+                    ui.horizontal(|ui| {
+                        let draft = &mut self.draft;
+                        let mut capped = draft.max_bitrate_kbps.is_some();
+                        if ui.checkbox(&mut capped, "").changed() {
+                            draft.max_bitrate_kbps = capped.then(|| draft.video.bandwidth_kbps());
+                        }
+                        if let Some(kbps) = draft.max_bitrate_kbps.as_mut() {
+                            ui.add(
+                                egui::DragValue::new(kbps)
+                                    .range(100..=20000)
+                                    .suffix(" kbps"),
+                            );
+                        } else {
+                            ui.label(RichText::new("Unlimited").color(colors::TEXT_MUTED));
+                        }
+                    });
+                    ui.end_row();
+
+                    ui.label("Status");
+                    egui::ComboBox::from_id_salt("settings_status")
+                        .selected_text(self.draft.preferred_status.label())
+                        .show_ui(ui, |ui| {
+                            for status in PreferredStatus::ALL {
+                                ui.selectable_value(
+                                    &mut self.draft.preferred_status,
+                                    status,
+                                    status.label(),
+                                );
+                            }
+                        });
+                    ui.end_row();
+
+                    ui.label("Theme");
+                    egui::ComboBox::from_id_salt("settings_theme")
+                        .selected_text(self.draft.theme.label())
+                        .show_ui(ui, |ui| {
+                            for theme in Theme::ALL {
+                                ui.selectable_value(&mut self.draft.theme, theme, theme.label());
+                            }
+                        });
+                    ui.end_row();
+
+                    ui.label("Notifications");
+                    ui.checkbox(
+                        &mut self.draft.notifications,
+                        "Notify calls and file transfers while in the background",
+                    );
+                    ui.end_row();
+
+                    ui.label("Server");
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.draft.server_addr)
+                            .desired_width(220.0),
+                    );
+                    ui.end_row();
+                });
+
+            ui.add_space(20.0);
+            ui.label(RichText::new("Blocked users").strong());
+            if self.blocked.is_empty() {
+                ui.label(
+                    RichText::new("Nobody. Right-click a user in the lobby to block them.")
+                        .color(colors::TEXT_MUTED),
+                );
+            }
+            for user in &self.blocked {
+                ui.horizontal(|ui| {
+                    ui.label(user);
+                    if ui.small_button("Unblock").clicked() {
+                        action = Some(SettingsAction::Unblock(user.clone()));
+                    }
+                });
+            }
+
+            if self.server_changed() {
+                ui.add_space(10.0);
+                ui.colored_label(
+                    colors::WARNING,
+                    "Changing the server closes this session, you will need to log in again",
+                );
+            }
+            if let Some(msg) = &self.status_message {
+                ui.add_space(10.0);
+                ui.colored_label(colors::DANGER, msg);
+            }
+
+            ui.add_space(20.0);
+            ui.horizontal(|ui| {
+                let save = egui::Button::new(RichText::new("Save").color(egui::Color32::WHITE))
+                    .fill(colors::PRIMARY)
+                    .min_size(egui::vec2(120.0, 36.0));
+                if ui.add(save).clicked() {
+                    let mut settings = self.draft.clone();
+                    settings.server_addr = settings.server_addr.trim().to_string();
+                    if settings.server_addr.is_empty() {
+                        self.status_message = Some("Server address cannot be empty".into());
+                    } else {
+                        action = Some(SettingsAction::Save(settings));
+                    }
+                }
+                if ui
+                    .add(egui::Button::new("Cancel").min_size(egui::vec2(120.0, 36.0)))
+                    .clicked()
+                {
+                    action = Some(SettingsAction::Cancel);
+                }
+                if ui
+                    .add(egui::Button::new("Test devices").min_size(egui::vec2(120.0, 36.0)))
+                    .clicked()
+                {
+                    action = Some(SettingsAction::TestDevices(self.draft.clone()));
+                }
+            });
+        });
+
+        action
+    }
+}
+
+/// Cámara por índice o patrón de prueba.
+pub(crate) fn camera_picker(ui: &mut egui::Ui, id: &str, settings: &mut UserSettings) {
+    ui.horizontal(|ui| {
+        let source = if settings.test_pattern {
+            TEST_PATTERN
+        } else {
+            "Camera"
+        };
+        egui::ComboBox::from_id_salt(id)
+            .selected_text(source)
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut settings.test_pattern, false, "Camera");
+                ui.selectable_value(&mut settings.test_pattern, true, TEST_PATTERN);
+            });
+        if !settings.test_pattern {
+            ui.add(
+                egui::DragValue::new(&mut settings.camera_index)
+                    .range(0..=MAX_CAMERA_INDEX)
+                    .prefix("#"),
+            );
+        }
+    });
+}
+
+/// Combo con "System default" más los dispositivos detectados. Uno guardado
+/// que ya no aparece se sigue mostrando para no perderlo sin querer.
+pub(crate) fn device_combo(
+    ui: &mut egui::Ui,
+    id: &str,
+    selected: &mut Option<String>,
+    devices: &[String],
+) {
+    let text = selected.as_deref().unwrap_or("System default").to_string();
+    egui::ComboBox::from_id_salt(id)
+        .selected_text(text)
+        .width(260.0)
+        .show_ui(ui, |ui| {
+            ui.selectable_value(selected, None, "System default");
+            let missing = selected.clone().filter(|name| !devices.contains(name));
+            for name in devices.iter().chain(missing.iter()) {
+                ui.selectable_value(selected, Some(name.clone()), name);
+            }
+        });
+}
@@ -0,0 +1,84 @@
+//! Progreso de la conexión con el peer, con un tope de tiempo, para las
+//! pantallas que esperan a que se establezca.
+
+use crate::client::webrtc_service::CallProgress;
+use eframe::egui::{self, RichText};
+use std::time::{Duration, Instant};
+
+pub enum ConnectionProgressAction {
+    Retry,
+    BackToLobby,
+}
+
+pub struct ConnectionProgress {
+    timeout: Duration,
+    since: Option<Instant>,
+}
+
+impl ConnectionProgress {
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            since: None,
+        }
+    }
+
+    /// Empieza (o vuelve a empezar) a contar el intento de conexión.
+    pub fn start(&mut self) {
+        self.since = Some(Instant::now());
+    }
+
+    pub fn stop(&mut self) {
+        self.since = None;
+    }
+
+    /// Pasó el tope sin que la conexión quedara lista.
+    pub fn timed_out(&self, progress: &CallProgress) -> bool {
+        *progress != CallProgress::Connected
+            && self
+                .since
+                .is_some_and(|since| since.elapsed() >= self.timeout)
+    }
+
+    /// Fase actual y barra de progreso; pasado el tope, el fallo con las
+    /// opciones de reintentar o volver al lobby. Sin intento en curso no
+    /// muestra nada.
+    pub fn show(
+        &self,
+        ui: &mut egui::Ui,
+        progress: &CallProgress,
+    ) -> Option<ConnectionProgressAction> {
+        let since = self.since?;
+        let mut action = None;
+        if self.timed_out(progress) {
+            ui.colored_label(
+                crate::ui::theme::colors::DANGER,
+                format!(
+                    "Could not connect within {} s (stuck at: {})",
+                    self.timeout.as_secs(),
+                    progress
+                ),
+            );
+            ui.horizontal(|ui| {
+                if ui.button("Retry").clicked() {
+                    action = Some(ConnectionProgressAction::Retry);
+                }
+                if ui.button("Back to lobby").clicked() {
+                    action = Some(ConnectionProgressAction::BackToLobby);
+                }
+            });
+        } else {
+            ui.label(
+                RichText::new(progress.to_string()).color(crate::ui::theme::colors::TEXT_PRIMARY),
+            );
+            ui.add(
+                egui::ProgressBar::new(progress.fraction())
+                    .desired_width(300.0)
+                    .text(format!("{} s", since.elapsed().as_secs())),
+            );
+            // Para que avance el contador aunque no haya otros eventos
+            ui.ctx().request_repaint_after(Duration::from_millis(500));
+        }
+        action
+    }
+}
@@ -1 +1,2 @@
+pub mod connection_progress;
 pub mod ui_status;
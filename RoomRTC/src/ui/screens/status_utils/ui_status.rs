@@ -2,6 +2,7 @@ use crate::ui::screens::lobby::LobbyAction;
 use crate::ui::screens::status_utils::ui_status::egui::Button;
 use eframe::egui;
 use eframe::egui::{Color32, Stroke, Vec2};
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Status {
     Connected,
     Disconnected,
@@ -9,11 +10,29 @@ pub enum Status {
 }
 
 impl Status {
-    fn color(&self) -> Color32 {
+    /// Estado tal como llega en USER_LIST / USER_STATUS_CHANGED. Lo
+    /// desconocido se muestra como desconectado.
+    pub fn from_wire(status: &str) -> Self {
+        match status {
+            "AVAILABLE" => Status::Connected,
+            "BUSY" => Status::Busy,
+            _ => Status::Disconnected,
+        }
+    }
+
+    pub fn color(&self) -> Color32 {
+        match self {
+            Status::Connected => crate::ui::theme::colors::SUCCESS,
+            Status::Disconnected => crate::ui::theme::colors::TEXT_MUTED,
+            Status::Busy => crate::ui::theme::colors::WARNING,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
         match self {
-            Status::Connected => Color32::GREEN,
-            Status::Disconnected => Color32::GRAY,
-            Status::Busy => Color32::RED,
+            Status::Connected => "Available",
+            Status::Disconnected => "Offline",
+            Status::Busy => "In a call",
         }
     }
 
@@ -29,12 +48,7 @@ pub fn user_row(
     current_user: Option<&str>,
 ) -> Option<LobbyAction> {
     //DEBUG: println!("Drawing user: {} with status: {}", name, status);
-    let status = match status {
-        "AVAILABLE" => Status::Connected,
-        "DISCONNECTED" => Status::Disconnected,
-        "BUSY" => Status::Busy,
-        _ => Status::Disconnected,
-    };
+    let status = Status::from_wire(status);
     let mut action = None;
     ui.horizontal(|ui| {
         ui.add_space(20.0);
@@ -69,17 +83,22 @@ pub fn user_row(
 
         ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
             if status.is_callable(name, current_user) {
-                let call_btn = Button::new(egui::RichText::new("📷 Call").color(egui::Color32::WHITE))
-                    .fill(crate::ui::theme::colors::SUCCESS)
-                    .rounding(4.0)
-                    .min_size(Vec2::new(60.0, 24.0));
-                    
+                let call_btn =
+                    Button::new(egui::RichText::new("📷 Call").color(egui::Color32::WHITE))
+                        .fill(crate::ui::theme::colors::SUCCESS)
+                        .rounding(4.0)
+                        .min_size(Vec2::new(60.0, 24.0));
+
                 let res_call_btn = ui.add(call_btn);
                 if res_call_btn.clicked() {
                     action = Some(LobbyAction::GoToWaitingCall(name.to_string()));
                 }
             } else {
-                ui.label(egui::RichText::new("Busy/Offline").size(10.0).color(crate::ui::theme::colors::TEXT_MUTED));
+                ui.label(
+                    egui::RichText::new("Busy/Offline")
+                        .size(10.0)
+                        .color(crate::ui::theme::colors::TEXT_MUTED),
+                );
             }
         });
     });
@@ -1,27 +1,65 @@
-use crate::client::p2p_client::P2PClient;
+use crate::client::call_controller::CallController;
+use crate::client::capabilities::PeerCapabilities;
+use crate::client::device_test::meter_fraction;
+use crate::client::identity::{KeyCheck, check_pinned_key};
+use crate::client::message_inbox::SharedInbox;
+use crate::client::notifier::Notification;
+use crate::client::p2p_client::{DataChannel, P2PClient, SCTP_STREAM_CLOSED_NOTICE};
+use crate::client::reconnect::{RECONNECT_WINDOW, Reconnect, ReconnectStep};
+use crate::client::settings::UserSettings;
+use crate::client::snapshot;
+use crate::client::storage;
+use crate::client::thumbnail;
+use crate::client::transfer_rate::TransferRate;
+use crate::client::webrtc_service::{
+    CallSignaling, apply_connection_events, handle_ice_restart, restart_ice,
+};
+use crate::ui::screens::join_meet::DEFAULT_RING_TIMEOUT;
 use eframe::egui::load::SizedTexture;
 use eframe::egui::{
-    self, Align2, Button, Color32, ColorImage, FontId, TextureHandle, TextureOptions, Vec2, RichText,
+    self, Align2, Button, Color32, ColorImage, FontId, RichText, TextureHandle, TextureOptions,
+    Vec2,
 };
 use opencv::core::Mat;
 use opencv::prelude::*;
-use room_rtc::worker_thread::media_metrics::CallMetricsSnapshot;
-use room_rtc::worker_thread::worker_audio::WorkerAudio;
-use room_rtc::worker_thread::worker_media::VideoParams;
-use std::sync::mpsc::{self, Receiver};
-use std::sync::{Arc, Mutex};
-use std::thread;
-use std::io::Write;
 use rfd::FileDialog;
-use room_rtc::protocols::file_transfer::FileTransferMessage;
+use room_rtc::audio::gain::MAX_OUTPUT_GAIN;
+use room_rtc::audio::level::AudioLevel;
+use room_rtc::camera::video_format::VideoFormat;
+use room_rtc::protocols::file_transfer::{
+    FILE_CONTROL_STREAM, FileTransferMessage, MAX_THUMBNAIL_BYTES, data_stream_for,
+    detect_mime_type, is_data_stream, is_image_mime, transfer_id,
+};
+use room_rtc::protocols::media_control::{MEDIA_CONTROL_STREAM, MediaControlMessage};
+use room_rtc::protocols::sdp::media_type::MediaType;
+use room_rtc::rtc::rtc_peer_connection::{PeerConnectionError, PeerConnectionRole};
+use room_rtc::rtc::rtc_sctp::{SctpError, SctpStreamStats};
+use room_rtc::worker_thread::media_metrics::{CallMetricsSnapshot, QualityLevel};
+use room_rtc::worker_thread::worker_audio::{AudioParams, WorkerAudio};
+use room_rtc::worker_thread::worker_media::VideoParams;
+use room_rtc::{log_debug, log_info, log_warn};
+use std::collections::{BTreeMap, VecDeque};
 use std::fs::File;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 struct IncomingFile {
     name: String,
     size: usize,
+    mime_type: String,
     received_bytes: usize,
     file_handle: Option<File>,
     path: Option<std::path::PathBuf>,
+    rate: TransferRate,
+    /// Último chunk recibido (o el accept); sin datos por un rato se aborta.
+    last_chunk_at: Instant,
+    /// Llegó el Eof antes que los últimos chunks: se completa con ellos.
+    eof: bool,
 }
 
 struct OutgoingFile {
@@ -29,43 +67,220 @@ struct OutgoingFile {
     total_size: usize,
     sent_bytes: usize,
     path: std::path::PathBuf,
+    mime_type: String,
+    /// Miniatura JPEG que va en el offer, solo para imágenes.
+    thumbnail: Option<Vec<u8>>,
+    rate: TransferRate,
+    /// Velocidad de lo que el peer ya confirmó, que es la efectiva.
+    acked_rate: TransferRate,
+    /// Contadores SCTP del stream, tomados en cada frame mientras se manda.
+    flow: Option<SctpStreamStats>,
+    /// Lo prende la UI para frenar el hilo que manda los chunks.
+    cancel: Arc<AtomicBool>,
+    /// El otro lado aceptó el offer y el hilo ya está mandando.
+    accepted: bool,
+}
+
+/// Resultado de una transferencia terminada, para el listado de la UI.
+enum TransferOutcome {
+    Completed,
+    Rejected,
+    /// Con el motivo, que la UI muestra en la fila.
+    Failed(String),
+    /// El otro lado reseteó el stream a mitad de camino.
+    Cancelled,
+}
+
+/// Quién puso la llamada en espera.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Hold {
+    /// Nosotros: cámara y micrófono pausados.
+    Local,
+    /// El peer; su media está pausada del otro lado.
+    Remote,
+}
+
+struct FinishedTransfer {
+    name: String,
+    outgoing: bool,
+    outcome: TransferOutcome,
+    /// Imagen recibida: la UI ofrece abrirla.
+    open_path: Option<std::path::PathBuf>,
+    /// Envío fallido: la UI ofrece mandarlo de nuevo.
+    retry_path: Option<std::path::PathBuf>,
+}
+
+/// Lo que el hilo que manda un archivo le avisa a la UI.
+enum SenderEvent {
+    /// Bytes mandados desde el aviso anterior.
+    Progress(u64),
+    Failed(String),
+    Completed,
+    /// El peer reseteó el stream mientras mandábamos.
+    CancelledByPeer,
+}
+
+/// Llamada que entró durante esta y espera que el usuario la atienda o la
+/// rechace; la actual sigue mientras tanto.
+pub struct CallWaiting {
+    pub from: String,
+    pub sdp: String,
+    pub caps: PeerCapabilities,
+    since: Instant,
+}
+
+/// Offer del peer esperando que el usuario lo acepte o rechace.
+struct PendingOffer {
+    id: u64,
+    name: String,
+    size: usize,
+    mime_type: String,
+    /// Miniatura ya decodificada; se sube como textura al mostrarla.
+    preview: Option<ColorImage>,
+    texture: Option<TextureHandle>,
+    /// Desde cuándo está en el popup; el plazo para contestar corre desde ahí.
+    shown_at: Option<std::time::Instant>,
+}
+
+// Stream interno (no sale por la red) con el path del archivo elegido
+const LOCAL_FILE_SELECTED_STREAM: u16 = 999;
+// Sin chunks por este tiempo, una transferencia entrante se aborta
+const INCOMING_STALL_TIMEOUT: Duration = Duration::from_secs(30);
+// Con el buffer SCTP lleno por este tiempo, el envío se da por trabado
+const OUTGOING_STALL_TIMEOUT: Duration = Duration::from_secs(200);
+// Cuántas transferencias terminadas se muestran
+const FINISHED_TRANSFERS_SHOWN: usize = 5;
+const TRANSFER_PANEL_WIDTH: f32 = 280.0;
+// Lado de la miniatura en el popup de un offer
+const THUMBNAIL_SHOWN_SIDE: f32 = 96.0;
+// Bytes del principio del archivo que alcanzan para reconocer su tipo
+const MIME_SNIFF_BYTES: usize = 16;
+// Pico a partir del cual el medidor de audio avisa que satura
+const CLIPPING_LEVEL: f32 = 0.98;
+
+/// Which end of a file transfer is waiting for progress.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TransferSide {
+    Sending,
+    Receiving,
+}
+
+/// Whether a transfer that made no progress since `last_progress` has
+/// stalled by `now`. The sender waits longer: a full SCTP buffer may just be
+/// a slow link, while a receiver without chunks means the sender is gone.
+fn is_stalled(side: TransferSide, last_progress: Instant, now: Instant) -> bool {
+    let timeout = match side {
+        TransferSide::Sending => OUTGOING_STALL_TIMEOUT,
+        TransferSide::Receiving => INCOMING_STALL_TIMEOUT,
+    };
+    now.saturating_duration_since(last_progress) > timeout
+}
+
+/// Si el peer mandó video o audio hace menos de 2 s. El audio cuenta aunque
+/// haya video: sin cámara de alguno de los dos lados solo llega audio.
+fn peer_recently_seen(
+    since_last_video_ms: Option<u32>,
+    since_last_audio: Option<Duration>,
+) -> bool {
+    since_last_video_ms.is_some_and(|ms| ms < 2_000)
+        || since_last_audio.is_some_and(|since| since.as_millis() < 2_000)
 }
 
 pub enum VideoMeetAction {
     GoToLobby,
+    // ICE/DTLS falló o se cerró la conexión: volver al lobby con el motivo
+    ConnectionFailed(String),
+    // Pasar la llamada actual a otro usuario
+    Transfer(String),
+    // Sin media hace rato: reiniciar ICE con el peer por la señalización
+    RestartIce,
+    // Atender la llamada en espera, cortando la actual
+    AcceptWaitingCall,
+    // Rechazar la llamada en espera de ese usuario
+    RejectWaitingCall(String),
+    // La llamada en espera sonó todo el timeout sin que se atendiera
+    MissedWaitingCall(String),
+    // El usuario comparó el número de seguridad: fijar el certificado del peer
+    PinKey { peer: String, fingerprint: String },
 }
 pub struct VideoCall {
     client: Option<P2PClient>,
     local_texture: Option<TextureHandle>,
     remote_texture: Option<TextureHandle>,
+    // Ya se intentó arrancar el video; con o sin él, la llamada sigue
     media_started: bool,
+    // La cámara o el encoder no arrancaron: la llamada sigue solo con audio
+    video_unavailable: bool,
     status_message: Option<String>,
-    message_inbox: Option<Arc<Mutex<Vec<String>>>>,
-    processed_messages: usize,
+    message_inbox: Option<SharedInbox>,
+    processed_messages: u64,
     quality_metrics: Option<CallMetricsSnapshot>,
     peer_username: Option<String>,
     video: VideoParams,
+    audio: AudioParams,
+    camera_index: i32,
+    fake_camera: bool,
+    // Tope total de envío de Settings, para la próxima llamada
+    bandwidth_cap_kbps: Option<u64>,
     media_loader: Option<Receiver<Result<P2PClient, (P2PClient, String)>>>,
     unstable: bool,
+    // Última banda de calidad medida, para loguear los cambios
+    quality_level: Option<QualityLevel>,
+    // Fingerprint DTLS verificado de cada contacto, de Settings
+    pinned_keys: BTreeMap<String, String>,
+    // Certificado del peer frente al fijado; se calcula una vez por llamada
+    key_check: Option<KeyCheck>,
+    remote_fingerprint: Option<String>,
+    safety_number: Option<String>,
+    show_security: bool,
     last_remote_seen: Option<std::time::Instant>,
+    reconnect: Reconnect,
     audio_started: bool,
     audio_worker: Option<WorkerAudio>,
+    // Volumen del peer, se mantiene entre llamadas
+    output_gain: f32,
+    output_muted: bool,
+    hold: Option<Hold>,
+    // Para devolver el micrófono como estaba al retomar
+    muted_before_hold: bool,
     show_stats: bool,
-    
-    // File Transfer
+
+    // File Transfer, keyed by transfer id
     sctp_rx: Option<Receiver<(u16, Vec<u8>)>>,
-    incoming_file: Option<IncomingFile>,
-    outgoing_file: Option<OutgoingFile>,
-    pending_offer: Option<(String, usize)>, // (name, size) waiting for user decision
+    // Progress and outcome of the sender threads
+    sender_tx: Sender<(u64, SenderEvent)>,
+    sender_rx: Receiver<(u64, SenderEvent)>,
+    incoming_files: BTreeMap<u64, IncomingFile>,
+    outgoing_files: BTreeMap<u64, OutgoingFile>, // offered or being sent
+    outgoing_queue: VecDeque<(u64, OutgoingFile)>, // waiting for a free slot
+    finished_transfers: Vec<FinishedTransfer>,
+    pending_offers: VecDeque<PendingOffer>, // waiting for user decision
+    next_transfer_seq: u64,
+    max_concurrent_transfers: usize,
+    // Without an answer by then the offer is rejected, so the sender moves on
+    file_offer_timeout: std::time::Duration,
+    // Dropped files (here or on a lobby user) waiting for the data channel
+    files_on_connect: Vec<std::path::PathBuf>,
+    max_file_size: u64,
+    // Offers and finished transfers for the desktop notifications
+    notifications: Vec<Notification>,
+    call_waiting: Option<CallWaiting>,
+    ring_timeout: Duration,
+    // Último frame del peer, para las capturas
+    last_remote_frame: Option<Mat>,
+    // Captura en curso: el diálogo de guardado y la escritura van en otro hilo
+    snapshot_rx: Option<Receiver<Result<PathBuf, String>>>,
 }
 
 impl VideoCall {
-    pub fn new(video: VideoParams) -> Self {
+    pub fn new(video: VideoParams, max_file_size: u64) -> Self {
+        let (sender_tx, sender_rx) = mpsc::channel();
         Self {
             client: None,
             local_texture: None,
             remote_texture: None,
             media_started: false,
+            video_unavailable: false,
             status_message: None,
             message_inbox: None,
             processed_messages: 0,
@@ -74,31 +289,102 @@ impl VideoCall {
             video,
             media_loader: None,
             unstable: false,
+            quality_level: None,
+            pinned_keys: BTreeMap::new(),
+            key_check: None,
+            remote_fingerprint: None,
+            safety_number: None,
+            show_security: false,
             last_remote_seen: None,
+            reconnect: Reconnect::default(),
             audio_started: false,
             audio_worker: None,
+            output_gain: 1.0,
+            output_muted: false,
+            hold: None,
+            muted_before_hold: false,
             show_stats: false,
             sctp_rx: None,
-            incoming_file: None,
-            outgoing_file: None,
-            pending_offer: None,
+            sender_tx,
+            sender_rx,
+            incoming_files: BTreeMap::new(),
+            outgoing_files: BTreeMap::new(),
+            outgoing_queue: VecDeque::new(),
+            finished_transfers: Vec::new(),
+            pending_offers: VecDeque::new(),
+            next_transfer_seq: 0,
+            max_concurrent_transfers: 1,
+            file_offer_timeout: std::time::Duration::from_secs(60),
+            files_on_connect: Vec::new(),
+            notifications: Vec::new(),
+            call_waiting: None,
+            ring_timeout: DEFAULT_RING_TIMEOUT,
+            last_remote_frame: None,
+            snapshot_rx: None,
+            max_file_size,
+            audio: AudioParams::default(),
+            camera_index: 0,
+            fake_camera: false,
+            bandwidth_cap_kbps: None,
         }
     }
 
+    /// Cámara, video y dispositivos de audio elegidos en Settings. Se usan
+    /// desde la próxima llamada: la actual sigue con los que arrancó.
+    pub fn apply_settings(&mut self, settings: &UserSettings) {
+        self.camera_index = settings.camera_index;
+        self.fake_camera = settings.test_pattern;
+        self.video = settings.video;
+        self.bandwidth_cap_kbps = settings.max_bitrate_kbps;
+        self.audio.input_device = settings.input_device.clone();
+        self.audio.output_device = settings.output_device.clone();
+        self.pinned_keys = settings.pinned_keys.clone();
+    }
+
+    /// Bitrate y FEC de Opus para el audio saliente.
+    pub fn with_audio_params(mut self, audio: AudioParams) -> Self {
+        self.audio = audio;
+        self
+    }
+
+    /// Cuántos archivos se mandan a la vez; los demás quedan en cola.
+    pub fn with_max_concurrent_transfers(mut self, max: usize) -> Self {
+        self.max_concurrent_transfers = max.max(1);
+        self
+    }
+
+    /// Plazo para aceptar o rechazar un archivo entrante; vencido, se rechaza.
+    pub fn with_file_offer_timeout(mut self, timeout: std::time::Duration) -> Self {
+        self.file_offer_timeout = timeout;
+        self
+    }
+
+    /// Cuánto suena una llamada en espera antes de rechazarla sola.
+    pub fn with_ring_timeout(mut self, ring_timeout: Duration) -> Self {
+        self.ring_timeout = ring_timeout;
+        self
+    }
+
+    /// Archivos a ofrecer apenas se abra el canal de datos de la llamada.
+    pub fn send_files_on_connect(&mut self, paths: Vec<std::path::PathBuf>) {
+        self.files_on_connect.extend(paths);
+    }
+
     pub fn set_client(
         &mut self,
         client: P2PClient,
-        inbox: Arc<Mutex<Vec<String>>>,
+        inbox: SharedInbox,
         peer_username: Option<String>,
     ) {
         self.client = Some(client);
         self.local_texture = None;
         self.remote_texture = None;
         self.media_started = false;
+        self.video_unavailable = false;
         self.status_message = None;
         self.processed_messages = {
             if let Ok(guard) = inbox.lock() {
-                guard.len()
+                guard.total()
             } else {
                 0
             }
@@ -107,7 +393,11 @@ impl VideoCall {
         self.peer_username = peer_username.clone();
         self.media_loader = None;
         self.unstable = false;
+        self.quality_level = None;
+        self.clear_peer_key();
         self.last_remote_seen = Some(std::time::Instant::now());
+        self.reconnect.reset();
+        self.last_remote_frame = None;
     }
 
     pub fn reset(&mut self) {
@@ -116,6 +406,7 @@ impl VideoCall {
         self.local_texture = None;
         self.remote_texture = None;
         self.media_started = false;
+        self.video_unavailable = false;
         self.audio_started = false;
         self.audio_worker = None;
         self.status_message = None;
@@ -125,24 +416,40 @@ impl VideoCall {
         self.peer_username = None;
         self.media_loader = None;
         self.unstable = false;
+        self.quality_level = None;
+        self.clear_peer_key();
         self.last_remote_seen = None;
+        self.reconnect.reset();
+        self.call_waiting = None;
+        self.last_remote_frame = None;
     }
 
     pub fn update(
         &mut self,
         ctx: &egui::Context,
         _frame: &mut eframe::Frame,
+        transfer_targets: &[String],
     ) -> Option<VideoMeetAction> {
         let mut next_action = None;
 
         let remote_hangup = self.consume_remote_messages();
+        self.poll_snapshot();
         if !self.media_started {
             self.quality_metrics = None;
             self.unstable = false;
+            self.quality_level = None;
             self.last_remote_seen = None;
         }
 
-        if remote_hangup {
+        let connection_failure = self
+            .client
+            .as_ref()
+            .and_then(|client| apply_connection_events(client, &mut self.status_message));
+
+        if let Some(reason) = connection_failure {
+            self.stop_current_call();
+            next_action = Some(VideoMeetAction::ConnectionFailed(reason));
+        } else if remote_hangup {
             self.stop_current_call();
             next_action = Some(VideoMeetAction::GoToLobby);
         } else {
@@ -150,17 +457,7 @@ impl VideoCall {
             if let Some(loader) = &self.media_loader {
                 if let Ok(result) = loader.try_recv() {
                     self.media_loader = None;
-                    match result {
-                        Ok(client_ready) => {
-                            self.client = Some(client_ready);
-                            self.media_started = true;
-                            self.status_message = None;
-                        }
-                        Err((client_failed, err)) => {
-                            self.client = Some(client_failed);
-                            self.status_message = Some(format!("Error starting camera: {}", err));
-                        }
-                    }
+                    self.finish_media_start(result);
                 }
             }
             // Start media if we have a client and haven't started yet
@@ -169,10 +466,20 @@ impl VideoCall {
                     self.status_message = Some("Starting Camera".to_string());
                     let (tx, rx) = std::sync::mpsc::channel();
                     let video_params = self.video;
+                    client.set_bandwidth_cap(self.bandwidth_cap_kbps);
+                    let fake_camera = self.fake_camera;
+                    let camera_index = self.camera_index;
                     thread::spawn(move || {
-                        let res = match client.start_media(0, video_params) {
+                        let res = match client.start_media(camera_index, video_params, fake_camera)
+                        {
                             Ok(_) => Ok(client),
-                            Err(e) => Err((client, e.to_string())),
+                            Err(e) => {
+                                // Sin cámara igual se ve el video del peer
+                                if let Err(err) = client.start_receive_only(video_params) {
+                                    log_warn!("Tampoco se pudo recibir video: {}", err);
+                                }
+                                Err((client, e.to_string()))
+                            }
                         };
                         let _ = tx.send(res);
                     });
@@ -184,208 +491,60 @@ impl VideoCall {
 
             //Update textures if media has started
             if self.media_started {
-                // Start audio once media is ready (must be in main thread due to cpal)
+                // Start audio once the video was started or given up on (must
+                // be in main thread due to cpal)
                 if !self.audio_started {
-                    if let Some(client) = self.client.as_ref() {
-                        let (socket, context) = client.audio_params();
-                        match WorkerAudio::start(socket, context) {
+                    if let Some(client) = self
+                        .client
+                        .as_ref()
+                        .filter(|client| client.is_media_accepted(MediaType::Audio))
+                    {
+                        // Con la conexión envenenada la llamada se corta sola
+                        match client.start_audio(self.audio.clone()) {
                             Ok(worker) => {
-                                // Connect audio incoming sender to client listener
-                                let sender = worker.incoming_sender();
-                                client.set_audio_incoming(sender);
-                                
+                                worker.set_output_gain(self.output_gain);
+                                worker.set_output_muted(self.output_muted);
+                                // En espera desde antes de que arrancara el audio
+                                if self.hold == Some(Hold::Local) {
+                                    self.muted_before_hold = false;
+                                    worker.set_muted(true);
+                                }
+
                                 self.audio_worker = Some(worker);
                                 self.audio_started = true;
                             }
                             Err(e) => {
-                                eprintln!("Failed to start audio: {}", e);
+                                log_warn!("Failed to start audio: {}", e);
                                 self.audio_started = true; // Don't retry
                             }
                         }
                     }
                 }
-                
-                if let Some(client) = self.client.as_ref() {
-                    // Initialize SCTP RX
-                    if self.sctp_rx.is_none() {
-                        let (tx, rx) = mpsc::sync_channel(10000);
-                        client.set_sctp_incoming(tx);
-                        self.sctp_rx = Some(rx);
-                    }
-                    
-                    // Poll SCTP Messages
-                    if let Some(rx) = &self.sctp_rx {
-                        while let Ok((stream, payload)) = rx.try_recv() {
-                            // Assume stream 1 is for file transfer control & data
-                             if stream == 1 {
-                                 // Try to parse control message (JSON)
-                                 // Or if it matches chunk prefix?
-                                 // Let's assume text messages are Control, binary are Chunks?
-                                 // My protocol says Chunks have "type":"chunk", "data":"base64".
-                                 // That is inefficient.
-                                 // Better: Control messages are JSON. Chunks are raw binary.
-                                 // But how to distinguish?
-                                 // Use separate streams? Stream 1 = Control, Stream 2 = Data.
-                                 // Let's use Stream 2 for Data.
-                                 if let Ok(msg_str) = String::from_utf8(payload.clone()) {
-                                     // Check if valid JSON
-                                     if let Ok(msg) = serde_json::from_str::<FileTransferMessage>(&msg_str) {
-                                         match msg {
-                                             FileTransferMessage::Offer { filename, size, .. } => {
-                                                 self.pending_offer = Some((filename, size));
-                                             }
-                                             FileTransferMessage::Answer { accepted } => {
-                                                 if accepted {
-                                                     // Spawn sender thread
-                                                     if let Some(out) = &self.outgoing_file {
-                                                         let path = out.path.clone();
-                                                         if let Some(client) = self.client.clone() {
-                                                             let sctp_inc = client.sctp_incoming.clone();
-                                                             thread::spawn(move || {
-                                                                 if let Ok(mut file) = std::fs::File::open(&path) {
-                                                                    use std::io::Read;
-                                                                    let mut buffer = [0u8; 4096]; // 4KB chunks (Reduced from 16KB to improve reliability)
-                                                                    let mut total_sent = 0;
-                                                                    loop {
-                                                                        let n = file.read(&mut buffer).unwrap_or(0);
-                                                                        if n == 0 { break; }
-                                                                        
-                                                                        let chunk = &buffer[..n];
-                                                                        
-                                                                        // Log progress every ~500KB
-                                                                        if (total_sent / 500_000) != ((total_sent + n) / 500_000) {
-                                                                            println!("DEBUG: Sender Thread: Sent {} bytes...", total_sent);
-                                                                        }
-
-                                // Send Chunk on Stream 2 (data channel for file chunks)
-                                let mut retries = 0;
-                                loop {
-                                    match client.send_sctp_data(2, chunk.to_vec()) {
-                                        Ok(_) => {
-                                                                                    if let Ok(guard) = sctp_inc.lock() {
-                                                                                        if let Some(tx) = guard.as_ref() {
-                                                                                            let len_bytes = n.to_le_bytes().to_vec();
-                                                                                            let _ = tx.send((998, len_bytes));
-                                                                                        }
-                                                                                    }
-                                                                                    total_sent += n;
-                                                                                    break;
-                                                                                }
-                                        Err(e) if e.contains("BufferFull") => {
-                                            retries += 1;
-                                            if retries > 4000 { // wait up to ~3.3 minutes at 50ms
-                                                eprintln!("DEBUG: Upload error: BufferFull timeout after {} bytes", total_sent);
-                                                break;
-                                            }
-                                            thread::sleep(std::time::Duration::from_millis(50));
-                                        }
-                                                                                Err(e) => {
-                                                                                    eprintln!("DEBUG: Upload error: {}", e);
-                                                                                    break;
-                                                                                }
-                                                                            }
-                                                                        }
-                                                                     }
-                                                                     // Send EOF
-                                                                     let eof = FileTransferMessage::Eof;
-                                                                     if let Ok(json) = serde_json::to_string(&eof) {
-                                                                         let _ = client.send_sctp_data(1, json.into_bytes());
-                                                                     }
-                                                                 }
-                                                             });
-                                                         }
-                                                     }
-                                                 } else {
-                                                     self.outgoing_file = None;
-                                                     self.status_message = Some("File transfer rejected".to_string());
-                                                 }
-                                             }
-                                    FileTransferMessage::Ack { bytes_received: _ } => {
-                                                 // Remote ack
-                                             }
-                                             FileTransferMessage::Eof => {
-                                                 if let Some(inc) = &mut self.incoming_file {
-                                                     // Close file
-                                                     inc.file_handle = None;
-                                                     self.status_message = Some(format!("Received file: {}", inc.name));
-                                                 }
-                                                 self.incoming_file = None;
-                                             }
-                                             _ => {}
-                                         }
-                                     }
-                                 }
-                             } else if stream == 0 {
-                                 // Data Chunk
-                                 if let Some(inc) = &mut self.incoming_file {
-                                      if let Some(f) = &mut inc.file_handle {
-                                          if let Err(e) = f.write_all(&payload) {
-                                              eprintln!("File write error: {}", e);
-                                          } else {
-                                              inc.received_bytes += payload.len();
-                                          }
-                                      }
-                                 }
-                             } else if stream == 998 {
-                                 // Internal: Local Progress Update
-                                 if payload.len() >= 8 { // usize is 8 bytes on 64bit
-                                     // Actually to_le_bytes of usize depends on arch. 
-                                     // Assuming x64.
-                                     let mut arr = [0u8; 8];
-                                     if payload.len() >= 8 {
-                                        arr.copy_from_slice(&payload[..8]);
-                                        let n = usize::from_le_bytes(arr);
-                                        if let Some(out) = &mut self.outgoing_file {
-                                            out.sent_bytes += n;
-                                        }
-                                     }
-                                 }
-                             } else if stream == 999 {
-                                 // Internal: Outgoing File Selected
-                                 let path_str = String::from_utf8(payload).unwrap_or_default();
-                                 let path = std::path::PathBuf::from(&path_str);
-                                 if let Ok(metadata) = std::fs::metadata(&path) {
-                                     let size = metadata.len() as usize;
-                                     let name = path.file_name().unwrap().to_string_lossy().to_string();
-                                     
-                                     // Send Offer
-                                     let offer = FileTransferMessage::Offer {
-                                         filename: name.clone(),
-                                         size,
-                                         mime_type: "application/octet-stream".to_string(),
-                                     };
-                                    let json = serde_json::to_string(&offer).unwrap();
-                                    if let Err(e) = client.send_sctp_data(1, json.into_bytes()) {
-                                        eprintln!("Error sending File Offer: {}", e);
-                                        self.status_message = Some(format!("Error sending offer: {}", e));
-                                        return None;
-                                    }
-                                     
-                                     // Set Outgoing File State
-                                     self.outgoing_file = Some(OutgoingFile {
-                                         name,
-                                         total_size: size,
-                                         sent_bytes: 0,
-                                         path,
-                                     });
-                                     self.status_message = Some("Sent File Offer...".to_string());
-                                 }
-                             } else if stream == 2 || stream == 0 {
-                                 // File data stream (primary 2, legacy 0)
-                                 if let Some(inc) = &mut self.incoming_file {
-                                     if let Some(f) = &mut inc.file_handle {
-                                         if let Err(e) = f.write_all(&payload) {
-                                             eprintln!("File write error: {}", e);
-                                         } else {
-                                             inc.received_bytes += payload.len();
-                                         }
-                                     }
-                                 }
-                             }
-                        }
-                    }
 
+                // Archivos soltados sobre la ventana, en el orden en que llegaron
+                self.files_on_connect.extend(ctx.input(|i| {
+                    i.raw
+                        .dropped_files
+                        .iter()
+                        .filter_map(|file| file.path.clone())
+                        .collect::<Vec<_>>()
+                }));
+                self.poll_sctp_messages();
+
+                // Si el peer eligió nuestro SSRC, el video cambia adentro del
+                // cliente y el audio acá
+                if let Some(client) = self.client.as_mut()
+                    && let Some(ssrc) = client.resolve_ssrc_collisions()
+                    && let Some(audio) = self.audio_worker.as_mut()
+                    && let Err(err) = audio.set_ssrc(ssrc)
+                {
+                    log_warn!("No se pudo cambiar el SSRC de audio: {}", err);
+                }
+
+                if let Some(client) = self.client.as_ref() {
                     self.quality_metrics = client.metrics_snapshot();
+                    self.track_quality();
+                    self.check_peer_key();
                     if let Some(frame) = client.try_recv_local_frame()
                         && let Some(image) = Self::mat_to_color_image(&frame)
                     {
@@ -407,28 +566,43 @@ impl VideoCall {
                             "roomrtc-remote-preview",
                             image,
                         );
+                        self.last_remote_frame = Some(frame);
                     }
 
                     ctx.request_repaint();
 
                     // Heartbeat remoto: si hay actividad reciente, refrescamos el último visto
-                    if let Some(metrics) = &self.quality_metrics {
-                        if let Some(ms) = metrics.since_last_ms {
-                            if ms < 2_000 {
-                                self.last_remote_seen = Some(std::time::Instant::now());
-                            }
-                        }
+                    let since_last_video = self
+                        .quality_metrics
+                        .as_ref()
+                        .and_then(|metrics| metrics.since_last_ms);
+                    if peer_recently_seen(since_last_video, client.since_last_audio()) {
+                        self.last_remote_seen = Some(std::time::Instant::now());
                     }
-                    // Evaluar inactividad remota con umbral más amplio
-                    if let Some(last_seen) = self.last_remote_seen {
-                        let gap = last_seen.elapsed().as_millis() as u64;
-                        self.unstable = gap > 2_000 && gap <= 30_000;
-                        if gap > 30_000 {
-                            self.status_message =
-                                Some("Conexión perdida, finalizando llamada".to_string());
-                            Self::send_hangup_signal(client);
-                            self.stop_current_call();
-                            next_action = Some(VideoMeetAction::GoToLobby);
+                    // Evaluar inactividad remota: tras un corte se reinicia
+                    // ICE y solo se corta si no vuelve nada. En espera el
+                    // video no llega a propósito: no se corta
+                    if self.hold.is_some() {
+                        self.unstable = false;
+                        self.reconnect.reset();
+                    } else if let Some(last_seen) = self.last_remote_seen {
+                        let silence = last_seen.elapsed();
+                        self.unstable = silence.as_millis() > 2_000;
+                        match self.reconnect.poll(silence) {
+                            ReconnectStep::Wait => {}
+                            ReconnectStep::RestartIce => {
+                                next_action = Some(VideoMeetAction::RestartIce);
+                            }
+                            ReconnectStep::Recovered => {
+                                log_info!("Media recuperada tras el reinicio de ICE");
+                            }
+                            ReconnectStep::GiveUp => {
+                                self.status_message =
+                                    Some("Conexión perdida, finalizando llamada".to_string());
+                                CallController::send_hangup(client);
+                                self.stop_current_call();
+                                next_action = Some(VideoMeetAction::GoToLobby);
+                            }
                         }
                     } else {
                         self.unstable = false;
@@ -444,36 +618,231 @@ impl VideoCall {
                     .anchor(Align2::LEFT_TOP, egui::vec2(20.0, 80.0))
                     .title_bar(false)
                     .resizable(false)
-                    .frame(egui::Frame::none().fill(Color32::from_black_alpha(180)).rounding(8.0).inner_margin(12.0))
+                    .frame(
+                        egui::Frame::none()
+                            .fill(Color32::from_black_alpha(180))
+                            .rounding(8.0)
+                            .inner_margin(12.0),
+                    )
                     .show(ctx, |ui| {
-                         ui.label(RichText::new("🔌 Network Statistics").strong().color(Color32::WHITE));
-                         ui.add_space(4.0);
-                         
-                         if let Some(metrics) = &self.quality_metrics {
-                             let text_color = crate::ui::theme::colors::TEXT_PRIMARY;
-                             ui.style_mut().override_text_style = Some(egui::TextStyle::Body);
-                             
-                             egui::Grid::new("stats_grid").num_columns(2).spacing(egui::vec2(20.0, 4.0)).show(ui, |ui| {
-                                 ui.label(RichText::new("Bitrate:").color(crate::ui::theme::colors::TEXT_MUTED));
-                                 ui.label(RichText::new(format!("{:.0} kbps", metrics.bitrate_kbps)).color(text_color));
-                                 ui.end_row();
-                                 
-                                 ui.label(RichText::new("Packet Loss:").color(crate::ui::theme::colors::TEXT_MUTED));
-                                 let loss_color = if metrics.packet_loss_pct > 5.0 { crate::ui::theme::colors::DANGER } else { crate::ui::theme::colors::SUCCESS };
-                                 ui.label(RichText::new(format!("{:.2}%", metrics.packet_loss_pct)).color(loss_color));
-                                 ui.end_row();
-                                 
-                                 ui.label(RichText::new("Jitter:").color(crate::ui::theme::colors::TEXT_MUTED));
-                                 ui.label(RichText::new(format!("{:.1} ms", metrics.jitter_ms)).color(text_color));
-                                 ui.end_row();
-                                 
-                                 ui.label(RichText::new("RTT (est):").color(crate::ui::theme::colors::TEXT_MUTED));
-                                 ui.label(RichText::new(format!("{} ms", metrics.since_last_ms.unwrap_or(0))).color(text_color));
-                                 ui.end_row();
-                             });
-                         } else {
-                             ui.label(RichText::new("Gathering metrics...").italics().color(crate::ui::theme::colors::TEXT_MUTED));
-                         }
+                        ui.label(
+                            RichText::new("🔌 Network Statistics")
+                                .strong()
+                                .color(Color32::WHITE),
+                        );
+                        ui.add_space(4.0);
+
+                        if let Some(metrics) = &self.quality_metrics {
+                            let text_color = crate::ui::theme::colors::TEXT_PRIMARY;
+                            let audio_metrics =
+                                self.audio_worker.as_ref().map(|audio| audio.metrics());
+                            let capabilities = self
+                                .client
+                                .as_ref()
+                                .map(|client| client.capabilities().to_string());
+                            let candidate_pair = self
+                                .client
+                                .as_ref()
+                                .and_then(|client| client.selected_candidate_pair());
+                            ui.style_mut().override_text_style = Some(egui::TextStyle::Body);
+
+                            egui::Grid::new("stats_grid")
+                                .num_columns(2)
+                                .spacing(egui::vec2(20.0, 4.0))
+                                .show(ui, |ui| {
+                                    ui.label(
+                                        RichText::new("Bitrate:")
+                                            .color(crate::ui::theme::colors::TEXT_MUTED),
+                                    );
+                                    let bitrate = match metrics.capped_at_kbps {
+                                        Some(cap) => format!(
+                                            "{:.0} kbps (capped at {} kbps)",
+                                            metrics.bitrate_kbps, cap
+                                        ),
+                                        None => format!("{:.0} kbps", metrics.bitrate_kbps),
+                                    };
+                                    ui.label(RichText::new(bitrate).color(text_color));
+                                    ui.end_row();
+
+                                    ui.label(
+                                        RichText::new("Packet Loss:")
+                                            .color(crate::ui::theme::colors::TEXT_MUTED),
+                                    );
+                                    let loss_color = if metrics.packet_loss_pct > 5.0 {
+                                        crate::ui::theme::colors::DANGER
+                                    } else {
+                                        crate::ui::theme::colors::SUCCESS
+                                    };
+                                    ui.label(
+                                        RichText::new(format!("{:.2}%", metrics.packet_loss_pct))
+                                            .color(loss_color),
+                                    );
+                                    ui.end_row();
+
+                                    ui.label(
+                                        RichText::new("Jitter:")
+                                            .color(crate::ui::theme::colors::TEXT_MUTED),
+                                    );
+                                    ui.label(
+                                        RichText::new(format!("{:.1} ms", metrics.jitter_ms))
+                                            .color(text_color),
+                                    );
+                                    ui.end_row();
+
+                                    ui.label(
+                                        RichText::new("RTT (est):")
+                                            .color(crate::ui::theme::colors::TEXT_MUTED),
+                                    );
+                                    ui.label(
+                                        RichText::new(format!(
+                                            "{} ms",
+                                            metrics.since_last_ms.unwrap_or(0)
+                                        ))
+                                        .color(text_color),
+                                    );
+                                    ui.end_row();
+
+                                    if metrics.unknown_ssrc_packets > 0 {
+                                        ui.label(
+                                            RichText::new("Unknown SSRC:")
+                                                .color(crate::ui::theme::colors::TEXT_MUTED),
+                                        );
+                                        ui.label(
+                                            RichText::new(format!(
+                                                "{} dropped",
+                                                metrics.unknown_ssrc_packets
+                                            ))
+                                            .color(crate::ui::theme::colors::WARNING),
+                                        );
+                                        ui.end_row();
+                                    }
+
+                                    // Lo que descartaron las colas por ir atrasadas, por etapa
+                                    let audio_drops =
+                                        audio_metrics.map(|audio| audio.drops).unwrap_or_default();
+                                    if metrics.drops.total() + audio_drops.total() > 0 {
+                                        ui.label(
+                                            RichText::new("Dropped:")
+                                                .color(crate::ui::theme::colors::TEXT_MUTED),
+                                        );
+                                        ui.label(
+                                            RichText::new(format!(
+                                                "{} video, {} audio",
+                                                metrics.drops.total(),
+                                                audio_drops.total()
+                                            ))
+                                            .color(text_color),
+                                        )
+                                        .on_hover_text(
+                                            format!(
+                                                "Video: {} incoming, {} decoded, {} preview\n\
+                                             Audio: {} microphone, {} incoming, {} speakers",
+                                                metrics.drops.incoming_rtp,
+                                                metrics.drops.decoded_frames,
+                                                metrics.drops.preview_frames,
+                                                audio_drops.capture,
+                                                audio_drops.incoming_rtp,
+                                                audio_drops.playback
+                                            ),
+                                        );
+                                        ui.end_row();
+                                    }
+
+                                    // Boca a oído medido con las sondas que el peer devuelve
+                                    if let Some(ms) =
+                                        audio_metrics.and_then(|audio| audio.mouth_to_ear_ms)
+                                    {
+                                        ui.label(
+                                            RichText::new("Audio latency:")
+                                                .color(crate::ui::theme::colors::TEXT_MUTED),
+                                        );
+                                        let latency_color = if ms > 150.0 {
+                                            crate::ui::theme::colors::WARNING
+                                        } else {
+                                            crate::ui::theme::colors::SUCCESS
+                                        };
+                                        ui.label(
+                                            RichText::new(format!("{:.0} ms", ms))
+                                                .color(latency_color),
+                                        );
+                                        ui.end_row();
+                                    }
+                                    if let Some(audio) =
+                                        audio_metrics.filter(|audio| audio.playout.late_frames > 0)
+                                    {
+                                        ui.label(
+                                            RichText::new("Late audio:")
+                                                .color(crate::ui::theme::colors::TEXT_MUTED),
+                                        );
+                                        ui.label(
+                                            RichText::new(format!(
+                                                "{} frames",
+                                                audio.playout.late_frames
+                                            ))
+                                            .color(text_color),
+                                        );
+                                        ui.end_row();
+                                    }
+                                    if let Some(audio) = audio_metrics {
+                                        ui.label(
+                                            RichText::new("Audio buffer:")
+                                                .color(crate::ui::theme::colors::TEXT_MUTED),
+                                        );
+                                        ui.label(
+                                            RichText::new(format!(
+                                                "{} ms ({} underruns)",
+                                                audio.playout.target_ms(),
+                                                audio.playout.underruns
+                                            ))
+                                            .color(text_color),
+                                        );
+                                        ui.end_row();
+                                    }
+                                    if audio_metrics.is_some_and(|audio| audio.dtx_active) {
+                                        ui.label(
+                                            RichText::new("Audio:")
+                                                .color(crate::ui::theme::colors::TEXT_MUTED),
+                                        );
+                                        ui.label(
+                                            RichText::new("DTX active (silence)").color(text_color),
+                                        );
+                                        ui.end_row();
+                                    }
+                                    if let Some(pair) = candidate_pair {
+                                        ui.label(
+                                            RichText::new("Connection:")
+                                                .color(crate::ui::theme::colors::TEXT_MUTED),
+                                        );
+                                        ui.label(RichText::new(pair.to_string()).color(text_color));
+                                        ui.end_row();
+                                        if let Some(rtt) = pair.rtt {
+                                            ui.label(
+                                                RichText::new("ICE check RTT:")
+                                                    .color(crate::ui::theme::colors::TEXT_MUTED),
+                                            );
+                                            ui.label(
+                                                RichText::new(format!("{} ms", rtt.as_millis()))
+                                                    .color(text_color),
+                                            );
+                                            ui.end_row();
+                                        }
+                                    }
+                                    if let Some(capabilities) = capabilities {
+                                        ui.label(
+                                            RichText::new("Capabilities:")
+                                                .color(crate::ui::theme::colors::TEXT_MUTED),
+                                        );
+                                        ui.label(RichText::new(capabilities).color(text_color));
+                                        ui.end_row();
+                                    }
+                                });
+                        } else {
+                            ui.label(
+                                RichText::new("Gathering metrics...")
+                                    .italics()
+                                    .color(crate::ui::theme::colors::TEXT_MUTED),
+                            );
+                        }
                     });
             }
 
@@ -481,24 +850,98 @@ impl VideoCall {
             if let Some(status) = &self.status_message {
                 ui.colored_label(crate::ui::theme::colors::DANGER, status);
             }
-            if self.unstable {
-                ui.colored_label(crate::ui::theme::colors::DANGER, "⚠ Network Unstable");
+            if self.video_unavailable {
+                ui.colored_label(
+                    crate::ui::theme::colors::WARNING,
+                    "Video unavailable — audio only",
+                );
+            }
+            if matches!(self.key_check, Some(KeyCheck::Changed { .. }))
+                && let Some(peer) = &self.peer_username
+            {
+                ui.colored_label(
+                    crate::ui::theme::colors::DANGER,
+                    format!(
+                        "⚠ {}'s security key changed since you verified it. Compare the safety number (🔒).",
+                        peer
+                    ),
+                );
+            }
+            if self.reconnect.is_reconnecting() {
+                let left = self
+                    .last_remote_seen
+                    .map(|seen| RECONNECT_WINDOW.saturating_sub(seen.elapsed()).as_secs())
+                    .unwrap_or_default();
+                ui.colored_label(
+                    crate::ui::theme::colors::DANGER,
+                    format!("⚠ Connection lost, reconnecting... ({}s)", left),
+                );
+            } else if let Some((score, level, detail)) = self.quality_indicator() {
+                ui.horizontal(|ui| {
+                    Self::draw_quality_bars(ui, score, level).on_hover_text(&detail);
+                    ui.colored_label(Self::quality_color(level), format!("Connection: {}", level))
+                        .on_hover_text(&detail);
+                });
             }
 
             // Main Video Area (Remote)
             let available_rect = ui.available_rect_before_wrap();
             let control_bar_height = 80.0;
             let video_area_height = available_rect.height() - control_bar_height;
-            
+
             // Allocate space for videos
-            let video_rect = egui::Rect::from_min_size(available_rect.min, egui::vec2(available_rect.width(), video_area_height));
+            let video_rect = egui::Rect::from_min_size(
+                available_rect.min,
+                egui::vec2(available_rect.width(), video_area_height),
+            );
             ui.allocate_new_ui(egui::UiBuilder::new().max_rect(video_rect), |ui| {
                 ui.centered_and_justified(|ui| {
                     if self.client.is_some() && self.media_started {
                         // Remote Video (Primary)
-                        Self::draw_video_slot(ui, self.remote_texture.as_ref(), "Waiting for participant...", ui.available_size());
+                        let placeholder = if self.video_unavailable {
+                            "Video unavailable — audio only"
+                        } else {
+                            "Waiting for participant..."
+                        };
+                        Self::draw_video_slot(
+                            ui,
+                            self.remote_texture.as_ref(),
+                            placeholder,
+                            ui.available_size(),
+                        );
+                        if let Some(hold) = self.hold {
+                            let detail = match hold {
+                                Hold::Local => "Your camera and microphone are paused".to_string(),
+                                Hold::Remote => format!(
+                                    "{} put the call on hold",
+                                    self.peer_username
+                                        .as_deref()
+                                        .unwrap_or("The other participant")
+                                ),
+                            };
+                            let painter = ui.painter();
+                            painter.rect_filled(video_rect, 0.0, Color32::from_black_alpha(180));
+                            painter.text(
+                                video_rect.center() - egui::vec2(0.0, 18.0),
+                                Align2::CENTER_CENTER,
+                                "⏸ On hold",
+                                FontId::proportional(32.0),
+                                Color32::WHITE,
+                            );
+                            painter.text(
+                                video_rect.center() + egui::vec2(0.0, 18.0),
+                                Align2::CENTER_CENTER,
+                                detail,
+                                FontId::proportional(16.0),
+                                crate::ui::theme::colors::TEXT_MUTED,
+                            );
+                        }
                     } else {
-                        ui.label(RichText::new("Connecting...").size(24.0).color(crate::ui::theme::colors::TEXT_MUTED));
+                        ui.label(
+                            RichText::new("Connecting...")
+                                .size(24.0)
+                                .color(crate::ui::theme::colors::TEXT_MUTED),
+                        );
                     }
                 });
             });
@@ -510,94 +953,352 @@ impl VideoCall {
             let pip_rect = egui::Rect::from_min_size(
                 egui::pos2(
                     available_rect.max.x - pip_width - 20.0,
-                    available_rect.min.y + video_area_height - pip_height - 20.0
+                    available_rect.min.y + video_area_height - pip_height - 20.0,
                 ),
-                egui::vec2(pip_width, pip_height)
+                egui::vec2(pip_width, pip_height),
             );
-            
+
             // Draw PiP frame
             ui.put(pip_rect, |ui: &mut egui::Ui| {
                 egui::Frame::none()
-                    .stroke(egui::Stroke::new(2.0, crate::ui::theme::colors::BACKGROUND_TERTIARY))
+                    .stroke(egui::Stroke::new(
+                        2.0,
+                        crate::ui::theme::colors::BACKGROUND_TERTIARY,
+                    ))
                     .shadow(egui::Shadow::default())
                     .show(ui, |ui| {
-                         Self::draw_video_slot(ui, self.local_texture.as_ref(), "No Cam", pip_rect.size());
-                    }).response
+                        Self::draw_video_slot(
+                            ui,
+                            self.local_texture.as_ref(),
+                            "No Cam",
+                            pip_rect.size(),
+                        );
+                    })
+                    .response
             });
 
+            // Llamada en espera: se avisa arriba sin tapar el video
+            if let Some(waiting) = &self.call_waiting {
+                let remaining = self.ring_timeout.saturating_sub(waiting.since.elapsed());
+                if remaining.is_zero() {
+                    self.status_message = Some(format!("Missed call from {}", waiting.from));
+                    next_action = Some(VideoMeetAction::MissedWaitingCall(waiting.from.clone()));
+                } else {
+                    ctx.request_repaint_after(Duration::from_secs(1));
+                    egui::Window::new("Call waiting")
+                        .collapsible(false)
+                        .resizable(false)
+                        .anchor(Align2::CENTER_TOP, Vec2::new(0.0, 40.0))
+                        .show(ctx, |ui| {
+                            ui.heading(format!("📞 {} is calling", waiting.from));
+                            ui.label(
+                                RichText::new(format!(
+                                    "Declined automatically in {} s",
+                                    remaining.as_secs_f32().ceil()
+                                ))
+                                .weak(),
+                            );
+                            ui.add_space(10.0);
+                            ui.horizontal(|ui| {
+                                let answer = Button::new(
+                                    RichText::new("Answer and end this call").color(Color32::WHITE),
+                                )
+                                .fill(crate::ui::theme::colors::SUCCESS);
+                                if ui.add(answer).clicked() {
+                                    next_action = Some(VideoMeetAction::AcceptWaitingCall);
+                                }
+                                let decline =
+                                    Button::new(RichText::new("Decline").color(Color32::WHITE))
+                                        .fill(crate::ui::theme::colors::DANGER);
+                                if ui.add(decline).clicked() {
+                                    next_action = Some(VideoMeetAction::RejectWaitingCall(
+                                        waiting.from.clone(),
+                                    ));
+                                }
+                            });
+                        });
+                }
+            }
+            if matches!(
+                next_action,
+                Some(VideoMeetAction::RejectWaitingCall(_) | VideoMeetAction::MissedWaitingCall(_))
+            ) {
+                self.call_waiting = None;
+            }
+
+            if self.show_security
+                && let Some(action) = self.show_security_window(ctx)
+            {
+                next_action = Some(action);
+            }
 
-            // File Offer Popup
-            if let Some((name, size)) = &self.pending_offer {
-                 let mut accepted = None;
-                 egui::Window::new("Incoming File")
-                    .collapsible(false)
-                    .resizable(false)
-                    .anchor(Align2::CENTER_CENTER, Vec2::ZERO)
-                    .show(ctx, |ui| {
-                        ui.heading("Incoming File Transfer");
-                        ui.add_space(10.0);
-                        ui.label(format!("File: {}", name));
-                        ui.label(format!("Size: {:.2} MB", *size as f32 / 1024.0 / 1024.0));
-                        ui.add_space(20.0);
-                        ui.horizontal(|ui| {
-                            if ui.button("Accept").clicked() {
-                                accepted = Some(true);
-                            }
-                            if ui.button("Reject").clicked() {
-                                accepted = Some(false);
+            // File Offer Popup, one offer at a time. It doesn't block the call:
+            // the controls keep working and the rest of the offers wait behind it
+            if let Some(offer) = self.pending_offers.front_mut() {
+                offer.shown_at.get_or_insert_with(std::time::Instant::now);
+                if offer.texture.is_none()
+                    && let Some(preview) = offer.preview.take()
+                {
+                    offer.texture = Some(ctx.load_texture(
+                        format!("offer_thumbnail_{}", offer.id),
+                        preview,
+                        TextureOptions::LINEAR,
+                    ));
+                }
+            }
+            if let Some(offer) = self.pending_offers.front() {
+                let (id, name, size) = (offer.id, offer.name.clone(), offer.size);
+                let mime_type = offer.mime_type.clone();
+                let texture = offer.texture.clone();
+                let remaining = offer
+                    .shown_at
+                    .map(|shown| self.file_offer_timeout.saturating_sub(shown.elapsed()))
+                    .unwrap_or(self.file_offer_timeout);
+                let mut accepted = None;
+                if remaining.is_zero() {
+                    accepted = Some(false);
+                    self.status_message = Some(format!(
+                        "Rejected {}: no answer in {} s",
+                        name,
+                        self.file_offer_timeout.as_secs()
+                    ));
+                } else {
+                    // Para que la cuenta regresiva avance sin mover el mouse
+                    ctx.request_repaint_after(std::time::Duration::from_secs(1));
+                    egui::Window::new("Incoming File")
+                        .collapsible(false)
+                        .resizable(false)
+                        .anchor(Align2::CENTER_CENTER, Vec2::ZERO)
+                        .show(ctx, |ui| {
+                            ui.heading("Incoming File Transfer");
+                            ui.add_space(10.0);
+                            ui.horizontal(|ui| {
+                                if let Some(texture) = &texture {
+                                    let shown = texture.size_vec2()
+                                        * (THUMBNAIL_SHOWN_SIDE / texture.size_vec2().max_elem());
+                                    ui.add(egui::Image::from_texture(SizedTexture::new(
+                                        texture.id(),
+                                        shown,
+                                    )));
+                                }
+                                ui.vertical(|ui| {
+                                    ui.label(format!("File: {}", name));
+                                    ui.label(format!(
+                                        "Size: {:.2} MB",
+                                        size as f32 / 1024.0 / 1024.0
+                                    ));
+                                });
+                            });
+                            ui.label(
+                                RichText::new(format!(
+                                    "Rejected automatically in {} s",
+                                    remaining.as_secs_f32().ceil()
+                                ))
+                                .weak(),
+                            );
+                            if self.pending_offers.len() > 1 {
+                                ui.label(format!(
+                                    "{} more file(s) waiting",
+                                    self.pending_offers.len() - 1
+                                ));
                             }
+                            ui.add_space(20.0);
+                            ui.horizontal(|ui| {
+                                if ui.button("Accept").clicked() {
+                                    accepted = Some(true);
+                                }
+                                if ui.button("Reject").clicked() {
+                                    accepted = Some(false);
+                                }
+                            });
                         });
-                    });
-                
+                }
+
                 if let Some(acc) = accepted {
-                    if acc {
-                        if let Some(path) = FileDialog::new().set_file_name(name).save_file() {
-                             if let Ok(file) = File::create(&path) {
-                                 self.incoming_file = Some(IncomingFile {
-                                     name: name.clone(),
-                                     size: *size,
-                                     received_bytes: 0,
-                                     file_handle: Some(file),
-                                     path: Some(path),
-                                 });
-                                 
-                                 let ans = FileTransferMessage::Answer { accepted: true };
-                                 let json = serde_json::to_string(&ans).unwrap();
-                                 if let Some(c) = &self.client {
-                                     let _ = c.send_sctp_data(1, json.into_bytes());
-                                 }
-                             }
+                    self.pending_offers.pop_front();
+                    let mut answered = false;
+                    if acc && let Some(path) = FileDialog::new().set_file_name(&name).save_file() {
+                        if let Err(reason) =
+                            storage::check_incoming_file(size, self.max_file_size, &path)
+                        {
+                            self.status_message = Some(reason);
+                        } else if let Ok(file) = File::create(&path) {
+                            self.incoming_files.insert(
+                                id,
+                                IncomingFile {
+                                    name: name.clone(),
+                                    size,
+                                    mime_type,
+                                    received_bytes: 0,
+                                    file_handle: Some(file),
+                                    path: Some(path),
+                                    rate: TransferRate::new(),
+                                    last_chunk_at: Instant::now(),
+                                    eof: false,
+                                },
+                            );
+
+                            if let Some(c) = &self.client {
+                                Self::send_file_answer(c, id, true);
+                            }
+                            answered = true;
                         }
-                    } else {
-                         let ans = FileTransferMessage::Answer { accepted: false };
-                         let json = serde_json::to_string(&ans).unwrap();
-                         if let Some(c) = &self.client {
-                             let _ = c.send_sctp_data(1, json.into_bytes());
-                         }
                     }
-                    self.pending_offer = None;
+                    // Rejected (or no place to save it): the sender moves on to its next file
+                    if !answered {
+                        if let Some(c) = &self.client {
+                            Self::send_file_answer(c, id, false);
+                        }
+                        self.finish_transfer(name, false, TransferOutcome::Rejected);
+                    }
                 }
             }
-            // File Progress Overlay
-            if let Some(inc) = &self.incoming_file {
-                 egui::Area::new("incoming_progress".into())
-                    .anchor(Align2::LEFT_BOTTOM, Vec2::new(10.0, -100.0))
+            // Transfers: active ones with their progress, queued and last finished
+            let mut cancel_clicked: Option<(u64, bool)> = None; // (id, outgoing)
+            let mut open_clicked: Option<std::path::PathBuf> = None;
+            let mut retry_clicked: Option<std::path::PathBuf> = None;
+            let has_transfers = !self.incoming_files.is_empty()
+                || !self.outgoing_files.is_empty()
+                || !self.outgoing_queue.is_empty()
+                || !self.finished_transfers.is_empty();
+            if has_transfers {
+                egui::Area::new("transfer_list".into())
+                    .anchor(Align2::RIGHT_TOP, Vec2::new(-10.0, 60.0))
                     .show(ctx, |ui| {
-                        egui::Frame::none().fill(Color32::from_black_alpha(200)).rounding(8.0).inner_margin(8.0).show(ui, |ui| {
-                             ui.label(RichText::new(format!("Receiving: {} ({:.1}%)", inc.name, (inc.received_bytes as f32 / inc.size as f32) * 100.0)).color(Color32::WHITE));
-                             ui.add(egui::ProgressBar::new(inc.received_bytes as f32 / inc.size as f32).animate(true));
-                        });
+                        egui::Frame::none()
+                            .fill(Color32::from_black_alpha(200))
+                            .rounding(8.0)
+                            .inner_margin(8.0)
+                            .show(ui, |ui| {
+                                ui.set_max_width(TRANSFER_PANEL_WIDTH);
+                                ui.label(RichText::new("Transfers").strong().color(Color32::WHITE));
+                                for (id, inc) in &self.incoming_files {
+                                    let fraction =
+                                        Self::transfer_fraction(inc.received_bytes, inc.size);
+                                    let title = format!(
+                                        "Receiving: {} ({:.1}%)",
+                                        inc.name,
+                                        fraction * 100.0
+                                    );
+                                    if Self::draw_transfer_row(
+                                        ui,
+                                        title,
+                                        Some(inc.rate.summary(inc.size)),
+                                        Some(fraction),
+                                    ) {
+                                        cancel_clicked = Some((*id, false));
+                                    }
+                                }
+                                for (id, out) in &self.outgoing_files {
+                                    let clicked = if out.accepted {
+                                        let fraction =
+                                            Self::transfer_fraction(out.sent_bytes, out.total_size);
+                                        let title = format!(
+                                            "Sending: {} ({:.1}%)",
+                                            out.name,
+                                            fraction * 100.0
+                                        );
+                                        Self::draw_transfer_row(
+                                            ui,
+                                            title,
+                                            Some(Self::outgoing_detail(out)),
+                                            Some(fraction),
+                                        )
+                                    } else {
+                                        let title = format!("Offered: {}", out.name);
+                                        Self::draw_transfer_row(
+                                            ui,
+                                            title,
+                                            Some("Waiting for answer".to_string()),
+                                            None,
+                                        )
+                                    };
+                                    if clicked {
+                                        cancel_clicked = Some((*id, true));
+                                    }
+                                }
+                                for (id, queued) in &self.outgoing_queue {
+                                    if Self::draw_transfer_row(
+                                        ui,
+                                        format!("⏳ {} (queued)", queued.name),
+                                        None,
+                                        None,
+                                    ) {
+                                        cancel_clicked = Some((*id, true));
+                                    }
+                                }
+                                for done in self
+                                    .finished_transfers
+                                    .iter()
+                                    .rev()
+                                    .take(FINISHED_TRANSFERS_SHOWN)
+                                {
+                                    let arrow = if done.outgoing { "↑" } else { "↓" };
+                                    let (state, color) = match &done.outcome {
+                                        TransferOutcome::Completed => ("done", Color32::GREEN),
+                                        TransferOutcome::Rejected => ("rejected", Color32::YELLOW),
+                                        TransferOutcome::Failed(reason) => {
+                                            // Bien visible: la fila dice por qué y deja reintentar
+                                            ui.horizontal(|ui| {
+                                                ui.label(
+                                                    RichText::new(format!(
+                                                        "⚠ {} {} failed",
+                                                        arrow, done.name
+                                                    ))
+                                                    .strong()
+                                                    .color(Color32::RED),
+                                                );
+                                                if let Some(path) = &done.retry_path
+                                                    && ui.small_button("Retry").clicked()
+                                                {
+                                                    retry_clicked = Some(path.clone());
+                                                }
+                                            });
+                                            ui.label(
+                                                RichText::new(reason)
+                                                    .small()
+                                                    .color(Color32::LIGHT_RED),
+                                            );
+                                            continue;
+                                        }
+                                        TransferOutcome::Cancelled => {
+                                            ("cancelled", Color32::LIGHT_RED)
+                                        }
+                                    };
+                                    ui.horizontal(|ui| {
+                                        ui.label(
+                                            RichText::new(format!(
+                                                "{} {} ({})",
+                                                arrow, done.name, state
+                                            ))
+                                            .small()
+                                            .color(color),
+                                        );
+                                        if let Some(path) = &done.open_path
+                                            && ui.small_button("Open").clicked()
+                                        {
+                                            open_clicked = Some(path.clone());
+                                        }
+                                    });
+                                }
+                            });
                     });
             }
-            if let Some(out) = &self.outgoing_file {
-                 egui::Area::new("outgoing_progress".into())
-                    .anchor(Align2::RIGHT_BOTTOM, Vec2::new(-10.0, -100.0))
-                    .show(ctx, |ui| {
-                        egui::Frame::none().fill(Color32::from_black_alpha(200)).rounding(8.0).inner_margin(8.0).show(ui, |ui| {
-                             ui.label(RichText::new(format!("Sending: {} ({:.1}%)", out.name, (out.sent_bytes as f32 / out.total_size as f32) * 100.0)).color(Color32::WHITE));
-                             ui.add(egui::ProgressBar::new(out.sent_bytes as f32 / out.total_size as f32).animate(true));
-                        });
-                    });
+            match cancel_clicked {
+                Some((id, true)) => self.cancel_outgoing(id),
+                Some((id, false)) => self.cancel_incoming(id),
+                None => {}
+            }
+            if let Some(path) = open_clicked
+                && let Err(reason) = storage::open_with_system(&path)
+            {
+                self.status_message = Some(reason);
+            }
+            if let Some(path) = retry_clicked {
+                self.retry_outgoing(path);
+            }
+            if ctx.input(|i| !i.raw.hovered_files.is_empty()) {
+                Self::draw_drop_target(ctx);
             }
 
             // Floating Control Bar (Bottom)
@@ -612,90 +1313,279 @@ impl VideoCall {
                         .show(ui, |ui| {
                             ui.horizontal(|ui| {
                                 ui.add_space(10.0);
-                                
+
                                 // Mute Button
-                                let is_muted = self.audio_worker.as_ref().map(|w| w.is_muted()).unwrap_or(false);
-                                let (mute_icon, _mute_color) = if is_muted { 
-                                    ("🔇", crate::ui::theme::colors::DANGER) 
-                                } else { 
-                                    ("🎤", crate::ui::theme::colors::TEXT_PRIMARY) 
+                                let is_muted = self
+                                    .audio_worker
+                                    .as_ref()
+                                    .map(|w| w.is_muted())
+                                    .unwrap_or(false);
+                                let (mute_icon, _mute_color) = if is_muted {
+                                    ("🔇", crate::ui::theme::colors::DANGER)
+                                } else {
+                                    ("🎤", crate::ui::theme::colors::TEXT_PRIMARY)
                                 };
-                                
+
                                 let mute_btn = Button::new(RichText::new(mute_icon).size(24.0))
-                                    .fill(if is_muted { crate::ui::theme::colors::BACKGROUND_SECONDARY } else { crate::ui::theme::colors::BACKGROUND })
+                                    .fill(if is_muted {
+                                        crate::ui::theme::colors::BACKGROUND_SECONDARY
+                                    } else {
+                                        crate::ui::theme::colors::BACKGROUND
+                                    })
                                     .frame(true)
                                     .rounding(30.0)
                                     .min_size(Vec2::new(50.0, 50.0));
-                                    
-                                if ui.add(mute_btn).on_hover_text("Toggle Mute").clicked() {
+
+                                // En espera el micrófono queda pausado hasta retomar
+                                let mute_response =
+                                    ui.add_enabled(self.hold != Some(Hold::Local), mute_btn);
+                                if mute_response.on_hover_text("Toggle Mute").clicked() {
                                     if let Some(audio) = &self.audio_worker {
                                         audio.toggle_mute();
                                     }
                                 }
-                                
+                                if let Some(audio) = &self.audio_worker {
+                                    Self::draw_level_meter(ui, audio.input_level())
+                                        .on_hover_text("Microphone level");
+                                }
+
+                                ui.add_space(20.0);
+
+                                // Speaker: mute and volume of the remote audio
+                                let speaker_icon = if self.output_muted { "🔈" } else { "🔊" };
+                                let speaker_btn =
+                                    Button::new(RichText::new(speaker_icon).size(24.0))
+                                        .fill(if self.output_muted {
+                                            crate::ui::theme::colors::BACKGROUND_SECONDARY
+                                        } else {
+                                            crate::ui::theme::colors::BACKGROUND
+                                        })
+                                        .rounding(30.0)
+                                        .min_size(Vec2::new(50.0, 50.0));
+                                let speaker_hover = if self.output_muted {
+                                    "Unmute Speaker"
+                                } else {
+                                    "Mute Speaker"
+                                };
+                                if ui.add(speaker_btn).on_hover_text(speaker_hover).clicked() {
+                                    self.output_muted = !self.output_muted;
+                                    if let Some(audio) = &self.audio_worker {
+                                        audio.set_output_muted(self.output_muted);
+                                    }
+                                }
+                                if let Some(audio) = &self.audio_worker {
+                                    Self::draw_level_meter(ui, audio.output_level())
+                                        .on_hover_text("Incoming audio level");
+                                }
+                                let volume =
+                                    egui::Slider::new(&mut self.output_gain, 0.0..=MAX_OUTPUT_GAIN)
+                                        .show_value(false);
+                                let volume_response =
+                                    ui.add_enabled(!self.output_muted, volume).on_hover_text(
+                                        format!("Volume {:.0}%", self.output_gain * 100.0),
+                                    );
+                                if volume_response.changed()
+                                    && let Some(audio) = &self.audio_worker
+                                {
+                                    audio.set_output_gain(self.output_gain);
+                                }
+
                                 ui.add_space(20.0);
-                                
+
                                 // Video Toggle (Placeholder)
                                 let video_btn = Button::new(RichText::new("📷").size(24.0))
                                     .fill(crate::ui::theme::colors::BACKGROUND)
                                     .rounding(30.0)
                                     .min_size(Vec2::new(50.0, 50.0));
                                 ui.add(video_btn).on_hover_text("Toggle Video");
-                                
+
+                                ui.add_space(20.0);
+
+                                // Hold: either side can resume
+                                let (hold_icon, hold_hover) = if self.hold.is_some() {
+                                    ("▶", "Resume Call")
+                                } else {
+                                    ("⏸", "Hold Call")
+                                };
+                                let hold_btn = Button::new(RichText::new(hold_icon).size(24.0))
+                                    .fill(if self.hold.is_some() {
+                                        crate::ui::theme::colors::PRIMARY
+                                    } else {
+                                        crate::ui::theme::colors::BACKGROUND
+                                    })
+                                    .rounding(30.0)
+                                    .min_size(Vec2::new(50.0, 50.0));
+                                if ui
+                                    .add_enabled(self.media_started, hold_btn)
+                                    .on_hover_text(hold_hover)
+                                    .clicked()
+                                {
+                                    if self.hold.is_some() {
+                                        self.resume_call();
+                                    } else {
+                                        self.hold_call();
+                                    }
+                                }
+
+                                ui.add_space(20.0);
+
+                                // Quality presets (renegotiated in-band)
+                                ui.menu_button(RichText::new("🎞").size(24.0), |ui| {
+                                    let current = self
+                                        .client
+                                        .as_ref()
+                                        .and_then(|client| client.video_params())
+                                        .map(|params| params.format());
+                                    for option in self.quality_options() {
+                                        let label = format!(
+                                            "{}x{} @ {} fps",
+                                            option.width, option.height, option.fps
+                                        );
+                                        let selected = current == Some(option.format());
+                                        if ui.selectable_label(selected, label).clicked() {
+                                            self.change_video_params(option);
+                                            ui.close_menu();
+                                        }
+                                    }
+                                })
+                                .response
+                                .on_hover_text("Video Quality");
+
                                 ui.add_space(20.0);
 
                                 // Stats Toggle Button
                                 let stats_icon = "📊";
                                 let stats_btn = Button::new(RichText::new(stats_icon).size(24.0))
-                                    .fill(if self.show_stats { crate::ui::theme::colors::PRIMARY } else { crate::ui::theme::colors::BACKGROUND })
+                                    .fill(if self.show_stats {
+                                        crate::ui::theme::colors::PRIMARY
+                                    } else {
+                                        crate::ui::theme::colors::BACKGROUND
+                                    })
                                     .rounding(30.0)
                                     .min_size(Vec2::new(50.0, 50.0));
-                                if ui.add(stats_btn).on_hover_text("Toggle Statistics").clicked() {
+                                if ui
+                                    .add(stats_btn)
+                                    .on_hover_text("Toggle Statistics")
+                                    .clicked()
+                                {
                                     self.show_stats = !self.show_stats;
                                 }
 
                                 ui.add_space(20.0);
-                                
+
+                                // Safety number, once DTLS is up
+                                let security_btn = Button::new(RichText::new("🔒").size(24.0))
+                                    .fill(if self.show_security {
+                                        crate::ui::theme::colors::PRIMARY
+                                    } else if matches!(
+                                        self.key_check,
+                                        Some(KeyCheck::Changed { .. })
+                                    ) {
+                                        crate::ui::theme::colors::DANGER
+                                    } else {
+                                        crate::ui::theme::colors::BACKGROUND
+                                    })
+                                    .rounding(30.0)
+                                    .min_size(Vec2::new(50.0, 50.0));
+                                if ui
+                                    .add_enabled(self.safety_number.is_some(), security_btn)
+                                    .on_hover_text("Verify Security")
+                                    .on_disabled_hover_text("The connection is not secured yet")
+                                    .clicked()
+                                {
+                                    self.show_security = !self.show_security;
+                                }
+
+                                ui.add_space(20.0);
+
+                                // Snapshot of the remote video, once there is a frame
+                                let snapshot_btn = Button::new(RichText::new("📸").size(24.0))
+                                    .fill(crate::ui::theme::colors::BACKGROUND)
+                                    .rounding(30.0)
+                                    .min_size(Vec2::new(50.0, 50.0));
+                                let can_snapshot =
+                                    self.last_remote_frame.is_some() && self.snapshot_rx.is_none();
+                                if ui
+                                    .add_enabled(can_snapshot, snapshot_btn)
+                                    .on_hover_text("Save Snapshot")
+                                    .on_disabled_hover_text(
+                                        "No video from the other participant yet",
+                                    )
+                                    .clicked()
+                                {
+                                    self.take_snapshot();
+                                }
+
+                                ui.add_space(20.0);
+
                                 // File Send Button
                                 let file_btn = Button::new(RichText::new("📎").size(24.0))
                                     .fill(crate::ui::theme::colors::BACKGROUND)
                                     .rounding(30.0)
                                     .min_size(Vec2::new(50.0, 50.0));
-                                if ui.add(file_btn).on_hover_text("Send File").clicked() {
-                                     // Spawn file picker thread
-                                     if let Some(client) = self.client.clone() {
-                                         let sctp_inc = client.sctp_incoming.clone();
-                                         thread::spawn(move || {
-                                            if let Some(path) = FileDialog::new().pick_file() {
-                                                let path_str = path.to_string_lossy().to_string();
-                                                if let Ok(guard) = sctp_inc.lock() {
-                                                    if let Some(tx) = guard.as_ref() {
-                                                        // Use stream 999 for internal path passing
-                                                        let _ = tx.send((999, path_str.into_bytes()));
-                                                    }
+                                if ui.add(file_btn).on_hover_text("Send Files").clicked() {
+                                    // Spawn file picker thread
+                                    if let Some(client) = self.client.clone() {
+                                        let sctp_inc = client.sctp_incoming.clone();
+                                        thread::spawn(move || {
+                                            let paths =
+                                                FileDialog::new().pick_files().unwrap_or_default();
+                                            if let Ok(guard) = sctp_inc.lock()
+                                                && let Some(tx) = guard.as_ref()
+                                            {
+                                                for path in paths {
+                                                    let path_str =
+                                                        path.to_string_lossy().to_string();
+                                                    // Internal stream for path passing, one message per file
+                                                    let _ = tx.send((
+                                                        LOCAL_FILE_SELECTED_STREAM,
+                                                        path_str.into_bytes(),
+                                                    ));
                                                 }
                                             }
-                                         });
-                                     }
+                                        });
+                                    }
                                 }
-                                
+
+                                ui.add_space(20.0);
+
+                                // Transfer the call to an available user
+                                ui.menu_button(RichText::new("↪").size(24.0), |ui| {
+                                    if transfer_targets.is_empty() {
+                                        ui.label("No available users");
+                                    }
+                                    for target in transfer_targets {
+                                        if ui.button(target).clicked() {
+                                            self.status_message =
+                                                Some(format!("Transferring call to {}...", target));
+                                            next_action =
+                                                Some(VideoMeetAction::Transfer(target.clone()));
+                                            ui.close_menu();
+                                        }
+                                    }
+                                })
+                                .response
+                                .on_hover_text("Transfer Call");
+
                                 ui.add_space(20.0);
 
                                 // Hangup Button
-                                let hangup_btn = Button::new(RichText::new("📞").size(24.0).color(egui::Color32::WHITE))
-                                    .fill(crate::ui::theme::colors::DANGER)
-                                    .rounding(30.0)
-                                    .min_size(Vec2::new(60.0, 50.0));
-                                    
+                                let hangup_btn = Button::new(
+                                    RichText::new("📞").size(24.0).color(egui::Color32::WHITE),
+                                )
+                                .fill(crate::ui::theme::colors::DANGER)
+                                .rounding(30.0)
+                                .min_size(Vec2::new(60.0, 50.0));
+
                                 if ui.add(hangup_btn).on_hover_text("End Call").clicked() {
                                     if let Some(client) = self.client.as_mut() {
-                                        Self::send_hangup_signal(client);
+                                        CallController::send_hangup(client);
                                     }
                                     self.stop_current_call();
                                     self.status_message = Some("Call Ended".to_string());
                                     next_action = Some(VideoMeetAction::GoToLobby);
                                 }
-                                
+
                                 ui.add_space(10.0);
                             });
                         });
@@ -705,39 +1595,397 @@ impl VideoCall {
         next_action
     }
 
-    fn update_texture(
-        ctx: &egui::Context,
-        handle: &mut Option<TextureHandle>,
-        name: &str,
-        image: ColorImage,
-    ) {
-        match handle {
-            Some(texture) => texture.set(image, TextureOptions::LINEAR),
-            None => {
-                *handle = Some(ctx.load_texture(name.to_string(), image, TextureOptions::LINEAR));
+    /// Lo que ofrece el menú de calidad: los formatos que reportó la cámara,
+    /// del más grande al más chico, o los presets si no se sabe cuáles
+    /// tiene.
+    fn quality_options(&self) -> Vec<VideoParams> {
+        let formats = self
+            .client
+            .as_ref()
+            .map(|client| client.supported_video_formats())
+            .unwrap_or_default();
+        if formats.is_empty() {
+            return Self::quality_presets(self.video).to_vec();
+        }
+        formats
+            .into_iter()
+            .rev()
+            .map(|format| {
+                if format == self.video.format() {
+                    self.video
+                } else {
+                    self.video.scaled_to(format)
+                }
+            })
+            .collect()
+    }
+
+    /// Configured resolution plus two lighter fallbacks. A configured
+    /// bitrate cap still applies to them.
+    fn quality_presets(base: VideoParams) -> [VideoParams; 3] {
+        [
+            base,
+            base.scaled_to(VideoFormat::new(640, 360, base.fps)),
+            base.scaled_to(VideoFormat::new(320, 240, 15)),
+        ]
+    }
+
+    fn change_video_params(&mut self, params: VideoParams) {
+        let Some(client) = self.client.as_mut() else {
+            return;
+        };
+        match client.renegotiate_video(params) {
+            Ok(applied) => {
+                self.status_message = Some(format!(
+                    "Video: {}x{} @ {} fps",
+                    applied.width, applied.height, applied.fps
+                ));
+            }
+            Err(e) => {
+                log_warn!("No se pudo cambiar la calidad de video: {}", e);
+            }
+        }
+    }
+
+    /// Escribe un chunk de un archivo entrante. Si se pasa del tamaño
+    /// anunciado devuelve el error y el llamador corta la transferencia.
+    fn write_incoming_chunk(inc: &mut IncomingFile, payload: &[u8]) -> Result<(), String> {
+        inc.last_chunk_at = Instant::now();
+        if inc.received_bytes + payload.len() > inc.size {
+            return Err(format!(
+                "Transfer of {} aborted: more data than announced",
+                inc.name
+            ));
+        }
+        if let Some(f) = &mut inc.file_handle {
+            if let Err(e) = f.write_all(payload) {
+                log_warn!("File write error: {}", e);
+            } else {
+                inc.received_bytes += payload.len();
+                inc.rate.record(inc.received_bytes);
             }
         }
+        Ok(())
+    }
+
+    fn send_file_answer(client: &P2PClient, id: u64, accepted: bool) {
+        let ans = FileTransferMessage::Answer { id, accepted };
+        if let Ok(json) = serde_json::to_string(&ans) {
+            let _ = client.send_sctp_data(FILE_CONTROL_STREAM, json.into_bytes());
+        }
     }
 
-    fn draw_video_slot(
+    /// Fila del panel de transferencias. Devuelve si se tocó la ✖.
+    fn draw_transfer_row(
         ui: &mut egui::Ui,
-        texture: Option<&TextureHandle>,
-        placeholder: &str,
-        target_size: Vec2,
-    ) {
-        let video_size = target_size;
+        title: String,
+        detail: Option<String>,
+        progress: Option<f32>,
+    ) -> bool {
+        let mut cancel = false;
+        ui.horizontal(|ui| {
+            ui.label(RichText::new(title).color(Color32::WHITE));
+            cancel = ui
+                .small_button("✖")
+                .on_hover_text("Cancel transfer")
+                .clicked();
+        });
+        if let Some(detail) = detail {
+            ui.label(RichText::new(detail).small().color(Color32::LIGHT_GRAY));
+        }
+        if let Some(fraction) = progress {
+            ui.add(egui::ProgressBar::new(fraction).animate(true));
+        }
+        cancel
+    }
 
-        ui.group(|ui| {
-            ui.vertical_centered(|ui| {
-                if let Some(texture) = texture {
-                    let tex_size = texture.size_vec2();
-                    let aspect = if tex_size.y > 0.0 {
-                        tex_size.x / tex_size.y
-                    } else {
-                        video_size.x / video_size.y
-                    };
+    /// Highlight over the whole window while files are dragged over it.
+    fn draw_drop_target(ctx: &egui::Context) {
+        let painter = ctx.layer_painter(egui::LayerId::new(
+            egui::Order::Foreground,
+            egui::Id::new("file_drop_target"),
+        ));
+        let rect = ctx.screen_rect().shrink(12.0);
+        painter.rect(
+            rect,
+            16.0,
+            Color32::from_black_alpha(160),
+            egui::Stroke::new(3.0, crate::ui::theme::colors::PRIMARY),
+        );
+        painter.text(
+            rect.center(),
+            Align2::CENTER_CENTER,
+            "Drop files to send",
+            FontId::proportional(28.0),
+            Color32::WHITE,
+        );
+    }
 
-                    let mut size = video_size;
+    /// Medidor vertical de los botones de audio: el RMS llena la barra y el
+    /// pico queda marcado con una línea; en amarillo si el pico satura.
+    fn draw_level_meter(ui: &mut egui::Ui, level: AudioLevel) -> egui::Response {
+        let (rect, response) = ui.allocate_exact_size(Vec2::new(6.0, 36.0), egui::Sense::hover());
+        let painter = ui.painter();
+        painter.rect_filled(rect, 3.0, crate::ui::theme::colors::BACKGROUND);
+
+        let color = if level.peak >= CLIPPING_LEVEL {
+            crate::ui::theme::colors::WARNING
+        } else {
+            crate::ui::theme::colors::SUCCESS
+        };
+        let mut bar = rect;
+        bar.set_top(rect.bottom() - rect.height() * meter_fraction(level.rms));
+        painter.rect_filled(bar, 3.0, color);
+
+        let peak = meter_fraction(level.peak);
+        if peak > 0.0 {
+            let y = rect.bottom() - rect.height() * peak;
+            painter.hline(rect.x_range(), y, egui::Stroke::new(1.0, color));
+        }
+        response
+    }
+
+    fn clear_peer_key(&mut self) {
+        self.key_check = None;
+        self.remote_fingerprint = None;
+        self.safety_number = None;
+        self.show_security = false;
+    }
+
+    /// Al terminar el handshake DTLS compara el certificado del peer con el
+    /// fijado para él. Una vez por llamada.
+    fn check_peer_key(&mut self) {
+        if self.key_check.is_some() {
+            return;
+        }
+        let (Some(client), Some(peer)) = (self.client.as_ref(), self.peer_username.as_deref())
+        else {
+            return;
+        };
+        let Some(fingerprint) = client.remote_fingerprint() else {
+            return;
+        };
+        let check = check_pinned_key(&self.pinned_keys, peer, &fingerprint);
+        match &check {
+            KeyCheck::Unpinned => {
+                log_debug!("Certificado de {} sin fijar: {}", peer, fingerprint);
+            }
+            KeyCheck::Verified => {
+                log_info!("Certificado de {} verificado, es el fijado", peer);
+            }
+            KeyCheck::Changed { pinned } => {
+                log_warn!(
+                    "El certificado de {} no es el fijado: se esperaba {} y llegó {}",
+                    peer,
+                    pinned,
+                    fingerprint
+                );
+            }
+        }
+        self.safety_number = client.safety_number();
+        self.remote_fingerprint = Some(fingerprint);
+        self.key_check = Some(check);
+    }
+
+    /// Ventana con el número de seguridad para compararlo de palabra con el
+    /// peer; si coincide, el usuario puede fijar su certificado.
+    fn show_security_window(&mut self, ctx: &egui::Context) -> Option<VideoMeetAction> {
+        let (Some(number), Some(check), Some(peer)) = (
+            self.safety_number.clone(),
+            self.key_check.clone(),
+            self.peer_username.clone(),
+        ) else {
+            return None;
+        };
+        let mut action = None;
+        let mut open = self.show_security;
+        egui::Window::new("🔒 Verify security")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .anchor(Align2::CENTER_TOP, Vec2::new(0.0, 40.0))
+            .show(ctx, |ui| {
+                ui.label(format!(
+                    "Read these numbers to {} over another channel (in person or on the phone). \
+                     If theirs are the same, nobody is between you.",
+                    peer
+                ));
+                ui.add_space(8.0);
+                let groups: Vec<&str> = number.split(' ').collect();
+                for line in groups.chunks(3) {
+                    ui.label(RichText::new(line.join("  ")).monospace().size(22.0));
+                }
+                ui.add_space(8.0);
+                let pin_label = match &check {
+                    KeyCheck::Verified => {
+                        ui.colored_label(
+                            crate::ui::theme::colors::SUCCESS,
+                            format!("✔ Same key you verified for {}", peer),
+                        );
+                        None
+                    }
+                    KeyCheck::Changed { .. } => {
+                        ui.colored_label(
+                            crate::ui::theme::colors::DANGER,
+                            format!("⚠ {} is not using the key you verified", peer),
+                        );
+                        Some("They match, trust the new key")
+                    }
+                    KeyCheck::Unpinned => {
+                        ui.label(
+                            RichText::new(format!("You haven't verified {} yet", peer))
+                                .color(crate::ui::theme::colors::TEXT_MUTED),
+                        );
+                        Some("They match, remember this key")
+                    }
+                };
+                if let Some(label) = pin_label
+                    && let Some(fingerprint) = self.remote_fingerprint.clone()
+                    && ui.button(label).clicked()
+                {
+                    self.pinned_keys.insert(peer.clone(), fingerprint.clone());
+                    self.key_check = Some(KeyCheck::Verified);
+                    action = Some(VideoMeetAction::PinKey {
+                        peer: peer.clone(),
+                        fingerprint,
+                    });
+                }
+            });
+        self.show_security = open;
+        action
+    }
+
+    /// Deja en el log cada cambio de banda de la calidad con las métricas
+    /// que la movieron.
+    fn track_quality(&mut self) {
+        let Some(metrics) = &self.quality_metrics else {
+            return;
+        };
+        let Some(level) = QualityLevel::from_score(metrics.quality_score) else {
+            return;
+        };
+        if let Some(previous) = self.quality_level
+            && previous != level
+        {
+            log_info!(
+                "Calidad de la llamada: {} -> {} ({:.1}/5; {})",
+                previous,
+                level,
+                metrics.quality_score,
+                metrics.quality_factors
+            );
+        }
+        self.quality_level = Some(level);
+    }
+
+    /// Lo que muestra el indicador de calidad: puntaje, banda y el detalle
+    /// del tooltip. Sin media hace más de 2 s es lo peor aunque el último
+    /// puntaje fuera bueno.
+    fn quality_indicator(&self) -> Option<(f32, QualityLevel, String)> {
+        let metrics = self.quality_metrics.as_ref()?;
+        if self.unstable {
+            return Some((
+                1.0,
+                QualityLevel::Poor,
+                "No media received for over 2 s".to_string(),
+            ));
+        }
+        let level = QualityLevel::from_score(metrics.quality_score)?;
+        let detail = format!(
+            "Quality {:.1}/5\n{}",
+            metrics.quality_score, metrics.quality_factors
+        );
+        Some((metrics.quality_score, level, detail))
+    }
+
+    fn quality_color(level: QualityLevel) -> Color32 {
+        match level {
+            QualityLevel::Good => crate::ui::theme::colors::SUCCESS,
+            QualityLevel::Fair => crate::ui::theme::colors::WARNING,
+            QualityLevel::Poor => crate::ui::theme::colors::DANGER,
+        }
+    }
+
+    /// Barras de señal: tantas llenas como el puntaje redondeado, en el
+    /// color de su banda.
+    fn draw_quality_bars(ui: &mut egui::Ui, score: f32, level: QualityLevel) -> egui::Response {
+        const BARS: usize = 5;
+        const BAR_WIDTH: f32 = 3.0;
+        const GAP: f32 = 1.5;
+        let (rect, response) = ui.allocate_exact_size(Vec2::new(22.0, 14.0), egui::Sense::hover());
+        let painter = ui.painter();
+        let filled = score.round().clamp(1.0, BARS as f32) as usize;
+        for i in 0..BARS {
+            let height = rect.height() * (i + 1) as f32 / BARS as f32;
+            let x = rect.left() + i as f32 * (BAR_WIDTH + GAP);
+            let bar = egui::Rect::from_min_max(
+                egui::pos2(x, rect.bottom() - height),
+                egui::pos2(x + BAR_WIDTH, rect.bottom()),
+            );
+            let color = if i < filled {
+                Self::quality_color(level)
+            } else {
+                crate::ui::theme::colors::TEXT_MUTED
+            };
+            painter.rect_filled(bar, 1.0, color);
+        }
+        response
+    }
+
+    /// Velocidad del envío. Con los contadores SCTP cuenta lo confirmado por
+    /// el peer y avisa si la cola espera a la ventana de congestión.
+    fn outgoing_detail(out: &OutgoingFile) -> String {
+        match out.flow {
+            Some(flow) if flow.is_congestion_limited() => format!(
+                "{}, congestion-limited",
+                out.acked_rate.summary(out.total_size)
+            ),
+            Some(_) => out.acked_rate.summary(out.total_size),
+            None => out.rate.summary(out.total_size),
+        }
+    }
+
+    fn transfer_fraction(done: usize, total: usize) -> f32 {
+        if total == 0 {
+            1.0
+        } else {
+            done as f32 / total as f32
+        }
+    }
+
+    pub(crate) fn update_texture(
+        ctx: &egui::Context,
+        handle: &mut Option<TextureHandle>,
+        name: &str,
+        image: ColorImage,
+    ) {
+        match handle {
+            Some(texture) => texture.set(image, TextureOptions::LINEAR),
+            None => {
+                *handle = Some(ctx.load_texture(name.to_string(), image, TextureOptions::LINEAR));
+            }
+        }
+    }
+
+    pub(crate) fn draw_video_slot(
+        ui: &mut egui::Ui,
+        texture: Option<&TextureHandle>,
+        placeholder: &str,
+        target_size: Vec2,
+    ) {
+        let video_size = target_size;
+
+        ui.group(|ui| {
+            ui.vertical_centered(|ui| {
+                if let Some(texture) = texture {
+                    let tex_size = texture.size_vec2();
+                    let aspect = if tex_size.y > 0.0 {
+                        tex_size.x / tex_size.y
+                    } else {
+                        video_size.x / video_size.y
+                    };
+
+                    let mut size = video_size;
                     if aspect > 0.0 {
                         size.y = size.x / aspect;
                     }
@@ -760,7 +2008,7 @@ impl VideoCall {
         });
     }
 
-    fn mat_to_color_image(mat: &Mat) -> Option<ColorImage> {
+    pub(crate) fn mat_to_color_image(mat: &Mat) -> Option<ColorImage> {
         let width = mat.cols();
         let height = mat.rows();
 
@@ -799,27 +2047,92 @@ impl VideoCall {
         Some(ColorImage::from_rgba_unmultiplied([width, height], &rgba))
     }
 
+    /// Guarda el último frame del peer donde elija el usuario. El diálogo
+    /// corre en otro hilo para no frenar el video.
+    fn take_snapshot(&mut self) {
+        let Some(frame) = self
+            .last_remote_frame
+            .as_ref()
+            .and_then(|frame| frame.try_clone().ok())
+        else {
+            return;
+        };
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|since| since.as_secs())
+            .unwrap_or_default();
+        let name = snapshot::default_file_name(self.peer_username.as_deref(), now);
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let Some(path) = FileDialog::new()
+                .set_file_name(&name)
+                .add_filter("Image", &["png", "jpg", "jpeg", "webp"])
+                .save_file()
+            else {
+                // Cancelado: se cierra el canal sin mandar nada
+                return;
+            };
+            let _ = tx.send(snapshot::save(&frame, &path));
+        });
+        self.snapshot_rx = Some(rx);
+    }
+
+    fn poll_snapshot(&mut self) {
+        let Some(rx) = &self.snapshot_rx else {
+            return;
+        };
+        match rx.try_recv() {
+            Ok(Ok(path)) => {
+                log_info!("Captura guardada en {}", path.display());
+                self.status_message = Some(format!("Snapshot saved to {}", path.display()));
+            }
+            Ok(Err(err)) => {
+                log_warn!("{}", err);
+                self.status_message = Some(err);
+            }
+            Err(TryRecvError::Empty) => return,
+            Err(TryRecvError::Disconnected) => {}
+        }
+        self.snapshot_rx = None;
+    }
+
     fn consume_remote_messages(&mut self) -> bool {
         if let Some(inbox) = &self.message_inbox
             && let Ok(messages) = inbox.lock()
         {
-            let total = messages.len();
-            if self.processed_messages < total {
-                for msg in messages.iter().skip(self.processed_messages) {
-                    if msg.trim() == "CALL_END" {
-                        self.status_message =
-                            Some("El otro participante colgó la llamada.".to_string());
-                        self.processed_messages = total;
-                        return true;
-                    }
-                }
-                self.processed_messages = total;
+            let dropped = messages.dropped_since(self.processed_messages);
+            if dropped > 0 {
+                log_warn!("Se descartaron {} mensajes del peer sin leer", dropped);
+            }
+            let hangup = messages
+                .since(self.processed_messages)
+                .any(|msg| msg.trim() == "CALL_END");
+            self.processed_messages = messages.total();
+            if hangup {
+                self.status_message = Some("El otro participante colgó la llamada.".to_string());
+                return true;
             }
         }
 
         false
     }
 
+    /// Deja la llamada andando con lo que devolvió el arranque del video. Si
+    /// la cámara falló sigue solo con audio (y el video del peer, si llega).
+    fn finish_media_start(&mut self, result: Result<P2PClient, (P2PClient, String)>) {
+        let client = match result {
+            Ok(client) => client,
+            Err((client, err)) => {
+                log_warn!("No se pudo arrancar el video, sigue solo audio: {}", err);
+                self.video_unavailable = true;
+                client
+            }
+        };
+        self.client = Some(client);
+        self.media_started = true;
+        self.status_message = None;
+    }
+
     fn stop_current_call(&mut self) {
         if let Some(client) = self.client.as_mut() {
             client.stop_media();
@@ -827,28 +2140,829 @@ impl VideoCall {
         self.media_started = false;
         self.local_texture = None;
         self.remote_texture = None;
+        self.hold = None;
         self.reset_file_transfer_state();
     }
 
+    /// Pone la llamada en espera: pausa cámara y micrófono y avisa al peer.
+    fn hold_call(&mut self) {
+        let Some(client) = self.client.as_ref() else {
+            return;
+        };
+        if let Err(err) = client.send_hold(true) {
+            self.status_message = Some(format!("Could not put the call on hold: {}", err));
+            return;
+        }
+        self.pause_local_media(true);
+        self.hold = Some(Hold::Local);
+    }
+
+    /// Retoma la llamada, la haya puesto en espera quien sea.
+    fn resume_call(&mut self) {
+        if let Some(client) = self.client.as_ref()
+            && let Err(err) = client.send_hold(false)
+        {
+            self.status_message = Some(format!("Could not resume the call: {}", err));
+            return;
+        }
+        self.end_hold();
+    }
+
+    /// Terminó la espera, de este lado o del peer: vuelve la media propia
+    /// sin renegociar nada.
+    fn end_hold(&mut self) {
+        if self.hold == Some(Hold::Local) {
+            self.pause_local_media(false);
+        }
+        self.hold = None;
+        // Durante la espera el peer pudo no mandar video
+        self.last_remote_seen = Some(std::time::Instant::now());
+    }
+
+    fn pause_local_media(&mut self, paused: bool) {
+        if let Some(client) = self.client.as_ref() {
+            client.set_video_enabled(!paused);
+        }
+        if let Some(audio) = &self.audio_worker {
+            if paused {
+                self.muted_before_hold = audio.is_muted();
+                audio.set_muted(true);
+            } else {
+                audio.set_muted(self.muted_before_hold);
+            }
+        }
+    }
+
     fn reset_file_transfer_state(&mut self) {
-        self.incoming_file = None;
-        self.outgoing_file = None;
-        self.pending_offer = None;
+        self.incoming_files.clear();
+        for out in self.outgoing_files.values() {
+            out.cancel.store(true, Ordering::Relaxed);
+        }
+        self.outgoing_files.clear();
+        self.outgoing_queue.clear();
+        self.finished_transfers.clear();
+        self.pending_offers.clear();
+        self.files_on_connect.clear();
+    }
+
+    /// Id for the next file we offer. Each side uses its own parity, so ids
+    /// from both directions never clash.
+    fn next_transfer_id(&mut self, client: &P2PClient) -> u64 {
+        let controlling = matches!(client.role(), PeerConnectionRole::Controlling);
+        let id = transfer_id(self.next_transfer_seq, controlling);
+        self.next_transfer_seq += 1;
+        id
     }
 
-    fn send_hangup_signal(client: &P2PClient) {
-        if let Err(err) = client.send_rtcp_bye() {
-            eprintln!("Error enviando RTCP BYE: {:?}", err);
-            if let Err(msg_err) = client.send_msg("CALL_END") {
-                eprintln!("Error enviando fin de llamada: {:?}", msg_err);
+    /// X on an outgoing row: a queued file just leaves the queue; an offered
+    /// or running one stops its sender thread and tells the peer.
+    fn cancel_outgoing(&mut self, id: u64) {
+        let Some(client) = self.client.clone() else {
+            return;
+        };
+        if let Some(pos) = self
+            .outgoing_queue
+            .iter()
+            .position(|(queued, _)| *queued == id)
+        {
+            if let Some((_, out)) = self.outgoing_queue.remove(pos) {
+                self.finish_transfer(out.name, true, TransferOutcome::Cancelled);
             }
+            return;
         }
+        let Some(out) = self.outgoing_files.remove(&id) else {
+            return;
+        };
+        out.cancel.store(true, Ordering::Relaxed);
+        Self::send_file_cancel(&client, id, "cancelled by sender");
+        self.status_message = Some(format!("Cancelled sending {}", out.name));
+        self.finish_transfer(out.name, true, TransferOutcome::Cancelled);
+        self.start_next_outgoing(&client);
+    }
+
+    /// X on an incoming row: drops the partial file and tells the peer.
+    fn cancel_incoming(&mut self, id: u64) {
+        let Some(client) = self.client.clone() else {
+            return;
+        };
+        let Some(inc) = self.incoming_files.remove(&id) else {
+            return;
+        };
+        Self::send_file_cancel(&client, id, "cancelled by receiver");
+        Self::close_data_stream(&client, data_stream_for(id));
+        self.status_message = Some(format!("Cancelled receiving {}", inc.name));
+        self.finish_transfer(inc.name.clone(), false, TransferOutcome::Cancelled);
+        Self::discard_incoming(inc);
+    }
+
+    /// The peer sent `Cancel`. It may have crossed the end of the stream
+    /// (ours, or its own reached us first), so a cancel for an id we no longer track is
+    /// ignored.
+    fn remote_cancelled(&mut self, client: &P2PClient, id: u64, reason: &str) {
+        let who = self
+            .peer_username
+            .clone()
+            .unwrap_or_else(|| "Peer".to_string());
+        if let Some(inc) = self.incoming_files.remove(&id) {
+            self.status_message = Some(format!("{} cancelled the transfer of {}", who, inc.name));
+            self.finish_transfer(inc.name.clone(), false, TransferOutcome::Cancelled);
+            Self::discard_incoming(inc);
+        } else if let Some(out) = self.outgoing_files.remove(&id) {
+            out.cancel.store(true, Ordering::Relaxed);
+            self.status_message = Some(format!("{} cancelled the transfer of {}", who, out.name));
+            self.finish_transfer(out.name, true, TransferOutcome::Cancelled);
+            self.start_next_outgoing(client);
+        } else if let Some(pos) = self.pending_offers.iter().position(|offer| offer.id == id) {
+            if let Some(offer) = self.pending_offers.remove(pos) {
+                self.status_message = Some(format!("{} withdrew the offer of {}", who, offer.name));
+                self.finish_transfer(offer.name, false, TransferOutcome::Cancelled);
+            }
+        } else {
+            log_debug!("Cancel de transferencia desconocida {} ({})", id, reason);
+        }
+    }
+
+    fn send_file_cancel(client: &P2PClient, id: u64, reason: &str) {
+        let cancel = FileTransferMessage::Cancel {
+            id,
+            reason: reason.to_string(),
+        };
+        if let Ok(json) = serde_json::to_string(&cancel) {
+            let _ = client.send_sctp_data(FILE_CONTROL_STREAM, json.into_bytes());
+        }
+    }
+
+    /// Closes and deletes a partially received file.
+    fn discard_incoming(mut inc: IncomingFile) {
+        inc.file_handle = None;
+        if let Some(path) = inc.path.take()
+            && let Err(e) = std::fs::remove_file(&path)
+        {
+            log_warn!("No se pudo borrar {}: {}", path.display(), e);
+        }
+    }
+
+    /// Applies what the sender threads reported since the last frame.
+    fn poll_sender_events(&mut self, client: &P2PClient) {
+        // Lo que el hilo manda puede quedar encolado en SCTP; la velocidad
+        // real es la de lo que el peer confirma
+        for (id, out) in self
+            .outgoing_files
+            .iter_mut()
+            .filter(|(_, out)| out.accepted)
+        {
+            out.flow = client.data_channel(data_stream_for(*id)).stats();
+            if let Some(flow) = out.flow {
+                out.acked_rate.record(flow.bytes_acked as usize);
+            }
+        }
+        let events: Vec<(u64, SenderEvent)> = self.sender_rx.try_iter().collect();
+        for (id, event) in events {
+            // If a Cancel got here first, the transfer is already gone
+            if let SenderEvent::Progress(sent) = event {
+                if let Some(out) = self.outgoing_files.get_mut(&id) {
+                    out.sent_bytes += sent as usize;
+                    out.rate.record(out.sent_bytes);
+                }
+                continue;
+            }
+            let Some(out) = self.outgoing_files.remove(&id) else {
+                continue;
+            };
+            match event {
+                SenderEvent::Completed => {
+                    self.status_message = Some(format!("Sent file: {}", out.name));
+                    self.finish_transfer(out.name, true, TransferOutcome::Completed);
+                }
+                SenderEvent::CancelledByPeer => {
+                    self.status_message =
+                        Some(format!("Transfer of {} cancelled by peer", out.name));
+                    self.finish_transfer(out.name, true, TransferOutcome::Cancelled);
+                }
+                SenderEvent::Failed(reason) => {
+                    self.status_message = Some(format!("Sending {} failed: {}", out.name, reason));
+                    self.fail_outgoing(out, reason);
+                }
+                SenderEvent::Progress(_) => {}
+            }
+            self.start_next_outgoing(client);
+        }
+    }
+
+    /// Aborts incoming transfers that got no chunk for
+    /// `INCOMING_STALL_TIMEOUT`, telling the peer and dropping the partial file.
+    fn expire_stalled_incoming(&mut self, client: &P2PClient) {
+        let now = Instant::now();
+        let stalled: Vec<u64> = self
+            .incoming_files
+            .iter()
+            .filter(|(_, inc)| is_stalled(TransferSide::Receiving, inc.last_chunk_at, now))
+            .map(|(id, _)| *id)
+            .collect();
+        for id in stalled {
+            let Some(inc) = self.incoming_files.remove(&id) else {
+                continue;
+            };
+            log_warn!(
+                "Transferencia {} sin datos hace {}s, se aborta",
+                inc.name,
+                INCOMING_STALL_TIMEOUT.as_secs()
+            );
+            Self::send_file_cancel(client, id, "receiver timed out");
+            Self::close_data_stream(client, data_stream_for(id));
+            let reason = format!("No data for {}s", INCOMING_STALL_TIMEOUT.as_secs());
+            self.status_message = Some(format!("Receiving {} failed: {}", inc.name, reason));
+            self.finish_transfer(inc.name.clone(), false, TransferOutcome::Failed(reason));
+            Self::discard_incoming(inc);
+        }
+    }
+
+    /// Handles file-transfer control/data and internal messages coming from
+    /// the SCTP association.
+    fn poll_sctp_messages(&mut self) {
+        // A clone shares the peer connection, enough to send on SCTP
+        let Some(client) = self.client.clone() else {
+            return;
+        };
+        let client = &client;
+
+        // Initialize SCTP RX
+        if self.sctp_rx.is_none() {
+            let (tx, rx) = mpsc::sync_channel(10000);
+            client.set_sctp_incoming(tx);
+            self.sctp_rx = Some(rx);
+        }
+
+        // Dropped files wait until the data channel is up
+        if !self.files_on_connect.is_empty() && client.is_sctp_connected() {
+            for path in std::mem::take(&mut self.files_on_connect) {
+                self.queue_outgoing_file(client, path);
+            }
+            self.start_next_outgoing(client);
+        }
+
+        self.poll_sender_events(client);
+        self.expire_stalled_incoming(client);
+
+        // Poll SCTP Messages. They are drained first so handling them can
+        // update the transfer state freely.
+        let messages: Vec<(u16, Vec<u8>)> = match &self.sctp_rx {
+            Some(rx) => rx.try_iter().collect(),
+            None => Vec::new(),
+        };
+        for (stream, payload) in messages {
+            // Control messages (JSON) on FILE_CONTROL_STREAM, raw chunks on
+            // one data stream per transfer
+            if stream == FILE_CONTROL_STREAM {
+                let Ok(msg_str) = String::from_utf8(payload) else {
+                    continue;
+                };
+                let Ok(msg) = serde_json::from_str::<FileTransferMessage>(&msg_str) else {
+                    continue;
+                };
+                match msg {
+                    FileTransferMessage::Offer {
+                        id,
+                        filename,
+                        size,
+                        mime_type,
+                        thumbnail,
+                    } => {
+                        if size as u64 > self.max_file_size {
+                            // Ni preguntamos: supera el máximo configurado
+                            Self::send_file_answer(client, id, false);
+                            self.status_message = Some(format!(
+                                "Rejected {}: larger than {} MB",
+                                filename,
+                                self.max_file_size / 1_048_576
+                            ));
+                        } else if self.pending_offers.iter().any(|offer| offer.id == id) {
+                            // Repetido: ya está esperando respuesta
+                        } else {
+                            let preview = thumbnail
+                                .filter(|_| is_image_mime(&mime_type))
+                                .and_then(|jpeg| Self::thumbnail_preview(&filename, &jpeg));
+                            self.notifications.push(Notification::FileOffer {
+                                from: self.peer_username.clone(),
+                                name: filename.clone(),
+                            });
+                            self.pending_offers.push_back(PendingOffer {
+                                id,
+                                name: filename,
+                                size,
+                                mime_type,
+                                preview,
+                                texture: None,
+                                shown_at: None,
+                            });
+                        }
+                    }
+                    FileTransferMessage::Answer { id, accepted: true } => {
+                        // An answer for an offer we already cancelled finds nothing
+                        if let Some(out) = self.outgoing_files.get_mut(&id)
+                            && !out.accepted
+                        {
+                            out.accepted = true;
+                            Self::spawn_file_sender(
+                                client.clone(),
+                                id,
+                                out.path.clone(),
+                                Arc::clone(&out.cancel),
+                                self.sender_tx.clone(),
+                            );
+                        }
+                    }
+                    FileTransferMessage::Answer {
+                        id,
+                        accepted: false,
+                    } => {
+                        if let Some(out) = self.outgoing_files.remove(&id) {
+                            out.cancel.store(true, Ordering::Relaxed);
+                            self.status_message =
+                                Some(format!("File transfer rejected: {}", out.name));
+                            self.finish_transfer(out.name, true, TransferOutcome::Rejected);
+                            self.start_next_outgoing(client);
+                        }
+                    }
+                    FileTransferMessage::Ack { .. } => {
+                        // Remote ack
+                    }
+                    FileTransferMessage::Cancel { id, reason } => {
+                        self.remote_cancelled(client, id, &reason);
+                    }
+                    FileTransferMessage::Eof { id } => {
+                        self.remote_eof(client, id);
+                    }
+                    _ => {}
+                }
+            } else if is_data_stream(stream) {
+                self.receive_chunk(client, stream, &payload);
+            } else if stream == SCTP_STREAM_CLOSED_NOTICE {
+                // The peer reset a stream: if it carried an incoming file, it gave up
+                let mut id = [0u8; 2];
+                if payload.len() == id.len() {
+                    id.copy_from_slice(&payload);
+                    self.remote_stream_closed(u16::from_be_bytes(id));
+                }
+            } else if stream == LOCAL_FILE_SELECTED_STREAM {
+                // Internal: Outgoing File Selected, goes to the queue
+                let path_str = String::from_utf8(payload).unwrap_or_default();
+                self.queue_outgoing_file(client, std::path::PathBuf::from(path_str));
+                self.start_next_outgoing(client);
+            } else if stream == MEDIA_CONTROL_STREAM {
+                match self
+                    .client
+                    .as_ref()
+                    .and_then(|c| c.handle_media_control(&payload))
+                {
+                    Some(MediaControlMessage::VideoParams(params)) => log_debug!(
+                        "remoto cambió video a {}x{}@{}",
+                        params.width,
+                        params.height,
+                        params.fps
+                    ),
+                    // Si ya la teníamos en espera nosotros, sigue así
+                    Some(MediaControlMessage::Hold) if self.hold.is_none() => {
+                        self.hold = Some(Hold::Remote);
+                    }
+                    Some(MediaControlMessage::Resume) => self.end_hold(),
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    /// Checks a picked or dropped file and puts it in the outgoing queue.
+    fn queue_outgoing_file(&mut self, client: &P2PClient, path: std::path::PathBuf) {
+        if !client.capabilities().file_transfer_v2 {
+            self.status_message =
+                Some("The other participant's RoomRTC can't receive files".to_string());
+            return;
+        }
+        let total_size = match storage::check_outgoing_file(&path) {
+            Ok(size) => size,
+            Err(reason) => {
+                self.status_message = Some(reason);
+                return;
+            }
+        };
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| path.to_string_lossy().to_string());
+        let mime_type = detect_mime_type(&name, &storage::read_head(&path, MIME_SNIFF_BYTES));
+        let thumbnail = if is_image_mime(mime_type) {
+            thumbnail::make_thumbnail(&path)
+        } else {
+            None
+        };
+        let id = self.next_transfer_id(client);
+        self.outgoing_queue.push_back((
+            id,
+            OutgoingFile {
+                name,
+                total_size,
+                sent_bytes: 0,
+                path,
+                mime_type: mime_type.to_string(),
+                thumbnail,
+                rate: TransferRate::new(),
+                acked_rate: TransferRate::new(),
+                flow: None,
+                cancel: Arc::new(AtomicBool::new(false)),
+                accepted: false,
+            },
+        ));
+    }
+
+    /// Offers queued files while there are free slots.
+    fn start_next_outgoing(&mut self, client: &P2PClient) {
+        while self.outgoing_files.len() < self.max_concurrent_transfers {
+            let Some((id, next)) = self.outgoing_queue.pop_front() else {
+                return;
+            };
+            let offer = FileTransferMessage::Offer {
+                id,
+                filename: next.name.clone(),
+                size: next.total_size,
+                mime_type: next.mime_type.clone(),
+                thumbnail: next.thumbnail.clone(),
+            };
+            let sent = serde_json::to_string(&offer)
+                .map_err(|e| e.to_string())
+                .and_then(|json| {
+                    client
+                        .send_sctp_data(FILE_CONTROL_STREAM, json.into_bytes())
+                        .map_err(|e| e.to_string())
+                });
+            match sent {
+                Ok(()) => {
+                    self.status_message = Some(format!("Sent File Offer: {}", next.name));
+                    self.outgoing_files.insert(id, next);
+                }
+                Err(e) => {
+                    log_warn!("Error sending File Offer: {}", e);
+                    self.status_message = Some(format!("Error sending offer: {}", e));
+                    self.fail_outgoing(next, format!("Could not send the offer: {}", e));
+                }
+            }
+        }
+    }
+
+    fn finish_transfer(&mut self, name: String, outgoing: bool, outcome: TransferOutcome) {
+        self.push_finished(FinishedTransfer {
+            name,
+            outgoing,
+            outcome,
+            open_path: None,
+            retry_path: None,
+        });
+    }
+
+    /// Completed and failed transfers are also worth a notification; the
+    /// ones cancelled or rejected were someone's own decision.
+    fn push_finished(&mut self, finished: FinishedTransfer) {
+        match &finished.outcome {
+            TransferOutcome::Completed => {
+                self.notifications.push(Notification::TransferCompleted {
+                    name: finished.name.clone(),
+                    outgoing: finished.outgoing,
+                })
+            }
+            TransferOutcome::Failed(reason) => {
+                self.notifications.push(Notification::TransferFailed {
+                    name: finished.name.clone(),
+                    reason: reason.clone(),
+                })
+            }
+            TransferOutcome::Rejected | TransferOutcome::Cancelled => {}
+        }
+        self.finished_transfers.push(finished);
+    }
+
+    /// What happened since the last call, to notify while in the background.
+    pub fn take_notifications(&mut self) -> Vec<Notification> {
+        std::mem::take(&mut self.notifications)
+    }
+
+    /// A send that failed on our side; the row keeps the path for Retry.
+    fn fail_outgoing(&mut self, out: OutgoingFile, reason: String) {
+        self.push_finished(FinishedTransfer {
+            name: out.name,
+            outgoing: true,
+            outcome: TransferOutcome::Failed(reason),
+            open_path: None,
+            retry_path: Some(out.path),
+        });
+    }
+
+    /// Retry on a failed send. There is no resumable transfer yet, so the
+    /// file is offered again under a new id and goes from the start.
+    fn retry_outgoing(&mut self, path: std::path::PathBuf) {
+        let Some(client) = self.client.clone() else {
+            return;
+        };
+        self.finished_transfers
+            .retain(|done| done.retry_path.as_ref() != Some(&path));
+        self.queue_outgoing_file(&client, path);
+        self.start_next_outgoing(&client);
+    }
+
+    /// Decodes the thumbnail of an offer. Oversized or undecodable ones are
+    /// dropped; the offer is still shown, just without a preview.
+    fn thumbnail_preview(filename: &str, jpeg: &[u8]) -> Option<ColorImage> {
+        if jpeg.len() > MAX_THUMBNAIL_BYTES {
+            log_warn!(
+                "Miniatura de {} descartada: {} bytes (máximo {})",
+                filename,
+                jpeg.len(),
+                MAX_THUMBNAIL_BYTES
+            );
+            return None;
+        }
+        thumbnail::decode_thumbnail(jpeg).and_then(|mat| Self::mat_to_color_image(&mat))
+    }
+
+    /// Id of the incoming transfer whose chunks arrive on `stream`.
+    fn incoming_on_stream(&self, stream: u16) -> Option<u64> {
+        self.incoming_files
+            .keys()
+            .copied()
+            .find(|id| data_stream_for(*id) == stream)
+    }
+
+    /// Writes a chunk to the incoming file of that stream, aborting it if
+    /// the sender goes past the announced size.
+    fn receive_chunk(&mut self, client: &P2PClient, stream: u16, payload: &[u8]) {
+        let Some(id) = self.incoming_on_stream(stream) else {
+            return;
+        };
+        let Some(inc) = self.incoming_files.get_mut(&id) else {
+            return;
+        };
+        if let Err(reason) = Self::write_incoming_chunk(inc, payload) {
+            Self::send_file_cancel(client, id, "more data than announced");
+            Self::close_data_stream(client, stream);
+            self.status_message = Some(reason);
+            if let Some(inc) = self.incoming_files.remove(&id) {
+                self.finish_transfer(
+                    inc.name.clone(),
+                    false,
+                    TransferOutcome::Failed("More data than announced".to_string()),
+                );
+                Self::discard_incoming(inc);
+            }
+            return;
+        }
+        // The Eof overtook the last chunks
+        if inc.eof && inc.received_bytes == inc.size {
+            Self::close_data_stream(client, stream);
+            self.complete_incoming(id);
+        }
+    }
+
+    /// The sender says the file is over. Chunks still in flight on the data
+    /// stream may arrive after it; then the last of them completes the file.
+    fn remote_eof(&mut self, client: &P2PClient, id: u64) {
+        let Some(inc) = self.incoming_files.get_mut(&id) else {
+            return;
+        };
+        if inc.received_bytes < inc.size {
+            inc.eof = true;
+            return;
+        }
+        Self::close_data_stream(client, data_stream_for(id));
+        self.complete_incoming(id);
+    }
+
+    /// The sender reset the stream of a file we were receiving. After the
+    /// whole file that is its end of stream; before, it gave up and the
+    /// partial file is dropped. Resets of other streams (our own close
+    /// echoing back, or a transfer that already ended) are ignored.
+    fn remote_stream_closed(&mut self, stream: u16) {
+        let Some(id) = self.incoming_on_stream(stream) else {
+            return;
+        };
+        if self
+            .incoming_files
+            .get(&id)
+            .is_some_and(|inc| inc.received_bytes == inc.size)
+        {
+            self.complete_incoming(id);
+            return;
+        }
+        let Some(inc) = self.incoming_files.remove(&id) else {
+            return;
+        };
+        self.status_message = Some(format!("Transfer of {} cancelled by peer", inc.name));
+        self.finish_transfer(inc.name.clone(), false, TransferOutcome::Cancelled);
+        Self::discard_incoming(inc);
+    }
+
+    fn complete_incoming(&mut self, id: u64) {
+        let Some(mut inc) = self.incoming_files.remove(&id) else {
+            return;
+        };
+        // Close file
+        inc.file_handle = None;
+        self.status_message = Some(format!("Received file: {}", inc.name));
+        // Images can be opened from the transfer list
+        let open_path = inc.path.filter(|path| {
+            is_image_mime(&inc.mime_type)
+                && is_image_mime(detect_mime_type(
+                    &path.to_string_lossy(),
+                    &storage::read_head(path, MIME_SNIFF_BYTES),
+                ))
+        });
+        self.push_finished(FinishedTransfer {
+            name: inc.name,
+            outgoing: false,
+            outcome: TransferOutcome::Completed,
+            open_path,
+            retry_path: None,
+        });
+    }
+
+    fn close_data_stream(client: &P2PClient, stream: u16) {
+        if let Err(e) = client.data_channel(stream).close() {
+            log_warn!("No se pudo cerrar el stream SCTP {}: {}", stream, e);
+        }
+    }
+
+    /// Streams the file in chunks on the data stream of transfer `id`, then
+    /// sends `Eof` on the control stream and closes the data stream. Progress
+    /// and the outcome go back to the UI on `events`. The stream is reset if
+    /// the transfer does not finish.
+    fn spawn_file_sender(
+        client: P2PClient,
+        id: u64,
+        path: std::path::PathBuf,
+        cancel: Arc<AtomicBool>,
+        events: Sender<(u64, SenderEvent)>,
+    ) {
+        let channel = client.data_channel(data_stream_for(id));
+        thread::spawn(move || {
+            // Closed at the end of the previous transfer
+            channel.reopen();
+            let result = Self::send_file_chunks(&client, &channel, id, &path, &cancel, &events);
+            let cancelled_by_peer = result.is_err() && channel.is_closed();
+            if !cancelled_by_peer {
+                // Past the Eof it just frees the stream; before it, it tells
+                // the receiver to drop what it got so far
+                Self::close_data_stream(&client, channel.stream());
+            }
+            // Cancelled transfers were already closed by the UI
+            if cancel.load(Ordering::Relaxed) {
+                return;
+            }
+            let event = match result {
+                Ok(()) => SenderEvent::Completed,
+                Err(_) if cancelled_by_peer => SenderEvent::CancelledByPeer,
+                Err(reason) => {
+                    log_warn!("Upload error en transferencia {}: {}", id, reason);
+                    SenderEvent::Failed(reason)
+                }
+            };
+            let _ = events.send((id, event));
+        });
+    }
+
+    /// Body of the sender thread: every chunk the stream takes is reported
+    /// as progress. Errors come back as the reason shown in the UI.
+    fn send_file_chunks(
+        client: &P2PClient,
+        channel: &DataChannel,
+        id: u64,
+        path: &std::path::Path,
+        cancel: &AtomicBool,
+        events: &Sender<(u64, SenderEvent)>,
+    ) -> Result<(), String> {
+        use std::io::Read;
+        let mut file =
+            File::open(path).map_err(|e| format!("Could not open {}: {}", path.display(), e))?;
+        let mut buffer = [0u8; 4096]; // 4KB chunks (Reduced from 16KB to improve reliability)
+        let mut total_sent = 0;
+        loop {
+            if cancel.load(Ordering::Relaxed) {
+                return Err("Cancelled".to_string());
+            }
+            let n = file
+                .read(&mut buffer)
+                .map_err(|e| format!("Could not read the file: {}", e))?;
+            if n == 0 {
+                break;
+            }
+            let chunk = &buffer[..n];
+
+            // Log progress every ~500KB
+            if (total_sent / 500_000) != ((total_sent + n) / 500_000) {
+                log_debug!("Sender Thread {}: Sent {} bytes...", id, total_sent);
+            }
+
+            let blocked_since = Instant::now();
+            loop {
+                match channel.send(chunk.to_vec()) {
+                    Ok(_) => {
+                        let _ = events.send((id, SenderEvent::Progress(n as u64)));
+                        total_sent += n;
+                        break;
+                    }
+                    Err(SctpError::BufferFull) => {
+                        if is_stalled(TransferSide::Sending, blocked_since, Instant::now()) {
+                            return Err(format!(
+                                "The connection stalled after {} bytes",
+                                total_sent
+                            ));
+                        }
+                        thread::sleep(Duration::from_millis(50));
+                    }
+                    Err(e) => return Err(format!("Connection error: {}", e)),
+                }
+            }
+        }
+        // The receiver does not learn about our stream reset from every
+        // SCTP stack, so the end always goes in band too
+        let eof = FileTransferMessage::Eof { id };
+        if let Ok(json) = serde_json::to_string(&eof)
+            && let Err(e) = client.send_sctp_data(FILE_CONTROL_STREAM, json.into_bytes())
+        {
+            log_warn!("No se pudo mandar el Eof de la transferencia {}: {}", id, e);
+        }
+        Ok(())
     }
 
     pub fn peer(&self) -> Option<String> {
         self.peer_username.clone()
     }
 
+    /// Reinicia ICE con el peer después de un corte de media.
+    pub fn restart_ice(&self, signaling: &dyn CallSignaling) -> Result<(), PeerConnectionError> {
+        match (self.client.as_ref(), self.peer_username.as_deref()) {
+            (Some(client), Some(peer)) => restart_ice(client, signaling, peer),
+            _ => Ok(()),
+        }
+    }
+
+    /// Oferta (o pedido de oferta) de reinicio de ICE; se ignora si no viene
+    /// del peer de esta llamada.
+    pub fn on_ice_restart(
+        &self,
+        signaling: &dyn CallSignaling,
+        from: &str,
+        offer: Option<&str>,
+    ) -> Result<(), PeerConnectionError> {
+        match self.client.as_ref() {
+            Some(client) if self.peer_username.as_deref() == Some(from) => {
+                handle_ice_restart(client, signaling, from, offer)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Respuesta del peer a nuestra oferta de reinicio de ICE.
+    pub fn on_ice_restart_answer(
+        &self,
+        from: &str,
+        answer: &str,
+    ) -> Result<(), PeerConnectionError> {
+        match self.client.as_ref() {
+            Some(client) if self.peer_username.as_deref() == Some(from) => {
+                client.apply_restart_answer(answer)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// The server refused the transfer; the current call keeps going.
+    pub fn on_transfer_failed(&mut self, target: &str, error: &str) {
+        self.status_message = Some(format!("Could not transfer to {}: {}", target, error));
+    }
+
+    /// `from` llamó durante esta llamada: queda sonando en un aviso hasta que
+    /// se atienda, se rechace o venza el timeout.
+    pub fn on_incoming_call(&mut self, from: String, sdp: String, caps: PeerCapabilities) {
+        self.call_waiting = Some(CallWaiting {
+            from,
+            sdp,
+            caps,
+            since: Instant::now(),
+        });
+    }
+
+    pub fn take_call_waiting(&mut self) -> Option<CallWaiting> {
+        self.call_waiting.take()
+    }
+
+    /// Quien esperaba colgó antes de que lo atendieran. Devuelve si era él.
+    pub fn dismiss_call_waiting(&mut self, from: &str) -> bool {
+        if self.call_waiting.as_ref().map(|w| w.from.as_str()) != Some(from) {
+            return false;
+        }
+        self.call_waiting = None;
+        self.status_message = Some(format!("Missed call from {}", from));
+        true
+    }
+
     pub fn handle_call_ended(&mut self, from: String) {
         if self.peer_username.as_deref() == Some(&from) {
             self.status_message = Some(format!("{} finalizó la llamada.", from));
@@ -857,3 +2971,84 @@ impl VideoCall {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_camera_failure_keeps_the_call_in_audio_only() {
+        let video = VideoParams {
+            width: 640,
+            height: 480,
+            fps: 30,
+            max_bitrate_kbps: None,
+        };
+        let mut call = VideoCall::new(video, 1024);
+        let client = P2PClient::new(PeerConnectionRole::Controlling, None, None).unwrap();
+        call.finish_media_start(Err((client, "no camera".to_string())));
+
+        assert!(call.client.is_some());
+        assert!(call.media_started);
+        assert!(call.video_unavailable);
+        assert_eq!(call.status_message, None);
+    }
+
+    #[test]
+    fn test_recent_audio_keeps_the_peer_alive_with_stale_video() {
+        let recent = Some(Duration::from_millis(500));
+        let stale = Some(Duration::from_secs(5));
+        assert!(peer_recently_seen(None, recent));
+        assert!(peer_recently_seen(Some(5_000), recent));
+        assert!(peer_recently_seen(Some(100), None));
+        assert!(!peer_recently_seen(Some(5_000), stale));
+        assert!(!peer_recently_seen(None, None));
+    }
+
+    #[test]
+    fn test_receiver_stalls_after_the_incoming_timeout() {
+        let start = Instant::now();
+        let just_over = INCOMING_STALL_TIMEOUT + Duration::from_millis(1);
+        assert!(!is_stalled(TransferSide::Receiving, start, start));
+        assert!(!is_stalled(
+            TransferSide::Receiving,
+            start,
+            start + INCOMING_STALL_TIMEOUT
+        ));
+        assert!(is_stalled(
+            TransferSide::Receiving,
+            start,
+            start + just_over
+        ));
+    }
+
+    #[test]
+    fn test_sender_waits_longer_than_the_receiver() {
+        let start = Instant::now();
+        let past_incoming = INCOMING_STALL_TIMEOUT + Duration::from_millis(1);
+        let past_outgoing = OUTGOING_STALL_TIMEOUT + Duration::from_millis(1);
+        assert!(!is_stalled(
+            TransferSide::Sending,
+            start,
+            start + past_incoming
+        ));
+        assert!(!is_stalled(
+            TransferSide::Sending,
+            start,
+            start + OUTGOING_STALL_TIMEOUT
+        ));
+        assert!(is_stalled(
+            TransferSide::Sending,
+            start,
+            start + past_outgoing
+        ));
+    }
+
+    #[test]
+    fn test_progress_after_now_is_not_a_stall() {
+        let start = Instant::now();
+        let later = start + Duration::from_secs(600);
+        assert!(!is_stalled(TransferSide::Sending, later, start));
+        assert!(!is_stalled(TransferSide::Receiving, later, start));
+    }
+}
@@ -1,28 +1,62 @@
+use crate::client::capabilities::PeerCapabilities;
+use crate::client::message_inbox::{MessageInbox, SharedInbox};
 use crate::client::p2p_client::P2PClient;
 use crate::client::signaling_client::SignalingClient;
-use crate::client::webrtc_service::WebRTCHandler;
+use crate::client::webrtc_service::{CallProgress, PeerConfig, WebRTCHandler};
+use crate::ui::screens::status_utils::connection_progress::{
+    ConnectionProgress, ConnectionProgressAction,
+};
+use crate::ui::theme::colors;
 use eframe::egui::{self, Button};
 use egui::RichText;
 use egui::TextStyle;
 use egui::Vec2;
+use room_rtc::audio::audio_playback::RingbackTone;
+use room_rtc::rtc::rtc_dtls::DtlsIdentity;
 use room_rtc::rtc::rtc_peer_connection::PeerConnectionRole;
-use std::sync::{Arc, Mutex};
+use room_rtc::{log_debug, log_warn};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Cuánto queda a la vista el motivo de una llamada fallida antes de volver
+/// al lobby.
+const FAILED_CALL_NOTICE: Duration = Duration::from_secs(3);
+/// Sin CALL_RINGING en este tiempo se avisa que el destino puede no estar
+/// alcanzable, aunque se sigue esperando.
+const RINGING_WAIT: Duration = Duration::from_secs(5);
 
 #[derive(Debug)]
 pub enum WaitingCallAction {
     GoToLobby,
     GoToVideo,
+    // ICE/DTLS falló: volver al lobby mostrando el motivo
+    ConnectionFailed(String),
+    // La conexión no se estableció a tiempo: llamar de nuevo
+    Retry,
+    // El destino no atendió (no existe, desconectado, ocupado o rechazó):
+    // volver al lobby con el motivo
+    CallFailed(String),
 }
 pub struct WaitingCall {
     pub local_sdp: String,
     pub role: PeerConnectionRole,
     pub target_username: String,
-    received_msgs: Arc<Mutex<Vec<String>>>,
+    received_msgs: SharedInbox,
     pub client: Option<P2PClient>,
     pub remote_sdp: String,
     ice_started: bool,
     pub status_message: Option<String>,
     active_peer: Option<String>,
+    peer_config: PeerConfig,
+    session_code: String,
+    connecting: ConnectionProgress,
+    // Motivo por el que la llamada no se pudo hacer y desde cuándo se muestra
+    failed: Option<(String, Instant)>,
+    // Cuándo salió la oferta, para notar que el destino no confirma que suena
+    offer_sent: Option<Instant>,
+    ringing: bool,
+    ringback: Option<RingbackTone>,
+    output_device: Option<String>,
 }
 
 impl WebRTCHandler for WaitingCall {
@@ -33,26 +67,57 @@ impl WebRTCHandler for WaitingCall {
     fn role(&self) -> PeerConnectionRole {
         self.role
     }
-    fn received_msgs(&self) -> &Arc<Mutex<Vec<String>>> {
+    fn received_msgs(&self) -> &SharedInbox {
         &self.received_msgs
     }
+
+    fn peer_config(&self) -> PeerConfig {
+        self.peer_config.clone()
+    }
 }
 
 impl WaitingCall {
-    pub fn new(role: PeerConnectionRole) -> Self {
+    pub fn new(
+        role: PeerConnectionRole,
+        peer_config: PeerConfig,
+        connect_timeout: Duration,
+    ) -> Self {
         Self {
             local_sdp: String::new(),
             role,
             target_username: String::new(),
-            received_msgs: Arc::new(Mutex::new(Vec::new())),
+            received_msgs: MessageInbox::shared(),
             client: None,
             remote_sdp: String::new(),
             ice_started: false,
             status_message: None,
             active_peer: None,
+            peer_config,
+            session_code: String::new(),
+            connecting: ConnectionProgress::new(connect_timeout),
+            failed: None,
+            offer_sent: None,
+            ringing: false,
+            ringback: None,
+            output_device: None,
         }
     }
 
+    /// Ancho de banda a anunciar en el SDP de la próxima llamada.
+    pub fn set_bandwidth_kbps(&mut self, bandwidth_kbps: u64) {
+        self.peer_config.bandwidth_kbps = Some(bandwidth_kbps);
+    }
+
+    /// Parlantes donde suena el tono de llamada.
+    pub fn set_output_device(&mut self, device: Option<String>) {
+        self.output_device = device;
+    }
+
+    /// Certificado DTLS de las próximas llamadas; `None` genera uno por llamada.
+    pub fn set_dtls_identity(&mut self, identity: Option<Arc<DtlsIdentity>>) {
+        self.peer_config.dtls.identity = identity;
+    }
+
     pub fn update(
         &mut self,
         ctx: &egui::Context,
@@ -60,11 +125,34 @@ impl WaitingCall {
     ) -> Option<WaitingCallAction> {
         let mut next_action = None;
 
+        if let Some(reason) = self.connection_failure() {
+            self.client = None;
+            self.ice_started = false;
+            self.connecting.stop();
+            next_action = Some(WaitingCallAction::ConnectionFailed(reason));
+        }
+        if let Some((reason, since)) = &self.failed {
+            let shown = since.elapsed();
+            if shown >= FAILED_CALL_NOTICE {
+                next_action = Some(WaitingCallAction::CallFailed(reason.clone()));
+                self.failed = None;
+            } else {
+                ctx.request_repaint_after(FAILED_CALL_NOTICE - shown);
+            }
+        }
+        if let Some(sent) = self.offer_sent
+            && !self.ringing
+            && sent.elapsed() < RINGING_WAIT
+        {
+            // Para que el aviso de no alcanzable aparezca solo
+            ctx.request_repaint_after(RINGING_WAIT - sent.elapsed());
+        }
+
         egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
             ui.heading(format!("Calling {}", self.target_username));
             let res_go_lobby = ui.add(Button::new("Go to Lobby"));
             if res_go_lobby.clicked() {
-                println!("Returning to Lobby");
+                log_debug!("Returning to Lobby");
                 next_action = Some(WaitingCallAction::GoToLobby);
             }
 
@@ -89,18 +177,59 @@ impl WaitingCall {
 
         egui::CentralPanel::default().show(ctx, |ui| {
             ui.vertical_centered(|ui| {
-                if let Some(status) = &self.status_message {
-                    ui.separator();
-                    ui.label(status);
-                } else {
+                if let Some((reason, _)) = &self.failed {
                     ui.label(
-                        egui::RichText::new(format!(
-                            "Waiting for {} to accept the call...",
-                            self.target_username
-                        ))
-                        .size(20.0)
-                        .color(egui::Color32::DARK_BLUE),
+                        egui::RichText::new(reason)
+                            .size(20.0)
+                            .color(egui::Color32::RED),
                     );
+                    ui.label("Returning to the lobby...");
+                } else {
+                    if let Some(status) = &self.status_message {
+                        ui.separator();
+                        ui.label(status);
+                    }
+                    if self.offer_sent.is_some() {
+                        let state = if self.ringing {
+                            format!("Ringing {}...", self.target_username)
+                        } else {
+                            format!("Connecting to {}...", self.target_username)
+                        };
+                        ui.label(
+                            egui::RichText::new(state)
+                                .size(20.0)
+                                .color(egui::Color32::DARK_BLUE),
+                        );
+                        if self.maybe_unreachable() {
+                            ui.colored_label(
+                                colors::WARNING,
+                                format!(
+                                    "{} may be unreachable, still waiting...",
+                                    self.target_username
+                                ),
+                            );
+                        }
+                    } else if self.status_message.is_none() {
+                        ui.label(
+                            egui::RichText::new(format!(
+                                "Waiting for {} to accept the call...",
+                                self.target_username
+                            ))
+                            .size(20.0)
+                            .color(egui::Color32::DARK_BLUE),
+                        );
+                    }
+                }
+                if let Some(progress) = self.connection_progress() {
+                    match self.connecting.show(ui, &progress) {
+                        Some(ConnectionProgressAction::Retry) => {
+                            next_action = Some(WaitingCallAction::Retry);
+                        }
+                        Some(ConnectionProgressAction::BackToLobby) => {
+                            next_action = Some(WaitingCallAction::GoToLobby);
+                        }
+                        None => {}
+                    }
                 }
                 ui.separator();
 
@@ -119,67 +248,135 @@ impl WaitingCall {
                         self.status_message = Some(
                             "Inicializa el peer y comparte la oferta antes de entrar.".to_string(),
                         );
-                    } else {
-                        if !self.ice_started {
-                            match self.start_ice() {
+                    } else if !self.ice_started {
+                        if let Err(err) = self.begin_connecting() {
+                            log_warn!("ICE ERROR {}", err);
+                            self.status_message = Some(err);
+                        }
+                    } else if self.connection_progress() == Some(CallProgress::Connected) {
+                        // Solo entramos con la conexión (ICE + DTLS) completa;
+                        // mientras tanto la fase se ve en la barra de progreso
+                        next_action = Some(WaitingCallAction::GoToVideo);
+                    }
+                }
+
+                ui.add_space(20.0);
+                ui.collapsing("Advanced Debug Info", |ui| {
+                    match &self.client {
+                        Some(client) => {
+                            if ui.button("Copy diagnostics").clicked() {
+                                let report = client.diagnostics(self.status_message.as_deref());
+                                ctx.output_mut(|o| o.copied_text = report);
+                            }
+                        }
+                        None => {
+                            ui.label("Peer not initialized");
+                        }
+                    }
+
+                    // Emparejar sin servidor: nuestra oferta y la respuesta
+                    // del otro peer viajan como códigos de sesión
+                    ui.separator();
+                    if ui.button("Copy session code").clicked() {
+                        match self.manual_offer_code() {
+                            Ok(code) => ctx.output_mut(|o| o.copied_text = code),
+                            Err(err) => {
+                                self.status_message =
+                                    Some(format!("Could not create session code: {}", err));
+                            }
+                        }
+                    }
+                    ui.horizontal(|ui| {
+                        ui.add(
+                            egui::TextEdit::singleline(&mut self.session_code)
+                                .hint_text("Callee's session code"),
+                        );
+                        if ui.button("Paste session code").clicked() {
+                            let code = self.session_code.clone();
+                            match self.import_session_code(&code) {
                                 Ok(_) => {
-                                    self.ice_started = true;
-                                    self.status_message =
-                                        Some("ICE iniciado, esperando conexión...".to_string());
+                                    self.session_code.clear();
+                                    if let Err(err) = self.begin_connecting() {
+                                        self.status_message = Some(err);
+                                    }
                                 }
-                                Err(e) => {
-                                    eprintln!("ICE ERROR {}", e);
+                                Err(err) => {
                                     self.status_message =
-                                        Some(format!("Error iniciando ICE: {}", e));
-                                    return;
+                                        Some(format!("Invalid session code: {}", err));
                                 }
                             }
-                            self.status_message = Some("Conectando... Por favor espere.".to_string());
-                        } else if let Some(client) = &self.client {
-                            // Solo entramos si la conexión (ICE + DTLS) está completa
-                            if client.has_connection() {
-                                self.status_message = Some("Entrando a la sala de video...".to_string());
-                                next_action = Some(WaitingCallAction::GoToVideo);
-                            } else {
-                                self.status_message = Some("Esperando a que finalice la conexión...".to_string());
-                            }
                         }
-                    }
-                }
+                    });
+                });
             });
         });
         next_action
     }
 
-    pub fn take_client_with_inbox(&mut self) -> Option<(P2PClient, Arc<Mutex<Vec<String>>>)> {
+    /// Código de sesión con nuestra oferta; la crea si todavía no hay una.
+    fn manual_offer_code(&mut self) -> Result<String, String> {
+        if self.local_sdp.is_empty() {
+            self.initialize_peer().map_err(|e| e.to_string())?;
+            self.local_sdp = self.generate_offer().map_err(|e| e.to_string())?;
+            // Desde acá se espera la respuesta del otro peer
+            self.connecting.start();
+        }
+        self.export_session_code().map_err(|e| e.to_string())
+    }
+
+    pub fn take_client_with_inbox(&mut self) -> Option<(P2PClient, SharedInbox)> {
+        self.connecting.stop();
+        self.stop_ringing();
         if let Some(client) = self.client.take() {
             let inbox = Arc::clone(&self.received_msgs);
-            self.received_msgs = Arc::new(Mutex::new(Vec::new()));
+            self.received_msgs = MessageInbox::shared();
             return Some((client, inbox));
         }
         None
     }
 
-    pub fn on_call_accepted(&mut self, from: String, sdp: String) {
+    pub fn on_call_accepted(&mut self, from: String, sdp: String, caps: PeerCapabilities) {
+        self.stop_ringing();
         self.active_peer = Some(from.clone());
         self.remote_sdp = sdp.clone();
-        if let Err(err) = self.apply_remote_description(&sdp) {
-            self.status_message = Some(format!("Error aplicando SDP remoto: {}", err));
-            return;
-        }
-        if let Err(err) = self.start_ice() {
-            self.status_message = Some(format!("Error iniciando ICE: {}", err));
+        if let Err(err) = self.accept_answer(&sdp, &caps) {
+            self.status_message = Some(format!("Error conectando con {}: {}", from, err));
             return;
         }
         self.ice_started = true;
+        self.connecting.start();
         self.status_message = Some(format!("{} aceptó la llamada", from));
-        // Pasar directamente a la sala de video
-        self.status_message = Some("Entrando a la sala de video...".to_string());
+    }
+
+    /// Arranca ICE, DTLS y SCTP con las descripciones que ya tenemos.
+    fn begin_connecting(&mut self) -> Result<(), String> {
+        self.start_ice()
+            .map_err(|e| format!("Error iniciando ICE: {}", e))?;
+        self.ice_started = true;
+        self.connecting.start();
+        Ok(())
     }
 
     pub fn on_call_rejected(&mut self, from: String) {
-        self.status_message = Some(format!("{} rechazó tu llamada", from));
+        self.on_call_failed(&from, format!("{} declined the call", from));
+    }
+
+    /// `to` no puede atender: se descarta el intento y, después de mostrar
+    /// `reason` un momento, se vuelve al lobby. Un `to` vacío (servidor
+    /// viejo) vale para la llamada en curso.
+    pub fn on_call_failed(&mut self, to: &str, reason: String) {
+        if !to.is_empty() && to != self.target_username {
+            return;
+        }
+        self.client = None;
+        self.remote_sdp.clear();
+        self.local_sdp.clear();
+        self.ice_started = false;
         self.active_peer = None;
+        self.connecting.stop();
+        self.stop_ringing();
+        self.status_message = Some(reason.clone());
+        self.failed = Some((reason, Instant::now()));
     }
 
     pub fn on_call_ended(&mut self, from: &str) {
@@ -190,35 +387,109 @@ impl WaitingCall {
             self.remote_sdp.clear();
             self.local_sdp.clear();
             self.ice_started = false;
+            self.connecting.stop();
+            self.stop_ringing();
         }
     }
 
+    /// El cliente de `from` ya muestra la llamada que le hicimos: suena el
+    /// tono de llamada hasta que atienda, rechace o dejemos de esperar.
+    pub fn on_call_ringing(&mut self, from: &str) {
+        if self.ringing || self.offer_sent.is_none() || self.pending_call().as_deref() != Some(from)
+        {
+            return;
+        }
+        self.ringing = true;
+        match RingbackTone::play(self.output_device.as_deref()) {
+            Ok(tone) => self.ringback = Some(tone),
+            Err(err) => log_warn!("No se pudo reproducir el tono de llamada: {}", err),
+        }
+    }
+
+    fn stop_ringing(&mut self) {
+        self.offer_sent = None;
+        self.ringing = false;
+        self.ringback = None;
+    }
+
+    /// Pasó `RINGING_WAIT` desde la oferta y el destino no avisó que suena.
+    fn maybe_unreachable(&self) -> bool {
+        !self.ringing
+            && self
+                .offer_sent
+                .is_some_and(|sent| sent.elapsed() >= RINGING_WAIT)
+    }
+
     pub fn active_peer(&self) -> Option<String> {
         self.active_peer.clone()
     }
 
+    /// Usuario al que mandamos la oferta y todavía no atendió.
+    pub fn pending_call(&self) -> Option<String> {
+        if self.client.is_some() && self.failed.is_none() {
+            self.active_peer.clone()
+        } else {
+            None
+        }
+    }
+
+    /// Se dejó de esperar (volver al lobby): el próximo pedido llama de
+    /// nuevo.
+    pub fn cancel(&mut self) {
+        self.client = None;
+        self.remote_sdp.clear();
+        self.local_sdp.clear();
+        self.ice_started = false;
+        self.active_peer = None;
+        self.failed = None;
+        self.connecting.stop();
+        self.stop_ringing();
+        self.status_message = None;
+    }
+
     fn place_call(&mut self, signaling: &SignalingClient) -> Result<(), String> {
+        self.failed = None;
+        self.stop_ringing();
         if self.target_username.trim().is_empty() {
             return Err("Input user to call".to_string());
         }
 
-        self.initialize_peer()
-            .map_err(|e| format!("Error initializing peer: {}", e))?;
-
         self.ice_started = false;
+        let target = self.target_username.clone();
         let offer = self
-            .generate_offer()
-            .map_err(|e| format!("Couldn't generate offer: {}", e))?;
-
-        signaling
-            .call(&self.target_username, &offer)
-            .map_err(|e| e.to_string())?;
+            .send_offer(signaling, &target)
+            .map_err(|e| format!("Couldn't place the call: {}", e))?;
         self.local_sdp = offer;
         self.active_peer = Some(self.target_username.clone());
+        self.offer_sent = Some(Instant::now());
         Ok(())
     }
 
+    /// Descarta el intento que no llegó a conectar. Con un usuario destino
+    /// lo vuelve a llamar; en el emparejado manual hay que intercambiar
+    /// códigos de nuevo.
+    pub fn retry(&mut self, signaling: Option<&SignalingClient>) -> Result<(), String> {
+        self.client = None;
+        self.remote_sdp.clear();
+        self.local_sdp.clear();
+        self.ice_started = false;
+        self.connecting.stop();
+        match signaling {
+            Some(signaling) if !self.target_username.trim().is_empty() => {
+                self.status_message = Some(format!("Calling {} again...", self.target_username));
+                self.place_call(signaling)
+            }
+            _ => {
+                self.status_message = Some("Copy a new session code to try again".to_string());
+                Ok(())
+            }
+        }
+    }
+
     pub fn call_user(&mut self, username: &str, signaling: &SignalingClient) -> Result<(), String> {
+        if self.pending_call().as_deref() == Some(username) {
+            return Err(format!("Already calling {}", username));
+        }
         self.target_username = username.to_string();
         self.place_call(signaling)
     }
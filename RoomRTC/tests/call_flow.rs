@@ -0,0 +1,192 @@
+//! Llamada completa entre dos clientes en 127.0.0.1: servidor de señalización
+//! real (TLS, usuarios en memoria), registro y login, CALL_OFFER/CALL_ANSWER,
+//! ICE + DTLS en los dos `P2PClient` y un mensaje por el canal de datos. Sin
+//! cámara ni audio: la media no se arranca.
+
+use std::net::{SocketAddr, TcpListener};
+use std::sync::Arc;
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use room_rtc::rtc::rtc_dtls::DtlsIdentity;
+use room_rtc::rtc::rtc_peer_connection::PeerConnectionRole;
+use room_rtc::stun::StunServer;
+use roomrtc::client::capabilities::PeerCapabilities;
+use roomrtc::client::p2p_client::P2PClient;
+use roomrtc::client::signaling_client::{SignalingClient, SignalingEvent};
+use roomrtc::client::tls::TlsClientSettings;
+use roomrtc::config::AppConfig;
+use roomrtc::logger::Logger;
+use roomrtc::server;
+use roomrtc::server::state::ServerState;
+use roomrtc::server::tls::build_tls_config;
+use roomrtc::server::user_store::MemoryUserStore;
+
+/// Plazo generoso: en CI las máquinas andan cargadas.
+const DEADLINE: Duration = Duration::from_secs(30);
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+/// Stream SCTP que la aplicación no usa para nada.
+const TEST_STREAM: u16 = 42;
+
+/// Levanta el servidor en un puerto libre. Devuelve su dirección y el
+/// fingerprint del certificado self-signed, para fijarlo en los clientes.
+fn start_server() -> (SocketAddr, String) {
+    let config = AppConfig {
+        tls_dev_mode: true,
+        ..AppConfig::default()
+    };
+    let (tls_config, fingerprint) = build_tls_config(&config).unwrap();
+    let state = ServerState::with_store(Box::new(MemoryUserStore::new()), Logger::noop());
+    state.load_users().unwrap();
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    thread::spawn(move || server::run(listener, Arc::new(state), tls_config));
+    (addr, fingerprint)
+}
+
+/// STUN local: sin él el agente ICE consulta al servidor público.
+fn start_stun() -> String {
+    let stun = StunServer::bind("127.0.0.1:0").unwrap();
+    let addr = stun.local_addr().unwrap();
+    stun.spawn();
+    addr.to_string()
+}
+
+/// Lee eventos hasta que `matcher` devuelve algo o vence `DEADLINE`.
+fn wait_for<T>(
+    client: &SignalingClient,
+    what: &str,
+    mut matcher: impl FnMut(SignalingEvent) -> Option<T>,
+) -> T {
+    let start = Instant::now();
+    while start.elapsed() < DEADLINE {
+        match client.try_next_event() {
+            Some(SignalingEvent::Disconnected) => {
+                panic!("server closed while waiting for {}", what)
+            }
+            Some(event) => {
+                if let Some(value) = matcher(event) {
+                    return value;
+                }
+            }
+            None => thread::sleep(POLL_INTERVAL),
+        }
+    }
+    panic!("no {} after {:?}", what, DEADLINE);
+}
+
+/// Registra al usuario y entra con él.
+fn sign_up(addr: SocketAddr, tls: &TlsClientSettings, username: &str) -> SignalingClient {
+    let client = SignalingClient::connect(&addr.to_string(), tls).unwrap();
+    let password = format!("{}-secret", username);
+    client.register(username, &password).unwrap();
+    wait_for(&client, "REGISTER_SUCCESS", |event| match event {
+        SignalingEvent::Registered(_) => Some(()),
+        SignalingEvent::RegisterError(err) => panic!("register failed: {}", err),
+        _ => None,
+    });
+    client.login(username, &password).unwrap();
+    wait_for(&client, "LOGIN_SUCCESS", |event| match event {
+        SignalingEvent::LoginSuccess(_) => Some(()),
+        SignalingEvent::LoginError(err) => panic!("login failed: {}", err),
+        _ => None,
+    });
+    client
+}
+
+/// Cada peer con su identidad DTLS, como la que el cliente carga al iniciar.
+fn peer(role: PeerConnectionRole, stun: &str) -> P2PClient {
+    let identity = Arc::new(DtlsIdentity::generate().unwrap());
+    let client = P2PClient::new(role, None, Some(identity)).unwrap();
+    client.set_stun_server(stun);
+    client
+}
+
+/// Espera ICE + DTLS + SCTP, fallando apenas el hilo de conexión avisa un
+/// error.
+fn wait_connected(client: &P2PClient, name: &str) {
+    let start = Instant::now();
+    while !(client.has_connection() && client.is_sctp_connected()) {
+        if let Some(event) = client
+            .connection_events()
+            .into_iter()
+            .find(|event| event.is_terminal())
+        {
+            panic!("{} failed to connect: {}", name, event);
+        }
+        assert!(
+            start.elapsed() < DEADLINE,
+            "{} not connected after {:?}",
+            name,
+            DEADLINE
+        );
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+#[test]
+fn two_clients_call_and_exchange_a_data_channel_message() {
+    let (addr, fingerprint) = start_server();
+    let stun = start_stun();
+    let tls = TlsClientSettings {
+        fingerprint: Some(fingerprint),
+        ..TlsClientSettings::default()
+    };
+
+    let alice = sign_up(addr, &tls, "alice");
+    let bob = sign_up(addr, &tls, "bob");
+
+    // Alice llama, Bob atiende
+    let mut caller = peer(PeerConnectionRole::Controlling, &stun);
+    let offer = caller.create_offer().unwrap();
+    alice.call("bob", &offer).unwrap();
+
+    let offer = wait_for(&bob, "INCOMING_CALL", |event| match event {
+        SignalingEvent::IncomingCall { from, sdp, caps } if from == "alice" => {
+            assert_eq!(caps, PeerCapabilities::local());
+            Some(sdp)
+        }
+        _ => None,
+    });
+    let mut callee = peer(PeerConnectionRole::Controlled, &stun);
+    let answer = callee.process_offer(&offer).unwrap();
+    bob.answer_call("alice", &answer).unwrap();
+
+    let answer = wait_for(&alice, "CALL_ACCEPTED", |event| match event {
+        SignalingEvent::CallAccepted { from, sdp, .. } if from == "bob" => Some(sdp),
+        SignalingEvent::CallRejected { .. } => panic!("bob rejected the call"),
+        _ => None,
+    });
+    caller.set_remote_description(&answer).unwrap();
+
+    let (incoming_tx, incoming_rx) = mpsc::sync_channel(16);
+    callee.set_sctp_incoming(incoming_tx);
+    for client in [&mut caller, &mut callee] {
+        client.establish_connection().unwrap();
+        client.start_listener(|_| {}).unwrap();
+    }
+    wait_connected(&caller, "caller");
+    wait_connected(&callee, "callee");
+
+    caller
+        .send_sctp_data(TEST_STREAM, b"hola desde alice".to_vec())
+        .unwrap();
+    let start = Instant::now();
+    let payload = loop {
+        let remaining = DEADLINE.saturating_sub(start.elapsed());
+        match incoming_rx.recv_timeout(remaining) {
+            Ok((TEST_STREAM, payload)) => break payload,
+            Ok(_) => continue,
+            Err(err) => panic!("no data channel message: {}", err),
+        }
+    };
+    assert_eq!(payload, b"hola desde alice");
+
+    alice.end_call("bob").unwrap();
+    wait_for(&bob, "CALL_ENDED", |event| match event {
+        SignalingEvent::CallEnded { from } if from == "alice" => Some(()),
+        _ => None,
+    });
+}
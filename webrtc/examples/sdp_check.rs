@@ -0,0 +1,59 @@
+//! Checks a captured SDP: `cargo run --example sdp_check <file>`.
+//!
+//! Prints what we extract from it in the format of the `.expected` files in
+//! `tests/fixtures/sdp`, so a field capture becomes a regression case by
+//! saving the output next to it. Then it builds the answer we would give and
+//! checks that it parses back with every required attribute.
+
+use room_rtc::rtc::rtc_peer_connection::{PeerConnectionRole, RtcPeerConnection};
+use room_rtc::rtc::sdp_negotiation::{missing_required_attributes, remote_sdp_summary};
+use room_rtc::SessionDescription;
+use std::process::ExitCode;
+use std::str::FromStr;
+
+fn main() -> ExitCode {
+    let Some(path) = std::env::args().nth(1) else {
+        eprintln!("usage: sdp_check <file.sdp>");
+        return ExitCode::FAILURE;
+    };
+    let sdp = match std::fs::read_to_string(&path) {
+        Ok(sdp) => sdp,
+        Err(err) => {
+            eprintln!("{}: {}", path, err);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    match remote_sdp_summary(&sdp) {
+        Ok(summary) => println!("{}", summary),
+        Err(err) => {
+            eprintln!("{}: {}", path, err);
+            return ExitCode::FAILURE;
+        }
+    }
+
+    match check_answer(&sdp) {
+        Ok(()) => {
+            eprintln!("answer: ok");
+            ExitCode::SUCCESS
+        }
+        Err(err) => {
+            eprintln!("answer: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Answers `sdp` as the controlled peer and checks the answer.
+fn check_answer(sdp: &str) -> Result<(), String> {
+    let mut answerer =
+        RtcPeerConnection::new(Some("127.0.0.1:0"), PeerConnectionRole::Controlled, None)
+            .map_err(|err| err.to_string())?;
+    let answer = answerer.process_offer(sdp).map_err(|err| err.to_string())?;
+    SessionDescription::from_str(&answer).map_err(|err| err.to_string())?;
+    let missing = missing_required_attributes(&answer);
+    if !missing.is_empty() {
+        return Err(format!("missing {}", missing.join(", ")));
+    }
+    Ok(())
+}
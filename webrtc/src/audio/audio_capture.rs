@@ -1,9 +1,9 @@
 //! Audio capture from microphone using cpal.
 
+use crate::worker_thread::media_channel::MediaSender;
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
 use cpal::{Device, SampleFormat, Stream, StreamConfig};
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::mpsc::SyncSender;
 use std::sync::Arc;
 
 const SAMPLE_RATE: u32 = 48000;
@@ -29,7 +29,15 @@ impl std::fmt::Display for AudioCaptureError {
     }
 }
 
-/// Captures audio from the default input device.
+/// Names of the input devices of the default host, for device pickers.
+pub fn input_device_names() -> Vec<String> {
+    cpal::default_host()
+        .input_devices()
+        .map(|devices| devices.filter_map(|d| d.name().ok()).collect())
+        .unwrap_or_default()
+}
+
+/// Captures audio from the default input device or a chosen one.
 pub struct AudioCapture {
     stream: Option<Stream>,
     muted: Arc<AtomicBool>,
@@ -37,11 +45,34 @@ pub struct AudioCapture {
 
 impl AudioCapture {
     /// Creates a new audio capture that sends PCM samples to the provided channel.
-    pub fn new(tx: SyncSender<Vec<i16>>) -> Result<Self, AudioCaptureError> {
+    pub fn new(tx: MediaSender<Vec<i16>>) -> Result<Self, AudioCaptureError> {
+        Self::with_device(tx, None)
+    }
+
+    /// Like [`AudioCapture::new`] but captures from the device called
+    /// `device_name`. A device that is no longer connected falls back to
+    /// the default one.
+    pub fn with_device(
+        tx: MediaSender<Vec<i16>>,
+        device_name: Option<&str>,
+    ) -> Result<Self, AudioCaptureError> {
         let host = cpal::default_host();
-        let device = host
-            .default_input_device()
-            .ok_or(AudioCaptureError::NoInputDevice)?;
+        let named = device_name.and_then(|name| {
+            let found = host
+                .input_devices()
+                .ok()?
+                .find(|d| d.name().map(|n| n == name).unwrap_or(false));
+            if found.is_none() {
+                log_warn!("Input device {} not found, using the default", name);
+            }
+            found
+        });
+        let device = match named {
+            Some(device) => device,
+            None => host
+                .default_input_device()
+                .ok_or(AudioCaptureError::NoInputDevice)?,
+        };
 
         let config = Self::find_config(&device)?;
         let muted = Arc::new(AtomicBool::new(false));
@@ -85,10 +116,10 @@ impl AudioCapture {
     fn build_stream(
         device: &Device,
         config: &StreamConfig,
-        tx: SyncSender<Vec<i16>>,
+        tx: MediaSender<Vec<i16>>,
         muted: Arc<AtomicBool>,
     ) -> Result<Stream, AudioCaptureError> {
-        let err_fn = |err| eprintln!("Audio capture error: {}", err);
+        let err_fn = |err| log_warn!("Audio capture error: {}", err);
 
         device
             .build_input_stream(
@@ -97,9 +128,9 @@ impl AudioCapture {
                     if muted.load(Ordering::Relaxed) {
                         // Send silence when muted
                         let silence = vec![0i16; data.len()];
-                        let _ = tx.try_send(silence);
+                        let _ = tx.send(silence);
                     } else {
-                        let _ = tx.try_send(data.to_vec());
+                        let _ = tx.send(data.to_vec());
                     }
                 },
                 err_fn,
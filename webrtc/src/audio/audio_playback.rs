@@ -1,12 +1,22 @@
 //! Audio playback to speakers using rodio (better PipeWire compatibility).
 
+use super::playout::{AudioFrame, PlayoutBuffer, PlayoutStats, PlayoutTarget};
+use super::ringback::{self, Ringback};
+use crate::worker_thread::media_channel::MediaReceiver;
+use rodio::cpal::traits::{DeviceTrait, HostTrait};
 use rodio::{OutputStream, Sink, Source};
-use std::sync::mpsc::Receiver;
+use std::sync::mpsc::Sender;
 use std::sync::{Arc, Mutex};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 const SAMPLE_RATE: u32 = 48000;
 const CHANNELS: u16 = 1; // Mono input
+/// The channel is drained into the playout buffer every 5 ms of audio.
+const DRAIN_PERIOD: usize = 240;
+/// A4, loud enough to hear without being unpleasant.
+const TEST_TONE_HZ: f32 = 440.0;
+const TEST_TONE_VOLUME: f32 = 0.2;
+const TEST_TONE_LENGTH: Duration = Duration::from_millis(1200);
 
 /// Error type for audio playback operations.
 #[derive(Debug)]
@@ -22,19 +32,39 @@ impl std::fmt::Display for AudioPlaybackError {
     }
 }
 
-/// Custom audio source that reads i16 samples from a channel
+/// Custom audio source that plays the frames received on a channel at
+/// their RTP time.
 struct ChannelSource {
-    rx: Arc<Mutex<Receiver<Vec<i16>>>>,
-    current_buffer: Vec<i16>,
-    position: usize,
+    rx: MediaReceiver<AudioFrame>,
+    buffer: PlayoutBuffer,
+    until_drain: usize,
+    stats: Arc<Mutex<PlayoutStats>>,
+    // Sondas de latencia ya reproducidas, con el momento en que sonaron
+    played: Option<Sender<(u32, Instant)>>,
 }
 
 impl ChannelSource {
-    fn new(rx: Receiver<Vec<i16>>) -> Self {
+    fn new(
+        rx: MediaReceiver<AudioFrame>,
+        target: PlayoutTarget,
+        stats: Arc<Mutex<PlayoutStats>>,
+        played: Option<Sender<(u32, Instant)>>,
+    ) -> Self {
         Self {
-            rx: Arc::new(Mutex::new(rx)),
-            current_buffer: Vec::new(),
-            position: 0,
+            rx,
+            buffer: PlayoutBuffer::adaptive(target),
+            until_drain: 0,
+            stats,
+            played,
+        }
+    }
+
+    fn drain(&mut self) {
+        for frame in self.rx.try_iter() {
+            self.buffer.push(frame);
+        }
+        if let Ok(mut stats) = self.stats.lock() {
+            *stats = self.buffer.stats();
         }
     }
 }
@@ -43,36 +73,19 @@ impl Iterator for ChannelSource {
     type Item = i16;
 
     fn next(&mut self) -> Option<Self::Item> {
-        // If we've consumed all samples in current buffer, try to get more
-        if self.position >= self.current_buffer.len() {
-            if let Ok(guard) = self.rx.lock() {
-                // Try to receive without blocking
-                match guard.try_recv() {
-                    Ok(new_samples) => {
-                        self.current_buffer = new_samples;
-                        self.position = 0;
-                    },
-                    Err(std::sync::mpsc::TryRecvError::Empty) => {
-                         return Some(0);
-                    },
-                    Err(std::sync::mpsc::TryRecvError::Disconnected) => {
-                        eprintln!("[PLAYBACK-RODIO] Source Disconnected!");
-                        return Some(0);
-                    }
-                }
-            } else {
-                return Some(0);
-            }
+        if self.until_drain == 0 {
+            self.drain();
+            self.until_drain = DRAIN_PERIOD;
         }
+        self.until_drain -= 1;
 
-        // Return next sample
-        if self.position < self.current_buffer.len() {
-            let sample = self.current_buffer[self.position];
-             self.position += 1;
-            Some(sample)
-        } else {
-            Some(0)
+        let sample = self.buffer.next_sample();
+        if let Some(probe) = self.buffer.take_played_probe() {
+            if let Some(played) = &self.played {
+                let _ = played.send((probe, Instant::now()));
+            }
         }
+        Some(sample)
     }
 }
 
@@ -94,34 +107,172 @@ impl Source for ChannelSource {
     }
 }
 
-/// Plays audio samples received from a channel.
+impl Source for Ringback {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        CHANNELS
+    }
+
+    fn sample_rate(&self) -> u32 {
+        ringback::SAMPLE_RATE
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None // Rings until dropped
+    }
+}
+
+/// Names of the output devices of the default host, for device pickers.
+pub fn output_device_names() -> Vec<String> {
+    rodio::cpal::default_host()
+        .output_devices()
+        .map(|devices| devices.filter_map(|d| d.name().ok()).collect())
+        .unwrap_or_default()
+}
+
+/// Opens the output device called `device_name`, or the default one if it is
+/// not set or no longer connected.
+fn open_output_stream(
+    device_name: Option<&str>,
+) -> Result<(OutputStream, rodio::OutputStreamHandle), rodio::StreamError> {
+    let named = device_name.and_then(|name| {
+        let found = rodio::cpal::default_host()
+            .output_devices()
+            .ok()?
+            .find(|d| d.name().map(|n| n == name).unwrap_or(false));
+        if found.is_none() {
+            log_warn!("Output device {} not found, using the default", name);
+        }
+        found
+    });
+    match named {
+        Some(device) => OutputStream::try_from_device(&device),
+        None => OutputStream::try_default(),
+    }
+}
+
+/// Plays audio frames received from a channel.
 pub struct AudioPlayback {
     _stream: OutputStream,
     _sink: Sink,
+    stats: Arc<Mutex<PlayoutStats>>,
 }
 
 impl AudioPlayback {
-    /// Creates a new audio playback that plays samples from the provided channel.
-    pub fn new(rx: Receiver<Vec<i16>>) -> Result<Self, AudioPlaybackError> {
-        eprintln!("[PLAYBACK-RODIO] Initializing rodio output stream...");
-        
-        let (stream, stream_handle) = OutputStream::try_default()
+    /// Creates a new audio playback that plays frames from the provided
+    /// channel, keeping a reserve within `target` against jitter.
+    pub fn new(
+        rx: MediaReceiver<AudioFrame>,
+        target: PlayoutTarget,
+    ) -> Result<Self, AudioPlaybackError> {
+        Self::with_device(rx, target, None, None)
+    }
+
+    /// Like [`AudioPlayback::new`] but plays on the device called
+    /// `device_name`. The latency probe of each frame, if any, goes to
+    /// `played` when the frame starts playing.
+    pub fn with_device(
+        rx: MediaReceiver<AudioFrame>,
+        target: PlayoutTarget,
+        device_name: Option<&str>,
+        played: Option<Sender<(u32, Instant)>>,
+    ) -> Result<Self, AudioPlaybackError> {
+        log_debug!("Initializing rodio output stream...");
+
+        let (stream, stream_handle) = open_output_stream(device_name)
             .map_err(|e| AudioPlaybackError::StreamError(e.to_string()))?;
 
-        eprintln!("[PLAYBACK-RODIO] Creating sink...");
+        log_debug!("Creating sink...");
         let sink = Sink::try_new(&stream_handle)
             .map_err(|e| AudioPlaybackError::StreamError(e.to_string()))?;
 
-        let source = ChannelSource::new(rx);
-        
-        eprintln!("[PLAYBACK-RODIO] Appending source to sink...");
+        let stats = Arc::new(Mutex::new(PlayoutStats {
+            target: target.min,
+            ..PlayoutStats::default()
+        }));
+        let source = ChannelSource::new(rx, target, Arc::clone(&stats), played);
+
+        log_debug!("Appending source to sink...");
         sink.append(source);
-        
-        eprintln!("[PLAYBACK-RODIO] Playback started successfully!");
 
+        log_debug!("Playback started successfully!");
+
+        Ok(Self {
+            _stream: stream,
+            _sink: sink,
+            stats,
+        })
+    }
+
+    /// Late frames, time stretching and underruns so far, and the reserve
+    /// the buffer is aiming for now.
+    pub fn stats(&self) -> PlayoutStats {
+        self.stats.lock().map(|stats| *stats).unwrap_or_default()
+    }
+}
+
+/// Short sine tone to check the speakers before a call. The device is
+/// released when it is dropped.
+pub struct TestTone {
+    _stream: OutputStream,
+    sink: Sink,
+}
+
+impl TestTone {
+    /// Starts playing the tone on the device called `device_name`, or the
+    /// default one.
+    pub fn play(device_name: Option<&str>) -> Result<Self, AudioPlaybackError> {
+        let (stream, sink) = play_local(
+            device_name,
+            rodio::source::SineWave::new(TEST_TONE_HZ)
+                .take_duration(TEST_TONE_LENGTH)
+                .amplify(TEST_TONE_VOLUME),
+        )?;
+        Ok(Self {
+            _stream: stream,
+            sink,
+        })
+    }
+
+    pub fn finished(&self) -> bool {
+        self.sink.empty()
+    }
+}
+
+/// Ringback heard by the caller while the callee's client rings. It stops
+/// when it is dropped.
+pub struct RingbackTone {
+    _stream: OutputStream,
+    _sink: Sink,
+}
+
+impl RingbackTone {
+    /// Starts ringing on the device called `device_name`, or the default
+    /// one.
+    pub fn play(device_name: Option<&str>) -> Result<Self, AudioPlaybackError> {
+        let (stream, sink) = play_local(device_name, Ringback::new())?;
         Ok(Self {
             _stream: stream,
             _sink: sink,
         })
     }
 }
+
+/// Plays a locally generated source on its own stream.
+fn play_local<S>(
+    device_name: Option<&str>,
+    source: S,
+) -> Result<(OutputStream, Sink), AudioPlaybackError>
+where
+    S: Source<Item = f32> + Send + 'static,
+{
+    let (stream, stream_handle) = open_output_stream(device_name)
+        .map_err(|e| AudioPlaybackError::StreamError(e.to_string()))?;
+    let sink = Sink::try_new(&stream_handle)
+        .map_err(|e| AudioPlaybackError::StreamError(e.to_string()))?;
+    sink.append(source);
+    Ok((stream, sink))
+}
@@ -0,0 +1,113 @@
+//! Decides when the receiver should make up audio because nothing arrived:
+//! a peer using Opus DTX only sends a comfort noise update now and then,
+//! and lost packets leave the speakers waiting too. The frames owed are
+//! generated by the decoder's concealment, which after a DTX update keeps
+//! producing comfort noise.
+
+use std::time::{Duration, Instant};
+
+const FRAME_SAMPLES: u32 = 960; // 20ms at 48kHz
+const FRAME_DURATION: Duration = Duration::from_millis(20);
+/// How late a frame may be before it is made up: jitter below this still
+/// lets the real packet play.
+const FILL_DELAY: Duration = Duration::from_millis(40);
+/// Opus DTX sends an update every 400 ms; longer than this without
+/// packets, the peer stopped and silence is better.
+const MAX_FILLED_FRAMES: u32 = 25;
+
+#[derive(Debug, Default)]
+pub struct ComfortNoise {
+    /// RTP timestamp and arrival of the newest packet.
+    last: Option<(u32, Instant)>,
+    /// The newest packet closed a talkspurt: the pause is meant to be silent.
+    talkspurt_ended: bool,
+    filled: u32,
+}
+
+impl ComfortNoise {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A packet arrived; reordered ones older than the newest are ignored.
+    pub fn on_packet(&mut self, timestamp: u32, marker: bool, now: Instant) {
+        let follows = match self.last {
+            Some((last, _)) => {
+                let ahead = timestamp.wrapping_sub(last) as i32;
+                if ahead <= 0 {
+                    return;
+                }
+                ahead as u32 == FRAME_SAMPLES
+            }
+            None => false,
+        };
+        // The marker also opens a talkspurt; only one right after the
+        // previous frame closes it
+        self.talkspurt_ended = marker && follows;
+        self.last = Some((timestamp, now));
+        self.filled = 0;
+    }
+
+    /// RTP timestamps of the frames to make up by `now`, oldest first.
+    pub fn due(&mut self, now: Instant) -> Vec<u32> {
+        let Some((last, arrived)) = self.last else {
+            return Vec::new();
+        };
+        if self.talkspurt_ended {
+            return Vec::new();
+        }
+        let waited = now.saturating_duration_since(arrived);
+        let mut due = Vec::new();
+        while self.filled < MAX_FILLED_FRAMES
+            && FRAME_DURATION * (self.filled + 1) + FILL_DELAY <= waited
+        {
+            self.filled += 1;
+            due.push(last.wrapping_add(self.filled * FRAME_SAMPLES));
+        }
+        due
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fills_the_gap_frame_by_frame() {
+        let start = Instant::now();
+        let mut comfort = ComfortNoise::new();
+        assert!(comfort.due(start + Duration::from_secs(1)).is_empty());
+
+        comfort.on_packet(1000, false, start);
+        assert!(comfort.due(start + Duration::from_millis(50)).is_empty());
+        assert_eq!(comfort.due(start + Duration::from_millis(60)), vec![1960]);
+        assert_eq!(
+            comfort.due(start + Duration::from_millis(100)),
+            vec![2920, 3880]
+        );
+
+        // Llega una actualización: se vuelve a contar desde ella
+        let update = start + Duration::from_millis(400);
+        comfort.on_packet(20_200, true, update);
+        assert_eq!(
+            comfort.due(update + Duration::from_millis(60)),
+            vec![21_160]
+        );
+        // Sin nada durante mucho tiempo se deja de inventar audio
+        let due = comfort.due(update + Duration::from_secs(5));
+        assert_eq!(due.len() as u32, MAX_FILLED_FRAMES - 1);
+    }
+
+    #[test]
+    fn test_end_of_talkspurt_stays_silent() {
+        let start = Instant::now();
+        let mut comfort = ComfortNoise::new();
+        comfort.on_packet(0, false, start);
+        comfort.on_packet(960, true, start);
+        assert!(comfort.due(start + Duration::from_secs(1)).is_empty());
+
+        // Uno viejo que llega tarde no cambia nada
+        comfort.on_packet(0, false, start);
+        assert!(comfort.due(start + Duration::from_secs(1)).is_empty());
+    }
+}
@@ -0,0 +1,121 @@
+//! Volume and mute of the received audio, applied before playout.
+
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+/// Loudest setting: twice the received level.
+pub const MAX_OUTPUT_GAIN: f32 = 2.0;
+
+/// Scales `samples` by `gain`, clamped to `0.0..=MAX_OUTPUT_GAIN`. Samples
+/// pushed past the `i16` range saturate instead of wrapping around.
+pub fn apply_gain(samples: &mut [i16], gain: f32) {
+    let gain = gain.clamp(0.0, MAX_OUTPUT_GAIN);
+    if gain == 1.0 {
+        return;
+    }
+    for sample in samples {
+        *sample = (f32::from(*sample) * gain)
+            .round()
+            .clamp(f32::from(i16::MIN), f32::from(i16::MAX)) as i16;
+    }
+}
+
+/// Playback volume shared between the UI and the decoder thread.
+#[derive(Debug)]
+pub struct OutputGain {
+    /// `f32` bits, so it can be read without locking.
+    gain: AtomicU32,
+    muted: AtomicBool,
+}
+
+impl OutputGain {
+    pub fn new() -> Self {
+        OutputGain {
+            gain: AtomicU32::new(1.0f32.to_bits()),
+            muted: AtomicBool::new(false),
+        }
+    }
+
+    /// Sets the volume, from 0.0 (silence) to `MAX_OUTPUT_GAIN`. Anything
+    /// that is not a number leaves it unchanged.
+    pub fn set_gain(&self, gain: f32) {
+        if !gain.is_nan() {
+            let gain = gain.clamp(0.0, MAX_OUTPUT_GAIN);
+            self.gain.store(gain.to_bits(), Ordering::Relaxed);
+        }
+    }
+
+    pub fn gain(&self) -> f32 {
+        f32::from_bits(self.gain.load(Ordering::Relaxed))
+    }
+
+    pub fn set_muted(&self, muted: bool) {
+        self.muted.store(muted, Ordering::Relaxed);
+    }
+
+    pub fn is_muted(&self) -> bool {
+        self.muted.load(Ordering::Relaxed)
+    }
+
+    /// Applies the volume to decoded samples; muted, they become silence.
+    pub fn apply(&self, samples: &mut [i16]) {
+        if self.is_muted() {
+            samples.fill(0);
+        } else {
+            apply_gain(samples, self.gain());
+        }
+    }
+}
+
+impl Default for OutputGain {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unity_gain_leaves_samples_alone() {
+        let mut samples = vec![i16::MIN, -1000, 0, 1000, i16::MAX];
+        apply_gain(&mut samples, 1.0);
+        assert_eq!(samples, vec![i16::MIN, -1000, 0, 1000, i16::MAX]);
+    }
+
+    #[test]
+    fn test_attenuation_and_amplification() {
+        let mut samples = vec![-1000, 0, 1000];
+        apply_gain(&mut samples, 0.5);
+        assert_eq!(samples, vec![-500, 0, 500]);
+
+        apply_gain(&mut samples, 2.0);
+        assert_eq!(samples, vec![-1000, 0, 1000]);
+
+        // Por encima del máximo se toma el máximo
+        apply_gain(&mut samples, 10.0);
+        assert_eq!(samples, vec![-2000, 0, 2000]);
+    }
+
+    #[test]
+    fn test_amplification_clips_instead_of_wrapping() {
+        let mut samples = vec![20_000, -20_000, i16::MAX, i16::MIN];
+        apply_gain(&mut samples, 2.0);
+        assert_eq!(samples, vec![i16::MAX, i16::MIN, i16::MAX, i16::MIN]);
+    }
+
+    #[test]
+    fn test_muted_output_is_silence() {
+        let output = OutputGain::new();
+        output.set_gain(1.5);
+        output.set_muted(true);
+        let mut samples = vec![1000; 4];
+        output.apply(&mut samples);
+        assert_eq!(samples, vec![0; 4]);
+
+        output.set_muted(false);
+        let mut samples = vec![1000; 4];
+        output.apply(&mut samples);
+        assert_eq!(samples, vec![1500; 4]);
+    }
+}
@@ -2,4 +2,10 @@
 
 pub mod audio_capture;
 pub mod audio_playback;
+pub mod comfort_noise;
+pub mod gain;
+pub mod level;
 pub mod opus_codec;
+pub mod playout;
+pub mod ringback;
+pub mod vad;
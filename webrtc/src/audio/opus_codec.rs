@@ -2,9 +2,11 @@
 
 use audiopus::coder::{Decoder, Encoder};
 use audiopus::packet::Packet;
-use audiopus::{Application, Channels, MutSignals, SampleRate};
+use audiopus::{Application, Bitrate, Channels, MutSignals, SampleRate};
 
 const FRAME_SIZE: usize = 960; // 20ms at 48kHz
+/// With DTX, packets this small carry nothing worth sending.
+const DTX_FRAME_MAX_BYTES: usize = 2;
 
 /// Error type for Opus codec operations.
 #[derive(Debug)]
@@ -13,6 +15,7 @@ pub enum OpusError {
     DecoderInit(String),
     EncodeError(String),
     DecodeError(String),
+    Config(String),
 }
 
 impl std::fmt::Display for OpusError {
@@ -22,6 +25,7 @@ impl std::fmt::Display for OpusError {
             Self::DecoderInit(e) => write!(f, "Decoder init failed: {}", e),
             Self::EncodeError(e) => write!(f, "Encode failed: {}", e),
             Self::DecodeError(e) => write!(f, "Decode failed: {}", e),
+            Self::Config(e) => write!(f, "Encoder config failed: {}", e),
         }
     }
 }
@@ -44,6 +48,37 @@ impl OpusEncoder {
         Ok(Self { encoder })
     }
 
+    /// Target bitrate in bits per second, `None` lets Opus choose.
+    pub fn set_bitrate(&mut self, bitrate_bps: Option<u32>) -> Result<(), OpusError> {
+        let bitrate = match bitrate_bps {
+            Some(bps) => Bitrate::BitsPerSecond(bps.min(i32::MAX as u32) as i32),
+            None => Bitrate::Auto,
+        };
+        self.encoder
+            .set_bitrate(bitrate)
+            .map_err(|e| OpusError::Config(e.to_string()))
+    }
+
+    /// In-band FEC: each packet also carries a low bitrate copy of the
+    /// previous one. Opus only adds it when `expected_loss_pct` is above 0.
+    pub fn set_fec(&mut self, enabled: bool, expected_loss_pct: u8) -> Result<(), OpusError> {
+        self.encoder
+            .set_inband_fec(enabled)
+            .map_err(|e| OpusError::Config(e.to_string()))?;
+        self.encoder
+            .set_packet_loss_perc(expected_loss_pct.min(100))
+            .map_err(|e| OpusError::Config(e.to_string()))
+    }
+
+    /// DTX: during silence Opus only sends a comfort noise update every
+    /// 400 ms; the frames in between come out as packets of one or two
+    /// bytes that need not be sent (see [`is_dtx_frame`]).
+    pub fn set_dtx(&mut self, enabled: bool) -> Result<(), OpusError> {
+        self.encoder
+            .set_dtx(enabled)
+            .map_err(|e| OpusError::Config(e.to_string()))
+    }
+
     /// Encodes PCM samples to Opus.
     /// Input should be 960 samples (20ms at 48kHz).
     /// Returns the encoded Opus frame.
@@ -66,6 +101,12 @@ impl OpusEncoder {
     }
 }
 
+/// Whether `packet` is a frame the encoder marked as not worth sending
+/// because of DTX.
+pub fn is_dtx_frame(packet: &[u8]) -> bool {
+    packet.len() <= DTX_FRAME_MAX_BYTES
+}
+
 /// Opus audio decoder.
 pub struct OpusDecoder {
     decoder: Decoder,
@@ -100,6 +141,26 @@ impl OpusDecoder {
         Ok(output)
     }
 
+    /// Rebuilds the frame lost right before `opus_data` from the FEC data it
+    /// carries. Without FEC data Opus falls back to concealment.
+    pub fn decode_fec(&mut self, opus_data: &[u8]) -> Result<Vec<i16>, OpusError> {
+        let mut output = vec![0i16; FRAME_SIZE];
+
+        let packet =
+            Packet::try_from(opus_data).map_err(|e| OpusError::DecodeError(e.to_string()))?;
+
+        let signals = MutSignals::try_from(&mut output[..])
+            .map_err(|e| OpusError::DecodeError(e.to_string()))?;
+
+        let samples = self
+            .decoder
+            .decode(Some(packet), signals, true)
+            .map_err(|e| OpusError::DecodeError(e.to_string()))?;
+
+        output.truncate(samples);
+        Ok(output)
+    }
+
     /// Generates concealment samples when a packet is lost.
     pub fn decode_lost(&mut self) -> Result<Vec<i16>, OpusError> {
         let mut output = vec![0i16; FRAME_SIZE];
@@ -137,4 +198,52 @@ mod tests {
         let decoded = decoder.decode(&encoded).expect("decode");
         assert_eq!(decoded.len(), FRAME_SIZE);
     }
+
+    #[test]
+    fn fec_recovers_a_lost_frame() {
+        let mut encoder = OpusEncoder::new().expect("encoder");
+        encoder.set_bitrate(Some(32_000)).expect("bitrate");
+        encoder.set_fec(true, 20).expect("fec");
+        let mut decoder = OpusDecoder::new().expect("decoder");
+
+        let frames: Vec<Vec<u8>> = (0..5)
+            .map(|n| {
+                let samples: Vec<i16> = (0..FRAME_SIZE)
+                    .map(|i| (((i + n * FRAME_SIZE) as f32 * 0.05).sin() * 8000.0) as i16)
+                    .collect();
+                encoder.encode(&samples).expect("encode")
+            })
+            .collect();
+
+        // Se pierde el frame 3: se reconstruye con la FEC del 4
+        for frame in &frames[..3] {
+            decoder.decode(frame).expect("decode");
+        }
+        let recovered = decoder.decode_fec(&frames[4]).expect("fec");
+        assert_eq!(recovered.len(), FRAME_SIZE);
+        assert_eq!(
+            decoder.decode(&frames[4]).expect("decode").len(),
+            FRAME_SIZE
+        );
+        assert_eq!(decoder.decode_lost().expect("plc").len(), FRAME_SIZE);
+    }
+
+    #[test]
+    fn dtx_skips_silent_frames() {
+        let mut encoder = OpusEncoder::new().expect("encoder");
+        encoder.set_dtx(true).expect("dtx");
+        let mut decoder = OpusDecoder::new().expect("decoder");
+
+        let frames: Vec<Vec<u8>> = (0..50)
+            .map(|_| encoder.encode(&[0; FRAME_SIZE]).expect("encode"))
+            .collect();
+        // Tras unos frames de silencio deja de haber algo que mandar
+        assert!(frames.iter().any(|frame| is_dtx_frame(frame)));
+
+        // Entre actualizaciones el decoder genera ruido de confort
+        for frame in frames.iter().filter(|frame| !is_dtx_frame(frame)) {
+            decoder.decode(frame).expect("decode");
+        }
+        assert_eq!(decoder.decode_lost().expect("cng").len(), FRAME_SIZE);
+    }
 }
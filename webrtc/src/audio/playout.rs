@@ -0,0 +1,559 @@
+//! Playout scheduling for received audio.
+//!
+//! Decoded frames are placed on the RTP timeline (48 kHz, one tick per
+//! sample). Frames whose time already played are dropped as late. The
+//! buffer is kept close to a target by dropping one sample now and then
+//! when it grows, by inserting one (interpolated) when it falls short, and
+//! by repeating the last sample when it runs dry.
+//! A sender using DTX marks the frames around its pauses; running dry after
+//! one of those plays silence instead.
+//!
+//! With an adaptive target, every underrun raises it a step, up to its
+//! maximum, and a few quiet seconds lower it back towards its minimum.
+
+use std::collections::VecDeque;
+
+/// Samples kept in reserve against network jitter: 5 ms.
+pub const DEFAULT_PLAYOUT_TARGET: usize = 240;
+/// How far the reserve grows after underruns by default: 120 ms.
+pub const DEFAULT_PLAYOUT_MAX: usize = 5_760;
+/// Largest reserve a target may ask for (150 ms), well under `MAX_BUFFERED`.
+pub const MAX_PLAYOUT_TARGET: usize = 7_200;
+/// Samples per millisecond at 48 kHz.
+pub const SAMPLES_PER_MS: usize = 48;
+/// An underrun raises the target this much: 10 ms.
+const UNDERRUN_STEP: usize = 480;
+/// Every this many windows (5 s) without an underrun...
+const QUIET_WINDOWS: usize = 10;
+/// ...the target goes down this much: 5 ms.
+const DECAY_STEP: usize = 240;
+/// The smallest buffer level seen over this many samples (0.5 s) says how
+/// much of it is not needed.
+const DRIFT_WINDOW: usize = 24_000;
+/// At most one sample dropped every this many, about 2% faster playback.
+const STRETCH_PERIOD: usize = 48;
+/// Past this (200 ms) the oldest frames are thrown away outright.
+const MAX_BUFFERED: usize = 9_600;
+/// A frame this far from the timeline (1 s) means the sender restarted.
+const RESYNC_DISTANCE: i64 = 48_000;
+
+/// Reserve the playout keeps against jitter, in samples: it starts at
+/// `min` and adapts up to `max`. Equal bounds keep it fixed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PlayoutTarget {
+    pub min: usize,
+    pub max: usize,
+}
+
+impl PlayoutTarget {
+    pub fn fixed(samples: usize) -> Self {
+        let samples = samples.min(MAX_PLAYOUT_TARGET);
+        PlayoutTarget {
+            min: samples,
+            max: samples,
+        }
+    }
+
+    /// From milliseconds. `max` is raised to `min` if lower and capped at
+    /// [`MAX_PLAYOUT_TARGET`].
+    pub fn from_ms(min_ms: u32, max_ms: u32) -> Self {
+        let min = (min_ms as usize * SAMPLES_PER_MS).min(MAX_PLAYOUT_TARGET);
+        let max = (max_ms as usize * SAMPLES_PER_MS).clamp(min, MAX_PLAYOUT_TARGET);
+        PlayoutTarget { min, max }
+    }
+}
+
+impl Default for PlayoutTarget {
+    fn default() -> Self {
+        PlayoutTarget {
+            min: DEFAULT_PLAYOUT_TARGET,
+            max: DEFAULT_PLAYOUT_MAX,
+        }
+    }
+}
+
+/// Decoded PCM with the RTP timestamp of its first sample.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AudioFrame {
+    pub timestamp: u32,
+    pub samples: Vec<i16>,
+    /// Latency probe carried by the packet, echoed once the frame plays.
+    pub probe: Option<u32>,
+    /// RTP marker: the last frame before a DTX pause, or the first after it.
+    pub marker: bool,
+}
+
+/// What the scheduler did to keep latency low.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PlayoutStats {
+    /// Frames that arrived after their time had already played.
+    pub late_frames: u64,
+    /// Samples skipped to shrink the buffer, including discarded frames.
+    pub dropped_samples: u64,
+    /// Samples repeated because the buffer ran dry.
+    pub inserted_samples: u64,
+    /// Samples interpolated to grow the buffer back to its target.
+    pub stretched_samples: u64,
+    /// Times the buffer ran dry while the sender was talking.
+    pub underruns: u64,
+    /// Reserve the buffer is aiming for now, in samples.
+    pub target: usize,
+}
+
+impl PlayoutStats {
+    /// The current target in milliseconds.
+    pub fn target_ms(&self) -> usize {
+        self.target / SAMPLES_PER_MS
+    }
+}
+
+pub struct PlayoutBuffer {
+    frames: VecDeque<AudioFrame>,
+    current: Option<AudioFrame>,
+    position: usize,
+    /// RTP timestamp of the next sample to play.
+    next_ts: Option<u32>,
+    last_sample: i16,
+    /// The frame played last was marked, so a pause may follow.
+    after_marker: bool,
+    /// Ran dry in the middle of speech; cleared by the next sample played.
+    dry: bool,
+    target: usize,
+    range: PlayoutTarget,
+    window_min: usize,
+    window_len: usize,
+    /// An underrun happened in the current window.
+    window_underrun: bool,
+    quiet_windows: usize,
+    /// Samples still to drop, spread over the next window.
+    excess: usize,
+    /// Samples still to insert, spread the same way.
+    deficit: usize,
+    since_stretch: usize,
+    played_probe: Option<u32>,
+    stats: PlayoutStats,
+}
+
+impl PlayoutBuffer {
+    /// `target` is the reserve, in samples, the buffer settles at.
+    pub fn new(target: usize) -> Self {
+        Self::adaptive(PlayoutTarget::fixed(target))
+    }
+
+    /// Starts with a reserve of `range.min` that underruns push towards
+    /// `range.max`.
+    pub fn adaptive(range: PlayoutTarget) -> Self {
+        PlayoutBuffer {
+            frames: VecDeque::new(),
+            current: None,
+            position: 0,
+            next_ts: None,
+            last_sample: 0,
+            after_marker: false,
+            dry: false,
+            target: range.min,
+            range,
+            window_min: usize::MAX,
+            window_len: 0,
+            window_underrun: false,
+            quiet_windows: 0,
+            excess: 0,
+            deficit: 0,
+            since_stretch: 0,
+            played_probe: None,
+            stats: PlayoutStats {
+                target: range.min,
+                ..PlayoutStats::default()
+            },
+        }
+    }
+
+    /// Queues a frame in timestamp order. Returns `false` if it came too
+    /// late to be played (or is a duplicate) and was dropped.
+    pub fn push(&mut self, frame: AudioFrame) -> bool {
+        if frame.samples.is_empty() {
+            return false;
+        }
+        // Speech resumes after a pause: the timeline starts over from it
+        if frame.marker && self.buffered() == 0 {
+            self.next_ts = None;
+        }
+        if let Some(next_ts) = self.next_ts {
+            let end = frame.timestamp.wrapping_add(frame.samples.len() as u32);
+            let behind = ts_diff(end, next_ts);
+            if behind < -RESYNC_DISTANCE || ts_diff(frame.timestamp, next_ts) > RESYNC_DISTANCE {
+                self.resync();
+            } else if behind <= 0 {
+                self.stats.late_frames += 1;
+                return false;
+            }
+        }
+
+        let index = self
+            .frames
+            .iter()
+            .position(|queued| ts_diff(frame.timestamp, queued.timestamp) <= 0)
+            .unwrap_or(self.frames.len());
+        if self
+            .frames
+            .get(index)
+            .is_some_and(|queued| queued.timestamp == frame.timestamp)
+        {
+            return false;
+        }
+        self.frames.insert(index, frame);
+
+        while self.buffered() > MAX_BUFFERED {
+            let Some(oldest) = self.frames.pop_front() else {
+                break;
+            };
+            self.stats.dropped_samples += oldest.samples.len() as u64;
+        }
+        true
+    }
+
+    /// Next sample for the speakers. Never runs out: an empty buffer plays
+    /// the last sample again (silence before anything arrived, or during a
+    /// DTX pause).
+    pub fn next_sample(&mut self) -> i16 {
+        self.track_drift();
+        if self.deficit > 0 && self.since_stretch >= STRETCH_PERIOD {
+            if let Some(next) = self.peek_sample() {
+                // Halfway to the next one, so the extra sample does not click
+                let sample = ((i32::from(self.last_sample) + i32::from(next)) / 2) as i16;
+                self.deficit -= 1;
+                self.since_stretch = 0;
+                self.stats.stretched_samples += 1;
+                self.last_sample = sample;
+                return sample;
+            }
+        }
+        let Some(sample) = self.take_sample() else {
+            if self.after_marker {
+                // The sender stopped on purpose: nothing is missing
+                self.last_sample = 0;
+            } else if self.next_ts.is_some() {
+                self.stats.inserted_samples += 1;
+                if !self.dry {
+                    self.dry = true;
+                    self.on_underrun();
+                }
+            }
+            return self.last_sample;
+        };
+
+        self.dry = false;
+        self.since_stretch += 1;
+        if self.excess > 0 && self.since_stretch >= STRETCH_PERIOD && self.take_sample().is_some() {
+            self.excess -= 1;
+            self.since_stretch = 0;
+            self.stats.dropped_samples += 1;
+        }
+        self.last_sample = sample;
+        sample
+    }
+
+    /// Samples waiting to be played.
+    pub fn buffered(&self) -> usize {
+        let current = self
+            .current
+            .as_ref()
+            .map_or(0, |frame| frame.samples.len() - self.position);
+        current
+            + self
+                .frames
+                .iter()
+                .map(|frame| frame.samples.len())
+                .sum::<usize>()
+    }
+
+    /// Probe of the frame that started playing since the last call.
+    pub fn take_played_probe(&mut self) -> Option<u32> {
+        self.played_probe.take()
+    }
+
+    pub fn stats(&self) -> PlayoutStats {
+        self.stats
+    }
+
+    fn peek_sample(&self) -> Option<i16> {
+        match &self.current {
+            Some(frame) => frame.samples.get(self.position).copied(),
+            None => self
+                .frames
+                .front()
+                .and_then(|frame| frame.samples.first().copied()),
+        }
+    }
+
+    fn take_sample(&mut self) -> Option<i16> {
+        if self.current.is_none() {
+            self.start_next_frame()?;
+        }
+        let frame = self.current.as_ref()?;
+        let sample = frame.samples[self.position];
+        self.position += 1;
+        if self.position >= frame.samples.len() {
+            self.current = None;
+        }
+        self.next_ts = self.next_ts.map(|ts| ts.wrapping_add(1));
+        Some(sample)
+    }
+
+    fn start_next_frame(&mut self) -> Option<()> {
+        let frame = self.frames.pop_front()?;
+        let next_ts = *self.next_ts.get_or_insert(frame.timestamp);
+        // A gap (uncovered loss) is skipped; an overlap starts mid frame
+        let position = ts_diff(next_ts, frame.timestamp).max(0) as usize;
+        if position == 0 {
+            self.next_ts = Some(frame.timestamp);
+        }
+        if position >= frame.samples.len() {
+            self.stats.dropped_samples += frame.samples.len() as u64;
+            return self.start_next_frame();
+        }
+        self.stats.dropped_samples += position as u64;
+        if frame.probe.is_some() {
+            self.played_probe = frame.probe;
+        }
+        self.after_marker = frame.marker;
+        self.position = position;
+        self.current = Some(frame);
+        Some(())
+    }
+
+    fn track_drift(&mut self) {
+        self.window_min = self.window_min.min(self.buffered());
+        self.window_len += 1;
+        if self.window_len >= DRIFT_WINDOW {
+            if self.window_underrun {
+                self.quiet_windows = 0;
+            } else {
+                self.quiet_windows += 1;
+                if self.quiet_windows >= QUIET_WINDOWS {
+                    self.quiet_windows = 0;
+                    self.set_target(self.target.saturating_sub(DECAY_STEP));
+                }
+            }
+            // A window that ran dry already grew the buffer by as much
+            if self.window_underrun || self.after_marker {
+                self.deficit = 0;
+            } else {
+                self.deficit = self.target.saturating_sub(self.window_min);
+            }
+            self.excess = self.window_min.saturating_sub(self.target);
+            self.window_min = usize::MAX;
+            self.window_len = 0;
+            self.window_underrun = false;
+        }
+    }
+
+    fn on_underrun(&mut self) {
+        self.stats.underruns += 1;
+        self.window_underrun = true;
+        self.quiet_windows = 0;
+        self.excess = 0;
+        self.set_target(self.target + UNDERRUN_STEP);
+    }
+
+    fn set_target(&mut self, target: usize) {
+        self.target = target.clamp(self.range.min, self.range.max);
+        self.stats.target = self.target;
+    }
+
+    fn resync(&mut self) {
+        self.stats.dropped_samples += self.buffered() as u64;
+        self.frames.clear();
+        self.current = None;
+        self.next_ts = None;
+        self.excess = 0;
+        self.deficit = 0;
+    }
+}
+
+/// `a - b` on the wrapping 32-bit RTP clock.
+fn ts_diff(a: u32, b: u32) -> i64 {
+    a.wrapping_sub(b) as i32 as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(timestamp: u32, value: i16) -> AudioFrame {
+        AudioFrame {
+            timestamp,
+            samples: vec![value; 960],
+            probe: None,
+            marker: false,
+        }
+    }
+
+    fn play(buffer: &mut PlayoutBuffer, count: usize) -> Vec<i16> {
+        (0..count).map(|_| buffer.next_sample()).collect()
+    }
+
+    #[test]
+    fn test_reorders_and_drops_late_frames() {
+        let mut buffer = PlayoutBuffer::new(DEFAULT_PLAYOUT_TARGET);
+        assert!(buffer.push(frame(960, 2)));
+        assert!(buffer.push(frame(0, 1)));
+        assert!(!buffer.push(frame(0, 1)));
+
+        let played = play(&mut buffer, 1920);
+        assert_eq!(played[0], 1);
+        assert_eq!(played[1919], 2);
+
+        // Its time already played
+        assert!(!buffer.push(frame(960, 3)));
+        assert_eq!(buffer.stats().late_frames, 1);
+        assert!(buffer.push(frame(1920, 4)));
+        assert_eq!(buffer.next_sample(), 4);
+    }
+
+    #[test]
+    fn test_underrun_repeats_and_gap_is_skipped() {
+        let mut buffer = PlayoutBuffer::new(DEFAULT_PLAYOUT_TARGET);
+        // Nothing received yet: silence, not counted
+        assert_eq!(buffer.next_sample(), 0);
+        assert_eq!(buffer.stats().inserted_samples, 0);
+
+        buffer.push(frame(0, 5));
+        play(&mut buffer, 960);
+        assert_eq!(play(&mut buffer, 10), vec![5; 10]);
+        assert_eq!(buffer.stats().inserted_samples, 10);
+
+        // The frame at 960 was lost: the next one plays right away
+        buffer.push(frame(1920, 6));
+        assert_eq!(buffer.next_sample(), 6);
+    }
+
+    #[test]
+    fn test_standing_backlog_is_drained() {
+        let mut buffer = PlayoutBuffer::new(DEFAULT_PLAYOUT_TARGET);
+        let mut ts = 0;
+        // 100 ms de más que nunca se consumen por el jitter
+        for _ in 0..5 {
+            buffer.push(frame(ts, 1));
+            ts += 960;
+        }
+        for _ in 0..200 {
+            buffer.push(frame(ts, 1));
+            ts += 960;
+            play(&mut buffer, 960);
+        }
+        assert!(buffer.buffered() < 3 * 960);
+        assert!(buffer.stats().dropped_samples > 0);
+    }
+
+    #[test]
+    fn test_underruns_raise_the_target_up_to_its_max() {
+        let mut buffer = PlayoutBuffer::adaptive(PlayoutTarget::from_ms(5, 20));
+        assert_eq!(buffer.stats().target_ms(), 5);
+        let mut ts = 0;
+        for _ in 0..3 {
+            buffer.push(frame(ts, 1));
+            ts += 960;
+            play(&mut buffer, 960 + 100);
+        }
+        let stats = buffer.stats();
+        assert_eq!(stats.underruns, 3);
+        // 5 + 10 y después tope en 20
+        assert_eq!(stats.target_ms(), 20);
+    }
+
+    #[test]
+    fn test_quiet_playout_lowers_the_target() {
+        let mut buffer = PlayoutBuffer::adaptive(PlayoutTarget::from_ms(5, 120));
+        buffer.push(frame(0, 1));
+        play(&mut buffer, 960 + 10);
+        assert_eq!(buffer.stats().target_ms(), 15);
+
+        // Sin cortes por más de QUIET_WINDOWS ventanas
+        let mut ts = 960;
+        for _ in 0..3 {
+            buffer.push(frame(ts, 1));
+            ts += 960;
+        }
+        for _ in 0..(QUIET_WINDOWS * DRIFT_WINDOW / 960 + 30) {
+            buffer.push(frame(ts, 1));
+            ts += 960;
+            play(&mut buffer, 960);
+        }
+        let stats = buffer.stats();
+        assert_eq!(stats.underruns, 1);
+        assert_eq!(stats.target_ms(), 10);
+    }
+
+    #[test]
+    fn test_short_reserve_is_stretched_without_underruns() {
+        let mut buffer = PlayoutBuffer::new(960);
+        let mut ts = 0;
+        for _ in 0..60 {
+            buffer.push(frame(ts, 3));
+            ts += 960;
+            assert!(play(&mut buffer, 960).iter().all(|sample| *sample == 3));
+        }
+        let stats = buffer.stats();
+        assert!(stats.stretched_samples > 0);
+        assert_eq!(stats.underruns, 0);
+        assert!(buffer.buffered() > 0);
+    }
+
+    #[test]
+    fn test_target_from_ms_is_bounded() {
+        assert_eq!(
+            PlayoutTarget::from_ms(40, 120),
+            PlayoutTarget {
+                min: 1_920,
+                max: 5_760
+            }
+        );
+        assert_eq!(PlayoutTarget::from_ms(60, 20).max, 2_880);
+        assert_eq!(PlayoutTarget::from_ms(5, 1_000).max, MAX_PLAYOUT_TARGET);
+    }
+
+    #[test]
+    fn test_probe_is_reported_when_played() {
+        let mut buffer = PlayoutBuffer::new(DEFAULT_PLAYOUT_TARGET);
+        buffer.push(AudioFrame {
+            probe: Some(77),
+            ..frame(0, 1)
+        });
+        assert_eq!(buffer.take_played_probe(), None);
+        buffer.next_sample();
+        assert_eq!(buffer.take_played_probe(), Some(77));
+        assert_eq!(buffer.take_played_probe(), None);
+    }
+
+    #[test]
+    fn test_dtx_pause_is_silence_not_underrun() {
+        let mut buffer = PlayoutBuffer::new(DEFAULT_PLAYOUT_TARGET);
+        buffer.push(frame(0, 5));
+        buffer.push(AudioFrame {
+            marker: true,
+            ..frame(960, 5)
+        });
+        play(&mut buffer, 1920 + 100);
+        assert_eq!(buffer.next_sample(), 0);
+        assert_eq!(buffer.stats().inserted_samples, 0);
+
+        // 300 ms después vuelve la voz, con el timestamp adelantado
+        assert!(buffer.push(AudioFrame {
+            marker: true,
+            ..frame(960 + 15_360, 7)
+        }));
+        assert_eq!(buffer.next_sample(), 7);
+        assert_eq!(buffer.stats().late_frames, 0);
+    }
+
+    #[test]
+    fn test_timestamp_jump_resyncs() {
+        let mut buffer = PlayoutBuffer::new(DEFAULT_PLAYOUT_TARGET);
+        buffer.push(frame(1_000_000, 1));
+        play(&mut buffer, 960);
+        // El emisor reinició su reloj
+        assert!(buffer.push(frame(0, 2)));
+        assert_eq!(buffer.next_sample(), 2);
+    }
+}
@@ -0,0 +1,79 @@
+//! Ringback tone played to the caller while the callee's client rings.
+//!
+//! Uses the cadence of the Argentine network (ITU-T E.180): 425 Hz, one
+//! second on and four off, repeated until it is dropped.
+
+/// Samples per second of the generated tone.
+pub const SAMPLE_RATE: u32 = 48_000;
+const FREQUENCY_HZ: f32 = 425.0;
+const VOLUME: f32 = 0.2;
+/// One second of tone...
+const ON_SAMPLES: u64 = SAMPLE_RATE as u64;
+/// ...out of every five.
+const PERIOD_SAMPLES: u64 = 5 * SAMPLE_RATE as u64;
+
+/// Endless mono source of ringback samples in `[-VOLUME, VOLUME]`.
+#[derive(Debug, Default)]
+pub struct Ringback {
+    sample: u64,
+}
+
+impl Ringback {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether the next sample falls in the audible part of the cadence.
+    pub fn is_on(&self) -> bool {
+        self.sample % PERIOD_SAMPLES < ON_SAMPLES
+    }
+}
+
+impl Iterator for Ringback {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let value = if self.is_on() {
+            // La fase se toma dentro del período para no perder precisión
+            let t = (self.sample % PERIOD_SAMPLES) as f32 / SAMPLE_RATE as f32;
+            (2.0 * std::f32::consts::PI * FREQUENCY_HZ * t).sin() * VOLUME
+        } else {
+            0.0
+        };
+        self.sample += 1;
+        Some(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_first_second_is_the_tone() {
+        let tone: Vec<f32> = Ringback::new().take(ON_SAMPLES as usize).collect();
+        let peak = tone.iter().fold(0.0f32, |max, s| max.max(s.abs()));
+        assert!(peak > VOLUME * 0.9);
+        assert!(peak <= VOLUME);
+    }
+
+    #[test]
+    fn test_silent_for_the_rest_of_the_period() {
+        let mut ringback = Ringback::new();
+        ringback.nth(ON_SAMPLES as usize - 1);
+        assert!(!ringback.is_on());
+        let gap = (PERIOD_SAMPLES - ON_SAMPLES) as usize;
+        assert!(ringback.by_ref().take(gap).all(|s| s == 0.0));
+        assert!(ringback.is_on());
+    }
+
+    #[test]
+    fn test_cadence_repeats() {
+        let first: Vec<f32> = Ringback::new().take(1000).collect();
+        let second: Vec<f32> = Ringback::new()
+            .skip(PERIOD_SAMPLES as usize)
+            .take(1000)
+            .collect();
+        assert_eq!(first, second);
+    }
+}
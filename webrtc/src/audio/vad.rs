@@ -0,0 +1,82 @@
+//! Energy based voice activity detection, used to stop sending audio during
+//! silence (DTX).
+
+/// Frames quieter than this (RMS, dBFS) count as silence.
+pub const DEFAULT_SPEECH_THRESHOLD_DB: f32 = -45.0;
+/// Silent frames still sent after speech (200 ms), so word endings and
+/// short pauses are not cut.
+pub const DEFAULT_HANGOVER_FRAMES: u32 = 10;
+
+pub struct VoiceActivityDetector {
+    threshold_db: f32,
+    hangover_frames: u32,
+    /// Silent frames left before reporting silence.
+    hangover: u32,
+}
+
+impl VoiceActivityDetector {
+    pub fn new(threshold_db: f32, hangover_frames: u32) -> Self {
+        VoiceActivityDetector {
+            threshold_db,
+            hangover_frames,
+            // Starts talking: the first frames always go out
+            hangover: hangover_frames,
+        }
+    }
+
+    /// Whether `frame` should be sent: it has speech, or speech ended less
+    /// than the hangover ago.
+    pub fn is_speech(&mut self, frame: &[i16]) -> bool {
+        if level_db(frame) >= self.threshold_db {
+            self.hangover = self.hangover_frames;
+            return true;
+        }
+        if self.hangover > 0 {
+            self.hangover -= 1;
+            return true;
+        }
+        false
+    }
+}
+
+impl Default for VoiceActivityDetector {
+    fn default() -> Self {
+        Self::new(DEFAULT_SPEECH_THRESHOLD_DB, DEFAULT_HANGOVER_FRAMES)
+    }
+}
+
+/// RMS level of `frame` in dBFS; digital silence is minus infinity.
+fn level_db(frame: &[i16]) -> f32 {
+    if frame.is_empty() {
+        return f32::NEG_INFINITY;
+    }
+    let sum: f64 = frame.iter().map(|s| f64::from(*s).powi(2)).sum();
+    let rms = (sum / frame.len() as f64).sqrt() / f64::from(i16::MAX);
+    20.0 * rms.log10() as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hangover_keeps_sending_after_speech() {
+        let speech = vec![i16::MAX / 4; 960];
+        let silence = vec![3; 960];
+        let mut vad = VoiceActivityDetector::new(DEFAULT_SPEECH_THRESHOLD_DB, 2);
+
+        assert!(vad.is_speech(&speech));
+        assert!(vad.is_speech(&silence));
+        assert!(vad.is_speech(&silence));
+        assert!(!vad.is_speech(&silence));
+        // La voz vuelve y se manda de inmediato
+        assert!(vad.is_speech(&speech));
+    }
+
+    #[test]
+    fn test_starts_sending_until_hangover_runs_out() {
+        let mut vad = VoiceActivityDetector::new(DEFAULT_SPEECH_THRESHOLD_DB, 1);
+        assert!(vad.is_speech(&[0; 960]));
+        assert!(!vad.is_speech(&[0; 960]));
+    }
+}
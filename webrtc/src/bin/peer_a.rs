@@ -4,7 +4,7 @@ use std::io::{self, BufRead};
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("Peer A (Caller)");
 
-    let mut peer_connection = RtcPeerConnection::new(None, PeerConnectionRole::Controlling)?;
+    let mut peer_connection = RtcPeerConnection::new(None, PeerConnectionRole::Controlling, None)?;
 
     let local_addr = peer_connection.local_addr()?;
     println!(" Local address: {}", local_addr);
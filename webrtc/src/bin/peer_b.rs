@@ -4,7 +4,7 @@ use std::io::{self, BufRead};
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("Peer B (Callee)");
 
-    let mut peer_connection = RtcPeerConnection::new(None, PeerConnectionRole::Controlled)?;
+    let mut peer_connection = RtcPeerConnection::new(None, PeerConnectionRole::Controlled, None)?;
     println!(" Local address: {}", peer_connection.local_addr()?);
 
     println!(" Input the SDP Offer of Peer A:");
@@ -1,13 +1,44 @@
 use crate::camera::camera_err::CameraError;
+use crate::camera::video_format::{merge_format, VideoFormat};
 use opencv::videoio::VideoCapture;
 use opencv::{imgproc, prelude::*, videoio};
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
 // src/camera/camera_opencv.rs
 //use opencv::prelude::*;
 //use std::thread::sleep;
 //use std::time::Duration;
 
+/// Resoluciones que se prueban para armar la lista de formatos.
+const PROBE_SIZES: [(f64, f64); 6] = [
+    (320.0, 240.0),
+    (640.0, 360.0),
+    (640.0, 480.0),
+    (800.0, 600.0),
+    (1280.0, 720.0),
+    (1920.0, 1080.0),
+];
+/// Se pide este fps y el driver contesta el mayor que acepta.
+const PROBE_FPS: f64 = 60.0;
+
+/// Formatos ya probados, por índice de dispositivo.
+fn probed_formats() -> &'static Mutex<HashMap<i32, Vec<VideoFormat>>> {
+    static PROBED: OnceLock<Mutex<HashMap<i32, Vec<VideoFormat>>>> = OnceLock::new();
+    PROBED.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Si no se pudo abrir, lo que haya en ese índice la próxima vez puede ser
+/// otra cámara.
+fn forget_formats(index: i32) {
+    if let Ok(mut probed) = probed_formats().lock() {
+        probed.remove(&index);
+    }
+}
+
 pub struct Camera {
     video_capture: VideoCapture,
+    // Dispositivo abierto, para no volver a probar sus formatos
+    index: i32,
 }
 
 impl Camera {
@@ -41,15 +72,16 @@ impl Camera {
         ];
 
         for (backend_const, backend_name) in backends.iter() {
-            eprintln!(
+            log_debug!(
                 "with_params -> intentando backend {} (code {})",
-                backend_name, backend_const
+                backend_name,
+                backend_const
             );
 
             let mut vc = match VideoCapture::new(index, *backend_const) {
                 Ok(c) => c,
                 Err(e) => {
-                    eprintln!("  Error creando VideoCapture con {}: {:?}", backend_name, e);
+                    log_debug!("  Error creando VideoCapture con {}: {:?}", backend_name, e);
                     continue;
                 }
             };
@@ -57,19 +89,19 @@ impl Camera {
             match vc.is_opened() {
                 Ok(true) => { /* Sigue porque todo bien */ }
                 Ok(false) => {
-                    eprintln!("  VideoCapture error no quedó abierto {}", backend_name);
+                    log_debug!("  VideoCapture error no quedó abierto {}", backend_name);
                     let _ = vc.release();
                     continue;
                 }
                 Err(e) => {
-                    eprintln!("  is_opened() error for {}: {:?}", backend_name, e);
+                    log_debug!("  is_opened() error for {}: {:?}", backend_name, e);
                     let _ = vc.release();
                     continue;
                 }
             }
 
             for (w, h, f) in candidates.iter() {
-                eprintln!("    Probando {}x{} @ {} fps", w, h, f);
+                log_debug!("    Probando {}x{} @ {} fps", w, h, f);
                 let _ = vc.set(videoio::CAP_PROP_FRAME_WIDTH, *w);
                 let _ = vc.set(videoio::CAP_PROP_FRAME_HEIGHT, *h);
                 let _ = vc.set(videoio::CAP_PROP_FPS, *f);
@@ -83,38 +115,47 @@ impl Camera {
                 let rw = vc.get(videoio::CAP_PROP_FRAME_WIDTH).unwrap_or(0.0);
                 let rh = vc.get(videoio::CAP_PROP_FRAME_HEIGHT).unwrap_or(0.0);
                 let rf = vc.get(videoio::CAP_PROP_FPS).unwrap_or(0.0);
-                eprintln!("    Reportado por driver: {}x{} @ {} fps", rw, rh, rf);
+                log_debug!("    Reportado por driver: {}x{} @ {} fps", rw, rh, rf);
 
                 // Tries to read a frame
                 let mut frame = opencv::prelude::Mat::default();
                 match vc.read(&mut frame) {
                     Ok(_) => {
                         if frame.size().map(|s| s.width > 0).unwrap_or(false) {
-                            eprintln!(
+                            log_debug!(
                                 "    Read OK with backend {} and {}x{}@{}",
-                                backend_name, rw, rh, rf
+                                backend_name,
+                                rw,
+                                rh,
+                                rf
                             );
-                            return Ok(Camera { video_capture: vc });
+                            return Ok(Camera {
+                                video_capture: vc,
+                                index,
+                            });
                         } else {
-                            eprintln!(
+                            log_debug!(
                                 "    Empty frame on backend {} (reported {}x{})",
-                                backend_name, rw, rh
+                                backend_name,
+                                rw,
+                                rh
                             );
                         }
                     }
                     Err(e) => {
-                        eprintln!("    Error reading frame with {}: {:?}", backend_name, e);
+                        log_debug!("    Error reading frame with {}: {:?}", backend_name, e);
                     }
                 }
             } // end candidatos
 
             let _ = vc.release();
-            eprintln!(
+            log_debug!(
                 "  Backend {} did not work with tested resolutions. Trying next backend...",
                 backend_name
             );
         } // end backends
 
+        forget_formats(index);
         Err(CameraError::CameraCreationError(
             "Could not open camera with any backend/resolution".into(),
         ))
@@ -138,14 +179,15 @@ impl Camera {
             ];
 
             for (backend, name) in backends.iter() {
-                eprintln!(
+                log_debug!(
                     "camera_opencv::new -> intentando backend {} (code {})",
-                    name, backend
+                    name,
+                    backend
                 );
                 let mut vc = match VideoCapture::new(index, *backend) {
                     Ok(c) => c,
                     Err(e) => {
-                        eprintln!("  Error creando VideoCapture con {}: {:?}", name, e);
+                        log_debug!("  Error creando VideoCapture con {}: {:?}", name, e);
                         continue;
                     }
                 };
@@ -154,20 +196,86 @@ impl Camera {
                     let mut frame = Mat::default();
                     if let Ok(_) = vc.read(&mut frame) {
                         if frame.size().map(|s| s.width > 0).unwrap_or(false) {
-                            eprintln!("  Abierto OK con backend {}", name);
-                            return Ok(Camera { video_capture: vc });
+                            log_debug!("  Abierto OK con backend {}", name);
+                            return Ok(Camera {
+                                video_capture: vc,
+                                index,
+                            });
                         }
                     }
                 }
                 let _ = vc.release();
             }
 
+            forget_formats(index);
             Err(CameraError::CameraCreationError(
                 "Camera::new: no se pudo abrir cámara (fallback)".into(),
             ))
         })
     }
 
+    /// Cambia resolución y fps sobre la captura abierta, sin reabrir el dispositivo.
+    pub fn apply_params(&mut self, width: f64, height: f64, fps: f64) {
+        let _ = self.video_capture.set(videoio::CAP_PROP_FRAME_WIDTH, width);
+        let _ = self.video_capture.set(videoio::CAP_PROP_FRAME_HEIGHT, height);
+        let _ = self.video_capture.set(videoio::CAP_PROP_FPS, fps);
+    }
+
+    /// Formatos que acepta el dispositivo. OpenCV no los enumera: se pide
+    /// cada resolución conocida y se anota la que el driver dice que quedó
+    /// (la más cercana que tenga). Al final vuelve al formato en el que
+    /// estaba. Probar cambia el formato varias veces, así que se hace una
+    /// sola vez por dispositivo y después sale de la caché, mientras la
+    /// resolución con la que abrió esté en la lista: si no, en ese índice
+    /// hay otra cámara y se vuelve a probar.
+    pub fn supported_formats(&mut self) -> Vec<VideoFormat> {
+        let probed = probed_formats();
+        let (width, height, _) = self.current_params();
+        let cached = probed
+            .lock()
+            .ok()
+            .and_then(|probed| probed.get(&self.index).cloned());
+        let still_fits = |formats: &[VideoFormat]| {
+            formats
+                .iter()
+                .any(|f| f.width == width as u32 && f.height == height as u32)
+        };
+        if let Some(formats) = cached.filter(|formats| still_fits(formats)) {
+            return formats;
+        }
+        let formats = self.probe_formats();
+        if let Ok(mut probed) = probed.lock() {
+            probed.insert(self.index, formats.clone());
+        }
+        formats
+    }
+
+    fn probe_formats(&mut self) -> Vec<VideoFormat> {
+        let (width, height, fps) = self.current_params();
+        let mut formats = Vec::new();
+        for (w, h) in PROBE_SIZES {
+            self.apply_params(w, h, PROBE_FPS);
+            let (rw, rh, rf) = self.current_params();
+            merge_format(
+                &mut formats,
+                VideoFormat::new(rw as u32, rh as u32, rf.round() as u32),
+            );
+        }
+        self.apply_params(width, height, fps);
+        log_debug!("Formatos de la cámara: {:?}", formats);
+        formats
+    }
+
+    /// Resolución y fps que reporta el driver (0 si no los sabe).
+    fn current_params(&self) -> (f64, f64, f64) {
+        let get = |prop| self.video_capture.get(prop).unwrap_or(0.0);
+        (
+            get(videoio::CAP_PROP_FRAME_WIDTH),
+            get(videoio::CAP_PROP_FRAME_HEIGHT),
+            get(videoio::CAP_PROP_FPS),
+        )
+    }
+
     /// Lee un frame (descarta frames viejos antes de read).
     pub fn capture_frame(&mut self) -> std::result::Result<Mat, CameraError> {
         let mut frame = Mat::default();
@@ -0,0 +1,183 @@
+//! Synthetic frame source for tests, demos and machines without a camera.
+
+use crate::camera::camera_err::CameraError;
+use crate::camera::frame_source::FrameSource;
+use opencv::core::{Point, Rect, Scalar, CV_8UC3};
+use opencv::imgproc;
+use opencv::prelude::*;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// BGR colors of the bars, from left to right.
+const BARS: [(f64, f64, f64); 8] = [
+    (255.0, 255.0, 255.0),
+    (0.0, 255.0, 255.0),
+    (255.0, 255.0, 0.0),
+    (0.0, 255.0, 0.0),
+    (255.0, 0.0, 255.0),
+    (0.0, 0.0, 255.0),
+    (255.0, 0.0, 0.0),
+    (0.0, 0.0, 0.0),
+];
+/// Pixels the pattern scrolls per frame.
+const SCROLL_STEP: i32 = 4;
+/// Height of the frame-number text at 240 lines; it scales with the frame.
+const COUNTER_SCALE: f64 = 0.8;
+
+/// Scrolling color bars with a bouncing box and the frame number, paced to
+/// the requested fps. Frame `n` is always the same image, so encoder issues
+/// reproduce exactly.
+pub struct FakeCamera {
+    width: i32,
+    height: i32,
+    frame_interval: Duration,
+    frame_count: u64,
+    last_frame: Option<Instant>,
+}
+
+impl FakeCamera {
+    pub fn new(width: u32, height: u32, fps: u32) -> Self {
+        let mut camera = FakeCamera {
+            width: 0,
+            height: 0,
+            frame_interval: Duration::ZERO,
+            frame_count: 0,
+            last_frame: None,
+        };
+        camera.apply_params(width as f64, height as f64, fps as f64);
+        camera
+    }
+
+    /// Frames produced so far.
+    pub fn frame_count(&self) -> u64 {
+        self.frame_count
+    }
+
+    fn draw(&self) -> Result<Mat, CameraError> {
+        let mut frame =
+            Mat::new_rows_cols_with_default(self.height, self.width, CV_8UC3, Scalar::all(0.0))?;
+
+        let bar_width = (self.width / BARS.len() as i32).max(1);
+        let offset = ((self.frame_count as i64 * SCROLL_STEP as i64) % self.width as i64) as i32;
+        // One extra bar covers the gap left by the scroll
+        for i in 0..=BARS.len() as i32 {
+            let (b, g, r) = BARS[i as usize % BARS.len()];
+            let x = (i * bar_width + offset) % (self.width + bar_width) - bar_width;
+            let rect = clip(
+                Rect::new(x, 0, bar_width, self.height),
+                self.width,
+                self.height,
+            );
+            if let Some(rect) = rect {
+                imgproc::rectangle(
+                    &mut frame,
+                    rect,
+                    Scalar::new(b, g, r, 0.0),
+                    -1,
+                    imgproc::LINE_8,
+                    0,
+                )?;
+            }
+        }
+
+        // The box moves vertically so the motion is not only horizontal
+        let side = (self.height / 4).max(2);
+        let travel = (self.height - side).max(1) as i64;
+        let phase = (self.frame_count as i64 * SCROLL_STEP as i64) % (2 * travel);
+        let y = if phase < travel {
+            phase
+        } else {
+            2 * travel - phase
+        } as i32;
+        if let Some(rect) = clip(
+            Rect::new((self.width - side) / 2, y, side, side),
+            self.width,
+            self.height,
+        ) {
+            imgproc::rectangle(
+                &mut frame,
+                rect,
+                Scalar::new(128.0, 128.0, 128.0, 0.0),
+                -1,
+                imgproc::LINE_8,
+                0,
+            )?;
+        }
+
+        // Numbered frames show losses and reordering at a glance
+        let scale = COUNTER_SCALE * self.height as f64 / 240.0;
+        imgproc::put_text(
+            &mut frame,
+            &format!("#{}", self.frame_count),
+            Point::new(self.width / 40 + 2, self.height / 10 + 8),
+            imgproc::FONT_HERSHEY_SIMPLEX,
+            scale,
+            Scalar::new(0.0, 0.0, 0.0, 0.0),
+            ((scale * 2.0) as i32).max(1),
+            imgproc::LINE_8,
+            false,
+        )?;
+        Ok(frame)
+    }
+}
+
+/// Intersects `rect` with the frame, `None` if nothing is left.
+fn clip(rect: Rect, width: i32, height: i32) -> Option<Rect> {
+    let x0 = rect.x.max(0);
+    let y0 = rect.y.max(0);
+    let x1 = (rect.x + rect.width).min(width);
+    let y1 = (rect.y + rect.height).min(height);
+    if x1 <= x0 || y1 <= y0 {
+        return None;
+    }
+    Some(Rect::new(x0, y0, x1 - x0, y1 - y0))
+}
+
+impl FrameSource for FakeCamera {
+    /// H264 needs even dimensions, so odd sizes are rounded down.
+    fn apply_params(&mut self, width: f64, height: f64, fps: f64) {
+        self.width = ((width as i32) & !1).max(2);
+        self.height = ((height as i32) & !1).max(2);
+        self.frame_interval = Duration::from_secs_f64(1.0 / fps.max(1.0));
+    }
+
+    fn capture_frame(&mut self) -> Result<Mat, CameraError> {
+        if let Some(last) = self.last_frame {
+            let elapsed = last.elapsed();
+            if elapsed < self.frame_interval {
+                thread::sleep(self.frame_interval - elapsed);
+            }
+        }
+        let frame = self.draw()?;
+        self.last_frame = Some(Instant::now());
+        self.frame_count += 1;
+        Ok(frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frames_match_params_and_move() {
+        let mut camera = FakeCamera::new(321, 240, 1000);
+        let first = camera.capture_frame().unwrap();
+        let second = camera.capture_frame().unwrap();
+
+        let size = first.size().unwrap();
+        assert_eq!((size.width, size.height), (320, 240));
+        assert_eq!(first.typ(), CV_8UC3);
+        assert_ne!(first.data_bytes().unwrap(), second.data_bytes().unwrap());
+        assert_eq!(camera.frame_count(), 2);
+    }
+
+    #[test]
+    fn test_apply_params_resizes_next_frame() {
+        let mut camera = FakeCamera::new(320, 240, 1000);
+        camera.apply_params(161.0, 120.0, 1000.0);
+        let frame = camera.capture_frame().unwrap();
+        let size = frame.size().unwrap();
+        assert_eq!((size.width, size.height), (160, 120));
+    }
+}
@@ -0,0 +1,33 @@
+use crate::camera::camera_err::CameraError;
+use crate::camera::camera_opencv::Camera;
+use crate::camera::video_format::VideoFormat;
+use opencv::prelude::Mat;
+
+/// Anything the camera thread can pull BGR frames from.
+pub trait FrameSource: Send {
+    /// Changes resolution and fps without restarting the source.
+    fn apply_params(&mut self, width: f64, height: f64, fps: f64);
+
+    /// Next BGR frame. `CameraError::FrameEmpty` is skipped by the caller.
+    fn capture_frame(&mut self) -> Result<Mat, CameraError>;
+
+    /// Formats the source can deliver. Empty when it takes any format, as
+    /// synthetic sources do.
+    fn supported_formats(&mut self) -> Vec<VideoFormat> {
+        Vec::new()
+    }
+}
+
+impl FrameSource for Camera {
+    fn apply_params(&mut self, width: f64, height: f64, fps: f64) {
+        Camera::apply_params(self, width, height, fps);
+    }
+
+    fn capture_frame(&mut self) -> Result<Mat, CameraError> {
+        Camera::capture_frame(self)
+    }
+
+    fn supported_formats(&mut self) -> Vec<VideoFormat> {
+        Camera::supported_formats(self)
+    }
+}
@@ -1,3 +1,7 @@
 pub mod camera_const;
 pub mod camera_err;
 pub mod camera_opencv;
+pub mod fake_camera;
+pub mod frame_source;
+pub mod video_file;
+pub mod video_format;
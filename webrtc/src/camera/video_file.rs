@@ -0,0 +1,93 @@
+//! Frame source that plays a video file in a loop, for headless runs and
+//! reproducible tests.
+
+use crate::camera::camera_err::CameraError;
+use crate::camera::frame_source::FrameSource;
+use opencv::core::Size;
+use opencv::imgproc;
+use opencv::prelude::*;
+use opencv::videoio::{self, VideoCapture};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Frames of the file scaled to the requested size, paced to the requested
+/// fps. At the end of the file it starts over.
+pub struct VideoFile {
+    capture: VideoCapture,
+    width: i32,
+    height: i32,
+    frame_interval: Duration,
+    last_frame: Option<Instant>,
+}
+
+impl VideoFile {
+    pub fn open(path: &str, width: u32, height: u32, fps: u32) -> Result<Self, CameraError> {
+        let capture = VideoCapture::from_file(path, videoio::CAP_ANY)?;
+        if !capture.is_opened()? {
+            return Err(CameraError::CameraOpenError(path.to_string()));
+        }
+        let mut file = VideoFile {
+            capture,
+            width: 0,
+            height: 0,
+            frame_interval: Duration::ZERO,
+            last_frame: None,
+        };
+        file.apply_params(width as f64, height as f64, fps as f64);
+        Ok(file)
+    }
+
+    fn read(&mut self) -> Result<Mat, CameraError> {
+        let mut frame = Mat::default();
+        self.capture.read(&mut frame)?;
+        if frame.empty() {
+            // Fin del archivo: vuelve al principio
+            self.capture.set(videoio::CAP_PROP_POS_FRAMES, 0.0)?;
+            self.capture.read(&mut frame)?;
+        }
+        if frame.empty() {
+            return Err(CameraError::ReadFrameError(
+                "video file has no frames".into(),
+            ));
+        }
+        Ok(frame)
+    }
+}
+
+impl FrameSource for VideoFile {
+    /// H264 needs even dimensions, so odd sizes are rounded down.
+    fn apply_params(&mut self, width: f64, height: f64, fps: f64) {
+        self.width = ((width as i32) & !1).max(2);
+        self.height = ((height as i32) & !1).max(2);
+        self.frame_interval = Duration::from_secs_f64(1.0 / fps.max(1.0));
+    }
+
+    fn capture_frame(&mut self) -> Result<Mat, CameraError> {
+        if let Some(last) = self.last_frame {
+            let elapsed = last.elapsed();
+            if elapsed < self.frame_interval {
+                thread::sleep(self.frame_interval - elapsed);
+            }
+        }
+        let frame = self.read()?;
+        self.last_frame = Some(Instant::now());
+        let size = Size::new(self.width, self.height);
+        if frame.size()? == size {
+            return Ok(frame);
+        }
+        let mut scaled = Mat::default();
+        imgproc::resize(&frame, &mut scaled, size, 0.0, 0.0, imgproc::INTER_LINEAR)?;
+        Ok(scaled)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_missing_file_is_an_error() {
+        let result = VideoFile::open("/nonexistent/roomrtc-test.mp4", 320, 240, 30);
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,127 @@
+//! Capture formats a frame source can deliver, and how to pick the closest
+//! one to what was asked for.
+
+/// A resolution and the highest frame rate the source reported for it.
+/// An `fps` of 0 means the driver did not say.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VideoFormat {
+    pub width: u32,
+    pub height: u32,
+    pub fps: u32,
+}
+
+impl VideoFormat {
+    pub fn new(width: u32, height: u32, fps: u32) -> Self {
+        Self { width, height, fps }
+    }
+
+    fn pixels(&self) -> u64 {
+        u64::from(self.width) * u64::from(self.height)
+    }
+
+    /// The format in `formats` closest to `wanted`: the resolution with the
+    /// nearest pixel count (then the nearest width, so the aspect ratio
+    /// tends to stay), at the wanted fps or the highest that resolution
+    /// reaches. An empty list means the source takes anything, so `wanted`
+    /// comes back as is.
+    pub fn nearest(formats: &[VideoFormat], wanted: VideoFormat) -> VideoFormat {
+        let Some(size) = formats.iter().min_by_key(|format| {
+            (
+                format.pixels().abs_diff(wanted.pixels()),
+                format.width.abs_diff(wanted.width),
+            )
+        }) else {
+            return wanted;
+        };
+        let max_fps = formats
+            .iter()
+            .filter(|format| format.width == size.width && format.height == size.height)
+            .map(|format| format.fps)
+            .max()
+            .unwrap_or(0);
+        let fps = if max_fps == 0 {
+            wanted.fps
+        } else {
+            wanted.fps.min(max_fps)
+        };
+        VideoFormat::new(size.width, size.height, fps)
+    }
+}
+
+/// Adds `format` to `formats`, keeping one entry per resolution with the
+/// highest fps, sorted from the smallest resolution up.
+pub fn merge_format(formats: &mut Vec<VideoFormat>, format: VideoFormat) {
+    if format.width == 0 || format.height == 0 {
+        return;
+    }
+    match formats
+        .iter_mut()
+        .find(|known| known.width == format.width && known.height == format.height)
+    {
+        Some(known) => known.fps = known.fps.max(format.fps),
+        None => {
+            formats.push(format);
+            formats.sort_by_key(|known| (known.pixels(), known.width));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn webcam() -> Vec<VideoFormat> {
+        vec![
+            VideoFormat::new(320, 240, 30),
+            VideoFormat::new(640, 480, 30),
+            VideoFormat::new(1280, 720, 10),
+        ]
+    }
+
+    #[test]
+    fn test_nearest_keeps_a_supported_format() {
+        let wanted = VideoFormat::new(640, 480, 30);
+        assert_eq!(VideoFormat::nearest(&webcam(), wanted), wanted);
+    }
+
+    #[test]
+    fn test_nearest_picks_the_closest_resolution() {
+        let nearest = VideoFormat::nearest(&webcam(), VideoFormat::new(800, 600, 30));
+        assert_eq!(nearest, VideoFormat::new(640, 480, 30));
+        let nearest = VideoFormat::nearest(&webcam(), VideoFormat::new(1920, 1080, 30));
+        assert_eq!((nearest.width, nearest.height), (1280, 720));
+    }
+
+    #[test]
+    fn test_nearest_caps_the_fps_at_what_the_resolution_reaches() {
+        let nearest = VideoFormat::nearest(&webcam(), VideoFormat::new(1280, 720, 30));
+        assert_eq!(nearest.fps, 10);
+        // Pedir menos que el máximo se respeta
+        let nearest = VideoFormat::nearest(&webcam(), VideoFormat::new(320, 240, 15));
+        assert_eq!(nearest.fps, 15);
+    }
+
+    #[test]
+    fn test_nearest_with_unknown_fps_or_formats() {
+        let formats = [VideoFormat::new(640, 480, 0)];
+        let wanted = VideoFormat::new(640, 480, 24);
+        assert_eq!(VideoFormat::nearest(&formats, wanted), wanted);
+        assert_eq!(VideoFormat::nearest(&[], wanted), wanted);
+    }
+
+    #[test]
+    fn test_merge_format_keeps_one_entry_per_resolution() {
+        let mut formats = Vec::new();
+        merge_format(&mut formats, VideoFormat::new(1280, 720, 10));
+        merge_format(&mut formats, VideoFormat::new(640, 480, 15));
+        merge_format(&mut formats, VideoFormat::new(640, 480, 30));
+        merge_format(&mut formats, VideoFormat::new(0, 0, 30));
+        assert_eq!(
+            formats,
+            vec![
+                VideoFormat::new(640, 480, 30),
+                VideoFormat::new(1280, 720, 10)
+            ]
+        );
+    }
+}
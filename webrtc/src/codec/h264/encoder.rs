@@ -12,11 +12,21 @@ pub struct H264Encoder {
 }
 impl H264Encoder {
     pub fn new() -> Result<H264Encoder, EncoderError> {
+        Self::with_bitrate(2_000_000)
+    }
+
+    /// Creates the encoder targeting the given bitrate (bits per second).
+    pub fn with_bitrate(bitrate_bps: u32) -> Result<H264Encoder, EncoderError> {
+        Self::with_config(bitrate_bps, 30.0)
+    }
+
+    /// Creates the encoder targeting the given bitrate (bits per second) and frame rate.
+    pub fn with_config(bitrate_bps: u32, fps: f32) -> Result<H264Encoder, EncoderError> {
         let api = OpenH264API::from_source();
 
         let config = EncoderConfig::new()
-            .bitrate(BitRate::from_bps(2_000_000))
-            .max_frame_rate(FrameRate::from_hz(30.0))
+            .bitrate(BitRate::from_bps(bitrate_bps))
+            .max_frame_rate(FrameRate::from_hz(fps))
             .usage_type(UsageType::CameraVideoRealTime)
             .rate_control_mode(RateControlMode::Bitrate)
             .profile(Profile::Baseline)
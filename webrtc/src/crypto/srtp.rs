@@ -1,4 +1,7 @@
-/// SRTP-ligero: XOR pseudo-aleatorio derivado de seq/timestamp + clave compartida.
+//! SRTP-ligero: XOR pseudo-aleatorio derivado de seq/timestamp + clave compartida.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
 
 #[derive(Clone)]
 pub struct SrtpContext {
@@ -52,9 +55,82 @@ impl SrtpContext {
     }
 }
 
+/// SRTP context shared by the peer connection and every worker, so a rekey
+/// reaches all of them at once.
+#[derive(Clone, Default)]
+pub struct SharedSrtpContext {
+    context: Arc<RwLock<Option<SrtpContext>>>,
+    generation: Arc<AtomicU64>,
+}
+
+impl SharedSrtpContext {
+    pub fn new(context: Option<SrtpContext>) -> Self {
+        Self {
+            context: Arc::new(RwLock::new(context)),
+            generation: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Swaps the context for every holder of this handle.
+    pub fn replace(&self, context: Option<SrtpContext>) {
+        let mut current = self.context.write().unwrap_or_else(|err| err.into_inner());
+        *current = context;
+        self.generation.fetch_add(1, Ordering::Release);
+    }
+
+    /// Copy of the current context.
+    pub fn get(&self) -> Option<SrtpContext> {
+        self.context
+            .read()
+            .unwrap_or_else(|err| err.into_inner())
+            .clone()
+    }
+
+    pub fn is_set(&self) -> bool {
+        self.context
+            .read()
+            .unwrap_or_else(|err| err.into_inner())
+            .is_some()
+    }
+
+    /// Bumped on every [`Self::replace`].
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Acquire)
+    }
+
+    /// Per-thread view that only takes the lock after a rekey.
+    pub fn cache(&self) -> SrtpCache {
+        SrtpCache {
+            shared: self.clone(),
+            generation: self.generation(),
+            context: self.get(),
+        }
+    }
+}
+
+/// Local copy of a [`SharedSrtpContext`] for the packet hot path. It is
+/// refreshed when the shared generation changes.
+pub struct SrtpCache {
+    shared: SharedSrtpContext,
+    generation: u64,
+    context: Option<SrtpContext>,
+}
+
+impl SrtpCache {
+    /// Context to use for the next packet.
+    pub fn current(&mut self) -> Option<&SrtpContext> {
+        let generation = self.shared.generation();
+        if generation != self.generation {
+            self.context = self.shared.get();
+            self.generation = generation;
+        }
+        self.context.as_ref()
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::SrtpContext;
+    use super::{SharedSrtpContext, SrtpContext};
 
     #[test]
     fn roundtrip_encrypt_decrypt() {
@@ -70,4 +146,112 @@ mod tests {
         let plain = ctx.unprotect(seq, ts, &cipher).expect("plain");
         assert_eq!(plain, payload);
     }
+
+    fn dtls_keying_material() -> Vec<u8> {
+        (0u8..32).map(|i| i.wrapping_mul(37).wrapping_add(11)).collect()
+    }
+
+    #[test]
+    fn rejects_keys_shorter_than_16_bytes() {
+        assert!(SrtpContext::new(&[0u8; 15]).is_none());
+        assert!(SrtpContext::new(&[0u8; 16]).is_some());
+    }
+
+    #[test]
+    fn roundtrip_with_32_byte_key() {
+        let ctx = SrtpContext::new(&dtls_keying_material()).expect("ctx");
+        let payload = b"\x78\x01\x02 opus frame";
+        let (seq, ts) = (65_535u16, u32::MAX);
+
+        let cipher = ctx.protect(seq, ts, payload).expect("cipher");
+        assert_eq!(cipher.len(), payload.len());
+        assert_ne!(cipher.as_slice(), payload.as_slice());
+        assert_eq!(ctx.get_key(), dtls_keying_material().as_slice());
+
+        let plain = ctx.unprotect(seq, ts, &cipher).expect("plain");
+        assert_eq!(plain, payload);
+    }
+
+    #[test]
+    fn wrong_sequence_number_does_not_recover_payload() {
+        let ctx = SrtpContext::new(&dtls_keying_material()).expect("ctx");
+        let payload = b"hola webrtc";
+        let cipher = ctx.protect(100, 4_800, payload).expect("cipher");
+
+        // Sin tag de autenticación: el fallo es que no se recupera el original
+        let wrong_seq = ctx.unprotect(101, 4_800, &cipher).expect("output");
+        assert_ne!(wrong_seq, payload);
+        let wrong_ts = ctx.unprotect(100, 4_801, &cipher).expect("output");
+        assert_ne!(wrong_ts, payload);
+    }
+
+    #[test]
+    fn wrong_key_does_not_recover_payload() {
+        let ctx = SrtpContext::new(&dtls_keying_material()).expect("ctx");
+        let other = SrtpContext::new(&[9u8; 32]).expect("ctx");
+        let payload = b"hola webrtc";
+        let cipher = ctx.protect(1, 960, payload).expect("cipher");
+
+        assert_ne!(other.unprotect(1, 960, &cipher).expect("output"), payload);
+    }
+
+    #[test]
+    fn offerer_and_answerer_contexts_decrypt_each_other() {
+        // Ambos extremos exportan el mismo material del handshake DTLS
+        let offerer = SrtpContext::new(&dtls_keying_material()).expect("offerer");
+        let answerer = SrtpContext::new(&dtls_keying_material()).expect("answerer");
+
+        for seq in [0u16, 1, 500, u16::MAX] {
+            let ts = u32::from(seq) * 960;
+            let from_offerer = offerer.protect(seq, ts, b"offer media").expect("cipher");
+            assert_eq!(
+                answerer.unprotect(seq, ts, &from_offerer).expect("plain"),
+                b"offer media"
+            );
+            let from_answerer = answerer.protect(seq, ts, b"answer media").expect("cipher");
+            assert_eq!(
+                offerer.unprotect(seq, ts, &from_answerer).expect("plain"),
+                b"answer media"
+            );
+        }
+    }
+
+    #[test]
+    fn rekey_reaches_every_cache() {
+        let payload = b"hola webrtc";
+        let (seq, ts) = (7u16, 90_000u32);
+        let shared = SharedSrtpContext::new(SrtpContext::new(&[1u8; 32]));
+        let mut sender = shared.cache();
+        let mut receiver = shared.cache();
+
+        let old_cipher = sender.current().unwrap().protect(seq, ts, payload).unwrap();
+        assert_eq!(
+            receiver.current().unwrap().unprotect(seq, ts, &old_cipher).unwrap(),
+            payload
+        );
+
+        shared.replace(SrtpContext::new(&[2u8; 32]));
+        assert_eq!(shared.generation(), 1);
+
+        // Lo cifrado con la clave A ya no se descifra con la B
+        let stale = receiver.current().unwrap().unprotect(seq, ts, &old_cipher).unwrap();
+        assert_ne!(stale, payload);
+
+        let new_cipher = sender.current().unwrap().protect(seq + 1, ts, payload).unwrap();
+        assert_eq!(
+            receiver.current().unwrap().unprotect(seq + 1, ts, &new_cipher).unwrap(),
+            payload
+        );
+    }
+
+    #[test]
+    fn cleared_context_disables_protection() {
+        let shared = SharedSrtpContext::new(SrtpContext::new(&[1u8; 16]));
+        let mut cache = shared.cache();
+        assert!(cache.current().is_some());
+
+        shared.replace(None);
+        assert!(!shared.is_set());
+        assert!(cache.current().is_none());
+    }
 }
@@ -3,9 +3,9 @@
 use std::net::{SocketAddr, UdpSocket};
 
 use super::candidate::{CandidateType, IceCandidate};
-use super::connectivity::run_connectivity_checks;
+use super::connectivity::{prune_pairs, run_connectivity_checks, IceCheckConfig};
 use super::gathering::{calculate_priority, create_host_candidate, create_srflx_candidate, determine_local_ipv4};
-use super::pair::{CandidatePair, CandidatePairState};
+use super::pair::{CandidatePair, CandidatePairState, IcePairStats};
 use crate::stun::StunClient;
 
 /// ICE agent that handles candidate gathering and connectivity checks.
@@ -20,6 +20,8 @@ pub struct IceAgent {
     pub(crate) selected_pair: Option<CandidatePair>,
 
     stun_client: StunClient,
+    check_config: IceCheckConfig,
+    pair_stats: IcePairStats,
 }
 
 impl Default for IceAgent {
@@ -40,6 +42,8 @@ impl IceAgent {
             candidate_pairs: Vec::new(),
             selected_pair: None,
             stun_client: StunClient::new(),
+            check_config: IceCheckConfig::default(),
+            pair_stats: IcePairStats::default(),
         }
     }
 
@@ -55,9 +59,10 @@ impl IceAgent {
             local_addr.port() as u32,
         );
 
-        println!(
+        log_debug!(
             " OK Host: {}: {}",
-            host_candidate.address, host_candidate.port
+            host_candidate.address,
+            host_candidate.port
         );
         self.local_candidate.push(host_candidate);
 
@@ -69,17 +74,18 @@ impl IceAgent {
                     public_addr.port() as u32,
                 );
 
-                println!(
+                log_debug!(
                     " OK Srflx: {}:{}",
-                    srflx_candidate.address, srflx_candidate.port
+                    srflx_candidate.address,
+                    srflx_candidate.port
                 );
                 self.local_candidate.push(srflx_candidate);
             }
-            Ok(None) => println!("STUN dont return a direction"),
-            Err(e) => println!("ERROR STUN: {}", e),
+            Ok(None) => log_info!("STUN dont return a direction"),
+            Err(e) => log_warn!("ERROR STUN: {}", e),
         }
 
-        println!(
+        log_info!(
             "Gathering complete: {} candidates",
             self.local_candidate.len()
         );
@@ -87,32 +93,56 @@ impl IceAgent {
     }
 
     /// Add a remote candidate and generate all possible pairs with the local ones.
+    /// A candidate already known (same address and port) is ignored, so a peer
+    /// that repeats its candidates in every media section doesn't double the pairs.
     pub fn add_remote_candidate(&mut self, candidate: IceCandidate) {
-        println!(
+        if self
+            .remote_candidate
+            .iter()
+            .any(|known| known.address == candidate.address && known.port == candidate.port)
+        {
+            return;
+        }
+        log_debug!(
             "Adding remote candidate: {}:{}",
-            candidate.address, candidate.port
+            candidate.address,
+            candidate.port
         );
 
         self.remote_candidate.push(candidate.clone());
 
         for local in &self.local_candidate {
-            let pair = CandidatePair {
-                local_candidate: local.clone(),
-                remote_candidate: candidate.clone(),
-                state: CandidatePairState::Waiting,
-            };
-            self.candidate_pairs.push(pair);
+            self.candidate_pairs
+                .push(CandidatePair::new(local.clone(), candidate.clone()));
         }
 
-        println!("   {} candidate pairs created", self.local_candidate.len());
+        log_debug!("   {} candidate pairs created", self.local_candidate.len());
     }
 
-    /// Run connectivity checks on known peers.
+    /// Run connectivity checks on known peers, after pruning the pairs.
     pub fn start_connectivity_checks(
         &mut self,
         socket: &UdpSocket,
     ) -> Result<(), Box<dyn std::error::Error>> {
-        match run_connectivity_checks(socket, &mut self.candidate_pairs, self.ice_rol)? {
+        let formed = self.candidate_pairs.len();
+        let pruned = prune_pairs(&mut self.candidate_pairs, self.check_config.max_pairs);
+        let result = run_connectivity_checks(
+            socket,
+            &mut self.candidate_pairs,
+            self.ice_rol,
+            &self.check_config,
+        );
+        let checked = self
+            .candidate_pairs
+            .iter()
+            .filter(|pair| pair.state != CandidatePairState::Waiting)
+            .count();
+        self.pair_stats = IcePairStats {
+            formed,
+            pruned,
+            checked,
+        };
+        match result? {
             Some(pair) => {
                 self.selected_pair = Some(pair);
                 Ok(())
@@ -154,6 +184,26 @@ impl IceAgent {
         self.selected_pair.as_ref()
     }
 
+    /// Candidates gathered locally (host and reflexive).
+    pub fn local_candidates(&self) -> &[IceCandidate] {
+        &self.local_candidate
+    }
+
+    /// Candidates learned from the remote description.
+    pub fn remote_candidates(&self) -> &[IceCandidate] {
+        &self.remote_candidate
+    }
+
+    /// Every local/remote combination with its check state.
+    pub fn candidate_pairs(&self) -> &[CandidatePair] {
+        &self.candidate_pairs
+    }
+
+    /// Pairs formed, pruned and checked in the last connectivity checks.
+    pub fn pair_stats(&self) -> IcePairStats {
+        self.pair_stats
+    }
+
     /// Indicates whether the agent already has a verified pair.
     pub fn has_connection(&self) -> bool {
         self.selected_pair.is_some()
@@ -165,6 +215,20 @@ impl IceAgent {
         self
     }
 
+    /// Timeout, retransmission interval and parallelism of the checks.
+    pub fn set_check_config(&mut self, config: IceCheckConfig) {
+        self.check_config = config;
+    }
+
+    pub fn check_config(&self) -> IceCheckConfig {
+        self.check_config
+    }
+
+    /// Point reflexive gathering at another STUN server (e.g. a local `StunServer`).
+    pub fn set_stun_server(&mut self, server: String) {
+        self.stun_client = StunClient::with_server(server);
+    }
+
     /// Ensure that the local address is registered as a host candidate.
     pub fn register_host_candidate(&mut self, addr: SocketAddr) {
         let ip = determine_local_ipv4(&self.stun_client, addr.ip());
@@ -184,13 +248,14 @@ impl IceAgent {
     }
 
     /// Reuse an existing socket to attempt to obtain reflexive candidates.
+    /// Without a NAT in between the reflexive address is the host one, and
+    /// announcing it twice would only repeat the same checks.
     pub fn gather_reflexive_candidates(&mut self, socket: &UdpSocket) {
         match self.stun_client.query(socket) {
             Ok(Some(public_addr)) => {
                 let already_present = self.local_candidate.iter().any(|candidate| {
                     candidate.address == public_addr.ip().to_string()
                         && candidate.port == public_addr.port() as u32
-                        && candidate.candidate_type == CandidateType::Srflx
                 });
 
                 if !already_present {
@@ -200,22 +265,81 @@ impl IceAgent {
                         public_addr.port() as u32,
                     );
 
-                    println!(
+                    log_debug!(
                         " OK Srflx (re-use socket): {}:{}",
-                        srflx_candidate.address, srflx_candidate.port
+                        srflx_candidate.address,
+                        srflx_candidate.port
                     );
                     self.local_candidate.push(srflx_candidate);
                 }
             }
             Ok(None) => {
-                println!("STUN did not return a public address");
+                log_info!("STUN did not return a public address");
             }
             Err(err) => {
-                println!("Error querying STUN: {}", err);
+                log_warn!("Error querying STUN: {}", err);
             }
         }
     }
 
+    /// Selects the pair whose remote is `addr` once the media moved there
+    /// mid-call (ICE restart, NAT rebinding). An address the peer never
+    /// announced is learned as a peer-reflexive candidate.
+    pub fn select_remote(&mut self, addr: SocketAddr) {
+        // Se llama por cada paquete de media: comparar sin armar strings
+        let is_addr = |candidate: &IceCandidate| {
+            candidate.port == addr.port() as u32 && candidate.address.parse() == Ok(addr.ip())
+        };
+        if self
+            .selected_pair
+            .as_ref()
+            .is_some_and(|pair| is_addr(&pair.remote_candidate))
+        {
+            return;
+        }
+
+        let index = match self
+            .candidate_pairs
+            .iter()
+            .position(|pair| is_addr(&pair.remote_candidate))
+        {
+            Some(index) => index,
+            None => {
+                let local = match &self.selected_pair {
+                    Some(pair) => pair.local_candidate.clone(),
+                    None => match self.local_candidate.first() {
+                        Some(candidate) => candidate.clone(),
+                        None => return,
+                    },
+                };
+                let remote = IceCandidate {
+                    name: format!("prflx-{}", self.remote_candidate.len()),
+                    address: addr.ip().to_string(),
+                    port: addr.port() as u32,
+                    candidate_type: CandidateType::Prflx,
+                    priority: calculate_priority(&CandidateType::Prflx, 65535),
+                };
+                self.remote_candidate.push(remote.clone());
+                self.candidate_pairs.push(CandidatePair::new(local, remote));
+                self.candidate_pairs.len() - 1
+            }
+        };
+        self.candidate_pairs[index].state = CandidatePairState::Succeeded;
+        self.selected_pair = Some(self.candidate_pairs[index].clone());
+    }
+
+    /// ICE restart (RFC 8445 9): new credentials and no remote state, so the
+    /// next offer/answer starts the checks over. Local candidates are kept:
+    /// the socket is the same, only the path to the peer changed.
+    pub fn restart(&mut self) {
+        self.user_fragment = Self::generate_random_string(8);
+        self.password = Self::generate_random_string(24);
+        self.remote_candidate.clear();
+        self.candidate_pairs.clear();
+        self.selected_pair = None;
+        self.pair_stats = IcePairStats::default();
+    }
+
     /// Access the `ufrag` generated for the ICE session.
     pub fn user_fragment(&self) -> &str {
         &self.user_fragment
@@ -230,6 +354,8 @@ impl IceAgent {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::ice::CandidatePairInfo;
+    use std::time::Duration;
 
     #[test]
     fn test_ice_agent_creation() {
@@ -314,6 +440,143 @@ mod tests {
         assert!(agent.candidate_pairs.len() > 0);
     }
 
+    #[test]
+    fn test_repeated_remote_candidate_is_ignored() {
+        let mut agent = IceAgent::new();
+        agent
+            .local_candidate
+            .push(create_host_candidate(0, "127.0.0.1".to_string(), 5000));
+        let remote = IceCandidate {
+            name: "remote-0".to_string(),
+            address: "192.168.2.100".to_string(),
+            port: 60000,
+            candidate_type: CandidateType::Host,
+            priority: 2130706431,
+        };
+
+        agent.add_remote_candidate(remote.clone());
+        agent.add_remote_candidate(remote);
+
+        assert_eq!(agent.remote_candidate.len(), 1);
+        assert_eq!(agent.candidate_pairs.len(), 1);
+    }
+
+    #[test]
+    fn test_reflexive_equal_to_host_is_not_announced() {
+        let stun = crate::stun::StunServer::bind("127.0.0.1:0").unwrap();
+        let stun_addr = stun.local_addr().unwrap();
+        stun.spawn();
+
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let mut agent = IceAgent::new();
+        agent.set_stun_server(stun_addr.to_string());
+        agent.local_candidate.push(create_host_candidate(
+            0,
+            "127.0.0.1".to_string(),
+            socket.local_addr().unwrap().port() as u32,
+        ));
+        agent.gather_reflexive_candidates(&socket);
+
+        assert_eq!(agent.local_candidate.len(), 1);
+        assert_eq!(agent.local_candidate[0].candidate_type, CandidateType::Host);
+    }
+
+    #[test]
+    fn test_restart_renews_credentials_and_forgets_the_remote() {
+        let mut agent = IceAgent::new();
+        agent
+            .local_candidate
+            .push(create_host_candidate(0, "127.0.0.1".to_string(), 5000));
+        agent.add_remote_candidate(IceCandidate {
+            name: "remote-0".to_string(),
+            address: "192.168.2.100".to_string(),
+            port: 60000,
+            candidate_type: CandidateType::Host,
+            priority: 2130706431,
+        });
+        let (ufrag, pwd) = (agent.user_fragment.clone(), agent.password.clone());
+
+        agent.restart();
+
+        assert_ne!(agent.user_fragment, ufrag);
+        assert_ne!(agent.password, pwd);
+        assert!(agent.remote_candidate.is_empty());
+        assert!(agent.candidate_pairs.is_empty());
+        assert!(!agent.has_connection());
+        assert_eq!(agent.local_candidate.len(), 1);
+    }
+
+    fn remote_candidate(address: &str, port: u32, candidate_type: CandidateType) -> IceCandidate {
+        IceCandidate {
+            name: "remote-0".to_string(),
+            address: address.to_string(),
+            port,
+            priority: calculate_priority(&candidate_type, 65535),
+            candidate_type,
+        }
+    }
+
+    #[test]
+    fn test_pair_info_reflects_the_check_results() {
+        let mut agent = IceAgent::new();
+        agent
+            .local_candidate
+            .push(create_host_candidate(0, "192.168.1.4".to_string(), 54321));
+        agent.add_remote_candidate(remote_candidate("203.0.113.7", 61000, CandidateType::Srflx));
+        agent.add_remote_candidate(remote_candidate("10.0.0.9", 4000, CandidateType::Host));
+        agent.candidate_pairs[0].state = CandidatePairState::Succeeded;
+        agent.candidate_pairs[0].rtt = Some(Duration::from_millis(12));
+        agent.candidate_pairs[1].state = CandidatePairState::Failed;
+        agent.selected_pair = Some(agent.candidate_pairs[0].clone());
+
+        let info = CandidatePairInfo::from(agent.get_selected_pair().unwrap());
+        assert_eq!(info.local_address, "192.168.1.4:54321");
+        assert_eq!(info.remote_address, "203.0.113.7:61000");
+        assert_eq!(info.remote_type, CandidateType::Srflx);
+        assert_eq!(info.state, CandidatePairState::Succeeded);
+        assert_eq!(info.rtt, Some(Duration::from_millis(12)));
+        assert_eq!(info.priority, agent.candidate_pairs[0].priority());
+        assert_eq!(info.to_string(), "host↔srflx via 203.0.113.7:61000");
+
+        let states: Vec<_> = agent
+            .candidate_pairs()
+            .iter()
+            .map(|pair| CandidatePairInfo::from(pair).state)
+            .collect();
+        assert_eq!(
+            states,
+            [CandidatePairState::Succeeded, CandidatePairState::Failed]
+        );
+    }
+
+    #[test]
+    fn test_select_remote_follows_the_path_change() {
+        let mut agent = IceAgent::new();
+        agent
+            .local_candidate
+            .push(create_host_candidate(0, "192.168.1.4".to_string(), 54321));
+        agent.add_remote_candidate(remote_candidate("10.0.0.9", 4000, CandidateType::Host));
+        agent.add_remote_candidate(remote_candidate("203.0.113.7", 61000, CandidateType::Srflx));
+
+        // Un candidato anunciado: se elige su par
+        agent.select_remote("203.0.113.7:61000".parse().unwrap());
+        let selected = agent.get_selected_pair().unwrap();
+        assert_eq!(
+            selected.remote_candidate.candidate_type,
+            CandidateType::Srflx
+        );
+        assert_eq!(selected.state, CandidatePairState::Succeeded);
+
+        // Uno que nadie anunció se aprende como peer-reflexive
+        agent.select_remote("198.51.100.2:7000".parse().unwrap());
+        let info = CandidatePairInfo::from(agent.get_selected_pair().unwrap());
+        assert_eq!(info.to_string(), "host↔prflx via 198.51.100.2:7000");
+        assert_eq!(agent.candidate_pairs().len(), 3);
+
+        agent.select_remote("198.51.100.2:7000".parse().unwrap());
+        assert_eq!(agent.candidate_pairs().len(), 3);
+    }
+
     #[test]
     fn test_has_connection() {
         let agent = IceAgent::new();
@@ -1,5 +1,9 @@
 //! Representations of local or remote ICE candidates.
 
+use std::fmt;
+
+use serde::Serialize;
+
 /// ICE candidate with its basic properties and priority.
 #[derive(Debug, Clone)]
 pub struct IceCandidate {
@@ -11,11 +15,26 @@ pub struct IceCandidate {
 }
 
 /// Types of candidates available during ICE negotiations.
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum CandidateType {
     Host,
     Srflx,
     Relay,
+    /// Peer-reflexive: an address the peer never announced, learned from
+    /// the traffic itself (RFC 8445 7.3.1.3).
+    Prflx,
+}
+
+impl fmt::Display for CandidateType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            CandidateType::Host => "host",
+            CandidateType::Srflx => "srflx",
+            CandidateType::Relay => "relay",
+            CandidateType::Prflx => "prflx",
+        })
+    }
 }
 
 #[cfg(test)]
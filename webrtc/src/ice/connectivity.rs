@@ -2,95 +2,66 @@
 
 use std::net::{IpAddr, SocketAddr, UdpSocket};
 use std::str::FromStr;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
+use super::candidate::CandidateType;
 use super::pair::{CandidatePair, CandidatePairState};
 use crate::stun::{MessageType, StunMessage};
 
-/// Result of connectivity checks.
-pub struct ConnectivityResult {
-    pub successful_pairs: usize,
-    pub selected_pair: Option<CandidatePair>,
-}
+/// Minimum time between starting two new checks (Ta, RFC 8445 section 14.2).
+pub const CHECK_PACING: Duration = Duration::from_millis(50);
+/// Transmissions of a check before its pair is given up.
+const MAX_CHECK_ATTEMPTS: u32 = 3;
 
-/// Perform a connectivity check on a single candidate pair.
-/// 
-/// Sends a STUN Binding Request and waits for the corresponding response.
-pub fn perform_connectivity_check(
-    socket: &UdpSocket,
-    pair: &CandidatePair,
-) -> Result<bool, Box<dyn std::error::Error>> {
-    let remote_ip = IpAddr::from_str(&pair.remote_candidate.address)?;
-    let remote_addr = SocketAddr::new(remote_ip, pair.remote_candidate.port as u16);
+/// Timing of the connectivity checks.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IceCheckConfig {
+    /// How long the checks may take before ICE fails.
+    pub timeout: Duration,
+    /// Wait for the answer to a check before sending it again; every
+    /// retransmission waits twice as long as the previous one.
+    pub check_interval: Duration,
+    /// Pairs with a check in flight at the same time.
+    pub max_parallel_checks: usize,
+    /// Longest check list; the lowest priority pairs past it are dropped.
+    pub max_pairs: usize,
+}
 
-    // Retry up to 3 times with increasing timeout
-    for attempt in 0..3 {
-        let timeout_ms = 500 + (attempt * 500); // 500ms, 1000ms, 1500ms
-        
-        let (request, transaction_id) = StunMessage::create_binding_request_with_transaction();
-        socket.send_to(&request, remote_addr)?;
-        socket.set_read_timeout(Some(Duration::from_millis(timeout_ms)))?;
-
-        let mut buf = [0u8; 1024];
-        let deadline = std::time::Instant::now() + Duration::from_millis(timeout_ms);
-        
-        while std::time::Instant::now() < deadline {
-            match socket.recv_from(&mut buf) {
-                Ok((len, addr)) => {
-                    // Process any STUN message
-                    match StunMessage::parse(&buf[..len]) {
-                        Ok(response) => match response.message_type {
-                            MessageType::BindingResponse => {
-                                if response.transaction_id == transaction_id {
-                                    socket.set_read_timeout(None)?;
-                                    return Ok(true);
-                                }
-                            }
-                            MessageType::BindingRequest => {
-                                // Respond to incoming binding requests (important for both peers)
-                                let reply = StunMessage::create_binding_success(
-                                    response.transaction_id,
-                                    addr,
-                                );
-                                let _ = socket.send_to(&reply, addr);
-                            }
-                            _ => {}
-                        },
-                        Err(_) => continue,
-                    }
-                }
-                Err(err) => {
-                    if err.kind() == std::io::ErrorKind::TimedOut
-                        || err.kind() == std::io::ErrorKind::WouldBlock
-                    {
-                        break; // Try next attempt
-                    }
-                    socket.set_read_timeout(None)?;
-                    return Err(Box::new(err));
-                }
-            }
+impl Default for IceCheckConfig {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(10),
+            check_interval: Duration::from_millis(500),
+            max_parallel_checks: 5,
+            max_pairs: 20,
         }
     }
-    
-    socket.set_read_timeout(None)?;
-    Ok(false)
+}
+
+/// A check waiting for its Binding response.
+struct InFlightCheck {
+    index: usize,
+    request: Vec<u8>,
+    transaction_id: [u8; 12],
+    remote_addr: SocketAddr,
+    attempts: u32,
+    sent_at: Instant,
+    retransmit_at: Instant,
+}
+
+/// Result of connectivity checks.
+pub struct ConnectivityResult {
+    pub successful_pairs: usize,
+    pub selected_pair: Option<CandidatePair>,
 }
 
 /// Sort candidate pairs by priority in descending order.
-/// 
+///
 /// Uses the ICE priority formula for candidate pairs.
 pub fn sort_pairs_by_priority(pairs: &mut Vec<CandidatePair>) {
     let mut pairs_with_priority: Vec<_> = pairs
         .iter()
-        .map(|pair| {
-            let g = pair.local_candidate.priority as u64;
-            let d = pair.remote_candidate.priority as u64;
-            let min_priority = g.min(d);
-            let max_priority = g.max(d);
-            let priority =
-                (1u64 << 32) * min_priority + 2 * max_priority + if g > d { 1 } else { 0 };
-            (pair.clone(), priority)
-        })
+        .map(|pair| (pair.clone(), pair.priority()))
         .collect();
 
     pairs_with_priority.sort_by(|a, b| b.1.cmp(&a.1));
@@ -101,6 +72,47 @@ pub fn sort_pairs_by_priority(pairs: &mut Vec<CandidatePair>) {
         .collect();
 }
 
+/// Sorts the check list by priority and prunes it (RFC 8445 6.1.2.4).
+///
+/// Every local candidate lives on the one media socket, so a local srflx
+/// candidate has the host candidate as its base and checks from it are
+/// the same as from the host: those pairs go when the host one with the same
+/// remote is there. Then the list is cut at `max_pairs`. Returns how many
+/// pairs were dropped.
+pub fn prune_pairs(pairs: &mut Vec<CandidatePair>, max_pairs: usize) -> usize {
+    let formed = pairs.len();
+    sort_pairs_by_priority(pairs);
+
+    let base = pairs
+        .iter()
+        .map(|pair| &pair.local_candidate)
+        .find(|candidate| candidate.candidate_type == CandidateType::Host)
+        .map(|host| (host.address.clone(), host.port));
+    let mut seen = Vec::with_capacity(formed);
+    pairs.retain(|pair| {
+        let local = &pair.local_candidate;
+        let local_base = match (&local.candidate_type, &base) {
+            (CandidateType::Srflx, Some(base)) => base.clone(),
+            _ => (local.address.clone(), local.port),
+        };
+        let remote = &pair.remote_candidate;
+        let key = (local_base, remote.address.clone(), remote.port);
+        // Ordenados por prioridad: el primero de cada clave es el que queda
+        if seen.contains(&key) {
+            return false;
+        }
+        seen.push(key);
+        true
+    });
+    pairs.truncate(max_pairs.max(1));
+
+    let pruned = formed - pairs.len();
+    if pruned > 0 {
+        log_debug!("  pruned {} of {} candidate pairs", pruned, formed);
+    }
+    pruned
+}
+
 /// Calculate the combined priority of a candidate pair.
 #[cfg(test)]
 pub fn calculate_pair_priority(pair: &CandidatePair) -> u64 {
@@ -114,12 +126,20 @@ pub fn calculate_pair_priority(pair: &CandidatePair) -> u64 {
 }
 
 /// Run connectivity checks on all candidate pairs.
+///
+/// Checks start in priority order, one every `CHECK_PACING` and at most
+/// `config.max_parallel_checks` at a time, so a pair that never answers does
+/// not hold up the ones behind it. The controlling agent stops at the first
+/// pair that works; the controlled one goes on, answering the peer's checks
+/// meanwhile, until no pair better than the one that worked is left. Pairs
+/// never checked stay `Waiting`.
 pub fn run_connectivity_checks(
     socket: &UdpSocket,
     pairs: &mut Vec<CandidatePair>,
     is_controlling: bool,
+    config: &IceCheckConfig,
 ) -> Result<Option<CandidatePair>, Box<dyn std::error::Error>> {
-    println!(" starting connectivity checks...");
+    log_debug!(" starting connectivity checks...");
 
     if pairs.is_empty() {
         return Err("No candidate pairs to check".into());
@@ -127,64 +147,407 @@ pub fn run_connectivity_checks(
 
     sort_pairs_by_priority(pairs);
 
-    println!("  trying {} pairs of candidates...", pairs.len());
+    log_debug!("  trying {} pairs of candidates...", pairs.len());
 
-    let mut successful_pairs = 0;
-    let mut selected_pair: Option<CandidatePair> = None;
+    let deadline = Instant::now() + config.timeout;
+    let result = check_pairs(socket, pairs, is_controlling, config, deadline);
+    socket.set_read_timeout(None)?;
+    let selected = result?;
 
-    let pairs_to_check = pairs.clone();
+    // Lo que quedó sin respuesta cuenta como fallido
+    for pair in pairs.iter_mut() {
+        if pair.state == CandidatePairState::InProgress {
+            pair.state = CandidatePairState::Failed;
+        }
+    }
 
-    for (idx, pair) in pairs_to_check.iter().enumerate() {
-        println!(
-            "  [{}] Trying: {}:{} → {}:{}",
-            idx + 1,
-            pair.local_candidate.address,
-            pair.local_candidate.port,
-            pair.remote_candidate.address,
-            pair.remote_candidate.port
-        );
+    match selected {
+        Some(index) => {
+            let successful_pairs = pairs
+                .iter()
+                .filter(|pair| pair.state == CandidatePairState::Succeeded)
+                .count();
+            log_debug!(" {} successful pairs", successful_pairs);
+            Ok(Some(pairs[index].clone()))
+        }
+        None if Instant::now() >= deadline => {
+            Err(format!("no pair of candidates worked in {:?}", config.timeout).into())
+        }
+        None => Err("Neither pair of candidates worked".into()),
+    }
+}
+
+/// Drives the paced checks until they are done or `deadline` passes.
+/// Returns the index of the best pair that worked.
+fn check_pairs(
+    socket: &UdpSocket,
+    pairs: &mut [CandidatePair],
+    is_controlling: bool,
+    config: &IceCheckConfig,
+    deadline: Instant,
+) -> Result<Option<usize>, Box<dyn std::error::Error>> {
+    let max_parallel = config.max_parallel_checks.max(1);
+    let mut in_flight: Vec<InFlightCheck> = Vec::new();
+    let mut next_start = Instant::now();
+    let mut selected = None;
+    let mut buf = [0u8; 1024];
 
-        if let Some(p) = pairs.get_mut(idx) {
-            p.state = CandidatePairState::InProgress;
+    loop {
+        let now = Instant::now();
+        if now >= deadline {
+            return Ok(selected);
         }
 
-        match perform_connectivity_check(socket, pair) {
-            Ok(true) => {
-                if let Some(p) = pairs.get_mut(idx) {
-                    p.state = CandidatePairState::Succeeded;
+        // Retransmisiones, y los pares que se quedaron sin intentos
+        in_flight.retain_mut(|check| {
+            if now < check.retransmit_at {
+                return true;
+            }
+            let pair = &mut pairs[check.index];
+            if check.attempts >= MAX_CHECK_ATTEMPTS {
+                pair.state = CandidatePairState::Failed;
+                log_debug!("    X Pair failed: {}", check.remote_addr);
+                return false;
+            }
+            if let Err(e) = socket.send_to(&check.request, check.remote_addr) {
+                pair.state = CandidatePairState::Failed;
+                log_debug!("    X Error: {}", e);
+                return false;
+            }
+            check.attempts += 1;
+            check.retransmit_at = now + config.check_interval * (1 << (check.attempts - 1));
+            true
+        });
+
+        // Con un par que anda y ninguno mejor por revisar, no hay nada más
+        // que ganar
+        if let Some(best) = selected {
+            if pairs[..best]
+                .iter()
+                .all(|pair| pair.state == CandidatePairState::Failed)
+            {
+                return Ok(selected);
+            }
+        }
+
+        // Un check nuevo por vez, respetando Ta
+        let waiting = pairs
+            .iter()
+            .position(|pair| pair.state == CandidatePairState::Waiting);
+        let can_start = now >= next_start && in_flight.len() < max_parallel;
+        if let Some(index) = waiting.filter(|_| can_start) {
+            next_start = now + CHECK_PACING;
+            match start_check(socket, &pairs[index], index, now, config) {
+                Ok(check) => {
+                    pairs[index].state = CandidatePairState::InProgress;
+                    in_flight.push(check);
                 }
-                successful_pairs += 1;
-                println!("    OK Pair works!");
-
-                if selected_pair.is_none() {
-                    selected_pair = Some(pair.clone());
-                    println!("    Pair selected como candidato principal");
-                    if is_controlling {
-                        return Ok(selected_pair);
-                    }
-                } else if is_controlling {
-                    return Ok(selected_pair);
+                Err(e) => {
+                    pairs[index].state = CandidatePairState::Failed;
+                    log_debug!("    X Error: {}", e);
                 }
             }
-            Ok(false) => {
-                if let Some(p) = pairs.get_mut(idx) {
-                    p.state = CandidatePairState::Failed;
+            continue;
+        }
+
+        if in_flight.is_empty() && waiting.is_none() {
+            return Ok(selected);
+        }
+
+        // Escuchar hasta lo próximo que haya que hacer
+        let mut wake = deadline;
+        if waiting.is_some() && in_flight.len() < max_parallel {
+            wake = wake.min(next_start);
+        }
+        for check in &in_flight {
+            wake = wake.min(check.retransmit_at);
+        }
+        let wait = wake
+            .saturating_duration_since(now)
+            .max(Duration::from_millis(1));
+        socket.set_read_timeout(Some(wait))?;
+
+        let (len, addr) = match socket.recv_from(&mut buf) {
+            Ok(received) => received,
+            Err(err)
+                if err.kind() == std::io::ErrorKind::TimedOut
+                    || err.kind() == std::io::ErrorKind::WouldBlock =>
+            {
+                continue;
+            }
+            Err(err) => return Err(Box::new(err)),
+        };
+        let Ok(message) = StunMessage::parse(&buf[..len]) else {
+            continue;
+        };
+        match message.message_type {
+            MessageType::BindingResponse => {
+                let Some(position) = in_flight
+                    .iter()
+                    .position(|check| check.transaction_id == message.transaction_id)
+                else {
+                    continue;
+                };
+                let check = in_flight.swap_remove(position);
+                pairs[check.index].state = CandidatePairState::Succeeded;
+                // Con retransmisiones no se sabe a cuál request responde
+                if check.attempts == 1 {
+                    pairs[check.index].rtt = Some(check.sent_at.elapsed());
+                }
+                log_debug!("    OK Pair works! {}", check.remote_addr);
+
+                if selected.is_none_or(|best| check.index < best) {
+                    selected = Some(check.index);
+                    log_debug!("    Pair selected como candidato principal");
                 }
-                println!("    X Pair failed");
+                if is_controlling {
+                    return Ok(selected);
+                }
+            }
+            MessageType::BindingRequest => {
+                // Respond to incoming binding requests (important for both peers)
+                let reply = StunMessage::create_binding_success(message.transaction_id, addr);
+                let _ = socket.send_to(&reply, addr);
             }
-            Err(e) => {
-                if let Some(p) = pairs.get_mut(idx) {
-                    p.state = CandidatePairState::Failed;
+            _ => {}
+        }
+    }
+}
+
+/// Sends the first Binding Request of a check.
+fn start_check(
+    socket: &UdpSocket,
+    pair: &CandidatePair,
+    index: usize,
+    now: Instant,
+    config: &IceCheckConfig,
+) -> Result<InFlightCheck, Box<dyn std::error::Error>> {
+    let remote_ip = IpAddr::from_str(&pair.remote_candidate.address)?;
+    let remote_addr = SocketAddr::new(remote_ip, pair.remote_candidate.port as u16);
+
+    log_debug!(
+        "  [{}] Trying: {}:{} → {}",
+        index + 1,
+        pair.local_candidate.address,
+        pair.local_candidate.port,
+        remote_addr
+    );
+
+    let (request, transaction_id) = StunMessage::create_binding_request_with_transaction();
+    socket.send_to(&request, remote_addr)?;
+    Ok(InFlightCheck {
+        index,
+        request,
+        transaction_id,
+        remote_addr,
+        attempts: 1,
+        sent_at: now,
+        retransmit_at: now + config.check_interval,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ice::candidate::{CandidateType, IceCandidate};
+    use std::sync::mpsc;
+    use std::thread;
+
+    fn candidate(port: u16, priority: u32) -> IceCandidate {
+        IceCandidate {
+            name: format!("host-{}", port),
+            address: "127.0.0.1".to_string(),
+            port: port as u32,
+            candidate_type: CandidateType::Host,
+            priority,
+        }
+    }
+
+    fn pair(local: &UdpSocket, remote: &UdpSocket, priority: u32) -> CandidatePair {
+        CandidatePair::new(
+            candidate(local.local_addr().unwrap().port(), priority),
+            candidate(remote.local_addr().unwrap().port(), priority),
+        )
+    }
+
+    /// Answers every Binding Request and reports when each one arrived.
+    fn responder() -> (UdpSocket, mpsc::Receiver<Instant>) {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let server = socket.try_clone().unwrap();
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let mut buf = [0u8; 1024];
+            while let Ok((len, addr)) = server.recv_from(&mut buf) {
+                let Ok(message) = StunMessage::parse(&buf[..len]) else {
+                    continue;
+                };
+                if message.message_type == MessageType::BindingRequest {
+                    let _ = tx.send(Instant::now());
+                    let reply = StunMessage::create_binding_success(message.transaction_id, addr);
+                    let _ = server.send_to(&reply, addr);
                 }
-                println!("    X Error: {}", e);
+            }
+        });
+        (socket, rx)
+    }
+
+    fn typed(address: &str, port: u32, candidate_type: CandidateType) -> IceCandidate {
+        IceCandidate {
+            name: format!("{}-{}", candidate_type, port),
+            address: address.to_string(),
+            port,
+            priority: crate::ice::gathering::calculate_priority(&candidate_type, 65535),
+            candidate_type,
+        }
+    }
+
+    fn endpoints(pairs: &[CandidatePair]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|pair| {
+                (
+                    format!("{}", pair.local_candidate.candidate_type),
+                    format!(
+                        "{}:{}",
+                        pair.remote_candidate.address, pair.remote_candidate.port
+                    ),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_srflx_pairs_sharing_the_host_base_are_pruned() {
+        let host = typed("192.168.1.4", 5000, CandidateType::Host);
+        let srflx = typed("198.51.100.1", 62000, CandidateType::Srflx);
+        let remote_host = typed("10.0.0.9", 4000, CandidateType::Host);
+        let remote_srflx = typed("203.0.113.7", 61000, CandidateType::Srflx);
+        let mut pairs = Vec::new();
+        for remote in [&remote_srflx, &remote_host] {
+            for local in [&srflx, &host] {
+                pairs.push(CandidatePair::new(local.clone(), remote.clone()));
             }
         }
+        // Repetido: el mismo par dos veces
+        pairs.push(CandidatePair::new(host.clone(), remote_host.clone()));
+
+        assert_eq!(prune_pairs(&mut pairs, 20), 3);
+        assert_eq!(
+            endpoints(&pairs),
+            [
+                ("host".to_string(), "10.0.0.9:4000".to_string()),
+                ("host".to_string(), "203.0.113.7:61000".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_srflx_pair_stays_without_a_host_pair() {
+        let srflx = typed("198.51.100.1", 62000, CandidateType::Srflx);
+        let remote = typed("10.0.0.9", 4000, CandidateType::Host);
+        let mut pairs = vec![CandidatePair::new(srflx, remote)];
+
+        assert_eq!(prune_pairs(&mut pairs, 20), 0);
+        assert_eq!(pairs.len(), 1);
+    }
+
+    #[test]
+    fn test_check_list_is_capped_by_priority() {
+        let host = typed("192.168.1.4", 5000, CandidateType::Host);
+        let mut pairs: Vec<_> = (0..6)
+            .map(|i| {
+                let mut remote = typed("10.0.0.9", 4000 + i, CandidateType::Host);
+                remote.priority = 100 + i;
+                CandidatePair::new(host.clone(), remote)
+            })
+            .collect();
+
+        assert_eq!(prune_pairs(&mut pairs, 4), 2);
+        let ports: Vec<u32> = pairs.iter().map(|p| p.remote_candidate.port).collect();
+        assert_eq!(ports, [4005, 4004, 4003, 4002]);
+        assert!(pairs.windows(2).all(|w| w[0].priority() >= w[1].priority()));
     }
 
-    if successful_pairs == 0 {
-        Err("Neither pair of candidates worked".into())
-    } else {
-        println!(" {} successful pairs", successful_pairs);
-        Ok(selected_pair)
+    #[test]
+    fn test_controlled_stops_once_the_best_pair_works() {
+        let local = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let (remote, arrivals) = responder();
+        let mut pairs: Vec<_> = (0..3).map(|i| pair(&local, &remote, 100 + i)).collect();
+
+        let selected =
+            run_connectivity_checks(&local, &mut pairs, false, &IceCheckConfig::default())
+                .unwrap()
+                .unwrap();
+        assert_eq!(selected.priority(), pairs[0].priority());
+        // El mejor anduvo: los de abajo no hace falta probarlos
+        assert!(pairs[1..]
+            .iter()
+            .any(|p| p.state == CandidatePairState::Waiting));
+        assert!(arrivals.try_iter().count() < 3);
+    }
+
+    #[test]
+    fn test_silent_pair_does_not_hold_up_the_next() {
+        let local = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let silent = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let (live, _) = responder();
+        let mut pairs = vec![pair(&local, &silent, 200), pair(&local, &live, 100)];
+
+        let start = Instant::now();
+        let selected =
+            run_connectivity_checks(&local, &mut pairs, true, &IceCheckConfig::default())
+                .unwrap()
+                .unwrap();
+        // Uno por uno habría esperado todos los reintentos del primero
+        assert!(start.elapsed() < Duration::from_millis(500));
+        assert_eq!(
+            selected.remote_candidate.port,
+            live.local_addr().unwrap().port() as u32
+        );
+    }
+
+    #[test]
+    fn test_checks_are_paced() {
+        let local = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let silent = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let (remote, arrivals) = responder();
+        let mut pairs = vec![
+            pair(&local, &silent, 200),
+            pair(&local, &remote, 101),
+            pair(&local, &remote, 100),
+        ];
+        let config = IceCheckConfig {
+            check_interval: Duration::from_millis(20),
+            ..IceCheckConfig::default()
+        };
+
+        // El controlado sigue revisando mientras el mejor par no falle
+        run_connectivity_checks(&local, &mut pairs, false, &config).unwrap();
+        assert_eq!(pairs[0].state, CandidatePairState::Failed);
+        assert!(pairs[1..]
+            .iter()
+            .all(|p| p.state == CandidatePairState::Succeeded));
+
+        let times: Vec<Instant> = arrivals.try_iter().collect();
+        assert_eq!(times.len(), 2);
+        for gap in times.windows(2) {
+            assert!(gap[1] - gap[0] >= CHECK_PACING - Duration::from_millis(5));
+        }
+    }
+
+    #[test]
+    fn test_gives_up_at_the_timeout() {
+        let local = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let silent = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let mut pairs = vec![pair(&local, &silent, 100)];
+        let config = IceCheckConfig {
+            timeout: Duration::from_millis(300),
+            ..IceCheckConfig::default()
+        };
+
+        let start = Instant::now();
+        assert!(run_connectivity_checks(&local, &mut pairs, true, &config).is_err());
+        assert!(start.elapsed() < Duration::from_secs(1));
+        assert_eq!(pairs[0].state, CandidatePairState::Failed);
     }
 }
@@ -46,6 +46,7 @@ pub(crate) fn probe_default_ipv4(stun_client: &StunClient) -> Option<IpAddr> {
 pub fn calculate_priority(candidate_type: &CandidateType, local_pref: u32) -> u32 {
     let type_pref = match candidate_type {
         CandidateType::Host => 126,
+        CandidateType::Prflx => 110,
         CandidateType::Srflx => 100,
         CandidateType::Relay => 0,
     };
@@ -8,3 +8,6 @@ mod pair;
 
 pub use agent::IceAgent;
 pub use candidate::{CandidateType, IceCandidate};
+pub use connectivity::IceCheckConfig;
+pub use pair::{CandidatePair, CandidatePairInfo, CandidatePairState, IcePairStats};
+pub(crate) use gathering::probe_default_ipv4;
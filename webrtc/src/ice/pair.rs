@@ -1,6 +1,11 @@
 //! ICE pairs that combine local and remote candidates.
 //!
-use super::candidate::IceCandidate;
+use std::fmt;
+use std::time::Duration;
+
+use serde::Serialize;
+
+use super::candidate::{CandidateType, IceCandidate};
 
 /// Candidate pair generated from local-remote combinations.
 #[derive(Debug, Clone)]
@@ -8,13 +13,96 @@ pub struct CandidatePair {
     pub local_candidate: IceCandidate,
     pub remote_candidate: IceCandidate,
     pub state: CandidatePairState,
+    /// Round trip of the connectivity check that succeeded, when it was
+    /// answered without retransmissions.
+    pub rtt: Option<Duration>,
+}
+
+impl CandidatePair {
+    pub fn new(local_candidate: IceCandidate, remote_candidate: IceCandidate) -> Self {
+        Self {
+            local_candidate,
+            remote_candidate,
+            state: CandidatePairState::Waiting,
+            rtt: None,
+        }
+    }
+
+    /// Pair priority (RFC 8445 6.1.2.3); `G` is taken from the local side.
+    pub fn priority(&self) -> u64 {
+        let g = self.local_candidate.priority as u64;
+        let d = self.remote_candidate.priority as u64;
+        (1u64 << 32) * g.min(d) + 2 * g.max(d) + if g > d { 1 } else { 0 }
+    }
 }
 
 /// Possible states during the life cycle of an ICE pair.
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub enum CandidatePairState {
     Waiting,
     InProgress,
     Succeeded,
     Failed,
 }
+
+/// Snapshot of a candidate pair for the UI and the logs.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CandidatePairInfo {
+    pub local_address: String,
+    pub remote_address: String,
+    pub local_type: CandidateType,
+    pub remote_type: CandidateType,
+    pub priority: u64,
+    pub state: CandidatePairState,
+    pub rtt: Option<Duration>,
+}
+
+impl From<&CandidatePair> for CandidatePairInfo {
+    fn from(pair: &CandidatePair) -> Self {
+        let address =
+            |candidate: &IceCandidate| format!("{}:{}", candidate.address, candidate.port);
+        Self {
+            local_address: address(&pair.local_candidate),
+            remote_address: address(&pair.remote_candidate),
+            local_type: pair.local_candidate.candidate_type.clone(),
+            remote_type: pair.remote_candidate.candidate_type.clone(),
+            priority: pair.priority(),
+            state: pair.state.clone(),
+            rtt: pair.rtt,
+        }
+    }
+}
+
+/// How the check list of the last connectivity checks came out.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct IcePairStats {
+    /// Pairs formed from every local and remote candidate.
+    pub formed: usize,
+    /// Dropped as redundant or over `IceCheckConfig::max_pairs`.
+    pub pruned: usize,
+    /// Pairs whose check was actually sent.
+    pub checked: usize,
+}
+
+/// `4 formed, 2 pruned, 1 checked`
+impl fmt::Display for IcePairStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} formed, {} pruned, {} checked",
+            self.formed, self.pruned, self.checked
+        )
+    }
+}
+
+/// `host↔srflx via 203.0.113.7:61000`, the remote address being the one
+/// the media goes to.
+impl fmt::Display for CandidatePairInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}↔{} via {}",
+            self.local_type, self.remote_type, self.remote_address
+        )
+    }
+}
@@ -1,3 +1,6 @@
+#[macro_use]
+pub mod log;
+
 pub mod ice;
 pub mod protocols;
 pub mod rtc;
@@ -1,26 +1,241 @@
 use serde::{Serialize, Deserialize};
 
+/// Control messages go on this SCTP stream as JSON.
+pub const FILE_CONTROL_STREAM: u16 = 1;
+/// First SCTP stream used for file data, one stream per transfer.
+pub const FILE_DATA_STREAM_BASE: u16 = 1024;
+/// How many data streams are cycled through. Even, so the id parity (who
+/// started the transfer) is kept in the stream number.
+const FILE_DATA_STREAM_COUNT: u64 = 2048;
+/// Largest thumbnail an offer may carry. Bigger ones are dropped by the
+/// receiver, the offer itself still goes through.
+pub const MAX_THUMBNAIL_BYTES: usize = 32 * 1024;
+/// MIME type of files we cannot tell apart.
+pub const DEFAULT_MIME_TYPE: &str = "application/octet-stream";
+
+/// Every message carries the id of the transfer it belongs to. Each side
+/// picks ids of its own parity (see [`transfer_id`]), so ids never clash.
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(tag = "type")]
 pub enum FileTransferMessage {
     #[serde(rename = "offer")]
     Offer {
+        id: u64,
         filename: String,
         size: usize,
         mime_type: String,
+        /// JPEG preview for images. Sent as base64 and left out when absent,
+        /// so offers from peers without previews still parse.
+        #[serde(
+            default,
+            skip_serializing_if = "Option::is_none",
+            with = "base64_bytes"
+        )]
+        thumbnail: Option<Vec<u8>>,
     },
     #[serde(rename = "answer")]
-    Answer {
-        accepted: bool,
-    },
+    Answer { id: u64, accepted: bool },
     #[serde(rename = "chunk")]
     Chunk {
+        id: u64,
         data: String, // Base64 if needed, but we prefer binary stream
     },
     #[serde(rename = "ack")]
-    Ack {
-        bytes_received: usize,
-    },
+    Ack { id: u64, bytes_received: usize },
+    /// Sent after the last chunk of every file. It may overtake the last
+    /// chunks, which go on another stream, so the receiver only completes
+    /// the file once it has `size` bytes.
     #[serde(rename = "eof")]
-    Eof,
+    Eof { id: u64 },
+    /// Either side gives up on the transfer in progress (or the pending offer).
+    #[serde(rename = "cancel")]
+    Cancel { id: u64, reason: String },
+}
+
+impl FileTransferMessage {
+    pub fn id(&self) -> u64 {
+        match self {
+            FileTransferMessage::Offer { id, .. }
+            | FileTransferMessage::Answer { id, .. }
+            | FileTransferMessage::Chunk { id, .. }
+            | FileTransferMessage::Ack { id, .. }
+            | FileTransferMessage::Eof { id }
+            | FileTransferMessage::Cancel { id, .. } => *id,
+        }
+    }
+}
+
+/// The `seq`-th transfer started by this side. The controlling peer uses
+/// even ids and the controlled one odd ids.
+pub fn transfer_id(seq: u64, controlling: bool) -> u64 {
+    seq * 2 + u64::from(!controlling)
+}
+
+/// SCTP stream that carries the raw chunks of transfer `id`.
+pub fn data_stream_for(id: u64) -> u16 {
+    FILE_DATA_STREAM_BASE + (id % FILE_DATA_STREAM_COUNT) as u16
+}
+
+/// MIME type of a file from its first bytes, falling back to the extension
+/// of `filename`.
+pub fn detect_mime_type(filename: &str, head: &[u8]) -> &'static str {
+    const MAGIC: [(&[u8], &str); 6] = [
+        (b"\x89PNG\r\n\x1a\n", "image/png"),
+        (b"\xff\xd8\xff", "image/jpeg"),
+        (b"GIF8", "image/gif"),
+        (b"BM", "image/bmp"),
+        (b"%PDF", "application/pdf"),
+        (b"PK\x03\x04", "application/zip"),
+    ];
+    if let Some((_, mime)) = MAGIC.iter().find(|(magic, _)| head.starts_with(magic)) {
+        return mime;
+    }
+    if head.len() >= 12 && &head[..4] == b"RIFF" && &head[8..12] == b"WEBP" {
+        return "image/webp";
+    }
+    let extension = filename
+        .rsplit_once('.')
+        .map(|(_, ext)| ext.to_ascii_lowercase())
+        .unwrap_or_default();
+    match extension.as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "bmp" => "image/bmp",
+        "webp" => "image/webp",
+        "pdf" => "application/pdf",
+        "zip" => "application/zip",
+        "txt" | "log" => "text/plain",
+        "mp3" => "audio/mpeg",
+        "mp4" => "video/mp4",
+        _ => DEFAULT_MIME_TYPE,
+    }
+}
+
+pub fn is_image_mime(mime_type: &str) -> bool {
+    mime_type.starts_with("image/")
+}
+
+/// Serializes `Option<Vec<u8>>` as a base64 string, much shorter in JSON
+/// than an array of numbers.
+mod base64_bytes {
+    use base64::engine::general_purpose::STANDARD;
+    use base64::Engine;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &Option<Vec<u8>>, s: S) -> Result<S::Ok, S::Error> {
+        match bytes {
+            Some(bytes) => s.serialize_str(&STANDARD.encode(bytes)),
+            None => s.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Vec<u8>>, D::Error> {
+        match Option::<String>::deserialize(d)? {
+            Some(text) => STANDARD
+                .decode(text)
+                .map(Some)
+                .map_err(serde::de::Error::custom),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Whether `stream` is one of the file data streams.
+pub fn is_data_stream(stream: u16) -> bool {
+    (FILE_DATA_STREAM_BASE as u64..FILE_DATA_STREAM_BASE as u64 + FILE_DATA_STREAM_COUNT)
+        .contains(&u64::from(stream))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cancel_wire_format() {
+        let msg = FileTransferMessage::Cancel {
+            id: 7,
+            reason: "cancelled by receiver".to_string(),
+        };
+        let json = serde_json::to_string(&msg).unwrap();
+        assert_eq!(
+            json,
+            r#"{"type":"cancel","id":7,"reason":"cancelled by receiver"}"#
+        );
+        match serde_json::from_str(&json).unwrap() {
+            FileTransferMessage::Cancel { id, reason } => {
+                assert_eq!(id, 7);
+                assert_eq!(reason, "cancelled by receiver");
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_every_message_has_an_id() {
+        let eof: FileTransferMessage = serde_json::from_str(r#"{"type":"eof","id":3}"#).unwrap();
+        assert_eq!(eof.id(), 3);
+        // Messages without an id (previous protocol version) are rejected
+        assert!(serde_json::from_str::<FileTransferMessage>(r#"{"type":"eof"}"#).is_err());
+    }
+
+    #[test]
+    fn test_ids_and_streams_do_not_clash_between_sides() {
+        let ours: Vec<u64> = (0..5000).map(|seq| transfer_id(seq, true)).collect();
+        let theirs: Vec<u64> = (0..5000).map(|seq| transfer_id(seq, false)).collect();
+        assert!(ours.iter().all(|id| !theirs.contains(id)));
+
+        for (a, b) in ours.iter().zip(&theirs) {
+            assert_ne!(data_stream_for(*a), data_stream_for(*b));
+            assert!(is_data_stream(data_stream_for(*a)));
+            assert!(is_data_stream(data_stream_for(*b)));
+        }
+        assert!(!is_data_stream(FILE_CONTROL_STREAM));
+        assert!(!is_data_stream(
+            crate::protocols::media_control::MEDIA_CONTROL_STREAM
+        ));
+    }
+
+    #[test]
+    fn test_offer_thumbnail_is_optional() {
+        let offer = FileTransferMessage::Offer {
+            id: 2,
+            filename: "cat.png".to_string(),
+            size: 10,
+            mime_type: "image/png".to_string(),
+            thumbnail: Some(vec![0xff, 0xd8, 0xff]),
+        };
+        let json = serde_json::to_string(&offer).unwrap();
+        assert!(json.contains(r#""thumbnail":"/9j/""#));
+        match serde_json::from_str(&json).unwrap() {
+            FileTransferMessage::Offer { thumbnail, .. } => {
+                assert_eq!(thumbnail, Some(vec![0xff, 0xd8, 0xff]))
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+
+        let without = r#"{"type":"offer","id":4,"filename":"a.bin","size":1,"mime_type":"application/octet-stream"}"#;
+        match serde_json::from_str(without).unwrap() {
+            FileTransferMessage::Offer { thumbnail, .. } => assert!(thumbnail.is_none()),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_detect_mime_type() {
+        assert_eq!(detect_mime_type("photo", b"\xff\xd8\xff\xe0"), "image/jpeg");
+        // Magic bytes win over a misleading extension
+        assert_eq!(
+            detect_mime_type("notes.txt", b"\x89PNG\r\n\x1a\n"),
+            "image/png"
+        );
+        assert_eq!(detect_mime_type("Holiday.JPG", b""), "image/jpeg");
+        assert_eq!(
+            detect_mime_type("report.pdf", b"garbage"),
+            "application/pdf"
+        );
+        assert_eq!(detect_mime_type("data", b"\x00\x01"), DEFAULT_MIME_TYPE);
+        assert!(is_image_mime("image/gif"));
+        assert!(!is_image_mime(DEFAULT_MIME_TYPE));
+    }
 }
@@ -0,0 +1,78 @@
+use crate::worker_thread::worker_media::VideoParams;
+use serde::{Deserialize, Serialize};
+
+/// SCTP stream reserved for in-band media control (file transfer uses 1 and
+/// the data streams from `FILE_DATA_STREAM_BASE`).
+pub const MEDIA_CONTROL_STREAM: u16 = 3;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type")]
+pub enum MediaControlMessage {
+    /// The sender switched its camera/encoder to these parameters.
+    #[serde(rename = "video_params")]
+    VideoParams(VideoParams),
+    /// The sender moved one of its streams to a new SSRC after a collision.
+    #[serde(rename = "ssrc_changed")]
+    SsrcChanged { old: u32, new: u32 },
+    /// The sender put the call on hold and stopped its camera and microphone.
+    #[serde(rename = "hold")]
+    Hold,
+    /// Either side took the call off hold.
+    #[serde(rename = "resume")]
+    Resume,
+}
+
+impl MediaControlMessage {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        serde_json::to_vec(self).unwrap_or_default()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        serde_json::from_slice(bytes).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn video_params_roundtrip() {
+        let params = VideoParams {
+            width: 640,
+            height: 360,
+            fps: 15,
+            max_bitrate_kbps: Some(400),
+        };
+        let msg = MediaControlMessage::VideoParams(params);
+        let parsed = MediaControlMessage::from_bytes(&msg.to_bytes());
+        assert_eq!(parsed, Some(msg));
+    }
+
+    #[test]
+    fn ssrc_changed_wire_format() {
+        let msg = MediaControlMessage::SsrcChanged { old: 1000, new: 77 };
+        assert_eq!(
+            msg.to_bytes(),
+            br#"{"type":"ssrc_changed","old":1000,"new":77}"#.to_vec()
+        );
+        assert_eq!(MediaControlMessage::from_bytes(&msg.to_bytes()), Some(msg));
+    }
+
+    #[test]
+    fn hold_wire_format() {
+        assert_eq!(
+            MediaControlMessage::Hold.to_bytes(),
+            br#"{"type":"hold"}"#.to_vec()
+        );
+        assert_eq!(
+            MediaControlMessage::from_bytes(br#"{"type":"resume"}"#),
+            Some(MediaControlMessage::Resume)
+        );
+    }
+
+    #[test]
+    fn invalid_bytes_are_ignored() {
+        assert_eq!(MediaControlMessage::from_bytes(b"{\"type\":\"offer\"}"), None);
+    }
+}
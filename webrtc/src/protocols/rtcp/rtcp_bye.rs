@@ -5,6 +5,9 @@ impl ByeRtcp {
     pub fn new(ssrc: u32) -> Self {
         Self { ssrc }
     }
+    pub fn get_ssrc(&self) -> u32 {
+        self.ssrc
+    }
     pub fn write_bytes(&self) -> Vec<u8> {
         self.ssrc.to_be_bytes().to_vec()
     }
@@ -3,3 +3,6 @@ pub const RTP_ERROR: &str = "RtpError";
 
 pub const INVALID_H264_TYPE_ERROR: &str = "Wrong number";
 pub const INVALID_RTP_PAYLOAD_TYPE_ERROR: &str = "Wrong RTP payload type";
+pub const TRUNCATED_RTP_HEADER_ERROR: &str = "bytes are not enough for the RTP header";
+pub const INVALID_RTP_PADDING_ERROR: &str = "is not a valid padding count for";
+pub const INVALID_RTP_VERSION_ERROR: &str = "is not RTP version 2";
@@ -0,0 +1,299 @@
+//! RTP header extensions in the one-byte form (RFC 8285), the
+//! abs-send-time extension used for bandwidth estimation and our own audio
+//! latency probe.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// "Defined by profile" value that marks a one-byte extension block.
+pub const ONE_BYTE_PROFILE: u16 = 0xBEDE;
+pub const ABS_SEND_TIME_URI: &str = "http://www.webrtc.org/experiments/rtp-hdrext/abs-send-time";
+/// Id we offer for abs-send-time in `a=extmap`.
+pub const ABS_SEND_TIME_ID: u8 = 3;
+/// Audio latency probe: a capture time the peer echoes back once played.
+pub const AUDIO_LATENCY_URI: &str = "urn:roomrtc:rtp-hdrext:audio-latency";
+/// Id we offer for the latency probe in `a=extmap`.
+pub const AUDIO_LATENCY_ID: u8 = 4;
+
+const ABS_SEND_TIME_LEN: usize = 3;
+/// abs-send-time is 6.18 fixed point seconds, wrapping every 64 s.
+const ABS_SEND_TIME_FRACTION: f64 = (1 << 18) as f64;
+const ABS_SEND_TIME_MASK: u32 = 0x00FF_FFFF;
+const MAX_ONE_BYTE_ID: u8 = 14;
+const MAX_ONE_BYTE_LEN: usize = 16;
+const LATENCY_PROBE: u8 = 0;
+const LATENCY_ECHO: u8 = 1;
+
+/// One element of the extension block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeaderExtension {
+    pub id: u8,
+    pub data: Vec<u8>,
+}
+
+impl HeaderExtension {
+    /// Ids go from 1 to 14 and data from 1 to 16 bytes in the one-byte form.
+    pub fn new(id: u8, data: Vec<u8>) -> Option<Self> {
+        if id == 0 || id > MAX_ONE_BYTE_ID || data.is_empty() || data.len() > MAX_ONE_BYTE_LEN {
+            return None;
+        }
+        Some(HeaderExtension { id, data })
+    }
+
+    pub fn abs_send_time(id: u8, send_time: u32) -> Option<Self> {
+        let bytes = (send_time & ABS_SEND_TIME_MASK).to_be_bytes();
+        Self::new(id, bytes[1..].to_vec())
+    }
+
+    /// Latency probe or echo: a kind byte and a 24-bit abs-send-time value.
+    pub fn latency(id: u8, stamp: LatencyStamp) -> Option<Self> {
+        let (kind, time) = match stamp {
+            LatencyStamp::Probe(time) => (LATENCY_PROBE, time),
+            LatencyStamp::Echo(time) => (LATENCY_ECHO, time),
+        };
+        let mut data = (time & ABS_SEND_TIME_MASK).to_be_bytes();
+        data[0] = kind;
+        Self::new(id, data.to_vec())
+    }
+}
+
+/// Contents of the audio latency extension, times in abs-send-time units.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LatencyStamp {
+    /// Capture time of the frame, to be echoed when it plays.
+    Probe(u32),
+    /// A probe coming back, moved forward by the time it was held.
+    Echo(u32),
+}
+
+impl LatencyStamp {
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        let [kind, a, b, c] = data else {
+            return None;
+        };
+        let time = u32::from_be_bytes([0, *a, *b, *c]);
+        match *kind {
+            LATENCY_PROBE => Some(LatencyStamp::Probe(time)),
+            LATENCY_ECHO => Some(LatencyStamp::Echo(time)),
+            _ => None,
+        }
+    }
+}
+
+/// The block that follows the CSRC list as it travels: the "defined by
+/// profile" value and its data, a whole number of 32-bit words.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExtensionHeader {
+    pub profile: u16,
+    pub data: Vec<u8>,
+}
+
+impl ExtensionHeader {
+    /// `data` must fill whole words, as the length field counts words.
+    pub fn new(profile: u16, data: Vec<u8>) -> Option<Self> {
+        if !data.len().is_multiple_of(4) || data.len() / 4 > u16::MAX as usize {
+            return None;
+        }
+        Some(ExtensionHeader { profile, data })
+    }
+
+    /// Parses the block at the start of `bytes`. Returns it with its size,
+    /// or `None` if it is truncated.
+    pub fn read(bytes: &[u8]) -> Option<(Self, usize)> {
+        if bytes.len() < 4 {
+            return None;
+        }
+        let profile = u16::from_be_bytes([bytes[0], bytes[1]]);
+        let words = u16::from_be_bytes([bytes[2], bytes[3]]) as usize;
+        let block_len = 4 + words * 4;
+        let data = bytes.get(4..block_len)?.to_vec();
+        Some((ExtensionHeader { profile, data }, block_len))
+    }
+
+    pub fn write(&self) -> Vec<u8> {
+        let mut block = Vec::with_capacity(4 + self.data.len());
+        block.extend_from_slice(&self.profile.to_be_bytes());
+        block.extend_from_slice(&((self.data.len() / 4) as u16).to_be_bytes());
+        block.extend_from_slice(&self.data);
+        block
+    }
+}
+
+/// Serializes the block that follows the CSRC list: profile, length in
+/// 32-bit words and the elements, zero padded to a word boundary.
+pub fn write_block(extensions: &[HeaderExtension]) -> Vec<u8> {
+    let mut elements = Vec::new();
+    for extension in extensions {
+        elements.push((extension.id << 4) | ((extension.data.len() - 1) as u8));
+        elements.extend_from_slice(&extension.data);
+    }
+    while !elements.len().is_multiple_of(4) {
+        elements.push(0);
+    }
+
+    ExtensionHeader {
+        profile: ONE_BYTE_PROFILE,
+        data: elements,
+    }
+    .write()
+}
+
+/// Parses the block at the start of `bytes`. Returns the elements and the
+/// block size, or `None` if it is truncated. Blocks of other profiles are
+/// skipped without elements.
+pub fn read_block(bytes: &[u8]) -> Option<(Vec<HeaderExtension>, usize)> {
+    let (block, block_len) = ExtensionHeader::read(bytes)?;
+    if block.profile != ONE_BYTE_PROFILE {
+        return Some((Vec::new(), block_len));
+    }
+    let elements = block.data.as_slice();
+
+    let mut extensions = Vec::new();
+    let mut pos = 0;
+    while pos < elements.len() {
+        let byte = elements[pos];
+        let id = byte >> 4;
+        if id == 0 {
+            // Padding
+            pos += 1;
+            continue;
+        }
+        if id == 15 {
+            break;
+        }
+        let len = (byte & 0x0F) as usize + 1;
+        let data = elements.get(pos + 1..pos + 1 + len)?;
+        extensions.push(HeaderExtension {
+            id,
+            data: data.to_vec(),
+        });
+        pos += 1 + len;
+    }
+    Some((extensions, block_len))
+}
+
+/// Current wall clock as a 24-bit abs-send-time value.
+pub fn abs_send_time_now() -> u32 {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    ((now.as_secs_f64() * ABS_SEND_TIME_FRACTION) as u64 as u32) & ABS_SEND_TIME_MASK
+}
+
+/// Reads the 24-bit value carried by an abs-send-time element.
+pub fn parse_abs_send_time(data: &[u8]) -> Option<u32> {
+    if data.len() != ABS_SEND_TIME_LEN {
+        return None;
+    }
+    Some(u32::from_be_bytes([0, data[0], data[1], data[2]]))
+}
+
+/// `time` moved forward by `by`, wrapping like the 24-bit value does.
+pub fn abs_send_time_add(time: u32, by: Duration) -> u32 {
+    let ticks = (by.as_secs_f64() * ABS_SEND_TIME_FRACTION) as u64 as u32;
+    time.wrapping_add(ticks) & ABS_SEND_TIME_MASK
+}
+
+/// Seconds from `earlier` to `later`, taking the 64 s wrap into account.
+pub fn abs_send_time_delta(earlier: u32, later: u32) -> f64 {
+    let diff = later.wrapping_sub(earlier) & ABS_SEND_TIME_MASK;
+    let signed = if diff > ABS_SEND_TIME_MASK / 2 {
+        diff as i64 - (ABS_SEND_TIME_MASK as i64 + 1)
+    } else {
+        diff as i64
+    };
+    signed as f64 / ABS_SEND_TIME_FRACTION
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_block_pads_to_word_boundary() {
+        let ext = HeaderExtension::abs_send_time(ABS_SEND_TIME_ID, 0x12_3456).unwrap();
+        let block = write_block(&[ext]);
+        assert_eq!(block, vec![0xBE, 0xDE, 0x00, 0x01, 0x32, 0x12, 0x34, 0x56]);
+
+        let ext = HeaderExtension::new(1, vec![7]).unwrap();
+        let block = write_block(&[ext]);
+        assert_eq!(block, vec![0xBE, 0xDE, 0x00, 0x01, 0x10, 0x07, 0x00, 0x00]);
+    }
+
+    #[test]
+    fn test_block_roundtrip_with_several_elements() {
+        let extensions = vec![
+            HeaderExtension::new(1, vec![1, 2]).unwrap(),
+            HeaderExtension::abs_send_time(ABS_SEND_TIME_ID, 42).unwrap(),
+        ];
+        let block = write_block(&extensions);
+        let (parsed, len) = read_block(&block).unwrap();
+        assert_eq!(len, block.len());
+        assert_eq!(parsed, extensions);
+    }
+
+    #[test]
+    fn test_read_block_rejects_truncated_data() {
+        assert!(read_block(&[0xBE, 0xDE]).is_none());
+        assert!(read_block(&[0xBE, 0xDE, 0x00, 0x02, 0x32, 0x00, 0x00, 0x00]).is_none());
+        // El elemento declara más bytes de los que hay en el bloque
+        assert!(read_block(&[0xBE, 0xDE, 0x00, 0x01, 0x3F, 0x00, 0x00, 0x00]).is_none());
+    }
+
+    #[test]
+    fn test_read_block_skips_other_profiles() {
+        let (parsed, len) = read_block(&[0x10, 0x00, 0x00, 0x01, 1, 2, 3, 4]).unwrap();
+        assert!(parsed.is_empty());
+        assert_eq!(len, 8);
+    }
+
+    #[test]
+    fn test_extension_header_roundtrip() {
+        let block = ExtensionHeader::new(0x1000, vec![1, 2, 3, 4, 5, 6, 7, 8]).unwrap();
+        let bytes = block.write();
+        assert_eq!(&bytes[..4], &[0x10, 0x00, 0x00, 0x02]);
+        assert_eq!(ExtensionHeader::read(&bytes), Some((block, 12)));
+        assert!(ExtensionHeader::read(&bytes[..11]).is_none());
+        assert!(ExtensionHeader::new(0x1000, vec![1, 2, 3]).is_none());
+    }
+
+    #[test]
+    fn test_invalid_ids_and_lengths() {
+        assert!(HeaderExtension::new(0, vec![1]).is_none());
+        assert!(HeaderExtension::new(15, vec![1]).is_none());
+        assert!(HeaderExtension::new(1, vec![]).is_none());
+        assert!(HeaderExtension::new(1, vec![0; 17]).is_none());
+    }
+
+    #[test]
+    fn test_abs_send_time_parse_and_delta() {
+        let ext = HeaderExtension::abs_send_time(ABS_SEND_TIME_ID, 0x04_0000).unwrap();
+        assert_eq!(parse_abs_send_time(&ext.data), Some(0x04_0000));
+        assert!(parse_abs_send_time(&[1, 2]).is_none());
+
+        // 0x04_0000 = 1 s en 6.18
+        assert_eq!(abs_send_time_delta(0, 0x04_0000), 1.0);
+        assert_eq!(abs_send_time_delta(0x04_0000, 0), -1.0);
+        // Cruza el wrap de 64 s
+        assert_eq!(
+            abs_send_time_delta(0xFF_FFFF, 0x00_0000),
+            1.0 / (1 << 18) as f64
+        );
+        assert!(abs_send_time_now() <= ABS_SEND_TIME_MASK);
+    }
+
+    #[test]
+    fn test_latency_stamp_roundtrip() {
+        for stamp in [LatencyStamp::Probe(0x12_3456), LatencyStamp::Echo(7)] {
+            let ext = HeaderExtension::latency(AUDIO_LATENCY_ID, stamp).unwrap();
+            assert_eq!(ext.data.len(), 4);
+            assert_eq!(LatencyStamp::parse(&ext.data), Some(stamp));
+        }
+        assert!(LatencyStamp::parse(&[9, 0, 0, 0]).is_none());
+        assert!(LatencyStamp::parse(&[0, 0, 0]).is_none());
+
+        // Medio segundo retenido, cruzando el wrap
+        let held = abs_send_time_add(0xFF_FFFF, Duration::from_millis(500));
+        assert_eq!(held, 0x01_FFFF);
+        assert_eq!(abs_send_time_delta(0xFF_FFFF, held), 0.5);
+    }
+}
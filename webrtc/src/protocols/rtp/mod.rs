@@ -1,5 +1,6 @@
 pub mod constants;
 pub mod h264_video_type;
+pub mod header_extension;
 pub mod payload_type;
 pub mod rtp_err;
 pub mod rtp_header;
@@ -1,4 +1,7 @@
-use crate::protocols::rtp::constants::rtp_err_const::{INVALID_RTP_PAYLOAD_TYPE_ERROR, RTP_ERROR};
+use crate::protocols::rtp::constants::rtp_err_const::{
+    INVALID_RTP_PADDING_ERROR, INVALID_RTP_PAYLOAD_TYPE_ERROR, INVALID_RTP_VERSION_ERROR,
+    RTP_ERROR, TRUNCATED_RTP_HEADER_ERROR,
+};
 use crate::protocols::rtp::rtp_err::h26_video_type_err::H26VideoTypeErr;
 use std::fmt;
 
@@ -6,6 +9,12 @@ use std::fmt;
 pub enum RtpError {
     InvalidH264(H26VideoTypeErr),
     InvalidRtpPayloadType(u8),
+    /// The packet ends before the fixed header or the CSRC list does.
+    TruncatedHeader(usize),
+    /// Pad count (last byte) and payload length.
+    InvalidPadding(u8, usize),
+    /// Version bits other than 2: not an RTP packet.
+    InvalidVersion(u8),
 }
 impl fmt::Display for RtpError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -16,6 +25,21 @@ impl fmt::Display for RtpError {
                 "{}: \"{}\" {}",
                 RTP_ERROR, number, INVALID_RTP_PAYLOAD_TYPE_ERROR
             ),
+            RtpError::TruncatedHeader(len) => write!(
+                f,
+                "{}: \"{}\" {}",
+                RTP_ERROR, len, TRUNCATED_RTP_HEADER_ERROR
+            ),
+            RtpError::InvalidPadding(count, len) => write!(
+                f,
+                "{}: \"{}\" {} {} bytes",
+                RTP_ERROR, count, INVALID_RTP_PADDING_ERROR, len
+            ),
+            RtpError::InvalidVersion(version) => write!(
+                f,
+                "{}: \"{}\" {}",
+                RTP_ERROR, version, INVALID_RTP_VERSION_ERROR
+            ),
         }
     }
 }
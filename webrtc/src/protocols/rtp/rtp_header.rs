@@ -1,3 +1,12 @@
+use crate::protocols::rtp::header_extension::{
+    self, ExtensionHeader, HeaderExtension, ONE_BYTE_PROFILE,
+};
+use crate::protocols::rtp::rtp_err::rtp_err::RtpError;
+
+const RTP_FIXED_HEADER_LEN: usize = 12;
+const RTP_VERSION: u8 = 2;
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct RtpHeader {
     version: u8,
     padding: bool,
@@ -9,6 +18,9 @@ pub struct RtpHeader {
     timestamp: u32,
     ssrc: u32,
     csrc_list: Vec<u32>,
+    extensions: Vec<HeaderExtension>,
+    /// Block of a profile other than the one-byte form, forwarded as is.
+    foreign_extension: Option<ExtensionHeader>,
 }
 impl RtpHeader {
     #[allow(clippy::too_many_arguments)]
@@ -35,7 +47,31 @@ impl RtpHeader {
             timestamp,
             ssrc,
             csrc_list,
+            extensions: Vec::new(),
+            foreign_extension: None,
+        }
+    }
+    /// Adds a one-byte header extension element and sets the extension bit.
+    pub fn with_extension(mut self, extension: HeaderExtension) -> Self {
+        self.extension = true;
+        self.foreign_extension = None;
+        self.extensions.push(extension);
+        self
+    }
+    /// Sets the whole extension block. One-byte blocks are split into
+    /// their elements, any other profile is kept raw.
+    pub fn with_extension_header(mut self, block: ExtensionHeader) -> Self {
+        self.extension = true;
+        if block.profile == ONE_BYTE_PROFILE {
+            self.extensions = header_extension::read_block(&block.write())
+                .map(|(extensions, _)| extensions)
+                .unwrap_or_default();
+            self.foreign_extension = None;
+        } else {
+            self.extensions.clear();
+            self.foreign_extension = Some(block);
         }
+        self
     }
     pub fn write_bytes(&self) -> Vec<u8> {
         let mut protocol = Vec::new();
@@ -57,11 +93,33 @@ impl RtpHeader {
             let byte = csrc.to_be_bytes();
             add_vec_bytes(&byte, &mut protocol);
         }
+        if self.extension {
+            add_vec_bytes(&self.extension_block(), &mut protocol);
+        }
         protocol
     }
-    pub fn read_bytes(protocol_bytes: &[u8]) -> (Self, usize) {
+    fn extension_block(&self) -> Vec<u8> {
+        match &self.foreign_extension {
+            Some(block) => block.write(),
+            None => header_extension::write_block(&self.extensions),
+        }
+    }
+    /// Parses the header at the start of a packet and returns it with its
+    /// size, which never exceeds the packet. The input comes from the
+    /// network: a packet shorter than its fixed header and CSRC list, or
+    /// that is not RTP version 2, is an error. A truncated extension block
+    /// leaves the packet without payload.
+    /// Padding is not touched here: with SRTP it is encrypted, see
+    /// [`RtpHeader::strip_padding`].
+    pub fn read_bytes(protocol_bytes: &[u8]) -> Result<(Self, usize), RtpError> {
+        if protocol_bytes.len() < RTP_FIXED_HEADER_LEN {
+            return Err(RtpError::TruncatedHeader(protocol_bytes.len()));
+        }
         let byte0 = protocol_bytes[0];
         let version = (byte0 >> 6) & 0b00000011;
+        if version != RTP_VERSION {
+            return Err(RtpError::InvalidVersion(version));
+        }
         let padding = ((byte0 >> 5) & 0b1) != 0;
         let extension = ((byte0 >> 4) & 0b1) != 0;
         let csrc_count = byte0 & 0b00001111;
@@ -82,18 +140,33 @@ impl RtpHeader {
             protocol_bytes[11],
         ]);
         let mut csrc_list = Vec::new();
-        let header_size = 12 + (csrc_count as usize) * 4;
-        for i in 0..csrc_count {
-            let start = 12 + (i as usize) * 4;
-            let csrc = u32::from_be_bytes([
-                protocol_bytes[start],
-                protocol_bytes[start + 1],
-                protocol_bytes[start + 2],
-                protocol_bytes[start + 3],
-            ]);
-            csrc_list.push(csrc);
-        }
-        (
+        let mut header_size = RTP_FIXED_HEADER_LEN + (csrc_count as usize) * 4;
+        if protocol_bytes.len() < header_size {
+            return Err(RtpError::TruncatedHeader(protocol_bytes.len()));
+        }
+        for word in protocol_bytes[RTP_FIXED_HEADER_LEN..header_size].chunks_exact(4) {
+            csrc_list.push(u32::from_be_bytes([word[0], word[1], word[2], word[3]]));
+        }
+        let mut extensions = Vec::new();
+        let mut foreign_extension = None;
+        if extension {
+            let rest = &protocol_bytes[header_size..];
+            match ExtensionHeader::read(rest) {
+                Some((block, block_len)) if block.profile != ONE_BYTE_PROFILE => {
+                    foreign_extension = Some(block);
+                    header_size += block_len;
+                }
+                _ => match header_extension::read_block(rest) {
+                    Some((parsed, block_len)) => {
+                        extensions = parsed;
+                        header_size += block_len;
+                    }
+                    // Bloque truncado: el paquete queda sin payload
+                    None => header_size = protocol_bytes.len(),
+                },
+            }
+        }
+        Ok((
             RtpHeader {
                 version,
                 padding,
@@ -105,9 +178,36 @@ impl RtpHeader {
                 timestamp,
                 ssrc,
                 csrc_list,
+                extensions,
+                foreign_extension,
             },
             header_size,
-        )
+        ))
+    }
+    /// Drops the padding at the end of a decrypted payload. With the P bit
+    /// set, the last byte counts the padding bytes, itself included.
+    pub fn strip_padding<'a>(&self, payload: &'a [u8]) -> Result<&'a [u8], RtpError> {
+        if !self.padding {
+            return Ok(payload);
+        }
+        match payload.last() {
+            Some(&count) if count != 0 && count as usize <= payload.len() => {
+                Ok(&payload[..payload.len() - count as usize])
+            }
+            last => Err(RtpError::InvalidPadding(
+                last.copied().unwrap_or(0),
+                payload.len(),
+            )),
+        }
+    }
+    /// Appends `count` bytes of padding to a payload (the header must have
+    /// the P bit set). `count` includes the trailing count byte.
+    pub fn pad_payload(payload: &mut Vec<u8>, count: u8) {
+        if count == 0 {
+            return;
+        }
+        payload.extend(std::iter::repeat_n(0, count as usize - 1));
+        payload.push(count);
     }
     pub fn get_payload_type(&self) -> u8 {
         self.payload_type
@@ -124,6 +224,37 @@ impl RtpHeader {
     pub fn get_ssrc(&self) -> u32 {
         self.ssrc
     }
+    /// Data of the extension element with the given id, if present.
+    pub fn get_extension(&self, id: u8) -> Option<&[u8]> {
+        self.extensions
+            .iter()
+            .find(|extension| extension.id == id)
+            .map(|extension| extension.data.as_slice())
+    }
+    /// abs-send-time carried under the negotiated `id`.
+    pub fn get_abs_send_time(&self, id: u8) -> Option<u32> {
+        self.get_extension(id)
+            .and_then(header_extension::parse_abs_send_time)
+    }
+    /// The extension block as sent: one-byte elements serialized, other
+    /// profiles as received.
+    pub fn get_extension_header(&self) -> Option<ExtensionHeader> {
+        if !self.extension {
+            return None;
+        }
+        self.foreign_extension.clone().or_else(|| {
+            ExtensionHeader::read(&header_extension::write_block(&self.extensions))
+                .map(|(block, _)| block)
+        })
+    }
+    /// Serialized size, extension block included. SRTP protects what follows.
+    pub fn size(&self) -> usize {
+        let mut size = RTP_FIXED_HEADER_LEN + self.csrc_list.len() * 4;
+        if self.extension {
+            size += self.extension_block().len();
+        }
+        size
+    }
 }
 
 fn add_vec_bytes(bytes: &[u8], protocol: &mut Vec<u8>) {
@@ -134,6 +265,8 @@ fn add_vec_bytes(bytes: &[u8], protocol: &mut Vec<u8>) {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
 
     #[test]
     fn test_rtp_header_to_bytes() {
@@ -148,6 +281,8 @@ mod tests {
             timestamp: 1450744508,
             ssrc: 3735928559,
             csrc_list: vec![],
+            extensions: vec![],
+            foreign_extension: None,
         };
         let bytes = header.write_bytes();
         assert_eq!(bytes.len(), 12);
@@ -179,11 +314,13 @@ mod tests {
             timestamp: 100,
             ssrc: 25,
             csrc_list: vec![122, 125],
+            extensions: vec![],
+            foreign_extension: None,
         };
 
         let bytes = original.write_bytes();
 
-        let (parsed, _) = RtpHeader::read_bytes(&bytes);
+        let (parsed, _) = RtpHeader::read_bytes(&bytes).unwrap();
 
         assert_eq!(parsed.version, original.version);
         assert_eq!(parsed.padding, original.padding);
@@ -206,4 +343,184 @@ mod tests {
         assert!(!header.get_marker());
         assert_eq!(header.get_ssrc(), 999);
     }
+
+    #[test]
+    fn test_abs_send_time_extension_roundtrip() {
+        let send_time = 0x12_3456;
+        let header = RtpHeader::new(2, false, false, 0, true, 96, 7, 3000, 42, vec![])
+            .with_extension(HeaderExtension::abs_send_time(3, send_time).unwrap());
+
+        let mut bytes = header.write_bytes();
+        assert_eq!(bytes.len(), 20);
+        assert_eq!(bytes[0] & 0b0001_0000, 0b0001_0000);
+        assert_eq!(header.size(), bytes.len());
+        bytes.extend_from_slice(&[0xAA, 0xBB]);
+
+        let (parsed, header_size) = RtpHeader::read_bytes(&bytes).unwrap();
+        assert_eq!(header_size, 20);
+        assert_eq!(&bytes[header_size..], &[0xAA, 0xBB]);
+        assert_eq!(parsed.get_abs_send_time(3), Some(send_time));
+        assert_eq!(parsed.get_extension(5), None);
+        assert_eq!(parsed.get_sequence_number(), 7);
+    }
+
+    #[test]
+    fn test_extension_after_csrc_list() {
+        let header = RtpHeader::new(2, false, false, 1, false, 96, 1, 1, 1, vec![77])
+            .with_extension(HeaderExtension::new(1, vec![9]).unwrap());
+        let bytes = header.write_bytes();
+        let (parsed, header_size) = RtpHeader::read_bytes(&bytes).unwrap();
+        assert_eq!(header_size, 24);
+        assert_eq!(parsed.csrc_list, vec![77]);
+        assert_eq!(parsed.get_extension(1), Some(&[9][..]));
+    }
+
+    #[test]
+    fn test_truncated_extension_leaves_no_payload() {
+        let mut bytes = RtpHeader::new(2, false, true, 0, false, 96, 1, 1, 1, vec![]).write_bytes();
+        bytes.truncate(14);
+        let (parsed, header_size) = RtpHeader::read_bytes(&bytes).unwrap();
+        assert_eq!(header_size, bytes.len());
+        assert_eq!(parsed.get_extension(3), None);
+    }
+
+    fn random_header(rng: &mut StdRng) -> RtpHeader {
+        let csrc_list: Vec<u32> = (0..rng.gen_range(0..=15)).map(|_| rng.gen()).collect();
+        let mut header = RtpHeader::new(
+            2,
+            rng.gen(),
+            rng.gen_bool(0.2),
+            csrc_list.len() as u8,
+            rng.gen(),
+            rng.gen_range(0..128),
+            rng.gen(),
+            rng.gen(),
+            rng.gen(),
+            csrc_list,
+        );
+        match rng.gen_range(0..3) {
+            0 => {}
+            1 => {
+                for id in 1..=rng.gen_range(1..=4) {
+                    let data = (0..rng.gen_range(1..=16)).map(|_| rng.gen()).collect();
+                    header = header.with_extension(HeaderExtension::new(id, data).unwrap());
+                }
+            }
+            _ => {
+                let data = (0..rng.gen_range(0..4) * 4).map(|_| rng.gen()).collect();
+                let block = ExtensionHeader::new(0x1000 | rng.gen_range(0..16), data).unwrap();
+                header = header.with_extension_header(block);
+            }
+        }
+        header
+    }
+
+    #[test]
+    fn test_read_write_identity_for_random_headers() {
+        let mut rng = StdRng::seed_from_u64(7);
+        for _ in 0..500 {
+            let header = random_header(&mut rng);
+            let mut packet = header.write_bytes();
+            assert_eq!(packet.len(), header.size());
+            let payload: Vec<u8> = (0..rng.gen_range(1..64)).map(|_| rng.gen()).collect();
+            packet.extend_from_slice(&payload);
+            if header.padding {
+                RtpHeader::pad_payload(&mut packet, rng.gen_range(1..=32));
+            }
+
+            let (parsed, header_size) = RtpHeader::read_bytes(&packet).unwrap();
+            assert_eq!(parsed, header);
+            assert_eq!(header_size, header.size());
+            assert_eq!(parsed.write_bytes(), packet[..header_size]);
+            assert_eq!(
+                parsed.strip_padding(&packet[header_size..]).unwrap(),
+                payload.as_slice()
+            );
+        }
+    }
+
+    #[test]
+    fn test_foreign_extension_is_kept() {
+        let block = ExtensionHeader::new(0x1000, vec![0x01, 0x02, 0xAA, 0xBB]).unwrap();
+        let header = RtpHeader::new(2, false, false, 0, false, 96, 1, 1, 1, vec![])
+            .with_extension_header(block.clone());
+        let (parsed, header_size) = RtpHeader::read_bytes(&header.write_bytes()).unwrap();
+        assert_eq!(header_size, 20);
+        assert_eq!(parsed.get_extension_header(), Some(block));
+        assert_eq!(parsed.get_extension(1), None);
+    }
+
+    #[test]
+    fn test_invalid_padding_and_truncated_header() {
+        let header = RtpHeader::new(2, true, false, 0, false, 96, 1, 1, 1, vec![]);
+        assert_eq!(header.strip_padding(&[1, 2, 3, 2]).unwrap(), &[1, 2]);
+        assert!(header.strip_padding(&[1, 2, 0]).is_err());
+        assert!(header.strip_padding(&[1, 9]).is_err());
+        assert!(header.strip_padding(&[]).is_err());
+
+        assert!(RtpHeader::read_bytes(&[0x80; 11]).is_err());
+        assert!(matches!(
+            RtpHeader::read_bytes(&[0x40; 12]),
+            Err(RtpError::InvalidVersion(1))
+        ));
+        // Declara dos CSRC pero trae uno
+        let mut bytes =
+            RtpHeader::new(2, false, false, 2, false, 96, 1, 1, 1, vec![5, 6]).write_bytes();
+        bytes.truncate(16);
+        assert!(matches!(
+            RtpHeader::read_bytes(&bytes),
+            Err(RtpError::TruncatedHeader(16))
+        ));
+    }
+
+    #[test]
+    fn test_maximum_csrc_count_with_extension() {
+        let csrc_list: Vec<u32> = (1..=15).collect();
+        let header = RtpHeader::new(2, false, false, 15, true, 96, 9, 90, 4, csrc_list.clone())
+            .with_extension(HeaderExtension::new(1, vec![7, 8]).unwrap());
+        let mut packet = header.write_bytes();
+        assert_eq!(packet[0] & 0x0F, 15);
+        packet.extend_from_slice(&[0xAB; 3]);
+
+        let (parsed, header_size) = RtpHeader::read_bytes(&packet).unwrap();
+        assert_eq!(header_size, 12 + 15 * 4 + 8);
+        assert_eq!(parsed.csrc_list, csrc_list);
+        assert_eq!(parsed.get_extension(1), Some(&[7, 8][..]));
+        assert_eq!(&packet[header_size..], &[0xAB; 3]);
+    }
+
+    #[test]
+    fn test_every_truncation_is_rejected_or_bounded() {
+        let header = RtpHeader::new(2, false, false, 15, false, 96, 1, 1, 1, vec![3; 15])
+            .with_extension(HeaderExtension::new(2, vec![1; 16]).unwrap());
+        let packet = header.write_bytes();
+        let fixed_and_csrc = 12 + 15 * 4;
+        for len in 0..=packet.len() {
+            match RtpHeader::read_bytes(&packet[..len]) {
+                Ok((_, header_size)) => {
+                    assert!(len >= fixed_and_csrc, "parsed {} bytes", len);
+                    assert!(header_size <= len);
+                }
+                Err(RtpError::TruncatedHeader(reported)) => {
+                    assert!(len < fixed_and_csrc);
+                    assert_eq!(reported, len);
+                }
+                Err(err) => panic!("unexpected error for {} bytes: {}", len, err),
+            }
+        }
+    }
+
+    #[test]
+    fn test_random_bytes_never_overrun_the_packet() {
+        let mut rng = StdRng::seed_from_u64(11);
+        for _ in 0..2000 {
+            let mut packet: Vec<u8> = (0..rng.gen_range(0..96)).map(|_| rng.gen()).collect();
+            if let Some(first) = packet.first_mut() {
+                *first = (*first & 0x3F) | 0x80;
+            }
+            if let Ok((_, header_size)) = RtpHeader::read_bytes(&packet) {
+                assert!(header_size <= packet.len());
+            }
+        }
+    }
 }
@@ -1,3 +1,4 @@
+use crate::protocols::rtp::constants::rtp_const::RTP_H264_TYPE;
 use crate::protocols::rtp::payload_type::PayloadType;
 use crate::protocols::rtp::rtp_err::rtp_err::RtpError;
 use crate::protocols::rtp::rtp_header::RtpHeader;
@@ -20,9 +21,17 @@ impl RtpPacket {
         bytes
     }
     pub fn read_bytes(bytes: &[u8]) -> Result<RtpPacket, RtpError> {
-        let (rtp_header, header_size) = RtpHeader::read_bytes(bytes);
-        let payload_bytes = &bytes[header_size..];
-        let payload = PayloadType::read_bytes(rtp_header.get_payload_type(), payload_bytes)?;
+        Self::read_h264_bytes(bytes, RTP_H264_TYPE)
+    }
+    /// Parses an H264 packet whose payload type was negotiated as `video_pt`.
+    pub fn read_h264_bytes(bytes: &[u8], video_pt: u8) -> Result<RtpPacket, RtpError> {
+        let (rtp_header, header_size) = RtpHeader::read_bytes(bytes)?;
+        let payload_number = rtp_header.get_payload_type();
+        if payload_number != video_pt {
+            return Err(RtpError::InvalidRtpPayloadType(payload_number));
+        }
+        let payload_bytes = rtp_header.strip_padding(&bytes[header_size..])?;
+        let payload = PayloadType::read_bytes(RTP_H264_TYPE, payload_bytes)?;
         Ok(RtpPacket {
             rtp_header,
             payload,
@@ -77,4 +86,15 @@ mod tests {
         assert!(parsed.get_marker());
         assert_eq!(parsed.get_payload_bytes()[0], 103);
     }
+
+    #[test]
+    fn rtp_packet_with_negotiated_payload_type() {
+        let mut packet = sample_packet();
+        packet.rtp_header =
+            RtpHeader::new(2, false, false, 0, true, 102, 10, 20, 30, vec![]);
+        let bytes = packet.write_bytes();
+        assert!(RtpPacket::read_bytes(&bytes).is_err());
+        let parsed = RtpPacket::read_h264_bytes(&bytes, 102).expect("parse rtp");
+        assert_eq!(parsed.get_payload_bytes()[0], 103);
+    }
 }
@@ -1,3 +1,4 @@
+use crate::protocols::sdp::media_direction::MediaDirection;
 use crate::protocols::sdp::property_attribute::PropertyAttribute;
 use crate::protocols::sdp::sdp_consts::error_consts::{BOTH_ATTRIBUTE_NONE, BOTH_ATTRIBUTES_SOME};
 use crate::protocols::sdp::sdp_consts::general_consts::{ATTRIBUTE_KEY, EQUAL_SYMBOL};
@@ -65,6 +66,73 @@ impl Attribute {
             _ => None,
         }
     }
+
+    pub fn is_rtcp_mux(&self) -> bool {
+        matches!(self.property_attribute, Some(PropertyAttribute::RtcpMux))
+    }
+
+    /// Returns the direction if this is `a=sendrecv`, `a=sendonly`,
+    /// `a=recvonly` or `a=inactive`.
+    pub fn get_direction(&self) -> Option<MediaDirection> {
+        self.property_attribute
+            .as_ref()
+            .and_then(MediaDirection::from_property)
+    }
+
+    pub fn get_mid(&self) -> Option<String> {
+        match &self.value_attribute {
+            Some(ValueAttribute::Mid(mid)) => Some(mid.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn get_setup(&self) -> Option<String> {
+        match &self.value_attribute {
+            Some(ValueAttribute::Setup(role)) => Some(role.clone()),
+            _ => None,
+        }
+    }
+
+    /// Returns the SSRC declared by an `a=ssrc` line.
+    pub fn get_ssrc(&self) -> Option<u32> {
+        match &self.value_attribute {
+            Some(ValueAttribute::Ssrc { ssrc, .. }) => Some(*ssrc),
+            _ => None,
+        }
+    }
+
+    /// Returns the port of an `a=rtcp` line.
+    pub fn get_rtcp_port(&self) -> Option<u32> {
+        match &self.value_attribute {
+            Some(ValueAttribute::Rtcp { port, .. }) => Some(*port),
+            _ => None,
+        }
+    }
+
+    /// Returns (id, uri) if this is an `a=extmap` line.
+    pub fn get_extmap(&self) -> Option<(u8, String)> {
+        match &self.value_attribute {
+            Some(ValueAttribute::ExtMap { id, uri }) => Some((*id, uri.clone())),
+            _ => None,
+        }
+    }
+
+    /// Returns (payload type, encoding name, clock rate) if this is an rtpmap.
+    pub fn get_rtpmap(&self) -> Option<(u8, String, u64)> {
+        match &self.value_attribute {
+            Some(ValueAttribute::RtpMap {
+                payload_type,
+                encoding_name,
+                clock_rate,
+                ..
+            }) => Some((
+                u8::try_from(*payload_type).ok()?,
+                encoding_name.clone(),
+                *clock_rate,
+            )),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -100,18 +168,27 @@ impl FromStr for Attribute {
             return Err(AttributeError::InvalidAttributeFormat(s.to_string()));
         }
 
-        if let Ok(property_attribute) = PropertyAttribute::from_str(s[2..].trim()) {
+        let raw = s[2..].trim();
+        if let Ok(property_attribute) = PropertyAttribute::from_str(raw) {
             return Ok(Attribute {
                 property_attribute: Some(property_attribute),
                 value_attribute: None,
             });
-        } else if let Ok(value_attribute) = ValueAttribute::from_str(s[2..].trim()) {
-            return Ok(Attribute {
-                property_attribute: None,
-                value_attribute: Some(value_attribute),
-            });
         }
-        Err(AttributeError::InvalidAttributeFormat(s.to_string()))
+        let value_attribute = match ValueAttribute::from_str(raw) {
+            Ok(value_attribute) => value_attribute,
+            // Los navegadores mandan muchos atributos que no usamos; uno
+            // conocido pero mal formado sí es un error
+            Err(AttributeError::InvalidKeyAttribute(_))
+            | Err(AttributeError::InvalidKeyValueFormat(_)) => {
+                ValueAttribute::Unknown(raw.to_string())
+            }
+            Err(_) => return Err(AttributeError::InvalidAttributeFormat(s.to_string())),
+        };
+        Ok(Attribute {
+            property_attribute: None,
+            value_attribute: Some(value_attribute),
+        })
     }
 }
 #[cfg(test)]
@@ -162,6 +239,23 @@ mod tests {
         );
     }
     #[test]
+    fn test_from_str_unknown_attribute_is_kept() {
+        for attribute_str in ["a=ice-options:trickle\n", "a=extmap-allow-mixed\n"] {
+            let attribute = Attribute::from_str(attribute_str).unwrap();
+            assert!(attribute.property_attribute.is_none());
+            assert_eq!(attribute.to_string(), attribute_str);
+        }
+    }
+    #[test]
+    fn test_from_str_malformed_known_attribute_fails() {
+        let attribute_str = "a=ptime:soon";
+        let err = Attribute::from_str(attribute_str).unwrap_err();
+        assert_eq!(
+            AttributeError::InvalidAttributeFormat(attribute_str.to_string()),
+            err
+        );
+    }
+    #[test]
     fn test_display_both_some_() {
         let property = PropertyAttribute::Recvonly;
         let value = ValueAttribute::Cat("hello".to_string());
@@ -0,0 +1,130 @@
+use crate::protocols::sdp::sdp_consts::general_consts::{
+    BANDWIDTH_AS, BANDWIDTH_CT, BANDWIDTH_KEY, BANDWIDTH_TIAS, EQUAL_SYMBOL,
+};
+use crate::protocols::sdp::sdp_error::bandwidth_error::BandwidthError;
+use crate::protocols::sdp::sdp_error::parse_error::ParsingError;
+use std::fmt;
+use std::str::FromStr;
+
+/// Bandwidth line (`b=<bwtype>:<bandwidth>`).
+#[derive(Debug, PartialEq)]
+pub struct Bandwidth {
+    bw_type: String,
+    bandwidth: u64,
+}
+impl Bandwidth {
+    pub fn new(bw_type: String, bandwidth: u64) -> Bandwidth {
+        Bandwidth { bw_type, bandwidth }
+    }
+
+    /// Application specific maximum (`b=AS:<kbps>`).
+    pub fn application_specific(kbps: u64) -> Bandwidth {
+        Bandwidth::new(BANDWIDTH_AS.to_string(), kbps)
+    }
+
+    pub fn bw_type(&self) -> &str {
+        &self.bw_type
+    }
+
+    pub fn bandwidth(&self) -> u64 {
+        self.bandwidth
+    }
+
+    /// Returns the limit in kbps, if the modifier is one we understand.
+    pub fn as_kbps(&self) -> Option<u64> {
+        match self.bw_type.as_str() {
+            BANDWIDTH_AS | BANDWIDTH_CT => Some(self.bandwidth),
+            BANDWIDTH_TIAS => Some(self.bandwidth / 1000),
+            _ => None,
+        }
+    }
+}
+impl fmt::Display for Bandwidth {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{}{}{}:{}",
+            BANDWIDTH_KEY, EQUAL_SYMBOL, self.bw_type, self.bandwidth
+        )
+    }
+}
+impl FromStr for Bandwidth {
+    type Err = BandwidthError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.len() < 2 {
+            return Err(BandwidthError::InvalidBandwidthFormat(s.to_string()));
+        }
+        // Viene del peer remoto: nada de cortar por bytes
+        let (bw_type, value) = s
+            .strip_prefix(format!("{}{}", BANDWIDTH_KEY, EQUAL_SYMBOL).as_str())
+            .ok_or_else(|| BandwidthError::InvalidBandwidthKey(s.chars().take(2).collect()))?
+            .split_once(':')
+            .ok_or_else(|| BandwidthError::InvalidBandwidthFormat(s.to_string()))?;
+        if bw_type.is_empty() {
+            return Err(BandwidthError::InvalidBandwidthFormat(s.to_string()));
+        }
+        let bandwidth = value
+            .parse::<u64>()
+            .map_err(|_| ParsingError::InvalidUint(value.to_string()))?;
+        Ok(Bandwidth::new(bw_type.to_string(), bandwidth))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::sdp::sdp_consts::error_consts::{
+        BANDWIDTH_ERROR, INVALID_BANDWIDTH_FORMAT_ERROR,
+    };
+
+    #[test]
+    fn test_bandwidth_display() {
+        let bandwidth = Bandwidth::application_specific(512);
+        assert_eq!(
+            bandwidth.to_string(),
+            format!("{}{}{}:512\n", BANDWIDTH_KEY, EQUAL_SYMBOL, BANDWIDTH_AS)
+        );
+    }
+    #[test]
+    fn test_bandwidth_from_str_round_trip() {
+        let bandwidth = Bandwidth::from_str("b=AS:512").unwrap();
+        assert_eq!(bandwidth.bw_type(), BANDWIDTH_AS);
+        assert_eq!(bandwidth.bandwidth(), 512);
+        assert_eq!(bandwidth.to_string(), "b=AS:512\n");
+    }
+    #[test]
+    fn test_bandwidth_as_kbps() {
+        assert_eq!(Bandwidth::from_str("b=TIAS:800000").unwrap().as_kbps(), Some(800));
+        assert_eq!(Bandwidth::from_str("b=CT:1000").unwrap().as_kbps(), Some(1000));
+        assert_eq!(Bandwidth::from_str("b=RR:0").unwrap().as_kbps(), None);
+    }
+    #[test]
+    fn test_bandwidth_format_error() {
+        let err = Bandwidth::from_str("b=AS512").unwrap_err();
+        assert_eq!(
+            BandwidthError::InvalidBandwidthFormat("b=AS512".to_string()),
+            err
+        );
+        assert_eq!(
+            format!("{}", err),
+            format!(
+                "{}: \"b=AS512\" {}\n",
+                BANDWIDTH_ERROR, INVALID_BANDWIDTH_FORMAT_ERROR
+            )
+        );
+    }
+    #[test]
+    fn test_bandwidth_key_error_on_multibyte_input() {
+        let err = Bandwidth::from_str("ñb=AS:512").unwrap_err();
+        assert_eq!(BandwidthError::InvalidBandwidthKey("ñb".to_string()), err);
+    }
+    #[test]
+    fn test_bandwidth_value_error() {
+        let err = Bandwidth::from_str("b=AS:fast").unwrap_err();
+        assert_eq!(
+            BandwidthError::BandwidthParseError(ParsingError::InvalidUint("fast".to_string())),
+            err
+        );
+    }
+}
@@ -0,0 +1,156 @@
+use crate::protocols::sdp::address_type::AddressType;
+use crate::protocols::sdp::net_type::NetType;
+use crate::protocols::sdp::sdp_consts::general_consts::{CONNECTION_KEY, EQUAL_SYMBOL};
+use crate::protocols::sdp::sdp_error::connection_info_error::ConnectionInfoError;
+use std::fmt;
+use std::str::FromStr;
+
+/// Connection data line (`c=<nettype> <addrtype> <address>`).
+#[derive(Debug, PartialEq)]
+pub struct ConnectionInfo {
+    net_type: NetType,
+    address_type: AddressType,
+    address: String,
+}
+impl ConnectionInfo {
+    pub fn new(net_type: NetType, address_type: AddressType, address: String) -> ConnectionInfo {
+        ConnectionInfo {
+            net_type,
+            address_type,
+            address,
+        }
+    }
+
+    /// Builds an `IN` connection line, picking IP4/IP6 from the address itself.
+    pub fn from_address(address: &str) -> ConnectionInfo {
+        let address_type = match address.parse::<std::net::IpAddr>() {
+            Ok(std::net::IpAddr::V6(_)) => AddressType::IP6,
+            _ => AddressType::IP4,
+        };
+        ConnectionInfo::new(NetType::In, address_type, address.to_string())
+    }
+
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+
+    pub fn address_type(&self) -> &AddressType {
+        &self.address_type
+    }
+}
+impl fmt::Display for ConnectionInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{}{}{} {} {}",
+            CONNECTION_KEY, EQUAL_SYMBOL, self.net_type, self.address_type, self.address
+        )
+    }
+}
+impl FromStr for ConnectionInfo {
+    type Err = ConnectionInfoError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let vec_connection: Vec<&str> = s.split_whitespace().collect();
+        if vec_connection.len() != 3 || vec_connection[0].len() < 2 {
+            return Err(ConnectionInfoError::InvalidConnectionInfoLength(
+                vec_connection.len(),
+            ));
+        }
+        // Viene del peer remoto: nada de cortar por bytes
+        let net_type_str = vec_connection[0]
+            .strip_prefix(format!("{}{}", CONNECTION_KEY, EQUAL_SYMBOL).as_str())
+            .ok_or_else(|| {
+                ConnectionInfoError::InvalidConnectionInfoKey(s.chars().take(2).collect())
+            })?;
+        let net_type = NetType::from_str(net_type_str)
+            .map_err(ConnectionInfoError::ConnectionInfoNetTypeError)?;
+        let address_type = AddressType::from_str(vec_connection[1])
+            .map_err(ConnectionInfoError::ConnectionInfoAddressTypeError)?;
+        let address = vec_connection[2].to_string();
+        Ok(ConnectionInfo::new(net_type, address_type, address))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::sdp::sdp_consts::error_consts::{
+        CONNECTION_INFO_ERROR, INVALID_CONNECTION_INFO_KEY_ERROR,
+        INVALID_CONNECTION_INFO_LENGTH_ERROR,
+    };
+    use crate::protocols::sdp::sdp_consts::general_consts::{IN_STR, IP4_STR};
+    use crate::protocols::sdp::sdp_error::address_type_error::AddressTypeError;
+
+    #[test]
+    fn test_connection_info_display() {
+        let connection =
+            ConnectionInfo::new(NetType::In, AddressType::IP4, "192.168.0.10".to_string());
+        assert_eq!(
+            connection.to_string(),
+            format!(
+                "{}{}{} {} 192.168.0.10\n",
+                CONNECTION_KEY, EQUAL_SYMBOL, IN_STR, IP4_STR
+            )
+        );
+    }
+    #[test]
+    fn test_connection_info_from_str() {
+        let connection = ConnectionInfo::from_str("c=IN IP6 ::1").unwrap();
+        assert_eq!(connection.net_type, NetType::In);
+        assert_eq!(connection.address_type, AddressType::IP6);
+        assert_eq!(connection.address(), "::1");
+    }
+    #[test]
+    fn test_connection_info_from_address_detects_ip6() {
+        let connection = ConnectionInfo::from_address("fe80::1");
+        assert_eq!(connection.address_type, AddressType::IP6);
+        let connection = ConnectionInfo::from_address("10.0.0.1");
+        assert_eq!(connection.address_type, AddressType::IP4);
+    }
+    #[test]
+    fn test_connection_info_length_error() {
+        let connection_str = "c=IN IP4";
+        let err = ConnectionInfo::from_str(connection_str).unwrap_err();
+        assert_eq!(ConnectionInfoError::InvalidConnectionInfoLength(2), err);
+        assert_eq!(
+            format!("{}", err),
+            format!(
+                "{}: {} \"2\"\n",
+                CONNECTION_INFO_ERROR, INVALID_CONNECTION_INFO_LENGTH_ERROR
+            )
+        );
+    }
+    #[test]
+    fn test_connection_info_key_error() {
+        let err = ConnectionInfo::from_str("x=IN IP4 10.0.0.1").unwrap_err();
+        assert_eq!(
+            ConnectionInfoError::InvalidConnectionInfoKey("x=".to_string()),
+            err
+        );
+        assert_eq!(
+            format!("{}", err),
+            format!(
+                "{}: {} \"{}{}\" \"x=\"\n",
+                CONNECTION_INFO_ERROR, INVALID_CONNECTION_INFO_KEY_ERROR, CONNECTION_KEY, EQUAL_SYMBOL
+            )
+        );
+    }
+    #[test]
+    fn test_connection_info_key_error_on_multibyte_input() {
+        let err = ConnectionInfo::from_str("éc=IN IP4 10.0.0.1").unwrap_err();
+        assert_eq!(
+            ConnectionInfoError::InvalidConnectionInfoKey("éc".to_string()),
+            err
+        );
+    }
+    #[test]
+    fn test_connection_info_address_type_error() {
+        let err = ConnectionInfo::from_str("c=IN IP9 10.0.0.1").unwrap_err();
+        assert_eq!(
+            ConnectionInfoError::ConnectionInfoAddressTypeError(AddressTypeError::InvalidAddrType(
+                "IP9".to_string()
+            )),
+            err
+        );
+    }
+}
@@ -1,3 +1,7 @@
+use crate::protocols::sdp::attribute::{Attribute, CandidateInfo};
+use crate::protocols::sdp::bandwidth::Bandwidth;
+use crate::protocols::sdp::connection_info::ConnectionInfo;
+use crate::protocols::sdp::media_direction::MediaDirection;
 use crate::protocols::sdp::media_type::MediaType;
 
 use crate::protocols::sdp::sdp_consts::general_consts::{EQUAL_SYMBOL, MEDIA_DESCRIPTION_KEY};
@@ -14,6 +18,9 @@ pub struct MediaDescription {
     port: u32,
     transport: TransportProtocol,
     fmt: Vec<u8>,
+    connection: Option<ConnectionInfo>,
+    bandwidth: Option<Bandwidth>,
+    attributes: Vec<Attribute>,
 }
 impl MediaDescription {
     pub fn new(
@@ -27,8 +34,109 @@ impl MediaDescription {
             port,
             transport,
             fmt,
+            connection: None,
+            bandwidth: None,
+            attributes: Vec::new(),
         }
     }
+
+    pub fn media_type(&self) -> MediaType {
+        self.media_type
+    }
+
+    pub fn transport(&self) -> &TransportProtocol {
+        &self.transport
+    }
+
+    pub fn port(&self) -> u32 {
+        self.port
+    }
+
+    pub fn fmt(&self) -> &[u8] {
+        &self.fmt
+    }
+
+    pub fn connection_info(&self) -> Option<&ConnectionInfo> {
+        self.connection.as_ref()
+    }
+
+    pub fn set_connection_info(&mut self, connection: Option<ConnectionInfo>) {
+        self.connection = connection;
+    }
+
+    pub fn bandwidth(&self) -> Option<&Bandwidth> {
+        self.bandwidth.as_ref()
+    }
+
+    pub fn set_bandwidth(&mut self, bandwidth: Option<Bandwidth>) {
+        self.bandwidth = bandwidth;
+    }
+
+    pub fn get_attributes(&self) -> &Vec<Attribute> {
+        &self.attributes
+    }
+
+    pub fn add_attribute(&mut self, attribute: Attribute) {
+        self.attributes.push(attribute);
+    }
+
+    /// Returns the rtpmaps of this section in offer order, as (pt, encoding, clock rate).
+    pub fn get_rtpmaps(&self) -> Vec<(u8, String, u64)> {
+        self.attributes
+            .iter()
+            .filter_map(|attr| attr.get_rtpmap())
+            .collect()
+    }
+
+    /// Media identification tag (`a=mid`) of this section, if any.
+    pub fn mid(&self) -> Option<String> {
+        self.attributes.iter().find_map(|attr| attr.get_mid())
+    }
+
+    /// Direction declared inside this section, if any. Without one the
+    /// session-level attribute applies, see
+    /// [`SessionDescription::media_direction`](crate::protocols::sdp::session_description::SessionDescription::media_direction).
+    pub fn direction(&self) -> Option<MediaDirection> {
+        self.attributes.iter().find_map(|attr| attr.get_direction())
+    }
+
+    /// DTLS role announced with `a=setup`, if any.
+    pub fn setup(&self) -> Option<String> {
+        self.attributes.iter().find_map(|attr| attr.get_setup())
+    }
+
+    /// Candidates announced inside this section.
+    pub fn candidates(&self) -> Vec<CandidateInfo> {
+        self.attributes
+            .iter()
+            .filter_map(|attr| attr.get_candidate())
+            .collect()
+    }
+
+    /// Id mapped to the header extension `uri` with `a=extmap`, if any.
+    pub fn extmap_id(&self, uri: &str) -> Option<u8> {
+        self.attributes
+            .iter()
+            .filter_map(|attr| attr.get_extmap())
+            .find(|(_, extmap_uri)| extmap_uri == uri)
+            .map(|(id, _)| id)
+    }
+
+    /// RTCP port declared with `a=rtcp`, if any.
+    pub fn rtcp_port(&self) -> Option<u32> {
+        self.attributes.iter().find_map(|attr| attr.get_rtcp_port())
+    }
+
+    /// SSRCs announced for this section, without duplicates.
+    pub fn ssrcs(&self) -> Vec<u32> {
+        let mut ssrcs: Vec<u32> = Vec::new();
+        for ssrc in self.attributes.iter().filter_map(|attr| attr.get_ssrc()) {
+            if !ssrcs.contains(&ssrc) {
+                ssrcs.push(ssrc);
+            }
+        }
+        ssrcs
+    }
 }
 
 impl fmt::Display for MediaDescription {
@@ -44,7 +152,17 @@ impl fmt::Display for MediaDescription {
             self.port,
             self.transport,
             fmt_joined,
-        )
+        )?;
+        if let Some(connection) = &self.connection {
+            write!(f, "{}", connection)?;
+        }
+        if let Some(bandwidth) = &self.bandwidth {
+            write!(f, "{}", bandwidth)?;
+        }
+        for attribute in &self.attributes {
+            write!(f, "{}", attribute)?;
+        }
+        Ok(())
     }
 }
 
@@ -81,12 +199,7 @@ impl FromStr for MediaDescription {
                 }
             };
         }
-        Ok(MediaDescription {
-            media_type,
-            port,
-            transport,
-            fmt,
-        })
+        Ok(MediaDescription::new(media_type, port, transport, fmt))
     }
 }
 
@@ -123,6 +236,18 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_media_description_display_with_connection_and_bandwidth() {
+        let mut media_description =
+            MediaDescription::new(MediaType::Video, 9, TransportProtocol::RtpSavp, vec![96]);
+        media_description.set_connection_info(Some(ConnectionInfo::from_address("10.0.0.2")));
+        media_description.set_bandwidth(Some(Bandwidth::application_specific(900)));
+        assert_eq!(
+            media_description.to_string(),
+            "m=video 9 RTP/SAVP 96\nc=IN IP4 10.0.0.2\nb=AS:900\n"
+        );
+    }
+
     #[test]
     fn test_from_str_media_descritpion_ok() -> Result<(), MediaDescriptionError> {
         let media_type_value = MediaType::Video;
@@ -0,0 +1,135 @@
+use crate::protocols::sdp::property_attribute::PropertyAttribute;
+use std::fmt;
+
+/// Direction of a media section (RFC 3264 section 5.1), from the point of
+/// view of whoever wrote the description.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MediaDirection {
+    /// What a section without any direction attribute means.
+    #[default]
+    SendRecv,
+    SendOnly,
+    RecvOnly,
+    Inactive,
+}
+
+impl MediaDirection {
+    pub fn from_flags(sends: bool, receives: bool) -> Self {
+        match (sends, receives) {
+            (true, true) => MediaDirection::SendRecv,
+            (true, false) => MediaDirection::SendOnly,
+            (false, true) => MediaDirection::RecvOnly,
+            (false, false) => MediaDirection::Inactive,
+        }
+    }
+
+    /// Direction attribute this property stands for, if it is one.
+    pub fn from_property(property: &PropertyAttribute) -> Option<Self> {
+        match property {
+            PropertyAttribute::Sendrecv => Some(MediaDirection::SendRecv),
+            PropertyAttribute::SendOnly => Some(MediaDirection::SendOnly),
+            PropertyAttribute::Recvonly => Some(MediaDirection::RecvOnly),
+            PropertyAttribute::Inactive => Some(MediaDirection::Inactive),
+            PropertyAttribute::RtcpMux => None,
+        }
+    }
+
+    pub fn property(self) -> PropertyAttribute {
+        match self {
+            MediaDirection::SendRecv => PropertyAttribute::Sendrecv,
+            MediaDirection::SendOnly => PropertyAttribute::SendOnly,
+            MediaDirection::RecvOnly => PropertyAttribute::Recvonly,
+            MediaDirection::Inactive => PropertyAttribute::Inactive,
+        }
+    }
+
+    pub fn sends(self) -> bool {
+        matches!(self, MediaDirection::SendRecv | MediaDirection::SendOnly)
+    }
+
+    pub fn receives(self) -> bool {
+        matches!(self, MediaDirection::SendRecv | MediaDirection::RecvOnly)
+    }
+
+    /// The same section seen from the other peer: what one sends the other
+    /// receives.
+    pub fn reversed(self) -> Self {
+        Self::from_flags(self.receives(), self.sends())
+    }
+
+    /// What both directions allow.
+    pub fn intersect(self, other: Self) -> Self {
+        Self::from_flags(
+            self.sends() && other.sends(),
+            self.receives() && other.receives(),
+        )
+    }
+}
+
+impl fmt::Display for MediaDirection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.property())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL: [MediaDirection; 4] = [
+        MediaDirection::SendRecv,
+        MediaDirection::SendOnly,
+        MediaDirection::RecvOnly,
+        MediaDirection::Inactive,
+    ];
+
+    #[test]
+    fn test_reversed_swaps_send_and_receive() {
+        assert_eq!(
+            MediaDirection::SendRecv.reversed(),
+            MediaDirection::SendRecv
+        );
+        assert_eq!(
+            MediaDirection::SendOnly.reversed(),
+            MediaDirection::RecvOnly
+        );
+        assert_eq!(
+            MediaDirection::RecvOnly.reversed(),
+            MediaDirection::SendOnly
+        );
+        assert_eq!(
+            MediaDirection::Inactive.reversed(),
+            MediaDirection::Inactive
+        );
+    }
+
+    #[test]
+    fn test_intersect_keeps_what_both_allow() {
+        for direction in ALL {
+            assert_eq!(direction.intersect(MediaDirection::SendRecv), direction);
+            assert_eq!(
+                direction.intersect(MediaDirection::Inactive),
+                MediaDirection::Inactive
+            );
+        }
+        assert_eq!(
+            MediaDirection::SendOnly.intersect(MediaDirection::RecvOnly),
+            MediaDirection::Inactive
+        );
+    }
+
+    #[test]
+    fn test_property_roundtrip() {
+        for direction in ALL {
+            assert_eq!(
+                MediaDirection::from_property(&direction.property()),
+                Some(direction)
+            );
+        }
+        assert_eq!(
+            MediaDirection::from_property(&PropertyAttribute::RtcpMux),
+            None
+        );
+        assert_eq!(MediaDirection::RecvOnly.to_string(), "recvonly");
+    }
+}
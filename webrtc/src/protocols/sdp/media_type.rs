@@ -1,12 +1,13 @@
 use std::fmt;
 use std::str::FromStr;
 
-use crate::protocols::sdp::sdp_consts::general_consts::VIDEO_STR;
+use crate::protocols::sdp::sdp_consts::general_consts::{AUDIO_STR, VIDEO_STR};
 use crate::protocols::sdp::sdp_error::media_type_error::MediaTypeError;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
 pub enum MediaType {
     Video,
+    Audio,
 }
 impl FromStr for MediaType {
     type Err = MediaTypeError;
@@ -14,6 +15,7 @@ impl FromStr for MediaType {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
             VIDEO_STR => Ok(MediaType::Video),
+            AUDIO_STR => Ok(MediaType::Audio),
             not_found => Err(MediaTypeError::InvalidMediaType(not_found.to_string())),
         }
     }
@@ -23,6 +25,7 @@ impl fmt::Display for MediaType {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             MediaType::Video => write!(f, "{}", VIDEO_STR),
+            MediaType::Audio => write!(f, "{}", AUDIO_STR),
         }
     }
 }
@@ -38,6 +41,12 @@ mod tests {
         assert_eq!(video_type, MediaType::Video);
     }
     #[test]
+    fn test_media_type_from_str_audio() {
+        let audio_type = MediaType::from_str(AUDIO_STR).unwrap();
+        assert_eq!(audio_type, MediaType::Audio);
+        assert_eq!(AUDIO_STR, audio_type.to_string());
+    }
+    #[test]
     fn test_display_video() {
         let video_type = MediaType::Video;
         assert_eq!(VIDEO_STR, video_type.to_string());
@@ -1,6 +1,9 @@
 pub mod address_type;
 pub mod attribute;
+pub mod bandwidth;
+pub mod connection_info;
 pub mod media_description;
+pub mod media_direction;
 pub mod media_type;
 pub mod net_type;
 pub mod origin;
@@ -9,6 +12,7 @@ mod sdp_consts;
 pub mod sdp_error;
 pub mod sdp_version;
 pub mod session_description;
+pub mod session_description_builder;
 pub mod time;
 pub mod transport_protocol;
 pub mod value_attribute;
@@ -4,12 +4,14 @@ use crate::protocols::sdp::sdp_consts::general_consts::{EQUAL_SYMBOL, ORIGIN_KEY
 use crate::protocols::sdp::sdp_error::origin_error::OriginError;
 use crate::protocols::sdp::sdp_error::parse_error::ParsingError;
 use std::fmt;
+use std::net::{Ipv4Addr, Ipv6Addr};
 use std::str::FromStr;
 #[derive(Debug)]
 pub struct Origin {
     username: String,
-    session_id: u32,
-    session_version: u32,
+    // Los navegadores usan ids de 64 bits
+    session_id: u64,
+    session_version: u64,
     net_type: NetType,
     address_type: AddressType,
     address: String,
@@ -17,8 +19,8 @@ pub struct Origin {
 impl Origin {
     pub fn new(
         username: String,
-        session_id: u32,
-        session_version: u32,
+        session_id: u64,
+        session_version: u64,
         net_type: NetType,
         address_type: AddressType,
         address: String,
@@ -32,6 +34,22 @@ impl Origin {
             address,
         }
     }
+
+    pub fn username(&self) -> &str {
+        &self.username
+    }
+
+    pub fn session_id(&self) -> u64 {
+        self.session_id
+    }
+
+    pub fn session_version(&self) -> u64 {
+        self.session_version
+    }
+
+    pub fn address(&self) -> &str {
+        &self.address
+    }
 }
 impl fmt::Display for Origin {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -57,20 +75,35 @@ impl FromStr for Origin {
             return Err(OriginError::InvalidOriginLength(vec_origin.len()));
         }
 
-        if s[0..2] != format!("{}{}", ORIGIN_KEY, EQUAL_SYMBOL) {
-            return Err(OriginError::InvalidOriginKey(s[0..2].to_string()));
-        }
-        let username = vec_origin[0][2..].to_string();
+        let key = format!("{}{}", ORIGIN_KEY, EQUAL_SYMBOL);
+        let username = match vec_origin[0].strip_prefix(&key) {
+            Some(username) => username.to_string(),
+            None => {
+                let found: String = vec_origin[0].chars().take(2).collect();
+                return Err(OriginError::InvalidOriginKey(found));
+            }
+        };
         let session_id = vec_origin[1]
-            .parse::<u32>()
+            .parse::<u64>()
             .map_err(|_| ParsingError::InvalidUint(vec_origin[1].to_string()))?;
         let session_version = vec_origin[2]
-            .parse::<u32>()
+            .parse::<u64>()
             .map_err(|_| ParsingError::InvalidUint(vec_origin[2].to_string()))?;
         let net_type = NetType::from_str(vec_origin[3]).map_err(OriginError::OriginNetTypeError)?;
         let addr_type =
             AddressType::from_str(vec_origin[4]).map_err(OriginError::OriginAddressTypeError)?;
-        let address: String = vec_origin[5].to_string();
+        let address = vec_origin[5];
+        let address_ok = match addr_type {
+            AddressType::IP4 => address.parse::<Ipv4Addr>().is_ok(),
+            AddressType::IP6 => address.parse::<Ipv6Addr>().is_ok(),
+        };
+        if !address_ok {
+            return Err(OriginError::InvalidOriginAddress(
+                addr_type,
+                address.to_string(),
+            ));
+        }
+        let address = address.to_string();
         Ok(Origin::new(
             username,
             session_id,
@@ -87,8 +120,8 @@ mod tests {
     use super::*;
     use crate::protocols::sdp::sdp_consts::error_consts::{
         ADDRESS_TYPE_ERROR, INVALID_ADDRESS_TYPE_ERROR, INVALID_NET_TYPE_ERROR,
-        INVALID_ORIGIN_KEY_ERROR, INVALID_ORIGIN_LENGTH_ERROR, INVALID_UINT_ERROR, NET_TYPE_ERROR,
-        ORIGIN_ERROR, PARSING_ERROR,
+        INVALID_ORIGIN_ADDRESS_ERROR, INVALID_ORIGIN_KEY_ERROR, INVALID_ORIGIN_LENGTH_ERROR,
+        INVALID_UINT_ERROR, NET_TYPE_ERROR, ORIGIN_ERROR, PARSING_ERROR,
     };
     use crate::protocols::sdp::sdp_consts::general_consts::{IN_STR, IP4_STR};
     use crate::protocols::sdp::sdp_error::address_type_error::AddressTypeError;
@@ -166,6 +199,14 @@ mod tests {
         );
     }
     #[test]
+    fn test_origin_from_str_with_64_bit_session_id() {
+        let input = "o=- 4611731400430051336 2 IN IP4 127.0.0.1";
+        let origin = Origin::from_str(input).unwrap();
+
+        assert_eq!(origin.session_id(), 4611731400430051336);
+        assert_eq!(origin.session_version(), 2);
+    }
+    #[test]
     fn test_from_str_session_id_error() {
         let origin_str = "o=- as2 123 IN IP4 172.16.2.1";
         let origin_vec: Vec<&str> = origin_str.split_whitespace().collect();
@@ -245,4 +286,32 @@ mod tests {
             )
         )
     }
+    #[test]
+    fn test_from_str_ip6_address_declared_as_ip4() {
+        let origin_str = "o=- 1234 5678 IN IP4 2001:db8::1";
+        let origin_err = Origin::from_str(origin_str).unwrap_err();
+        assert_eq!(
+            OriginError::InvalidOriginAddress(AddressType::IP4, "2001:db8::1".to_string()),
+            origin_err
+        );
+        assert_eq!(
+            format!("{}", origin_err),
+            format!(
+                "{}: \"2001:db8::1\" {} {}\n",
+                ORIGIN_ERROR, INVALID_ORIGIN_ADDRESS_ERROR, IP4_STR
+            )
+        );
+    }
+    #[test]
+    fn test_from_str_address_checks() {
+        let origin = Origin::from_str("o=- 1234 5678 IN IP6 ::1").unwrap();
+        assert_eq!(origin.address(), "::1");
+        assert!(Origin::from_str("o=- 1234 5678 IN IP6 127.0.0.1").is_err());
+        assert!(Origin::from_str("o=- 1234 5678 IN IP4 300.1.1.1").is_err());
+        // A multi-byte key is reported, not sliced in half
+        assert_eq!(
+            Origin::from_str("€=- 1234 5678 IN IP4 127.0.0.1").unwrap_err(),
+            OriginError::InvalidOriginKey("€=".to_string())
+        );
+    }
 }
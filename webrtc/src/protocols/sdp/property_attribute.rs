@@ -1,4 +1,6 @@
-use crate::protocols::sdp::sdp_consts::general_consts::{INACTIVE, RECVONLY, SENDONLY, SENDRECV};
+use crate::protocols::sdp::sdp_consts::general_consts::{
+    INACTIVE, RECVONLY, RTCP_MUX, SENDONLY, SENDRECV,
+};
 use crate::protocols::sdp::sdp_error::attribute_error::AttributeError;
 use std::fmt;
 use std::str::FromStr;
@@ -9,6 +11,8 @@ pub enum PropertyAttribute {
     Sendrecv,
     SendOnly,
     Inactive,
+    /// RTP and RTCP share the same port (RFC 5761).
+    RtcpMux,
 }
 
 impl FromStr for PropertyAttribute {
@@ -19,6 +23,7 @@ impl FromStr for PropertyAttribute {
             SENDRECV => Ok(PropertyAttribute::Sendrecv),
             SENDONLY => Ok(PropertyAttribute::SendOnly),
             INACTIVE => Ok(PropertyAttribute::Inactive),
+            RTCP_MUX => Ok(PropertyAttribute::RtcpMux),
             not_found => Err(AttributeError::InvalidKeyAttribute(not_found.to_string())),
         }
     }
@@ -31,6 +36,7 @@ impl fmt::Display for PropertyAttribute {
             PropertyAttribute::Sendrecv => write!(f, "{}", SENDRECV),
             PropertyAttribute::SendOnly => write!(f, "{}", SENDONLY),
             PropertyAttribute::Inactive => write!(f, "{}", INACTIVE),
+            PropertyAttribute::RtcpMux => write!(f, "{}", RTCP_MUX),
         }
     }
 }
@@ -66,6 +72,12 @@ mod tests {
         assert_eq!(PropertyAttribute::Inactive.to_string(), INACTIVE);
     }
     #[test]
+    fn test_from_str_property_attribute_rtcp_mux() {
+        let property_attribute = PropertyAttribute::from_str(RTCP_MUX).unwrap();
+        assert_eq!(property_attribute, PropertyAttribute::RtcpMux);
+        assert_eq!(PropertyAttribute::RtcpMux.to_string(), RTCP_MUX);
+    }
+    #[test]
     fn test_from_str_property_attribute_error() {
         let property_attribute = PropertyAttribute::from_str("hello").unwrap_err();
         assert_eq!(
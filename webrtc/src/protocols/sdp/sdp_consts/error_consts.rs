@@ -7,11 +7,14 @@ pub const TRANSPORT_PROTOCOL_ERROR: &str = "TransportProtocolError";
 pub const ATTRIBUTE_ERROR: &str = "AttributeError";
 pub const MEDIA_DESCRIPTION_ERROR: &str = "MediaDescriptionError";
 pub const SDP_ERROR: &str = "SdpError";
+pub const CONNECTION_INFO_ERROR: &str = "ConnectionInfoError";
+pub const BANDWIDTH_ERROR: &str = "BandwidthError";
 
 pub const INVALID_NET_TYPE_ERROR: &str = "is not a valid net type";
 pub const INVALID_ADDRESS_TYPE_ERROR: &str = "is not a valid IP type";
 pub const INVALID_ORIGIN_LENGTH_ERROR: &str = "origin must have exactly 6 elements, not";
 pub const INVALID_ORIGIN_KEY_ERROR: &str = "origin key must be";
+pub const INVALID_ORIGIN_ADDRESS_ERROR: &str = "is not a valid address for";
 pub const INVALID_UINT_ERROR: &str = "invalid number";
 pub const INVALID_MEDIA_TYPE_ERROR: &str = "is a invalid media type";
 pub const INVALID_TRANSPORT_PROTOCOL_ERROR: &str = "is a invalid transport protocol error";
@@ -27,3 +30,8 @@ pub const INVALID_SDP_VERSION_FORMAT: &str = "is a invalid SDP version format";
 pub const INVALID_SDP_LENGTH_ERROR: &str = "is a invalid SDP length";
 pub const INVALID_SDP_TIME_FORMAT: &str = "is a invalid SDP time format";
 pub const INVALID_SDP_FORMAT: &str = "is a invalid SDP format";
+pub const INVALID_CONNECTION_INFO_LENGTH_ERROR: &str = "connection must have exactly 3 elements, not";
+pub const INVALID_CONNECTION_INFO_KEY_ERROR: &str = "connection key must be";
+pub const INVALID_BANDWIDTH_KEY_ERROR: &str = "bandwidth key must be";
+pub const INVALID_BANDWIDTH_FORMAT_ERROR: &str = "is a invalid bandwidth format";
+pub const MISSING_SDP_TIME_ERROR: &str = "SDP is missing the time line";
@@ -2,17 +2,23 @@ pub const IN_STR: &str = "IN";
 pub const IP4_STR: &str = "IP4";
 pub const IP6_STR: &str = "IP6";
 pub const VIDEO_STR: &str = "video";
+pub const AUDIO_STR: &str = "audio";
 pub const UDP: &str = "udp";
 pub const RTP_AVP: &str = "RTP/AVP";
 pub const RTP_SAVP: &str = "RTP/SAVP";
+pub const UDP_TLS_RTP_SAVPF: &str = "UDP/TLS/RTP/SAVPF";
 pub const EQUAL_SYMBOL: &str = "=";
 pub const ORIGIN_KEY: &str = "o";
 pub const MEDIA_DESCRIPTION_KEY: &str = "m";
+pub const CONNECTION_KEY: &str = "c";
+pub const BANDWIDTH_KEY: &str = "b";
 pub const ATTRIBUTE_KEY: &str = "a";
 pub const RECVONLY: &str = "recvonly";
 pub const SENDRECV: &str = "sendrecv";
 pub const SENDONLY: &str = "sendonly";
 pub const INACTIVE: &str = "inactive";
+pub const RTCP_MUX: &str = "rtcp-mux";
+pub const RTCP: &str = "rtcp";
 pub const RTPMAP: &str = "rtpmap";
 pub const PTIME: &str = "ptime";
 pub const MAXPTIME: &str = "maxptime";
@@ -25,4 +31,11 @@ pub const ICE_PWD: &str = "ice-pwd";
 // Change for: DTLS Fingerprint
 pub const FINGERPRINT: &str = "fingerprint";
 pub const GROUP: &str = "group";
+pub const MID: &str = "mid";
+pub const SSRC: &str = "ssrc";
+pub const SETUP: &str = "setup";
 pub const MSID_SEMANTIC: &str = "msid-semantic";
+pub const EXTMAP: &str = "extmap";
+pub const BANDWIDTH_AS: &str = "AS";
+pub const BANDWIDTH_CT: &str = "CT";
+pub const BANDWIDTH_TIAS: &str = "TIAS";
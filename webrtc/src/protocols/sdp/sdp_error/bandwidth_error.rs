@@ -0,0 +1,35 @@
+use crate::protocols::sdp::sdp_consts::error_consts::{
+    BANDWIDTH_ERROR, INVALID_BANDWIDTH_FORMAT_ERROR, INVALID_BANDWIDTH_KEY_ERROR,
+};
+use crate::protocols::sdp::sdp_consts::general_consts::{BANDWIDTH_KEY, EQUAL_SYMBOL};
+use crate::protocols::sdp::sdp_error::parse_error::ParsingError;
+use std::fmt;
+
+#[derive(Debug, PartialEq)]
+pub enum BandwidthError {
+    InvalidBandwidthKey(String),
+    InvalidBandwidthFormat(String),
+    BandwidthParseError(ParsingError),
+}
+impl fmt::Display for BandwidthError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BandwidthError::InvalidBandwidthKey(str) => writeln!(
+                f,
+                "{}: {} \"{}{}\" \"{}\"",
+                BANDWIDTH_ERROR, INVALID_BANDWIDTH_KEY_ERROR, BANDWIDTH_KEY, EQUAL_SYMBOL, str
+            ),
+            BandwidthError::InvalidBandwidthFormat(str) => writeln!(
+                f,
+                "{}: \"{}\" {}",
+                BANDWIDTH_ERROR, str, INVALID_BANDWIDTH_FORMAT_ERROR
+            ),
+            BandwidthError::BandwidthParseError(err) => write!(f, "{}", err),
+        }
+    }
+}
+impl From<ParsingError> for BandwidthError {
+    fn from(err: ParsingError) -> Self {
+        BandwidthError::BandwidthParseError(err)
+    }
+}
@@ -0,0 +1,38 @@
+use crate::protocols::sdp::sdp_consts::error_consts::{
+    CONNECTION_INFO_ERROR, INVALID_CONNECTION_INFO_KEY_ERROR, INVALID_CONNECTION_INFO_LENGTH_ERROR,
+};
+use crate::protocols::sdp::sdp_consts::general_consts::{CONNECTION_KEY, EQUAL_SYMBOL};
+use crate::protocols::sdp::sdp_error::address_type_error::AddressTypeError;
+use crate::protocols::sdp::sdp_error::net_type_error::NetTypeError;
+use std::fmt;
+#[derive(Debug, PartialEq)]
+pub enum ConnectionInfoError {
+    InvalidConnectionInfoLength(usize),
+    InvalidConnectionInfoKey(String),
+    ConnectionInfoNetTypeError(NetTypeError),
+    ConnectionInfoAddressTypeError(AddressTypeError),
+}
+impl fmt::Display for ConnectionInfoError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConnectionInfoError::InvalidConnectionInfoLength(n) => writeln!(
+                f,
+                "{}: {} \"{}\"",
+                CONNECTION_INFO_ERROR, INVALID_CONNECTION_INFO_LENGTH_ERROR, n
+            ),
+            ConnectionInfoError::InvalidConnectionInfoKey(str) => writeln!(
+                f,
+                "{}: {} \"{}{}\" \"{}\"",
+                CONNECTION_INFO_ERROR,
+                INVALID_CONNECTION_INFO_KEY_ERROR,
+                CONNECTION_KEY,
+                EQUAL_SYMBOL,
+                str
+            ),
+            ConnectionInfoError::ConnectionInfoNetTypeError(net) => write!(f, "{}", net),
+            ConnectionInfoError::ConnectionInfoAddressTypeError(addr_type) => {
+                write!(f, "{}", addr_type)
+            }
+        }
+    }
+}
@@ -1,6 +1,8 @@
 #![allow(clippy::module_inception)]
 pub mod address_type_error;
 pub mod attribute_error;
+pub mod bandwidth_error;
+pub mod connection_info_error;
 pub mod media_description_error;
 pub mod media_type_error;
 pub mod net_type_error;
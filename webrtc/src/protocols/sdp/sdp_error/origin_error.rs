@@ -1,5 +1,7 @@
+use crate::protocols::sdp::address_type::AddressType;
 use crate::protocols::sdp::sdp_consts::error_consts::{
-    INVALID_ORIGIN_KEY_ERROR, INVALID_ORIGIN_LENGTH_ERROR, ORIGIN_ERROR,
+    INVALID_ORIGIN_ADDRESS_ERROR, INVALID_ORIGIN_KEY_ERROR, INVALID_ORIGIN_LENGTH_ERROR,
+    ORIGIN_ERROR,
 };
 use crate::protocols::sdp::sdp_consts::general_consts::{EQUAL_SYMBOL, ORIGIN_KEY};
 use crate::protocols::sdp::sdp_error::address_type_error::AddressTypeError;
@@ -10,6 +12,8 @@ use std::fmt;
 pub enum OriginError {
     InvalidOriginLength(usize),
     InvalidOriginKey(String),
+    /// The address is not an IP literal of the declared address type.
+    InvalidOriginAddress(AddressType, String),
     OriginParseError(ParsingError),
     OriginNetTypeError(NetTypeError),
     OriginAddressTypeError(AddressTypeError),
@@ -27,6 +31,11 @@ impl fmt::Display for OriginError {
                 "{}: {} \"{}{}\" \"{}\"",
                 ORIGIN_ERROR, INVALID_ORIGIN_KEY_ERROR, ORIGIN_KEY, EQUAL_SYMBOL, str
             ),
+            OriginError::InvalidOriginAddress(addr_type, address) => writeln!(
+                f,
+                "{}: \"{}\" {} {}",
+                ORIGIN_ERROR, address, INVALID_ORIGIN_ADDRESS_ERROR, addr_type
+            ),
             OriginError::OriginParseError(parsing_error) => write!(f, "{}", parsing_error),
             OriginError::OriginNetTypeError(net) => write!(f, "{}", net),
             OriginError::OriginAddressTypeError(addr_type) => write!(f, "{}", addr_type),
@@ -1,8 +1,10 @@
 use crate::protocols::sdp::sdp_consts::error_consts::{
     INVALID_SDP_FORMAT, INVALID_SDP_LENGTH_ERROR, INVALID_SDP_TIME_FORMAT,
-    INVALID_SDP_VERSION_FORMAT, SDP_ERROR,
+    INVALID_SDP_VERSION_FORMAT, MISSING_SDP_TIME_ERROR, SDP_ERROR,
 };
 use crate::protocols::sdp::sdp_error::attribute_error::AttributeError;
+use crate::protocols::sdp::sdp_error::bandwidth_error::BandwidthError;
+use crate::protocols::sdp::sdp_error::connection_info_error::ConnectionInfoError;
 use crate::protocols::sdp::sdp_error::media_description_error::MediaDescriptionError;
 use crate::protocols::sdp::sdp_error::origin_error::OriginError;
 use crate::protocols::sdp::sdp_error::parse_error::ParsingError;
@@ -14,10 +16,13 @@ pub enum SdpError {
     OriginCreationError(OriginError),
     MediaDescriptionCreationError(MediaDescriptionError),
     AttributeCreationError(AttributeError),
+    ConnectionInfoCreationError(ConnectionInfoError),
+    BandwidthCreationError(BandwidthError),
     InvalidSdpVersionFormat(String),
     InvalidSdpFormatLength(usize),
     InvalidSdpTimeFormat(String),
     InvalidSdpFormat(String),
+    MissingSdpTime,
 }
 impl fmt::Display for SdpError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -26,6 +31,8 @@ impl fmt::Display for SdpError {
             SdpError::OriginCreationError(err) => write!(f, "{}", err),
             SdpError::MediaDescriptionCreationError(err) => write!(f, "{}", err),
             SdpError::AttributeCreationError(err) => write!(f, "{}", err),
+            SdpError::ConnectionInfoCreationError(err) => write!(f, "{}", err),
+            SdpError::BandwidthCreationError(err) => write!(f, "{}", err),
             SdpError::InvalidSdpVersionFormat(s) => {
                 writeln!(f, "{}: \"{}\" {}", SDP_ERROR, s, INVALID_SDP_VERSION_FORMAT)
             }
@@ -38,6 +45,7 @@ impl fmt::Display for SdpError {
             SdpError::InvalidSdpFormat(string) => {
                 writeln!(f, "{}: \"{}\" {}", SDP_ERROR, string, INVALID_SDP_FORMAT)
             }
+            SdpError::MissingSdpTime => writeln!(f, "{}: {}", SDP_ERROR, MISSING_SDP_TIME_ERROR),
         }
     }
 }
@@ -1,5 +1,9 @@
 use crate::protocols::sdp::attribute::Attribute;
+use crate::protocols::sdp::bandwidth::Bandwidth;
+use crate::protocols::sdp::connection_info::ConnectionInfo;
 use crate::protocols::sdp::media_description::MediaDescription;
+use crate::protocols::sdp::media_direction::MediaDirection;
+use crate::protocols::sdp::media_type::MediaType;
 use crate::protocols::sdp::origin::Origin;
 use crate::protocols::sdp::sdp_error::sdp_error::SdpError;
 use crate::protocols::sdp::sdp_version::SdpVersion;
@@ -13,6 +17,8 @@ pub struct SessionDescription {
     version: SdpVersion,
     origin: Origin,
     time: Time,
+    connection: Option<ConnectionInfo>,
+    bandwidth: Option<Bandwidth>,
     media_description: Vec<MediaDescription>,
     attributes: Vec<Attribute>,
 }
@@ -29,26 +35,73 @@ impl SessionDescription {
             version,
             origin,
             time,
+            connection: None,
+            bandwidth: None,
             media_description,
             attributes,
         }
     }
 
+    pub fn origin(&self) -> &Origin {
+        &self.origin
+    }
+
+    pub fn connection_info(&self) -> Option<&ConnectionInfo> {
+        self.connection.as_ref()
+    }
+
+    pub fn set_connection_info(&mut self, connection: Option<ConnectionInfo>) {
+        self.connection = connection;
+    }
+
+    pub fn bandwidth(&self) -> Option<&Bandwidth> {
+        self.bandwidth.as_ref()
+    }
+
+    pub fn set_bandwidth(&mut self, bandwidth: Option<Bandwidth>) {
+        self.bandwidth = bandwidth;
+    }
+
+    pub fn get_media_descriptions(&self) -> &Vec<MediaDescription> {
+        &self.media_description
+    }
+
+    /// Bandwidth cap announced by the peer in kbps.
+    /// The session-level `b=` line wins over the media-level ones.
+    pub fn get_bandwidth_kbps(&self) -> Option<u64> {
+        if let Some(kbps) = self.bandwidth.as_ref().and_then(|bw| bw.as_kbps()) {
+            return Some(kbps);
+        }
+        self.media_description
+            .iter()
+            .filter_map(|media| media.bandwidth().and_then(|bw| bw.as_kbps()))
+            .min()
+    }
+
     pub fn get_attributes(&self) -> &Vec<Attribute> {
         &self.attributes
     }
 
+    /// Session-level attributes followed by the attributes of every media section.
+    fn all_attributes(&self) -> impl Iterator<Item = &Attribute> {
+        self.attributes.iter().chain(
+            self.media_description
+                .iter()
+                .flat_map(|media| media.get_attributes().iter()),
+        )
+    }
+
     pub fn get_ice_credentials(&self) -> Result<(String, String), String> {
         let mut ice_ufrag: Option<String> = None;
         let mut ice_pwd = None;
 
-        for attr in &self.attributes {
+        for attr in self.all_attributes() {
             if let Some(ufrag) = attr.get_ice_ufrag() {
-                ice_ufrag = Some(ufrag);
+                ice_ufrag.get_or_insert(ufrag);
             }
 
             if let Some(pwd) = attr.get_ice_pwd() {
-                ice_pwd = Some(pwd);
+                ice_pwd.get_or_insert(pwd);
             }
         }
 
@@ -64,12 +117,17 @@ impl SessionDescription {
 
         let mut candidates = Vec::new();
 
-        for attr in &self.attributes {
+        for attr in self.all_attributes() {
             if let Some(candidate_info) = attr.get_candidate() {
+                // Solo chequeamos por UDP; los navegadores también mandan TCP
+                if !candidate_info.protocol.eq_ignore_ascii_case("udp") {
+                    continue;
+                }
                 let candidate_type = match candidate_info.typ.as_str() {
                     "host" => CandidateType::Host,
                     "srflx" => CandidateType::Srflx,
                     "relay" => CandidateType::Relay,
+                    "prflx" => CandidateType::Prflx,
                     _ => CandidateType::Host,
                 };
 
@@ -86,28 +144,43 @@ impl SessionDescription {
         candidates
     }
 
-    // Devuelve Option<String> con el hash ("AA:BB:CC").
-    /// Busca el fingerprint DTLS en los atributos.
-    pub fn get_fingerprint(&self) -> Option<String> {
-        // 1. Primero buscamos a nivel de sesión (self.attributes)
-        for attr in &self.attributes {
-            if let Some(fp) = attr.get_fingerprint() {
-                return Some(fp);
-            }
+    /// Whether the peer declared `a=rtcp-mux` in any section.
+    pub fn has_rtcp_mux(&self) -> bool {
+        self.all_attributes().any(|attr| attr.is_rtcp_mux())
+    }
+
+    /// Port the peer expects RTCP on for the first `media_type` section:
+    /// the one in `a=rtcp`, the RTP port itself with rtcp-mux, or the RTP
+    /// port + 1 otherwise (RFC 3605).
+    pub fn get_rtcp_port(&self, media_type: MediaType) -> Option<u32> {
+        let media = self
+            .media_description
+            .iter()
+            .find(|media| media.media_type() == media_type)?;
+        if let Some(port) = media.rtcp_port() {
+            return Some(port);
         }
-        /*
-        // WIP: 2. Si no lo encuentro, buscamos dentro de cada Media Description
-        // (Asumiendo que SessionDescription tiene un campo `media_descriptions`)
-        for media in &self.media_descriptions {
-            for attr in &media.attributes {
-                if let Some(fp) = attr.get_fingerprint() {
-                    return Some(fp);
-                }
-            }
+        if self.has_rtcp_mux() {
+            Some(media.port())
+        } else {
+            Some(media.port() + 1)
         }
-        */
+    }
 
-        None
+    /// Direction of `media`: its own attribute, the session-level one, or
+    /// sendrecv when neither says anything (RFC 4566 section 6).
+    pub fn media_direction(&self, media: &MediaDescription) -> MediaDirection {
+        media
+            .direction()
+            .or_else(|| self.attributes.iter().find_map(|attr| attr.get_direction()))
+            .unwrap_or_default()
+    }
+
+    // Devuelve Option<String> con el hash ("AA:BB:CC").
+    /// Busca el fingerprint DTLS en los atributos, primero a nivel de sesión
+    /// y después dentro de cada media description.
+    pub fn get_fingerprint(&self) -> Option<String> {
+        self.all_attributes().find_map(|attr| attr.get_fingerprint())
     }
 }
 
@@ -125,30 +198,71 @@ impl fmt::Display for SessionDescription {
             .map(|attribute_linea| attribute_linea.to_string())
             .collect();
         let attributes_strs = attributes_str_vec.join("");
+        let connection_str = self
+            .connection
+            .as_ref()
+            .map(|connection| connection.to_string())
+            .unwrap_or_default();
+        let bandwidth_str = self
+            .bandwidth
+            .as_ref()
+            .map(|bandwidth| bandwidth.to_string())
+            .unwrap_or_default();
         write!(
             f,
-            "{}{}{}{}{}",
-            self.version, self.origin, self.time, media_description_str, attributes_strs
+            "{}{}{}{}{}{}{}",
+            self.version,
+            self.origin,
+            connection_str,
+            bandwidth_str,
+            self.time,
+            attributes_strs,
+            media_description_str
         )
     }
 }
 impl FromStr for SessionDescription {
     type Err = SdpError;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let vec_sdp: Vec<&str> = s.split('\n').filter(|line| !line.is_empty()).collect();
+        // RFC 4566 pide CRLF; los nuestros van solo con LF
+        let vec_sdp: Vec<&str> = s.lines().filter(|line| !line.is_empty()).collect();
         if vec_sdp.len() < 5 {
             return Err(SdpError::InvalidSdpFormatLength(vec_sdp.len()));
         }
         let version = SdpVersion::from_str(vec_sdp[0])?;
         let origin = Origin::from_str(vec_sdp[1]).map_err(SdpError::OriginCreationError)?;
-        let time = Time::from_str(vec_sdp[2])?;
+        let mut time: Option<Time> = None;
+        let mut connection: Option<ConnectionInfo> = None;
+        let mut bandwidth: Option<Bandwidth> = None;
         let mut vec_media: Vec<MediaDescription> = Vec::new();
         let mut vec_attributes: Vec<Attribute> = Vec::new();
-        for line in &vec_sdp[3..] {
-            if line.len() < 2 {
-                return Err(SdpError::InvalidSdpFormat(line.to_string()));
-            }
-            match &line[0..2] {
+        for line in &vec_sdp[2..] {
+            // `get` y no `[0..2]`: una línea remota puede empezar con un
+            // carácter multibyte
+            match line.get(0..2).unwrap_or_default() {
+                // El nombre de la sesión no se usa
+                "s=" if vec_media.is_empty() => {}
+                "t=" if time.is_none() => {
+                    time = Some(Time::from_str(line)?);
+                }
+                // c=, b= and a= before the first m= are session-level, after it
+                // they belong to the last media section.
+                "c=" => {
+                    let parsed = ConnectionInfo::from_str(line)
+                        .map_err(SdpError::ConnectionInfoCreationError)?;
+                    match vec_media.last_mut() {
+                        Some(media) => media.set_connection_info(Some(parsed)),
+                        None => connection = Some(parsed),
+                    }
+                }
+                "b=" => {
+                    let parsed =
+                        Bandwidth::from_str(line).map_err(SdpError::BandwidthCreationError)?;
+                    match vec_media.last_mut() {
+                        Some(media) => media.set_bandwidth(Some(parsed)),
+                        None => bandwidth = Some(parsed),
+                    }
+                }
                 "m=" => {
                     let media = MediaDescription::from_str(line)
                         .map_err(SdpError::MediaDescriptionCreationError)?;
@@ -157,14 +271,21 @@ impl FromStr for SessionDescription {
                 "a=" => {
                     let attribute =
                         Attribute::from_str(line).map_err(SdpError::AttributeCreationError)?;
-                    vec_attributes.push(attribute);
+                    match vec_media.last_mut() {
+                        Some(media) => media.add_attribute(attribute),
+                        None => vec_attributes.push(attribute),
+                    }
                 }
                 _ => {
                     return Err(SdpError::InvalidSdpFormat(line.to_string()));
                 }
             }
         }
-        Ok(Self::new(version, origin, time, vec_media, vec_attributes))
+        let time = time.ok_or(SdpError::MissingSdpTime)?;
+        let mut session = Self::new(version, origin, time, vec_media, vec_attributes);
+        session.set_connection_info(connection);
+        session.set_bandwidth(bandwidth);
+        Ok(session)
     }
 }
 #[cfg(test)]
@@ -234,6 +355,7 @@ mod tests {
             payload_type: 96,
             encoding_name: "L8".to_string(),
             clock_rate: 8000,
+            encoding_params: None,
         };
         let attribute1: Attribute = Attribute::new(None, Some(rtp_map_attribute));
         let send_only_attribute = SendOnly;
@@ -267,4 +389,78 @@ mod tests {
             )
         );
     }
+    #[test]
+    fn test_from_str_session_connection_and_bandwidth() {
+        let sdp_str = "v=0\no=- 1 1 IN IP4 0.0.0.0\nc=IN IP4 192.168.1.5\nb=AS:512\nt=0\nm=video 9 RTP/SAVP 96\nc=IN IP4 192.168.1.6\nb=AS:300\na=sendrecv\n";
+        let sdp = SessionDescription::from_str(sdp_str).unwrap();
+        assert_eq!(
+            sdp.connection_info().map(|c| c.address()),
+            Some("192.168.1.5")
+        );
+        assert_eq!(sdp.bandwidth().map(|b| b.bandwidth()), Some(512));
+        let media = &sdp.get_media_descriptions()[0];
+        assert_eq!(
+            media.connection_info().map(|c| c.address()),
+            Some("192.168.1.6")
+        );
+        assert_eq!(sdp.get_bandwidth_kbps(), Some(512));
+        assert_eq!(sdp.to_string(), sdp_str);
+    }
+    #[test]
+    fn test_from_str_multibyte_line_is_an_error() {
+        let sdp_str = "v=0\no=- 1 1 IN IP4 0.0.0.0\nt=0\nm=video 9 RTP/SAVP 96\nñ=x\n";
+        let err = SessionDescription::from_str(sdp_str).unwrap_err();
+        assert_eq!(SdpError::InvalidSdpFormat("ñ=x".to_string()), err);
+    }
+    #[test]
+    fn test_bandwidth_kbps_falls_back_to_media_level() {
+        let sdp_str = "v=0\no=- 1 1 IN IP4 0.0.0.0\nt=0\nm=video 9 RTP/SAVP 96\nb=TIAS:600000\na=sendrecv\n";
+        let sdp = SessionDescription::from_str(sdp_str).unwrap();
+        assert!(sdp.bandwidth().is_none());
+        assert_eq!(sdp.get_bandwidth_kbps(), Some(600));
+    }
+    #[test]
+    fn test_rtcp_port_explicit_muxed_or_next_port() {
+        let base = "v=0\no=- 1 1 IN IP4 0.0.0.0\nt=0\nm=audio 5004 RTP/SAVP 111\n";
+        let sdp = SessionDescription::from_str(&format!("{}a=rtcp:6000 IN IP4 10.0.0.1\n", base))
+            .unwrap();
+        assert_eq!(sdp.get_rtcp_port(MediaType::Audio), Some(6000));
+        assert!(sdp.to_string().contains("a=rtcp:6000 IN IP4 10.0.0.1\n"));
+
+        let sdp = SessionDescription::from_str(&format!("{}a=rtcp-mux\n", base)).unwrap();
+        assert_eq!(sdp.get_rtcp_port(MediaType::Audio), Some(5004));
+
+        let sdp = SessionDescription::from_str(&format!("{}a=sendrecv\n", base)).unwrap();
+        assert_eq!(sdp.get_rtcp_port(MediaType::Audio), Some(5005));
+        assert_eq!(sdp.get_rtcp_port(MediaType::Video), None);
+    }
+    #[test]
+    fn test_media_direction_falls_back_to_session_level() {
+        let sdp_str = "v=0\no=- 1 1 IN IP4 0.0.0.0\nt=0\na=recvonly\nm=audio 9 RTP/SAVP 111\nm=video 9 RTP/SAVP 96\na=sendonly\n";
+        let sdp = SessionDescription::from_str(sdp_str).unwrap();
+        let medias = sdp.get_media_descriptions();
+        assert_eq!(medias[0].direction(), None);
+        assert_eq!(sdp.media_direction(&medias[0]), MediaDirection::RecvOnly);
+        assert_eq!(sdp.media_direction(&medias[1]), MediaDirection::SendOnly);
+
+        let sdp_str = "v=0\no=- 1 1 IN IP4 0.0.0.0\nt=0\nm=audio 9 RTP/SAVP 111\na=rtpmap:111 opus/48000/2\n";
+        let sdp = SessionDescription::from_str(sdp_str).unwrap();
+        let media = &sdp.get_media_descriptions()[0];
+        assert_eq!(sdp.media_direction(media), MediaDirection::SendRecv);
+    }
+    #[test]
+    fn test_from_str_missing_time() {
+        let sdp_str = "v=0\no=- 1 1 IN IP4 0.0.0.0\nc=IN IP4 10.0.0.1\nm=video 9 RTP/SAVP 96\na=sendrecv\n";
+        let sdp_err = SessionDescription::from_str(sdp_str).unwrap_err();
+        assert_eq!(SdpError::MissingSdpTime, sdp_err);
+    }
+    #[test]
+    fn test_from_str_accepts_browser_framing() {
+        // CRLF, s= y t= con inicio y fin, como los manda un navegador
+        let sdp_str = "v=0\r\no=- 4611731400430051336 2 IN IP4 127.0.0.1\r\ns=-\r\nt=0 0\r\nm=audio 9 UDP/TLS/RTP/SAVPF 111\r\na=rtcp-mux\r\n";
+        let sdp = SessionDescription::from_str(sdp_str).unwrap();
+        assert_eq!(sdp.origin().session_id(), 4611731400430051336);
+        assert!(sdp.has_rtcp_mux());
+        assert!(sdp.to_string().contains("t=0 0\n"));
+    }
 }
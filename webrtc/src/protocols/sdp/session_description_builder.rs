@@ -0,0 +1,459 @@
+use crate::protocols::sdp::address_type::AddressType;
+use crate::protocols::sdp::attribute::{Attribute, CandidateInfo};
+use crate::protocols::sdp::bandwidth::Bandwidth;
+use crate::protocols::sdp::connection_info::ConnectionInfo;
+use crate::protocols::sdp::media_description::MediaDescription;
+use crate::protocols::sdp::media_direction::MediaDirection;
+use crate::protocols::sdp::media_type::MediaType;
+use crate::protocols::sdp::net_type::NetType;
+use crate::protocols::sdp::origin::Origin;
+use crate::protocols::sdp::property_attribute::PropertyAttribute;
+use crate::protocols::sdp::sdp_version::SdpVersion;
+use crate::protocols::sdp::session_description::SessionDescription;
+use crate::protocols::sdp::time::Time;
+use crate::protocols::sdp::transport_protocol::TransportProtocol;
+use crate::protocols::sdp::value_attribute::ValueAttribute;
+
+const DEFAULT_USERNAME: &str = "-";
+const DEFAULT_ADDRESS: &str = "0.0.0.0";
+const DISCARD_PORT: u32 = 9;
+
+/// Fluent construction of a [`SessionDescription`].
+///
+/// Lines are always emitted in the same order regardless of the order the
+/// methods are called in: group, msid-semantic, ICE credentials, fingerprint,
+/// extra attributes, then each media section.
+#[derive(Debug)]
+pub struct SessionDescriptionBuilder {
+    username: String,
+    session_id: u32,
+    session_version: u32,
+    connection: Option<ConnectionInfo>,
+    bandwidth: Option<Bandwidth>,
+    ice_credentials: Option<(String, String)>,
+    fingerprint: Option<(String, String)>,
+    msid_semantic: bool,
+    attributes: Vec<ValueAttribute>,
+    medias: Vec<MediaBuilder>,
+}
+
+impl Default for SessionDescriptionBuilder {
+    fn default() -> Self {
+        SessionDescriptionBuilder {
+            username: DEFAULT_USERNAME.to_string(),
+            session_id: 0,
+            session_version: 0,
+            connection: None,
+            bandwidth: None,
+            ice_credentials: None,
+            fingerprint: None,
+            msid_semantic: false,
+            attributes: Vec::new(),
+            medias: Vec::new(),
+        }
+    }
+}
+
+impl SessionDescriptionBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the `o=` line. The session version starts equal to the id.
+    pub fn origin(mut self, username: &str, session_id: u32) -> Self {
+        self.username = username.to_string();
+        self.session_id = session_id;
+        self.session_version = session_id;
+        self
+    }
+
+    /// Session-level `c=` line.
+    pub fn connection_address(mut self, address: &str) -> Self {
+        self.connection = Some(ConnectionInfo::from_address(address));
+        self
+    }
+
+    /// Session-level `b=` line.
+    pub fn bandwidth(mut self, bandwidth: Option<Bandwidth>) -> Self {
+        self.bandwidth = bandwidth;
+        self
+    }
+
+    pub fn ice_credentials(mut self, ufrag: &str, pwd: &str) -> Self {
+        self.ice_credentials = Some((ufrag.to_string(), pwd.to_string()));
+        self
+    }
+
+    /// DTLS certificate fingerprint, e.g. `("sha-256", "AB:CD:...")`.
+    pub fn fingerprint(mut self, algorithm: &str, value: &str) -> Self {
+        self.fingerprint = Some((algorithm.to_string(), value.to_string()));
+        self
+    }
+
+    /// Announces `a=msid-semantic:WMS`.
+    pub fn msid_semantic(mut self) -> Self {
+        self.msid_semantic = true;
+        self
+    }
+
+    /// Any other session-level attribute.
+    pub fn attribute(mut self, attribute: ValueAttribute) -> Self {
+        self.attributes.push(attribute);
+        self
+    }
+
+    pub fn add_media(mut self, media: MediaBuilder) -> Self {
+        self.medias.push(media);
+        self
+    }
+
+    /// Builds the description. When media sections carry a mid, they are
+    /// grouped with `a=group:BUNDLE` in declaration order.
+    pub fn build(self) -> SessionDescription {
+        let origin = Origin::new(
+            self.username,
+            self.session_id.into(),
+            self.session_version.into(),
+            NetType::In,
+            AddressType::IP4,
+            DEFAULT_ADDRESS.to_string(),
+        );
+
+        let mut attributes = Vec::new();
+        let mids: Vec<&str> = self
+            .medias
+            .iter()
+            .filter_map(|media| media.mid.as_deref())
+            .collect();
+        if !mids.is_empty() {
+            attributes.push(value(ValueAttribute::Group(format!(
+                "BUNDLE {}",
+                mids.join(" ")
+            ))));
+        }
+        if self.msid_semantic {
+            attributes.push(value(ValueAttribute::MsidSemantic));
+        }
+        if let Some((ufrag, pwd)) = self.ice_credentials {
+            attributes.push(value(ValueAttribute::IceUfrag(ufrag)));
+            attributes.push(value(ValueAttribute::IcePwd(pwd)));
+        }
+        if let Some((algorithm, fingerprint)) = self.fingerprint {
+            attributes.push(value(ValueAttribute::Fingerprint(algorithm, fingerprint)));
+        }
+        attributes.extend(self.attributes.into_iter().map(value));
+
+        let medias = self.medias.into_iter().map(MediaBuilder::build).collect();
+
+        let mut session =
+            SessionDescription::new(SdpVersion::new(0), origin, Time::new(0), medias, attributes);
+        session.set_connection_info(self.connection);
+        session.set_bandwidth(self.bandwidth);
+        session
+    }
+}
+
+/// Fluent construction of one `m=` section.
+///
+/// Attributes are emitted as: rtpmaps, extmaps, mid, direction, ssrc,
+/// rtcp-mux, setup, candidates.
+#[derive(Debug)]
+pub struct MediaBuilder {
+    media_type: MediaType,
+    port: u32,
+    protocol: TransportProtocol,
+    payload_types: Vec<u8>,
+    rtpmaps: Vec<ValueAttribute>,
+    extmaps: Vec<ValueAttribute>,
+    mid: Option<String>,
+    direction: Option<MediaDirection>,
+    ssrc: Option<(u32, String)>,
+    rtcp_mux: bool,
+    setup: Option<String>,
+    candidates: Vec<CandidateInfo>,
+    connection: Option<ConnectionInfo>,
+    bandwidth: Option<Bandwidth>,
+}
+
+impl MediaBuilder {
+    /// Starts a section on the discard port (9) using RTP/SAVP.
+    pub fn new(media_type: MediaType) -> Self {
+        MediaBuilder {
+            media_type,
+            port: DISCARD_PORT,
+            protocol: TransportProtocol::RtpSavp,
+            payload_types: Vec::new(),
+            rtpmaps: Vec::new(),
+            extmaps: Vec::new(),
+            mid: None,
+            direction: None,
+            ssrc: None,
+            rtcp_mux: false,
+            setup: None,
+            candidates: Vec::new(),
+            connection: None,
+            bandwidth: None,
+        }
+    }
+
+    pub fn port(mut self, port: u32) -> Self {
+        self.port = port;
+        self
+    }
+
+    pub fn protocol(mut self, protocol: TransportProtocol) -> Self {
+        self.protocol = protocol;
+        self
+    }
+
+    /// Appends a payload type to the `m=` format list.
+    pub fn payload_type(mut self, payload_type: u8) -> Self {
+        if !self.payload_types.contains(&payload_type) {
+            self.payload_types.push(payload_type);
+        }
+        self
+    }
+
+    /// Adds an `a=rtpmap` and lists its payload type in the `m=` line.
+    pub fn rtpmap(
+        self,
+        payload_type: u8,
+        encoding_name: &str,
+        clock_rate: u64,
+        channels: Option<u64>,
+    ) -> Self {
+        let mut media = self.payload_type(payload_type);
+        media.rtpmaps.push(ValueAttribute::RtpMap {
+            payload_type: u64::from(payload_type),
+            encoding_name: encoding_name.to_string(),
+            clock_rate,
+            encoding_params: channels,
+        });
+        media
+    }
+
+    /// Adds an `a=extmap` mapping `id` to an RTP header extension.
+    pub fn extmap(mut self, id: u8, uri: &str) -> Self {
+        self.extmaps.push(ValueAttribute::ExtMap {
+            id,
+            uri: uri.to_string(),
+        });
+        self
+    }
+
+    pub fn mid(mut self, mid: &str) -> Self {
+        self.mid = Some(mid.to_string());
+        self
+    }
+
+    /// `a=sendrecv`, `a=sendonly`, `a=recvonly` or `a=inactive`. Without it
+    /// the section is sendrecv.
+    pub fn direction(mut self, direction: MediaDirection) -> Self {
+        self.direction = Some(direction);
+        self
+    }
+
+    /// Declares the SSRC of the stream sent in this section.
+    pub fn ssrc(mut self, ssrc: u32, cname: &str) -> Self {
+        self.ssrc = Some((ssrc, cname.to_string()));
+        self
+    }
+
+    pub fn rtcp_mux(mut self, rtcp_mux: bool) -> Self {
+        self.rtcp_mux = rtcp_mux;
+        self
+    }
+
+    /// DTLS role: `actpass`, `active` or `passive`.
+    pub fn setup(mut self, role: &str) -> Self {
+        self.setup = Some(role.to_string());
+        self
+    }
+
+    pub fn candidate(mut self, candidate: CandidateInfo) -> Self {
+        self.candidates.push(candidate);
+        self
+    }
+
+    pub fn connection_address(mut self, address: &str) -> Self {
+        self.connection = Some(ConnectionInfo::from_address(address));
+        self
+    }
+
+    pub fn bandwidth(mut self, bandwidth: Option<Bandwidth>) -> Self {
+        self.bandwidth = bandwidth;
+        self
+    }
+
+    pub fn build(self) -> MediaDescription {
+        let mut media =
+            MediaDescription::new(self.media_type, self.port, self.protocol, self.payload_types);
+        media.set_connection_info(self.connection);
+        media.set_bandwidth(self.bandwidth);
+
+        for rtpmap in self.rtpmaps {
+            media.add_attribute(value(rtpmap));
+        }
+        for extmap in self.extmaps {
+            media.add_attribute(value(extmap));
+        }
+        if let Some(mid) = self.mid {
+            media.add_attribute(value(ValueAttribute::Mid(mid)));
+        }
+        if let Some(direction) = self.direction {
+            media.add_attribute(Attribute::new(Some(direction.property()), None));
+        }
+        if let Some((ssrc, cname)) = self.ssrc {
+            media.add_attribute(value(ValueAttribute::Ssrc {
+                ssrc,
+                attribute: format!("cname:{}", cname),
+            }));
+        }
+        if self.rtcp_mux {
+            media.add_attribute(Attribute::new(Some(PropertyAttribute::RtcpMux), None));
+        }
+        if let Some(role) = self.setup {
+            media.add_attribute(value(ValueAttribute::Setup(role)));
+        }
+        for candidate in self.candidates {
+            media.add_attribute(value(ValueAttribute::Candidate {
+                foundation: candidate.foundation,
+                component: candidate.component,
+                protocol: candidate.protocol,
+                priority: candidate.priority,
+                address: candidate.address,
+                port: candidate.port,
+                typ: candidate.typ,
+            }));
+        }
+        media
+    }
+}
+
+fn value(attribute: ValueAttribute) -> Attribute {
+    Attribute::new(None, Some(attribute))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn host_candidate(port: u32) -> CandidateInfo {
+        CandidateInfo {
+            foundation: 1,
+            component: 1,
+            protocol: "UDP".to_string(),
+            priority: 2130706431,
+            address: "192.168.1.10".to_string(),
+            port,
+            typ: "host".to_string(),
+        }
+    }
+
+    fn full_session() -> SessionDescription {
+        SessionDescriptionBuilder::new()
+            .origin("alice", 42)
+            .connection_address("192.168.1.10")
+            .bandwidth(Some(Bandwidth::application_specific(800)))
+            .msid_semantic()
+            .ice_credentials("ufrag1", "pwd123")
+            .fingerprint("sha-256", "AB:CD:EF")
+            .add_media(
+                MediaBuilder::new(MediaType::Audio)
+                    .protocol(TransportProtocol::UdpTlsRtpSavpf)
+                    .rtpmap(111, "opus", 48000, Some(2))
+                    .mid("audio")
+                    .ssrc(1234, "cname1")
+                    .rtcp_mux(true)
+                    .setup("actpass")
+                    .candidate(host_candidate(50000)),
+            )
+            .add_media(
+                MediaBuilder::new(MediaType::Video)
+                    .rtpmap(96, "H264", 90000, None)
+                    .extmap(3, "urn:example:abs-send-time")
+                    .mid("video")
+                    .direction(MediaDirection::SendOnly)
+                    .ssrc(5678, "cname1"),
+            )
+            .build()
+    }
+
+    #[test]
+    fn test_builder_round_trip_session_fields() {
+        let built = full_session();
+        let parsed = SessionDescription::from_str(&built.to_string()).unwrap();
+
+        assert_eq!(parsed.origin().username(), "alice");
+        assert_eq!(parsed.origin().session_id(), 42);
+        assert_eq!(parsed.origin().session_version(), 42);
+        assert_eq!(
+            parsed.connection_info().map(|c| c.address().to_string()),
+            Some("192.168.1.10".to_string())
+        );
+        assert_eq!(parsed.get_bandwidth_kbps(), Some(800));
+        assert_eq!(
+            parsed.get_ice_credentials().unwrap(),
+            ("ufrag1".to_string(), "pwd123".to_string())
+        );
+        assert_eq!(parsed.get_fingerprint().as_deref(), Some("AB:CD:EF"));
+        assert_eq!(parsed.get_ice_candidates().len(), 1);
+        assert!(parsed.has_rtcp_mux());
+        assert_eq!(parsed.to_string(), built.to_string());
+    }
+
+    #[test]
+    fn test_builder_round_trip_media_fields() {
+        let parsed = SessionDescription::from_str(&full_session().to_string()).unwrap();
+        let medias = parsed.get_media_descriptions();
+        assert_eq!(medias.len(), 2);
+
+        let audio = &medias[0];
+        assert_eq!(audio.media_type(), MediaType::Audio);
+        assert_eq!(audio.port(), DISCARD_PORT);
+        assert_eq!(audio.transport(), &TransportProtocol::UdpTlsRtpSavpf);
+        assert_eq!(audio.fmt(), &[111]);
+        assert_eq!(audio.get_rtpmaps(), vec![(111, "opus".to_string(), 48000)]);
+        assert_eq!(audio.mid().as_deref(), Some("audio"));
+        assert_eq!(audio.ssrcs(), vec![1234]);
+        assert_eq!(audio.direction(), None);
+        assert_eq!(audio.setup().as_deref(), Some("actpass"));
+        assert_eq!(audio.candidates().len(), 1);
+        assert_eq!(audio.candidates()[0].port, 50000);
+
+        let video = &medias[1];
+        assert_eq!(video.media_type(), MediaType::Video);
+        assert_eq!(video.transport(), &TransportProtocol::RtpSavp);
+        assert_eq!(video.fmt(), &[96]);
+        assert_eq!(video.get_rtpmaps(), vec![(96, "H264".to_string(), 90000)]);
+        assert_eq!(video.extmap_id("urn:example:abs-send-time"), Some(3));
+        assert_eq!(audio.extmap_id("urn:example:abs-send-time"), None);
+        assert_eq!(video.mid().as_deref(), Some("video"));
+        assert_eq!(video.ssrcs(), vec![5678]);
+        assert_eq!(video.direction(), Some(MediaDirection::SendOnly));
+        assert!(video.setup().is_none());
+        assert!(video.candidates().is_empty());
+    }
+
+    #[test]
+    fn test_builder_line_order_ignores_call_order() {
+        let session = SessionDescriptionBuilder::new()
+            .fingerprint("sha-256", "AA")
+            .ice_credentials("u", "p")
+            .add_media(
+                MediaBuilder::new(MediaType::Video)
+                    .setup("active")
+                    .mid("video")
+                    .rtpmap(96, "H264", 90000, None),
+            )
+            .build();
+
+        assert_eq!(
+            session.to_string(),
+            "v=0\no=- 0 0 IN IP4 0.0.0.0\nt=0\na=group:BUNDLE video\n\
+             a=ice-ufrag:u\na=ice-pwd:p\na=fingerprint:sha-256 AA\n\
+             m=video 9 RTP/SAVP 96\na=rtpmap:96 H264/90000\na=mid:video\na=setup:active\n"
+        );
+    }
+}
@@ -7,16 +7,21 @@ use std::str::FromStr;
 #[derive(Debug)]
 pub struct Time {
     time: u64,
+    /// Fin de la sesión; los navegadores mandan `t=0 0`.
+    stop: Option<u64>,
 }
 impl Time {
     pub fn new(time: u64) -> Time {
-        Time { time }
+        Time { time, stop: None }
     }
 }
 
 impl fmt::Display for Time {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        writeln!(f, "{}{}{}", TIME_KEY, EQUAL_SYMBOL, self.time)
+        match self.stop {
+            Some(stop) => writeln!(f, "{}{}{} {}", TIME_KEY, EQUAL_SYMBOL, self.time, stop),
+            None => writeln!(f, "{}{}{}", TIME_KEY, EQUAL_SYMBOL, self.time),
+        }
     }
 }
 
@@ -24,7 +29,7 @@ impl FromStr for Time {
     type Err = SdpError;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let vec_time: Vec<&str> = s.split_whitespace().collect();
-        if vec_time.len() != 1 || s.len() < 2 {
+        if vec_time.is_empty() || vec_time.len() > 2 || s.len() < 2 {
             return Err(SdpError::InvalidSdpVersionFormat(s.to_string()));
         }
         if s[0..2] != format!("{}{}", TIME_KEY, EQUAL_SYMBOL) {
@@ -33,6 +38,13 @@ impl FromStr for Time {
         let time = vec_time[0][2..]
             .parse()
             .map_err(|_| ParsingError::InvalidUint(vec_time[0][2..].to_string()))?;
-        Ok(Time { time })
+        let stop = match vec_time.get(1) {
+            Some(stop) => Some(
+                stop.parse()
+                    .map_err(|_| ParsingError::InvalidUint(stop.to_string()))?,
+            ),
+            None => None,
+        };
+        Ok(Time { time, stop })
     }
 }
@@ -1,4 +1,6 @@
-use crate::protocols::sdp::sdp_consts::general_consts::{RTP_AVP, RTP_SAVP, UDP};
+use crate::protocols::sdp::sdp_consts::general_consts::{
+    RTP_AVP, RTP_SAVP, UDP, UDP_TLS_RTP_SAVPF,
+};
 use crate::protocols::sdp::sdp_error::transport_protocol_error::TransportProtocolError;
 use std::fmt;
 use std::str::FromStr;
@@ -8,6 +10,8 @@ pub enum TransportProtocol {
     Udp,
     RtpAvp,
     RtpSavp,
+    /// DTLS-SRTP profile used by WebRTC.
+    UdpTlsRtpSavpf,
 }
 impl FromStr for TransportProtocol {
     type Err = TransportProtocolError;
@@ -16,6 +20,7 @@ impl FromStr for TransportProtocol {
             UDP => Ok(TransportProtocol::Udp),
             RTP_AVP => Ok(TransportProtocol::RtpAvp),
             RTP_SAVP => Ok(TransportProtocol::RtpSavp),
+            UDP_TLS_RTP_SAVPF => Ok(TransportProtocol::UdpTlsRtpSavpf),
             not_found => Err(TransportProtocolError::InvalidTransportProtocol(
                 not_found.to_string(),
             )),
@@ -29,6 +34,7 @@ impl fmt::Display for TransportProtocol {
             TransportProtocol::Udp => write!(f, "{}", UDP),
             TransportProtocol::RtpAvp => write!(f, "{}", RTP_AVP),
             TransportProtocol::RtpSavp => write!(f, "{}", RTP_SAVP),
+            TransportProtocol::UdpTlsRtpSavpf => write!(f, "{}", UDP_TLS_RTP_SAVPF),
         }
     }
 }
@@ -73,4 +79,10 @@ mod tests {
         assert_eq!(RTP_AVP, rtp_avp.to_string());
         assert_eq!(RTP_SAVP, rtp_savp.to_string());
     }
+    #[test]
+    fn test_from_str_udp_tls_rtp_savpf() {
+        let protocol = TransportProtocol::from_str(UDP_TLS_RTP_SAVPF).unwrap();
+        assert_eq!(protocol, TransportProtocol::UdpTlsRtpSavpf);
+        assert_eq!(protocol.to_string(), UDP_TLS_RTP_SAVPF);
+    }
 }
@@ -1,5 +1,6 @@
 use crate::protocols::sdp::sdp_consts::general_consts::{
-    CANDIDATE, CAT, FINGERPRINT, GROUP, ICE_PWD, ICE_UFRAG, MAXPTIME, MSID_SEMANTIC, PTIME, RTPMAP,
+    CANDIDATE, CAT, EXTMAP, FINGERPRINT, GROUP, ICE_PWD, ICE_UFRAG, MAXPTIME, MID, MSID_SEMANTIC,
+    PTIME, RTCP, RTPMAP, SETUP, SSRC,
 };
 use crate::protocols::sdp::sdp_error::attribute_error::AttributeError;
 use crate::protocols::sdp::sdp_error::parse_error::ParsingError;
@@ -12,6 +13,7 @@ pub enum ValueAttribute {
         payload_type: u64,
         encoding_name: String,
         clock_rate: u64,
+        encoding_params: Option<u64>,
     },
     PTime(u64),
     MaxPtime(u64),
@@ -30,7 +32,29 @@ pub enum ValueAttribute {
     },
     Fingerprint(String, String), // Acá le pongo (hash function, fp)
     Group(String),
+    Mid(String),
+    /// DTLS role: `actpass`, `active` or `passive`.
+    Setup(String),
+    /// `a=ssrc:<ssrc> <attribute>`, e.g. `a=ssrc:1234 cname:abc`.
+    Ssrc {
+        ssrc: u32,
+        attribute: String,
+    },
     MsidSemantic,
+    /// `a=extmap:<id> <uri>`, an RTP header extension and its id.
+    ExtMap {
+        id: u8,
+        uri: String,
+    },
+    /// `a=rtcp:<port> [<nettype> <addrtype> <address>]` (RFC 3605), where
+    /// RTCP goes when it is not muxed with RTP.
+    Rtcp {
+        port: u32,
+        address: Option<String>,
+    },
+    /// An attribute we do not use (`a=ice-options:trickle`, `a=rtcp-fb:...`),
+    /// kept verbatim; RFC 4566 says to ignore them.
+    Unknown(String),
 }
 
 impl FromStr for ValueAttribute {
@@ -65,11 +89,21 @@ impl FromStr for ValueAttribute {
 
             GROUP => Ok(ValueAttribute::Group(value.to_string())),
 
+            MID => Ok(ValueAttribute::Mid(value.to_string())),
+
+            SSRC => from_str_ssrc(value),
+
+            SETUP => Ok(ValueAttribute::Setup(value.to_string())),
+
             MSID_SEMANTIC => {
                 // El valor "WMS" es estándar, así que no necesitamos almacenarlo.
                 Ok(ValueAttribute::MsidSemantic)
             }
 
+            EXTMAP => from_str_extmap(value),
+
+            RTCP => from_str_rtcp(value),
+
             _ => Err(AttributeError::InvalidKeyAttribute(key.to_string())),
         }
     }
@@ -82,11 +116,18 @@ impl fmt::Display for ValueAttribute {
                 payload_type,
                 encoding_name,
                 clock_rate,
-            } => write!(
-                f,
-                "{}:{} {}/{}",
-                RTPMAP, payload_type, encoding_name, clock_rate
-            ),
+                encoding_params,
+            } => {
+                write!(
+                    f,
+                    "{}:{} {}/{}",
+                    RTPMAP, payload_type, encoding_name, clock_rate
+                )?;
+                if let Some(params) = encoding_params {
+                    write!(f, "/{}", params)?;
+                }
+                Ok(())
+            }
             ValueAttribute::PTime(time) => write!(f, "{}:{}", PTIME, time),
             ValueAttribute::MaxPtime(time) => write!(f, "{}:{}", MAXPTIME, time),
             ValueAttribute::Cat(value) => write!(f, "{}:{}", CAT, value),
@@ -110,8 +151,22 @@ impl fmt::Display for ValueAttribute {
                 write!(f, "{}:{} {}",FINGERPRINT, hash_func, hash_value)
             }
             ValueAttribute::Group(value) => write!(f, "{}:{}", GROUP, value),
+            ValueAttribute::Mid(mid) => write!(f, "{}:{}", MID, mid),
+            ValueAttribute::Setup(role) => write!(f, "{}:{}", SETUP, role),
+            ValueAttribute::Ssrc { ssrc, attribute } => {
+                write!(f, "{}:{} {}", SSRC, ssrc, attribute)
+            }
             // WMS is the default value
             ValueAttribute::MsidSemantic => write!(f, "{}:WMS", MSID_SEMANTIC),
+            ValueAttribute::ExtMap { id, uri } => write!(f, "{}:{} {}", EXTMAP, id, uri),
+            ValueAttribute::Rtcp { port, address } => {
+                write!(f, "{}:{}", RTCP, port)?;
+                if let Some(address) = address {
+                    write!(f, " {}", address)?;
+                }
+                Ok(())
+            }
+            ValueAttribute::Unknown(raw) => write!(f, "{}", raw),
         }
     }
 }
@@ -169,18 +224,28 @@ fn from_str_rtpmap(value: &str) -> Result<ValueAttribute, AttributeError> {
     let payload_type = vec_value[0]
         .parse::<u64>()
         .map_err(|_| ParsingError::InvalidUint(vec_value[0].to_string()))?;
+    // encoding/clock_rate con un tercer campo opcional (canales en audio).
     let encoding_vector: Vec<&str> = vec_value[1].split('/').collect();
-    if encoding_vector.len() != 2 {
+    if encoding_vector.len() != 2 && encoding_vector.len() != 3 {
         return Err(AttributeError::InvalidValueFormat(value.to_string()));
     }
     let encoding_name = encoding_vector[0].to_string();
     let clock_rate = encoding_vector[1]
         .parse::<u64>()
         .map_err(|_| ParsingError::InvalidUint(encoding_vector[1].to_string()))?;
+    let encoding_params = match encoding_vector.get(2) {
+        Some(params) => Some(
+            params
+                .parse::<u64>()
+                .map_err(|_| ParsingError::InvalidUint(params.to_string()))?,
+        ),
+        None => None,
+    };
     Ok(ValueAttribute::RtpMap {
         payload_type,
         encoding_name,
         clock_rate,
+        encoding_params,
     })
 }
 
@@ -198,6 +263,51 @@ fn from_str_fingerprint(value: &str) -> Result<ValueAttribute, AttributeError> {
 
     Ok(ValueAttribute::Fingerprint(hash_func, fingerprint))
 }
+fn from_str_ssrc(value: &str) -> Result<ValueAttribute, AttributeError> {
+    // format: 1234 cname:abc
+    let (ssrc, attribute) = value
+        .split_once(' ')
+        .ok_or(AttributeError::InvalidValueFormat(value.to_string()))?;
+
+    let ssrc = ssrc
+        .parse::<u32>()
+        .map_err(|_| ParsingError::InvalidUint(ssrc.to_string()))?;
+
+    Ok(ValueAttribute::Ssrc {
+        ssrc,
+        attribute: attribute.trim().to_string(),
+    })
+}
+fn from_str_extmap(value: &str) -> Result<ValueAttribute, AttributeError> {
+    // format: 3 http://www.webrtc.org/experiments/rtp-hdrext/abs-send-time
+    let (id, uri) = value
+        .split_once(' ')
+        .ok_or(AttributeError::InvalidValueFormat(value.to_string()))?;
+
+    // El id puede traer dirección ("3/sendrecv"), no la usamos
+    let id = id.split('/').next().unwrap_or(id);
+    let id = id
+        .parse::<u8>()
+        .map_err(|_| ParsingError::InvalidUint(id.to_string()))?;
+
+    Ok(ValueAttribute::ExtMap {
+        id,
+        uri: uri.trim().to_string(),
+    })
+}
+fn from_str_rtcp(value: &str) -> Result<ValueAttribute, AttributeError> {
+    // format: 9 IN IP4 0.0.0.0 (la dirección es opcional)
+    let (port, address) = match value.split_once(' ') {
+        Some((port, address)) => (port, Some(address.trim().to_string())),
+        None => (value, None),
+    };
+    let port = port
+        .parse::<u32>()
+        .map_err(|_| ParsingError::InvalidUint(port.to_string()))?;
+
+    Ok(ValueAttribute::Rtcp { port, address })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -217,10 +327,26 @@ mod tests {
             payload_type: 96,
             encoding_name: "L8".to_string(),
             clock_rate: 8000,
+            encoding_params: None,
         };
         assert_eq!(display.to_string(), format!("{}:96 L8/8000", RTPMAP));
     }
     #[test]
+    fn test_from_str_rtpmap_with_channels_ok() {
+        let string_value = format!("{}:111 opus/48000/2", RTPMAP);
+        let rtpmap_value = ValueAttribute::from_str(&string_value).unwrap();
+        assert!(matches!(
+            rtpmap_value,
+            ValueAttribute::RtpMap {
+                payload_type: 111,
+                clock_rate: 48000,
+                encoding_params: Some(2),
+                ..
+            }
+        ));
+        assert_eq!(rtpmap_value.to_string(), string_value);
+    }
+    #[test]
     fn test_from_str_rtpmap_invalid_value_format_length_err() {
         let string_value = format!("{}:96", RTPMAP);
         let rtpmap_err = ValueAttribute::from_str(&string_value).unwrap_err();
@@ -363,6 +489,91 @@ mod tests {
         assert_eq!(display.to_string(), string_value);
     }
     #[test]
+    fn test_from_str_mid_ok() {
+        let string_value = format!("{}:audio", MID);
+        let mid_value = ValueAttribute::from_str(&string_value).unwrap();
+        assert!(matches!(&mid_value, ValueAttribute::Mid(mid) if mid == "audio"));
+        assert_eq!(mid_value.to_string(), string_value);
+    }
+    #[test]
+    fn test_from_str_ssrc_ok() {
+        let string_value = format!("{}:3735928559 cname:room", SSRC);
+        let ssrc_value = ValueAttribute::from_str(&string_value).unwrap();
+        assert!(matches!(
+            &ssrc_value,
+            ValueAttribute::Ssrc { ssrc: 3735928559, attribute } if attribute == "cname:room"
+        ));
+        assert_eq!(ssrc_value.to_string(), string_value);
+    }
+    #[test]
+    fn test_from_str_ssrc_invalid_value_format_err() {
+        let value = "1234";
+        let string_value = format!("{}:{}", SSRC, value);
+        let ssrc_error = ValueAttribute::from_str(&string_value).unwrap_err();
+        assert_eq!(
+            AttributeError::InvalidValueFormat(value.to_string()),
+            ssrc_error
+        );
+    }
+    #[test]
+    fn test_from_str_extmap_ok() {
+        let string_value = format!(
+            "{}:3 http://www.webrtc.org/experiments/rtp-hdrext/abs-send-time",
+            EXTMAP
+        );
+        let extmap_value = ValueAttribute::from_str(&string_value).unwrap();
+        assert!(matches!(
+            &extmap_value,
+            ValueAttribute::ExtMap { id: 3, uri } if uri.ends_with("abs-send-time")
+        ));
+        assert_eq!(extmap_value.to_string(), string_value);
+    }
+    #[test]
+    fn test_from_str_extmap_with_direction_ok() {
+        let string_value = format!("{}:5/recvonly urn:example", EXTMAP);
+        let extmap_value = ValueAttribute::from_str(&string_value).unwrap();
+        assert!(matches!(extmap_value, ValueAttribute::ExtMap { id: 5, .. }));
+    }
+    #[test]
+    fn test_from_str_extmap_invalid_value_format_err() {
+        let string_value = format!("{}:3", EXTMAP);
+        let extmap_error = ValueAttribute::from_str(&string_value).unwrap_err();
+        assert_eq!(
+            AttributeError::InvalidValueFormat("3".to_string()),
+            extmap_error
+        );
+    }
+    #[test]
+    fn test_from_str_rtcp_ok() {
+        let string_value = format!("{}:53020", RTCP);
+        let rtcp_value = ValueAttribute::from_str(&string_value).unwrap();
+        assert!(matches!(
+            rtcp_value,
+            ValueAttribute::Rtcp {
+                port: 53020,
+                address: None
+            }
+        ));
+        assert_eq!(rtcp_value.to_string(), string_value);
+
+        let string_value = format!("{}:9 IN IP4 0.0.0.0", RTCP);
+        let rtcp_value = ValueAttribute::from_str(&string_value).unwrap();
+        assert!(matches!(
+            &rtcp_value,
+            ValueAttribute::Rtcp { port: 9, address: Some(address) } if address == "IN IP4 0.0.0.0"
+        ));
+        assert_eq!(rtcp_value.to_string(), string_value);
+    }
+    #[test]
+    fn test_from_str_rtcp_invalid_port_err() {
+        let string_value = format!("{}:port IN IP4 0.0.0.0", RTCP);
+        let rtcp_error = ValueAttribute::from_str(&string_value).unwrap_err();
+        assert_eq!(
+            AttributeError::AttributeParseError(ParsingError::InvalidUint("port".to_string())),
+            rtcp_error
+        );
+    }
+    #[test]
     fn test_from_str_invalid_key_attribute_error() {
         let key = "top";
         let value = "hello";
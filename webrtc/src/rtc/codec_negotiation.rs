@@ -0,0 +1,409 @@
+//! Payload type and direction negotiation for the audio and video m-lines.
+
+use crate::protocols::rtp::constants::rtp_const::{RTP_H264_TYPE, RTP_OPUS_TYPE};
+use crate::protocols::rtp::header_extension::{
+    ABS_SEND_TIME_ID, ABS_SEND_TIME_URI, AUDIO_LATENCY_ID, AUDIO_LATENCY_URI,
+};
+use crate::protocols::sdp::media_direction::MediaDirection;
+use crate::protocols::sdp::media_type::MediaType;
+use crate::protocols::sdp::session_description::SessionDescription;
+
+use super::peer_connection_error::PeerConnectionError;
+
+/// A codec supported by this implementation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CodecSpec {
+    pub media_type: MediaType,
+    pub encoding_name: &'static str,
+    pub clock_rate: u64,
+    /// Channel count announced in the rtpmap (audio only).
+    pub channels: Option<u64>,
+    /// Payload type used when we are the offerer.
+    pub default_pt: u8,
+}
+
+pub const H264_CODEC: CodecSpec = CodecSpec {
+    media_type: MediaType::Video,
+    encoding_name: "H264",
+    clock_rate: 90000,
+    channels: None,
+    default_pt: RTP_H264_TYPE,
+};
+
+pub const OPUS_CODEC: CodecSpec = CodecSpec {
+    media_type: MediaType::Audio,
+    encoding_name: "opus",
+    clock_rate: 48000,
+    channels: Some(2),
+    default_pt: RTP_OPUS_TYPE,
+};
+
+/// Supported codecs in preference order.
+pub const SUPPORTED_CODECS: [CodecSpec; 2] = [OPUS_CODEC, H264_CODEC];
+
+/// Direction of each media section, from our side.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct MediaDirections {
+    pub audio: MediaDirection,
+    pub video: MediaDirection,
+}
+
+impl MediaDirections {
+    pub fn get(&self, media_type: MediaType) -> MediaDirection {
+        match media_type {
+            MediaType::Audio => self.audio,
+            MediaType::Video => self.video,
+        }
+    }
+
+    pub fn set(&mut self, media_type: MediaType, direction: MediaDirection) {
+        match media_type {
+            MediaType::Audio => self.audio = direction,
+            MediaType::Video => self.video = direction,
+        }
+    }
+}
+
+/// Payload types agreed for each media section.
+///
+/// A section without a common codec (or with port 0 in the remote SDP) is
+/// rejected: it keeps the default payload type but no media flows on it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NegotiatedCodecs {
+    pub audio_pt: u8,
+    pub video_pt: u8,
+    pub audio_accepted: bool,
+    pub video_accepted: bool,
+    /// `a=extmap` id of abs-send-time on the video section, if both sides
+    /// support it.
+    pub abs_send_time_id: Option<u8>,
+    /// `a=extmap` id of the latency probe on the audio section, if both
+    /// sides support it.
+    pub audio_latency_id: Option<u8>,
+    /// What we send and receive on each section.
+    pub directions: MediaDirections,
+}
+
+impl Default for NegotiatedCodecs {
+    fn default() -> Self {
+        NegotiatedCodecs {
+            audio_pt: OPUS_CODEC.default_pt,
+            video_pt: H264_CODEC.default_pt,
+            audio_accepted: true,
+            video_accepted: true,
+            abs_send_time_id: Some(ABS_SEND_TIME_ID),
+            audio_latency_id: Some(AUDIO_LATENCY_ID),
+            directions: MediaDirections::default(),
+        }
+    }
+}
+
+impl NegotiatedCodecs {
+    /// Payload type to use for the given media.
+    pub fn payload_type(&self, media_type: MediaType) -> u8 {
+        match media_type {
+            MediaType::Audio => self.audio_pt,
+            MediaType::Video => self.video_pt,
+        }
+    }
+
+    /// Whether the given media was negotiated (not rejected with port 0).
+    pub fn is_accepted(&self, media_type: MediaType) -> bool {
+        match media_type {
+            MediaType::Audio => self.audio_accepted,
+            MediaType::Video => self.video_accepted,
+        }
+    }
+
+    pub fn direction(&self, media_type: MediaType) -> MediaDirection {
+        self.directions.get(media_type)
+    }
+
+    /// Whether we capture and send the given media.
+    pub fn sends(&self, media_type: MediaType) -> bool {
+        self.is_accepted(media_type) && self.direction(media_type).sends()
+    }
+
+    /// Whether the remote sends us the given media.
+    pub fn receives(&self, media_type: MediaType) -> bool {
+        self.is_accepted(media_type) && self.direction(media_type).receives()
+    }
+}
+
+/// Intersects the remote rtpmaps with our supported codecs.
+///
+/// For each media type the first payload type of the remote m-line that maps
+/// to a supported codec wins, keeping the remote numbering. A media without a
+/// common codec is marked as rejected; only when nothing is left to negotiate
+/// it fails with [`PeerConnectionError::NoCommonCodec`]. abs-send-time and the
+/// latency probe are used with the id of the remote `a=extmap` on their
+/// section, if it declares one.
+///
+/// Each section goes in the reverse direction of the remote one, limited to
+/// the `wanted` one: a sendonly offer gets a recvonly answer, and what we do
+/// not want to send is not sent even if the remote would receive it.
+pub fn negotiate_codecs(
+    remote: &SessionDescription,
+    wanted: MediaDirections,
+) -> Result<NegotiatedCodecs, PeerConnectionError> {
+    let audio = select_payload_type(remote, MediaType::Audio);
+    let video = select_payload_type(remote, MediaType::Video);
+    if audio.is_none() && video.is_none() {
+        return Err(PeerConnectionError::NoCommonCodec(format!(
+            "{} and {}",
+            MediaType::Audio,
+            MediaType::Video
+        )));
+    }
+
+    Ok(NegotiatedCodecs {
+        audio_pt: audio.unwrap_or(OPUS_CODEC.default_pt),
+        video_pt: video.unwrap_or(H264_CODEC.default_pt),
+        audio_accepted: audio.is_some(),
+        video_accepted: video.is_some(),
+        abs_send_time_id: video
+            .and_then(|_| remote_extmap_id(remote, MediaType::Video, ABS_SEND_TIME_URI)),
+        audio_latency_id: audio
+            .and_then(|_| remote_extmap_id(remote, MediaType::Audio, AUDIO_LATENCY_URI)),
+        directions: MediaDirections {
+            audio: negotiate_direction(remote, MediaType::Audio, wanted.audio),
+            video: negotiate_direction(remote, MediaType::Video, wanted.video),
+        },
+    })
+}
+
+fn negotiate_direction(
+    remote: &SessionDescription,
+    media_type: MediaType,
+    wanted: MediaDirection,
+) -> MediaDirection {
+    let remote_direction = remote
+        .get_media_descriptions()
+        .iter()
+        .find(|media| media.media_type() == media_type && media.port() != 0)
+        .map(|media| remote.media_direction(media))
+        .unwrap_or_default();
+    wanted.intersect(remote_direction.reversed())
+}
+
+fn remote_extmap_id(remote: &SessionDescription, media_type: MediaType, uri: &str) -> Option<u8> {
+    remote
+        .get_media_descriptions()
+        .iter()
+        .filter(|media| media.media_type() == media_type && media.port() != 0)
+        .find_map(|media| media.extmap_id(uri))
+}
+
+fn select_payload_type(remote: &SessionDescription, media_type: MediaType) -> Option<u8> {
+    let supported: Vec<&CodecSpec> = SUPPORTED_CODECS
+        .iter()
+        .filter(|codec| codec.media_type == media_type)
+        .collect();
+
+    for media in remote
+        .get_media_descriptions()
+        .iter()
+        .filter(|media| media.media_type() == media_type && media.port() != 0)
+    {
+        let rtpmaps = media.get_rtpmaps();
+        // El orden del m= indica la preferencia del remoto.
+        for pt in media.fmt() {
+            let Some((_, encoding_name, clock_rate)) =
+                rtpmaps.iter().find(|(map_pt, _, _)| map_pt == pt)
+            else {
+                continue;
+            };
+            let matches = supported.iter().any(|codec| {
+                codec.encoding_name.eq_ignore_ascii_case(encoding_name)
+                    && codec.clock_rate == *clock_rate
+            });
+            if matches {
+                return Some(*pt);
+            }
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn sdp_with_medias(medias: &str) -> SessionDescription {
+        let sdp = format!("v=0\no=- 1 1 IN IP4 0.0.0.0\nt=0\n{}", medias);
+        SessionDescription::from_str(&sdp).unwrap()
+    }
+
+    #[test]
+    fn test_negotiate_default_payload_types() {
+        let remote = sdp_with_medias(&format!(
+            "m=audio 9 RTP/SAVP 111\na=rtpmap:111 opus/48000/2\na=extmap:{} {}\nm=video 9 RTP/SAVP 96\na=rtpmap:96 H264/90000\na=extmap:{} {}\n",
+            AUDIO_LATENCY_ID, AUDIO_LATENCY_URI, ABS_SEND_TIME_ID, ABS_SEND_TIME_URI
+        ));
+        let codecs = negotiate_codecs(&remote, MediaDirections::default()).unwrap();
+        assert_eq!(codecs, NegotiatedCodecs::default());
+    }
+
+    #[test]
+    fn test_negotiate_remapped_opus_payload_type() {
+        let remote = sdp_with_medias(
+            "m=audio 9 RTP/SAVP 109\na=rtpmap:109 opus/48000/2\nm=video 9 RTP/SAVP 102\na=rtpmap:102 H264/90000\n",
+        );
+        let codecs = negotiate_codecs(&remote, MediaDirections::default()).unwrap();
+        assert_eq!(codecs.audio_pt, 109);
+        assert_eq!(codecs.video_pt, 102);
+    }
+
+    #[test]
+    fn test_negotiate_picks_first_supported_in_remote_order() {
+        let remote = sdp_with_medias(
+            "m=audio 9 RTP/SAVP 0 109\na=rtpmap:0 PCMU/8000\na=rtpmap:109 opus/48000/2\nm=video 9 RTP/SAVP 98 97\na=rtpmap:97 H264/90000\na=rtpmap:98 VP8/90000\n",
+        );
+        let codecs = negotiate_codecs(&remote, MediaDirections::default()).unwrap();
+        assert_eq!(codecs.audio_pt, 109);
+        assert_eq!(codecs.video_pt, 97);
+    }
+
+    #[test]
+    fn test_negotiate_rejects_media_without_common_codec() {
+        let remote = sdp_with_medias(
+            "m=audio 9 RTP/SAVP 111\na=rtpmap:111 opus/48000/2\nm=video 9 RTP/SAVP 98\na=rtpmap:98 VP8/90000\n",
+        );
+        let codecs = negotiate_codecs(&remote, MediaDirections::default()).unwrap();
+        assert!(codecs.is_accepted(MediaType::Audio));
+        assert!(!codecs.is_accepted(MediaType::Video));
+    }
+
+    #[test]
+    fn test_negotiate_rejects_media_with_port_zero() {
+        let remote = sdp_with_medias(
+            "m=audio 9 RTP/SAVP 111\na=rtpmap:111 opus/48000/2\nm=video 0 RTP/SAVP 96\na=rtpmap:96 H264/90000\n",
+        );
+        let codecs = negotiate_codecs(&remote, MediaDirections::default()).unwrap();
+        assert!(codecs.audio_accepted);
+        assert!(!codecs.video_accepted);
+    }
+
+    #[test]
+    fn test_negotiate_echoes_remote_abs_send_time_id() {
+        let remote = sdp_with_medias(&format!(
+            "m=audio 9 RTP/SAVP 111\na=rtpmap:111 opus/48000/2\nm=video 9 RTP/SAVP 96\na=rtpmap:96 H264/90000\na=extmap:7 {}\n",
+            ABS_SEND_TIME_URI
+        ));
+        assert_eq!(
+            negotiate_codecs(&remote, MediaDirections::default())
+                .unwrap()
+                .abs_send_time_id,
+            Some(7)
+        );
+    }
+
+    #[test]
+    fn test_negotiate_without_remote_extmap_disables_abs_send_time() {
+        let remote = sdp_with_medias(
+            "m=audio 9 RTP/SAVP 111\na=rtpmap:111 opus/48000/2\nm=video 9 RTP/SAVP 96\na=rtpmap:96 H264/90000\n",
+        );
+        let codecs = negotiate_codecs(&remote, MediaDirections::default()).unwrap();
+        assert_eq!(codecs.abs_send_time_id, None);
+        assert_eq!(codecs.audio_latency_id, None);
+    }
+
+    #[test]
+    fn test_latency_probe_is_only_taken_from_the_audio_section() {
+        let remote = sdp_with_medias(&format!(
+            "m=audio 9 RTP/SAVP 111\na=rtpmap:111 opus/48000/2\nm=video 9 RTP/SAVP 96\na=rtpmap:96 H264/90000\na=extmap:9 {}\n",
+            AUDIO_LATENCY_URI
+        ));
+        assert_eq!(
+            negotiate_codecs(&remote, MediaDirections::default())
+                .unwrap()
+                .audio_latency_id,
+            None
+        );
+    }
+
+    fn video_offer(direction: &str) -> SessionDescription {
+        sdp_with_medias(&format!(
+            "m=audio 9 RTP/SAVP 111\na=rtpmap:111 opus/48000/2\nm=video 9 RTP/SAVP 96\na=rtpmap:96 H264/90000\n{}",
+            direction
+        ))
+    }
+
+    #[test]
+    fn test_answer_direction_reverses_the_offer() {
+        let pairings = [
+            ("", MediaDirection::SendRecv),
+            ("a=sendrecv\n", MediaDirection::SendRecv),
+            ("a=sendonly\n", MediaDirection::RecvOnly),
+            ("a=recvonly\n", MediaDirection::SendOnly),
+            ("a=inactive\n", MediaDirection::Inactive),
+        ];
+        for (offered, answered) in pairings {
+            let codecs =
+                negotiate_codecs(&video_offer(offered), MediaDirections::default()).unwrap();
+            assert_eq!(
+                codecs.direction(MediaType::Video),
+                answered,
+                "{:?}",
+                offered
+            );
+            assert_eq!(codecs.direction(MediaType::Audio), MediaDirection::SendRecv);
+        }
+    }
+
+    #[test]
+    fn test_answer_direction_is_limited_to_the_wanted_one() {
+        let monitor = MediaDirections {
+            audio: MediaDirection::RecvOnly,
+            video: MediaDirection::RecvOnly,
+        };
+        let pairings = [
+            ("a=sendrecv\n", MediaDirection::RecvOnly),
+            ("a=sendonly\n", MediaDirection::RecvOnly),
+            ("a=recvonly\n", MediaDirection::Inactive),
+            ("a=inactive\n", MediaDirection::Inactive),
+        ];
+        for (offered, answered) in pairings {
+            let codecs = negotiate_codecs(&video_offer(offered), monitor).unwrap();
+            assert_eq!(
+                codecs.direction(MediaType::Video),
+                answered,
+                "{:?}",
+                offered
+            );
+            assert!(!codecs.sends(MediaType::Video));
+        }
+
+        let broadcast = MediaDirections {
+            audio: MediaDirection::SendOnly,
+            video: MediaDirection::SendOnly,
+        };
+        let codecs = negotiate_codecs(&video_offer("a=sendrecv\n"), broadcast).unwrap();
+        assert!(codecs.sends(MediaType::Video));
+        assert!(!codecs.receives(MediaType::Video));
+        let codecs = negotiate_codecs(&video_offer("a=sendonly\n"), broadcast).unwrap();
+        assert_eq!(codecs.direction(MediaType::Video), MediaDirection::Inactive);
+    }
+
+    #[test]
+    fn test_rejected_media_neither_sends_nor_receives() {
+        let remote = sdp_with_medias(
+            "m=audio 9 RTP/SAVP 111\na=rtpmap:111 opus/48000/2\nm=video 0 RTP/SAVP 96\na=rtpmap:96 H264/90000\na=sendrecv\n",
+        );
+        let codecs = negotiate_codecs(&remote, MediaDirections::default()).unwrap();
+        assert!(!codecs.sends(MediaType::Video));
+        assert!(!codecs.receives(MediaType::Video));
+        assert!(codecs.sends(MediaType::Audio));
+    }
+
+    #[test]
+    fn test_negotiate_no_common_codec() {
+        let remote = sdp_with_medias(
+            "m=audio 9 RTP/SAVP 0\na=rtpmap:0 PCMU/8000\nm=video 9 RTP/SAVP 98\na=rtpmap:98 VP8/90000\n",
+        );
+        let err = negotiate_codecs(&remote, MediaDirections::default()).unwrap_err();
+        assert!(matches!(err, PeerConnectionError::NoCommonCodec(_)));
+    }
+}
@@ -0,0 +1,176 @@
+//! Progress and failures of the connection setup (ICE, DTLS, SCTP), as
+//! reported to the application.
+
+use std::fmt;
+
+use super::peer_connection_error::PeerConnectionError;
+
+/// Step reached (or failed) while establishing a peer connection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConnectionEvent {
+    IceConnected,
+    IceFailed(String),
+    DtlsConnected,
+    DtlsFailed(String),
+    SctpReady,
+    Closed,
+}
+
+impl ConnectionEvent {
+    /// Failure event for an error raised while establishing the connection.
+    ///
+    /// DTLS errors map to `DtlsFailed`; everything else happens before the
+    /// handshake and is reported as an ICE failure.
+    pub fn from_error(err: &PeerConnectionError) -> Self {
+        match err {
+            PeerConnectionError::Dtls(reason) => ConnectionEvent::DtlsFailed(reason.clone()),
+            PeerConnectionError::Ice(reason) => ConnectionEvent::IceFailed(reason.clone()),
+            other => ConnectionEvent::IceFailed(other.to_string()),
+        }
+    }
+
+    /// Whether the connection is unusable after this event.
+    pub fn is_terminal(&self) -> bool {
+        matches!(
+            self,
+            ConnectionEvent::IceFailed(_)
+                | ConnectionEvent::DtlsFailed(_)
+                | ConnectionEvent::Closed
+        )
+    }
+}
+
+impl fmt::Display for ConnectionEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConnectionEvent::IceConnected => write!(f, "ICE connected, securing connection..."),
+            ConnectionEvent::IceFailed(reason) => write!(f, "ICE failed: {}", reason),
+            ConnectionEvent::DtlsConnected => write!(f, "Secure connection established"),
+            ConnectionEvent::DtlsFailed(reason) => write!(f, "DTLS failed: {}", reason),
+            ConnectionEvent::SctpReady => write!(f, "Data channel ready"),
+            ConnectionEvent::Closed => write!(f, "Connection closed"),
+        }
+    }
+}
+
+/// Phase of the connection setup, built from the events seen so far.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum ConnectionState {
+    /// Connectivity checks have not started: candidates are still being
+    /// gathered or exchanged with the peer.
+    #[default]
+    Gathering,
+    /// ICE connectivity checks in progress.
+    Checking,
+    /// ICE connected, DTLS handshake in progress.
+    Handshaking,
+    /// Secure connection up, media can flow.
+    Ready,
+    Failed(String),
+    Closed,
+}
+
+impl ConnectionState {
+    /// State after `event`. Once failed or closed it stays that way.
+    pub fn on_event(&self, event: &ConnectionEvent) -> Self {
+        if self.is_terminal() {
+            return self.clone();
+        }
+        match event {
+            ConnectionEvent::IceConnected => ConnectionState::Handshaking,
+            ConnectionEvent::DtlsConnected | ConnectionEvent::SctpReady => ConnectionState::Ready,
+            ConnectionEvent::IceFailed(_) | ConnectionEvent::DtlsFailed(_) => {
+                ConnectionState::Failed(event.to_string())
+            }
+            ConnectionEvent::Closed => ConnectionState::Closed,
+        }
+    }
+
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, ConnectionState::Failed(_) | ConnectionState::Closed)
+    }
+
+    /// Rough completion of the setup, from 0 to 1, for progress bars.
+    pub fn progress(&self) -> f32 {
+        match self {
+            ConnectionState::Gathering => 0.1,
+            ConnectionState::Checking => 0.4,
+            ConnectionState::Handshaking => 0.7,
+            ConnectionState::Ready => 1.0,
+            ConnectionState::Failed(_) | ConnectionState::Closed => 0.0,
+        }
+    }
+}
+
+impl fmt::Display for ConnectionState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConnectionState::Gathering => write!(f, "Waiting for the peer's candidates..."),
+            ConnectionState::Checking => write!(f, "Checking connectivity..."),
+            ConnectionState::Handshaking => write!(f, "Securing connection (DTLS)..."),
+            ConnectionState::Ready => write!(f, "Connected"),
+            ConnectionState::Failed(reason) => write!(f, "{}", reason),
+            ConnectionState::Closed => write!(f, "Connection closed"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dtls_error_maps_to_dtls_failed() {
+        let err = PeerConnectionError::Dtls("fingerprint mismatch".to_string());
+        let event = ConnectionEvent::from_error(&err);
+        assert_eq!(
+            event,
+            ConnectionEvent::DtlsFailed("fingerprint mismatch".to_string())
+        );
+        assert_eq!(event.to_string(), "DTLS failed: fingerprint mismatch");
+    }
+
+    #[test]
+    fn test_other_errors_map_to_ice_failed() {
+        let err = PeerConnectionError::Ice("No ICE connection established".to_string());
+        assert_eq!(
+            ConnectionEvent::from_error(&err),
+            ConnectionEvent::IceFailed("No ICE connection established".to_string())
+        );
+
+        let err = PeerConnectionError::Sdp("bad candidate".to_string());
+        assert_eq!(
+            ConnectionEvent::from_error(&err),
+            ConnectionEvent::IceFailed("SDP error: bad candidate".to_string())
+        );
+    }
+
+    #[test]
+    fn test_only_failures_and_close_are_terminal() {
+        assert!(ConnectionEvent::IceFailed("timeout".to_string()).is_terminal());
+        assert!(ConnectionEvent::DtlsFailed("timeout".to_string()).is_terminal());
+        assert!(ConnectionEvent::Closed.is_terminal());
+        assert!(!ConnectionEvent::IceConnected.is_terminal());
+        assert!(!ConnectionEvent::DtlsConnected.is_terminal());
+        assert!(!ConnectionEvent::SctpReady.is_terminal());
+    }
+
+    #[test]
+    fn test_state_follows_the_setup() {
+        let state = ConnectionState::Checking;
+        let state = state.on_event(&ConnectionEvent::IceConnected);
+        assert_eq!(state, ConnectionState::Handshaking);
+        let state = state.on_event(&ConnectionEvent::DtlsConnected);
+        assert_eq!(state, ConnectionState::Ready);
+        assert_eq!(state.progress(), 1.0);
+
+        let failed = ConnectionState::Handshaking
+            .on_event(&ConnectionEvent::DtlsFailed("timeout".to_string()));
+        assert_eq!(
+            failed,
+            ConnectionState::Failed("DTLS failed: timeout".to_string())
+        );
+        // Un fallo no se pisa con eventos posteriores
+        assert_eq!(failed.on_event(&ConnectionEvent::Closed), failed);
+    }
+}
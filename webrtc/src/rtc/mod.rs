@@ -1,3 +1,5 @@
+pub mod codec_negotiation;
+pub mod connection_event;
 pub mod jitter_buffer;
 pub mod peer_connection_error;
 pub mod rtc_const;
@@ -6,5 +8,7 @@ pub mod rtc_err;
 pub mod rtc_peer_connection;
 pub mod rtc_rtp;
 pub mod sdp_negotiation;
+pub mod session_blob;
 pub mod socket;
+pub mod ssrc_routing;
 pub mod rtc_sctp;
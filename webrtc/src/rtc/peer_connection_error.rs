@@ -19,6 +19,8 @@ pub enum PeerConnectionError {
     InvalidRole(&'static str),
     /// Error in DTLS handshake or configuration.
     Dtls(String),
+    /// The remote offer has no codec in common with us for the given media.
+    NoCommonCodec(String),
 }
 
 impl fmt::Display for PeerConnectionError {
@@ -30,6 +32,9 @@ impl fmt::Display for PeerConnectionError {
             PeerConnectionError::Ice(err) => write!(f, "ICE error: {}", err),
             PeerConnectionError::InvalidRole(msg) => write!(f, "Invalid role: {}", msg),
             PeerConnectionError::Dtls(msg) => write!(f, "DTLS error: {}", msg),
+            PeerConnectionError::NoCommonCodec(media) => {
+                write!(f, "No common codec for {} media", media)
+            }
         }
     }
 }
@@ -7,3 +7,4 @@ pub const LOCAL_ADDR_ERROR: &str = "LocalAddrError";
 pub const CLONE_ERROR: &str = "CloneError";
 pub const RECEIVER_ERROR: &str = "ReceiverError";
 pub const SEND_ERROR: &str = "SendError";
+pub const PORT_RANGE_ERROR: &str = "PortRangeExhausted";
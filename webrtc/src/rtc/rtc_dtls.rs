@@ -1,14 +1,19 @@
 use openssl::asn1::Asn1Time;
+use openssl::ec::{EcGroup, EcKey};
 use openssl::hash::MessageDigest;
-use openssl::pkey::PKey;
+use openssl::nid::Nid;
+use openssl::pkey::{PKey, Private};
 use openssl::rsa::Rsa;
-use openssl::ssl::{Ssl, SslContext, SslMethod, SslStream, SslVerifyMode, HandshakeError};
-use openssl::x509::{X509NameBuilder, X509};
+use openssl::ssl::{
+    HandshakeError, Ssl, SslContext, SslMethod, SslStream, SslVerifyMode, SslVersion,
+};
+use openssl::x509::{X509NameBuilder, X509Ref, X509};
 use std::io::{self, Read, Write};
 use std::net::{SocketAddr, UdpSocket};
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, OnceLock};
 use std::sync::mpsc::{Receiver};
 use std::cmp;
+use std::str::FromStr;
 
 /// Stream que conecta OpenSSL con el mundo UDP a través de un Channel.
 /// - Escritura: Directa al UdpSocket.
@@ -62,7 +67,7 @@ impl Read for UdpStream {
         // 2. Si no hay datos, intentamos recibir del canal sin bloquear.
         match self.receiver.try_recv() {
             Ok(packet) => {
-                println!("DEBUG: UdpStream READ packet of {} bytes", packet.len());
+                log_debug!("UdpStream READ packet of {} bytes", packet.len());
                 let n = cmp::min(packet.len(), buf.len());
                 buf[..n].copy_from_slice(&packet[..n]);
 
@@ -80,7 +85,7 @@ impl Read for UdpStream {
             }
             Err(std::sync::mpsc::TryRecvError::Disconnected) => {
                 // El canal se cerró
-                println!("DEBUG: UdpStream Channel CLOSED (sender dropped)");
+                log_debug!("UdpStream Channel CLOSED (sender dropped)");
                 Err(io::Error::new(
                     io::ErrorKind::BrokenPipe,
                     "DTLS Channel closed",
@@ -92,7 +97,11 @@ impl Read for UdpStream {
 
 impl Write for UdpStream {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        println!("DEBUG: UdpStream WRITE {} bytes to {}", buf.len(), self.remote_addr);
+        log_debug!(
+            "UdpStream WRITE {} bytes to {}",
+            buf.len(),
+            self.remote_addr
+        );
         // La escritura sigue siendo directa al socket
         let socket = self.socket.lock().unwrap();
         socket.send_to(buf, self.remote_addr)
@@ -108,57 +117,274 @@ pub enum DtlsRole {
     Client,
     Server,
 }
+
+/// DTLS protocol version, ordered from oldest to newest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DtlsVersion {
+    Dtls1_0,
+    Dtls1_2,
+}
+
+impl DtlsVersion {
+    fn ssl_version(self) -> SslVersion {
+        match self {
+            DtlsVersion::Dtls1_0 => SslVersion::DTLS1,
+            DtlsVersion::Dtls1_2 => SslVersion::DTLS1_2,
+        }
+    }
+}
+
+impl FromStr for DtlsVersion {
+    type Err = String;
+
+    /// Parses `1.0` or `1.2`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "1.0" => Ok(DtlsVersion::Dtls1_0),
+            "1.2" => Ok(DtlsVersion::Dtls1_2),
+            other => Err(format!("Unknown DTLS version: {}", other)),
+        }
+    }
+}
+
+/// Validity of the identity shared by the sessions without one of their
+/// own, generated once per run.
+const EPHEMERAL_CERT_DAYS: u32 = 365;
+/// Validity of an identity meant to be kept across calls. The peer accepts
+/// self-signed certificates and only checks the fingerprint, so it is only
+/// informative.
+const IDENTITY_CERT_DAYS: u32 = 3650;
+/// Groups of five digits in a safety number.
+const SAFETY_NUMBER_GROUPS: usize = 6;
+
+/// Certificate, private key and fingerprint of the local endpoint, parsed
+/// once and shared by every session that uses it. Using the same identity in
+/// every call keeps our fingerprint stable, so the peer can pin it; sessions
+/// without one share [`DtlsIdentity::ephemeral`].
+#[derive(Clone)]
+pub struct DtlsIdentity {
+    certificate: X509,
+    private_key: PKey<Private>,
+    fingerprint: String,
+}
+
+impl DtlsIdentity {
+    /// New self-signed ECDSA P-256 identity: generating it takes about a
+    /// millisecond, against the hundreds an RSA key may take.
+    pub fn generate() -> Result<Self, String> {
+        Self::generate_ecdsa(IDENTITY_CERT_DAYS)
+    }
+
+    /// New self-signed RSA 2048 identity, for cipher lists that only allow
+    /// RSA-authenticated suites.
+    pub fn generate_rsa() -> Result<Self, String> {
+        let rsa = Rsa::generate(2048).map_err(|e| e.to_string())?;
+        let pkey = PKey::from_rsa(rsa).map_err(|e| e.to_string())?;
+        Self::self_signed(pkey, IDENTITY_CERT_DAYS)
+    }
+
+    /// Identity shared by the sessions created without one in this run. It
+    /// is generated the first time it is asked for.
+    pub fn ephemeral() -> Result<Arc<Self>, String> {
+        static EPHEMERAL: OnceLock<Result<Arc<DtlsIdentity>, String>> = OnceLock::new();
+        EPHEMERAL
+            .get_or_init(|| Self::generate_ecdsa(EPHEMERAL_CERT_DAYS).map(Arc::new))
+            .clone()
+    }
+
+    /// Identity saved with [`Self::certificate_pem`] and
+    /// [`Self::private_key_pem`]. Fails if either does not parse or the key
+    /// does not belong to the certificate.
+    pub fn from_pem(certificate_pem: &str, private_key_pem: &str) -> Result<Self, String> {
+        let certificate = X509::from_pem(certificate_pem.as_bytes())
+            .map_err(|e| format!("Invalid DTLS certificate: {}", e))?;
+        let private_key = PKey::private_key_from_pem(private_key_pem.as_bytes())
+            .map_err(|e| format!("Invalid DTLS private key: {}", e))?;
+        let public_key = certificate.public_key().map_err(|e| e.to_string())?;
+        if !private_key.public_eq(&public_key) {
+            return Err("The DTLS private key does not match the certificate".to_string());
+        }
+        Self::new(certificate, private_key)
+    }
+
+    fn new(certificate: X509, private_key: PKey<Private>) -> Result<Self, String> {
+        let fingerprint = certificate_fingerprint(&certificate)?;
+        Ok(Self {
+            certificate,
+            private_key,
+            fingerprint,
+        })
+    }
+
+    fn generate_ecdsa(days: u32) -> Result<Self, String> {
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).map_err(|e| e.to_string())?;
+        let key = EcKey::generate(&group).map_err(|e| e.to_string())?;
+        let pkey = PKey::from_ec_key(key).map_err(|e| e.to_string())?;
+        Self::self_signed(pkey, days)
+    }
+
+    fn self_signed(private_key: PKey<Private>, days: u32) -> Result<Self, String> {
+        let certificate = self_signed_certificate(&private_key, days)?;
+        Self::new(certificate, private_key)
+    }
+
+    pub fn certificate_pem(&self) -> Result<String, String> {
+        let pem = self.certificate.to_pem().map_err(|e| e.to_string())?;
+        Ok(String::from_utf8_lossy(&pem).into_owned())
+    }
+
+    /// PKCS#8 PEM of the private key, to save the identity.
+    pub fn private_key_pem(&self) -> Result<String, String> {
+        let pem = self
+            .private_key
+            .private_key_to_pem_pkcs8()
+            .map_err(|e| e.to_string())?;
+        Ok(String::from_utf8_lossy(&pem).into_owned())
+    }
+
+    /// SHA-256 fingerprint of the certificate, as announced in the SDP.
+    pub fn fingerprint(&self) -> &str {
+        &self.fingerprint
+    }
+}
+
+// El fingerprint identifica al certificado, y con él a la llave
+impl PartialEq for DtlsIdentity {
+    fn eq(&self, other: &Self) -> bool {
+        self.fingerprint == other.fingerprint
+    }
+}
+
+impl Eq for DtlsIdentity {}
+
+// La llave privada no tiene que terminar en un log
+impl std::fmt::Debug for DtlsIdentity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DtlsIdentity").finish_non_exhaustive()
+    }
+}
+
+/// Cipher suites and versions the DTLS handshake may negotiate, and the
+/// identity to present. The default leaves OpenSSL's own choice untouched
+/// and uses the ephemeral identity of the run.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DtlsConfig {
+    /// OpenSSL cipher list (`ECDHE-ECDSA-AES128-GCM-SHA256:...`). It needs
+    /// at least one suite for the key of the identity: ECDSA unless it was
+    /// generated with [`DtlsIdentity::generate_rsa`].
+    pub cipher_list: Option<String>,
+    pub min_version: Option<DtlsVersion>,
+    pub max_version: Option<DtlsVersion>,
+    pub identity: Option<Arc<DtlsIdentity>>,
+}
+
+/// Self-signed certificate for `pkey`, valid for `days`.
+fn self_signed_certificate(pkey: &PKey<Private>, days: u32) -> Result<X509, String> {
+    let mut x509 = X509::builder().map_err(|e| e.to_string())?;
+    x509.set_version(2).map_err(|e| e.to_string())?;
+    x509.set_pubkey(pkey).map_err(|e| e.to_string())?;
+
+    // Asignar un "subject name" para que sea un certificado válido
+    let mut name = X509NameBuilder::new().unwrap();
+    name.append_entry_by_text("CN", "webrtc-peer").unwrap();
+    let name = name.build();
+    x509.set_subject_name(&name).unwrap();
+
+    let not_before = Asn1Time::days_from_now(0).map_err(|e| e.to_string())?;
+    let not_after = Asn1Time::days_from_now(days).map_err(|e| e.to_string())?;
+    x509.set_not_before(&not_before)
+        .map_err(|e| e.to_string())?;
+    x509.set_not_after(&not_after).map_err(|e| e.to_string())?;
+
+    // Firma el certificado
+    x509.sign(pkey, MessageDigest::sha256())
+        .map_err(|e| e.to_string())?;
+    Ok(x509.build())
+}
+
+/// SHA-256 fingerprint of `cert` in SDP form, `AA:BB:CC:...`.
+fn certificate_fingerprint(cert: &X509Ref) -> Result<String, String> {
+    let digest = cert
+        .digest(MessageDigest::sha256())
+        .map_err(|e| format!("Digest error: {}", e))?;
+    Ok(hex::encode(digest)
+        .to_uppercase()
+        .as_bytes()
+        .chunks(2)
+        .map(|c| std::str::from_utf8(c).unwrap())
+        .collect::<Vec<&str>>()
+        .join(":"))
+}
+
+/// Short number both peers can read aloud to check that nobody sits between
+/// them. It is derived from both certificate fingerprints, so it is the same
+/// on each end and changes if either certificate does: six groups of five
+/// digits, like `04219 88310 ...`.
+pub fn safety_number(local_fingerprint: &str, remote_fingerprint: &str) -> String {
+    let local = local_fingerprint.to_ascii_uppercase();
+    let remote = remote_fingerprint.to_ascii_uppercase();
+    // Ordenados, para que los dos lados hasheen lo mismo
+    let (first, second) = if local <= remote {
+        (local, remote)
+    } else {
+        (remote, local)
+    };
+    let digest = openssl::sha::sha256(format!("{}|{}", first, second).as_bytes());
+    digest
+        .chunks(5)
+        .take(SAFETY_NUMBER_GROUPS)
+        .map(|chunk| {
+            let value = chunk
+                .iter()
+                .fold(0u64, |acc, &byte| (acc << 8) | byte as u64);
+            format!("{:05}", value % 100_000)
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
 pub struct DtlsSession {
     context: SslContext,
     ssl_stream: Option<SslStream<UdpStream>>,
     local_fingerprint: String,
     remote_fingerprint: Option<String>,
+    peer_fingerprint: Option<String>,
     role: DtlsRole,
 }
 
 impl DtlsSession {
-    pub fn new(role: DtlsRole) -> Result<Self, String> {
-        // 1. Generar Certificado y Llave Privada (Self-Signed)
-        let rsa = Rsa::generate(2048).map_err(|e| e.to_string())?;
-        let pkey = PKey::from_rsa(rsa).map_err(|e| e.to_string())?;
-
-        let mut x509 = X509::builder().map_err(|e| e.to_string())?;
-        x509.set_version(2).map_err(|e| e.to_string())?;
-        x509.set_pubkey(&pkey).map_err(|e| e.to_string())?;
-
-        // Asignar un "subject name" para que sea un certificado válido
-        let mut name = X509NameBuilder::new().unwrap();
-        name.append_entry_by_text("CN", "webrtc-peer").unwrap();
-        let name = name.build();
-        x509.set_subject_name(&name).unwrap();
-
-        let not_before = Asn1Time::days_from_now(0).map_err(|e| e.to_string())?;
-        let not_after = Asn1Time::days_from_now(365).map_err(|e| e.to_string())?;
-        x509.set_not_before(&not_before)
-            .map_err(|e| e.to_string())?;
-        x509.set_not_after(&not_after).map_err(|e| e.to_string())?;
+    /// Session presenting `identity`. Nothing is generated here, so it is
+    /// cheap to create one per call.
+    pub fn new(role: DtlsRole, identity: Arc<DtlsIdentity>) -> Result<Self, String> {
+        Self::with_config(
+            role,
+            &DtlsConfig {
+                identity: Some(identity),
+                ..DtlsConfig::default()
+            },
+        )
+    }
 
-        // Firma el certificado
-        x509.sign(&pkey, MessageDigest::sha256())
-            .map_err(|e| e.to_string())?;
-        let cert = x509.build();
+    /// Like [`Self::new`], restricting what the handshake may negotiate.
+    /// Without an identity in `config` it uses [`DtlsIdentity::ephemeral`].
+    /// Fails if OpenSSL accepts none of the listed ciphers or the version
+    /// range is empty.
+    pub fn with_config(role: DtlsRole, config: &DtlsConfig) -> Result<Self, String> {
+        // 1. Certificado y Llave Privada: los de la identidad fija, o los
+        // de la efímera que comparten todas las sesiones de esta corrida
+        let identity = match &config.identity {
+            Some(identity) => Arc::clone(identity),
+            None => DtlsIdentity::ephemeral()?,
+        };
 
-        // 2. Calcular Fingerprint (SHA-256) para SDP
-        let digest = cert
-            .digest(MessageDigest::sha256())
-            .map_err(|e| e.to_string())?;
-        let fingerprint = hex::encode(digest)
-            .to_uppercase()
-            .as_bytes()
-            .chunks(2)
-            .map(|c| std::str::from_utf8(c).unwrap())
-            .collect::<Vec<&str>>()
-            .join(":");
+        // 2. Fingerprint (SHA-256) para SDP, calculado con la identidad
+        let fingerprint = identity.fingerprint().to_string();
 
         // 3. Configurar Contexto SSL
         let mut ctx = SslContext::builder(SslMethod::dtls()).map_err(|e| e.to_string())?;
-        ctx.set_certificate(&cert).map_err(|e| e.to_string())?;
-        ctx.set_private_key(&pkey).map_err(|e| e.to_string())?;
+        ctx.set_certificate(&identity.certificate)
+            .map_err(|e| e.to_string())?;
+        ctx.set_private_key(&identity.private_key)
+            .map_err(|e| e.to_string())?;
         
         // Configurar Mutual TLS: Pedir certificado y aceptar autofirmados (callback retorna true)
         let mut mode = SslVerifyMode::PEER;
@@ -169,11 +395,31 @@ impl DtlsSession {
         ctx.set_tlsext_use_srtp("SRTP_AES128_CM_SHA1_80")
             .map_err(|e| e.to_string())?;
 
+        // Restricciones de la config. El perfil SRTP va en una extensión aparte
+        // y no depende del suite elegido
+        if let (Some(min), Some(max)) = (config.min_version, config.max_version) {
+            if min > max {
+                return Err(format!(
+                    "DTLS min version {:?} is newer than max version {:?}",
+                    min, max
+                ));
+            }
+        }
+        if let Some(list) = &config.cipher_list {
+            ctx.set_cipher_list(list)
+                .map_err(|e| format!("Invalid DTLS cipher list '{}': {}", list, e))?;
+        }
+        ctx.set_min_proto_version(config.min_version.map(DtlsVersion::ssl_version))
+            .map_err(|e| e.to_string())?;
+        ctx.set_max_proto_version(config.max_version.map(DtlsVersion::ssl_version))
+            .map_err(|e| e.to_string())?;
+
         Ok(Self {
             context: ctx.build(),
             ssl_stream: None,
             local_fingerprint: fingerprint,
             remote_fingerprint: None,
+            peer_fingerprint: None,
             role,
         })
     }
@@ -187,17 +433,54 @@ impl DtlsSession {
         self.local_fingerprint.clone()
     }
 
+    pub fn remote_fingerprint(&self) -> Option<&str> {
+        self.remote_fingerprint.as_deref()
+    }
+
+    /// Fingerprint of the certificate the peer presented in the handshake,
+    /// already checked against the one in its SDP. `None` until the
+    /// handshake completes.
+    pub fn peer_certificate_fingerprint(&self) -> Option<&str> {
+        self.peer_fingerprint.as_deref()
+    }
+
+    /// Sends the records of an established session to `addr` from now on,
+    /// after the path to the peer changed (ICE restart, NAT rebinding).
+    /// The keys stay the same, so there is no new handshake.
+    pub fn set_remote_addr(&mut self, addr: SocketAddr) {
+        if let Some(stream) = self.ssl_stream.as_mut() {
+            stream.get_mut().remote_addr = addr;
+        }
+    }
+
     pub fn is_handshake_complete(&self) -> bool {
         self.ssl_stream.is_some()
     }
 
+    /// Cipher suite agreed in the handshake, e.g. `ECDHE-ECDSA-AES128-GCM-SHA256`.
+    pub fn negotiated_cipher(&self) -> Option<&'static str> {
+        self.ssl_stream
+            .as_ref()
+            .and_then(|s| s.ssl().current_cipher())
+            .map(|cipher| cipher.name())
+    }
+
+    /// Protocol version agreed in the handshake, e.g. `DTLSv1.2`.
+    pub fn negotiated_version(&self) -> Option<&'static str> {
+        self.ssl_stream.as_ref().map(|s| s.ssl().version_str())
+    }
+
     pub fn perform_handshake(
         &mut self,
         socket: Arc<Mutex<UdpSocket>>, // Usamos Arc<Mutex> para poder clonarlo dentro del UdpStream
         receiver: Receiver<Vec<u8>>, // El canal por donde llegan los paquetes filtrados (byte 20-63)
         remote_addr: SocketAddr,
     ) -> Result<(), String> {
-        println!("DEBUG: Starting DTLS Handshake as {:?} with remote {}", self.role, remote_addr);
+        log_debug!(
+            "Starting DTLS Handshake as {:?} with remote {}",
+            self.role,
+            remote_addr
+        );
         // 1. Crear el wrapper que conecta OpenSSL con el Canal y el Socket
         let stream = UdpStream::new(socket, remote_addr, receiver);
 
@@ -228,31 +511,20 @@ impl DtlsSession {
             }
         };
 
-        println!("DEBUG: DTLS Handshake successfully completed!");
+        log_debug!("DTLS Handshake successfully completed!");
 
         // 4. VERIFICACIÓN DEL FINGERPRINT (Crucial)
+        // Obtenemos el certificado que nos envió el peer
+        let peer_cert = stream
+            .ssl()
+            .peer_certificate()
+            .ok_or("Peer did not present a certificate")?;
+
+        // Calculamos el hash SHA-256 de ese certificado, en formato "AA:BB:CC..."
+        let calculated_fp = certificate_fingerprint(&peer_cert)?;
+
+        // Comparamos
         if let Some(expected_fp) = &self.remote_fingerprint {
-            // Obtenemos el certificado que nos envió el peer
-            let peer_cert = stream
-                .ssl()
-                .peer_certificate()
-                .ok_or("Peer did not present a certificate")?;
-
-            // Calculamos el hash SHA-256 de ese certificado
-            let digest = peer_cert
-                .digest(MessageDigest::sha256())
-                .map_err(|e| format!("Digest error: {}", e))?;
-
-            // Lo convertimos a formato string "AA:BB:CC..."
-            let calculated_fp = hex::encode(digest)
-                .to_uppercase()
-                .as_bytes()
-                .chunks(2)
-                .map(|c| std::str::from_utf8(c).unwrap())
-                .collect::<Vec<&str>>()
-                .join(":");
-
-            // Comparamos
             if calculated_fp != *expected_fp {
                 return Err(format!(
                     "Fingerprint mismatch! Expected: {}, Got: {}",
@@ -262,8 +534,13 @@ impl DtlsSession {
         }
 
         // 5. Guardar el stream establecido
+        self.peer_fingerprint = Some(calculated_fp);
         self.ssl_stream = Some(stream);
-        println!("DTLS Handshake successfully completed!");
+        log_info!(
+            "DTLS Handshake successfully completed! ({} {})",
+            self.negotiated_version().unwrap_or("?"),
+            self.negotiated_cipher().unwrap_or("?")
+        );
 
         Ok(())
     }
@@ -298,3 +575,239 @@ impl DtlsSession {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::mpsc;
+    use std::thread;
+    use std::time::Duration;
+
+    /// Runs both handshakes over loopback sockets, each fed by a thread that
+    /// plays the part of the demultiplexer.
+    fn handshake(
+        client: &mut DtlsSession,
+        server: &mut DtlsSession,
+    ) -> (Result<(), String>, Result<(), String>) {
+        let client_socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let server_socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let client_addr = client_socket.local_addr().unwrap();
+        let server_addr = server_socket.local_addr().unwrap();
+        let done = AtomicBool::new(false);
+
+        thread::scope(|scope| {
+            let mut ends = Vec::new();
+            for socket in [&client_socket, &server_socket] {
+                let (tx, rx) = mpsc::channel();
+                let reader = socket.try_clone().unwrap();
+                reader
+                    .set_read_timeout(Some(Duration::from_millis(50)))
+                    .unwrap();
+                let done = &done;
+                scope.spawn(move || {
+                    let mut buf = [0u8; 2048];
+                    while !done.load(Ordering::Relaxed) {
+                        if let Ok((n, _)) = reader.recv_from(&mut buf) {
+                            let _ = tx.send(buf[..n].to_vec());
+                        }
+                    }
+                });
+                ends.push((Arc::new(Mutex::new(socket.try_clone().unwrap())), rx));
+            }
+            let (server_socket, server_rx) = ends.pop().unwrap();
+            let (client_socket, client_rx) = ends.pop().unwrap();
+
+            let server_side =
+                scope.spawn(|| server.perform_handshake(server_socket, server_rx, client_addr));
+            let client_result = client.perform_handshake(client_socket, client_rx, server_addr);
+            let server_result = server_side.join().unwrap();
+            done.store(true, Ordering::Relaxed);
+            (client_result, server_result)
+        })
+    }
+
+    #[test]
+    fn test_restricted_config_still_negotiates_srtp() {
+        let config = DtlsConfig {
+            cipher_list: Some("ECDHE-ECDSA-AES128-GCM-SHA256".to_string()),
+            min_version: Some(DtlsVersion::Dtls1_2),
+            max_version: Some(DtlsVersion::Dtls1_2),
+            ..DtlsConfig::default()
+        };
+        let mut client = DtlsSession::with_config(DtlsRole::Client, &config).unwrap();
+        let mut server = DtlsSession::with_config(DtlsRole::Server, &config).unwrap();
+        client
+            .set_remote_fingerprint(&server.certificate_fingerprint())
+            .unwrap();
+        server
+            .set_remote_fingerprint(&client.certificate_fingerprint())
+            .unwrap();
+
+        let (client_result, server_result) = handshake(&mut client, &mut server);
+        client_result.unwrap();
+        server_result.unwrap();
+
+        for session in [&client, &server] {
+            assert_eq!(
+                session.negotiated_cipher(),
+                Some("ECDHE-ECDSA-AES128-GCM-SHA256")
+            );
+            assert_eq!(session.negotiated_version(), Some("DTLSv1.2"));
+            let profile = session
+                .ssl_stream
+                .as_ref()
+                .and_then(|s| s.ssl().selected_srtp_profile())
+                .map(|p| p.name());
+            assert_eq!(profile, Some("SRTP_AES128_CM_SHA1_80"));
+        }
+        assert_eq!(
+            client.export_srtp_keying_material(32).unwrap(),
+            server.export_srtp_keying_material(32).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_peers_agree_on_the_safety_number() {
+        let identity = DtlsIdentity::generate().unwrap();
+        let config = DtlsConfig {
+            identity: Some(Arc::new(identity.clone())),
+            ..DtlsConfig::default()
+        };
+        let mut client = DtlsSession::with_config(DtlsRole::Client, &config).unwrap();
+        let mut server =
+            DtlsSession::new(DtlsRole::Server, DtlsIdentity::ephemeral().unwrap()).unwrap();
+        assert_eq!(client.certificate_fingerprint(), identity.fingerprint());
+        client
+            .set_remote_fingerprint(&server.certificate_fingerprint())
+            .unwrap();
+        server
+            .set_remote_fingerprint(&client.certificate_fingerprint())
+            .unwrap();
+        assert!(server.peer_certificate_fingerprint().is_none());
+
+        let (client_result, server_result) = handshake(&mut client, &mut server);
+        client_result.unwrap();
+        server_result.unwrap();
+
+        assert_eq!(
+            server.peer_certificate_fingerprint(),
+            Some(client.certificate_fingerprint().as_str())
+        );
+        let client_number = safety_number(
+            &client.certificate_fingerprint(),
+            client.peer_certificate_fingerprint().unwrap(),
+        );
+        let server_number = safety_number(
+            &server.certificate_fingerprint(),
+            server.peer_certificate_fingerprint().unwrap(),
+        );
+        assert_eq!(client_number, server_number);
+    }
+
+    #[test]
+    fn test_safety_number_format() {
+        let number = safety_number("AA:BB", "cc:dd");
+        assert_eq!(number, safety_number("CC:DD", "aa:bb"));
+        let groups: Vec<&str> = number.split(' ').collect();
+        assert_eq!(groups.len(), SAFETY_NUMBER_GROUPS);
+        assert!(groups
+            .iter()
+            .all(|group| group.len() == 5 && group.bytes().all(|b| b.is_ascii_digit())));
+        assert_ne!(number, safety_number("AA:BB", "CC:DE"));
+    }
+
+    #[test]
+    fn test_identity_survives_pem() {
+        let identity = DtlsIdentity::generate().unwrap();
+        let certificate = identity.certificate_pem().unwrap();
+        let key = identity.private_key_pem().unwrap();
+        let loaded = DtlsIdentity::from_pem(&certificate, &key).unwrap();
+        assert_eq!(loaded.fingerprint(), identity.fingerprint());
+        assert_eq!(loaded, identity);
+        assert!(!format!("{:?}", loaded).contains("PRIVATE"));
+
+        let other = DtlsIdentity::generate_rsa().unwrap();
+        let other_key = other.private_key_pem().unwrap();
+        assert!(DtlsIdentity::from_pem(&certificate, &other_key).is_err());
+        assert!(DtlsIdentity::from_pem("garbage", &key).is_err());
+        let rsa = DtlsIdentity::from_pem(&other.certificate_pem().unwrap(), &other_key).unwrap();
+        assert_eq!(rsa, other);
+    }
+
+    #[test]
+    fn test_no_common_cipher_fails_the_handshake() {
+        let client_config = DtlsConfig {
+            cipher_list: Some("ECDHE-ECDSA-AES128-GCM-SHA256".to_string()),
+            ..DtlsConfig::default()
+        };
+        let server_config = DtlsConfig {
+            cipher_list: Some("ECDHE-ECDSA-AES256-GCM-SHA384".to_string()),
+            ..DtlsConfig::default()
+        };
+        let mut client = DtlsSession::with_config(DtlsRole::Client, &client_config).unwrap();
+        let mut server = DtlsSession::with_config(DtlsRole::Server, &server_config).unwrap();
+
+        let (client_result, server_result) = handshake(&mut client, &mut server);
+        assert!(client_result.is_err());
+        assert!(server_result.is_err());
+        assert!(client.negotiated_cipher().is_none());
+    }
+
+    #[test]
+    fn test_invalid_config_is_rejected() {
+        let unknown_cipher = DtlsConfig {
+            cipher_list: Some("NOT-A-CIPHER".to_string()),
+            ..DtlsConfig::default()
+        };
+        let err = DtlsSession::with_config(DtlsRole::Client, &unknown_cipher)
+            .err()
+            .unwrap();
+        assert!(err.contains("NOT-A-CIPHER"));
+
+        let empty_range = DtlsConfig {
+            min_version: Some(DtlsVersion::Dtls1_2),
+            max_version: Some(DtlsVersion::Dtls1_0),
+            ..DtlsConfig::default()
+        };
+        assert!(DtlsSession::with_config(DtlsRole::Server, &empty_range).is_err());
+
+        assert_eq!("1.2".parse(), Ok(DtlsVersion::Dtls1_2));
+        assert_eq!(" 1.0".parse(), Ok(DtlsVersion::Dtls1_0));
+        assert!("1.3".parse::<DtlsVersion>().is_err());
+    }
+
+    #[test]
+    fn test_sessions_without_identity_share_the_ephemeral_one() {
+        let first = DtlsSession::with_config(DtlsRole::Client, &DtlsConfig::default()).unwrap();
+        let second = DtlsSession::with_config(DtlsRole::Server, &DtlsConfig::default()).unwrap();
+        let ephemeral = DtlsIdentity::ephemeral().unwrap();
+        assert_eq!(first.certificate_fingerprint(), ephemeral.fingerprint());
+        assert_eq!(second.certificate_fingerprint(), ephemeral.fingerprint());
+    }
+
+    #[test]
+    fn test_rsa_identity_negotiates_rsa_suites() {
+        let config = DtlsConfig {
+            cipher_list: Some("ECDHE-RSA-AES128-GCM-SHA256".to_string()),
+            identity: Some(Arc::new(DtlsIdentity::generate_rsa().unwrap())),
+            ..DtlsConfig::default()
+        };
+        let mut client = DtlsSession::with_config(DtlsRole::Client, &config).unwrap();
+        let mut server = DtlsSession::with_config(DtlsRole::Server, &config).unwrap();
+        client
+            .set_remote_fingerprint(&server.certificate_fingerprint())
+            .unwrap();
+        server
+            .set_remote_fingerprint(&client.certificate_fingerprint())
+            .unwrap();
+
+        let (client_result, server_result) = handshake(&mut client, &mut server);
+        client_result.unwrap();
+        server_result.unwrap();
+        assert_eq!(
+            client.negotiated_cipher(),
+            Some("ECDHE-RSA-AES128-GCM-SHA256")
+        );
+    }
+}
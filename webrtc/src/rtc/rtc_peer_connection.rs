@@ -1,17 +1,27 @@
 //! `RTCPeerConnection` API based in ICE.
 
-use std::net::SocketAddr;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use std::sync::mpsc::Receiver;
 use std::sync::{mpsc, Arc, Mutex};
 
-use crate::crypto::srtp::SrtpContext;
-use crate::ice::IceAgent;
-use crate::rtc::rtc_dtls::{DtlsRole, DtlsSession};
+use crate::crypto::srtp::{SharedSrtpContext, SrtpContext};
+use crate::rtc::codec_negotiation::{MediaDirections, NegotiatedCodecs};
+use crate::ice::{
+    CandidatePair, CandidatePairInfo, IceAgent, IceCandidate, IceCheckConfig, IcePairStats,
+};
+use crate::rtc::rtc_dtls::{safety_number, DtlsConfig, DtlsIdentity, DtlsRole, DtlsSession};
 use crate::rtc::socket::peer_socket::PeerSocket;
 use crate::rtc::socket::peer_socket_err::PeerSocketErr;
+use crate::rtc::socket::port_range::PortRange;
+use crate::rtc::session_blob::{decode_session_blob, encode_session_blob};
+use crate::protocols::sdp::media_direction::MediaDirection;
+use crate::protocols::sdp::media_type::MediaType;
+use crate::rtc::ssrc_routing::{LocalStreams, SsrcRouter};
 
 pub use super::peer_connection_error::PeerConnectionError;
-use super::sdp_negotiation::{build_local_description, process_remote_sdp, validate_dtls_fingerprint};
+use super::sdp_negotiation::{
+    build_local_description, process_remote_sdp, remote_dtls_fingerprint, validate_dtls_fingerprint,
+};
 use crate::rtc::rtc_sctp::SctpAssociation;
 
 /// Defines the role assumed by the peer within the signaling flow.
@@ -27,6 +37,17 @@ impl PeerConnectionRole {
     }
 }
 
+/// Progress of the DTLS transport, as reported for diagnostics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DtlsState {
+    /// The DTLS session could not be created.
+    Unavailable,
+    /// Waiting for ICE or for the handshake to finish.
+    Pending,
+    /// Handshake done and SRTP keys exported.
+    Connected,
+}
+
 pub struct RtcPeerConnection {
     role: PeerConnectionRole,
     ice_agent: IceAgent,
@@ -36,19 +57,62 @@ pub struct RtcPeerConnection {
     remote_credentials: Option<(String, String)>,
     host_candidate_registered: bool,
     listener_started: bool,
-    srtp_context: Option<SrtpContext>,
+    srtp_context: SharedSrtpContext,
     dtls_session: Option<DtlsSession>,
+    // La que presenta DTLS si la config no trae otra; None usa la efímera
+    dtls_identity: Option<Arc<DtlsIdentity>>,
     dtls_receiver: Option<Receiver<Vec<u8>>>,
     dtls_sender: Option<mpsc::SyncSender<Vec<u8>>>,
+    local_bandwidth_kbps: Option<u64>,
+    remote_bandwidth_kbps: Option<u64>,
+    negotiated_codecs: NegotiatedCodecs,
+    local_directions: MediaDirections,
+    rtcp_mux: bool,
+    rtcp_socket: Option<Arc<Mutex<PeerSocket>>>,
+    local_streams: LocalStreams,
+    // SSRCs we dropped after a collision; a BYE for them is not a hang-up
+    retired_ssrcs: Vec<u32>,
+    ssrc_router: SsrcRouter,
+    // Puertos permitidos para la media; también acota el socket de RTCP
+    port_range: Option<PortRange>,
     pub sctp_association: Option<SctpAssociation>,
 }
 
 impl RtcPeerConnection {
+    /// DTLS presents `identity`, usually one kept for the whole run so
+    /// creating a connection generates no keys; `None` uses
+    /// [`DtlsIdentity::ephemeral`].
     pub fn new(
         local_addr: Option<&str>,
         role: PeerConnectionRole,
+        identity: Option<Arc<DtlsIdentity>>,
+    ) -> Result<Self, PeerConnectionError> {
+        Ok(Self::with_socket(
+            PeerSocket::new(local_addr)?,
+            role,
+            None,
+            identity,
+        ))
+    }
+
+    /// Like [`Self::new`] on all interfaces, binding the media socket to the
+    /// first free port of `range`.
+    pub fn with_port_range(
+        range: PortRange,
+        role: PeerConnectionRole,
+        identity: Option<Arc<DtlsIdentity>>,
     ) -> Result<Self, PeerConnectionError> {
-        let socket = Arc::new(Mutex::new(PeerSocket::new(local_addr)?));
+        let socket = PeerSocket::bind_in_range(IpAddr::V4(Ipv4Addr::UNSPECIFIED), range)?;
+        Ok(Self::with_socket(socket, role, Some(range), identity))
+    }
+
+    fn with_socket(
+        socket: PeerSocket,
+        role: PeerConnectionRole,
+        port_range: Option<PortRange>,
+        identity: Option<Arc<DtlsIdentity>>,
+    ) -> Self {
+        let socket = Arc::new(Mutex::new(socket));
         let ice_agent = match role {
             PeerConnectionRole::Controlling => IceAgent::new().set_controlling(true),
             PeerConnectionRole::Controlled => IceAgent::new(),
@@ -58,12 +122,16 @@ impl RtcPeerConnection {
             PeerConnectionRole::Controlling => DtlsRole::Client,
             PeerConnectionRole::Controlled => DtlsRole::Server,
         };
-        let dtls_session = DtlsSession::new(dtls_role).ok();
+        let dtls_config = DtlsConfig {
+            identity: identity.clone(),
+            ..DtlsConfig::default()
+        };
+        let dtls_session = DtlsSession::with_config(dtls_role, &dtls_config).ok();
         let (dtls_tx, dtls_rx) = mpsc::sync_channel(100);
 
         let sctp_association = Some(SctpAssociation::new(role == PeerConnectionRole::Controlled));
 
-        Ok(Self {
+        Self {
             role,
             ice_agent,
             socket,
@@ -72,12 +140,23 @@ impl RtcPeerConnection {
             remote_credentials: None,
             host_candidate_registered: false,
             listener_started: false,
-            srtp_context: None,
+            srtp_context: SharedSrtpContext::default(),
             dtls_receiver: Some(dtls_rx),
             dtls_sender: Some(dtls_tx),
             dtls_session,
+            dtls_identity: identity,
+            local_bandwidth_kbps: None,
+            remote_bandwidth_kbps: None,
+            negotiated_codecs: NegotiatedCodecs::default(),
+            local_directions: MediaDirections::default(),
+            rtcp_mux: true,
+            rtcp_socket: None,
+            local_streams: LocalStreams::generate(),
+            retired_ssrcs: Vec::new(),
+            ssrc_router: SsrcRouter::default(),
+            port_range,
             sctp_association,
-        })
+        }
     }
 
     // ========== Basic accessors ==========
@@ -106,16 +185,86 @@ impl RtcPeerConnection {
     }
 
     /// Updates the remote address if it changed (e.g., after NAT rebinding).
+    /// The DTLS records (data channel) and the selected pair follow the media
+    /// to the new address.
     pub fn update_remote_addr(&mut self, new_addr: SocketAddr) {
         if let Ok(mut socket) = self.socket.lock() {
             socket.update_remote_addr(new_addr);
         }
+        if let Some(session) = self.dtls_session.as_mut() {
+            session.set_remote_addr(new_addr);
+        }
+        self.ice_agent.select_remote(new_addr);
+    }
+
+    /// Consent freshness check for a silent media path (RFC 7675): adopts the
+    /// address that answered the previous round, if any, and sends a new round
+    /// of binding requests to the current remote address and every remote
+    /// candidate.
+    pub fn probe_consent(&mut self) -> Option<SocketAddr> {
+        let targets = self.consent_targets();
+        let adopted = {
+            let mut socket = self.socket.lock().ok()?;
+            let adopted = socket.adopt_consented_addr();
+            socket.send_consent_requests(&targets);
+            adopted
+        };
+        if let Some(addr) = adopted {
+            if let Some(session) = self.dtls_session.as_mut() {
+                session.set_remote_addr(addr);
+            }
+            self.ice_agent.select_remote(addr);
+        }
+        adopted
+    }
+
+    /// Addresses probed by [`Self::probe_consent`], the current one first.
+    fn consent_targets(&self) -> Vec<SocketAddr> {
+        let mut targets: Vec<SocketAddr> =
+            self.remote_addr().ok().flatten().into_iter().collect();
+        for candidate in self.ice_agent.remote_candidates() {
+            let Ok(addr) = format!("{}:{}", candidate.address, candidate.port).parse() else {
+                continue;
+            };
+            if !targets.contains(&addr) {
+                targets.push(addr);
+            }
+        }
+        targets
+    }
+
+    /// STUN server used to gather reflexive candidates.
+    pub fn set_stun_server(&mut self, server: &str) {
+        self.ice_agent.set_stun_server(server.to_string());
+    }
+
+    /// Timing of the ICE connectivity checks.
+    pub fn set_ice_config(&mut self, config: IceCheckConfig) {
+        self.ice_agent.set_check_config(config);
+    }
+
+    pub fn ice_config(&self) -> IceCheckConfig {
+        self.ice_agent.check_config()
     }
 
     pub fn media_socket(&self) -> Arc<Mutex<PeerSocket>> {
         Arc::clone(&self.socket)
     }
 
+    /// Socket RTCP must be sent on: the media socket with rtcp-mux, or the
+    /// dedicated port+1 socket when the remote did not accept it.
+    pub fn rtcp_socket(&self) -> Arc<Mutex<PeerSocket>> {
+        match &self.rtcp_socket {
+            Some(socket) => Arc::clone(socket),
+            None => self.media_socket(),
+        }
+    }
+
+    /// Whether RTP and RTCP share the media socket.
+    pub fn rtcp_mux(&self) -> bool {
+        self.rtcp_mux
+    }
+
     /// Access the generated local description.
     pub fn local_description(&self) -> Option<&str> {
         self.local_description.as_deref()
@@ -131,6 +280,43 @@ impl RtcPeerConnection {
         self.ice_agent.has_connection()
     }
 
+    /// Local ICE candidates gathered so far.
+    pub fn local_candidates(&self) -> &[IceCandidate] {
+        self.ice_agent.local_candidates()
+    }
+
+    /// Remote ICE candidates taken from the remote description.
+    pub fn remote_candidates(&self) -> &[IceCandidate] {
+        self.ice_agent.remote_candidates()
+    }
+
+    /// Candidate pairs and the state of their connectivity checks.
+    pub fn candidate_pairs(&self) -> Vec<CandidatePairInfo> {
+        self.ice_agent
+            .candidate_pairs()
+            .iter()
+            .map(CandidatePairInfo::from)
+            .collect()
+    }
+
+    /// The pair the media goes through: addresses, candidate types, priority
+    /// and the RTT of its check. Follows ICE restarts and NAT rebindings.
+    pub fn selected_candidate_pair_info(&self) -> Option<CandidatePairInfo> {
+        self.ice_agent
+            .get_selected_pair()
+            .map(CandidatePairInfo::from)
+    }
+
+    /// How many pairs the last ICE checks formed, pruned and checked.
+    pub fn ice_pair_stats(&self) -> IcePairStats {
+        self.ice_agent.pair_stats()
+    }
+
+    /// Pair selected by ICE, once the checks succeeded.
+    pub fn selected_pair(&self) -> Option<&CandidatePair> {
+        self.ice_agent.get_selected_pair()
+    }
+
     /// Retrieves the ICE credentials announced by the remote peer.
     pub fn remote_credentials(&self) -> Option<(&str, &str)> {
         self.remote_credentials
@@ -138,6 +324,63 @@ impl RtcPeerConnection {
             .map(|(ufrag, pwd)| (ufrag.as_str(), pwd.as_str()))
     }
 
+    /// Sets the bandwidth cap (kbps) announced in the local description.
+    pub fn set_local_bandwidth(&mut self, kbps: Option<u64>) {
+        self.local_bandwidth_kbps = kbps;
+    }
+
+    /// Bandwidth cap (kbps) announced by the remote peer, if any.
+    pub fn remote_bandwidth_kbps(&self) -> Option<u64> {
+        self.remote_bandwidth_kbps
+    }
+
+    /// Payload types to stamp on outgoing audio/video RTP.
+    /// Until a remote description is applied these are our defaults.
+    pub fn negotiated_codecs(&self) -> NegotiatedCodecs {
+        self.negotiated_codecs
+    }
+
+    /// What we want to do with `media_type`, e.g. `RecvOnly` to only watch.
+    /// Applies from the next offer or answer on; the negotiated direction
+    /// may end up narrower if the remote does not send or receive.
+    pub fn set_local_direction(&mut self, media_type: MediaType, direction: MediaDirection) {
+        self.local_directions.set(media_type, direction);
+    }
+
+    /// SSRCs and CNAME our audio/video streams must be sent with.
+    pub fn local_streams(&self) -> &LocalStreams {
+        &self.local_streams
+    }
+
+    /// SSRC routing table learned from the remote description.
+    pub fn ssrc_router(&self) -> &SsrcRouter {
+        &self.ssrc_router
+    }
+
+    /// An incoming packet carried `ssrc`. If it is one of ours, the peer
+    /// picked the same SSRC (RFC 3550 8.2): our stream moves to a new one
+    /// and `(media, old, new)` is returned so senders can follow.
+    pub fn resolve_ssrc_collision(&mut self, ssrc: u32) -> Option<(MediaType, u32, u32)> {
+        let media = self.local_streams.media_of(ssrc)?;
+        let router = &self.ssrc_router;
+        let retired = &self.retired_ssrcs;
+        let old = self.local_streams.replace_ssrc(media, |candidate| {
+            router.route(candidate).is_some() || retired.contains(&candidate)
+        });
+        self.retired_ssrcs.push(old);
+        Some((media, old, self.local_streams.ssrc(media)))
+    }
+
+    /// Whether `ssrc` is or was one of our outgoing streams.
+    pub fn owns_ssrc(&self, ssrc: u32) -> bool {
+        self.local_streams.media_of(ssrc).is_some() || self.retired_ssrcs.contains(&ssrc)
+    }
+
+    /// The remote announced that its stream `old` now sends as `new`.
+    pub fn replace_remote_ssrc(&mut self, old: u32, new: u32) -> Option<MediaType> {
+        self.ssrc_router.replace(old, new)
+    }
+
     // ========== SDP Negotiation ==========
 
     /// Generate an SDP offer to start the negotiation as the controlling peer.
@@ -149,7 +392,17 @@ impl RtcPeerConnection {
         }
 
         self.ensure_host_candidate()?;
-        let offer = build_local_description(&self.ice_agent, self.dtls_session.as_ref());
+        let offer = build_local_description(
+            &self.ice_agent,
+            self.dtls_session.as_ref(),
+            self.local_bandwidth_kbps,
+            &NegotiatedCodecs {
+                directions: self.local_directions,
+                ..NegotiatedCodecs::default()
+            },
+            &self.local_streams,
+            true,
+        );
         self.local_description = Some(offer.clone());
 
         Ok(offer)
@@ -165,17 +418,31 @@ impl RtcPeerConnection {
 
         self.ensure_host_candidate()?;
 
-        let (ufrag, pwd, fingerprint) = process_remote_sdp(&mut self.ice_agent, offer_sdp)?;
+        let remote = process_remote_sdp(&mut self.ice_agent, offer_sdp, self.local_directions)?;
         
-        println!("SDP Offer:\n{}", offer_sdp);
+        log_debug!("SDP Offer:\n{}", offer_sdp);
         
-        let fp = validate_dtls_fingerprint(&fingerprint)?;
+        let fp = validate_dtls_fingerprint(&remote.fingerprint)?;
         self.set_remote_dtls_fingerprint(fp)?;
 
         self.remote_description = Some(offer_sdp.to_string());
-        self.remote_credentials = Some((ufrag, pwd));
-
-        let answer = build_local_description(&self.ice_agent, self.dtls_session.as_ref());
+        self.remote_credentials = Some((remote.ufrag, remote.pwd));
+        self.remote_bandwidth_kbps = remote.bandwidth_kbps;
+        self.negotiated_codecs = remote.codecs;
+        self.ssrc_router = remote.ssrc_router;
+        self.apply_rtcp_mux(remote.rtcp_mux)?;
+
+        // The answer reuses the payload types chosen from the offer, rejects
+        // (port 0) the media without a common codec, answers each direction
+        // with its reverse and only accepts rtcp-mux if it was offered
+        let answer = build_local_description(
+            &self.ice_agent,
+            self.dtls_session.as_ref(),
+            self.local_bandwidth_kbps,
+            &self.negotiated_codecs,
+            &self.local_streams,
+            self.rtcp_mux,
+        );
         self.local_description = Some(answer.clone());
 
         Ok(answer)
@@ -189,17 +456,143 @@ impl RtcPeerConnection {
             ));
         }
 
-        let (ufrag, pwd, fingerprint) = process_remote_sdp(&mut self.ice_agent, remote_sdp)?;
+        let remote = process_remote_sdp(&mut self.ice_agent, remote_sdp, self.local_directions)?;
 
-        let fp = validate_dtls_fingerprint(&fingerprint)?;
+        let fp = validate_dtls_fingerprint(&remote.fingerprint)?;
         self.set_remote_dtls_fingerprint(fp)?;
 
         self.remote_description = Some(remote_sdp.to_string());
-        self.remote_credentials = Some((ufrag, pwd));
+        self.remote_credentials = Some((remote.ufrag, remote.pwd));
+        self.remote_bandwidth_kbps = remote.bandwidth_kbps;
+        self.negotiated_codecs = remote.codecs;
+        self.ssrc_router = remote.ssrc_router;
+        self.apply_rtcp_mux(remote.rtcp_mux)?;
+
+        Ok(())
+    }
+
+    // ========== ICE Restart ==========
+
+    /// Starts an ICE restart as the controlling peer: new credentials and a
+    /// new offer for the same call. The current path is used until the
+    /// answer arrives and a consent check confirms a new one.
+    pub fn create_restart_offer(&mut self) -> Result<String, PeerConnectionError> {
+        if !self.role.is_controlling() {
+            return Err(PeerConnectionError::InvalidRole(
+                "ICE restart offers can only be created by a controlling peer",
+            ));
+        }
+
+        self.restart_ice_agent()?;
+        self.create_offer()
+    }
+
+    /// Answers the peer's ICE restart offer. The DTLS session and the SRTP
+    /// keys survive the restart, so the offer must bring the same certificate.
+    pub fn process_restart_offer(
+        &mut self,
+        offer_sdp: &str,
+    ) -> Result<String, PeerConnectionError> {
+        if self.role.is_controlling() {
+            return Err(PeerConnectionError::InvalidRole(
+                "ICE restart offers can only be answered by a controlled peer",
+            ));
+        }
+
+        self.check_restart_fingerprint(offer_sdp)?;
+        self.restart_ice_agent()?;
+        self.process_offer(offer_sdp)
+    }
+
+    /// Applies the answer to our ICE restart offer.
+    pub fn apply_restart_answer(&mut self, answer_sdp: &str) -> Result<(), PeerConnectionError> {
+        self.check_restart_fingerprint(answer_sdp)?;
+        self.set_remote_description(answer_sdp)
+    }
+
+    /// A different certificate would mean someone else answering for the peer.
+    fn check_restart_fingerprint(&self, sdp: &str) -> Result<(), PeerConnectionError> {
+        let fingerprint = remote_dtls_fingerprint(sdp)?;
+        let expected = self
+            .dtls_session
+            .as_ref()
+            .and_then(|session| session.remote_fingerprint());
+        match (fingerprint.as_deref(), expected) {
+            (Some(new), Some(known)) if new.eq_ignore_ascii_case(known) => Ok(()),
+            _ => Err(PeerConnectionError::Dtls(
+                "DTLS fingerprint changed in the ICE restart".to_string(),
+            )),
+        }
+    }
 
+    /// Fresh credentials and remote state. The host candidate is registered
+    /// again in case the interface changed (e.g. another WiFi); reflexive
+    /// candidates are not gathered again, the listener owns the socket now.
+    fn restart_ice_agent(&mut self) -> Result<(), PeerConnectionError> {
+        let local_addr = self.local_addr()?;
+        self.ice_agent.restart();
+        self.ice_agent.register_host_candidate(local_addr);
         Ok(())
     }
 
+    /// Local description packed as a one-line session code, for pairing by
+    /// hand without the signaling server. The description already lists
+    /// every gathered candidate.
+    pub fn export_session_blob(&self) -> Result<String, PeerConnectionError> {
+        let sdp = self
+            .local_description
+            .as_deref()
+            .ok_or_else(|| PeerConnectionError::Sdp("No local description yet".to_string()))?;
+        encode_session_blob(sdp).map_err(PeerConnectionError::Io)
+    }
+
+    /// Applies a session code from the other peer: the offer when we are
+    /// controlled, in which case the answer is returned, or the answer when
+    /// we are controlling.
+    pub fn import_session_blob(
+        &mut self,
+        blob: &str,
+    ) -> Result<Option<String>, PeerConnectionError> {
+        let sdp =
+            decode_session_blob(blob).map_err(|err| PeerConnectionError::Sdp(err.to_string()))?;
+        if self.role.is_controlling() {
+            self.set_remote_description(&sdp)?;
+            Ok(None)
+        } else {
+            self.process_offer(&sdp).map(Some)
+        }
+    }
+
+    /// Records the negotiated rtcp-mux mode. Without mux, RTCP goes through a
+    /// second socket bound to the port next to the media one.
+    fn apply_rtcp_mux(&mut self, remote_supports_mux: bool) -> Result<(), PeerConnectionError> {
+        self.rtcp_mux = remote_supports_mux;
+        if self.rtcp_mux || self.rtcp_socket.is_some() {
+            return Ok(());
+        }
+
+        let local_addr = self.local_addr()?;
+        let rtcp_port = local_addr
+            .port()
+            .checked_add(1)
+            .filter(|port| self.port_range.is_none_or(|range| range.contains(*port)))
+            .ok_or_else(|| {
+                PeerConnectionError::Sdp("No port available for RTCP without rtcp-mux".to_string())
+            })?;
+        let rtcp_addr = SocketAddr::new(local_addr.ip(), rtcp_port).to_string();
+        let mut rtcp_socket = PeerSocket::new(Some(&rtcp_addr))?;
+        rtcp_socket.listener(None)?;
+        log_debug!("Remote does not support rtcp-mux, RTCP on {}", rtcp_addr);
+        self.rtcp_socket = Some(Arc::new(Mutex::new(rtcp_socket)));
+        Ok(())
+    }
+
+    /// Receiver of the dedicated RTCP socket, only present without rtcp-mux.
+    pub fn take_rtcp_receiver(&mut self) -> Option<Receiver<(Vec<u8>, SocketAddr)>> {
+        let socket = self.rtcp_socket.as_ref()?;
+        socket.lock().ok()?.get_receiver().ok()
+    }
+
     // ========== ICE Connectivity ==========
 
     /// Start ICE checks and register the selected address in the socket.
@@ -227,6 +620,20 @@ impl RtcPeerConnection {
                 .map_err(|_| PeerConnectionError::Socket(PeerSocketErr::PoisonedThread))?
                 .add_remote_address(&remote_addr)
                 .map_err(PeerConnectionError::Io)?;
+
+            // Without rtcp-mux the remote expects RTCP on the adjacent port
+            if let Some(rtcp_socket) = &self.rtcp_socket {
+                let rtcp_remote = format!(
+                    "{}:{}",
+                    pair.remote_candidate.address,
+                    pair.remote_candidate.port.saturating_add(1)
+                );
+                rtcp_socket
+                    .lock()
+                    .map_err(|_| PeerConnectionError::Socket(PeerSocketErr::PoisonedThread))?
+                    .add_remote_address(&rtcp_remote)
+                    .map_err(PeerConnectionError::Io)?;
+            }
         }
 
         Ok(())
@@ -293,16 +700,67 @@ impl RtcPeerConnection {
 
     /// Configures the shared SRTP key (32 bytes).
     fn set_srtp_key(&mut self, key: &[u8]) {
-        self.srtp_context = SrtpContext::new(key);
+        self.srtp_context.replace(SrtpContext::new(key));
     }
 
     /// Returns the SRTP context if available.
     pub fn srtp_context(&self) -> Option<SrtpContext> {
+        self.srtp_context.get()
+    }
+
+    /// Handle to the SRTP context that follows later rekeys. Workers should
+    /// take this one instead of a copy of [`Self::srtp_context`].
+    pub fn shared_srtp_context(&self) -> SharedSrtpContext {
         self.srtp_context.clone()
     }
 
+    /// Re-exports the SRTP keying material after a renegotiated DTLS
+    /// handshake. Every worker holding the shared context switches to it.
+    pub fn rekey_srtp(&mut self) -> Result<(), PeerConnectionError> {
+        let session = self
+            .dtls_session
+            .as_ref()
+            .ok_or_else(|| PeerConnectionError::Dtls("DTLS session not available".to_string()))?;
+        let key = session
+            .export_srtp_keying_material(32)
+            .map_err(PeerConnectionError::Dtls)?;
+        self.set_srtp_key(&key);
+        Ok(())
+    }
+
     // ========== DTLS ==========
 
+    /// Restricts the cipher suites and versions of the DTLS handshake. The
+    /// session is recreated, with the identity of `config` if it has one, so
+    /// it must be called before the local description is created.
+    pub fn set_dtls_config(&mut self, config: &DtlsConfig) -> Result<(), PeerConnectionError> {
+        if self.local_description.is_some() {
+            return Err(PeerConnectionError::Dtls(
+                "DTLS config must be set before the local description".to_string(),
+            ));
+        }
+        let dtls_role = match self.role {
+            PeerConnectionRole::Controlling => DtlsRole::Client,
+            PeerConnectionRole::Controlled => DtlsRole::Server,
+        };
+        let mut config = config.clone();
+        if config.identity.is_none() {
+            config.identity = self.dtls_identity.clone();
+        }
+        let session =
+            DtlsSession::with_config(dtls_role, &config).map_err(PeerConnectionError::Dtls)?;
+        self.dtls_session = Some(session);
+        self.dtls_identity = config.identity;
+        Ok(())
+    }
+
+    /// Protocol version and cipher suite agreed in the DTLS handshake,
+    /// e.g. `("DTLSv1.2", "ECDHE-RSA-AES128-GCM-SHA256")`.
+    pub fn dtls_negotiated(&self) -> Option<(&'static str, &'static str)> {
+        let session = self.dtls_session.as_ref()?;
+        Some((session.negotiated_version()?, session.negotiated_cipher()?))
+    }
+
     /// Returns the local DTLS certificate fingerprint for SDP.
     pub fn dtls_fingerprint(&self) -> Option<String> {
         self.dtls_session
@@ -310,6 +768,24 @@ impl RtcPeerConnection {
             .map(|s| s.certificate_fingerprint())
     }
 
+    /// Fingerprint of the certificate the peer presented in the DTLS
+    /// handshake, once it matched the one in its SDP.
+    pub fn remote_certificate_fingerprint(&self) -> Option<String> {
+        self.dtls_session
+            .as_ref()
+            .and_then(|s| s.peer_certificate_fingerprint())
+            .map(str::to_string)
+    }
+
+    /// Number both users can compare out-of-band to check that nobody is
+    /// in the middle of the call; see [`safety_number`]. Available once the
+    /// DTLS handshake completes.
+    pub fn safety_number(&self) -> Option<String> {
+        let session = self.dtls_session.as_ref()?;
+        let remote = session.peer_certificate_fingerprint()?;
+        Some(safety_number(&session.certificate_fingerprint(), remote))
+    }
+
     /// Sets the remote peer's DTLS fingerprint (extracted from remote SDP).
     pub fn set_remote_dtls_fingerprint(
         &mut self,
@@ -368,7 +844,7 @@ impl RtcPeerConnection {
                 .map_err(|e| PeerConnectionError::Dtls(e.to_string()))?;
 
             self.set_srtp_key(&key);
-            println!("DEBUG: SRTP key successfully exported from DTLS session.");
+            log_debug!("SRTP key successfully exported from DTLS session.");
 
             Ok(())
         } else {
@@ -384,7 +860,18 @@ impl RtcPeerConnection {
             .as_ref()
             .map(|s| s.is_handshake_complete())
             .unwrap_or(false)
-            && self.srtp_context.is_some()
+            && self.srtp_context.is_set()
+    }
+
+    /// Current DTLS state.
+    pub fn dtls_state(&self) -> DtlsState {
+        if self.dtls_session.is_none() {
+            DtlsState::Unavailable
+        } else if self.is_dtls_connected() {
+            DtlsState::Connected
+        } else {
+            DtlsState::Pending
+        }
     }
 
     /// Returns whether a DTLS session object is present.
@@ -412,12 +899,14 @@ impl RtcPeerConnection {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::ice::CandidatePairState;
     use std::thread;
-    use std::time::Duration;
+    use std::time::{Duration, Instant};
 
     #[test]
     fn controlling_peer_generates_offer() -> Result<(), PeerConnectionError> {
-        let mut pc = RtcPeerConnection::new(Some("127.0.0.1:0"), PeerConnectionRole::Controlling)?;
+        let mut pc =
+            RtcPeerConnection::new(Some("127.0.0.1:0"), PeerConnectionRole::Controlling, None)?;
 
         let offer = pc.create_offer()?;
 
@@ -426,14 +915,72 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn dtls_config_only_applies_before_the_local_description() -> Result<(), PeerConnectionError> {
+        let mut pc =
+            RtcPeerConnection::new(Some("127.0.0.1:0"), PeerConnectionRole::Controlling, None)?;
+        let default_fingerprint = pc.dtls_fingerprint();
+        let config = DtlsConfig {
+            cipher_list: Some("ECDHE-ECDSA-AES128-GCM-SHA256".to_string()),
+            ..DtlsConfig::default()
+        };
+        pc.set_dtls_config(&config)?;
+        // Sesión nueva con la misma identidad
+        assert_eq!(pc.dtls_fingerprint(), default_fingerprint);
+        let identity = Arc::new(DtlsIdentity::generate().unwrap());
+        pc.set_dtls_config(&DtlsConfig {
+            identity: Some(Arc::clone(&identity)),
+            ..config
+        })?;
+        assert_eq!(
+            pc.dtls_fingerprint().as_deref(),
+            Some(identity.fingerprint())
+        );
+
+        let offer = pc.create_offer()?;
+        assert!(offer.contains(&pc.dtls_fingerprint().unwrap()));
+        assert!(matches!(
+            pc.set_dtls_config(&DtlsConfig::default()),
+            Err(PeerConnectionError::Dtls(_))
+        ));
+        assert!(pc.dtls_negotiated().is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn peer_connections_with_a_shared_identity_are_cheap() -> Result<(), PeerConnectionError> {
+        let identity = Arc::new(DtlsIdentity::generate().unwrap());
+        let started = Instant::now();
+        let pcs = (0..10)
+            .map(|_| {
+                RtcPeerConnection::new(
+                    Some("127.0.0.1:0"),
+                    PeerConnectionRole::Controlling,
+                    Some(Arc::clone(&identity)),
+                )
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let elapsed = started.elapsed();
+
+        // Con una llave RSA nueva por conexión esto tardaba segundos
+        assert!(elapsed < Duration::from_millis(500), "took {:?}", elapsed);
+        for pc in &pcs {
+            assert_eq!(
+                pc.dtls_fingerprint().as_deref(),
+                Some(identity.fingerprint())
+            );
+        }
+        Ok(())
+    }
+
     #[test]
     fn controlled_peer_process_offer_and_generates_answer() -> Result<(), PeerConnectionError> {
         let mut offerer =
-            RtcPeerConnection::new(Some("127.0.0.1:0"), PeerConnectionRole::Controlling)?;
+            RtcPeerConnection::new(Some("127.0.0.1:0"), PeerConnectionRole::Controlling, None)?;
         let offer = offerer.create_offer()?;
 
         let mut answerer =
-            RtcPeerConnection::new(Some("127.0.0.1:0"), PeerConnectionRole::Controlled)?;
+            RtcPeerConnection::new(Some("127.0.0.1:0"), PeerConnectionRole::Controlled, None)?;
 
         let answer = answerer.process_offer(&offer)?;
 
@@ -447,15 +994,384 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn ice_restart_renews_credentials_and_keeps_the_certificate() -> Result<(), PeerConnectionError>
+    {
+        let mut offerer =
+            RtcPeerConnection::new(Some("127.0.0.1:0"), PeerConnectionRole::Controlling, None)?;
+        // Each peer has its own certificate, as two separate apps would
+        let mut answerer = RtcPeerConnection::new(
+            Some("127.0.0.1:0"),
+            PeerConnectionRole::Controlled,
+            Some(Arc::new(DtlsIdentity::generate().unwrap())),
+        )?;
+        let answer = answerer.process_offer(&offerer.create_offer()?)?;
+        offerer.set_remote_description(&answer)?;
+        let old_credentials = answerer
+            .remote_credentials()
+            .map(|(u, p)| (u.to_string(), p.to_string()));
+        let answerer_addr = answerer.local_addr()?;
+        offerer.update_remote_addr(answerer_addr);
+        assert!(offerer.selected_candidate_pair_info().is_some());
+
+        let restart_offer = offerer.create_restart_offer()?;
+        assert!(offerer.remote_candidates().is_empty());
+        assert!(offerer.selected_candidate_pair_info().is_none());
+        let restart_answer = answerer.process_restart_offer(&restart_offer)?;
+        offerer.apply_restart_answer(&restart_answer)?;
+
+        // The pair info follows the media once it flows again
+        offerer.update_remote_addr(answerer_addr);
+        let info = offerer.selected_candidate_pair_info().unwrap();
+        assert_eq!(info.remote_address, answerer_addr.to_string());
+        assert_eq!(info.state, CandidatePairState::Succeeded);
+
+        let new_credentials = answerer
+            .remote_credentials()
+            .map(|(u, p)| (u.to_string(), p.to_string()));
+        assert_ne!(new_credentials, old_credentials);
+        assert!(!offerer.remote_candidates().is_empty());
+        assert!(answerer.process_restart_offer(&restart_answer).is_err());
+
+        // A restart offer from another peer brings another certificate
+        let mut impostor = RtcPeerConnection::new(
+            Some("127.0.0.1:0"),
+            PeerConnectionRole::Controlling,
+            Some(Arc::new(DtlsIdentity::generate().unwrap())),
+        )?;
+        let forged = impostor.create_offer()?;
+        assert!(matches!(
+            answerer.process_restart_offer(&forged),
+            Err(PeerConnectionError::Dtls(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn diagnostics_accessors_expose_candidates_and_state() -> Result<(), PeerConnectionError> {
+        let mut offerer =
+            RtcPeerConnection::new(Some("127.0.0.1:0"), PeerConnectionRole::Controlling, None)?;
+        let offer = offerer.create_offer()?;
+
+        let mut answerer =
+            RtcPeerConnection::new(Some("127.0.0.1:0"), PeerConnectionRole::Controlled, None)?;
+        answerer.process_offer(&offer)?;
+
+        assert!(!answerer.local_candidates().is_empty());
+        assert!(!answerer.remote_candidates().is_empty());
+        assert!(!answerer.candidate_pairs().is_empty());
+        assert!(answerer.selected_pair().is_none());
+        assert_eq!(answerer.dtls_state(), DtlsState::Pending);
+        Ok(())
+    }
+
+    #[test]
+    fn session_blobs_pair_peers_without_signaling() -> Result<(), PeerConnectionError> {
+        let mut offerer =
+            RtcPeerConnection::new(Some("127.0.0.1:0"), PeerConnectionRole::Controlling, None)?;
+        assert!(offerer.export_session_blob().is_err());
+        offerer.create_offer()?;
+        let offer_blob = offerer.export_session_blob()?;
+        // Tiene que entrar en un QR
+        assert!(
+            offer_blob.len() < 1536,
+            "blob of {} bytes",
+            offer_blob.len()
+        );
+
+        let mut answerer =
+            RtcPeerConnection::new(Some("127.0.0.1:0"), PeerConnectionRole::Controlled, None)?;
+        let answer = answerer.import_session_blob(&offer_blob)?;
+        assert_eq!(answer.as_deref(), answerer.local_description());
+        assert_eq!(answerer.remote_description(), offerer.local_description());
+
+        let answer_blob = answerer.export_session_blob()?;
+        assert_eq!(offerer.import_session_blob(&answer_blob)?, None);
+        assert_eq!(offerer.remote_description(), answer.as_deref());
+
+        let truncated = &answer_blob[..answer_blob.len() / 2];
+        assert!(matches!(
+            offerer.import_session_blob(truncated),
+            Err(PeerConnectionError::Sdp(msg)) if msg.contains("incomplete")
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn answer_reuses_remapped_payload_types() -> Result<(), PeerConnectionError> {
+        let mut offerer =
+            RtcPeerConnection::new(Some("127.0.0.1:0"), PeerConnectionRole::Controlling, None)?;
+        let offer = offerer
+            .create_offer()?
+            .replace("m=audio 9 RTP/SAVP 111", "m=audio 9 RTP/SAVP 109")
+            .replace("a=rtpmap:111 opus", "a=rtpmap:109 opus");
+
+        let mut answerer =
+            RtcPeerConnection::new(Some("127.0.0.1:0"), PeerConnectionRole::Controlled, None)?;
+        let answer = answerer.process_offer(&offer)?;
+
+        assert_eq!(answerer.negotiated_codecs().audio_pt, 109);
+        assert!(answer.contains("a=rtpmap:109 opus/48000/2"));
+
+        offerer.set_remote_description(&answer)?;
+        assert_eq!(offerer.negotiated_codecs(), answerer.negotiated_codecs());
+        Ok(())
+    }
+
+    #[test]
+    fn answer_echoes_only_the_supported_payload_type_among_several_offered(
+    ) -> Result<(), PeerConnectionError> {
+        let mut offerer =
+            RtcPeerConnection::new(Some("127.0.0.1:0"), PeerConnectionRole::Controlling, None)?;
+        // Codecs we do not support come first in both sections
+        let offer = offerer
+            .create_offer()?
+            .replace(
+                "m=audio 9 RTP/SAVP 111",
+                "m=audio 9 RTP/SAVP 0 110\na=rtpmap:0 PCMU/8000",
+            )
+            .replace("a=rtpmap:111 opus", "a=rtpmap:110 opus")
+            .replace(
+                "m=video 9 RTP/SAVP 96",
+                "m=video 9 RTP/SAVP 98 120\na=rtpmap:98 VP8/90000",
+            )
+            .replace("a=rtpmap:96 H264", "a=rtpmap:120 H264");
+
+        let mut answerer =
+            RtcPeerConnection::new(Some("127.0.0.1:0"), PeerConnectionRole::Controlled, None)?;
+        let answer = answerer.process_offer(&offer)?;
+
+        assert!(answer.lines().any(|line| line == "m=audio 9 RTP/SAVP 110"));
+        assert!(answer.contains("a=rtpmap:110 opus/48000/2"));
+        assert!(answer.lines().any(|line| line == "m=video 9 RTP/SAVP 120"));
+        assert!(answer.contains("a=rtpmap:120 H264/90000"));
+        assert!(!answer.contains("PCMU") && !answer.contains("VP8"));
+        Ok(())
+    }
+
+    #[test]
+    fn offer_answer_exchange_builds_ssrc_routes() -> Result<(), PeerConnectionError> {
+        use crate::protocols::sdp::media_type::MediaType;
+
+        let mut offerer =
+            RtcPeerConnection::new(Some("127.0.0.1:0"), PeerConnectionRole::Controlling, None)?;
+        let mut answerer =
+            RtcPeerConnection::new(Some("127.0.0.1:0"), PeerConnectionRole::Controlled, None)?;
+
+        let offer = offerer.create_offer()?;
+        let answer = answerer.process_offer(&offer)?;
+        offerer.set_remote_description(&answer)?;
+
+        let offerer_streams = offerer.local_streams().clone();
+        let answerer_streams = answerer.local_streams().clone();
+        assert_eq!(
+            answerer.ssrc_router().route(offerer_streams.audio_ssrc),
+            Some(MediaType::Audio)
+        );
+        assert_eq!(
+            answerer.ssrc_router().route(offerer_streams.video_ssrc),
+            Some(MediaType::Video)
+        );
+        assert_eq!(
+            offerer.ssrc_router().route(answerer_streams.audio_ssrc),
+            Some(MediaType::Audio)
+        );
+        assert_eq!(
+            offerer.ssrc_router().route(answerer_streams.video_ssrc),
+            Some(MediaType::Video)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn ssrc_collision_moves_our_stream_once() -> Result<(), PeerConnectionError> {
+        let mut pc =
+            RtcPeerConnection::new(Some("127.0.0.1:0"), PeerConnectionRole::Controlling, None)?;
+        let video = pc.local_streams().video_ssrc;
+
+        let (media, old, new) = pc.resolve_ssrc_collision(video).unwrap();
+        assert_eq!((media, old), (MediaType::Video, video));
+        assert_eq!(pc.local_streams().video_ssrc, new);
+        assert!(pc.owns_ssrc(old) && pc.owns_ssrc(new));
+        // Los paquetes que siguen llegando con el SSRC viejo ya no chocan
+        assert_eq!(pc.resolve_ssrc_collision(old), None);
+        Ok(())
+    }
+
+    #[test]
+    fn answer_rejects_video_and_keeps_audio() -> Result<(), PeerConnectionError> {
+        use crate::protocols::sdp::media_type::MediaType;
+
+        let mut offerer =
+            RtcPeerConnection::new(Some("127.0.0.1:0"), PeerConnectionRole::Controlling, None)?;
+        let offer = offerer
+            .create_offer()?
+            .replace("a=rtpmap:96 H264/90000", "a=rtpmap:96 VP8/90000");
+
+        let mut answerer =
+            RtcPeerConnection::new(Some("127.0.0.1:0"), PeerConnectionRole::Controlled, None)?;
+        let answer = answerer.process_offer(&offer)?;
+
+        assert!(answer.contains("m=video 0 RTP/SAVP 96"));
+        assert!(answer.contains("m=audio 9 RTP/SAVP 111"));
+        assert!(!answer.contains("H264"));
+        assert!(answerer.negotiated_codecs().is_accepted(MediaType::Audio));
+        assert!(!answerer.negotiated_codecs().is_accepted(MediaType::Video));
+
+        offerer.set_remote_description(&answer)?;
+        assert!(offerer.negotiated_codecs().is_accepted(MediaType::Audio));
+        assert!(!offerer.negotiated_codecs().is_accepted(MediaType::Video));
+        assert_eq!(
+            offerer.ssrc_router().route(answerer.local_streams().audio_ssrc),
+            Some(MediaType::Audio)
+        );
+        assert_eq!(
+            offerer.ssrc_router().route(answerer.local_streams().video_ssrc),
+            None
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn one_way_video_is_answered_with_the_reverse_direction() -> Result<(), PeerConnectionError> {
+        // A camera that only broadcasts to someone who only watches
+        let mut offerer =
+            RtcPeerConnection::new(Some("127.0.0.1:0"), PeerConnectionRole::Controlling, None)?;
+        offerer.set_local_direction(MediaType::Video, MediaDirection::SendOnly);
+        let offer = offerer.create_offer()?;
+        assert!(offer.contains("a=mid:video\na=sendonly\n"));
+        assert!(offer.contains("a=mid:audio\na=sendrecv\n"));
+
+        let mut answerer =
+            RtcPeerConnection::new(Some("127.0.0.1:0"), PeerConnectionRole::Controlled, None)?;
+        let answer = answerer.process_offer(&offer)?;
+        assert!(answer.contains("a=mid:video\na=recvonly\n"));
+        let codecs = answerer.negotiated_codecs();
+        assert!(!codecs.sends(MediaType::Video));
+        assert!(codecs.receives(MediaType::Video));
+        assert!(codecs.sends(MediaType::Audio) && codecs.receives(MediaType::Audio));
+
+        offerer.set_remote_description(&answer)?;
+        let codecs = offerer.negotiated_codecs();
+        assert_eq!(codecs.direction(MediaType::Video), MediaDirection::SendOnly);
+        assert!(codecs.sends(MediaType::Video));
+        assert!(!codecs.receives(MediaType::Video));
+        Ok(())
+    }
+
+    #[test]
+    fn answerer_that_only_watches_answers_recvonly() -> Result<(), PeerConnectionError> {
+        let mut offerer =
+            RtcPeerConnection::new(Some("127.0.0.1:0"), PeerConnectionRole::Controlling, None)?;
+        let offer = offerer.create_offer()?;
+
+        let mut answerer =
+            RtcPeerConnection::new(Some("127.0.0.1:0"), PeerConnectionRole::Controlled, None)?;
+        answerer.set_local_direction(MediaType::Audio, MediaDirection::RecvOnly);
+        answerer.set_local_direction(MediaType::Video, MediaDirection::RecvOnly);
+        let answer = answerer.process_offer(&offer)?;
+        assert!(answer.contains("a=mid:audio\na=recvonly\n"));
+        assert!(answer.contains("a=mid:video\na=recvonly\n"));
+        assert!(!answerer.negotiated_codecs().sends(MediaType::Audio));
+
+        // The offerer asked for sendrecv and ends up only sending
+        offerer.set_remote_description(&answer)?;
+        for media_type in [MediaType::Audio, MediaType::Video] {
+            assert_eq!(
+                offerer.negotiated_codecs().direction(media_type),
+                MediaDirection::SendOnly
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn process_offer_without_common_codec_fails() -> Result<(), PeerConnectionError> {
+        let mut offerer =
+            RtcPeerConnection::new(Some("127.0.0.1:0"), PeerConnectionRole::Controlling, None)?;
+        let offer = offerer
+            .create_offer()?
+            .replace("a=rtpmap:96 H264/90000", "a=rtpmap:96 VP8/90000")
+            .replace("a=rtpmap:111 opus/48000/2", "a=rtpmap:111 PCMU/8000");
+
+        let mut answerer =
+            RtcPeerConnection::new(Some("127.0.0.1:0"), PeerConnectionRole::Controlled, None)?;
+
+        assert!(matches!(
+            answerer.process_offer(&offer),
+            Err(PeerConnectionError::NoCommonCodec(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn rtcp_mux_is_negotiated_by_default() -> Result<(), PeerConnectionError> {
+        let mut offerer =
+            RtcPeerConnection::new(Some("127.0.0.1:0"), PeerConnectionRole::Controlling, None)?;
+        let offer = offerer.create_offer()?;
+        assert!(offer.contains("a=rtcp-mux"));
+
+        let mut answerer =
+            RtcPeerConnection::new(Some("127.0.0.1:0"), PeerConnectionRole::Controlled, None)?;
+        let answer = answerer.process_offer(&offer)?;
+        assert!(answer.contains("a=rtcp-mux"));
+        assert!(answerer.rtcp_mux());
+
+        offerer.set_remote_description(&answer)?;
+        assert!(offerer.rtcp_mux());
+        assert!(offerer.take_rtcp_receiver().is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn answer_without_rtcp_mux_opens_adjacent_socket() -> Result<(), PeerConnectionError> {
+        let mut offerer =
+            RtcPeerConnection::new(Some("127.0.0.1:0"), PeerConnectionRole::Controlling, None)?;
+        let offer = offerer.create_offer()?.replace("a=rtcp-mux\n", "");
+
+        let mut answerer =
+            RtcPeerConnection::new(Some("127.0.0.1:0"), PeerConnectionRole::Controlled, None)?;
+        let answer = answerer.process_offer(&offer)?;
+
+        assert!(!answer.contains("a=rtcp-mux"));
+        assert!(!answerer.rtcp_mux());
+        let media_port = answerer.local_addr()?.port();
+        let rtcp_port = answerer.rtcp_socket().lock().unwrap().local_addr().port();
+        assert_eq!(rtcp_port, media_port + 1);
+        assert!(answerer.take_rtcp_receiver().is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn port_range_bounds_the_media_and_rtcp_sockets() -> Result<(), PeerConnectionError> {
+        let port = std::net::UdpSocket::bind("0.0.0.0:0")?.local_addr()?.port();
+        let range = PortRange::new(port, port).unwrap();
+        let mut answerer =
+            RtcPeerConnection::with_port_range(range, PeerConnectionRole::Controlled, None)?;
+        assert_eq!(answerer.local_addr()?.port(), port);
+
+        // Sin rtcp-mux, RTCP iría al puerto siguiente, fuera del rango
+        let mut offerer =
+            RtcPeerConnection::new(Some("127.0.0.1:0"), PeerConnectionRole::Controlling, None)?;
+        let offer = offerer.create_offer()?.replace("a=rtcp-mux\n", "");
+        assert!(matches!(
+            answerer.process_offer(&offer),
+            Err(PeerConnectionError::Sdp(_))
+        ));
+        Ok(())
+    }
+
     #[test]
     fn dtls_handshake_integration_test() -> Result<(), PeerConnectionError> {
         let offerer_pc = Arc::new(Mutex::new(RtcPeerConnection::new(
             Some("0.0.0.0:8444"),
             PeerConnectionRole::Controlling,
+            None,
         )?));
         let answerer_pc = Arc::new(Mutex::new(RtcPeerConnection::new(
             Some("0.0.0.0:8445"),
             PeerConnectionRole::Controlled,
+            None,
         )?));
         println!("RTC PeerConnections created.");
 
@@ -514,7 +1430,7 @@ mod tests {
             answerer_result.err()
         );
 
-        let offerer_lock = offerer_pc.lock().unwrap();
+        let mut offerer_lock = offerer_pc.lock().unwrap();
         let answerer_lock = answerer_pc.lock().unwrap();
 
         assert!(
@@ -535,6 +1451,28 @@ mod tests {
             "Answerer SRTP context is missing"
         );
 
+        // Both ends derived the same key from the DTLS keying material
+        let offerer_srtp = offerer_lock.srtp_context().unwrap();
+        let answerer_srtp = answerer_lock.srtp_context().unwrap();
+        let cipher = offerer_srtp.protect(10, 960, b"rtp payload").unwrap();
+        assert_eq!(answerer_srtp.unprotect(10, 960, &cipher).unwrap(), b"rtp payload");
+
+        // A rekey is seen by handles taken before it
+        let shared = offerer_lock.shared_srtp_context();
+        let generation = shared.generation();
+        offerer_lock.rekey_srtp()?;
+        assert_eq!(shared.generation(), generation + 1);
+        assert!(shared.is_set());
+
+        // After a path change (ICE restart) the DTLS records follow the media
+        let new_path = std::net::UdpSocket::bind("127.0.0.1:0")?;
+        new_path.set_read_timeout(Some(Duration::from_secs(2)))?;
+        offerer_lock.update_remote_addr(new_path.local_addr()?);
+        offerer_lock.dtls_write(b"data channel")?;
+        let mut buf = [0u8; 1500];
+        let (len, _) = new_path.recv_from(&mut buf)?;
+        assert!(len > 0 && (20..=63).contains(&buf[0]));
+
         Ok(())
     }
 }
@@ -1 +1,2 @@
 pub mod rtc_rtp_sender;
+pub mod rtp_pacer;
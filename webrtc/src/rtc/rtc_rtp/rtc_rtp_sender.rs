@@ -3,38 +3,71 @@ use crate::codec::h264::fu_a::FragmentationUnitTypeA;
 use crate::codec::h264::fu_header::FuHeader;
 use crate::codec::h264::nalu_header::NaluHeader;
 use crate::codec::h264::single_nal_unit_packet::SingleNalUnitPacket;
-use crate::crypto::srtp::SrtpContext;
-use crate::protocols::rtp::constants::rtp_const::RTP_H264_TYPE;
+use crate::crypto::srtp::{SharedSrtpContext, SrtpCache};
 use crate::protocols::rtp::h264_video_type::H264VideoType;
+use crate::protocols::rtp::header_extension::{abs_send_time_now, HeaderExtension};
 use crate::protocols::rtp::payload_type::PayloadType;
 use crate::protocols::rtp::rtp_header::RtpHeader;
 use crate::protocols::rtp::rtp_packet::RtpPacket;
 use crate::rtc::rtc_err::RtcError;
+use crate::rtc::rtc_rtp::rtp_pacer::RtpPacer;
 use crate::rtc::socket::peer_socket::PeerSocket;
 use crate::worker_thread::media_metrics::MediaMetrics;
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Instant;
 
 pub struct RtcRtpSender {
     ssrc: u32,
+    payload_type: u8,
     sequence_number: u16,
     timestamp: u32,
+    frame_ticks: u32,
     metrics: Arc<Mutex<MediaMetrics>>,
-    srtp: Option<SrtpContext>,
+    srtp: SrtpCache,
+    abs_send_time_id: Option<u8>,
+    pacer: RtpPacer,
 }
 impl RtcRtpSender {
-    pub fn new(ssrc: u32, metrics: Arc<Mutex<MediaMetrics>>, key: Option<Vec<u8>>) -> Self {
+    pub fn new(
+        ssrc: u32,
+        payload_type: u8,
+        metrics: Arc<Mutex<MediaMetrics>>,
+        srtp: SharedSrtpContext,
+    ) -> Self {
         RtcRtpSender {
             ssrc,
+            payload_type,
             sequence_number: 0,
             timestamp: 0,
+            frame_ticks: 3000,
             metrics,
-            srtp: key.and_then(|k| SrtpContext::new(&k)),
+            srtp: srtp.cache(),
+            abs_send_time_id: None,
+            pacer: RtpPacer::new(None),
         }
     }
+    /// Stamps abs-send-time with the negotiated `a=extmap` id on every packet.
+    pub fn set_abs_send_time_id(&mut self, id: Option<u8>) {
+        self.abs_send_time_id = id;
+    }
+    /// Sends from now on with `ssrc`, after an SSRC collision.
+    pub fn set_ssrc(&mut self, ssrc: u32) {
+        self.ssrc = ssrc;
+    }
+    /// Adjusts the timestamp step to a new frame rate.
+    /// Sequence number and timestamp keep running, so the receiver sees one stream.
+    pub fn set_frame_rate(&mut self, fps: u32) {
+        self.frame_ticks = 90_000 / fps.max(1);
+    }
+    /// Paces packets at `rate_kbps`, `None` sends them as they come.
+    pub fn set_pacing_rate(&mut self, rate_kbps: Option<u64>) {
+        self.pacer.set_rate_kbps(rate_kbps);
+    }
+
     pub fn send_video_payload(
         &mut self,
         frame_bytes: Vec<u8>,
-        rtp_socket: &mut PeerSocket,
+        rtp_socket: &Mutex<PeerSocket>,
     ) -> Result<(), RtcError> {
         let nalus = H264Encoder::split_by_startcode(&frame_bytes);
         let total_nalus = nalus.len();
@@ -49,8 +82,8 @@ impl RtcRtpSender {
             }
         }
 
-        // clock rate 90kHz, target 30 fps -> 3000 ticks por frame
-        self.timestamp = self.timestamp.wrapping_add(3000);
+        // clock rate 90kHz, 30 fps -> 3000 ticks por frame
+        self.timestamp = self.timestamp.wrapping_add(self.frame_ticks);
         Ok(())
     }
     fn send_single_nalu(
@@ -58,7 +91,7 @@ impl RtcRtpSender {
         header: NaluHeader,
         nalu: Vec<u8>,
         last_nalu: bool,
-        rtp_socket: &mut PeerSocket,
+        rtp_socket: &Mutex<PeerSocket>,
     ) -> Result<(), RtcError> {
         let single = SingleNalUnitPacket::new(header, nalu[1..].to_vec());
         let payload = PayloadType::H264Video(H264VideoType::Single(single));
@@ -68,27 +101,13 @@ impl RtcRtpSender {
             false,
             0,
             last_nalu,
-            RTP_H264_TYPE,
+            self.payload_type,
             self.sequence_number,
             self.timestamp,
             self.ssrc,
             vec![],
         );
-        let packet = RtpPacket::new(rtp_header, payload);
-        let mut bytes = packet.write_bytes();
-        if let Some(ref srtp) = self.srtp {
-            let header = &bytes[..12];
-            if let Some(cipher) = srtp.protect(self.sequence_number, self.timestamp, &bytes[12..]) {
-                let mut out = Vec::with_capacity(12 + cipher.len());
-                out.extend_from_slice(header);
-                out.extend_from_slice(&cipher);
-                bytes = out;
-            }
-        }
-        rtp_socket.send(&bytes).map_err(RtcError::RtcPeerError)?;
-        self.sequence_number = self.sequence_number.wrapping_add(1);
-        self.register_send(bytes.len(), self.timestamp);
-        Ok(())
+        self.send_packet(rtp_header, payload, rtp_socket)
     }
 
     fn send_fragmented_nalu(
@@ -96,7 +115,7 @@ impl RtcRtpSender {
         header: NaluHeader,
         nalu: Vec<u8>,
         last_nalu: bool,
-        rtp_socket: &mut PeerSocket,
+        rtp_socket: &Mutex<PeerSocket>,
     ) -> Result<(), RtcError> {
         let nalu_type = header.get_nalu_type();
         let nri = header.get_nri();
@@ -117,29 +136,57 @@ impl RtcRtpSender {
                 false,
                 0,
                 marker,
-                RTP_H264_TYPE,
+                self.payload_type,
                 self.sequence_number,
                 self.timestamp,
                 self.ssrc,
                 vec![],
             );
-            let packet = RtpPacket::new(rtp_header, payload);
-            let mut bytes = packet.write_bytes();
-            if let Some(ref srtp) = self.srtp {
-                let header = &bytes[..12];
-                if let Some(cipher) =
-                    srtp.protect(self.sequence_number, self.timestamp, &bytes[12..])
-                {
-                    let mut out = Vec::with_capacity(12 + cipher.len());
-                    out.extend_from_slice(header);
-                    out.extend_from_slice(&cipher);
-                    bytes = out;
-                }
+            self.send_packet(rtp_header, payload, rtp_socket)?;
+        }
+        Ok(())
+    }
+
+    /// Waits for the pacer, stamps the header extensions, protects the
+    /// payload (everything after the header, extension block included) and
+    /// sends the packet. The socket is locked only for the send itself, so
+    /// audio is not held back while video waits.
+    fn send_packet(
+        &mut self,
+        rtp_header: RtpHeader,
+        payload: PayloadType,
+        rtp_socket: &Mutex<PeerSocket>,
+    ) -> Result<(), RtcError> {
+        self.pacer.pace();
+        let rtp_header = match self
+            .abs_send_time_id
+            .and_then(|id| HeaderExtension::abs_send_time(id, abs_send_time_now()))
+        {
+            Some(extension) => rtp_header.with_extension(extension),
+            None => rtp_header,
+        };
+        let header_len = rtp_header.size();
+        let packet = RtpPacket::new(rtp_header, payload);
+        let mut bytes = packet.write_bytes();
+        if let Some(srtp) = self.srtp.current() {
+            let header = &bytes[..header_len];
+            if let Some(cipher) =
+                srtp.protect(self.sequence_number, self.timestamp, &bytes[header_len..])
+            {
+                let mut out = Vec::with_capacity(header_len + cipher.len());
+                out.extend_from_slice(header);
+                out.extend_from_slice(&cipher);
+                bytes = out;
             }
-            rtp_socket.send(&bytes).map_err(RtcError::RtcPeerError)?;
-            self.sequence_number = self.sequence_number.wrapping_add(1);
-            self.register_send(bytes.len(), self.timestamp);
         }
+        rtp_socket
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .send(&bytes)
+            .map_err(RtcError::RtcPeerError)?;
+        self.pacer.charge(bytes.len(), Instant::now());
+        self.sequence_number = self.sequence_number.wrapping_add(1);
+        self.register_send(bytes.len(), self.timestamp);
         Ok(())
     }
 
@@ -0,0 +1,173 @@
+//! Token bucket pacing for outgoing RTP.
+//!
+//! The encoder hands over whole frames and a keyframe can be many times the
+//! size of the rest. Without pacing its packets leave back to back at line
+//! rate, which a capped uplink (a phone hotspot) answers with loss. The
+//! pacer lets packets out at the configured rate instead: a keyframe worth
+//! ~100 ms of the cap takes ~100 ms to leave.
+
+use std::time::{Duration, Instant};
+
+/// What may leave back to back after an idle period.
+const BURST: Duration = Duration::from_millis(5);
+/// The bucket always holds at least one full packet.
+const MIN_BURST_BYTES: f64 = 1_500.0;
+
+pub struct RtpPacer {
+    /// Bytes per second; `None` sends everything right away.
+    rate: Option<f64>,
+    capacity: f64,
+    /// Negative after a packet bigger than what was left: the debt is paid
+    /// before the next one.
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl RtpPacer {
+    /// A pacer at `rate_kbps`, or one that never waits with `None`.
+    pub fn new(rate_kbps: Option<u64>) -> Self {
+        let mut pacer = RtpPacer {
+            rate: None,
+            capacity: MIN_BURST_BYTES,
+            tokens: MIN_BURST_BYTES,
+            last_refill: Instant::now(),
+        };
+        pacer.set_rate_kbps(rate_kbps);
+        pacer
+    }
+
+    /// Changes the rate; packets already charged keep their debt.
+    pub fn set_rate_kbps(&mut self, rate_kbps: Option<u64>) {
+        self.rate = rate_kbps
+            .filter(|kbps| *kbps > 0)
+            .map(|kbps| kbps as f64 * 1000.0 / 8.0);
+        self.capacity = match self.rate {
+            Some(rate) => (rate * BURST.as_secs_f64()).max(MIN_BURST_BYTES),
+            None => MIN_BURST_BYTES,
+        };
+        self.tokens = self.tokens.min(self.capacity);
+    }
+
+    pub fn rate_kbps(&self) -> Option<u64> {
+        self.rate.map(|rate| (rate * 8.0 / 1000.0).round() as u64)
+    }
+
+    /// How long to wait at `now` before the next packet may leave.
+    pub fn wait_time(&mut self, now: Instant) -> Duration {
+        let Some(rate) = self.rate else {
+            return Duration::ZERO;
+        };
+        self.refill(now, rate);
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / rate)
+        }
+    }
+
+    /// Charges a packet of `len` bytes that left at `now`.
+    pub fn charge(&mut self, len: usize, now: Instant) {
+        if let Some(rate) = self.rate {
+            self.refill(now, rate);
+            self.tokens -= len as f64;
+        }
+    }
+
+    /// Sleeps until the next packet may leave.
+    pub fn pace(&mut self) {
+        let wait = self.wait_time(Instant::now());
+        if !wait.is_zero() {
+            std::thread::sleep(wait);
+        }
+    }
+
+    fn refill(&mut self, now: Instant, rate: f64) {
+        let elapsed = now.saturating_duration_since(self.last_refill);
+        self.tokens = (self.tokens + elapsed.as_secs_f64() * rate).min(self.capacity);
+        self.last_refill = self.last_refill.max(now);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PACKET: usize = 1_200;
+
+    /// Queues `packets` at once and returns when the last one leaves, with a
+    /// virtual clock that advances only while the sender waits.
+    fn drain(pacer: &mut RtpPacer, start: Instant, packets: usize) -> Duration {
+        let mut now = start;
+        for _ in 0..packets {
+            now += pacer.wait_time(now);
+            pacer.charge(PACKET, now);
+        }
+        now - start
+    }
+
+    /// When the last of `packets` leaves if all are queued at once at
+    /// `rate` bytes per second: the bucket lets the first ones out, the rest
+    /// wait for tokens.
+    fn expected_spread(packets: usize, rate: f64) -> f64 {
+        (((packets - 1) * PACKET) as f64 - MIN_BURST_BYTES) / rate
+    }
+
+    #[test]
+    fn test_burst_is_spread_at_the_rate() {
+        // 500 kbps = 62 500 B/s; 50 packets = 60 000 B
+        let start = Instant::now();
+        let mut pacer = RtpPacer::new(Some(500));
+        pacer.last_refill = start;
+        let spread = drain(&mut pacer, start, 50).as_secs_f64();
+
+        let expected = expected_spread(50, 62_500.0);
+        assert!(
+            (spread - expected).abs() < 0.001,
+            "{} vs {}",
+            spread,
+            expected
+        );
+    }
+
+    #[test]
+    fn test_sleeping_sender_takes_the_expected_time() {
+        // 1 000 kbps = 125 000 B/s: 13 packets take ~100 ms
+        let mut pacer = RtpPacer::new(Some(1_000));
+        let start = Instant::now();
+        for _ in 0..13 {
+            pacer.pace();
+            pacer.charge(PACKET, Instant::now());
+        }
+        let elapsed = start.elapsed().as_secs_f64();
+        let expected = expected_spread(13, 125_000.0);
+        assert!(
+            elapsed >= expected - 0.005 && elapsed <= expected + 0.05,
+            "{} vs {}",
+            elapsed,
+            expected
+        );
+    }
+
+    #[test]
+    fn test_idle_time_only_refills_the_burst() {
+        let start = Instant::now();
+        let mut pacer = RtpPacer::new(Some(500));
+        pacer.last_refill = start;
+        drain(&mut pacer, start, 10);
+
+        // A long pause does not let the next keyframe out at line rate
+        let later = start + Duration::from_secs(10);
+        let spread = drain(&mut pacer, later, 50);
+        assert!(spread > Duration::from_millis(850), "{:?}", spread);
+    }
+
+    #[test]
+    fn test_uncapped_never_waits() {
+        let mut pacer = RtpPacer::new(None);
+        assert_eq!(drain(&mut pacer, Instant::now(), 100), Duration::ZERO);
+        assert_eq!(pacer.rate_kbps(), None);
+
+        pacer.set_rate_kbps(Some(300));
+        assert_eq!(pacer.rate_kbps(), Some(300));
+    }
+}
@@ -2,19 +2,87 @@ use sctp_proto::{
     Association, AssociationHandle, ClientConfig, DatagramEvent, Endpoint, EndpointConfig,
     Payload, PayloadProtocolIdentifier, ServerConfig, Transmit,
 };
-use std::collections::VecDeque;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
 use std::sync::Arc;
 use std::time::Instant;
 use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use bytes::Bytes;
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SctpError {
+    NotEstablished,
+    /// The send buffer is full, retry later.
+    BufferFull,
+    /// The stream was reset by us or by the remote.
+    StreamClosed(u16),
+    /// The packets could not be handed to the transport underneath.
+    Io(String),
+    Other(String),
+}
+
+impl fmt::Display for SctpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SctpError::NotEstablished => write!(f, "Association not established"),
+            SctpError::BufferFull => write!(f, "Send buffer full"),
+            SctpError::StreamClosed(id) => write!(f, "Stream {} closed", id),
+            SctpError::Io(e) => write!(f, "I/O error: {}", e),
+            SctpError::Other(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for SctpError {}
+
+/// Association events other than incoming data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SctpEvent {
+    /// The remote reset the stream (RE-CONFIG, RFC 6525): everything it sent
+    /// on it has already been delivered, so this is its end of stream.
+    StreamReset { id: u16 },
+}
+
+/// Queued data above this means the association, not the sender, sets the pace.
+const CONGESTION_LIMITED_BYTES: u64 = 64 * 1024;
+
+/// Flow-control counters of one stream, read through `SctpAssociation::stream_stats`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SctpStreamStats {
+    /// Bytes accepted by `send_data` since the stream was (re)opened.
+    pub bytes_sent: u64,
+    /// Bytes in the send buffer, pending or in flight, not yet acked.
+    pub bytes_queued: u64,
+    /// Bytes the remote acknowledged.
+    pub bytes_acked: u64,
+    /// Fast retransmissions and T3-rtx timeouts of the whole association.
+    pub retransmits: u64,
+}
+
+impl SctpStreamStats {
+    /// Whether data piles up waiting for the congestion window to open.
+    pub fn is_congestion_limited(&self) -> bool {
+        self.bytes_queued > CONGESTION_LIMITED_BYTES
+    }
+}
+
 pub struct SctpAssociation {
     endpoint: Endpoint,
     association: Option<Association>,
     association_handle: Option<AssociationHandle>,
     incoming_data: VecDeque<(u16, Vec<u8>)>,
     outgoing_queue: VecDeque<Vec<u8>>,
+    events: VecDeque<SctpEvent>,
+    /// Streams we reset; sending on them fails until `reopen_stream`.
+    closed_streams: HashSet<u16>,
+    /// Streams the remote reset; cleared when it sends on them again.
+    remote_closed: HashSet<u16>,
+    /// Streams alive in the association, to notice when a reset removes them.
+    open_streams: HashSet<u16>,
+    /// Bytes accepted per stream, for `stream_stats`.
+    bytes_sent: HashMap<u16, u64>,
     is_server: bool,
+    connected: bool,
 }
 
 impl SctpAssociation {
@@ -38,7 +106,13 @@ impl SctpAssociation {
             association_handle: None,
             incoming_data: VecDeque::new(),
             outgoing_queue: VecDeque::new(),
+            events: VecDeque::new(),
+            closed_streams: HashSet::new(),
+            remote_closed: HashSet::new(),
+            open_streams: HashSet::new(),
+            bytes_sent: HashMap::new(),
             is_server,
+            connected: false,
         }
     }
 
@@ -99,18 +173,18 @@ impl SctpAssociation {
         // Placeholder kept for backward compatibility.
     }
 
-    pub fn send_data(&mut self, stream_id: u16, payload: Vec<u8>) -> Result<(), String> {
+    pub fn send_data(&mut self, stream_id: u16, payload: Vec<u8>) -> Result<(), SctpError> {
+        if self.is_stream_closed(stream_id) {
+            return Err(SctpError::StreamClosed(stream_id));
+        }
         {
-            let assoc = self
-                .association
-                .as_mut()
-                .ok_or_else(|| "Association not established".to_string())?;
+            let assoc = self.association.as_mut().ok_or(SctpError::NotEstablished)?;
 
             let mut stream = match assoc.stream(stream_id) {
                 Ok(s) => s,
                 Err(_) => assoc
                     .open_stream(stream_id, PayloadProtocolIdentifier::Binary)
-                    .map_err(|e| e.to_string())?,
+                    .map_err(|e| SctpError::Other(e.to_string()))?,
             };
 
             let mut offset = 0;
@@ -119,21 +193,86 @@ impl SctpAssociation {
                     Ok(n) => {
                         offset += n;
                         if n == 0 {
-                            return Err("BufferFull".to_string());
+                            return Err(SctpError::BufferFull);
                         }
                     }
                     Err(e) => {
-                        println!("DEBUG: SCTP send error on stream {}: {:?}", stream_id, e);
-                        return Err(e.to_string());
+                        log_debug!("SCTP send error on stream {}: {:?}", stream_id, e);
+                        return Err(SctpError::Other(e.to_string()));
                     }
                 }
             }
         }
+        self.open_streams.insert(stream_id);
+        *self.bytes_sent.entry(stream_id).or_default() += payload.len() as u64;
 
         self.pump_association(Instant::now());
         Ok(())
     }
 
+    /// Resets the stream in both directions (RE-CONFIG). The remote sees a
+    /// `SctpEvent::StreamReset` after the data sent before it, and sending on
+    /// it fails until `reopen_stream`.
+    pub fn reset_stream(&mut self, stream_id: u16) -> Result<(), SctpError> {
+        let assoc = self.association.as_mut().ok_or(SctpError::NotEstablished)?;
+        if self.closed_streams.contains(&stream_id) {
+            return Ok(());
+        }
+        // A stream we never used has nothing to reset
+        if let Ok(mut stream) = assoc.stream(stream_id) {
+            stream.stop().map_err(|e| SctpError::Other(e.to_string()))?;
+        }
+        self.closed_streams.insert(stream_id);
+        self.bytes_sent.remove(&stream_id);
+        self.pump_association(Instant::now());
+        Ok(())
+    }
+
+    /// Allows sending on a reset stream again, whichever side reset it. The
+    /// stream is opened anew on the next `send_data`.
+    pub fn reopen_stream(&mut self, stream_id: u16) {
+        self.closed_streams.remove(&stream_id);
+        self.remote_closed.remove(&stream_id);
+        self.bytes_sent.remove(&stream_id);
+    }
+
+    pub fn is_stream_closed(&self, stream_id: u16) -> bool {
+        self.closed_streams.contains(&stream_id) || self.remote_closed.contains(&stream_id)
+    }
+
+    /// Flow-control counters of a stream. The congestion window itself is
+    /// private to `sctp_proto`, so the queue tells whether it is the limit.
+    pub fn stream_stats(&mut self, stream_id: u16) -> SctpStreamStats {
+        let bytes_sent = self.bytes_sent.get(&stream_id).copied().unwrap_or(0);
+        let Some(assoc) = self.association.as_mut() else {
+            return SctpStreamStats {
+                bytes_sent,
+                ..SctpStreamStats::default()
+            };
+        };
+        let mut stats = assoc.stats();
+        // A stream without buffer (never opened or already reset) has nothing queued
+        let bytes_queued = assoc
+            .stream(stream_id)
+            .and_then(|stream| stream.buffered_amount())
+            .unwrap_or(0) as u64;
+        SctpStreamStats {
+            bytes_sent,
+            bytes_queued,
+            bytes_acked: bytes_sent.saturating_sub(bytes_queued),
+            retransmits: stats.get_num_fast_retrans() + stats.get_num_t3timeouts(),
+        }
+    }
+
+    pub fn poll_event(&mut self) -> Option<SctpEvent> {
+        self.events.pop_front()
+    }
+
+    /// Whether the SCTP handshake finished and the association is alive.
+    pub fn is_connected(&self) -> bool {
+        self.connected
+    }
+
     pub fn drive(&mut self) {
         self.pump_association(Instant::now());
     }
@@ -226,12 +365,15 @@ impl SctpAssociation {
                  use sctp_proto::StreamEvent;
                  
                  // Debug Log
-                 println!("DEBUG: SCTP Event: {:?}", event);
+                 log_debug!("SCTP Event: {:?}", event);
                  
                  match event {
                     Event::Stream(StreamEvent::Readable { id }) => {
                         // We need to borrow assoc again to read.
                         // This is fine as we are in the main loop scope, not inside the if-let.
+                        // New data after a remote reset means the remote reopened it
+                        self.remote_closed.remove(&id);
+                        self.open_streams.insert(id);
                         if let Some(assoc) = self.association.as_mut() {
                              match assoc.stream(id) {
                                 Ok(mut stream) => {
@@ -241,13 +383,13 @@ impl SctpAssociation {
                                           Ok(Some(chunks)) => {
                                               let mut buf = vec![0u8; chunks.len()];
                                               if let Ok(_) = chunks.read(&mut buf) {
-                                                  println!("DEBUG: Read {} bytes from Stream {}", buf.len(), id);
+                                                  log_debug!("Read {} bytes from Stream {}", buf.len(), id);
                                                   self.incoming_data.push_back((id, buf));
                                               }
                                           }
                                           Ok(None) => break, 
                                           Err(e) => {
-                                              println!("DEBUG: Stream read error: {:?}", e);
+                                              log_debug!("Stream read error: {:?}", e);
                                               break;
                                           }
                                       }
@@ -257,31 +399,206 @@ impl SctpAssociation {
                                   }
                                 }
                                 Err(e) => {
-                                    println!("DEBUG: Failed to get stream {}: {:?}", id, e);
+                                    log_debug!("Failed to get stream {}: {:?}", id, e);
                                 }
                              }
                         }
                         progressed = true;
                     }
                     Event::Stream(StreamEvent::Writable { id }) => {
-                         println!("DEBUG: Stream {} is writable", id);
+                         log_debug!("Stream {} is writable", id);
                     }
                     Event::AssociationLost { reason } => {
-                        println!("DEBUG: SCTP Association Lost: {:?}", reason);
+                        log_debug!("SCTP Association Lost: {:?}", reason);
                         self.association = None;
+                        self.connected = false;
                         progressed = true;
                     }
                     Event::Connected => {
-                        println!("DEBUG: SCTP Connected");
+                        log_debug!("SCTP Connected");
+                        self.connected = true;
                         progressed = true;
                     }
                     _ => {}
                  }
             }
 
+            self.detect_reset_streams();
+
             if !progressed {
                 break;
             }
         }
     }
+
+    /// `sctp_proto` reports no event for a RE-CONFIG: a reset stream just
+    /// disappears from the association, from both ends once the reset is
+    /// answered. One we did not reset ourselves was reset by the remote.
+    fn detect_reset_streams(&mut self) {
+        let Some(assoc) = self.association.as_mut() else {
+            return;
+        };
+        let gone: Vec<u16> = self
+            .open_streams
+            .iter()
+            .copied()
+            .filter(|id| assoc.stream(*id).is_err())
+            .collect();
+        for id in gone {
+            self.open_streams.remove(&id);
+            // Our own close is not news for the caller
+            if !self.closed_streams.contains(&id) && self.remote_closed.insert(id) {
+                self.events.push_back(SctpEvent::StreamReset { id });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Moves every pending datagram between both ends until nothing is left.
+    fn exchange(a: &mut SctpAssociation, b: &mut SctpAssociation) {
+        for _ in 0..100 {
+            let mut moved = false;
+            while let Some(packet) = a.poll_output() {
+                b.handle_input(&packet);
+                moved = true;
+            }
+            while let Some(packet) = b.poll_output() {
+                a.handle_input(&packet);
+                moved = true;
+            }
+            a.drive();
+            b.drive();
+            if !moved {
+                break;
+            }
+        }
+    }
+
+    fn connected_pair() -> (SctpAssociation, SctpAssociation) {
+        let mut client = SctpAssociation::new(false);
+        let mut server = SctpAssociation::new(true);
+        client.establish();
+        exchange(&mut client, &mut server);
+        assert!(client.is_connected() && server.is_connected());
+        (client, server)
+    }
+
+    #[test]
+    fn test_send_before_the_handshake_is_not_established() {
+        let mut client = SctpAssociation::new(false);
+        assert_eq!(
+            client.send_data(1, b"hola".to_vec()),
+            Err(SctpError::NotEstablished)
+        );
+    }
+
+    #[test]
+    fn test_data_flows_over_loopback_pair() {
+        let (mut client, mut server) = connected_pair();
+        client.send_data(2, b"hola".to_vec()).unwrap();
+        exchange(&mut client, &mut server);
+        assert_eq!(server.recv_data(), Some((2, b"hola".to_vec())));
+    }
+
+    #[test]
+    fn test_stream_stats_track_queued_and_acked_bytes() {
+        let (mut client, mut server) = connected_pair();
+        client.send_data(2, vec![7; 1000]).unwrap();
+        let queued = client.stream_stats(2);
+        assert_eq!(queued.bytes_sent, 1000);
+        assert_eq!(queued.bytes_queued, 1000);
+        assert_eq!(queued.bytes_acked, 0);
+
+        exchange(&mut client, &mut server);
+        // El SACK sale cuando vence el timer de ack demorado
+        std::thread::sleep(std::time::Duration::from_millis(250));
+        server.drive();
+        exchange(&mut client, &mut server);
+        let acked = client.stream_stats(2);
+        assert_eq!(acked.bytes_queued, 0);
+        assert_eq!(acked.bytes_acked, 1000);
+        assert_eq!(acked.retransmits, 0);
+        assert!(!acked.is_congestion_limited());
+        // Los demás streams no cuentan lo de este
+        assert_eq!(client.stream_stats(1), SctpStreamStats::default());
+
+        // Reabierto, arranca de cero
+        client.reset_stream(2).unwrap();
+        client.reopen_stream(2);
+        assert_eq!(client.stream_stats(2).bytes_sent, 0);
+    }
+
+    #[test]
+    fn test_close_stream_reaches_the_remote() {
+        let (mut client, mut server) = connected_pair();
+        client.send_data(2, b"chunk".to_vec()).unwrap();
+        exchange(&mut client, &mut server);
+        assert!(server.recv_data().is_some());
+
+        // El receptor corta: el emisor se entera y deja de poder mandar
+        server.reset_stream(2).unwrap();
+        exchange(&mut client, &mut server);
+        assert_eq!(client.poll_event(), Some(SctpEvent::StreamReset { id: 2 }));
+        assert_eq!(
+            client.send_data(2, b"more".to_vec()),
+            Err(SctpError::StreamClosed(2))
+        );
+        assert_eq!(
+            server.send_data(2, b"more".to_vec()),
+            Err(SctpError::StreamClosed(2))
+        );
+        // El cierre propio no se reporta como evento
+        assert_eq!(server.poll_event(), None);
+
+        // Los demás streams siguen andando
+        client.send_data(1, b"control".to_vec()).unwrap();
+        exchange(&mut client, &mut server);
+        assert_eq!(server.recv_data(), Some((1, b"control".to_vec())));
+    }
+
+    #[test]
+    fn test_reopened_stream_carries_data_again() {
+        let (mut client, mut server) = connected_pair();
+        client.send_data(2, b"first".to_vec()).unwrap();
+        exchange(&mut client, &mut server);
+        assert!(server.recv_data().is_some());
+        client.reset_stream(2).unwrap();
+        exchange(&mut client, &mut server);
+        assert_eq!(server.poll_event(), Some(SctpEvent::StreamReset { id: 2 }));
+
+        client.reopen_stream(2);
+        assert!(!client.is_stream_closed(2));
+        client.send_data(2, b"second".to_vec()).unwrap();
+        exchange(&mut client, &mut server);
+        assert_eq!(server.recv_data(), Some((2, b"second".to_vec())));
+        // Los datos nuevos le muestran al otro lado que el stream volvió
+        assert!(!server.is_stream_closed(2));
+    }
+
+    #[test]
+    fn test_reset_after_data_marks_end_of_stream() {
+        let (mut client, mut server) = connected_pair();
+        for chunk in [b"uno".to_vec(), b"dos".to_vec(), b"tres".to_vec()] {
+            client.send_data(3, chunk).unwrap();
+            exchange(&mut client, &mut server);
+        }
+        client.reset_stream(3).unwrap();
+        exchange(&mut client, &mut server);
+
+        // Todo lo mandado llega antes del fin del stream
+        let received: Vec<u8> = std::iter::from_fn(|| server.recv_data())
+            .flat_map(|(stream, data)| {
+                assert_eq!(stream, 3);
+                data
+            })
+            .collect();
+        assert_eq!(received, b"unodostres");
+        assert_eq!(server.poll_event(), Some(SctpEvent::StreamReset { id: 3 }));
+        assert_eq!(server.poll_event(), None);
+        assert!(server.is_stream_closed(3));
+    }
 }
@@ -3,22 +3,58 @@
 use std::str::FromStr;
 
 use crate::ice::IceAgent;
+use crate::protocols::sdp::media_type::MediaType;
+use crate::protocols::sdp::bandwidth::Bandwidth;
 use crate::protocols::sdp::session_description::SessionDescription;
-use crate::sdp_helper::{ice_to_sdp, sdp_to_ice_candidates};
+use crate::sdp_helper::{local_session_builder, sdp_to_ice_candidates};
 
+use super::codec_negotiation::{negotiate_codecs, MediaDirections, NegotiatedCodecs};
 use super::peer_connection_error::PeerConnectionError;
 use super::rtc_dtls::DtlsSession;
+use super::ssrc_routing::{LocalStreams, SsrcRouter};
+
+/// Lines every description we generate carries, so the remote can run ICE
+/// and the DTLS handshake.
+pub const REQUIRED_LOCAL_ATTRIBUTES: [&str; 5] = [
+    "a=ice-ufrag:",
+    "a=ice-pwd:",
+    "a=fingerprint:",
+    "a=candidate:",
+    "a=group:BUNDLE",
+];
+
+/// Values extracted from a remote SDP.
+#[derive(Debug, Clone)]
+pub struct RemoteSdpInfo {
+    pub ufrag: String,
+    pub pwd: String,
+    pub fingerprint: Option<String>,
+    /// Bandwidth cap (kbps) announced with `b=`, if any.
+    pub bandwidth_kbps: Option<u64>,
+    /// Payload types picked from the remote rtpmaps, and our direction on
+    /// each section.
+    pub codecs: NegotiatedCodecs,
+    /// Whether the remote declared `a=rtcp-mux`.
+    pub rtcp_mux: bool,
+    /// Remote SSRCs announced per media section.
+    pub ssrc_router: SsrcRouter,
+}
 
 /// Process a remote SDP offer and extract ICE candidates.
 /// 
-/// Returns the extracted credentials (ufrag, pwd) and fingerprint.
+/// Returns the extracted credentials (ufrag, pwd), fingerprint, bandwidth cap
+/// and the negotiated payload types. Directions are the reverse of the
+/// remote ones, limited to what we `want`.
 pub fn process_remote_sdp(
     ice_agent: &mut IceAgent,
     sdp: &str,
-) -> Result<(String, String, Option<String>), PeerConnectionError> {
+    want: MediaDirections,
+) -> Result<RemoteSdpInfo, PeerConnectionError> {
     let remote_session = SessionDescription::from_str(sdp)
         .map_err(|err| PeerConnectionError::Sdp(err.to_string()))?;
 
+    let codecs = negotiate_codecs(&remote_session, want)?;
+
     let (ufrag, pwd, candidates, fingerprint) =
         sdp_to_ice_candidates(&remote_session).map_err(PeerConnectionError::Sdp)?;
 
@@ -26,16 +62,88 @@ pub fn process_remote_sdp(
         ice_agent.add_remote_candidate(candidate);
     }
 
-    println!("DEBUG: Remote ICE candidates and credentials processed.");
+    log_debug!("Remote ICE candidates and credentials processed.");
 
-    Ok((ufrag, pwd, fingerprint))
+    Ok(RemoteSdpInfo {
+        ufrag,
+        pwd,
+        fingerprint,
+        bandwidth_kbps: remote_session.get_bandwidth_kbps(),
+        codecs,
+        rtcp_mux: remote_session.has_rtcp_mux(),
+        ssrc_router: SsrcRouter::from_remote(&remote_session),
+    })
+}
+
+/// What `process_remote_sdp` extracts from `sdp`, one `key=value` per line:
+/// credentials, fingerprint, bandwidth, rtcp-mux, payload types and
+/// candidates. It is the format of the `.expected` files next to the SDP
+/// fixtures in `tests/fixtures/sdp`, and what the `sdp_check` example prints.
+pub fn remote_sdp_summary(sdp: &str) -> Result<String, PeerConnectionError> {
+    let mut agent = IceAgent::new();
+    let info = process_remote_sdp(&mut agent, sdp, MediaDirections::default())?;
+
+    let mut lines = vec![
+        format!("ufrag={}", info.ufrag),
+        format!("pwd={}", info.pwd),
+        format!("fingerprint={}", info.fingerprint.as_deref().unwrap_or("none")),
+    ];
+    if let Some(kbps) = info.bandwidth_kbps {
+        lines.push(format!("bandwidth_kbps={}", kbps));
+    }
+    lines.push(format!("rtcp_mux={}", info.rtcp_mux));
+    for media_type in [MediaType::Audio, MediaType::Video] {
+        let payload_type = if info.codecs.is_accepted(media_type) {
+            info.codecs.payload_type(media_type).to_string()
+        } else {
+            "rejected".to_string()
+        };
+        lines.push(format!("{}={}", media_type, payload_type));
+    }
+    for candidate in agent.remote_candidates() {
+        lines.push(format!(
+            "candidate={} {}:{} {}",
+            candidate.candidate_type, candidate.address, candidate.port, candidate.priority
+        ));
+    }
+    Ok(lines.join("\n"))
+}
+
+/// Entries of [`REQUIRED_LOCAL_ATTRIBUTES`] missing from `sdp`.
+pub fn missing_required_attributes(sdp: &str) -> Vec<&'static str> {
+    REQUIRED_LOCAL_ATTRIBUTES
+        .into_iter()
+        .filter(|attribute| !sdp.lines().any(|line| line.starts_with(attribute)))
+        .collect()
+}
+
+/// DTLS fingerprint announced in a remote SDP, without touching any ICE state.
+pub fn remote_dtls_fingerprint(sdp: &str) -> Result<Option<String>, PeerConnectionError> {
+    let remote_session = SessionDescription::from_str(sdp)
+        .map_err(|err| PeerConnectionError::Sdp(err.to_string()))?;
+    let (_, _, _, fingerprint) =
+        sdp_to_ice_candidates(&remote_session).map_err(PeerConnectionError::Sdp)?;
+    Ok(fingerprint)
 }
 
 /// Build a local SDP description from the ICE agent state.
-pub fn build_local_description(ice_agent: &IceAgent, dtls_session: Option<&DtlsSession>) -> String {
+///
+/// When `bandwidth_kbps` is set a session-level `b=AS` line is announced.
+/// The audio and video m-lines use the payload types and directions in
+/// `codecs`, and declare `a=rtcp-mux` when `rtcp_mux` is set. `streams` holds the SSRCs we send with.
+pub fn build_local_description(
+    ice_agent: &IceAgent,
+    dtls_session: Option<&DtlsSession>,
+    bandwidth_kbps: Option<u64>,
+    codecs: &NegotiatedCodecs,
+    streams: &LocalStreams,
+    rtcp_mux: bool,
+) -> String {
     let fingerprint = dtls_session.map(|s| s.certificate_fingerprint());
-    let session = ice_to_sdp(ice_agent, fingerprint.as_deref());
-    session.to_string()
+    local_session_builder(ice_agent, fingerprint.as_deref(), codecs, streams, rtcp_mux)
+        .bandwidth(bandwidth_kbps.map(Bandwidth::application_specific))
+        .build()
+        .to_string()
 }
 
 /// Validate that the remote SDP contains a DTLS fingerprint.
@@ -0,0 +1,243 @@
+//! Session codes for manual signaling: a whole SDP in one line that survives
+//! chat apps and is short enough for a QR code later on.
+//!
+//! Layout before base64url: version (1 byte), compressed length (2 bytes BE),
+//! the deflated SDP and the CRC-32 of the original SDP (4 bytes BE).
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::{DecodeError, Engine};
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+use std::fmt;
+use std::io::{self, Read, Write};
+
+/// Marks the text as a session code and carries the format version.
+pub const SESSION_BLOB_PREFIX: &str = "RTC1:";
+const SESSION_BLOB_VERSION: u8 = 1;
+const HEADER_LEN: usize = 3;
+const CHECKSUM_LEN: usize = 4;
+/// Largest SDP a session code may inflate to. Deflate packs long runs into a
+/// few bytes, so a short paste could otherwise expand to gigabytes.
+const MAX_DECODED_LEN: usize = 64 * 1024;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum SessionBlobError {
+    /// The text does not start with [`SESSION_BLOB_PREFIX`].
+    NotASessionCode,
+    /// Written by a newer version of the format.
+    UnsupportedVersion(u8),
+    /// Shorter than the length it declares, usually a paste cut off.
+    Truncated,
+    /// Characters outside base64url or bytes that do not inflate.
+    Corrupted,
+    /// The content does not match its checksum.
+    ChecksumMismatch,
+    /// Inflates to more than any SDP needs.
+    TooLarge,
+}
+
+impl fmt::Display for SessionBlobError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SessionBlobError::NotASessionCode => write!(f, "not a session code"),
+            SessionBlobError::UnsupportedVersion(v) => {
+                write!(f, "session code version {} is not supported", v)
+            }
+            SessionBlobError::Truncated => {
+                write!(
+                    f,
+                    "session code is incomplete, it was probably cut off while pasting"
+                )
+            }
+            SessionBlobError::Corrupted => write!(f, "session code is corrupted"),
+            SessionBlobError::ChecksumMismatch => {
+                write!(f, "session code checksum does not match, copy it again")
+            }
+            SessionBlobError::TooLarge => write!(f, "session code is too large"),
+        }
+    }
+}
+
+impl std::error::Error for SessionBlobError {}
+
+/// Packs an SDP into a session code.
+pub fn encode_session_blob(sdp: &str) -> io::Result<String> {
+    let mut encoder = DeflateEncoder::new(Vec::new(), Compression::best());
+    encoder.write_all(sdp.as_bytes())?;
+    let compressed = encoder.finish()?;
+    let compressed_len = u16::try_from(compressed.len())
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "SDP too large"))?;
+
+    let mut bytes = Vec::with_capacity(HEADER_LEN + compressed.len() + CHECKSUM_LEN);
+    bytes.push(SESSION_BLOB_VERSION);
+    bytes.extend_from_slice(&compressed_len.to_be_bytes());
+    bytes.extend_from_slice(&compressed);
+    bytes.extend_from_slice(&crc32(sdp.as_bytes()).to_be_bytes());
+    Ok(format!(
+        "{}{}",
+        SESSION_BLOB_PREFIX,
+        URL_SAFE_NO_PAD.encode(bytes)
+    ))
+}
+
+/// Unpacks a session code into the SDP it carries. Whitespace is ignored,
+/// since chat apps tend to wrap long lines.
+pub fn decode_session_blob(blob: &str) -> Result<String, SessionBlobError> {
+    let compact: String = blob.chars().filter(|c| !c.is_whitespace()).collect();
+    let encoded = compact
+        .strip_prefix(SESSION_BLOB_PREFIX)
+        .ok_or(SessionBlobError::NotASessionCode)?;
+    let bytes = URL_SAFE_NO_PAD.decode(encoded).map_err(|err| match err {
+        // A base64 string cut at a random point ends in a partial group
+        DecodeError::InvalidLength(_) | DecodeError::InvalidLastSymbol(_, _) => {
+            SessionBlobError::Truncated
+        }
+        _ => SessionBlobError::Corrupted,
+    })?;
+
+    if bytes.len() < HEADER_LEN {
+        return Err(SessionBlobError::Truncated);
+    }
+    if bytes[0] != SESSION_BLOB_VERSION {
+        return Err(SessionBlobError::UnsupportedVersion(bytes[0]));
+    }
+    let compressed_len = u16::from_be_bytes([bytes[1], bytes[2]]) as usize;
+    let expected_len = HEADER_LEN + compressed_len + CHECKSUM_LEN;
+    if bytes.len() < expected_len {
+        return Err(SessionBlobError::Truncated);
+    }
+    if bytes.len() > expected_len {
+        return Err(SessionBlobError::Corrupted);
+    }
+
+    let compressed = &bytes[HEADER_LEN..HEADER_LEN + compressed_len];
+    let checksum_bytes = &bytes[HEADER_LEN + compressed_len..];
+    let checksum = u32::from_be_bytes([
+        checksum_bytes[0],
+        checksum_bytes[1],
+        checksum_bytes[2],
+        checksum_bytes[3],
+    ]);
+
+    let mut sdp = Vec::new();
+    DeflateDecoder::new(compressed)
+        .take(MAX_DECODED_LEN as u64 + 1)
+        .read_to_end(&mut sdp)
+        .map_err(|_| SessionBlobError::Corrupted)?;
+    if sdp.len() > MAX_DECODED_LEN {
+        return Err(SessionBlobError::TooLarge);
+    }
+    if crc32(&sdp) != checksum {
+        return Err(SessionBlobError::ChecksumMismatch);
+    }
+    String::from_utf8(sdp).map_err(|_| SessionBlobError::Corrupted)
+}
+
+/// CRC-32 (IEEE), the one used by zip and PNG.
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for byte in data {
+        crc ^= u32::from(*byte);
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SDP: &str = "v=0\r\no=- 1 1 IN IP4 0.0.0.0\r\ns=-\r\nt=0 0\r\n\
+        a=group:BUNDLE audio video\r\n\
+        m=audio 9 RTP/SAVP 111\r\na=mid:audio\r\na=rtpmap:111 opus/48000/2\r\n\
+        a=candidate:1 1 udp 2130706431 192.168.0.10 50000 typ host\r\n\
+        m=video 9 RTP/SAVP 96\r\na=mid:video\r\na=rtpmap:96 H264/90000\r\n";
+
+    #[test]
+    fn test_roundtrip_is_a_single_line() {
+        let blob = encode_session_blob(SDP).unwrap();
+        assert!(blob.starts_with(SESSION_BLOB_PREFIX));
+        assert!(!blob.contains('\n'));
+        assert_eq!(decode_session_blob(&blob).unwrap(), SDP);
+    }
+
+    #[test]
+    fn test_blob_is_compressed() {
+        // Las SDP reales repiten mucho (dos m-lines casi iguales)
+        let sdp = SDP.repeat(4);
+        let blob = encode_session_blob(&sdp).unwrap();
+        assert!(blob.len() < sdp.len());
+    }
+
+    #[test]
+    fn test_wrapped_lines_are_accepted() {
+        let blob = encode_session_blob(SDP).unwrap();
+        let (head, tail) = blob.split_at(blob.len() / 2);
+        let wrapped = format!("  {}\r\n{}\n", head, tail);
+        assert_eq!(decode_session_blob(&wrapped).unwrap(), SDP);
+    }
+
+    #[test]
+    fn test_truncated_paste_is_reported() {
+        let blob = encode_session_blob(SDP).unwrap();
+        for cut in [
+            SESSION_BLOB_PREFIX.len() + 2,
+            blob.len() / 2,
+            blob.len() - 1,
+        ] {
+            assert_eq!(
+                decode_session_blob(&blob[..cut]),
+                Err(SessionBlobError::Truncated),
+                "cut at {}",
+                cut
+            );
+        }
+    }
+
+    #[test]
+    fn test_corruption_is_detected() {
+        let blob = encode_session_blob(SDP).unwrap();
+        assert_eq!(
+            decode_session_blob(&SDP[..20]),
+            Err(SessionBlobError::NotASessionCode)
+        );
+        assert_eq!(
+            decode_session_blob(&blob.replacen(SESSION_BLOB_PREFIX, "RTC1:*", 1)),
+            Err(SessionBlobError::Corrupted)
+        );
+
+        // Cambia el checksum sin tocar el largo
+        let mut bytes = URL_SAFE_NO_PAD
+            .decode(&blob[SESSION_BLOB_PREFIX.len()..])
+            .unwrap();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xFF;
+        let tampered = format!("{}{}", SESSION_BLOB_PREFIX, URL_SAFE_NO_PAD.encode(&bytes));
+        assert_eq!(
+            decode_session_blob(&tampered),
+            Err(SessionBlobError::ChecksumMismatch)
+        );
+    }
+
+    #[test]
+    fn test_oversized_content_is_rejected() {
+        // Unos pocos cientos de bytes comprimidos que inflan a 1 MiB
+        let huge = "a".repeat(1024 * 1024);
+        let blob = encode_session_blob(&huge).unwrap();
+        assert!(blob.len() < 4 * 1024);
+        assert_eq!(decode_session_blob(&blob), Err(SessionBlobError::TooLarge));
+
+        let largest = "a".repeat(MAX_DECODED_LEN);
+        let blob = encode_session_blob(&largest).unwrap();
+        assert_eq!(decode_session_blob(&blob).unwrap(), largest);
+    }
+
+    #[test]
+    fn test_crc32_known_value() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+}
@@ -0,0 +1,114 @@
+//! ICE consent freshness (RFC 7675).
+//!
+//! When media stops arriving, binding requests are sent to the known remote
+//! candidates. The address that answers one of them becomes the new remote
+//! address, so a call survives a path change instead of timing out.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use crate::stun::StunMessage;
+
+/// Silence on the media path after which consent checks start.
+pub const CONSENT_SILENCE: Duration = Duration::from_secs(2);
+
+/// Pause between two rounds of consent checks while the silence lasts.
+pub const CONSENT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Outstanding consent requests and the last address that answered one.
+#[derive(Debug, Default)]
+pub struct ConsentFreshness {
+    pending: HashMap<[u8; 12], SocketAddr>,
+    confirmed: Option<SocketAddr>,
+}
+
+impl ConsentFreshness {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts a new round: one binding request per target. Requests of the
+    /// previous round that never got an answer are forgotten.
+    pub fn start_round(&mut self, targets: &[SocketAddr]) -> Vec<(SocketAddr, Vec<u8>)> {
+        self.pending.clear();
+        targets
+            .iter()
+            .map(|target| {
+                let (request, transaction_id) =
+                    StunMessage::create_binding_request_with_transaction();
+                self.pending.insert(transaction_id, *target);
+                (*target, request)
+            })
+            .collect()
+    }
+
+    /// Handles a binding response. Returns `true` when it answers one of our
+    /// consent requests, recording `source` as the confirmed address.
+    pub fn handle_response(&mut self, transaction_id: &[u8; 12], source: SocketAddr) -> bool {
+        if self.pending.remove(transaction_id).is_none() {
+            return false;
+        }
+        self.confirmed = Some(source);
+        true
+    }
+
+    /// Address confirmed since the last call, if any.
+    pub fn take_confirmed(&mut self) -> Option<SocketAddr> {
+        self.confirmed.take()
+    }
+
+    /// Number of requests still waiting for an answer.
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(port: u16) -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], port))
+    }
+
+    fn transaction_id(request: &[u8]) -> [u8; 12] {
+        StunMessage::parse(request).unwrap().transaction_id
+    }
+
+    #[test]
+    fn test_round_sends_one_request_per_target() {
+        let mut consent = ConsentFreshness::new();
+        let requests = consent.start_round(&[addr(5000), addr(5001)]);
+
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].0, addr(5000));
+        assert_eq!(requests[1].0, addr(5001));
+        assert_eq!(consent.pending(), 2);
+    }
+
+    #[test]
+    fn test_response_confirms_the_answering_address() {
+        let mut consent = ConsentFreshness::new();
+        let requests = consent.start_round(&[addr(5000), addr(5001)]);
+        let id = transaction_id(&requests[1].1);
+
+        // El remoto puede contestar desde otra dirección (NAT rebind)
+        assert!(consent.handle_response(&id, addr(6000)));
+        assert_eq!(consent.take_confirmed(), Some(addr(6000)));
+        assert_eq!(consent.take_confirmed(), None);
+        assert_eq!(consent.pending(), 1);
+    }
+
+    #[test]
+    fn test_unknown_or_stale_responses_are_ignored() {
+        let mut consent = ConsentFreshness::new();
+        let old = consent.start_round(&[addr(5000)]);
+        let old_id = transaction_id(&old[0].1);
+        consent.start_round(&[addr(5000)]);
+
+        assert!(!consent.handle_response(&old_id, addr(5000)));
+        assert!(!consent.handle_response(&[0; 12], addr(5000)));
+        assert_eq!(consent.take_confirmed(), None);
+    }
+}
@@ -1,2 +1,4 @@
+pub mod consent;
 pub mod peer_socket;
 pub mod peer_socket_err;
+pub mod port_range;
@@ -1,12 +1,22 @@
 //! UDP socket with specific utilities for WebRTC traffic.
 
+use crate::rtc::socket::consent::ConsentFreshness;
 use crate::rtc::socket::peer_socket_err::PeerSocketErr;
+use crate::rtc::socket::port_range::PortRange;
 use crate::stun::{MessageType, StunMessage};
-use std::net::{SocketAddr, UdpSocket};
+use std::cell::Cell;
+use std::io::ErrorKind;
+use std::net::{IpAddr, SocketAddr, UdpSocket};
 use std::sync::mpsc;
 use std::sync::mpsc::{Receiver, SyncSender};
+use std::sync::{Arc, Mutex};
 use std::thread;
 use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// Silence on the sending side after which a keepalive goes out, so the NAT
+/// binding does not expire while no media is sent (e.g. video paused).
+pub const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(15);
 
 /// Encapsulates a UDP socket and the associated listening loop for an RTC peer.
 pub struct PeerSocket {
@@ -15,12 +25,30 @@ pub struct PeerSocket {
     remote_addr: Option<SocketAddr>,
     handler: Vec<JoinHandle<()>>,
     receiver: Option<Receiver<(Vec<u8>, SocketAddr)>>,
+    consent: Arc<Mutex<ConsentFreshness>>,
+    last_send: Cell<Instant>,
 }
 impl PeerSocket {
     /// Creates and binds a UDP socket at the specified address.
     pub fn new(local_addr: Option<&str>) -> Result<PeerSocket, PeerSocketErr> {
         let bind_addr = local_addr.unwrap_or("0.0.0.0:0");
         let socket = UdpSocket::bind(bind_addr).map_err(PeerSocketErr::BindSocketError)?;
+        Self::from_socket(socket)
+    }
+
+    /// Binds to the first free port of `range` on `ip`.
+    pub fn bind_in_range(ip: IpAddr, range: PortRange) -> Result<PeerSocket, PeerSocketErr> {
+        for port in range.ports() {
+            match UdpSocket::bind(SocketAddr::new(ip, port)) {
+                Ok(socket) => return Self::from_socket(socket),
+                Err(err) if err.kind() == ErrorKind::AddrInUse => continue,
+                Err(err) => return Err(PeerSocketErr::BindSocketError(err)),
+            }
+        }
+        Err(PeerSocketErr::PortRangeExhausted(range))
+    }
+
+    fn from_socket(socket: UdpSocket) -> Result<PeerSocket, PeerSocketErr> {
         let local_addr = socket
             .local_addr()
             .map_err(PeerSocketErr::SetLocalAddrError)?;
@@ -30,6 +58,8 @@ impl PeerSocket {
             remote_addr: None,
             handler: vec![],
             receiver: None,
+            consent: Arc::new(Mutex::new(ConsentFreshness::new())),
+            last_send: Cell::new(Instant::now()),
         })
     }
 
@@ -38,13 +68,14 @@ impl PeerSocket {
     /// Checks handle_stun_message to automatically respond to STUN Binding Requests.
     /// If it's not a STUN message now we look for the first byte to send the packet to DTLS or SRTP.
     pub fn listener(&mut self, dtls_sender: Option<SyncSender<Vec<u8>>>) -> Result<(), PeerSocketErr> {
-        println!("DEBUG: Starting PeerSocket listener");
+        log_debug!("Starting PeerSocket listener");
         let (tx, rx) = mpsc::channel();
 
         let socket = self
             .socket
             .try_clone()
             .map_err(PeerSocketErr::CloneSocketError)?;
+        let consent = Arc::clone(&self.consent);
 
         self.receiver = Some(rx);
         let handle = thread::spawn(move || {
@@ -55,7 +86,7 @@ impl PeerSocket {
                     Ok((size, src_addr)) => {
                         let data = buffer[..size].to_vec();
                         // First: check if it's a STUN message and handle iT
-                        if Self::handle_stun_message(&socket, &data, src_addr) {
+                        if Self::handle_stun_message(&socket, &data, src_addr, &consent) {
                             continue;
                         }
 
@@ -65,8 +96,8 @@ impl PeerSocket {
                             if *first_byte >= 20 && *first_byte <= 63 {
                                 if let Some(ref d_tx) = dtls_sender {
                                     if let Err(e) = d_tx.send(data) {
-                                        println!(
-                                            "DEBUG: DTLS channel send failed ({}), keeping listener alive",
+                                        log_debug!(
+                                            "DTLS channel send failed ({}), keeping listener alive",
                                             e
                                         );
                                     }
@@ -76,8 +107,8 @@ impl PeerSocket {
                         }
                         // If it was not STUN nor DTLS, we send it back.
                         if let Err(e) = tx.send((data, src_addr)) {
-                            println!(
-                                "DEBUG: RTP/RTCP channel closed ({}), dropping packet but listener stays alive",
+                            log_debug!(
+                                "RTP/RTCP channel closed ({}), dropping packet but listener stays alive",
                                 e
                             );
                             continue;
@@ -92,13 +123,13 @@ impl PeerSocket {
                             continue;
                         }
                         _ => {
-                            println!("DEBUG: PeerSocket listener recv_from error: {}", err);
+                            log_debug!("PeerSocket listener recv_from error: {}", err);
                             break;
                         }
                     },
                 }
             }
-            println!("DEBUG: PeerSocket listener exiting");
+            log_debug!("PeerSocket listener exiting");
         });
         self.handler.push(handle);
         Ok(())
@@ -120,6 +151,7 @@ impl PeerSocket {
             self.socket
                 .send_to(data, addr)
                 .map_err(PeerSocketErr::SendError)?;
+            self.last_send.set(Instant::now());
             Ok(())
         } else {
             Err(PeerSocketErr::NotConnectedSocket)
@@ -131,9 +163,10 @@ impl PeerSocket {
     pub fn update_remote_addr(&mut self, new_addr: SocketAddr) {
         if let Some(current) = self.remote_addr {
             if current != new_addr {
-                println!(
-                    "DEBUG: Remote address changed from {} to {} (NAT rebind detected)",
-                    current, new_addr
+                log_debug!(
+                    "Remote address changed from {} to {} (NAT rebind detected)",
+                    current,
+                    new_addr
                 );
                 self.remote_addr = Some(new_addr);
             }
@@ -142,6 +175,41 @@ impl PeerSocket {
         }
     }
 
+    /// Time since the last packet sent with [`Self::send`].
+    pub fn idle_for(&self) -> Duration {
+        self.last_send.get().elapsed()
+    }
+
+    /// Sends a STUN Binding Indication to the remote address if nothing was
+    /// sent for `interval`. Returns whether one went out.
+    pub fn send_keepalive_if_idle(&self, interval: Duration) -> Result<bool, PeerSocketErr> {
+        if self.idle_for() < interval {
+            return Ok(false);
+        }
+        self.send(&StunMessage::create_binding_indication())?;
+        Ok(true)
+    }
+
+    /// Sends a round of consent binding requests to `targets` (RFC 7675).
+    /// Returns how many of them went out.
+    pub fn send_consent_requests(&self, targets: &[SocketAddr]) -> usize {
+        let requests = match self.consent.lock() {
+            Ok(mut consent) => consent.start_round(targets),
+            Err(_) => return 0,
+        };
+        requests
+            .iter()
+            .filter(|(target, request)| self.socket.send_to(request, target).is_ok())
+            .count()
+    }
+
+    /// Switches to the address that answered the last consent round, if any.
+    pub fn adopt_consented_addr(&mut self) -> Option<SocketAddr> {
+        let confirmed = self.consent.lock().ok()?.take_confirmed()?;
+        self.update_remote_addr(confirmed);
+        Some(confirmed)
+    }
+
     /// Returns the receiver channel associated with the listener thread.
     pub fn get_receiver(&mut self) -> Result<Receiver<(Vec<u8>, SocketAddr)>, PeerSocketErr> {
         if let Some(receiver) = self.receiver.take() {
@@ -171,8 +239,14 @@ impl PeerSocket {
         &self.socket
     }
 
-    /// Automatically responds to STUN Binding Request messages.
-    fn handle_stun_message(socket: &UdpSocket, data: &[u8], src_addr: SocketAddr) -> bool {
+    /// Automatically responds to STUN Binding Request messages. Binding
+    /// responses are matched against the pending consent checks.
+    fn handle_stun_message(
+        socket: &UdpSocket,
+        data: &[u8],
+        src_addr: SocketAddr,
+        consent: &Mutex<ConsentFreshness>,
+    ) -> bool {
         if data.len() < 20 {
             return false;
         }
@@ -185,10 +259,102 @@ impl PeerSocket {
                     let _ = socket.send_to(&response, src_addr);
                     true
                 }
-                MessageType::BindingResponse => true,
+                MessageType::BindingResponse => {
+                    if let Ok(mut consent) = consent.lock() {
+                        consent.handle_response(&message.transaction_id, src_addr);
+                    }
+                    true
+                }
+                // Keepalive from the remote, nothing to answer
+                MessageType::BindingIndication => true,
                 _ => false,
             },
             Err(_) => false,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_consent_response_moves_remote_address() -> Result<(), PeerSocketErr> {
+        let mut remote = PeerSocket::new(Some("127.0.0.1:0"))?;
+        remote.listener(None)?;
+        let mut local = PeerSocket::new(Some("127.0.0.1:0"))?;
+        local.listener(None)?;
+        local
+            .add_remote_address("127.0.0.1:9")
+            .map_err(PeerSocketErr::SendError)?;
+
+        assert_eq!(local.send_consent_requests(&[remote.local_addr()]), 1);
+
+        let deadline = Instant::now() + Duration::from_secs(2);
+        let mut adopted = None;
+        while adopted.is_none() && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+            adopted = local.adopt_consented_addr();
+        }
+
+        assert_eq!(adopted, Some(remote.local_addr()));
+        assert_eq!(local.remote_addr(), Some(remote.local_addr()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_bind_in_range_skips_taken_ports_and_reports_exhaustion() -> Result<(), PeerSocketErr> {
+        let localhost = IpAddr::from([127, 0, 0, 1]);
+        // El primer puerto del rango ya lo tiene otro socket
+        let taken = UdpSocket::bind("127.0.0.1:0").map_err(PeerSocketErr::BindSocketError)?;
+        let port = taken
+            .local_addr()
+            .map_err(PeerSocketErr::SetLocalAddrError)?
+            .port();
+        let Some(range) = port
+            .checked_add(1)
+            .and_then(|next| PortRange::new(port, next))
+        else {
+            return Ok(());
+        };
+
+        match PeerSocket::bind_in_range(localhost, range) {
+            // El siguiente puede estar ocupado por otro proceso
+            Ok(socket) => assert_eq!(socket.local_addr().port(), port + 1),
+            Err(PeerSocketErr::PortRangeExhausted(r)) => assert_eq!(r, range),
+            Err(err) => return Err(err),
+        }
+
+        let single = PortRange::new(port, port).unwrap();
+        let err = PeerSocket::bind_in_range(localhost, single).err();
+        assert!(matches!(err, Some(PeerSocketErr::PortRangeExhausted(r)) if r == single));
+        assert!(err
+            .unwrap()
+            .to_string()
+            .contains(&format!("{}-{}", port, port)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_keepalive_only_when_idle_and_not_delivered_as_media() -> Result<(), PeerSocketErr> {
+        let mut remote = PeerSocket::new(Some("127.0.0.1:0"))?;
+        remote.listener(None)?;
+        let media = remote.get_receiver()?;
+        let mut local = PeerSocket::new(Some("127.0.0.1:0"))?;
+        local
+            .add_remote_address(&remote.local_addr().to_string())
+            .map_err(PeerSocketErr::SendError)?;
+
+        local.send(b"media")?;
+        assert!(!local.send_keepalive_if_idle(Duration::from_secs(60))?);
+        assert!(local.send_keepalive_if_idle(Duration::ZERO)?);
+        assert!(local.idle_for() < Duration::from_secs(1));
+
+        // Only the media packet reaches the RTP/RTCP channel
+        let (data, _) = media.recv_timeout(Duration::from_secs(2)).unwrap();
+        assert_eq!(data, b"media");
+        assert!(media.recv_timeout(Duration::from_millis(200)).is_err());
+        Ok(())
+    }
+}
@@ -1,7 +1,8 @@
 use crate::rtc::rtc_const::err_const::{
-    BINDING_ERROR, CLONE_ERROR, CONNECT_ERROR, LOCAL_ADDR_ERROR, PEER_SOCKET_ERROR, RECEIVER_ERROR,
-    SEND_ERROR,
+    BINDING_ERROR, CLONE_ERROR, CONNECT_ERROR, LOCAL_ADDR_ERROR, PEER_SOCKET_ERROR,
+    PORT_RANGE_ERROR, RECEIVER_ERROR, SEND_ERROR,
 };
+use crate::rtc::socket::port_range::PortRange;
 use std::fmt;
 use std::io::Error;
 
@@ -15,6 +16,8 @@ pub enum PeerSocketErr {
     SendError(Error),
     PoisonedThread,
     SetRemoteAddrError,
+    /// Every port of the configured media range is taken.
+    PortRangeExhausted(PortRange),
 }
 
 impl fmt::Display for PeerSocketErr {
@@ -42,6 +45,11 @@ impl fmt::Display for PeerSocketErr {
             PeerSocketErr::SetRemoteAddrError => {
                 writeln!(f, "{}: Remote address error ", PEER_SOCKET_ERROR)
             }
+            PeerSocketErr::PortRangeExhausted(range) => writeln!(
+                f,
+                "{}: \"{}\" no free UDP port in {}",
+                PEER_SOCKET_ERROR, PORT_RANGE_ERROR, range
+            ),
         }
     }
 }
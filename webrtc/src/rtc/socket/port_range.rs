@@ -0,0 +1,59 @@
+//! Local UDP ports the media socket may use.
+
+use std::fmt;
+
+/// Inclusive range of local ports, so a firewall only has to open a known
+/// range for the media path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PortRange {
+    min: u16,
+    max: u16,
+}
+
+impl PortRange {
+    /// Returns `None` for an empty range or one that includes port 0, which
+    /// would let the OS pick any port.
+    pub fn new(min: u16, max: u16) -> Option<Self> {
+        (min > 0 && min <= max).then_some(Self { min, max })
+    }
+
+    pub fn min(&self) -> u16 {
+        self.min
+    }
+
+    pub fn max(&self) -> u16 {
+        self.max
+    }
+
+    pub fn contains(&self, port: u16) -> bool {
+        (self.min..=self.max).contains(&port)
+    }
+
+    pub fn ports(&self) -> impl Iterator<Item = u16> {
+        self.min..=self.max
+    }
+}
+
+impl fmt::Display for PortRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.min, self.max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_invalid_ranges_are_rejected() {
+        assert_eq!(PortRange::new(0, 10), None);
+        assert_eq!(PortRange::new(5000, 4999), None);
+        let single = PortRange::new(5000, 5000).unwrap();
+        assert_eq!(single.ports().collect::<Vec<_>>(), vec![5000]);
+        assert!(!single.contains(5001));
+        assert_eq!(
+            PortRange::new(40000, 40100).unwrap().to_string(),
+            "40000-40100"
+        );
+    }
+}
@@ -0,0 +1,293 @@
+//! BUNDLE routing: audio and video share one transport and incoming RTP is
+//! demultiplexed by the SSRCs each peer declares in its SDP.
+
+use std::collections::HashMap;
+
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+
+use crate::protocols::rtp::rtp_header::RtpHeader;
+use crate::protocols::sdp::media_type::MediaType;
+use crate::protocols::sdp::session_description::SessionDescription;
+
+const CNAME_LEN: usize = 16;
+const RTP_MIN_HEADER_LEN: usize = 12;
+
+/// SSRCs and CNAME announced for our outgoing streams.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LocalStreams {
+    pub audio_ssrc: u32,
+    pub video_ssrc: u32,
+    pub cname: String,
+}
+
+impl LocalStreams {
+    /// Picks random, distinct SSRCs and a random CNAME.
+    pub fn generate() -> Self {
+        let mut rng = rand::thread_rng();
+        let audio_ssrc: u32 = rng.gen();
+        let mut video_ssrc: u32 = rng.gen();
+        while video_ssrc == audio_ssrc {
+            video_ssrc = rng.gen();
+        }
+        let cname = (&mut rng)
+            .sample_iter(&Alphanumeric)
+            .take(CNAME_LEN)
+            .map(char::from)
+            .collect();
+
+        LocalStreams {
+            audio_ssrc,
+            video_ssrc,
+            cname,
+        }
+    }
+
+    /// SSRC of the outgoing stream of the given media.
+    pub fn ssrc(&self, media_type: MediaType) -> u32 {
+        match media_type {
+            MediaType::Audio => self.audio_ssrc,
+            MediaType::Video => self.video_ssrc,
+        }
+    }
+
+    /// Which of our streams sends with `ssrc`, if any.
+    pub fn media_of(&self, ssrc: u32) -> Option<MediaType> {
+        if ssrc == self.audio_ssrc {
+            Some(MediaType::Audio)
+        } else if ssrc == self.video_ssrc {
+            Some(MediaType::Video)
+        } else {
+            None
+        }
+    }
+
+    /// Gives the stream of `media_type` a new random SSRC that is not ours
+    /// nor `taken`, as RFC 3550 asks after a collision. Returns the old one.
+    pub fn replace_ssrc(&mut self, media_type: MediaType, taken: impl Fn(u32) -> bool) -> u32 {
+        let mut rng = rand::thread_rng();
+        let mut ssrc: u32 = rng.gen();
+        while self.media_of(ssrc).is_some() || taken(ssrc) {
+            ssrc = rng.gen();
+        }
+        let slot = match media_type {
+            MediaType::Audio => &mut self.audio_ssrc,
+            MediaType::Video => &mut self.video_ssrc,
+        };
+        std::mem::replace(slot, ssrc)
+    }
+}
+
+/// What the listener does with a packet from the media socket.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PacketRoute {
+    /// RTP from an SSRC the remote declared for that media.
+    Media(MediaType, u32),
+    /// RTP from an SSRC the remote never declared.
+    Unknown(u32),
+    Rtcp,
+    /// Too short to be RTP or RTCP.
+    Malformed,
+}
+
+impl PacketRoute {
+    /// SSRC of the RTP packet, RTCP and malformed packets have none.
+    pub fn ssrc(&self) -> Option<u32> {
+        match self {
+            PacketRoute::Media(_, ssrc) | PacketRoute::Unknown(ssrc) => Some(*ssrc),
+            PacketRoute::Rtcp | PacketRoute::Malformed => None,
+        }
+    }
+}
+
+/// RTP and RTCP share the port with rtcp-mux: RTCP packet types fall in
+/// 192..=223, where no RTP payload type (plus marker bit) lands (RFC 5761).
+pub fn is_rtcp_packet(packet: &[u8]) -> bool {
+    packet.len() >= 4 && (192..=223).contains(&packet[1])
+}
+
+/// SSRC -> media table built from the remote description.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SsrcRouter {
+    routes: HashMap<u32, MediaType>,
+}
+
+impl SsrcRouter {
+    /// Maps every `a=ssrc` of each remote m-line to that section's media.
+    /// Rejected sections (port 0) are skipped.
+    pub fn from_remote(remote: &SessionDescription) -> Self {
+        let mut routes = HashMap::new();
+        for media in remote
+            .get_media_descriptions()
+            .iter()
+            .filter(|media| media.port() != 0)
+        {
+            for ssrc in media.ssrcs() {
+                routes.insert(ssrc, media.media_type());
+            }
+        }
+        SsrcRouter { routes }
+    }
+
+    /// Media the given SSRC belongs to, if the remote declared it.
+    pub fn route(&self, ssrc: u32) -> Option<MediaType> {
+        self.routes.get(&ssrc).copied()
+    }
+
+    /// SSRC the remote declared for the given media. With several, the
+    /// lowest one, so the choice does not depend on map order.
+    pub fn remote_ssrc(&self, media_type: MediaType) -> Option<u32> {
+        self.routes
+            .iter()
+            .filter(|(_, media)| **media == media_type)
+            .map(|(ssrc, _)| *ssrc)
+            .min()
+    }
+
+    /// Remote stream `old` now sends as `new` (it changed after a
+    /// collision). Returns the media of the moved route.
+    pub fn replace(&mut self, old: u32, new: u32) -> Option<MediaType> {
+        let media = self.routes.remove(&old)?;
+        self.routes.insert(new, media);
+        Some(media)
+    }
+
+    /// Tells RTCP apart from RTP and routes the latter by SSRC.
+    pub fn classify(&self, packet: &[u8]) -> PacketRoute {
+        if is_rtcp_packet(packet) {
+            return PacketRoute::Rtcp;
+        }
+        if packet.len() < RTP_MIN_HEADER_LEN {
+            return PacketRoute::Malformed;
+        }
+        match RtpHeader::read_bytes(packet) {
+            Ok((header, _)) => {
+                let ssrc = header.get_ssrc();
+                match self.route(ssrc) {
+                    Some(media) => PacketRoute::Media(media, ssrc),
+                    None => PacketRoute::Unknown(ssrc),
+                }
+            }
+            Err(_) => PacketRoute::Malformed,
+        }
+    }
+
+    /// Reads the SSRC of an RTP packet and routes it.
+    pub fn route_packet(&self, packet: &[u8]) -> Option<MediaType> {
+        if packet.len() < RTP_MIN_HEADER_LEN {
+            return None;
+        }
+        let (header, _) = RtpHeader::read_bytes(packet).ok()?;
+        self.route(header.get_ssrc())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.routes.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+    use std::sync::mpsc;
+
+    fn rtp_packet(ssrc: u32) -> Vec<u8> {
+        let header = RtpHeader::new(2, false, false, 0, false, 96, 1, 1, ssrc, vec![]);
+        let mut bytes = header.write_bytes();
+        bytes.extend_from_slice(&[1, 2, 3]);
+        bytes
+    }
+
+    fn remote_with_ssrcs(audio_ssrc: u32, video_ssrc: u32) -> SessionDescription {
+        let sdp = format!(
+            "v=0\no=- 1 1 IN IP4 0.0.0.0\nt=0\na=group:BUNDLE audio video\n\
+             m=audio 9 RTP/SAVP 111\na=mid:audio\na=ssrc:{} cname:peer\n\
+             m=video 9 RTP/SAVP 96\na=mid:video\na=ssrc:{} cname:peer\n",
+            audio_ssrc, video_ssrc
+        );
+        SessionDescription::from_str(&sdp).unwrap()
+    }
+
+    #[test]
+    fn test_generate_local_streams_uses_distinct_ssrcs() {
+        let streams = LocalStreams::generate();
+        assert_ne!(streams.audio_ssrc, streams.video_ssrc);
+        assert_eq!(streams.cname.len(), CNAME_LEN);
+        assert_eq!(streams.ssrc(MediaType::Audio), streams.audio_ssrc);
+    }
+
+    #[test]
+    fn test_router_sends_arbitrary_ssrcs_to_their_sinks() {
+        let router = SsrcRouter::from_remote(&remote_with_ssrcs(0xDEAD_BEEF, 7));
+        let (audio_tx, audio_rx) = mpsc::channel();
+        let (video_tx, video_rx) = mpsc::channel();
+
+        for packet in [rtp_packet(7), rtp_packet(0xDEAD_BEEF), rtp_packet(7)] {
+            match router.route_packet(&packet) {
+                Some(MediaType::Audio) => audio_tx.send(packet).unwrap(),
+                Some(MediaType::Video) => video_tx.send(packet).unwrap(),
+                None => panic!("packet was not routed"),
+            }
+        }
+
+        assert_eq!(audio_rx.try_iter().count(), 1);
+        assert_eq!(video_rx.try_iter().count(), 2);
+    }
+
+    #[test]
+    fn test_router_ignores_undeclared_ssrc() {
+        let router = SsrcRouter::from_remote(&remote_with_ssrcs(1000, 2000));
+        assert_eq!(router.route(1000), Some(MediaType::Audio));
+        assert_eq!(router.route(2000), Some(MediaType::Video));
+        assert_eq!(router.route(3000), None);
+        assert_eq!(router.route_packet(&[0x80, 96]), None);
+    }
+
+    #[test]
+    fn test_classify_separates_rtcp_unknown_and_malformed() {
+        let router = SsrcRouter::from_remote(&remote_with_ssrcs(1000, 2000));
+        assert_eq!(
+            router.classify(&rtp_packet(1000)),
+            PacketRoute::Media(MediaType::Audio, 1000)
+        );
+        // Antes un SSRC desconocido terminaba en el pipeline de video
+        assert_eq!(
+            router.classify(&rtp_packet(3000)),
+            PacketRoute::Unknown(3000)
+        );
+        assert_eq!(router.classify(&rtp_packet(3000)).ssrc(), Some(3000));
+        let bye = crate::protocols::rtcp::rtcp_packet::RtcpPacket::bye(1000).write_bytes();
+        assert_eq!(router.classify(&bye), PacketRoute::Rtcp);
+        assert_eq!(router.classify(&[0x80, 96, 0, 1]), PacketRoute::Malformed);
+    }
+
+    #[test]
+    fn test_replace_ssrc_after_collision() {
+        let mut streams = LocalStreams::generate();
+        let old_video = streams.video_ssrc;
+        let audio = streams.audio_ssrc;
+        let old = streams.replace_ssrc(MediaType::Video, |ssrc| ssrc % 2 == 0);
+        assert_eq!(old, old_video);
+        assert_ne!(streams.video_ssrc, old_video);
+        assert_ne!(streams.video_ssrc, audio);
+        assert_eq!(streams.video_ssrc % 2, 1);
+        assert_eq!(streams.audio_ssrc, audio);
+        assert_eq!(streams.media_of(streams.video_ssrc), Some(MediaType::Video));
+        assert_eq!(streams.media_of(old_video), None);
+
+        let mut router = SsrcRouter::from_remote(&remote_with_ssrcs(1000, 2000));
+        assert_eq!(router.replace(2000, 2500), Some(MediaType::Video));
+        assert_eq!(router.route(2500), Some(MediaType::Video));
+        assert_eq!(router.route(2000), None);
+        assert_eq!(router.replace(9999, 1), None);
+    }
+
+    #[test]
+    fn test_remote_ssrc_per_media() {
+        let router = SsrcRouter::from_remote(&remote_with_ssrcs(1000, 2000));
+        assert_eq!(router.remote_ssrc(MediaType::Audio), Some(1000));
+        assert_eq!(router.remote_ssrc(MediaType::Video), Some(2000));
+        assert_eq!(SsrcRouter::default().remote_ssrc(MediaType::Video), None);
+    }
+}
@@ -1,80 +1,134 @@
 use crate::ice::{CandidateType, IceAgent, IceCandidate};
+use crate::protocols::rtp::header_extension::{ABS_SEND_TIME_URI, AUDIO_LATENCY_URI};
+use crate::protocols::sdp::media_type::MediaType;
+use crate::rtc::codec_negotiation::{NegotiatedCodecs, SUPPORTED_CODECS};
+use crate::rtc::ssrc_routing::LocalStreams;
 use crate::protocols::sdp::{
-    address_type::AddressType, attribute::Attribute, media_description::MediaDescription,
-    media_type::MediaType, net_type::NetType, origin::Origin, sdp_version::SdpVersion, session_description::SessionDescription, time::Time, transport_protocol::TransportProtocol, value_attribute::ValueAttribute
+    attribute::CandidateInfo, session_description::SessionDescription,
+    session_description_builder::{MediaBuilder, SessionDescriptionBuilder},
 };
 
 /// Generates an SDP session from ICE agent state and an optional DTLS fingerprint.
 pub fn ice_to_sdp(ice_agent: &IceAgent, fingerprint: Option<&str>) -> SessionDescription {
-    let version = SdpVersion::new(0);
+    ice_to_sdp_with_codecs(
+        ice_agent,
+        fingerprint,
+        &NegotiatedCodecs::default(),
+        &LocalStreams::generate(),
+        true,
+    )
+}
+
+/// Same as [`ice_to_sdp`] but announcing the given payload types in the m-lines.
+/// Each section carries its `a=mid`, its direction and the SSRC/CNAME of our
+/// stream, and all of them are bundled on one transport. `rtcp_mux` adds
+/// `a=rtcp-mux` to every media section.
+pub fn ice_to_sdp_with_codecs(
+    ice_agent: &IceAgent,
+    fingerprint: Option<&str>,
+    codecs: &NegotiatedCodecs,
+    streams: &LocalStreams,
+    rtcp_mux: bool,
+) -> SessionDescription {
+    local_session_builder(ice_agent, fingerprint, codecs, streams, rtcp_mux).build()
+}
 
+/// Builder preloaded with our local description, so callers can still add
+/// session-level lines (e.g. bandwidth) before building it.
+pub fn local_session_builder(
+    ice_agent: &IceAgent,
+    fingerprint: Option<&str>,
+    codecs: &NegotiatedCodecs,
+    streams: &LocalStreams,
+    rtcp_mux: bool,
+) -> SessionDescriptionBuilder {
     let timestamp = match std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH) {
         Ok(dur) => dur.as_secs() as u32,
         Err(err) => {
-            eprintln!("ice_to_sdp: clock error (using 0): {}", err);
+            log_warn!("ice_to_sdp: clock error (using 0): {}", err);
             0
         }
     };
 
-    let origin = Origin::new(
-        "-".to_string(),
-        timestamp,
-        timestamp,
-        NetType::In,
-        AddressType::IP4,
-        "0.0.0.0".to_string(),
-    );
-
-    let time = Time::new(0);
-
-    let media_desc = MediaDescription::new(
-        MediaType::Video,
-        9,                         //dummy port
-        TransportProtocol::RtpSavp, // Usar RTP/SAVP para indicar que se usará SRTP (RTP Seguro)
-        vec![96],                   // dummy payload type
-    );
-
-    // ICE attributes
-
-    let mut attributes = Vec::new();
-
-    attributes.push(Attribute::new(
-        None,
-        Some(ValueAttribute::Group("BUNDLE 0".to_string())),
-    ));
-    attributes.push(Attribute::new(None, Some(ValueAttribute::MsidSemantic)));
-
-    // ICE attributes
-    attributes.push(Attribute::new(
-        None,
-        Some(ValueAttribute::IceUfrag(ice_agent.user_fragment.clone())),
-    ));
-
-    attributes.push(Attribute::new(
-        None,
-        Some(ValueAttribute::IcePwd(ice_agent.password.clone())),
-    ));
+    // Session-level connection line with the host candidate address
+    let connection_addr = ice_agent
+        .local_candidate
+        .iter()
+        .find(|candidate| matches!(candidate.candidate_type, CandidateType::Host))
+        .map(|candidate| candidate.address.as_str())
+        .unwrap_or("0.0.0.0");
+
+    let mut builder = SessionDescriptionBuilder::new()
+        .origin("-", timestamp)
+        .connection_address(connection_addr)
+        .msid_semantic()
+        .ice_credentials(&ice_agent.user_fragment, &ice_agent.password);
 
     // DTLS fingerprint
     if let Some(fp) = fingerprint {
-        attributes.push(Attribute::new(
-            None,
-            Some(ValueAttribute::Fingerprint("sha-256".to_string(), fp.to_string())),
-        ));
+        builder = builder.fingerprint("sha-256", fp);
     }
 
-    // ICE candidates
+    // One m-line per codec (audio first), each one with its rtpmap. All of them
+    // share the transport, so the candidates only go in the first accepted
+    // section. Rejected media keep their m-line with port 0 and stay out of the
+    // BUNDLE group.
+    let mut candidates_added = false;
+    for codec in SUPPORTED_CODECS.iter() {
+        let payload_type = codecs.payload_type(codec.media_type);
+        if !codecs.is_accepted(codec.media_type) {
+            let media = MediaBuilder::new(codec.media_type)
+                .port(0)
+                .payload_type(payload_type);
+            builder = builder.add_media(media);
+            continue;
+        }
 
-    for (idx, candidate) in ice_agent.local_candidate.iter().enumerate() {
-        let typ_str = match candidate.candidate_type {
-            CandidateType::Host => "host",
-            CandidateType::Srflx => "srflx",
-            CandidateType::Relay => "relay",
+        let mut media = MediaBuilder::new(codec.media_type)
+            .rtpmap(
+                payload_type,
+                codec.encoding_name,
+                codec.clock_rate,
+                codec.channels,
+            )
+            .mid(&codec.media_type.to_string())
+            .direction(codecs.direction(codec.media_type))
+            .ssrc(streams.ssrc(codec.media_type), &streams.cname)
+            .rtcp_mux(rtcp_mux);
+        // abs-send-time solo viaja en los paquetes de video y la sonda de
+        // latencia en los de audio
+        let extmap = match codec.media_type {
+            MediaType::Video => codecs.abs_send_time_id.map(|id| (id, ABS_SEND_TIME_URI)),
+            MediaType::Audio => codecs.audio_latency_id.map(|id| (id, AUDIO_LATENCY_URI)),
         };
+        if let Some((id, uri)) = extmap {
+            media = media.extmap(id, uri);
+        }
+        if !candidates_added {
+            for candidate in local_candidates(ice_agent) {
+                media = media.candidate(candidate);
+            }
+            candidates_added = true;
+        }
+        builder = builder.add_media(media);
+    }
 
-        attributes.push(Attribute::new(
-            None,
-            Some(ValueAttribute::Candidate {
+    builder
+}
+
+fn local_candidates(ice_agent: &IceAgent) -> Vec<CandidateInfo> {
+    ice_agent
+        .local_candidate
+        .iter()
+        .enumerate()
+        .map(|(idx, candidate)| {
+            let typ_str = match candidate.candidate_type {
+                CandidateType::Host => "host",
+                CandidateType::Srflx => "srflx",
+                CandidateType::Relay => "relay",
+                CandidateType::Prflx => "prflx",
+            };
+            CandidateInfo {
                 foundation: (idx + 1) as u32,
                 component: 1,
                 protocol: "UDP".to_string(),
@@ -82,11 +136,9 @@ pub fn ice_to_sdp(ice_agent: &IceAgent, fingerprint: Option<&str>) -> SessionDes
                 address: candidate.address.clone(),
                 port: candidate.port,
                 typ: typ_str.to_string(),
-            }),
-        ));
-    }
-
-    SessionDescription::new(version, origin, time, vec![media_desc], attributes)
+            }
+        })
+        .collect()
 }
 
 // gets the ICE candidates of SessionDescription
@@ -109,6 +161,7 @@ pub fn sdp_to_ice_candidates(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::protocols::sdp::media_direction::MediaDirection;
     use std::str::FromStr;
 
     #[test]
@@ -125,7 +178,7 @@ mod tests {
         let sdp = ice_to_sdp(&ice_agent, Some(dummy_fingerprint));
         let sdp_string = sdp.to_string();
 
-        println!("SDP generated:\n{}", sdp_string);
+        log_debug!("SDP generated:\n{}", sdp_string);
 
         // parse again
         let parsed_sdp = SessionDescription::from_str(&sdp_string).unwrap();
@@ -137,6 +190,169 @@ mod tests {
         assert_eq!(pwd, ice_agent.password);
         assert_eq!(candidates.len(), ice_agent.local_candidate.len());
     }
+    #[test]
+    fn test_ice_to_sdp_has_host_connection_line() {
+        let mut ice_agent = IceAgent::new();
+        ice_agent.gather_candidates().unwrap();
+
+        let sdp = ice_to_sdp(&ice_agent, None);
+        let parsed_sdp = SessionDescription::from_str(&sdp.to_string()).unwrap();
+        let host_addr = ice_agent
+            .local_candidate
+            .iter()
+            .find(|c| matches!(c.candidate_type, CandidateType::Host))
+            .map(|c| c.address.clone())
+            .unwrap_or_else(|| "0.0.0.0".to_string());
+
+        assert_eq!(
+            parsed_sdp.connection_info().map(|c| c.address().to_string()),
+            Some(host_addr)
+        );
+    }
+    #[test]
+    fn test_ice_to_sdp_has_audio_and_video_rtpmaps() {
+        let ice_agent = IceAgent::new();
+        let codecs = NegotiatedCodecs {
+            audio_pt: 109,
+            video_pt: 96,
+            ..NegotiatedCodecs::default()
+        };
+
+        let sdp_string =
+            ice_to_sdp_with_codecs(&ice_agent, None, &codecs, &LocalStreams::generate(), false)
+                .to_string();
+
+        assert!(sdp_string.contains("m=audio 9 RTP/SAVP 109\na=rtpmap:109 opus/48000/2\n"));
+        assert!(sdp_string.contains("m=video 9 RTP/SAVP 96\na=rtpmap:96 H264/90000\n"));
+    }
+    #[test]
+    fn test_ice_to_sdp_declares_rtcp_mux() {
+        let ice_agent = IceAgent::new();
+
+        let sdp_string = ice_to_sdp(&ice_agent, None).to_string();
+        let parsed_sdp = SessionDescription::from_str(&sdp_string).unwrap();
+
+        assert!(sdp_string.contains("a=rtcp-mux\n"));
+        assert!(parsed_sdp.has_rtcp_mux());
+    }
+    #[test]
+    fn test_ice_to_sdp_bundles_media_with_mid_and_ssrc() {
+        let ice_agent = IceAgent::new();
+        let streams = LocalStreams {
+            audio_ssrc: 0xCAFE_0001,
+            video_ssrc: 42,
+            cname: "room".to_string(),
+        };
+
+        let sdp_string = ice_to_sdp_with_codecs(
+            &ice_agent,
+            None,
+            &NegotiatedCodecs::default(),
+            &streams,
+            true,
+        )
+        .to_string();
+        let parsed_sdp = SessionDescription::from_str(&sdp_string).unwrap();
+
+        assert!(sdp_string.contains("a=group:BUNDLE audio video\n"));
+        let medias = parsed_sdp.get_media_descriptions();
+        assert_eq!(medias[0].mid().as_deref(), Some("audio"));
+        assert_eq!(medias[0].ssrcs(), vec![0xCAFE_0001]);
+        assert_eq!(medias[1].mid().as_deref(), Some("video"));
+        assert_eq!(medias[1].ssrcs(), vec![42]);
+        assert_eq!(medias[1].direction(), Some(MediaDirection::SendRecv));
+        assert!(sdp_string.contains("a=ssrc:42 cname:room\n"));
+    }
+    #[test]
+    fn test_ice_to_sdp_declares_abs_send_time_on_video() {
+        let ice_agent = IceAgent::new();
+
+        let sdp = ice_to_sdp(&ice_agent, None);
+        let medias = sdp.get_media_descriptions();
+
+        assert_eq!(medias[0].extmap_id(ABS_SEND_TIME_URI), None);
+        assert_eq!(
+            medias[1].extmap_id(ABS_SEND_TIME_URI),
+            NegotiatedCodecs::default().abs_send_time_id
+        );
+        assert_eq!(
+            medias[0].extmap_id(AUDIO_LATENCY_URI),
+            NegotiatedCodecs::default().audio_latency_id
+        );
+        assert_eq!(medias[1].extmap_id(AUDIO_LATENCY_URI), None);
+
+        let codecs = NegotiatedCodecs {
+            abs_send_time_id: None,
+            audio_latency_id: None,
+            ..NegotiatedCodecs::default()
+        };
+        let sdp_string =
+            ice_to_sdp_with_codecs(&ice_agent, None, &codecs, &LocalStreams::generate(), true)
+                .to_string();
+        assert!(!sdp_string.contains("a=extmap"));
+    }
+    #[test]
+    fn test_ice_to_sdp_puts_candidates_in_first_bundled_section() {
+        let mut ice_agent = IceAgent::new();
+        ice_agent.gather_candidates().unwrap();
+
+        let sdp = ice_to_sdp(&ice_agent, None);
+        let medias = sdp.get_media_descriptions();
+
+        assert_eq!(medias[0].candidates().len(), ice_agent.local_candidate.len());
+        assert!(medias[1].candidates().is_empty());
+    }
+    fn candidate(
+        name: &str,
+        address: &str,
+        port: u32,
+        candidate_type: CandidateType,
+    ) -> IceCandidate {
+        IceCandidate {
+            name: name.to_string(),
+            address: address.to_string(),
+            port,
+            candidate_type,
+            priority: 100,
+        }
+    }
+    #[test]
+    fn test_every_local_candidate_round_trips() {
+        let mut ice_agent = IceAgent::new();
+        ice_agent.local_candidate = vec![
+            candidate("host-0", "192.168.1.10", 50000, CandidateType::Host),
+            candidate("srflx-1", "203.0.113.7", 61000, CandidateType::Srflx),
+            candidate("relay-2", "198.51.100.20", 3479, CandidateType::Relay),
+        ];
+
+        let sdp_string = ice_to_sdp(&ice_agent, Some("AA:BB")).to_string();
+        assert_eq!(sdp_string.matches("a=candidate:").count(), 3);
+
+        let parsed_sdp = SessionDescription::from_str(&sdp_string).unwrap();
+        let (_, _, candidates, _) = sdp_to_ice_candidates(&parsed_sdp).unwrap();
+        let announced: Vec<_> = candidates
+            .iter()
+            .map(|c| (c.address.as_str(), c.port, c.candidate_type.clone()))
+            .collect();
+        assert_eq!(
+            announced,
+            vec![
+                ("192.168.1.10", 50000, CandidateType::Host),
+                ("203.0.113.7", 61000, CandidateType::Srflx),
+                ("198.51.100.20", 3479, CandidateType::Relay),
+            ]
+        );
+
+        // El peer los empareja todos con su candidato local
+        let mut remote_agent = IceAgent::new();
+        remote_agent.local_candidate =
+            vec![candidate("host-0", "10.0.0.2", 40000, CandidateType::Host)];
+        for candidate in candidates {
+            remote_agent.add_remote_candidate(candidate);
+        }
+        assert_eq!(remote_agent.remote_candidates().len(), 3);
+        assert_eq!(remote_agent.candidate_pairs().len(), 3);
+    }
     //WIP Hacer test con fingerprint
 
 }
@@ -1,6 +1,8 @@
 //! STUN client for discovering reflexive addresses using Binding Requests.
 
 use super::message::{MessageType, StunMessage};
+use super::nat::{classify_nat, NatReport, NatType};
+use crate::ice::probe_default_ipv4;
 use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
 use std::time::Duration;
 
@@ -87,6 +89,29 @@ impl StunClient {
         }
         Ok(None)
     }
+
+    /// Query the default server and then `others` from the same socket, and
+    /// compare the mapped addresses. Heuristic: only the mapping is probed,
+    /// not how the NAT filters incoming packets.
+    pub fn detect_nat(&self, socket: &UdpSocket, others: &[String]) -> NatReport {
+        let mapped: Vec<SocketAddr> = std::iter::once(&self.default_server)
+            .chain(
+                others
+                    .iter()
+                    .filter(|server| **server != self.default_server),
+            )
+            .filter_map(|server| self.query_server(socket, server).ok().flatten())
+            .collect();
+        NatReport {
+            public_addr: mapped.first().copied(),
+            nat_type: classify_nat(probe_default_ipv4(self), &mapped),
+        }
+    }
+
+    /// Same as [`StunClient::detect_nat`], keeping only the NAT type.
+    pub fn detect_nat_type(&self, socket: &UdpSocket, others: &[String]) -> NatType {
+        self.detect_nat(socket, others).nat_type
+    }
 }
 
 impl Default for StunClient {
@@ -8,6 +8,7 @@ use std::net::{IpAddr, SocketAddr};
 #[derive(Debug, Clone, PartialEq)]
 pub enum MessageType {
     BindingRequest,
+    BindingIndication,
     BindingResponse,
     BindingErrorResponse,
     Unknown(u16),
@@ -18,6 +19,7 @@ impl MessageType {
     pub fn from_u16(value: u16) -> Self {
         match value {
             0x0001 => MessageType::BindingRequest,
+            0x0011 => MessageType::BindingIndication,
             0x0101 => MessageType::BindingResponse,
             0x0111 => MessageType::BindingErrorResponse,
             other => MessageType::Unknown(other),
@@ -28,6 +30,7 @@ impl MessageType {
     pub fn to_u16(&self) -> u16 {
         match self {
             MessageType::BindingRequest => 0x0001,
+            MessageType::BindingIndication => 0x0011,
             MessageType::BindingResponse => 0x0101,
             MessageType::BindingErrorResponse => 0x0111,
             MessageType::Unknown(val) => *val,
@@ -69,6 +72,16 @@ impl StunMessage {
         (msg, transaction_id)
     }
 
+    /// Build a Binding Indication, used as a keepalive: it needs no answer.
+    pub fn create_binding_indication() -> Vec<u8> {
+        let mut msg = Vec::with_capacity(20);
+        msg.extend_from_slice(&MessageType::BindingIndication.to_u16().to_be_bytes());
+        msg.extend_from_slice(&0x0000u16.to_be_bytes());
+        msg.extend_from_slice(&MAGIC_COOKIE.to_be_bytes());
+        msg.extend_from_slice(&Self::generate_transaction_id());
+        msg
+    }
+
     /// Build a Binding Success Response with address XOR-MAPPED-ADDRESS.
     pub fn create_binding_success(transaction_id: [u8; 12], addr: SocketAddr) -> Vec<u8> {
         let mut msg = Vec::with_capacity(20 + 12);
@@ -100,6 +113,30 @@ impl StunMessage {
         msg
     }
 
+    /// Build a classic (RFC 3489) Binding Response with a plain MAPPED-ADDRESS.
+    ///
+    /// Classic requests carry a 16-byte transaction ID and no magic cookie.
+    pub fn create_classic_binding_success(transaction_id: [u8; 16], addr: SocketAddr) -> Vec<u8> {
+        let mut msg = Vec::with_capacity(20 + 12);
+
+        msg.extend_from_slice(&MessageType::BindingResponse.to_u16().to_be_bytes());
+        msg.extend_from_slice(&12u16.to_be_bytes());
+        msg.extend_from_slice(&transaction_id);
+
+        if let IpAddr::V4(ipv4) = addr.ip() {
+            // MAPPED-ADDRESS header
+            msg.extend_from_slice(&0x0001u16.to_be_bytes());
+            msg.extend_from_slice(&0x0008u16.to_be_bytes());
+
+            msg.push(0x00);
+            msg.push(0x01);
+            msg.extend_from_slice(&addr.port().to_be_bytes());
+            msg.extend_from_slice(&ipv4.octets());
+        }
+
+        msg
+    }
+
     /// Analyzes a STUN message and returns the structured representation.
     pub fn parse(data: &[u8]) -> Result<Self, Box<dyn std::error::Error>> {
         if data.len() < 20 {
@@ -142,7 +179,7 @@ impl StunMessage {
         let now = match SystemTime::now().duration_since(UNIX_EPOCH) {
             Ok(dur) => dur.as_nanos(),
             Err(err) => {
-                eprintln!("STUN txid clock error, using 0: {}", err);
+                log_warn!("STUN txid clock error, using 0: {}", err);
                 0
             }
         };
@@ -176,6 +213,16 @@ mod tests {
         assert_eq!(magic, MAGIC_COOKIE);
     }
 
+    #[test]
+    fn test_binding_indication_round_trip() {
+        let indication = StunMessage::create_binding_indication();
+        assert_eq!(indication.len(), 20);
+        let parsed = StunMessage::parse(&indication).unwrap();
+        assert_eq!(parsed.message_type, MessageType::BindingIndication);
+        assert_eq!(parsed.length, 0);
+        assert!(parsed.xor_mapped_address.is_none());
+    }
+
     #[test]
     fn test_message_type_conversion() {
         assert_eq!(MessageType::from_u16(0x0001), MessageType::BindingRequest);
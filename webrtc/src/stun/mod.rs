@@ -2,8 +2,12 @@ mod attributes;
 mod binding;
 mod client;
 mod message;
+mod nat;
+mod server;
 
 pub use client::StunClient;
 pub use message::{MessageType, StunMessage};
+pub use nat::{classify_nat, NatReport, NatType};
+pub use server::StunServer;
 pub const MAGIC_COOKIE: u32 = 0x2112A442;
 pub const STUN_HEADER_SIZE: usize = 20;
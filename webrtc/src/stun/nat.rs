@@ -0,0 +1,94 @@
+//! NAT type detection by comparing the addresses several STUN servers see.
+//!
+//! This is a heuristic. Plain STUN servers (RFC 5389) do not implement the
+//! CHANGE-REQUEST probes of RFC 3489, so filtering behavior cannot be told
+//! apart: full cone, restricted and port-restricted NATs all show up as
+//! [`NatType::EndpointIndependent`]. Only the mapping is compared, from a
+//! single socket towards different servers.
+
+use std::net::{IpAddr, SocketAddr};
+
+/// How a NAT maps outgoing packets from one local socket.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NatType {
+    /// The mapped address is the one of the local interface.
+    Open,
+    /// Every server sees the same `ip:port` (some kind of cone NAT).
+    EndpointIndependent,
+    /// Each server sees a different mapping: direct calls may need TURN.
+    Symmetric,
+    /// Only one server answered, so mappings could not be compared.
+    Unknown,
+    /// No server answered; UDP is probably filtered.
+    Blocked,
+}
+
+impl NatType {
+    pub fn label(&self) -> &'static str {
+        match self {
+            NatType::Open => "No NAT (public address)",
+            NatType::EndpointIndependent => "Cone NAT, calls should connect",
+            NatType::Symmetric => "Symmetric NAT, direct calls may fail",
+            NatType::Unknown => "Unknown (only one STUN server answered)",
+            NatType::Blocked => "No STUN answer, UDP may be blocked",
+        }
+    }
+
+    /// Whether a direct (non relayed) connection is likely to work.
+    pub fn allows_direct(&self) -> bool {
+        matches!(self, NatType::Open | NatType::EndpointIndependent)
+    }
+}
+
+/// Outcome of [`StunClient::detect_nat`](super::StunClient::detect_nat).
+#[derive(Debug, Clone)]
+pub struct NatReport {
+    /// Address seen by the first server that answered.
+    pub public_addr: Option<SocketAddr>,
+    pub nat_type: NatType,
+}
+
+/// Classify from the mapped addresses each server reported for the same
+/// socket. `local_ip` is the one of the outgoing interface.
+pub fn classify_nat(local_ip: Option<IpAddr>, mapped: &[SocketAddr]) -> NatType {
+    let Some(first) = mapped.first() else {
+        return NatType::Blocked;
+    };
+    if local_ip == Some(first.ip()) {
+        return NatType::Open;
+    }
+    if mapped.len() < 2 {
+        return NatType::Unknown;
+    }
+    if mapped.iter().all(|addr| addr == first) {
+        NatType::EndpointIndependent
+    } else {
+        NatType::Symmetric
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_nat() {
+        let public: SocketAddr = "203.0.113.7:40000".parse().unwrap();
+        let other_port: SocketAddr = "203.0.113.7:40001".parse().unwrap();
+        let local: IpAddr = "192.168.1.20".parse().unwrap();
+
+        assert_eq!(classify_nat(Some(local), &[]), NatType::Blocked);
+        assert_eq!(classify_nat(Some(public.ip()), &[public]), NatType::Open);
+        assert_eq!(classify_nat(Some(local), &[public]), NatType::Unknown);
+        assert_eq!(
+            classify_nat(Some(local), &[public, public, public]),
+            NatType::EndpointIndependent
+        );
+        // One server out of three seeing another port is enough
+        assert_eq!(
+            classify_nat(None, &[public, public, other_port]),
+            NatType::Symmetric
+        );
+        assert!(!NatType::Symmetric.allows_direct());
+    }
+}
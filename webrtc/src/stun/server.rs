@@ -0,0 +1,141 @@
+//! Minimal STUN server answering Binding Requests, for networks without
+//! access to public STUN servers.
+
+use super::message::{MessageType, StunMessage};
+use super::{MAGIC_COOKIE, STUN_HEADER_SIZE};
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::thread::{self, JoinHandle};
+
+const MAX_DATAGRAM_SIZE: usize = 1500;
+
+/// UDP STUN server that echoes back the source address of each request.
+pub struct StunServer {
+    socket: UdpSocket,
+}
+
+impl StunServer {
+    /// Bind the server to the given UDP address (e.g. `0.0.0.0:3478`).
+    pub fn bind<A: ToSocketAddrs>(addr: A) -> io::Result<Self> {
+        Ok(Self {
+            socket: UdpSocket::bind(addr)?,
+        })
+    }
+
+    /// Address the server is listening on.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    /// Serve requests until the socket fails.
+    pub fn run(&self) -> io::Result<()> {
+        let mut buf = [0u8; MAX_DATAGRAM_SIZE];
+        loop {
+            let (len, source) = match self.socket.recv_from(&mut buf) {
+                Ok(received) => received,
+                // ICMP port unreachable from a previous reply; not fatal.
+                Err(e) if e.kind() == io::ErrorKind::ConnectionReset => continue,
+                Err(e) => return Err(e),
+            };
+
+            if let Some(response) = binding_response(&buf[..len], source) {
+                if let Err(e) = self.socket.send_to(&response, source) {
+                    log_warn!("STUN server: failed to answer {}: {}", source, e);
+                }
+            }
+        }
+    }
+
+    /// Run the server in a background thread.
+    pub fn spawn(self) -> JoinHandle<()> {
+        thread::spawn(move || {
+            if let Err(e) = self.run() {
+                log_error!("STUN server stopped: {}", e);
+            }
+        })
+    }
+}
+
+/// Builds the answer to a Binding Request received from `source`.
+///
+/// RFC 5389 requests get an XOR-MAPPED-ADDRESS; classic RFC 3489 requests
+/// (no magic cookie) get a MAPPED-ADDRESS. Anything else is ignored.
+pub(crate) fn binding_response(request: &[u8], source: SocketAddr) -> Option<Vec<u8>> {
+    if request.len() < STUN_HEADER_SIZE || !source.is_ipv4() {
+        return None;
+    }
+
+    let message_type = MessageType::from_u16(u16::from_be_bytes([request[0], request[1]]));
+    if message_type != MessageType::BindingRequest {
+        return None;
+    }
+
+    let length = u16::from_be_bytes([request[2], request[3]]) as usize;
+    if !length.is_multiple_of(4) || STUN_HEADER_SIZE + length > request.len() {
+        return None;
+    }
+
+    let magic = u32::from_be_bytes([request[4], request[5], request[6], request[7]]);
+    if magic == MAGIC_COOKIE {
+        let mut transaction_id = [0u8; 12];
+        transaction_id.copy_from_slice(&request[8..20]);
+        Some(StunMessage::create_binding_success(transaction_id, source))
+    } else {
+        let mut transaction_id = [0u8; 16];
+        transaction_id.copy_from_slice(&request[4..20]);
+        Some(StunMessage::create_classic_binding_success(
+            transaction_id,
+            source,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{IpAddr, Ipv4Addr};
+
+    fn source() -> SocketAddr {
+        SocketAddr::new(IpAddr::V4(Ipv4Addr::new(192, 168, 1, 20)), 50000)
+    }
+
+    #[test]
+    fn test_rfc5389_request_gets_xor_mapped_address() {
+        let (request, transaction_id) = StunMessage::create_binding_request_with_transaction();
+        let response = binding_response(&request, source()).unwrap();
+
+        let parsed = StunMessage::parse(&response).unwrap();
+        assert_eq!(parsed.message_type, MessageType::BindingResponse);
+        assert_eq!(parsed.transaction_id, transaction_id);
+        assert_eq!(parsed.xor_mapped_address, Some(source()));
+    }
+
+    #[test]
+    fn test_classic_request_gets_mapped_address() {
+        let mut request = vec![0x00, 0x01, 0x00, 0x00];
+        let transaction_id: [u8; 16] = [7; 16];
+        request.extend_from_slice(&transaction_id);
+
+        let response = binding_response(&request, source()).unwrap();
+
+        assert_eq!(&response[0..2], &[0x01, 0x01]);
+        assert_eq!(&response[4..20], &transaction_id);
+        // MAPPED-ADDRESS, length 8, IPv4
+        assert_eq!(&response[20..26], &[0x00, 0x01, 0x00, 0x08, 0x00, 0x01]);
+        assert_eq!(u16::from_be_bytes([response[26], response[27]]), 50000);
+        assert_eq!(&response[28..32], &[192, 168, 1, 20]);
+    }
+
+    #[test]
+    fn test_non_binding_messages_are_ignored() {
+        let (mut request, _) = StunMessage::create_binding_request_with_transaction();
+        request[1] = 0x03;
+        assert!(binding_response(&request, source()).is_none());
+        assert!(binding_response(&[0u8; 10], source()).is_none());
+
+        // Length field pointing past the datagram
+        let (mut request, _) = StunMessage::create_binding_request_with_transaction();
+        request[3] = 0x08;
+        assert!(binding_response(&request, source()).is_none());
+    }
+}
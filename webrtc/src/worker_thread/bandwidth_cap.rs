@@ -0,0 +1,66 @@
+//! Splits a total send cap (`max_bitrate_kbps`) between audio and video.
+//!
+//! Audio gets a tenth of the cap, kept between `MIN_AUDIO_BPS` and
+//! `MAX_AUDIO_BPS`, plus the RTP/SRTP/UDP/IP headers of its 50 packets per
+//! second. Video gets the rest: the pacer sends at that rate and the encoder
+//! aims a bit lower so the headers of its own packets fit too.
+
+/// Opus bitrate under the tightest caps; speech is still intelligible.
+pub const MIN_AUDIO_BPS: u32 = 12_000;
+/// Opus bitrate once the cap is loose enough.
+pub const MAX_AUDIO_BPS: u32 = 32_000;
+/// Headers of one audio packet every 20 ms: 12 (RTP) + 10 (SRTP tag) +
+/// 8 (UDP) + 20 (IPv4) bytes.
+const AUDIO_OVERHEAD_BPS: u64 = 50 * 50 * 8;
+/// Part of the video share left for packet headers.
+const VIDEO_OVERHEAD_PCT: u64 = 10;
+/// Video never gets less than this, even if the cap does not leave it.
+pub const MIN_VIDEO_KBPS: u64 = 32;
+
+/// Opus bitrate for a total cap of `total_kbps`.
+pub fn audio_bps(total_kbps: u64) -> u32 {
+    // A tenth of the cap, in bits per second
+    let tenth_bps = total_kbps.saturating_mul(100);
+    tenth_bps.clamp(u64::from(MIN_AUDIO_BPS), u64::from(MAX_AUDIO_BPS)) as u32
+}
+
+/// What the video packets may use under `total_kbps`: the pacer's rate.
+pub fn video_kbps(total_kbps: u64) -> u64 {
+    let audio_kbps = (u64::from(audio_bps(total_kbps)) + AUDIO_OVERHEAD_BPS).div_ceil(1000);
+    total_kbps.saturating_sub(audio_kbps).max(MIN_VIDEO_KBPS)
+}
+
+/// Encoder target under `total_kbps`: the video share minus its headers.
+pub fn video_encoder_kbps(total_kbps: u64) -> u64 {
+    (video_kbps(total_kbps) * (100 - VIDEO_OVERHEAD_PCT) / 100).max(MIN_VIDEO_KBPS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_audio_drops_under_tight_caps() {
+        assert_eq!(audio_bps(2_000), MAX_AUDIO_BPS);
+        assert_eq!(audio_bps(500), MAX_AUDIO_BPS);
+        assert_eq!(audio_bps(200), 20_000);
+        assert_eq!(audio_bps(64), MIN_AUDIO_BPS);
+    }
+
+    #[test]
+    fn test_shares_add_up_to_the_cap() {
+        for total in [100, 250, 500, 1_000, 4_000] {
+            let audio_kbps = (u64::from(audio_bps(total)) + AUDIO_OVERHEAD_BPS) / 1000;
+            assert!(audio_kbps + video_kbps(total) <= total, "{}", total);
+            assert!(video_encoder_kbps(total) < video_kbps(total));
+        }
+        assert_eq!(video_kbps(500), 448);
+        assert_eq!(video_encoder_kbps(500), 403);
+    }
+
+    #[test]
+    fn test_video_keeps_a_floor() {
+        assert_eq!(video_kbps(10), MIN_VIDEO_KBPS);
+        assert_eq!(video_encoder_kbps(10), MIN_VIDEO_KBPS);
+    }
+}
@@ -1,20 +1,83 @@
 use crate::camera::camera_err::CameraError;
 use crate::camera::camera_opencv::Camera;
+use crate::camera::frame_source::FrameSource;
 use crate::worker_thread::error::worker_error::WorkerError;
+use crate::worker_thread::media_channel::MediaSender;
+use crate::worker_thread::worker_media::VideoParams;
 use opencv::prelude::Mat;
-use std::sync::mpsc::SyncSender;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{Receiver, SyncSender};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+/// How often a paused camera checks whether it was resumed.
+const PAUSED_POLL: Duration = Duration::from_millis(50);
 
 pub struct CameraThread {
-    tx_bgr: SyncSender<Mat>,
-    tx_rgb: SyncSender<Mat>,
+    tx_bgr: MediaSender<Mat>,
+    // None when the frames only feed the preview
+    tx_rgb: Option<SyncSender<Mat>>,
+    rx_params: Receiver<VideoParams>,
+    enabled: Arc<AtomicBool>,
+    stop: Arc<AtomicBool>,
 }
 impl CameraThread {
-    pub fn new(tx_bgr: SyncSender<Mat>, tx_rgb: SyncSender<Mat>) -> Self {
-        CameraThread { tx_bgr, tx_rgb }
+    pub fn new(
+        tx_bgr: MediaSender<Mat>,
+        tx_rgb: SyncSender<Mat>,
+        rx_params: Receiver<VideoParams>,
+    ) -> Self {
+        CameraThread {
+            tx_bgr,
+            tx_rgb: Some(tx_rgb),
+            rx_params,
+            enabled: Arc::new(AtomicBool::new(true)),
+            stop: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Captures only for the preview: frames are not converted for the
+    /// encoder.
+    pub fn preview_only(tx_bgr: MediaSender<Mat>, rx_params: Receiver<VideoParams>) -> Self {
+        CameraThread {
+            tx_bgr,
+            tx_rgb: None,
+            rx_params,
+            enabled: Arc::new(AtomicBool::new(true)),
+            stop: Arc::new(AtomicBool::new(false)),
+        }
     }
 
-    pub fn run(&mut self, camera: &mut Camera) -> Result<(), WorkerError> {
-        loop {
+    /// Flag that pauses the capture while it is `false`: no frames are
+    /// read, so nothing is encoded or sent.
+    pub fn with_enabled(mut self, enabled: Arc<AtomicBool>) -> Self {
+        self.enabled = enabled;
+        self
+    }
+
+    /// Flag that ends `run` once it is `true`. It is checked before every
+    /// frame, so a paused camera or one that only returns empty frames
+    /// still stops.
+    pub fn with_stop(mut self, stop: Arc<AtomicBool>) -> Self {
+        self.stop = stop;
+        self
+    }
+
+    pub fn run(&mut self, camera: &mut dyn FrameSource) -> Result<(), WorkerError> {
+        while !self.stop.load(Ordering::Relaxed) {
+            // Solo importa el último cambio pedido
+            if let Some(params) = self.rx_params.try_iter().last() {
+                camera.apply_params(
+                    params.width as f64,
+                    params.height as f64,
+                    params.fps as f64,
+                );
+            }
+            if !self.enabled.load(Ordering::Relaxed) {
+                thread::sleep(PAUSED_POLL);
+                continue;
+            }
             let frame_bgr = match camera.capture_frame() {
                 Ok(f) => f,
                 Err(CameraError::FrameEmpty) => {
@@ -23,14 +86,51 @@ impl CameraThread {
                 }
                 Err(err) => return Err(WorkerError::CaptureFrameError(err)),
             };
-            let frame_rgb =
-                Camera::transform_frame_rgb(&frame_bgr).map_err(WorkerError::ConvertRgbFrame)?;
-            self.tx_rgb
-                .send(frame_rgb)
-                .map_err(|_| WorkerError::SendError)?;
+            if let Some(tx_rgb) = &self.tx_rgb {
+                let frame_rgb = Camera::transform_frame_rgb(&frame_bgr)
+                    .map_err(WorkerError::ConvertRgbFrame)?;
+                tx_rgb.send(frame_rgb).map_err(|_| WorkerError::SendError)?;
+            }
             self.tx_bgr
                 .send(frame_bgr)
                 .map_err(|_| WorkerError::SendError)?;
         }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::worker_thread::media_channel::media_channel;
+    use std::sync::mpsc;
+
+    /// Una cámara que nunca entrega un frame.
+    struct EmptySource;
+
+    impl FrameSource for EmptySource {
+        fn apply_params(&mut self, _width: f64, _height: f64, _fps: f64) {}
+
+        fn capture_frame(&mut self) -> Result<Mat, CameraError> {
+            thread::sleep(Duration::from_millis(1));
+            Err(CameraError::FrameEmpty)
+        }
+    }
+
+    #[test]
+    fn test_stop_ends_a_camera_that_only_returns_empty_frames() {
+        let (tx_bgr, _rx_bgr) = media_channel::<Mat>(1);
+        let (_tx_params, rx_params) = mpsc::channel::<VideoParams>();
+        let stop = Arc::new(AtomicBool::new(false));
+        let mut camera_thread =
+            CameraThread::preview_only(tx_bgr, rx_params).with_stop(Arc::clone(&stop));
+        let (tx_done, rx_done) = mpsc::channel();
+        thread::spawn(move || {
+            let _ = tx_done.send(camera_thread.run(&mut EmptySource).is_ok());
+        });
+
+        thread::sleep(Duration::from_millis(20));
+        stop.store(true, Ordering::Relaxed);
+        assert_eq!(rx_done.recv_timeout(Duration::from_secs(1)), Ok(true));
     }
 }
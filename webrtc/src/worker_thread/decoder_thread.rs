@@ -1,15 +1,16 @@
 use crate::codec::h264::decoder::H264Decoder;
 use crate::worker_thread::error::worker_error::WorkerError;
+use crate::worker_thread::media_channel::MediaSender;
 use opencv::prelude::Mat;
-use std::sync::mpsc::{Receiver, SyncSender};
+use std::sync::mpsc::Receiver;
 
 pub struct DecodeThread {
     rx_encoded: Receiver<Vec<u8>>,
-    tx_frame: SyncSender<Mat>,
+    tx_frame: MediaSender<Mat>,
     decoder: H264Decoder,
 }
 impl DecodeThread {
-    pub fn new(rx_encoded: Receiver<Vec<u8>>, tx_frame: SyncSender<Mat>) -> Self {
+    pub fn new(rx_encoded: Receiver<Vec<u8>>, tx_frame: MediaSender<Mat>) -> Self {
         let decoder = H264Decoder::new().unwrap_or_else(|err| {
             panic!("No se pudo iniciar decodificador H264: {}", err);
         });
@@ -24,7 +25,7 @@ impl DecodeThread {
             let encoded_bytes = match self.rx_encoded.recv() {
                 Ok(data) => data,
                 Err(_) => {
-                    eprintln!("DecodeThread Close Channel");
+                    log_debug!("DecodeThread Close Channel");
                     break;
                 }
             };
@@ -38,7 +39,7 @@ impl DecodeThread {
                             .map_err(|_| WorkerError::SendError)?;
                     }
                     Err(err) => {
-                        eprintln!("DecodeThread: error to convert to RGB: {:?}", err);
+                        log_warn!("DecodeThread: error to convert to RGB: {:?}", err);
                         continue;
                     }
                 }
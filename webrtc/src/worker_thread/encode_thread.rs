@@ -1,25 +1,36 @@
 use crate::codec::h264::encoder::H264Encoder;
 use crate::worker_thread::error::worker_error::WorkerError;
+use crate::worker_thread::worker_media::VideoParams;
 use opencv::prelude::Mat;
 use std::sync::mpsc::{Receiver, SyncSender};
 
 pub struct EncoderThread {
     rx_rgb: Receiver<Mat>,
     tx_encoded: SyncSender<Vec<u8>>,
+    rx_params: Receiver<VideoParams>,
     encoder: H264Encoder,
 }
 impl EncoderThread {
     pub fn new(
         rx_rgb: Receiver<Mat>,
         tx_encoded: SyncSender<Vec<u8>>,
+        rx_params: Receiver<VideoParams>,
+        params: VideoParams,
     ) -> Result<Self, WorkerError> {
-        let encoder = H264Encoder::new().map_err(|_| WorkerError::SendError)?;
+        let encoder = Self::build_encoder(&params)?;
         Ok(Self {
             rx_rgb,
             tx_encoded,
+            rx_params,
             encoder,
         })
     }
+
+    fn build_encoder(params: &VideoParams) -> Result<H264Encoder, WorkerError> {
+        H264Encoder::with_config(params.bitrate_bps(), params.fps.max(1) as f32)
+            .map_err(|_| WorkerError::SendError)
+    }
+
     pub fn run(&mut self) -> Result<(), WorkerError> {
         loop {
             let frame = match self.rx_rgb.recv() {
@@ -28,6 +39,11 @@ impl EncoderThread {
                     break;
                 }
             };
+            // A new encoder starts with SPS/PPS + IDR, so the remote decoder
+            // picks up the new resolution on the next frame.
+            if let Some(params) = self.rx_params.try_iter().last() {
+                self.encoder = Self::build_encoder(&params)?;
+            }
             let yuv = H264Encoder::rgb_to_yuv(&frame).map_err(WorkerError::ConvertToYuvError)?;
             let bitstream = self
                 .encoder
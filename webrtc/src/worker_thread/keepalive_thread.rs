@@ -0,0 +1,37 @@
+use crate::rtc::socket::peer_socket::{PeerSocket, KEEPALIVE_INTERVAL};
+use crate::worker_thread::error::worker_error::WorkerError;
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// How often the sockets are checked for idleness.
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Sends a STUN keepalive on the media sockets whenever nothing went out on
+/// them for `KEEPALIVE_INTERVAL`, so the NAT binding outlives a paused video.
+pub struct KeepaliveThread {
+    rx_stop: Receiver<()>,
+    interval: Duration,
+}
+
+impl KeepaliveThread {
+    /// The thread ends when the sender of `rx_stop` is dropped.
+    pub fn new(rx_stop: Receiver<()>) -> Self {
+        Self {
+            rx_stop,
+            interval: KEEPALIVE_INTERVAL,
+        }
+    }
+
+    pub fn run(&mut self, sockets: Vec<Arc<Mutex<PeerSocket>>>) -> Result<(), WorkerError> {
+        while let Err(RecvTimeoutError::Timeout) = self.rx_stop.recv_timeout(CHECK_INTERVAL) {
+            for socket in &sockets {
+                let socket = socket.lock().map_err(|_| WorkerError::SendError)?;
+                socket
+                    .send_keepalive_if_idle(self.interval)
+                    .map_err(|_| WorkerError::SendError)?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,271 @@
+//! Bounded channel between media threads that never blocks the producer.
+//! When it is full the oldest item is dropped, so a slow consumer catches up
+//! with the newest frames instead of working through a backlog, and every
+//! drop is counted.
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{RecvError, RecvTimeoutError, SendError, TryRecvError};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+
+struct Shared<T> {
+    queue: Mutex<VecDeque<T>>,
+    available: Condvar,
+    capacity: usize,
+    senders: AtomicUsize,
+    receiver_alive: AtomicBool,
+    dropped: DropCounter,
+}
+
+impl<T> Shared<T> {
+    fn queue(&self) -> MutexGuard<'_, VecDeque<T>> {
+        // Push y pop no dejan la cola a medias aunque un hilo haga panic
+        self.queue.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn disconnected(&self) -> bool {
+        self.senders.load(Ordering::Acquire) == 0
+    }
+}
+
+/// Creates a channel holding at most `capacity` items (at least one).
+pub fn media_channel<T>(capacity: usize) -> (MediaSender<T>, MediaReceiver<T>) {
+    let capacity = capacity.max(1);
+    let shared = Arc::new(Shared {
+        queue: Mutex::new(VecDeque::with_capacity(capacity)),
+        available: Condvar::new(),
+        capacity,
+        senders: AtomicUsize::new(1),
+        receiver_alive: AtomicBool::new(true),
+        dropped: DropCounter::default(),
+    });
+    (
+        MediaSender {
+            shared: Arc::clone(&shared),
+        },
+        MediaReceiver { shared },
+    )
+}
+
+/// Items a channel dropped to make room, readable after the channel ends
+/// moved to their threads.
+#[derive(Debug, Clone, Default)]
+pub struct DropCounter(Arc<AtomicU64>);
+
+impl DropCounter {
+    pub fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    fn increment(&self) {
+        self.0.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+pub struct MediaSender<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> MediaSender<T> {
+    /// Queues `item`, dropping the oldest one if the channel is full. Fails
+    /// only once the receiver is gone.
+    pub fn send(&self, item: T) -> Result<(), SendError<T>> {
+        if !self.shared.receiver_alive.load(Ordering::Acquire) {
+            return Err(SendError(item));
+        }
+        let mut queue = self.shared.queue();
+        if queue.len() >= self.shared.capacity {
+            queue.pop_front();
+            self.shared.dropped.increment();
+        }
+        queue.push_back(item);
+        drop(queue);
+        self.shared.available.notify_one();
+        Ok(())
+    }
+
+    pub fn dropped(&self) -> u64 {
+        self.shared.dropped.get()
+    }
+
+    pub fn drop_counter(&self) -> DropCounter {
+        self.shared.dropped.clone()
+    }
+}
+
+impl<T> Clone for MediaSender<T> {
+    fn clone(&self) -> Self {
+        self.shared.senders.fetch_add(1, Ordering::AcqRel);
+        Self {
+            shared: Arc::clone(&self.shared),
+        }
+    }
+}
+
+impl<T> Drop for MediaSender<T> {
+    fn drop(&mut self) {
+        if self.shared.senders.fetch_sub(1, Ordering::AcqRel) == 1 {
+            // Con la cola tomada, para no despertar antes de que el receptor espere
+            let _queue = self.shared.queue();
+            self.shared.available.notify_all();
+        }
+    }
+}
+
+pub struct MediaReceiver<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> MediaReceiver<T> {
+    /// Waits for the next item. Fails once every sender is gone and the
+    /// queue is empty.
+    pub fn recv(&self) -> Result<T, RecvError> {
+        let mut queue = self.shared.queue();
+        loop {
+            if let Some(item) = queue.pop_front() {
+                return Ok(item);
+            }
+            if self.shared.disconnected() {
+                return Err(RecvError);
+            }
+            queue = self
+                .shared
+                .available
+                .wait(queue)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+    }
+
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<T, RecvTimeoutError> {
+        let deadline = Instant::now() + timeout;
+        let mut queue = self.shared.queue();
+        loop {
+            if let Some(item) = queue.pop_front() {
+                return Ok(item);
+            }
+            if self.shared.disconnected() {
+                return Err(RecvTimeoutError::Disconnected);
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(RecvTimeoutError::Timeout);
+            }
+            queue = self
+                .shared
+                .available
+                .wait_timeout(queue, deadline - now)
+                .unwrap_or_else(PoisonError::into_inner)
+                .0;
+        }
+    }
+
+    pub fn try_recv(&self) -> Result<T, TryRecvError> {
+        match self.shared.queue().pop_front() {
+            Some(item) => Ok(item),
+            None if self.shared.disconnected() => Err(TryRecvError::Disconnected),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+
+    /// Items already queued, without waiting.
+    pub fn try_iter(&self) -> impl Iterator<Item = T> + '_ {
+        std::iter::from_fn(move || self.try_recv().ok())
+    }
+
+    pub fn dropped(&self) -> u64 {
+        self.shared.dropped.get()
+    }
+
+    pub fn drop_counter(&self) -> DropCounter {
+        self.shared.dropped.clone()
+    }
+}
+
+impl<T> Drop for MediaReceiver<T> {
+    fn drop(&mut self) {
+        self.shared.receiver_alive.store(false, Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn test_full_channel_drops_the_oldest_item() {
+        let (tx, rx) = media_channel(2);
+        for i in 1..=5 {
+            tx.send(i).unwrap();
+        }
+        assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec![4, 5]);
+        assert_eq!(tx.dropped(), 3);
+        assert_eq!(rx.drop_counter().get(), 3);
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+    }
+
+    #[test]
+    fn test_disconnection_on_both_ends() {
+        let (tx, rx) = media_channel(4);
+        let tx2 = tx.clone();
+        tx.send(1).unwrap();
+        drop(tx);
+        tx2.send(2).unwrap();
+        drop(tx2);
+        // Lo que quedó en la cola se entrega antes de avisar el corte
+        assert_eq!(rx.recv(), Ok(1));
+        assert_eq!(rx.recv_timeout(Duration::from_millis(10)), Ok(2));
+        assert_eq!(rx.recv(), Err(RecvError));
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Disconnected));
+
+        let (tx, rx) = media_channel(1);
+        drop(rx);
+        assert_eq!(tx.send(7), Err(SendError(7)));
+    }
+
+    #[test]
+    fn test_recv_waits_for_a_sender_thread() {
+        let (tx, rx) = media_channel(1);
+        assert_eq!(
+            rx.recv_timeout(Duration::from_millis(10)),
+            Err(RecvTimeoutError::Timeout)
+        );
+        let producer = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            tx.send("frame").unwrap();
+        });
+        assert_eq!(rx.recv(), Ok("frame"));
+        producer.join().unwrap();
+        assert_eq!(rx.recv(), Err(RecvError));
+    }
+
+    #[test]
+    fn test_drop_counter_accounts_for_every_item_under_concurrent_producers() {
+        const PRODUCERS: usize = 4;
+        const ITEMS: usize = 5_000;
+        let (tx, rx) = media_channel(8);
+        let counter = tx.drop_counter();
+        let producers: Vec<_> = (0..PRODUCERS)
+            .map(|_| {
+                let tx = tx.clone();
+                thread::spawn(move || {
+                    for i in 0..ITEMS {
+                        tx.send(i).unwrap();
+                    }
+                })
+            })
+            .collect();
+        drop(tx);
+
+        let mut received = 0;
+        while rx.recv().is_ok() {
+            received += 1;
+        }
+        for producer in producers {
+            producer.join().unwrap();
+        }
+        assert_eq!(received + counter.get() as usize, PRODUCERS * ITEMS);
+        assert!(received >= 8);
+    }
+}
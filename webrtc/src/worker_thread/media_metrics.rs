@@ -1,10 +1,18 @@
 use crate::protocols::rtcp::receiver_report::ReceiverReport;
 use crate::protocols::rtcp::report_block::ReportBlock;
 use crate::protocols::rtcp::sender_report::SenderReport;
+use crate::protocols::rtp::header_extension::abs_send_time_delta;
 use crate::protocols::rtp::rtp_packet::RtpPacket;
+use crate::worker_thread::bandwidth_cap;
+use std::fmt;
 use std::time::{Duration, Instant, SystemTime};
 
 const VIDEO_CLOCK_RATE: f64 = 90_000.0;
+/// Scores from here up are good, from `FAIR_SCORE` up fair, the rest poor.
+const GOOD_SCORE: f32 = 4.0;
+const FAIR_SCORE: f32 = 3.0;
+/// Round trips longer than this come from a stale or bogus report.
+const MAX_RTT_MS: f32 = 10_000.0;
 
 #[derive(Clone, Copy, Debug, Default)]
 pub struct CallMetricsSnapshot {
@@ -15,12 +23,138 @@ pub struct CallMetricsSnapshot {
     pub fraction_lost: u8,
     pub cumulative_lost: u32,
     pub since_last_ms: Option<u32>,
+    /// Smoothed inter-packet delay variation from abs-send-time. Positive
+    /// values mean packets arrive increasingly late (a queue is building).
+    pub delay_variation_ms: f32,
+    /// RTP packets dropped because their SSRC was not declared by the remote.
+    pub unknown_ssrc_packets: u64,
+    /// Items the video pipeline dropped because the next stage fell behind.
+    pub drops: VideoDrops,
+    /// Call quality from 1 (bad) to 5 (excellent), refreshed every second.
+    /// 0 until the first second of media has been received.
+    pub quality_score: f32,
+    /// What the last score was computed from.
+    pub quality_factors: QualityFactors,
+    /// The total send cap, while it is what holds the encoder target down.
+    pub capped_at_kbps: Option<u64>,
+}
+
+/// What the quality score is computed from.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct QualityFactors {
+    /// Packets lost over the last second, in percent.
+    pub loss_pct: f32,
+    pub jitter_ms: f32,
+    /// From RTCP reports about our stream; `None` until one arrives.
+    pub rtt_ms: Option<f32>,
+    /// Bitrate we send over the encoder target; `None` while nothing goes
+    /// out (camera paused) or without a target.
+    pub bitrate_ratio: Option<f32>,
+}
+
+impl fmt::Display for QualityFactors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "loss {:.1}%, jitter {:.0} ms",
+            self.loss_pct, self.jitter_ms
+        )?;
+        if let Some(rtt) = self.rtt_ms {
+            write!(f, ", rtt {:.0} ms", rtt)?;
+        }
+        if let Some(ratio) = self.bitrate_ratio {
+            write!(f, ", bitrate {:.0}% of target", ratio * 100.0)?;
+        }
+        Ok(())
+    }
+}
+
+/// MOS-like score from 1 to 5: each factor takes off up to a cap, so one
+/// bad factor alone cannot hide the others.
+pub fn quality_score(factors: &QualityFactors) -> f32 {
+    // 1% de pérdida resta 0.2, hasta 3
+    let loss = (factors.loss_pct * 0.2).min(3.0);
+    // Lo que pase de 10 ms de jitter, 0.02 por ms hasta 1.5
+    let jitter = ((factors.jitter_ms - 10.0).max(0.0) * 0.02).min(1.5);
+    // Lo que pase de 150 ms de RTT, 0.004 por ms hasta 1.5
+    let rtt = factors
+        .rtt_ms
+        .map_or(0.0, |rtt| ((rtt - 150.0).max(0.0) * 0.004).min(1.5));
+    // Mandar menos del 80% del objetivo resta hasta 1
+    let bitrate = factors
+        .bitrate_ratio
+        .map_or(0.0, |ratio| ((0.8 - ratio).max(0.0) * 1.5).min(1.0));
+    (5.0 - loss - jitter - rtt - bitrate).clamp(1.0, 5.0)
+}
+
+/// Quality score in three bands, for the indicator and the logs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QualityLevel {
+    Good,
+    Fair,
+    Poor,
+}
+
+impl QualityLevel {
+    /// `None` for a score not measured yet (0).
+    pub fn from_score(score: f32) -> Option<Self> {
+        if score < 1.0 {
+            None
+        } else if score >= GOOD_SCORE {
+            Some(QualityLevel::Good)
+        } else if score >= FAIR_SCORE {
+            Some(QualityLevel::Fair)
+        } else {
+            Some(QualityLevel::Poor)
+        }
+    }
+}
+
+impl fmt::Display for QualityLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            QualityLevel::Good => "good",
+            QualityLevel::Fair => "fair",
+            QualityLevel::Poor => "poor",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Items dropped by each full channel of the video pipeline.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct VideoDrops {
+    /// Packets from the socket waiting for the RTP receiver.
+    pub incoming_rtp: u64,
+    /// Decoded frames the UI did not pick up in time.
+    pub decoded_frames: u64,
+    /// Frames of the local preview the UI did not pick up in time.
+    pub preview_frames: u64,
+}
+
+impl VideoDrops {
+    pub fn total(&self) -> u64 {
+        self.incoming_rtp + self.decoded_frames + self.preview_frames
+    }
 }
 
 pub struct MediaMetrics {
     ssrc: u32,
     sender: SenderMetrics,
     receiver: ReceiverMetrics,
+    quality: QualityState,
+}
+
+/// Counters at the last refresh of the score and what it gave.
+#[derive(Default)]
+struct QualityState {
+    received: u32,
+    lost: u32,
+    target_bitrate_kbps: Option<f32>,
+    bandwidth_cap_kbps: Option<u64>,
+    rtt_ms: Option<f32>,
+    factors: QualityFactors,
+    score: f32,
 }
 
 impl MediaMetrics {
@@ -29,6 +163,7 @@ impl MediaMetrics {
             ssrc,
             sender: SenderMetrics::default(),
             receiver: ReceiverMetrics::default(),
+            quality: QualityState::default(),
         }
     }
 
@@ -36,6 +171,11 @@ impl MediaMetrics {
         self.ssrc
     }
 
+    /// Our stream moved to a new SSRC; reports go out under it from now on.
+    pub fn set_ssrc(&mut self, ssrc: u32) {
+        self.ssrc = ssrc;
+    }
+
     pub fn update_sender(&mut self, payload_len: usize, rtp_timestamp: u32) {
         self.sender.packet_count = self.sender.packet_count.wrapping_add(1);
         self.sender.octet_count = self.sender.octet_count.wrapping_add(payload_len as u32);
@@ -55,12 +195,24 @@ impl MediaMetrics {
         }
     }
 
+    /// Pins the remote SSRC to the one declared in the remote SDP. Without
+    /// it, the first received packet decides.
+    pub fn expect_remote_ssrc(&mut self, ssrc: u32) {
+        self.receiver.remote_ssrc = Some(ssrc);
+    }
+
+    /// Packets from any SSRC other than the tracked one are ignored, so a
+    /// stray stream cannot corrupt the sequence and loss counters.
     pub fn update_receiver_on_rtp(&mut self, packet: &RtpPacket, arrival: Instant) {
         let seq = packet.get_sequence_number();
         let timestamp = packet.get_timestamp();
         let ssrc = packet.get_ssrc();
-        if self.receiver.remote_ssrc.is_none() {
-            self.receiver.remote_ssrc = Some(ssrc);
+        match self.receiver.remote_ssrc {
+            Some(expected) if expected != ssrc => return,
+            Some(_) => {}
+            None => self.receiver.remote_ssrc = Some(ssrc),
+        }
+        if self.receiver.base_time.is_none() {
             self.receiver.base_time = Some(arrival);
         }
 
@@ -99,10 +251,90 @@ impl MediaMetrics {
         self.receiver.last_rtp_timestamp = Some(timestamp);
     }
 
+    /// Compares the sender clock (abs-send-time) with the arrival time of
+    /// consecutive packets: d = (arrival_i - arrival_j) - (send_i - send_j).
+    pub fn update_receiver_on_send_time(&mut self, send_time: u32, arrival: Instant) {
+        if let Some((prev_send, prev_arrival)) = self.receiver.last_send_time {
+            let arrival_delta = arrival.duration_since(prev_arrival).as_secs_f64();
+            let send_delta = abs_send_time_delta(prev_send, send_time);
+            let d = arrival_delta - send_delta;
+            self.receiver.delay_variation += (d - self.receiver.delay_variation) / 16.0;
+        }
+        self.receiver.last_send_time = Some((send_time, arrival));
+    }
+
     pub fn record_remote_sr(&mut self, sr: &SenderReport, arrival: Instant) {
         self.receiver.last_sr = Some((sr.ntp_msw, sr.ntp_lsw, arrival));
     }
 
+    /// Round trip from a report block about our stream (RFC 3550 6.4.1):
+    /// arrival minus the LSR and DLSR it carries. Blocks about other
+    /// streams, or from before our first SR, are ignored.
+    pub fn record_remote_report(&mut self, block: &ReportBlock, now: SystemTime) {
+        if block.ssrc != self.ssrc || block.last_sr == 0 {
+            return;
+        }
+        let (msw, lsw) = system_time_to_ntp(now);
+        let arrival = ((msw & 0xFFFF) << 16) | (lsw >> 16);
+        let rtt = arrival
+            .wrapping_sub(block.last_sr)
+            .wrapping_sub(block.delay_since_last_sr);
+        let rtt_ms = rtt as f32 * 1000.0 / 65_536.0;
+        if rtt_ms < MAX_RTT_MS {
+            self.quality.rtt_ms = Some(rtt_ms);
+        }
+    }
+
+    /// Bitrate the encoder aims at, to judge the one actually sent.
+    pub fn set_target_bitrate(&mut self, kbps: u64) {
+        self.quality.target_bitrate_kbps = Some(kbps as f32);
+    }
+
+    /// Total send cap for audio and video, `None` without one.
+    pub fn set_bandwidth_cap(&mut self, total_kbps: Option<u64>) {
+        self.quality.bandwidth_cap_kbps = total_kbps;
+    }
+
+    /// The cap binds when the encoder aims at the cap's video share: without
+    /// it, the target would be higher.
+    fn binding_cap(&self) -> Option<u64> {
+        let cap = self.quality.bandwidth_cap_kbps?;
+        let target = self.quality.target_bitrate_kbps?;
+        (target >= bandwidth_cap::video_encoder_kbps(cap) as f32).then_some(cap)
+    }
+
+    /// Recomputes the quality score from the last second. A second without
+    /// packets (the peer paused its camera) keeps the previous score.
+    pub fn refresh_quality(&mut self) {
+        let received = self
+            .receiver
+            .received_packets
+            .wrapping_sub(self.quality.received);
+        let lost = self.receiver.lost_packets.wrapping_sub(self.quality.lost);
+        self.quality.received = self.receiver.received_packets;
+        self.quality.lost = self.receiver.lost_packets;
+        if received == 0 {
+            return;
+        }
+
+        let sent_kbps = self.sender.bitrate_kbps;
+        self.quality.factors = QualityFactors {
+            loss_pct: lost as f32 * 100.0 / (received + lost) as f32,
+            jitter_ms: self.jitter_ms(),
+            rtt_ms: self.quality.rtt_ms,
+            bitrate_ratio: self
+                .quality
+                .target_bitrate_kbps
+                .filter(|target| *target > 0.0 && sent_kbps > 0.0)
+                .map(|target| sent_kbps / target),
+        };
+        self.quality.score = quality_score(&self.quality.factors);
+    }
+
+    fn jitter_ms(&self) -> f32 {
+        ((self.receiver.jitter / VIDEO_CLOCK_RATE) * 1000.0) as f32
+    }
+
     pub fn build_sender_report(&mut self, ntp: (u32, u32)) -> Option<SenderReport> {
         if self.sender.packet_count == 0 {
             return None;
@@ -167,11 +399,18 @@ impl MediaMetrics {
         CallMetricsSnapshot {
             bitrate_kbps: self.sender.bitrate_kbps,
             packet_loss_pct: loss_pct,
-            jitter_ms: ((self.receiver.jitter / VIDEO_CLOCK_RATE) * 1000.0) as f32,
+            jitter_ms: self.jitter_ms(),
             highest_seq: self.receiver.highest_ext_seq,
             fraction_lost,
             cumulative_lost: cumulative,
             since_last_ms,
+            delay_variation_ms: (self.receiver.delay_variation * 1000.0) as f32,
+            unknown_ssrc_packets: 0,
+            // Los canales son del worker, que completa esto
+            drops: VideoDrops::default(),
+            quality_score: self.quality.score,
+            quality_factors: self.quality.factors,
+            capped_at_kbps: self.binding_cap(),
         }
     }
 }
@@ -224,6 +463,8 @@ struct ReceiverMetrics {
     last_rtp_timestamp: Option<u32>,
     base_time: Option<Instant>,
     last_sr: Option<(u32, u32, Instant)>,
+    last_send_time: Option<(u32, Instant)>,
+    delay_variation: f64,
 }
 
 impl Default for ReceiverMetrics {
@@ -241,6 +482,8 @@ impl Default for ReceiverMetrics {
             last_rtp_timestamp: None,
             base_time: None,
             last_sr: None,
+            last_send_time: None,
+            delay_variation: 0.0,
         }
     }
 }
@@ -254,3 +497,143 @@ pub fn system_time_to_ntp(now: SystemTime) -> (u32, u32) {
     let fraction = ((duration.subsec_nanos() as u64) << 32) / 1_000_000_000u64;
     (seconds as u32, fraction as u32)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::h264::nalu_header::NaluHeader;
+    use crate::codec::h264::single_nal_unit_packet::SingleNalUnitPacket;
+    use crate::protocols::rtp::h264_video_type::H264VideoType;
+    use crate::protocols::rtp::payload_type::PayloadType;
+    use crate::protocols::rtp::rtp_header::RtpHeader;
+
+    fn packet(ssrc: u32, seq: u16) -> RtpPacket {
+        let single = SingleNalUnitPacket::new(NaluHeader::new(false, 0, 1), vec![0xAA]);
+        let payload = PayloadType::H264Video(H264VideoType::Single(single));
+        let header = RtpHeader::new(2, false, false, 0, false, 96, seq, 0, ssrc, vec![]);
+        RtpPacket::new(header, payload)
+    }
+
+    #[test]
+    fn test_packets_from_other_ssrcs_are_ignored() {
+        let mut metrics = MediaMetrics::new(1);
+        metrics.expect_remote_ssrc(0xABCD);
+        let now = Instant::now();
+
+        metrics.update_receiver_on_rtp(&packet(0x9999, 500), now);
+        metrics.update_receiver_on_rtp(&packet(0xABCD, 1), now);
+        metrics.update_receiver_on_rtp(&packet(0x9999, 900), now);
+        metrics.update_receiver_on_rtp(&packet(0xABCD, 2), now);
+
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.highest_seq, 2);
+        assert_eq!(snapshot.cumulative_lost, 0);
+    }
+
+    fn factors(
+        loss_pct: f32,
+        jitter_ms: f32,
+        rtt_ms: Option<f32>,
+        bitrate_ratio: Option<f32>,
+    ) -> QualityFactors {
+        QualityFactors {
+            loss_pct,
+            jitter_ms,
+            rtt_ms,
+            bitrate_ratio,
+        }
+    }
+
+    #[test]
+    fn test_quality_score_table() {
+        let cases = [
+            (factors(0.0, 0.0, None, None), 5.0),
+            (factors(0.0, 10.0, Some(150.0), Some(1.0)), 5.0),
+            (factors(2.0, 0.0, None, None), 4.6),
+            (factors(0.0, 40.0, None, None), 4.4),
+            (factors(0.0, 0.0, Some(300.0), None), 4.4),
+            (factors(0.0, 0.0, None, Some(0.5)), 4.55),
+            (factors(0.0, 0.0, None, Some(1.3)), 5.0),
+            (factors(5.0, 30.0, Some(200.0), Some(0.7)), 3.25),
+            (factors(10.0, 40.0, None, None), 2.4),
+            (factors(50.0, 0.0, None, None), 2.0),
+            (factors(20.0, 100.0, Some(1_000.0), Some(0.1)), 1.0),
+        ];
+        for (inputs, expected) in cases {
+            let score = quality_score(&inputs);
+            assert!((score - expected).abs() < 1e-4, "{inputs}: {score}");
+        }
+    }
+
+    #[test]
+    fn test_quality_levels() {
+        assert_eq!(QualityLevel::from_score(0.0), None);
+        assert_eq!(QualityLevel::from_score(4.6), Some(QualityLevel::Good));
+        assert_eq!(QualityLevel::from_score(4.0), Some(QualityLevel::Good));
+        assert_eq!(QualityLevel::from_score(3.4), Some(QualityLevel::Fair));
+        assert_eq!(QualityLevel::from_score(2.4), Some(QualityLevel::Poor));
+    }
+
+    #[test]
+    fn test_quality_follows_the_last_second() {
+        let mut metrics = MediaMetrics::new(1);
+        let now = Instant::now();
+        assert_eq!(metrics.snapshot().quality_score, 0.0);
+
+        // 4 de 5 paquetes: 20% de pérdida
+        for seq in [1, 2, 3, 5] {
+            metrics.update_receiver_on_rtp(&packet(0xABCD, seq), now);
+        }
+        metrics.refresh_quality();
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.quality_factors.loss_pct, 20.0);
+        assert_eq!(snapshot.quality_score, 2.0);
+
+        // Un segundo sin paquetes no cambia nada; uno limpio, sí
+        metrics.refresh_quality();
+        assert_eq!(metrics.snapshot().quality_score, 2.0);
+        for seq in 6..=10 {
+            metrics.update_receiver_on_rtp(&packet(0xABCD, seq), now);
+        }
+        metrics.refresh_quality();
+        assert_eq!(metrics.snapshot().quality_score, 5.0);
+    }
+
+    #[test]
+    fn test_rtt_from_a_report_about_our_stream() {
+        let mut metrics = MediaMetrics::new(7);
+        let now = SystemTime::now();
+        // Nuestro SR salió hace 150 ms y el peer lo retuvo 50 ms
+        let (msw, lsw) = system_time_to_ntp(now - Duration::from_millis(150));
+        let block = |ssrc| ReportBlock {
+            ssrc,
+            fraction_lost: 0,
+            cumulative_lost: 0,
+            highest_seq: 0,
+            jitter: 0,
+            last_sr: ((msw & 0xFFFF) << 16) | (lsw >> 16),
+            delay_since_last_sr: 65_536 / 20,
+        };
+        metrics.record_remote_report(&block(99), now);
+        assert_eq!(metrics.quality.rtt_ms, None);
+        metrics.record_remote_report(&block(7), now);
+        let rtt = metrics.quality.rtt_ms.unwrap();
+        assert!((rtt - 100.0).abs() < 1.0, "{rtt}");
+    }
+
+    #[test]
+    fn test_cap_is_reported_only_while_it_binds() {
+        let mut metrics = MediaMetrics::new(1);
+        metrics.set_bandwidth_cap(Some(500));
+        metrics.set_target_bitrate(bandwidth_cap::video_encoder_kbps(500));
+        assert_eq!(metrics.snapshot().capped_at_kbps, Some(500));
+
+        // 640x360@15 asks for less than the cap leaves
+        metrics.set_target_bitrate(345);
+        assert_eq!(metrics.snapshot().capped_at_kbps, None);
+
+        metrics.set_bandwidth_cap(None);
+        metrics.set_target_bitrate(2_000);
+        assert_eq!(metrics.snapshot().capped_at_kbps, None);
+    }
+}
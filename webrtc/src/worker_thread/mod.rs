@@ -1,8 +1,11 @@
+pub mod bandwidth_cap;
 pub mod camera_thread;
 mod decoder_thread;
 mod encode_thread;
 pub mod error;
+mod keepalive_thread;
 pub mod local_preview_thread;
+pub mod media_channel;
 pub mod media_metrics;
 mod rtc_rtp_sender_thread;
 mod rtcp_reporter_thread;
@@ -1,37 +1,49 @@
 use crate::rtc::rtc_rtp::rtc_rtp_sender::RtcRtpSender;
 use crate::rtc::socket::peer_socket::PeerSocket;
 use crate::worker_thread::error::worker_error::WorkerError;
+use crate::worker_thread::worker_media::VideoParams;
 use std::sync::mpsc::Receiver;
 use std::sync::{Arc, Mutex};
 
 pub struct RtpSenderThread {
     rx_encoded: Receiver<Vec<u8>>,
+    rx_params: Receiver<VideoParams>,
+    rx_ssrc: Receiver<u32>,
+    rx_pacing: Receiver<Option<u64>>,
     sender: RtcRtpSender,
 }
 impl RtpSenderThread {
-    pub fn new(rx_encoded: Receiver<Vec<u8>>, sender: RtcRtpSender) -> Self {
-        RtpSenderThread { rx_encoded, sender }
+    pub fn new(
+        rx_encoded: Receiver<Vec<u8>>,
+        rx_params: Receiver<VideoParams>,
+        rx_ssrc: Receiver<u32>,
+        rx_pacing: Receiver<Option<u64>>,
+        sender: RtcRtpSender,
+    ) -> Self {
+        RtpSenderThread {
+            rx_encoded,
+            rx_params,
+            rx_ssrc,
+            rx_pacing,
+            sender,
+        }
     }
 
     pub fn run(&mut self, peer_socket: Arc<Mutex<PeerSocket>>) -> Result<(), WorkerError> {
         let mut consecutive_errors = 0;
         
         while let Ok(encoded_bytes) = self.rx_encoded.recv() {
-            let send_result = {
-                let mut socket = match peer_socket.lock() {
-                    Ok(s) => s,
-                    Err(_) => {
-                        // Lock poisoned, but keep trying
-                        consecutive_errors += 1;
-                        if consecutive_errors > 100 {
-                            eprintln!("RTP Sender: Too many consecutive errors, stopping");
-                            return Err(WorkerError::SendError);
-                        }
-                        continue;
-                    }
-                };
-                self.sender.send_video_payload(encoded_bytes, &mut socket)
-            };
+            if let Some(params) = self.rx_params.try_iter().last() {
+                self.sender.set_frame_rate(params.fps);
+            }
+            if let Some(ssrc) = self.rx_ssrc.try_iter().last() {
+                self.sender.set_ssrc(ssrc);
+            }
+            if let Some(rate_kbps) = self.rx_pacing.try_iter().last() {
+                self.sender.set_pacing_rate(rate_kbps);
+            }
+            // The sender locks the socket per packet, between pacing waits
+            let send_result = self.sender.send_video_payload(encoded_bytes, &peer_socket);
             
             match send_result {
                 Ok(_) => {
@@ -41,11 +53,11 @@ impl RtpSenderThread {
                     // Log but continue - network might recover
                     consecutive_errors += 1;
                     if consecutive_errors == 1 || consecutive_errors % 50 == 0 {
-                        eprintln!("RTP Sender: Send failed ({}), continuing... (errors: {})", e, consecutive_errors);
+                        log_warn!("RTP Sender: Send failed ({}), continuing... (errors: {})", e, consecutive_errors);
                     }
                     // Only give up after many consecutive failures
                     if consecutive_errors > 300 {
-                        eprintln!("RTP Sender: Too many errors, stopping");
+                        log_error!("RTP Sender: Too many errors, stopping");
                         return Err(WorkerError::SendError);
                     }
                 }
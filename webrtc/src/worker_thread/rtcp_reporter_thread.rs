@@ -30,6 +30,7 @@ impl RtcpReporterThread {
 
             let (sender_report, receiver_report) = {
                 let mut guard = self.metrics.lock().map_err(|_| WorkerError::SendError)?;
+                guard.refresh_quality();
                 (
                     guard.build_sender_report(now),
                     guard.build_receiver_report(),
@@ -1,37 +1,49 @@
-use crate::crypto::srtp::SrtpContext;
+use crate::crypto::srtp::{SharedSrtpContext, SrtpCache, SrtpContext};
 use crate::protocols::rtcp::rtcp_packet::RtcpPacket;
 use crate::protocols::rtcp::rtcp_payload::RtcpPayload;
+use crate::protocols::rtp::rtp_header::RtpHeader;
 use crate::protocols::rtp::rtp_packet::RtpPacket;
 use crate::rtc::jitter_buffer::j_buffer::JitterBuffer;
 use crate::worker_thread::error::worker_error::WorkerError;
+use crate::worker_thread::media_channel::MediaReceiver;
 use crate::worker_thread::media_metrics::MediaMetrics;
-use std::sync::mpsc::{Receiver, SyncSender};
+use std::sync::mpsc::SyncSender;
 use std::sync::{Arc, Mutex};
-use std::time::Instant;
+use std::time::{Instant, SystemTime};
 
 pub struct RtpReceiverThread {
-    rx_socket: Receiver<Vec<u8>>,
+    rx_socket: MediaReceiver<Vec<u8>>,
     tx_decoded: SyncSender<Vec<u8>>,
+    payload_type: u8,
     jitter: JitterBuffer,
     metrics: Arc<Mutex<MediaMetrics>>,
-    srtp: Option<SrtpContext>,
+    srtp: SrtpCache,
+    abs_send_time_id: Option<u8>,
 }
 
 impl RtpReceiverThread {
     pub fn new(
-        rx_socket: Receiver<Vec<u8>>,
+        rx_socket: MediaReceiver<Vec<u8>>,
         tx_decoded: SyncSender<Vec<u8>>,
+        payload_type: u8,
         metrics: Arc<Mutex<MediaMetrics>>,
-        srtp_context: Option<SrtpContext>,
+        srtp_context: SharedSrtpContext,
     ) -> Self {
         Self {
             rx_socket,
             tx_decoded,
+            payload_type,
             jitter: JitterBuffer::new(),
             metrics,
-            srtp: srtp_context,
+            srtp: srtp_context.cache(),
+            abs_send_time_id: None,
         }
     }
+    /// Reads abs-send-time under the negotiated id to track delay variation.
+    pub fn with_abs_send_time_id(mut self, id: Option<u8>) -> Self {
+        self.abs_send_time_id = id;
+        self
+    }
     pub fn run(&mut self) -> Result<(), WorkerError> {
         while let Ok(bytes) = self.rx_socket.recv() {
             if Self::is_rtcp(&bytes) {
@@ -39,7 +51,7 @@ impl RtpReceiverThread {
                 continue;
             }
 
-            let plain_bytes = if let Some(ref srtp) = self.srtp {
+            let plain_bytes = if let Some(srtp) = self.srtp.current() {
                 match Self::decrypt_rtp(&bytes, srtp) {
                     Some(p) => p,
                     None => continue,
@@ -49,7 +61,7 @@ impl RtpReceiverThread {
             };
 
             let arrival = Instant::now();
-            let rtp_packet = match RtpPacket::read_bytes(&plain_bytes) {
+            let rtp_packet = match RtpPacket::read_h264_bytes(&plain_bytes, self.payload_type) {
                 Ok(rtp_packet) => rtp_packet,
                 Err(_) => {
                     continue;
@@ -58,6 +70,12 @@ impl RtpReceiverThread {
 
             if let Ok(mut metrics) = self.metrics.lock() {
                 metrics.update_receiver_on_rtp(&rtp_packet, arrival);
+                if let Some(send_time) = self
+                    .abs_send_time_id
+                    .and_then(|id| rtp_packet.rtp_header.get_abs_send_time(id))
+                {
+                    metrics.update_receiver_on_send_time(send_time, arrival);
+                }
             }
 
             self.jitter.push(rtp_packet);
@@ -83,6 +101,16 @@ impl RtpReceiverThread {
                 RtcpPayload::SenderReport(sr) => {
                     if let Ok(mut metrics) = self.metrics.lock() {
                         metrics.record_remote_sr(&sr, arrival);
+                        for block in &sr.report_blocks {
+                            metrics.record_remote_report(block, SystemTime::now());
+                        }
+                    }
+                }
+                RtcpPayload::ReceiverReport(rr) => {
+                    if let Ok(mut metrics) = self.metrics.lock() {
+                        for block in &rr.report_blocks {
+                            metrics.record_remote_report(block, SystemTime::now());
+                        }
                     }
                 }
                 RtcpPayload::Bye(_) => {}
@@ -95,12 +123,15 @@ impl RtpReceiverThread {
         if bytes.len() <= 12 {
             return None;
         }
-        let seq = u16::from_be_bytes([bytes[2], bytes[3]]);
-        let ts = u32::from_be_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]);
-        let cipher = &bytes[12..];
-        let payload = srtp.unprotect(seq, ts, cipher)?;
-        let mut out = Vec::with_capacity(12 + payload.len());
-        out.extend_from_slice(&bytes[..12]);
+        let (header, header_size) = RtpHeader::read_bytes(bytes).ok()?;
+        if bytes.len() <= header_size {
+            return None;
+        }
+        let cipher = &bytes[header_size..];
+        let payload =
+            srtp.unprotect(header.get_sequence_number(), header.get_timestamp(), cipher)?;
+        let mut out = Vec::with_capacity(header_size + payload.len());
+        out.extend_from_slice(&bytes[..header_size]);
         out.extend_from_slice(&payload);
         Some(out)
     }
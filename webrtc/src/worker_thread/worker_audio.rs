@@ -2,18 +2,151 @@
 
 use crate::audio::audio_capture::{AudioCapture, AudioCaptureError};
 use crate::audio::audio_playback::{AudioPlayback, AudioPlaybackError};
-use crate::audio::opus_codec::{OpusDecoder, OpusEncoder, OpusError};
-use crate::crypto::srtp::SrtpContext;
-use crate::protocols::rtp::constants::rtp_const::RTP_OPUS_TYPE;
+use crate::audio::comfort_noise::ComfortNoise;
+use crate::audio::gain::OutputGain;
+use crate::audio::level::{AudioLevel, LevelMeter};
+use crate::audio::opus_codec::{is_dtx_frame, OpusDecoder, OpusEncoder, OpusError};
+use crate::audio::playout::{AudioFrame, PlayoutStats, PlayoutTarget};
+use crate::audio::vad::VoiceActivityDetector;
+use crate::crypto::srtp::SharedSrtpContext;
+use crate::protocols::rtp::header_extension::{
+    abs_send_time_add, abs_send_time_delta, abs_send_time_now, HeaderExtension, LatencyStamp,
+};
 use crate::protocols::rtp::rtp_header::RtpHeader;
 use crate::rtc::socket::peer_socket::PeerSocket;
+use crate::worker_thread::bandwidth_cap;
+use crate::worker_thread::media_channel::{media_channel, DropCounter, MediaSender};
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::mpsc::{self, SyncSender};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
 use std::sync::{Arc, Mutex};
 use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
 
-const AUDIO_SSRC: u32 = 2000;
 const OPUS_FRAME_SIZE: usize = 960; // 20ms at 48kHz
+const OPUS_FRAME_DURATION: Duration = Duration::from_millis(20);
+/// Longer gaps are not concealed: after that much silence, synthetic audio
+/// sounds worse than a clean cut.
+const MAX_CONCEALED_FRAMES: u16 = 5;
+/// Frames per channel between the audio threads: 40 ms at most.
+pub const DEFAULT_AUDIO_QUEUE_DEPTH: usize = 2;
+/// One latency probe every this many frames (1 s).
+const LATENCY_PROBE_INTERVAL: u32 = 50;
+/// Echoes claiming more than this are from a previous stream or bogus.
+const MAX_LATENCY_SECS: f64 = 10.0;
+
+/// Opus encoder settings and audio devices. The default keeps Opus' own
+/// bitrate, no FEC, the system's default devices and short queues.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AudioParams {
+    /// Target bitrate in bits per second, `None` lets Opus choose.
+    pub bitrate_bps: Option<u32>,
+    /// In-band forward error correction.
+    pub fec: bool,
+    /// Packet loss the encoder should prepare for, 0 to 100.
+    pub expected_loss_pct: u8,
+    /// Microphone by name, `None` for the default one.
+    pub input_device: Option<String>,
+    /// Speakers by name, `None` for the default ones.
+    pub output_device: Option<String>,
+    /// Depth of each channel of the audio pipeline, in 20 ms frames. Every
+    /// frame queued adds up to 20 ms of delay.
+    pub queue_depth: usize,
+    /// Discontinuous transmission: nothing is sent while the microphone is
+    /// silent.
+    pub dtx: bool,
+    /// Opus' own DTX: silent frames are reduced to a comfort noise update
+    /// every 400 ms.
+    pub opus_dtx: bool,
+    /// Reserve the playout keeps against jitter. It starts at the minimum,
+    /// grows after underruns and shrinks back while playback is smooth.
+    pub playout: PlayoutTarget,
+    /// Capture and send the microphone. Off for a recvonly or inactive
+    /// section: the peer is still played.
+    pub send: bool,
+}
+
+impl Default for AudioParams {
+    fn default() -> Self {
+        AudioParams {
+            bitrate_bps: None,
+            fec: false,
+            expected_loss_pct: 0,
+            input_device: None,
+            output_device: None,
+            queue_depth: DEFAULT_AUDIO_QUEUE_DEPTH,
+            dtx: false,
+            opus_dtx: false,
+            playout: PlayoutTarget::default(),
+            send: true,
+        }
+    }
+}
+
+impl AudioParams {
+    /// Lowers the Opus bitrate to the audio share of a total send cap, when
+    /// the configured one (or Opus' own choice) would not fit under it.
+    pub fn with_bandwidth_cap(mut self, total_kbps: Option<u64>) -> Self {
+        if let Some(total) = total_kbps {
+            let share = bandwidth_cap::audio_bps(total);
+            self.bitrate_bps = Some(self.bitrate_bps.map_or(share, |bps| bps.min(share)));
+        }
+        self
+    }
+}
+
+/// What the audio path measured so far.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct AudioMetricsSnapshot {
+    /// From our microphone to the peer's speakers, plus the network trip
+    /// back (negligible on a LAN). `None` until a probe comes back, or if
+    /// the peer does not echo them.
+    pub mouth_to_ear_ms: Option<f64>,
+    pub playout: PlayoutStats,
+    /// DTX is holding our packets back because the microphone is silent.
+    pub dtx_active: bool,
+    /// Recent level of the microphone (silence while muted).
+    pub input_level: AudioLevel,
+    /// Recent level of the peer's audio, before our volume and mute.
+    pub output_level: AudioLevel,
+    /// Items the audio pipeline dropped because the next stage fell behind.
+    pub drops: AudioDrops,
+}
+
+/// Items dropped by each full channel of the audio pipeline.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AudioDrops {
+    /// Microphone blocks the encoder did not pick up in time.
+    pub capture: u64,
+    /// Packets from the socket waiting for the decoder.
+    pub incoming_rtp: u64,
+    /// Decoded frames waiting for the speakers.
+    pub playback: u64,
+}
+
+impl AudioDrops {
+    pub fn total(&self) -> u64 {
+        self.capture + self.incoming_rtp + self.playback
+    }
+}
+
+/// Counters behind `AudioDrops`, kept after the channels move to their
+/// threads.
+struct AudioDropCounters {
+    capture: DropCounter,
+    incoming_rtp: DropCounter,
+    playback: DropCounter,
+}
+
+/// An encoded frame on its way to the RTP sender.
+struct EncodedFrame {
+    opus: Vec<u8>,
+    /// For the latency probes.
+    captured_at: u32,
+    /// Position in the capture, counting the frames DTX did not send.
+    index: u32,
+    /// Last frame before a DTX pause.
+    last_of_talkspurt: bool,
+}
 
 /// Error type for audio worker operations.
 #[derive(Debug)]
@@ -21,6 +154,8 @@ pub enum WorkerAudioError {
     Capture(String),
     Playback(String),
     Codec(String),
+    /// The sender thread is gone, e.g. after the call ended.
+    Stopped,
 }
 
 impl std::fmt::Display for WorkerAudioError {
@@ -29,6 +164,7 @@ impl std::fmt::Display for WorkerAudioError {
             Self::Capture(e) => write!(f, "Audio capture error: {}", e),
             Self::Playback(e) => write!(f, "Audio playback error: {}", e),
             Self::Codec(e) => write!(f, "Audio codec error: {}", e),
+            Self::Stopped => write!(f, "Audio worker stopped"),
         }
     }
 }
@@ -55,146 +191,277 @@ impl From<OpusError> for WorkerAudioError {
 pub struct WorkerAudio {
     capture: Option<AudioCapture>,
     playback: Option<AudioPlayback>, // Keep playback alive
-    tx_incoming: SyncSender<Vec<u8>>,
+    tx_incoming: MediaSender<Vec<u8>>,
+    drops: AudioDropCounters,
     running: Arc<AtomicBool>,
+    ssrc: u32,
+    // None while we only receive
+    tx_ssrc: Option<Sender<u32>>,
+    mouth_to_ear_ms: Arc<Mutex<Option<f64>>>,
+    dtx_active: Arc<AtomicBool>,
+    output: Arc<OutputGain>,
+    input_level: Arc<LevelMeter>,
+    output_level: Arc<LevelMeter>,
     #[allow(dead_code)]
     handles: Vec<JoinHandle<()>>,
 }
 
 impl WorkerAudio {
     /// Starts the audio worker with capture, encoding, transmission and playback.
+    /// Outgoing packets are stamped with the negotiated Opus `payload_type` and
+    /// the `ssrc` declared in our SDP, and encoded with `params`. With
+    /// `latency_id` a latency probe goes out every second under that
+    /// `a=extmap` id, and the peer's probes are echoed once played.
+    ///
+    /// With `params.dtx` silent frames are not sent. The RTP timestamp keeps
+    /// running through the pause while the sequence number does not, so the
+    /// peer sees no loss; the marker bit flags the last frame before the
+    /// pause and the first one after it. With `params.opus_dtx` the frames
+    /// Opus leaves empty are skipped the same way. While the peer sends
+    /// nothing, the decoder makes up the missing frames with concealment
+    /// (comfort noise after a DTX update), unless its last packet closed a
+    /// talkspurt.
+    ///
+    /// Without `params.send` the microphone is not opened and nothing goes
+    /// out; only the peer's audio is decoded and played.
     pub fn start(
         peer_socket: Arc<Mutex<PeerSocket>>,
-        srtp_context: Option<SrtpContext>,
+        payload_type: u8,
+        ssrc: u32,
+        srtp_context: SharedSrtpContext,
+        params: AudioParams,
+        latency_id: Option<u8>,
     ) -> Result<Self, WorkerAudioError> {
         let running = Arc::new(AtomicBool::new(true));
         let mut handles = Vec::new();
+        let depth = params.queue_depth.max(1);
+        let send = params.send;
+        let mouth_to_ear_ms = Arc::new(Mutex::new(None));
+        let dtx_active = Arc::new(AtomicBool::new(false));
+        let output = Arc::new(OutputGain::new());
+        let input_level = Arc::new(LevelMeter::new());
+        let output_level = Arc::new(LevelMeter::new());
 
         // Channels for audio pipeline
-        let (tx_pcm_capture, rx_pcm_capture) = mpsc::sync_channel::<Vec<i16>>(4);
-        let (tx_opus_encoded, rx_opus_encoded) = mpsc::sync_channel::<Vec<u8>>(4);
-        let (tx_incoming, rx_incoming) = mpsc::sync_channel::<Vec<u8>>(8);
-        let (tx_pcm_playback, rx_pcm_playback) = mpsc::sync_channel::<Vec<i16>>(4);
+        // Channels for audio pipeline. The PCM and incoming ones drop the
+        // oldest frame when full, so a stall never adds delay
+        let (tx_pcm_capture, rx_pcm_capture) = media_channel::<Vec<i16>>(depth);
+        let (tx_opus_encoded, rx_opus_encoded) = mpsc::sync_channel::<EncodedFrame>(depth);
+        let (tx_incoming, rx_incoming) = media_channel::<Vec<u8>>(depth);
+        let (tx_pcm_playback, rx_pcm_playback) = media_channel::<AudioFrame>(depth);
+        let drops = AudioDropCounters {
+            capture: tx_pcm_capture.drop_counter(),
+            incoming_rtp: tx_incoming.drop_counter(),
+            playback: tx_pcm_playback.drop_counter(),
+        };
+        let (tx_ssrc, rx_ssrc) = mpsc::channel::<u32>();
+        let (tx_played, rx_played) = mpsc::channel();
 
         // Start audio capture
-        let capture = AudioCapture::new(tx_pcm_capture)?;
+        let capture = if send {
+            Some(AudioCapture::with_device(
+                tx_pcm_capture,
+                params.input_device.as_deref(),
+            )?)
+        } else {
+            None
+        };
 
         // Start audio playback
-        let playback = AudioPlayback::new(rx_pcm_playback)?;
-
-        // Encoder thread: PCM -> Opus
-        let running_enc = Arc::clone(&running);
-        let encoder_handle = thread::spawn(move || {
-            let mut encoder = match OpusEncoder::new() {
-                Ok(e) => e,
-                Err(e) => {
-                    eprintln!("Failed to create Opus encoder: {}", e);
-                    return;
+        let playback = AudioPlayback::with_device(
+            rx_pcm_playback,
+            params.playout,
+            params.output_device.as_deref(),
+            // The echoes of the peer's probes go out with our packets
+            latency_id.filter(|_| send).map(|_| tx_played),
+        )?;
+
+        if send {
+            // Encoder thread: PCM -> Opus
+            let running_enc = Arc::clone(&running);
+            let dtx_for_encoder = Arc::clone(&dtx_active);
+            let level_for_encoder = Arc::clone(&input_level);
+            let encoder_handle = thread::spawn(move || {
+                let mut encoder = match OpusEncoder::new() {
+                    Ok(e) => e,
+                    Err(e) => {
+                        log_error!("Failed to create Opus encoder: {}", e);
+                        return;
+                    }
+                };
+                // A config error leaves the encoder on its defaults
+                if let Err(e) = encoder.set_bitrate(params.bitrate_bps) {
+                    log_warn!("Opus bitrate not applied: {}", e);
+                }
+                if let Err(e) = encoder.set_fec(params.fec, params.expected_loss_pct) {
+                    log_warn!("Opus FEC not applied: {}", e);
+                }
+                if let Err(e) = encoder.set_dtx(params.opus_dtx) {
+                    log_warn!("Opus DTX not applied: {}", e);
                 }
-            };
-
-            let mut buffer = Vec::with_capacity(OPUS_FRAME_SIZE * 2);
-
-            while running_enc.load(Ordering::Relaxed) {
-                match rx_pcm_capture.recv() {
-                    Ok(samples) => {
-                        buffer.extend(samples);
 
-                        // Process complete frames
-                        while buffer.len() >= OPUS_FRAME_SIZE {
-                            let frame: Vec<i16> = buffer.drain(..OPUS_FRAME_SIZE).collect();
-                            if let Ok(encoded) = encoder.encode(&frame) {
-                                // eprintln!("[AUDIO] Encoded {} bytes", encoded.len());
-                                let _ = tx_opus_encoded.try_send(encoded);
+                let mut buffer = Vec::with_capacity(OPUS_FRAME_SIZE * 2);
+                let mut vad = params.dtx.then(VoiceActivityDetector::default);
+                let mut index: u32 = 0;
+                let mut paused = false;
+
+                while running_enc.load(Ordering::Relaxed) {
+                    match rx_pcm_capture.recv() {
+                        Ok(samples) => {
+                            level_for_encoder.update(&samples);
+                            buffer.extend(samples);
+
+                            // Process complete frames
+                            while buffer.len() >= OPUS_FRAME_SIZE {
+                                let frame: Vec<i16> = buffer.drain(..OPUS_FRAME_SIZE).collect();
+                                let captured_at = abs_send_time_now();
+                                let frame_index = index;
+                                index = index.wrapping_add(1);
+                                // Silencio: se manda un último frame marcado y después nada
+                                let speech = vad.as_mut().is_none_or(|vad| vad.is_speech(&frame));
+                                if !speech && paused {
+                                    continue;
+                                }
+                                paused = !speech;
+                                dtx_for_encoder.store(paused, Ordering::Relaxed);
+                                if let Ok(encoded) = encoder.encode(&frame) {
+                                    // eprintln!("[AUDIO] Encoded {} bytes", encoded.len());
+                                    // Opus no tiene nada que mandar en este frame
+                                    if params.opus_dtx && is_dtx_frame(&encoded) {
+                                        dtx_for_encoder.store(true, Ordering::Relaxed);
+                                        continue;
+                                    }
+                                    let _ = tx_opus_encoded.try_send(EncodedFrame {
+                                        opus: encoded,
+                                        captured_at,
+                                        index: frame_index,
+                                        last_of_talkspurt: paused,
+                                    });
+                                }
                             }
                         }
+                        Err(_) => break,
                     }
-                    Err(_) => break,
                 }
-            }
-        });
-        handles.push(encoder_handle);
-
-        // RTP sender thread: Opus -> RTP -> Socket
-        let running_rtp = Arc::clone(&running);
-        let socket_for_rtp = Arc::clone(&peer_socket);
-        let srtp_for_sender = srtp_context.clone();
-        let rtp_sender_handle = thread::spawn(move || {
-            let mut sequence: u16 = rand::random();
-            let mut timestamp: u32 = rand::random();
-
-            while running_rtp.load(Ordering::Relaxed) {
-                match rx_opus_encoded.recv() {
-                    Ok(opus_frame) => {
-                        // Build RTP header
-                        let header = RtpHeader::new(
-                            2,              // version
-                            false,          // padding
-                            false,          // extension
-                            0,              // csrc count
-                            true,           // marker (each Opus frame is complete)
-                            RTP_OPUS_TYPE,  // payload type
-                            sequence,
-                            timestamp,
-                            AUDIO_SSRC,
-                            vec![],
-                        );
-
-                        // Encrypt payload if SRTP is available
-                        let payload = if let Some(ref ctx) = srtp_for_sender {
-                            match ctx.protect(sequence, timestamp, &opus_frame) {
-                                Some(encrypted) => encrypted,
-                                None => opus_frame.clone(),
+            });
+            handles.push(encoder_handle);
+
+            // RTP sender thread: Opus -> RTP -> Socket
+            let running_rtp = Arc::clone(&running);
+            let socket_for_rtp = Arc::clone(&peer_socket);
+            let mut srtp_for_sender = srtp_context.cache();
+            let rtp_sender_handle = thread::spawn(move || {
+                let mut sequence: u16 = rand::random();
+                let first_timestamp: u32 = rand::random();
+                let mut last_index: Option<u32> = None;
+                let mut current_ssrc = ssrc;
+                let mut frames_sent: u32 = 0;
+
+                while running_rtp.load(Ordering::Relaxed) {
+                    match rx_opus_encoded.recv() {
+                        Ok(frame) => {
+                            let opus_frame = frame.opus;
+                            let timestamp = first_timestamp
+                                .wrapping_add(frame.index.wrapping_mul(OPUS_FRAME_SIZE as u32));
+                            // Primer frame después de una pausa (o de frames descartados)
+                            let resumed = last_index != Some(frame.index.wrapping_sub(1));
+                            let marker = frame.last_of_talkspurt || resumed;
+                            last_index = Some(frame.index);
+                            // Cambio de SSRC por una colisión
+                            if let Some(new_ssrc) = rx_ssrc.try_iter().last() {
+                                current_ssrc = new_ssrc;
+                            }
+                            // Build RTP header
+                            let mut header = RtpHeader::new(
+                                2,            // version
+                                false,        // padding
+                                false,        // extension
+                                0,            // csrc count
+                                marker,       // marker (talkspurt edges)
+                                payload_type, // payload type
+                                sequence,
+                                timestamp,
+                                current_ssrc,
+                                vec![],
+                            );
+                            // Eco de la sonda del peer (descontando lo que esperó
+                            // acá) o una sonda propia cada tanto
+                            let echo = rx_played.try_iter().last().map(|(probe, played_at)| {
+                                LatencyStamp::Echo(abs_send_time_add(probe, played_at.elapsed()))
+                            });
+                            let stamp = echo.or_else(|| {
+                                frames_sent
+                                    .is_multiple_of(LATENCY_PROBE_INTERVAL)
+                                    .then_some(LatencyStamp::Probe(frame.captured_at))
+                            });
+                            if let Some(extension) = latency_id
+                                .zip(stamp)
+                                .and_then(|(id, stamp)| HeaderExtension::latency(id, stamp))
+                            {
+                                header = header.with_extension(extension);
+                            }
+                            frames_sent = frames_sent.wrapping_add(1);
+
+                            // Encrypt payload if SRTP is available
+                            let payload = if let Some(ctx) = srtp_for_sender.current() {
+                                match ctx.protect(sequence, timestamp, &opus_frame) {
+                                    Some(encrypted) => encrypted,
+                                    None => opus_frame.clone(),
+                                }
+                            } else {
+                                opus_frame
+                            };
+
+                            let mut packet_bytes = header.write_bytes();
+                            packet_bytes.extend(payload);
+
+                            if let Ok(socket) = socket_for_rtp.lock() {
+                                let _ = socket.send(&packet_bytes);
+                                // eprintln!("[AUDIO] Sent RTP packet: seq={}, ts={}, size={}", sequence, timestamp, packet_bytes.len());
                             }
-                        } else {
-                            opus_frame
-                        };
-
-                        let mut packet_bytes = header.write_bytes();
-                        packet_bytes.extend(payload);
 
-                        if let Ok(socket) = socket_for_rtp.lock() {
-                            let _ = socket.send(&packet_bytes);
-                            // eprintln!("[AUDIO] Sent RTP packet: seq={}, ts={}, size={}", sequence, timestamp, packet_bytes.len());
+                            sequence = sequence.wrapping_add(1);
                         }
-
-                        sequence = sequence.wrapping_add(1);
-                        timestamp = timestamp.wrapping_add(OPUS_FRAME_SIZE as u32);
+                        Err(_) => break,
                     }
-                    Err(_) => break,
                 }
-            }
-        });
-        handles.push(rtp_sender_handle);
+            });
+            handles.push(rtp_sender_handle);
+        }
 
         // Decoder thread: RTP -> Opus -> PCM
         let running_dec = Arc::clone(&running);
-        let srtp_for_receiver = srtp_context;
+        let mut srtp_for_receiver = srtp_context.cache();
+        let latency_for_decoder = Arc::clone(&mouth_to_ear_ms);
+        let output_for_decoder = Arc::clone(&output);
+        let level_for_decoder = Arc::clone(&output_level);
         let decoder_handle = thread::spawn(move || {
             let mut decoder = match OpusDecoder::new() {
                 Ok(d) => d,
                 Err(e) => {
-                    eprintln!("Failed to create Opus decoder: {}", e);
+                    log_error!("Failed to create Opus decoder: {}", e);
                     return;
                 }
             };
+            let mut last_seq: Option<u16> = None;
+            let mut comfort = ComfortNoise::new();
 
             while running_dec.load(Ordering::Relaxed) {
-                match rx_incoming.recv() {
+                match rx_incoming.recv_timeout(OPUS_FRAME_DURATION) {
                     Ok(rtp_data) => {
                         if rtp_data.len() < 12 {
                             continue;
                         }
 
-                        // Extract payload from RTP
-                        let (header, header_size) = RtpHeader::read_bytes(&rtp_data);
-                        if header.get_ssrc() != AUDIO_SSRC {
-                            continue; // Not an audio packet
-                        }
+                        // Extract payload from RTP. Packets were already routed
+                        // to audio by SSRC on the shared transport.
+                        let Ok((header, header_size)) = RtpHeader::read_bytes(&rtp_data) else {
+                            continue;
+                        };
 
                         let encrypted_payload = &rtp_data[header_size..];
                         
-                        let opus_data = if let Some(ref ctx) = srtp_for_receiver {
+                        let opus_data = if let Some(ctx) = srtp_for_receiver.current() {
                             match ctx.unprotect(
                                 header.get_sequence_number(),
                                 header.get_timestamp(),
@@ -206,31 +473,146 @@ impl WorkerAudio {
                         } else {
                             encrypted_payload.to_vec()
                         };
+                        // SRTP encrypts the padding too, so it goes after unprotect
+                        let Ok(opus_data) = header.strip_padding(&opus_data) else {
+                            continue;
+                        };
+
+                        let stamp = latency_id
+                            .and_then(|id| header.get_extension(id))
+                            .and_then(LatencyStamp::parse);
+                        let probe = match stamp {
+                            Some(LatencyStamp::Probe(time)) => Some(time),
+                            Some(LatencyStamp::Echo(time)) => {
+                                let secs = abs_send_time_delta(time, abs_send_time_now());
+                                if (0.0..MAX_LATENCY_SECS).contains(&secs) {
+                                    if let Ok(mut latency) = latency_for_decoder.lock() {
+                                        *latency = Some(secs * 1000.0);
+                                    }
+                                }
+                                None
+                            }
+                            None => None,
+                        };
 
-                        if let Ok(pcm) = decoder.decode(&opus_data) {
-                            let _ = tx_pcm_playback.try_send(pcm);
+                        // Fill the gap left by lost packets: FEC from this
+                        // packet for the last one, concealment for the rest.
+                        // A DTX pause leaves no gap in the sequence numbers.
+                        let seq = header.get_sequence_number();
+                        let timestamp = header.get_timestamp();
+                        let lost = lost_before(last_seq, seq);
+                        if lost > 0 && lost <= MAX_CONCEALED_FRAMES {
+                            for missing in 1..=lost {
+                                let concealed = if missing == lost {
+                                    decoder.decode_fec(opus_data)
+                                } else {
+                                    decoder.decode_lost()
+                                };
+                                if let Ok(mut samples) = concealed {
+                                    output_for_decoder.apply(&mut samples);
+                                    let behind = u32::from(lost - missing + 1);
+                                    let _ = tx_pcm_playback.send(AudioFrame {
+                                        timestamp: timestamp
+                                            .wrapping_sub(behind * OPUS_FRAME_SIZE as u32),
+                                        samples,
+                                        probe: None,
+                                        marker: false,
+                                    });
+                                }
+                            }
+                        }
+                        if is_newer(last_seq, seq) {
+                            last_seq = Some(seq);
+                        }
+                        comfort.on_packet(timestamp, header.get_marker(), Instant::now());
+
+                        // Muted output still decodes, so the playout keeps its timing
+                        if let Ok(mut samples) = decoder.decode(opus_data) {
+                            level_for_decoder.update(&samples);
+                            output_for_decoder.apply(&mut samples);
+                            let _ = tx_pcm_playback.send(AudioFrame {
+                                timestamp,
+                                samples,
+                                probe,
+                                marker: header.get_marker(),
+                            });
+                        }
+                    }
+                    // Nada llegó a tiempo: DTX del peer o paquetes perdidos.
+                    // Marcados, para que al agotarse suene silencio
+                    Err(RecvTimeoutError::Timeout) => {
+                        level_for_decoder.update(&[]);
+                        for timestamp in comfort.due(Instant::now()) {
+                            if let Ok(mut samples) = decoder.decode_lost() {
+                                output_for_decoder.apply(&mut samples);
+                                let _ = tx_pcm_playback.send(AudioFrame {
+                                    timestamp,
+                                    samples,
+                                    probe: None,
+                                    marker: true,
+                                });
+                            }
                         }
                     }
-                    Err(_) => break,
+                    Err(RecvTimeoutError::Disconnected) => break,
                 }
             }
         });
         handles.push(decoder_handle);
 
         Ok(Self {
-            capture: Some(capture),
+            capture,
             playback: Some(playback),
             tx_incoming,
+            drops,
             running,
+            ssrc,
+            tx_ssrc: send.then_some(tx_ssrc),
+            mouth_to_ear_ms,
+            dtx_active,
+            output,
+            input_level,
+            output_level,
             handles,
         })
     }
 
+    /// Measured latency and what the playout did to keep it low.
+    pub fn metrics(&self) -> AudioMetricsSnapshot {
+        AudioMetricsSnapshot {
+            mouth_to_ear_ms: self.mouth_to_ear_ms.lock().ok().and_then(|ms| *ms),
+            playout: self
+                .playback
+                .as_ref()
+                .map(|playback| playback.stats())
+                .unwrap_or_default(),
+            dtx_active: self.dtx_active.load(Ordering::Relaxed),
+            input_level: self.input_level.level(),
+            output_level: self.output_level.level(),
+            drops: AudioDrops {
+                capture: self.drops.capture.get(),
+                incoming_rtp: self.drops.incoming_rtp.get(),
+                playback: self.drops.playback.get(),
+            },
+        }
+    }
+
     /// Returns the sender for incoming audio RTP packets.
-    pub fn incoming_sender(&self) -> SyncSender<Vec<u8>> {
+    pub fn incoming_sender(&self) -> MediaSender<Vec<u8>> {
         self.tx_incoming.clone()
     }
 
+    /// Recent level of the microphone, for a level meter.
+    pub fn input_level(&self) -> AudioLevel {
+        self.input_level.level()
+    }
+
+    /// Recent level of the peer's audio, for a level meter. It is measured
+    /// before our volume, so it moves even with the speaker muted.
+    pub fn output_level(&self) -> AudioLevel {
+        self.output_level.level()
+    }
+
     /// Mutes or unmutes the microphone.
     pub fn set_muted(&self, muted: bool) {
         if let Some(ref capture) = self.capture {
@@ -252,9 +634,57 @@ impl WorkerAudio {
         }
     }
 
+    /// Volume of the remote audio, from 0.0 to `MAX_OUTPUT_GAIN` (1.0 plays
+    /// it as received).
+    pub fn set_output_gain(&self, gain: f32) {
+        self.output.set_gain(gain);
+    }
+
+    pub fn output_gain(&self) -> f32 {
+        self.output.gain()
+    }
+
+    /// Silences the remote audio without stopping its reception.
+    pub fn set_output_muted(&self, muted: bool) {
+        self.output.set_muted(muted);
+    }
+
+    pub fn is_output_muted(&self) -> bool {
+        self.output.is_muted()
+    }
+
     /// Returns the SSRC used for audio.
-    pub fn ssrc() -> u32 {
-        AUDIO_SSRC
+    pub fn ssrc(&self) -> u32 {
+        self.ssrc
+    }
+
+    /// Sends from now on with `ssrc`, after an SSRC collision.
+    pub fn set_ssrc(&mut self, ssrc: u32) -> Result<(), WorkerAudioError> {
+        if let Some(tx_ssrc) = self.tx_ssrc.as_ref() {
+            tx_ssrc.send(ssrc).map_err(|_| WorkerAudioError::Stopped)?;
+        }
+        self.ssrc = ssrc;
+        Ok(())
+    }
+}
+
+/// Whether `seq` comes after the last one seen, taking wrap into account.
+fn is_newer(last_seq: Option<u16>, seq: u16) -> bool {
+    match last_seq {
+        Some(last) => {
+            let diff = seq.wrapping_sub(last);
+            diff != 0 && diff < 0x8000
+        }
+        None => true,
+    }
+}
+
+/// Packets missing between the last one seen and `seq`. Late or repeated
+/// packets leave no gap.
+fn lost_before(last_seq: Option<u16>, seq: u16) -> u16 {
+    match last_seq {
+        Some(last) if is_newer(last_seq, seq) => seq.wrapping_sub(last) - 1,
+        _ => 0,
     }
 }
 
@@ -265,3 +695,48 @@ impl Drop for WorkerAudio {
         // Handles will be dropped automatically
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lost_before_counts_gaps() {
+        assert_eq!(lost_before(None, 10), 0);
+        assert_eq!(lost_before(Some(10), 11), 0);
+        assert_eq!(lost_before(Some(10), 13), 2);
+        // Cruza el wrap de 16 bits
+        assert_eq!(lost_before(Some(65_535), 1), 1);
+    }
+
+    #[test]
+    fn test_late_and_repeated_packets_leave_no_gap() {
+        assert_eq!(lost_before(Some(10), 10), 0);
+        assert_eq!(lost_before(Some(10), 8), 0);
+        assert!(!is_newer(Some(10), 8));
+        assert!(is_newer(Some(65_535), 0));
+    }
+
+    #[test]
+    fn test_tight_cap_lowers_the_opus_bitrate() {
+        let auto = AudioParams::default();
+        assert_eq!(auto.clone().with_bandwidth_cap(None).bitrate_bps, None);
+        assert_eq!(auto.with_bandwidth_cap(Some(150)).bitrate_bps, Some(15_000));
+
+        let configured = AudioParams {
+            bitrate_bps: Some(24_000),
+            ..AudioParams::default()
+        };
+        assert_eq!(
+            configured
+                .clone()
+                .with_bandwidth_cap(Some(2_000))
+                .bitrate_bps,
+            Some(24_000)
+        );
+        assert_eq!(
+            configured.with_bandwidth_cap(Some(100)).bitrate_bps,
+            Some(bandwidth_cap::MIN_AUDIO_BPS)
+        );
+    }
+}
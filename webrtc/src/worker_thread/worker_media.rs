@@ -1,54 +1,272 @@
 use crate::camera::camera_opencv::Camera;
+use crate::camera::frame_source::FrameSource;
+use crate::camera::video_format::VideoFormat;
 use opencv::prelude::Mat;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 
-use crate::crypto::srtp::SrtpContext;
+use crate::crypto::srtp::SharedSrtpContext;
 use crate::protocols::rtcp::rtcp_packet::RtcpPacket;
 use crate::rtc::rtc_rtp::rtc_rtp_sender::RtcRtpSender;
 use crate::rtc::socket::peer_socket::PeerSocket;
+use crate::worker_thread::bandwidth_cap;
 use crate::worker_thread::camera_thread::CameraThread;
 use crate::worker_thread::decoder_thread::DecodeThread;
 use crate::worker_thread::encode_thread::EncoderThread;
 use crate::worker_thread::error::worker_error::WorkerError;
-use crate::worker_thread::media_metrics::{CallMetricsSnapshot, MediaMetrics};
+use crate::worker_thread::keepalive_thread::KeepaliveThread;
+use crate::worker_thread::media_channel::{media_channel, MediaReceiver, MediaSender};
+use crate::worker_thread::media_metrics::{CallMetricsSnapshot, MediaMetrics, VideoDrops};
 use crate::worker_thread::rtc_rtp_sender_thread::RtpSenderThread;
 use crate::worker_thread::rtcp_reporter_thread::RtcpReporterThread;
 use crate::worker_thread::rtp_receiver_thread::RtpReceiverThread;
-use std::sync::mpsc::{self, Receiver, SyncSender};
-use std::thread;
+use std::sync::mpsc::{self, Sender};
+use std::thread::{self, JoinHandle};
 
-const VIDEO_SSRC: u32 = 1000;
-#[derive(Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct VideoParams {
     pub width: u32,
     pub height: u32,
     pub fps: u32,
+    /// Upper bound for the encoder bitrate, in kbps.
+    pub max_bitrate_kbps: Option<u64>,
+}
+
+impl VideoParams {
+    /// Bandwidth to announce/encode at: the configured cap, or ~0.1 bits per pixel.
+    pub fn bandwidth_kbps(&self) -> u64 {
+        match self.max_bitrate_kbps {
+            Some(cap) => cap,
+            None => {
+                let pixels_per_sec =
+                    u64::from(self.width) * u64::from(self.height) * u64::from(self.fps);
+                (pixels_per_sec / 10_000).max(64)
+            }
+        }
+    }
+
+    /// Encoder target in bits per second.
+    pub fn bitrate_bps(&self) -> u32 {
+        (self.bandwidth_kbps() * 1000).min(u64::from(u32::MAX)) as u32
+    }
+
+    /// Resolution and fps, without the bitrate.
+    pub fn format(&self) -> VideoFormat {
+        VideoFormat::new(self.width, self.height, self.fps)
+    }
+
+    /// The same parameters captured in `format`.
+    pub fn with_format(self, format: VideoFormat) -> Self {
+        Self {
+            width: format.width,
+            height: format.height,
+            fps: format.fps,
+            ..self
+        }
+    }
+
+    /// The same parameters captured in `format`. A configured bitrate cap is
+    /// kept, lowered to what `format` needs on its own if that is less.
+    pub fn scaled_to(self, format: VideoFormat) -> Self {
+        let scaled = Self {
+            max_bitrate_kbps: None,
+            ..self.with_format(format)
+        };
+        Self {
+            max_bitrate_kbps: self
+                .max_bitrate_kbps
+                .map(|cap| cap.min(scaled.bandwidth_kbps())),
+            ..scaled
+        }
+    }
+
+    /// Clamps the bitrate to a cap announced by the remote peer.
+    pub fn with_remote_cap(mut self, remote_kbps: Option<u64>) -> Self {
+        if let Some(remote) = remote_kbps {
+            self.max_bitrate_kbps = Some(self.bandwidth_kbps().min(remote));
+        }
+        self
+    }
+
+    /// Clamps the bitrate to the video share of a total send cap. Applied
+    /// after every other clamp, so nothing raises the encoder above it.
+    pub fn with_bandwidth_cap(mut self, total_kbps: Option<u64>) -> Self {
+        if let Some(total) = total_kbps {
+            let share = bandwidth_cap::video_encoder_kbps(total);
+            self.max_bitrate_kbps = Some(self.bandwidth_kbps().min(share));
+        }
+        self
+    }
+}
+
+/// What a running `WorkerMedia` does with the camera and the network.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MediaMode {
+    /// Captures, encodes and sends, and decodes what arrives.
+    SendReceive,
+    /// Only decodes what arrives; no camera is opened.
+    ReceiveOnly,
+    /// Only captures for the local preview: nothing is encoded, sent or
+    /// received.
+    PreviewOnly,
 }
 
 pub struct WorkerMedia {
-    rx_preview: Receiver<Mat>,
-    rx_decoded: Receiver<Mat>,
-    tx_incoming: SyncSender<Vec<u8>>,
-    peer_socket: Arc<Mutex<PeerSocket>>,
+    mode: MediaMode,
+    rx_preview: MediaReceiver<Mat>,
+    rx_decoded: MediaReceiver<Mat>,
+    tx_incoming: MediaSender<Vec<u8>>,
+    // None in preview-only mode, where nothing goes out
+    rtcp_socket: Option<Arc<Mutex<PeerSocket>>>,
     ssrc: u32,
+    // None while we only receive
+    tx_ssrc: Option<Sender<u32>>,
+    tx_pacing: Option<Sender<Option<u64>>>,
     metrics: Arc<Mutex<MediaMetrics>>,
+    params: VideoParams,
+    // What the capture device reported when it was opened; empty if it
+    // takes anything or nothing is captured
+    formats: Vec<VideoFormat>,
+    params_txs: Vec<Sender<VideoParams>>,
+    video_enabled: Arc<AtomicBool>,
+    // Set on drop so the camera thread ends even if it is not getting
+    // frames
+    camera_stop: Arc<AtomicBool>,
+    // Dropping it stops the keepalive thread
+    _keepalive_stop: Option<Sender<()>>,
+    // Preview-only: joined on drop so the camera is free right after
+    preview_thread: Option<JoinHandle<()>>,
 }
 
 impl WorkerMedia {
+    /// Starts the video pipeline sending with the `ssrc` declared in our SDP.
+    /// With `abs_send_time_id` every packet carries abs-send-time under that
+    /// `a=extmap` id.
+    #[allow(clippy::too_many_arguments)]
     pub fn start(
         camera_index: i32,
         peer_socket: Arc<Mutex<PeerSocket>>,
+        rtcp_socket: Arc<Mutex<PeerSocket>>,
         params: VideoParams,
-        srtp_context: Option<SrtpContext>,
+        payload_type: u8,
+        ssrc: u32,
+        srtp_context: SharedSrtpContext,
+        abs_send_time_id: Option<u8>,
     ) -> Result<Self, WorkerError> {
-        let (tx_bgr, rx_bgr) = mpsc::sync_channel(1);
-        let (tx_rgb, rx_rgb) = mpsc::sync_channel::<Mat>(3);
-        let (tx_encoded, rx_encoded) = mpsc::sync_channel::<Vec<u8>>(1);
-        let (tx_rtp, rx_rtp) = mpsc::sync_channel::<Vec<u8>>(3);
-        let (tx_incoming, rx_incoming) = mpsc::sync_channel::<Vec<u8>>(8);
-        let (tx_decoded, rx_decoded) = mpsc::sync_channel::<Mat>(1);
-        println!("DEBUG: WorkerMedia initializing camera...");
-        let mut camera = match Camera::with_params(
+        let camera = Self::open_camera(camera_index, params)?;
+        Self::start_with_source(
+            Box::new(camera),
+            peer_socket,
+            rtcp_socket,
+            params,
+            payload_type,
+            ssrc,
+            srtp_context,
+            abs_send_time_id,
+        )
+    }
+
+    /// Same as `start`, but frames come from `source` instead of opening a
+    /// device, e.g. a `FakeCamera` in tests and demos.
+    #[allow(clippy::too_many_arguments)]
+    pub fn start_with_source(
+        source: Box<dyn FrameSource>,
+        peer_socket: Arc<Mutex<PeerSocket>>,
+        rtcp_socket: Arc<Mutex<PeerSocket>>,
+        params: VideoParams,
+        payload_type: u8,
+        ssrc: u32,
+        srtp_context: SharedSrtpContext,
+        abs_send_time_id: Option<u8>,
+    ) -> Result<Self, WorkerError> {
+        Self::start_pipeline(
+            Some(source),
+            peer_socket,
+            rtcp_socket,
+            params,
+            payload_type,
+            ssrc,
+            srtp_context,
+            abs_send_time_id,
+        )
+    }
+
+    /// Only decodes the remote video, for a recvonly section: no camera is
+    /// opened and nothing is encoded or sent. Reports and keepalives still
+    /// go out.
+    #[allow(clippy::too_many_arguments)]
+    pub fn start_receive_only(
+        peer_socket: Arc<Mutex<PeerSocket>>,
+        rtcp_socket: Arc<Mutex<PeerSocket>>,
+        params: VideoParams,
+        payload_type: u8,
+        ssrc: u32,
+        srtp_context: SharedSrtpContext,
+        abs_send_time_id: Option<u8>,
+    ) -> Result<Self, WorkerError> {
+        Self::start_pipeline(
+            None,
+            peer_socket,
+            rtcp_socket,
+            params,
+            payload_type,
+            ssrc,
+            srtp_context,
+            abs_send_time_id,
+        )
+    }
+
+    /// Only captures from camera `camera_index` for the local preview, e.g.
+    /// to check the camera before a call. Dropping the worker releases it.
+    pub fn preview(camera_index: i32, params: VideoParams) -> Result<Self, WorkerError> {
+        let camera = Self::open_camera(camera_index, params)?;
+        Ok(Self::preview_with_source(Box::new(camera), params))
+    }
+
+    /// Same as `preview`, with frames from `source`.
+    pub fn preview_with_source(mut source: Box<dyn FrameSource>, params: VideoParams) -> Self {
+        let formats = source.supported_formats();
+        let (tx_bgr, rx_bgr) = media_channel::<Mat>(1);
+        let (tx_camera_params, rx_camera_params) = mpsc::channel::<VideoParams>();
+        // Nada llega ni se decodifica: el receptor y el emisor se sueltan ya
+        let (tx_incoming, _) = media_channel::<Vec<u8>>(1);
+        let (_, rx_decoded) = media_channel::<Mat>(1);
+
+        let video_enabled = Arc::new(AtomicBool::new(true));
+        let camera_stop = Arc::new(AtomicBool::new(false));
+        let mut camera_thread = CameraThread::preview_only(tx_bgr, rx_camera_params)
+            .with_enabled(Arc::clone(&video_enabled))
+            .with_stop(Arc::clone(&camera_stop));
+        let preview_thread = thread::spawn(move || {
+            if let Err(err) = camera_thread.run(source.as_mut()) {
+                log_debug!("Vista previa terminada: {:?}", err);
+            }
+        });
+
+        Self {
+            mode: MediaMode::PreviewOnly,
+            rx_preview: rx_bgr,
+            rx_decoded,
+            tx_incoming,
+            rtcp_socket: None,
+            ssrc: 0,
+            tx_ssrc: None,
+            tx_pacing: None,
+            metrics: Arc::new(Mutex::new(MediaMetrics::new(0))),
+            params,
+            formats,
+            params_txs: vec![tx_camera_params],
+            video_enabled,
+            camera_stop,
+            _keepalive_stop: None,
+            preview_thread: Some(preview_thread),
+        }
+    }
+
+    fn open_camera(camera_index: i32, params: VideoParams) -> Result<Camera, WorkerError> {
+        log_debug!("WorkerMedia initializing camera...");
+        let camera = match Camera::with_params(
             camera_index,
             params.width as f64,
             params.height as f64,
@@ -56,112 +274,337 @@ impl WorkerMedia {
         ) {
             Ok(cam) => cam,
             Err(err) => {
-                eprintln!(
+                log_warn!(
                     "No se pudo abrir cámara con {}x{}@{}fps: {:?}. Intentando fallback...",
-                    params.width, params.height, params.fps, err
+                    params.width,
+                    params.height,
+                    params.fps,
+                    err
                 );
-                Camera::new(camera_index).map_err(|_| WorkerError::SendError)?
+                Camera::new(camera_index).map_err(WorkerError::CaptureFrameError)?
             }
         };
-        println!("DEBUG: Camera initialized successfully");
+        log_debug!("Camera initialized successfully");
+        Ok(camera)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn start_pipeline(
+        mut source: Option<Box<dyn FrameSource>>,
+        peer_socket: Arc<Mutex<PeerSocket>>,
+        rtcp_socket: Arc<Mutex<PeerSocket>>,
+        params: VideoParams,
+        payload_type: u8,
+        ssrc: u32,
+        srtp_context: SharedSrtpContext,
+        abs_send_time_id: Option<u8>,
+    ) -> Result<Self, WorkerError> {
+        // What the UI or the network hand over drops the oldest item when
+        // the next stage falls behind, instead of stalling the producer
+        let (tx_bgr, rx_bgr) = media_channel::<Mat>(1);
+        let (tx_rgb, rx_rgb) = mpsc::sync_channel::<Mat>(3);
+        let (tx_encoded, rx_encoded) = mpsc::sync_channel::<Vec<u8>>(1);
+        let (tx_rtp, rx_rtp) = mpsc::sync_channel::<Vec<u8>>(3);
+        let (tx_incoming, rx_incoming) = media_channel::<Vec<u8>>(8);
+        let (tx_decoded, rx_decoded) = media_channel::<Mat>(1);
+        // Live reconfiguration, one channel per stage that cares about it
+        let (tx_camera_params, rx_camera_params) = mpsc::channel::<VideoParams>();
+        let (tx_encoder_params, rx_encoder_params) = mpsc::channel::<VideoParams>();
+        let (tx_rtp_params, rx_rtp_params) = mpsc::channel::<VideoParams>();
+        let (tx_ssrc, rx_ssrc) = mpsc::channel::<u32>();
+        let (tx_pacing, rx_pacing) = mpsc::channel::<Option<u64>>();
         let socket_for_rtp = Arc::clone(&peer_socket);
-        let socket_for_rtcp = Arc::clone(&peer_socket);
-        let metrics = Arc::new(Mutex::new(MediaMetrics::new(VIDEO_SSRC)));
+        let socket_for_rtcp = Arc::clone(&rtcp_socket);
+        let mut media_metrics = MediaMetrics::new(ssrc);
+        media_metrics.set_target_bitrate(params.bandwidth_kbps());
+        let metrics = Arc::new(Mutex::new(media_metrics));
         let sender_metrics = Arc::clone(&metrics);
         let receiver_metrics = Arc::clone(&metrics);
         let reporter_metrics = Arc::clone(&metrics);
 
-        // Extract the raw SRTP key bytes
-        let srtp_key_bytes = srtp_context.as_ref().map(|ctx| ctx.get_key().to_vec());
+        let video_enabled = Arc::new(AtomicBool::new(true));
+        let camera_stop = Arc::new(AtomicBool::new(false));
+        // Recvonly: no camera, encoder nor sender
+        let sending = source.is_some();
+        let formats = source
+            .as_mut()
+            .map(|source| source.supported_formats())
+            .unwrap_or_default();
+        if let Some(mut source) = source {
+            // Sender and receiver follow the shared SRTP context across rekeys
+            let mut rtp_sender =
+                RtcRtpSender::new(ssrc, payload_type, sender_metrics, srtp_context.clone());
+            rtp_sender.set_frame_rate(params.fps);
+            rtp_sender.set_abs_send_time_id(abs_send_time_id);
 
-        let rtp_sender = RtcRtpSender::new(VIDEO_SSRC, sender_metrics, srtp_key_bytes);
+            let mut camera_thread = CameraThread::new(tx_bgr, tx_rgb, rx_camera_params)
+                .with_enabled(Arc::clone(&video_enabled))
+                .with_stop(Arc::clone(&camera_stop));
+            thread::spawn(move || {
+                if let Err(err) = camera_thread.run(source.as_mut()) {
+                    log_error!("{:?}", err);
+                }
+            });
 
-        let mut camera_thread = CameraThread::new(tx_bgr, tx_rgb);
-        thread::spawn(move || {
-            if let Err(err) = camera_thread.run(&mut camera) {
-                eprintln!("{:?}", err);
-            }
-        });
+            let mut encode_thread =
+                EncoderThread::new(rx_rgb, tx_encoded, rx_encoder_params, params)
+                    .map_err(|_| WorkerError::SendError)?;
+            thread::spawn(move || {
+                if let Err(err) = encode_thread.run() {
+                    log_error!("{:?}", err);
+                }
+            });
 
-        let mut encode_thread =
-            EncoderThread::new(rx_rgb, tx_encoded).map_err(|_| WorkerError::SendError)?;
-        thread::spawn(move || {
-            if let Err(err) = encode_thread.run() {
-                eprintln!("{:?}", err);
-            }
-        });
+            let mut rtp_thread =
+                RtpSenderThread::new(rx_encoded, rx_rtp_params, rx_ssrc, rx_pacing, rtp_sender);
+            thread::spawn(move || {
+                if let Err(err) = rtp_thread.run(socket_for_rtp) {
+                    log_error!("{:?}", err);
+                }
+            });
+        }
 
-        let mut rtp_thread = RtpSenderThread::new(rx_encoded, rtp_sender);
+        let mut receiver_thread = RtpReceiverThread::new(
+            rx_incoming,
+            tx_rtp,
+            payload_type,
+            receiver_metrics,
+            srtp_context,
+        )
+        .with_abs_send_time_id(abs_send_time_id);
         thread::spawn(move || {
-            if let Err(err) = rtp_thread.run(socket_for_rtp) {
-                eprintln!("{:?}", err);
+            if let Err(err) = receiver_thread.run() {
+                log_error!("{:?}", err);
             }
         });
 
-        let mut receiver_thread =
-            RtpReceiverThread::new(rx_incoming, tx_rtp, receiver_metrics, srtp_context);
         thread::spawn(move || {
-            if let Err(err) = receiver_thread.run() {
-                eprintln!("{:?}", err);
+            let mut reporter = RtcpReporterThread::new(reporter_metrics);
+            if let Err(err) = reporter.run(socket_for_rtcp) {
+                log_error!("{:?}", err);
             }
         });
 
+        // Keeps the NAT binding while no media goes out, on the RTCP socket
+        // too when it is a separate one
+        let (keepalive_stop, rx_keepalive_stop) = mpsc::channel::<()>();
+        let mut keepalive_sockets = vec![Arc::clone(&peer_socket)];
+        if !Arc::ptr_eq(&peer_socket, &rtcp_socket) {
+            keepalive_sockets.push(Arc::clone(&rtcp_socket));
+        }
         thread::spawn(move || {
-            let mut reporter = RtcpReporterThread::new(reporter_metrics);
-            if let Err(err) = reporter.run(socket_for_rtcp) {
-                eprintln!("{:?}", err);
+            let mut keepalive = KeepaliveThread::new(rx_keepalive_stop);
+            if let Err(err) = keepalive.run(keepalive_sockets) {
+                log_error!("{:?}", err);
             }
         });
 
         let mut decode_thread = DecodeThread::new(rx_rtp, tx_decoded);
         thread::spawn(move || {
             if let Err(err) = decode_thread.run() {
-                eprintln!("{:?}", err);
+                log_error!("{:?}", err);
             }
         });
         Ok(Self {
+            mode: if sending {
+                MediaMode::SendReceive
+            } else {
+                MediaMode::ReceiveOnly
+            },
             rx_preview: rx_bgr,
             rx_decoded,
             tx_incoming,
-            peer_socket,
-            ssrc: VIDEO_SSRC,
+            rtcp_socket: Some(rtcp_socket),
+            ssrc,
+            tx_ssrc: sending.then_some(tx_ssrc),
+            tx_pacing: sending.then_some(tx_pacing),
             metrics,
+            params,
+            formats,
+            params_txs: if sending {
+                vec![tx_camera_params, tx_encoder_params, tx_rtp_params]
+            } else {
+                Vec::new()
+            },
+            video_enabled,
+            camera_stop,
+            _keepalive_stop: Some(keepalive_stop),
+            preview_thread: None,
         })
     }
 
-    pub fn get_preview_receiver(&self) -> &Receiver<Mat> {
+    pub fn mode(&self) -> MediaMode {
+        self.mode
+    }
+
+    /// Switches resolution/fps/bitrate without restarting the pipeline.
+    /// SSRC, sequence numbers and RTP timestamps keep running across the change.
+    pub fn reconfigure(&mut self, params: VideoParams) -> Result<(), WorkerError> {
+        for tx in &self.params_txs {
+            tx.send(params).map_err(|_| WorkerError::SendError)?;
+        }
+        if let Ok(mut metrics) = self.metrics.lock() {
+            metrics.set_target_bitrate(params.bandwidth_kbps());
+        }
+        self.params = params;
+        Ok(())
+    }
+
+    /// Resolutions and fps the camera can capture, from the smallest up.
+    /// Empty when the source takes any format (or nothing is captured).
+    pub fn supported_formats(&self) -> &[VideoFormat] {
+        &self.formats
+    }
+
+    /// Like `reconfigure`, but first moves `params` to the nearest format
+    /// the camera supports. Returns what was applied.
+    pub fn set_format(&mut self, params: VideoParams) -> Result<VideoParams, WorkerError> {
+        let format = VideoFormat::nearest(&self.formats, params.format());
+        if format != params.format() {
+            log_debug!(
+                "{}x{}@{} no está soportado, se usa {}x{}@{}",
+                params.width,
+                params.height,
+                params.fps,
+                format.width,
+                format.height,
+                format.fps
+            );
+        }
+        let params = params.with_format(format);
+        self.reconfigure(params)?;
+        Ok(params)
+    }
+
+    /// Paces the video packets at their share of a total send cap of
+    /// `total_kbps` (`None` lifts it). The encoder target is clamped
+    /// separately, with `VideoParams::with_bandwidth_cap`.
+    pub fn set_bandwidth_cap(&self, total_kbps: Option<u64>) -> Result<(), WorkerError> {
+        if let Some(tx_pacing) = self.tx_pacing.as_ref() {
+            tx_pacing
+                .send(total_kbps.map(bandwidth_cap::video_kbps))
+                .map_err(|_| WorkerError::SendError)?;
+        }
+        if let Ok(mut metrics) = self.metrics.lock() {
+            metrics.set_bandwidth_cap(total_kbps);
+        }
+        Ok(())
+    }
+
+    /// Moves the outgoing stream to `ssrc` after a collision. Packets and
+    /// reports follow with the new one; the BYE for the old one is up to
+    /// the caller.
+    pub fn set_ssrc(&mut self, ssrc: u32) -> Result<(), WorkerError> {
+        if let Some(tx_ssrc) = self.tx_ssrc.as_ref() {
+            tx_ssrc.send(ssrc).map_err(|_| WorkerError::SendError)?;
+        }
+        if let Ok(mut metrics) = self.metrics.lock() {
+            metrics.set_ssrc(ssrc);
+        }
+        self.ssrc = ssrc;
+        Ok(())
+    }
+
+    /// Pauses or resumes the camera. While paused no video goes out; the
+    /// keepalives keep the NAT binding open and sequence numbers and RTP
+    /// timestamps pick up where they left off.
+    pub fn set_video_enabled(&self, enabled: bool) {
+        self.video_enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    pub fn is_video_enabled(&self) -> bool {
+        self.video_enabled.load(Ordering::Relaxed)
+    }
+
+    /// Parameters the local pipeline is currently running with.
+    pub fn video_params(&self) -> VideoParams {
+        self.params
+    }
+
+    pub fn get_preview_receiver(&self) -> &MediaReceiver<Mat> {
         &self.rx_preview
     }
 
-    pub fn get_decoded_receiver(&self) -> &Receiver<Mat> {
+    pub fn get_decoded_receiver(&self) -> &MediaReceiver<Mat> {
         &self.rx_decoded
     }
 
-    pub fn incoming_sender(&self) -> SyncSender<Vec<u8>> {
+    pub fn incoming_sender(&self) -> MediaSender<Vec<u8>> {
         self.tx_incoming.clone()
     }
 
+    /// What each channel of the pipeline dropped so far.
+    pub fn drops(&self) -> VideoDrops {
+        VideoDrops {
+            incoming_rtp: self.tx_incoming.dropped(),
+            decoded_frames: self.rx_decoded.dropped(),
+            preview_frames: self.rx_preview.dropped(),
+        }
+    }
+
     pub fn metrics(&self) -> Arc<Mutex<MediaMetrics>> {
         Arc::clone(&self.metrics)
     }
 
     pub fn metrics_snapshot(&self) -> CallMetricsSnapshot {
-        match self.metrics.lock() {
+        let snapshot = match self.metrics.lock() {
             Ok(m) => m.snapshot(),
             Err(err) => {
-                eprintln!("metrics_snapshot: lock poisoned ({})", err);
+                log_warn!("metrics_snapshot: lock poisoned ({})", err);
                 CallMetricsSnapshot::default()
             }
+        };
+        CallMetricsSnapshot {
+            drops: self.drops(),
+            ..snapshot
         }
     }
 
     pub fn send_rtcp_bye(&self) -> Result<(), WorkerError> {
+        // En vista previa no se anunció ningún stream
+        let Some(rtcp_socket) = self.rtcp_socket.as_ref() else {
+            return Ok(());
+        };
         let packet = RtcpPacket::bye(self.ssrc);
         let bytes = packet.write_bytes();
-        let socket = self
-            .peer_socket
-            .lock()
-            .map_err(|_| WorkerError::SendError)?;
+        let socket = rtcp_socket.lock().map_err(|_| WorkerError::SendError)?;
         socket.send(&bytes).map_err(|_| WorkerError::SendError)
     }
 }
+
+impl Drop for WorkerMedia {
+    fn drop(&mut self) {
+        // El hilo lo mira antes de cada frame, pausado o sin frames también
+        self.camera_stop.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.preview_thread.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hd(max_bitrate_kbps: Option<u64>) -> VideoParams {
+        VideoParams {
+            width: 1280,
+            height: 720,
+            fps: 30,
+            max_bitrate_kbps,
+        }
+    }
+
+    #[test]
+    fn test_scaled_to_keeps_the_configured_cap() {
+        let small = VideoFormat::new(640, 360, 30);
+        assert_eq!(hd(Some(300)).scaled_to(small).max_bitrate_kbps, Some(300));
+        // A cap above what the smaller format needs comes down to it
+        assert_eq!(hd(Some(2_000)).scaled_to(small).max_bitrate_kbps, Some(691));
+        assert_eq!(hd(None).scaled_to(small).max_bitrate_kbps, None);
+
+        let scaled = hd(Some(300)).scaled_to(VideoFormat::new(1920, 1080, 30));
+        assert_eq!(scaled.format(), VideoFormat::new(1920, 1080, 30));
+        assert_eq!(scaled.max_bitrate_kbps, Some(300));
+    }
+}
@@ -5,11 +5,11 @@ use std::str::FromStr;
 #[test]
 fn offer_answer_roundtrip_sets_descriptions() {
     let mut offerer =
-        RtcPeerConnection::new(Some("127.0.0.1:0"), PeerConnectionRole::Controlling).unwrap();
+        RtcPeerConnection::new(Some("127.0.0.1:0"), PeerConnectionRole::Controlling, None).unwrap();
     let offer = offerer.create_offer().unwrap();
 
     let mut answerer =
-        RtcPeerConnection::new(Some("127.0.0.1:0"), PeerConnectionRole::Controlled).unwrap();
+        RtcPeerConnection::new(Some("127.0.0.1:0"), PeerConnectionRole::Controlled, None).unwrap();
     let answer = answerer.process_offer(&offer).unwrap();
 
     offerer.set_remote_description(&answer).unwrap();
@@ -0,0 +1,18 @@
+use room_rtc::stun::{StunClient, StunServer};
+use std::net::UdpSocket;
+use std::time::Duration;
+
+#[test]
+fn local_stun_server_echoes_client_source_address() {
+    let server = StunServer::bind("127.0.0.1:0").unwrap();
+    let server_addr = server.local_addr().unwrap();
+    server.spawn();
+
+    let mut client = StunClient::with_server(server_addr.to_string());
+    client.timeout = Duration::from_secs(2);
+
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let mapped = client.query(&socket).unwrap();
+
+    assert_eq!(mapped, Some(socket.local_addr().unwrap()));
+}
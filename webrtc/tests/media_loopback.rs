@@ -0,0 +1,191 @@
+use opencv::prelude::Mat;
+use room_rtc::camera::camera_err::CameraError;
+use room_rtc::camera::fake_camera::FakeCamera;
+use room_rtc::camera::frame_source::FrameSource;
+use room_rtc::camera::video_format::VideoFormat;
+use room_rtc::crypto::srtp::{SharedSrtpContext, SrtpContext};
+use room_rtc::protocols::rtp::header_extension::ABS_SEND_TIME_ID;
+use room_rtc::rtc::socket::peer_socket::PeerSocket;
+use room_rtc::worker_thread::worker_media::{MediaMode, VideoParams, WorkerMedia};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const VIDEO_PT: u8 = 96;
+const FRAMES_EXPECTED: usize = 10;
+const DEADLINE: Duration = Duration::from_secs(15);
+
+/// Two sockets on localhost that send to each other.
+fn connected_sockets() -> (PeerSocket, PeerSocket) {
+    let mut a = PeerSocket::new(Some("127.0.0.1:0")).unwrap();
+    let mut b = PeerSocket::new(Some("127.0.0.1:0")).unwrap();
+    a.add_remote_address(&b.local_addr().to_string()).unwrap();
+    b.add_remote_address(&a.local_addr().to_string()).unwrap();
+    (a, b)
+}
+
+/// Starts a worker on `socket` with a fake camera and forwards everything the
+/// socket receives to it, like P2PClient does after DTLS.
+fn start_worker(mut socket: PeerSocket, ssrc: u32, srtp: SharedSrtpContext) -> WorkerMedia {
+    let params = VideoParams {
+        width: 320,
+        height: 240,
+        fps: 15,
+        max_bitrate_kbps: None,
+    };
+    socket.listener(None).unwrap();
+    let rx = socket.get_receiver().unwrap();
+    let socket = Arc::new(Mutex::new(socket));
+
+    let worker = WorkerMedia::start_with_source(
+        Box::new(FakeCamera::new(params.width, params.height, params.fps)),
+        Arc::clone(&socket),
+        socket,
+        params,
+        VIDEO_PT,
+        ssrc,
+        srtp,
+        Some(ABS_SEND_TIME_ID),
+    )
+    .unwrap();
+
+    let incoming = worker.incoming_sender();
+    thread::spawn(move || {
+        while let Ok((bytes, _)) = rx.recv() {
+            if incoming.send(bytes).is_err() {
+                break;
+            }
+        }
+    });
+    worker
+}
+
+#[test]
+fn fake_camera_frames_survive_the_whole_pipeline() {
+    let srtp = SharedSrtpContext::new(SrtpContext::new(&[7u8; 32]));
+    let (socket_a, socket_b) = connected_sockets();
+    let alice = start_worker(socket_a, 0x1111_1111, srtp.clone());
+    let bob = start_worker(socket_b, 0x2222_2222, srtp);
+
+    let mut decoded = [0usize; 2];
+    let deadline = Instant::now() + DEADLINE;
+    while Instant::now() < deadline {
+        for (i, worker) in [&alice, &bob].into_iter().enumerate() {
+            // Como la UI: se vacía la vista previa aunque ya no frene a la cámara
+            while worker.get_preview_receiver().try_recv().is_ok() {}
+            decoded[i] += worker.get_decoded_receiver().try_iter().count();
+        }
+        let done = decoded.iter().all(|n| *n >= FRAMES_EXPECTED)
+            && alice.metrics_snapshot().bitrate_kbps > 0.0
+            && bob.metrics_snapshot().bitrate_kbps > 0.0;
+        if done {
+            break;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    assert!(
+        decoded.iter().all(|n| *n >= FRAMES_EXPECTED),
+        "decoded frames per side: {:?}",
+        decoded
+    );
+    for worker in [&alice, &bob] {
+        let snapshot = worker.metrics_snapshot();
+        assert!(snapshot.bitrate_kbps > 0.0, "{:?}", snapshot);
+        assert_eq!(snapshot.packet_loss_pct, 0.0, "{:?}", snapshot);
+        assert_eq!(snapshot.cumulative_lost, 0);
+    }
+}
+
+#[test]
+fn preview_only_worker_captures_without_sending() {
+    let params = VideoParams {
+        width: 320,
+        height: 240,
+        fps: 15,
+        max_bitrate_kbps: None,
+    };
+    let worker = WorkerMedia::preview_with_source(
+        Box::new(FakeCamera::new(params.width, params.height, params.fps)),
+        params,
+    );
+    assert_eq!(worker.mode(), MediaMode::PreviewOnly);
+
+    let mut previewed = 0;
+    let deadline = Instant::now() + DEADLINE;
+    while previewed < FRAMES_EXPECTED && Instant::now() < deadline {
+        previewed += worker.get_preview_receiver().try_iter().count();
+        thread::sleep(Duration::from_millis(10));
+    }
+    assert!(
+        previewed >= FRAMES_EXPECTED,
+        "preview frames: {}",
+        previewed
+    );
+
+    // Nada se decodifica ni sale a la red
+    assert!(worker.get_decoded_receiver().try_recv().is_err());
+    assert!(worker.incoming_sender().send(vec![0x80; 12]).is_err());
+    assert_eq!(worker.metrics_snapshot().bitrate_kbps, 0.0);
+    assert!(worker.send_rtcp_bye().is_ok());
+}
+
+/// A fake camera that only takes two formats and records every change the
+/// camera thread applies.
+struct LimitedCamera {
+    inner: FakeCamera,
+    applied: Arc<Mutex<Vec<(f64, f64, f64)>>>,
+}
+
+impl FrameSource for LimitedCamera {
+    fn apply_params(&mut self, width: f64, height: f64, fps: f64) {
+        self.applied.lock().unwrap().push((width, height, fps));
+        self.inner.apply_params(width, height, fps);
+    }
+
+    fn capture_frame(&mut self) -> Result<Mat, CameraError> {
+        self.inner.capture_frame()
+    }
+
+    fn supported_formats(&mut self) -> Vec<VideoFormat> {
+        vec![
+            VideoFormat::new(320, 240, 30),
+            VideoFormat::new(640, 480, 15),
+        ]
+    }
+}
+
+#[test]
+fn set_format_falls_back_to_the_nearest_supported_format() {
+    let params = VideoParams {
+        width: 320,
+        height: 240,
+        fps: 30,
+        max_bitrate_kbps: Some(500),
+    };
+    let applied = Arc::new(Mutex::new(Vec::new()));
+    let camera = LimitedCamera {
+        inner: FakeCamera::new(params.width, params.height, params.fps),
+        applied: Arc::clone(&applied),
+    };
+    let mut worker = WorkerMedia::preview_with_source(Box::new(camera), params);
+    assert_eq!(worker.supported_formats().len(), 2);
+
+    let wanted = VideoParams {
+        width: 1280,
+        height: 720,
+        fps: 30,
+        ..params
+    };
+    let chosen = worker.set_format(wanted).unwrap();
+    assert_eq!(chosen.format(), VideoFormat::new(640, 480, 15));
+    assert_eq!(chosen.max_bitrate_kbps, Some(500));
+    assert_eq!(worker.video_params(), chosen);
+
+    // La cámara recibe el formato elegido, no el pedido
+    let deadline = Instant::now() + DEADLINE;
+    while applied.lock().unwrap().is_empty() && Instant::now() < deadline {
+        thread::sleep(Duration::from_millis(10));
+    }
+    assert_eq!(*applied.lock().unwrap(), vec![(640.0, 480.0, 15.0)]);
+}
@@ -0,0 +1,164 @@
+//! Regression corpus for the SDP negotiation.
+//!
+//! Every `tests/fixtures/sdp/<name>.sdp` is a captured description (our own
+//! builds and browsers) with a `<name>.expected` next to it holding what
+//! `remote_sdp_summary` must extract; `#` lines are comments. Names ending in
+//! `offer` are also answered, and the rest are treated as answers to one of
+//! our offers. Adding a case is dropping both files in the directory; the
+//! `sdp_check` example prints the `.expected` for a new capture.
+
+use room_rtc::rtc::rtc_peer_connection::{PeerConnectionRole, RtcPeerConnection};
+use room_rtc::rtc::sdp_negotiation::{missing_required_attributes, remote_sdp_summary};
+use room_rtc::SessionDescription;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+struct Fixture {
+    path: PathBuf,
+    sdp: String,
+}
+
+impl Fixture {
+    fn name(&self) -> String {
+        self.path.file_name().unwrap().to_string_lossy().into_owned()
+    }
+
+    fn is_offer(&self) -> bool {
+        self.path
+            .file_stem()
+            .is_some_and(|stem| stem.to_string_lossy().ends_with("offer"))
+    }
+
+    fn expected(&self) -> String {
+        let path = self.path.with_extension("expected");
+        let text = fs::read_to_string(&path)
+            .unwrap_or_else(|err| panic!("{}: {}", path.display(), err));
+        text.lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+fn fixtures() -> Vec<Fixture> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/sdp");
+    let mut paths: Vec<PathBuf> = fs::read_dir(&dir)
+        .unwrap_or_else(|err| panic!("{}: {}", dir.display(), err))
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "sdp"))
+        .collect();
+    paths.sort();
+    assert!(!paths.is_empty(), "no fixtures in {}", dir.display());
+    paths
+        .into_iter()
+        .map(|path| Fixture {
+            sdp: fs::read_to_string(&path).unwrap(),
+            path,
+        })
+        .collect()
+}
+
+/// The first line where both summaries differ, for the failure message.
+fn first_difference(expected: &str, actual: &str) -> String {
+    let mut expected_lines = expected.lines();
+    let mut actual_lines = actual.lines();
+    loop {
+        match (expected_lines.next(), actual_lines.next()) {
+            (Some(e), Some(a)) if e == a => continue,
+            (e, a) => {
+                return format!(
+                    "expected {:?}, got {:?}",
+                    e.unwrap_or("(end)"),
+                    a.unwrap_or("(end)")
+                )
+            }
+        }
+    }
+}
+
+/// Lines of a summary with the payload type of each media.
+fn payload_types(summary: &str) -> Vec<&str> {
+    summary
+        .lines()
+        .filter(|line| line.starts_with("audio=") || line.starts_with("video="))
+        .collect()
+}
+
+#[test]
+fn fixtures_parse() {
+    let failures: Vec<String> = fixtures()
+        .iter()
+        .filter_map(|fixture| {
+            SessionDescription::from_str(&fixture.sdp)
+                .err()
+                .map(|err| format!("{}: {}", fixture.name(), err))
+        })
+        .collect();
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
+
+#[test]
+fn fixtures_match_expected_values() {
+    let failures: Vec<String> = fixtures()
+        .iter()
+        .filter_map(|fixture| {
+            let expected = fixture.expected();
+            match remote_sdp_summary(&fixture.sdp) {
+                Ok(actual) if actual == expected => None,
+                Ok(actual) => Some(format!(
+                    "{}: {}",
+                    fixture.name(),
+                    first_difference(&expected, &actual)
+                )),
+                Err(err) => Some(format!("{}: {}", fixture.name(), err)),
+            }
+        })
+        .collect();
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
+
+#[test]
+fn answers_to_offer_fixtures_reparse_with_required_attributes() {
+    for fixture in fixtures().iter().filter(|fixture| fixture.is_offer()) {
+        let name = fixture.name();
+        let mut answerer =
+            RtcPeerConnection::new(Some("127.0.0.1:0"), PeerConnectionRole::Controlled, None)
+                .unwrap();
+        let answer = answerer
+            .process_offer(&fixture.sdp)
+            .unwrap_or_else(|err| panic!("{}: {}", name, err));
+
+        SessionDescription::from_str(&answer)
+            .unwrap_or_else(|err| panic!("{}: answer does not parse: {}", name, err));
+        assert_eq!(
+            missing_required_attributes(&answer),
+            Vec::<&str>::new(),
+            "{}: answer is missing attributes",
+            name
+        );
+        // The answer keeps the payload types picked from the offer
+        let summary = remote_sdp_summary(&answer).unwrap();
+        let expected = fixture.expected();
+        assert_eq!(
+            payload_types(&summary),
+            payload_types(&expected),
+            "{}",
+            name
+        );
+    }
+}
+
+#[test]
+fn answer_fixtures_are_accepted_by_the_offerer() {
+    for fixture in fixtures().iter().filter(|fixture| !fixture.is_offer()) {
+        let mut offerer =
+            RtcPeerConnection::new(Some("127.0.0.1:0"), PeerConnectionRole::Controlling, None)
+                .unwrap();
+        offerer.create_offer().unwrap();
+        offerer
+            .set_remote_description(&fixture.sdp)
+            .unwrap_or_else(|err| panic!("{}: {}", fixture.name(), err));
+    }
+}